@@ -0,0 +1,54 @@
+// socketcan/examples/canopen_nmt.rs
+//
+// Example of a CANopen NMT master.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Resets the CANopen nodes on a bus, starts each one as it boots, and
+//! prints the state changes and the nodes that lose their heartbeat.
+//!
+//!   $ cargo run --example canopen_nmt -- can0 1500
+
+use anyhow::Context;
+use socketcan::canopen::{Monitor, NmtMaster, ALL_NODES};
+use std::{env, sync::mpsc, time::Duration};
+
+fn main() -> anyhow::Result<()> {
+    let iface = env::args().nth(1).unwrap_or_else(|| "vcan0".into());
+    let timeout = env::args()
+        .nth(2)
+        .map(|ms| ms.parse())
+        .transpose()
+        .context("Invalid heartbeat timeout")?
+        .unwrap_or(1500);
+    let timeout = Duration::from_millis(timeout);
+
+    let (tx, booted) = mpsc::channel();
+    let mut monitor = Monitor::new()
+        .on_boot_up(move |node| {
+            let _ = tx.send(node);
+        })
+        .on_state_change(|node, state| println!("Node {}: {}", node, state));
+    for node in 1..=127 {
+        monitor = monitor.heartbeat(node, timeout, |node| {
+            println!("Node {}: heartbeat lost", node)
+        });
+    }
+
+    let mut master = NmtMaster::open(&iface, monitor)
+        .with_context(|| format!("Failed to open socket on interface {}", iface))?;
+    master.reset_node(ALL_NODES)?;
+
+    loop {
+        master.process(Duration::from_millis(100))?;
+        for node in booted.try_iter() {
+            master.start_node(node)?;
+        }
+    }
+}
@@ -0,0 +1,52 @@
+// socketcan/examples/canopen_sdo.rs
+//
+// Example of a CANopen SDO client.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Reads the identity of a CANopen node with SDO uploads.
+//!
+//!   $ cargo run --example canopen_sdo -- can0 5
+
+use anyhow::Context;
+use socketcan::canopen::{Error, SdoClient};
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let iface = args.next().unwrap_or_else(|| "vcan0".into());
+    let node = args
+        .next()
+        .map(|node| node.parse())
+        .transpose()
+        .context("Invalid node ID")?
+        .unwrap_or(1);
+
+    let client = SdoClient::open(&iface, node)
+        .with_context(|| format!("Failed to open socket on interface {}", iface))?;
+
+    let device_type = client.upload(0x1000, 0)?;
+    println!("Device type: {:02X?}", device_type);
+
+    for (sub, name) in [(1, "Vendor ID"), (2, "Product code"), (3, "Revision")] {
+        match client.upload(0x1018, sub) {
+            Ok(data) => println!("{}: {:02X?}", name, data),
+            Err(Error::Aborted(code)) => println!("{}: {}", name, code),
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    for (index, name) in [(0x1008, "Device name"), (0x100A, "Software version")] {
+        match client.upload(index, 0) {
+            Ok(data) => println!("{}: {}", name, String::from_utf8_lossy(&data)),
+            Err(Error::Aborted(code)) => println!("{}: {}", name, code),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,37 @@
+// socketcan/examples/dbc_decode.rs
+//
+// Example of decoding CAN traffic with a DBC database.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Decodes a candump log read from stdin with a DBC database, printing the
+//! messages and the values of their signals.
+//!
+//!   $ candump -L can0 | cargo run --example dbc_decode -- vehicle.dbc
+
+use anyhow::Context;
+use socketcan::{dbc::Database, dump::Reader};
+use std::{env, io};
+
+fn main() -> anyhow::Result<()> {
+    let path = env::args().nth(1).context("Usage: dbc_decode <file.dbc>")?;
+
+    let db = Database::from_file(&path).with_context(|| format!("Failed to read '{}'", path))?;
+
+    let stdin = io::stdin();
+    let mut rdr = Reader::from_reader(stdin.lock());
+
+    for item in db.decoder(rdr.records()) {
+        let item = item.context("Failed to parse the log")?;
+        let (t_us, _) = item.item();
+        println!("({}.{:06}) {}", t_us / 1_000_000, t_us % 1_000_000, item);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+// socketcan/examples/j1939_node.rs
+//
+// Example of a J1939 node that claims an address.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Claims a J1939 address, answers requests for the software
+//! identification, and prints the messages sent to the node.
+//!
+//!   $ cargo run --example j1939_node -- can0
+
+use anyhow::Context;
+use socketcan::j1939::{J1939Socket, Name, Pgn};
+use std::env;
+
+fn main() -> anyhow::Result<()> {
+    let iface = env::args().nth(1).unwrap_or_else(|| "vcan0".into());
+
+    let name = Name::default()
+        .with_identity_number(0x1234)
+        .with_function(0x81)
+        .with_arbitrary_address_capable(true);
+    let mut sock = J1939Socket::open(&iface, name, 0x80)
+        .with_context(|| format!("Failed to open socket on interface {}", iface))?;
+
+    sock.respond_to(Pgn(0xFEDA), |_req| Some(b"\x01socketcan-rs*".to_vec()));
+
+    let addr = sock.claim()?;
+    println!("Claimed address 0x{:02X} for NAME {}", addr, sock.name());
+
+    loop {
+        let msg = sock.recv()?;
+        println!(
+            "PGN {} from 0x{:02X}: {:02X?}",
+            msg.id.pgn, msg.id.source, msg.data
+        );
+    }
+}
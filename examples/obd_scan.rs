@@ -0,0 +1,56 @@
+// socketcan/examples/obd_scan.rs
+//
+// Example of querying the OBD-II ECUs in a vehicle.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Scans the OBD-II ECUs on a bus, printing the VIN, the current value of
+//! each supported PID, and the stored and pending DTCs from each ECU.
+//!
+//!   $ cargo run --example obd_scan -- can0
+
+use anyhow::Context;
+use socketcan::{
+    obd::{pid, Client},
+    CanSocket, Socket,
+};
+use std::env;
+
+fn main() -> anyhow::Result<()> {
+    let iface = env::args().nth(1).unwrap_or_else(|| "vcan0".into());
+
+    let sock = CanSocket::open(&iface)
+        .with_context(|| format!("Failed to open socket on interface {}", iface))?;
+    let mut client = Client::new(sock);
+
+    for resp in client.vin()? {
+        println!("{:?}: VIN {}", resp.ecu, resp.value);
+    }
+
+    for ecu in client.supported_pids()? {
+        println!("{:?}:", ecu.ecu);
+        for pid in ecu
+            .value
+            .into_iter()
+            .filter(|pid| !pid::is_supported_pids(*pid))
+        {
+            let data = client.request_ecu(ecu.ecu, &[0x01, pid])?;
+            let name = pid::name(pid).unwrap_or("Unknown");
+            println!("  {:02X} {}: {}", pid, name, pid::decode(pid, &data[1..]));
+        }
+    }
+
+    for resp in client.stored_dtcs()? {
+        println!("{:?}: stored DTCs {:?}", resp.ecu, resp.value);
+    }
+    for resp in client.pending_dtcs()? {
+        println!("{:?}: pending DTCs {:?}", resp.ecu, resp.value);
+    }
+    Ok(())
+}
@@ -0,0 +1,55 @@
+// socketcan/examples/recorder.rs
+//
+// Example of recording CAN traffic into rotating log files.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Records traffic from the interfaces on the command line into log files
+//! in the current directory, rotated every minute, until ^C is pressed.
+//!
+//!   $ cargo run --example recorder -- vcan0 vcan1
+
+use anyhow::Context;
+use socketcan::dump::Recorder;
+use std::{
+    env,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+static RUNNING: AtomicBool = AtomicBool::new(true);
+
+fn main() -> anyhow::Result<()> {
+    let mut ifaces: Vec<_> = env::args().skip(1).collect();
+    if ifaces.is_empty() {
+        ifaces.push("vcan0".into());
+    }
+
+    ctrlc::set_handler(|| {
+        RUNNING.store(false, Ordering::Relaxed);
+    })
+    .expect("Failed to set ^C handler");
+
+    let mut recorder = Recorder::new("candump-{date}_{time}.log")
+        .wall_clock(Duration::from_secs(60))
+        .on_segment_closed(|seg| {
+            println!(
+                "{}: {} frames, {} bytes",
+                seg.path.display(),
+                seg.records,
+                seg.size
+            )
+        });
+
+    recorder
+        .capture(&ifaces, &RUNNING)
+        .with_context(|| format!("Failed to record from {:?}", ifaces))?;
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+// socketcan/examples/slcan_attach.rs
+//
+// Example of attaching an slcan adapter to the kernel line discipline.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Attaches an slcan adapter as a CAN network interface, like `slcand`,
+//! and holds it until ^C.
+//!
+//!   $ sudo cargo run --example slcan_attach -- /dev/ttyACM0 500000 can7
+
+use anyhow::{anyhow, Context};
+use socketcan::slcan::{AttachOptions, Bitrate, SlcanInterface};
+use std::{
+    convert::TryFrom,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let path = args.next().unwrap_or_else(|| "/dev/ttyACM0".into());
+    let bitrate = args
+        .next()
+        .map(|rate| rate.parse::<u32>())
+        .transpose()
+        .context("Invalid bitrate")?
+        .unwrap_or(500_000);
+    let bitrate =
+        Bitrate::try_from(bitrate).map_err(|rate| anyhow!("Unsupported bitrate: {}", rate))?;
+
+    let mut opts = AttachOptions::new().bitrate(bitrate);
+    if let Some(name) = args.next() {
+        opts = opts.name(&name);
+    }
+
+    let slcan = SlcanInterface::attach(&path, opts)
+        .with_context(|| format!("Failed to attach {}", path))?;
+    println!("Attached {} as {}", path, slcan.name());
+
+    static QUIT: AtomicBool = AtomicBool::new(false);
+
+    ctrlc::set_handler(|| {
+        QUIT.store(true, Ordering::Relaxed);
+    })
+    .expect("Failed to set ^C handler");
+
+    while !QUIT.load(Ordering::Relaxed) {
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    println!("Detaching {}", slcan.name());
+    slcan.detach()?;
+    Ok(())
+}
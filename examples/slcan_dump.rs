@@ -0,0 +1,50 @@
+// socketcan/examples/slcan_dump.rs
+//
+// Example of reading frames from an slcan adapter.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Opens an slcan adapter at a bitrate, and prints the frames from the
+//! bus with the adapter's timestamps, like candump.
+//!
+//!   $ cargo run --example slcan_dump -- /dev/ttyACM0 500000
+
+use anyhow::{anyhow, Context};
+use socketcan::slcan::{Bitrate, SlcanPort};
+use std::convert::TryFrom;
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let path = args.next().unwrap_or_else(|| "/dev/ttyACM0".into());
+    let bitrate = args
+        .next()
+        .map(|rate| rate.parse::<u32>())
+        .transpose()
+        .context("Invalid bitrate")?
+        .unwrap_or(500_000);
+    let bitrate =
+        Bitrate::try_from(bitrate).map_err(|rate| anyhow!("Unsupported bitrate: {}", rate))?;
+
+    let port = SlcanPort::open(&path).with_context(|| format!("Failed to open {}", path))?;
+
+    // The channel may have been left open
+    let _ = port.close_channel();
+    port.set_bitrate(bitrate)?;
+    if port.set_timestamps(true).is_err() {
+        println!("The adapter doesn't support timestamps");
+    }
+    port.open_channel()?;
+
+    loop {
+        match port.read_timestamped(None)? {
+            (frame, Some(ts)) => println!("({:>6.3}) {}", ts.as_secs_f64(), frame),
+            (frame, None) => println!("{}", frame),
+        }
+    }
+}
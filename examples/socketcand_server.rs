@@ -0,0 +1,42 @@
+// socketcan/examples/socketcand_server.rs
+//
+// Example of serving local CAN interfaces with the socketcand protocol.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Serves the named CAN interfaces to socketcand clients, like Kayak or
+//! python-can, and announces the server on the local network.
+//!
+//!   $ cargo run --example socketcand_server -- can0 can1
+
+use anyhow::Context;
+use socketcan::socketcand::{Server, DEFAULT_PORT};
+use std::thread;
+
+fn main() -> anyhow::Result<()> {
+    let buses: Vec<String> = std::env::args().skip(1).collect();
+    let buses: Vec<&str> = match buses.is_empty() {
+        true => vec!["vcan0"],
+        false => buses.iter().map(String::as_str).collect(),
+    };
+
+    let server = Server::bind(("0.0.0.0", DEFAULT_PORT), &buses)
+        .with_context(|| format!("Failed to listen on port {}", DEFAULT_PORT))?;
+
+    let beacon = server.beacon()?;
+    println!("Serving {:?} at {}", server.buses(), beacon.addr());
+    thread::spawn(move || {
+        if let Err(err) = beacon.run() {
+            eprintln!("Failed to send the discovery beacon: {}", err);
+        }
+    });
+
+    server.serve()?;
+    Ok(())
+}
@@ -23,12 +23,15 @@
 
 use futures_util::StreamExt;
 use socketcan::{
-    tokio::CanSocket, CanFilter, CanFrame, EmbeddedFrame, Error, Frame, Result, SocketOptions,
-    StandardId,
+    tokio::CanSocket, ByteOrder, CanFilter, CanFrame, Error, Frame, Result, SignalSpec,
+    SocketOptions, StandardId, ValueType,
 };
 use std::collections::VecDeque;
 use tokio::sync::mpsc;
 
+/// The 32-bit value in the first four bytes of the frames
+const VALUE: SignalSpec = SignalSpec::new(0, 32, ByteOrder::LittleEndian, ValueType::Signed);
+
 struct MovingAverage {
     sum: i32,
     data: VecDeque<i32>,
@@ -63,11 +66,15 @@ async fn main() -> Result<()> {
         let mut data = MovingAverage::new(5);
 
         while let Some(mut frame) = rx.recv().await {
-            let n = i32::from_le_bytes(frame.data()[0..4].try_into().unwrap());
+            let n = match frame.get_signal(&VALUE) {
+                Ok(n) => n as i32,
+                Err(_) => continue,
+            };
             let avg = data.avg(n);
 
             frame.set_id(StandardId::new(0x101).unwrap());
-            frame.set_data(&avg.to_le_bytes()).unwrap();
+            frame.set_data(&[0; 4]).unwrap();
+            frame.set_signal(&VALUE, avg as f64).unwrap();
 
             sock_tx.write_frame(frame)?.await?;
         }
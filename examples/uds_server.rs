@@ -0,0 +1,52 @@
+// socketcan/examples/uds_server.rs
+//
+// Example of simulating an ECU with the UDS server.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Simulates an ECU answering UDS requests on physical addresses
+//! 0x7E0/0x7E8, with a VIN, a writable calibration value behind security
+//! access, a self-test routine, a flash area, and a couple of stored DTCs.
+//!
+//!   $ cargo run --example uds_server -- vcan0
+
+use anyhow::Context;
+use socketcan::{
+    isotp::{IsoTpSocket, Options},
+    uds::{Access, Did, Dtc, Nrc, RoutineControl, Server, Session},
+    CanSocket, StandardId,
+};
+use std::env;
+
+fn main() -> anyhow::Result<()> {
+    let iface = env::args().nth(1).unwrap_or_else(|| "vcan0".into());
+
+    let id = |id| StandardId::new(id).unwrap();
+    let opts = Options::new(id(0x7E8), id(0x7E0)).padding(Some(0xAA));
+    let mut sock = IsoTpSocket::<CanSocket>::open(&iface, opts)
+        .with_context(|| format!("Failed to open socket on interface {}", iface))?;
+
+    let mut server = Server::new()
+        .did(0xF190, Did::new(*b"WVWZZZ1JZXW000001"))
+        .did(
+            0x0101,
+            Did::new([0x00, 0x64]).writable(Access::session(Session::Extended).security(0x01)),
+        )
+        .routine(0x0200, Access::any(), |ctrl, _params| match ctrl {
+            RoutineControl::Start => Ok(vec![0x00]),
+            _ => Err(Nrc::RequestSequenceError),
+        })
+        .security_level(0x01, |seed| seed.iter().map(|b| b ^ 0x5A).collect())
+        .memory(0x0800_0000, 0x1_0000)
+        .dtcs(vec![Dtc::new(0x012300, 0x09), Dtc::new(0xC10000, 0x28)]);
+
+    println!("Simulating an ECU on {}", iface);
+    server.serve(&mut sock)?;
+    Ok(())
+}
@@ -0,0 +1,71 @@
+// socketcan/examples/xcp_daq.rs
+//
+// Example of XCP measurement with a DAQ list.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+//
+
+//! Connects to an XCP slave and samples two addresses on an event
+//! channel, printing the timestamped values.
+//!
+//!   $ cargo run --example xcp_daq -- can0 0x2000_0100 0x2000_0104
+
+use anyhow::Context;
+use socketcan::{
+    xcp::{DaqList, Options, XcpMaster},
+    CanSocket, StandardId,
+};
+use std::time::Duration;
+
+fn parse_addr(s: &str) -> anyhow::Result<u32> {
+    let s = s.trim_start_matches("0x").replace('_', "");
+    u32::from_str_radix(&s, 16).with_context(|| format!("Invalid address: {}", s))
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let iface = args.next().unwrap_or_else(|| "vcan0".into());
+    let addrs = args
+        .map(|arg| parse_addr(&arg))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let opts = Options::new(
+        StandardId::new(0x7F0).unwrap(),
+        StandardId::new(0x7F1).unwrap(),
+    )
+    .padding(Some(0x00));
+    let mut master = XcpMaster::<CanSocket>::open(&iface, opts)
+        .with_context(|| format!("Failed to open socket on interface {}", iface))?;
+
+    let info = master.connect().context("Failed to connect to the slave")?;
+    println!(
+        "Connected: {:?}, max CTO {}, max DTO {}",
+        info.byte_order, info.max_cto, info.max_dto
+    );
+
+    let mut list = DaqList::new(0).timestamp(true);
+    for (i, addr) in addrs.iter().enumerate() {
+        list = list.measure(&format!("m{}", i), *addr, 0, 4);
+    }
+    master.configure_daq(&[list])?;
+    let resolution = *master.resolution().context("No DAQ resolution")?;
+
+    master.start_daq()?;
+    for _ in 0..100 {
+        match master.recv_daq(Duration::from_secs(1))? {
+            Some(pkt) => {
+                let ts = pkt.timestamp.map(|ts| resolution.duration(ts));
+                println!("{:?} {:02X?}", ts, pkt.values);
+            }
+            None => println!("No data"),
+        }
+    }
+    master.stop_daq()?;
+    master.disconnect()?;
+    Ok(())
+}
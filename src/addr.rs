@@ -14,7 +14,11 @@
 use libc::{sa_family_t, sockaddr, sockaddr_can, sockaddr_storage, socklen_t};
 use nix::net::if_::if_nametoindex;
 use socket2::SockAddr;
-use std::{fmt, io, mem, os::raw::c_int};
+use std::{
+    fmt, io,
+    mem::{self, size_of},
+    os::raw::c_int,
+};
 
 pub use libc::{AF_CAN, CAN_RAW, PF_CAN};
 
@@ -59,7 +63,7 @@ impl CanAddr {
 
     /// Gets the size of the address structure.
     pub fn len() -> usize {
-        mem::size_of::<sockaddr_can>()
+        size_of::<sockaddr_can>()
     }
 
     /// Gets the underlying address as a byte slice
@@ -137,14 +141,14 @@ mod tests {
     fn test_addr() {
         let _addr = CanAddr::new(IDX);
 
-        assert_eq!(mem::size_of::<sockaddr_can>(), CanAddr::len());
+        assert_eq!(size_of::<sockaddr_can>(), CanAddr::len());
     }
 
     #[test]
     fn test_addr_to_sock_addr() {
         let addr = CanAddr::new(IDX);
 
-        let (sock_addr, len) = addr.clone().into_storage();
+        let (sock_addr, len) = addr.into_storage();
 
         assert_eq!(CanAddr::len() as socklen_t, len);
         assert_eq!(as_bytes(&addr), &as_bytes(&sock_addr)[0..len as usize]);
@@ -0,0 +1,679 @@
+// socketcan/src/canopen/eds.rs
+//
+// CANopen EDS and DCF files.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! Parsing of CANopen electronic data sheets (EDS) and device
+//! configuration files (DCF), as in CiA 306.
+//!
+//! An EDS describes the object dictionary of a device: the name, data
+//! type, access, and default value of each entry. A DCF is an EDS for a
+//! configured device, with its node ID and the configured value of the
+//! entries.
+//!
+//! ```
+//! use socketcan::canopen::{eds::Eds, od::{DataType, Value}};
+//!
+//! let eds = Eds::parse("
+//! [1017]
+//! ParameterName=Producer heartbeat time
+//! ObjectType=0x7
+//! DataType=0x0006
+//! AccessType=rw
+//! DefaultValue=1000
+//! ").unwrap();
+//!
+//! let var = eds.variable_by_name("Producer heartbeat time").unwrap();
+//! assert_eq!(var.data_type, DataType::Unsigned16);
+//! assert_eq!(var.value(None), Some(Value::Uint(1000)));
+//! ```
+
+use super::od::{parse_int, Access, DataType, Dictionary, Value};
+use std::{collections::BTreeMap, error, fmt, fs, io, path::Path, str::FromStr};
+
+// ===== ObjectType =====
+
+/// The type of an object in the dictionary.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ObjectType {
+    /// An entry with no data
+    Null,
+    /// A large amount of data
+    Domain,
+    /// A data type definition
+    DefType,
+    /// A structure type definition
+    DefStruct,
+    /// A single value
+    #[default]
+    Var,
+    /// Sub-entries of the same type
+    Array,
+    /// Sub-entries of different types
+    Record,
+    /// Another object code
+    Other(u8),
+}
+
+impl From<u8> for ObjectType {
+    fn from(code: u8) -> Self {
+        use ObjectType::*;
+        match code {
+            0x00 => Null,
+            0x02 => Domain,
+            0x05 => DefType,
+            0x06 => DefStruct,
+            0x07 => Var,
+            0x08 => Array,
+            0x09 => Record,
+            code => Other(code),
+        }
+    }
+}
+
+impl From<ObjectType> for u8 {
+    fn from(object_type: ObjectType) -> Self {
+        use ObjectType::*;
+        match object_type {
+            Null => 0x00,
+            Domain => 0x02,
+            DefType => 0x05,
+            DefStruct => 0x06,
+            Var => 0x07,
+            Array => 0x08,
+            Record => 0x09,
+            Other(code) => code,
+        }
+    }
+}
+
+// ===== Variable =====
+
+/// A single entry in the object dictionary, with its index and sub-index.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Variable {
+    /// The parameter name
+    pub name: String,
+    /// The index
+    pub index: u16,
+    /// The sub-index
+    pub sub: u8,
+    /// The data type
+    pub data_type: DataType,
+    /// The access over SDO
+    pub access: Access,
+    /// Whether the entry can be mapped into a PDO
+    pub pdo_mapping: bool,
+    /// The default value, as written in the file
+    pub default_value: Option<String>,
+    /// The configured value in a DCF, as written in the file
+    pub parameter_value: Option<String>,
+    /// The lowest allowed value, as written in the file
+    pub low_limit: Option<String>,
+    /// The highest allowed value, as written in the file
+    pub high_limit: Option<String>,
+}
+
+impl Variable {
+    /// Gets the value of the entry: the configured value in a DCF, or the
+    /// default value.
+    ///
+    /// The node ID is used for values like `$NODEID+0x180`.
+    pub fn value(&self, node_id: Option<u8>) -> Option<Value> {
+        let s = self
+            .parameter_value
+            .as_deref()
+            .or(self.default_value.as_deref())?;
+        self.data_type.parse(s, node_id)
+    }
+}
+
+// ===== Object =====
+
+/// An object in the dictionary, with one or more entries.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Object {
+    /// The parameter name
+    pub name: String,
+    /// The index
+    pub index: u16,
+    /// The object type
+    pub object_type: ObjectType,
+    /// The entries, by sub-index. A [`Var`](ObjectType::Var) object has
+    /// a single one with sub-index zero and the name of the object.
+    pub subs: BTreeMap<u8, Variable>,
+}
+
+// ===== Eds =====
+
+/// An electronic data sheet or device configuration file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Eds {
+    /// The entries of the `[FileInfo]` section
+    pub file_info: BTreeMap<String, String>,
+    /// The entries of the `[DeviceInfo]` section
+    pub device_info: BTreeMap<String, String>,
+    /// The node ID of a configured device, from a DCF
+    pub node_id: Option<u8>,
+    /// The objects in the dictionary, by index
+    pub objects: BTreeMap<u16, Object>,
+}
+
+impl Eds {
+    /// Parses the contents of an EDS or DCF file.
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        let sections = sections(s)?;
+        let mut eds = Eds::default();
+        let mut subs = Vec::new();
+
+        for section in &sections {
+            let name = section.name.to_ascii_lowercase();
+            match name.as_str() {
+                "fileinfo" => eds.file_info = section.values(),
+                "deviceinfo" => eds.device_info = section.values(),
+                "devicecomissioning" | "devicecommissioning" => {
+                    if let Some(id) = section.get("NodeID") {
+                        let id = parse_int(id).and_then(|id| u8::try_from(id).ok());
+                        eds.node_id = Some(id.ok_or_else(|| section.error("invalid NodeID"))?);
+                    }
+                }
+                _ => {
+                    if let Some(index) = parse_index(&name) {
+                        eds.objects.insert(index, section.object(index)?);
+                    } else if let Some((index, sub)) = parse_sub_index(&name) {
+                        subs.push((index, sub, section));
+                    }
+                }
+            }
+        }
+
+        for (index, sub, section) in subs {
+            let obj = eds
+                .objects
+                .get_mut(&index)
+                .ok_or_else(|| section.error("sub-index without an object"))?;
+            let var = section.variable(index, sub)?;
+            obj.subs.insert(sub, var);
+        }
+        Ok(eds)
+    }
+
+    /// Reads and parses an EDS or DCF file.
+    ///
+    /// Files that aren't valid UTF-8 are read as Latin-1.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+        let buf = fs::read(path)?;
+        match String::from_utf8(buf) {
+            Ok(s) => Self::parse(&s),
+            Err(err) => {
+                let s: String = err.as_bytes().iter().map(|&b| b as char).collect();
+                Self::parse(&s)
+            }
+        }
+    }
+
+    /// Gets an object.
+    pub fn object(&self, index: u16) -> Option<&Object> {
+        self.objects.get(&index)
+    }
+
+    /// Gets an entry.
+    pub fn variable(&self, index: u16, sub: u8) -> Option<&Variable> {
+        self.object(index)?.subs.get(&sub)
+    }
+
+    /// Gets an entry by name.
+    ///
+    /// The name of a single value object is the name of the object. The
+    /// name of an entry in an array or record is the name of the object and
+    /// the name of the entry, separated by a dot, like
+    /// `Identity object.Vendor-ID`.
+    pub fn variable_by_name(&self, name: &str) -> Option<&Variable> {
+        self.objects.values().find_map(|obj| {
+            obj.subs
+                .values()
+                .find(|var| Self::full_name(obj, var) == name)
+        })
+    }
+
+    /// Gets the name of an entry, as used by
+    /// [`variable_by_name()`](Self::variable_by_name).
+    pub fn name(&self, index: u16, sub: u8) -> Option<String> {
+        let obj = self.object(index)?;
+        Some(Self::full_name(obj, obj.subs.get(&sub)?))
+    }
+
+    fn full_name(obj: &Object, var: &Variable) -> String {
+        match obj.object_type {
+            ObjectType::Var | ObjectType::Domain => obj.name.clone(),
+            _ => format!("{}.{}", obj.name, var.name),
+        }
+    }
+
+    /// Creates an in-memory object dictionary with the values of the
+    /// entries.
+    ///
+    /// Entries without a value are empty.
+    pub fn dictionary(&self) -> Dictionary {
+        let mut od = Dictionary::new();
+        for obj in self.objects.values() {
+            for var in obj.subs.values() {
+                let value = var
+                    .value(self.node_id)
+                    .and_then(|value| var.data_type.encode(&value))
+                    .unwrap_or_default();
+                od = od.entry(var.index, var.sub, var.access, value);
+            }
+        }
+        od
+    }
+}
+
+impl FromStr for Eds {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+// ===== Parser =====
+
+/// A section of the file, with the keys in lowercase
+struct Section<'a> {
+    name: &'a str,
+    line: usize,
+    entries: Vec<(String, &'a str, &'a str)>,
+}
+
+impl<'a> Section<'a> {
+    fn error(&self, msg: &str) -> ParseError {
+        ParseError::Syntax {
+            line: self.line,
+            msg: format!("[{}]: {}", self.name, msg),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&'a str> {
+        let key = key.to_ascii_lowercase();
+        self.entries
+            .iter()
+            .find(|(k, _, _)| *k == key)
+            .map(|&(_, _, value)| value)
+    }
+
+    fn get_int(&self, key: &str) -> Result<Option<i64>, ParseError> {
+        self.get(key)
+            .filter(|s| !s.is_empty())
+            .map(|s| parse_int(s).ok_or_else(|| self.error(&format!("invalid {}", key))))
+            .transpose()
+    }
+
+    fn values(&self) -> BTreeMap<String, String> {
+        self.entries
+            .iter()
+            .map(|&(_, key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn object(&self, index: u16) -> Result<Object, ParseError> {
+        let name = self
+            .get("ParameterName")
+            .ok_or_else(|| self.error("missing ParameterName"))?
+            .to_string();
+        let object_type = self.get_int("ObjectType")?.unwrap_or(7) as u8;
+        let object_type = ObjectType::from(object_type);
+
+        let mut subs = BTreeMap::new();
+        match object_type {
+            ObjectType::Var | ObjectType::Domain => {
+                subs.insert(0, self.variable(index, 0)?);
+            }
+            _ => {
+                // An array can list its entries compactly, all of one type
+                let compact = self.get_int("CompactSubObj")?.unwrap_or(0);
+                if compact > 0 {
+                    let count = compact.min(254) as u8;
+                    let var = Variable {
+                        name: "NrOfObjects".into(),
+                        index,
+                        data_type: DataType::Unsigned8,
+                        access: Access::ReadOnly,
+                        default_value: Some(count.to_string()),
+                        ..Variable::default()
+                    };
+                    subs.insert(0, var);
+                    for sub in 1..=count {
+                        let mut var = self.variable(index, sub)?;
+                        var.name = format!("{}{}", name, sub);
+                        subs.insert(sub, var);
+                    }
+                }
+            }
+        }
+        Ok(Object {
+            name,
+            index,
+            object_type,
+            subs,
+        })
+    }
+
+    fn variable(&self, index: u16, sub: u8) -> Result<Variable, ParseError> {
+        let data_type = self.get_int("DataType")?.unwrap_or(0x07) as u16;
+        let access = match self.get("AccessType").map(str::to_ascii_lowercase) {
+            None => Access::ReadWrite,
+            Some(s) => match s.as_str() {
+                "ro" => Access::ReadOnly,
+                "wo" => Access::WriteOnly,
+                "rw" | "rwr" | "rww" => Access::ReadWrite,
+                "const" => Access::Const,
+                _ => return Err(self.error("invalid AccessType")),
+            },
+        };
+        let string = |key| self.get(key).filter(|s| !s.is_empty()).map(String::from);
+
+        Ok(Variable {
+            name: self.get("ParameterName").unwrap_or_default().to_string(),
+            index,
+            sub,
+            data_type: data_type.into(),
+            access,
+            pdo_mapping: self.get_int("PDOMapping")?.unwrap_or(0) != 0,
+            default_value: string("DefaultValue"),
+            parameter_value: string("ParameterValue"),
+            low_limit: string("LowLimit"),
+            high_limit: string("HighLimit"),
+        })
+    }
+}
+
+/// Splits the file into its sections.
+fn sections(s: &str) -> Result<Vec<Section<'_>>, ParseError> {
+    let mut sections: Vec<Section> = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+            continue;
+        }
+        let syntax = |msg: &str| ParseError::Syntax {
+            line: i + 1,
+            msg: msg.into(),
+        };
+        if let Some(name) = line.strip_prefix('[') {
+            let name = name
+                .strip_suffix(']')
+                .ok_or_else(|| syntax("unterminated section name"))?;
+            sections.push(Section {
+                name: name.trim(),
+                line: i + 1,
+                entries: Vec::new(),
+            });
+        } else {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| syntax("expected a key and value"))?;
+            let section = sections
+                .last_mut()
+                .ok_or_else(|| syntax("value outside of a section"))?;
+            let key = key.trim();
+            section
+                .entries
+                .push((key.to_ascii_lowercase(), key, value.trim()));
+        }
+    }
+    Ok(sections)
+}
+
+/// Parses an object section name, like `1018`.
+fn parse_index(name: &str) -> Option<u16> {
+    match name.len() {
+        4 => u16::from_str_radix(name, 16).ok(),
+        _ => None,
+    }
+}
+
+/// Parses a sub-index section name, like `1018sub2`.
+fn parse_sub_index(name: &str) -> Option<(u16, u8)> {
+    let (index, sub) = name.split_once("sub")?;
+    Some((parse_index(index)?, u8::from_str_radix(sub, 16).ok()?))
+}
+
+// ===== ParseError =====
+
+/// An error reading an EDS or DCF file.
+#[derive(Debug)]
+pub enum ParseError {
+    /// An I/O error reading the file
+    Io(io::Error),
+    /// A syntax error at the specified line
+    Syntax {
+        /// The line number, starting at 1
+        line: usize,
+        /// A description of the error
+        msg: String,
+    },
+}
+
+impl error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ParseError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParseError::*;
+        match self {
+            Io(err) => write!(f, "I/O error: {}", err),
+            Syntax { line, msg } => write!(f, "EDS syntax error at line {}: {}", line, msg),
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(err: io::Error) -> Self {
+        ParseError::Io(err)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::canopen::od::ObjectDictionary;
+
+    /// A DCF for a simple I/O node
+    pub(crate) const DCF: &str = "\
+[FileInfo]
+FileName=io.dcf
+FileVersion=1
+
+[DeviceInfo]
+VendorName=ACME
+ProductName=IO node
+
+[DeviceComissioning]
+NodeID=0x05
+
+[MandatoryObjects]
+SupportedObjects=2
+1=0x1000
+2=0x1018
+
+[1000]
+ParameterName=Device type
+ObjectType=0x7
+DataType=0x0007
+AccessType=ro
+DefaultValue=0x00000191
+
+[1018]
+ParameterName=Identity object
+ObjectType=0x9
+SubNumber=2
+
+[1018sub0]
+ParameterName=Highest sub-index supported
+DataType=0x0005
+AccessType=const
+DefaultValue=1
+
+[1018sub1]
+ParameterName=Vendor-ID
+DataType=0x0007
+AccessType=ro
+DefaultValue=0x12345678
+
+[1800]
+ParameterName=TPDO1 communication parameter
+ObjectType=0x9
+SubNumber=3
+
+[1800sub1]
+ParameterName=COB-ID
+DataType=0x0007
+AccessType=rw
+DefaultValue=$NODEID+0x180
+
+[1800sub2]
+ParameterName=Transmission type
+DataType=0x0005
+AccessType=rw
+DefaultValue=254
+ParameterValue=1
+
+[1800sub5]
+ParameterName=Event timer
+DataType=0x0006
+AccessType=rw
+DefaultValue=100
+
+[1A00]
+ParameterName=TPDO1 mapping parameter
+ObjectType=0x9
+SubNumber=4
+
+[1A00sub0]
+ParameterName=Number of mapped objects
+DataType=0x0005
+AccessType=rw
+DefaultValue=3
+
+[1A00sub1]
+ParameterName=Mapped object 1
+DataType=0x0007
+AccessType=rw
+DefaultValue=0x60000108
+
+[1A00sub2]
+ParameterName=Mapped object 2
+DataType=0x0007
+AccessType=rw
+DefaultValue=0x60000201
+
+[1A00sub3]
+ParameterName=Mapped object 3
+DataType=0x0007
+AccessType=rw
+DefaultValue=0x64010110
+
+[6000]
+ParameterName=Read input 8-bit
+ObjectType=0x8
+DataType=0x0005
+AccessType=ro
+PDOMapping=1
+CompactSubObj=2
+
+[6401]
+ParameterName=Read analog input 16-bit
+ObjectType=0x8
+SubNumber=2
+
+[6401sub0]
+ParameterName=Number of inputs
+DataType=0x0005
+AccessType=ro
+DefaultValue=1
+
+[6401sub1]
+ParameterName=Analog input 1
+DataType=0x0003
+AccessType=ro
+PDOMapping=1
+DefaultValue=-100
+";
+
+    #[test]
+    fn test_parse() {
+        let eds = Eds::parse(DCF).unwrap();
+        assert_eq!(eds.node_id, Some(5));
+        assert_eq!(eds.file_info["FileName"], "io.dcf");
+        assert_eq!(eds.device_info["ProductName"], "IO node");
+        assert_eq!(eds.objects.len(), 6);
+
+        let obj = eds.object(0x1018).unwrap();
+        assert_eq!(obj.object_type, ObjectType::Record);
+        assert_eq!(obj.subs.len(), 2);
+
+        let var = eds.variable_by_name("Identity object.Vendor-ID").unwrap();
+        assert_eq!((var.index, var.sub), (0x1018, 1));
+        assert_eq!(var.access, Access::ReadOnly);
+        assert_eq!(var.value(None), Some(Value::Uint(0x12345678)));
+
+        let var = eds.variable(0x1800, 1).unwrap();
+        assert_eq!(var.value(eds.node_id), Some(Value::Uint(0x185)));
+        let var = eds.variable(0x1800, 2).unwrap();
+        assert_eq!(var.value(None), Some(Value::Uint(1)));
+
+        // A compact array
+        let obj = eds.object(0x6000).unwrap();
+        assert_eq!(obj.subs.len(), 3);
+        assert_eq!(obj.subs[&0].value(None), Some(Value::Uint(2)));
+        assert!(obj.subs[&2].pdo_mapping);
+        assert_eq!(
+            eds.name(0x6000, 2).unwrap(),
+            "Read input 8-bit.Read input 8-bit2"
+        );
+        assert_eq!(eds.name(0x1000, 0).unwrap(), "Device type");
+    }
+
+    #[test]
+    fn test_dictionary() {
+        let eds = Eds::parse(DCF).unwrap();
+        let mut od = eds.dictionary();
+        assert_eq!(od.read(0x1000, 0), Ok(vec![0x91, 0x01, 0, 0]));
+        assert_eq!(od.read(0x1800, 1), Ok(vec![0x85, 0x01, 0, 0]));
+        assert_eq!(od.read(0x6401, 1), Ok(vec![0x9C, 0xFF]));
+        assert_eq!(od.get(0x6000, 1), Some(&[][..]));
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = Eds::parse("[1000]\nDataType=0x0007\n").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 1, .. }));
+
+        let err = Eds::parse("ParameterName=X\n").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 1, .. }));
+
+        let err = Eds::parse("[1000]\nParameterName=X\nAccessType=rx\n").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 1, .. }));
+
+        let err = Eds::parse("\n[2000sub1]\nParameterName=X\n").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 2, .. }));
+
+        let err = Eds::parse("[FileInfo\n").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 1, .. }));
+    }
+}
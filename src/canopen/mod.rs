@@ -0,0 +1,253 @@
+// socketcan/src/canopen/mod.rs
+//
+// CANopen in userspace.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! CANopen (CiA 301) network management, service data objects, and
+//! process data objects.
+//!
+//! CANopen nodes have a node ID from 1 to 127, which is added to a
+//! function code to get the standard 11-bit COB-ID of each of its
+//! services. The network management (NMT) master controls the state of
+//! the nodes with NMT commands, and watches that they're alive with the
+//! error control services: the heartbeats that the nodes send, or, on
+//! older nodes, node guarding, where the master polls each node with a
+//! remote frame.
+//!
+//! The [`NmtMaster`] sends the commands and runs the heartbeat consumer
+//! and node guarding over a [`CanSocket`](crate::CanSocket):
+//!
+//! ```no_run
+//! use socketcan::canopen::{Monitor, NmtMaster};
+//! use std::time::Duration;
+//!
+//! let monitor = Monitor::new()
+//!     .on_boot_up(|node| println!("Node {} booted", node))
+//!     .heartbeat(5, Duration::from_millis(1500), |node| {
+//!         println!("Lost the heartbeat of node {}", node)
+//!     });
+//!
+//! let mut master = NmtMaster::open("can0", monitor).unwrap();
+//! master.start_node(5).unwrap();
+//! master.run().unwrap();
+//! ```
+//!
+//! The [`SdoClient`] reads and writes the object dictionary of a node,
+//! and the [`SdoServer`] serves an [`ObjectDictionary`] to clients. See
+//! the [`sdo`] module.
+//!
+//! The [`ProcessImage`] exchanges values with the nodes in [`Pdo`]s, with
+//! the SYNC producer and consumer, and [`PdoExchange`] runs it over a
+//! socket. The PDOs and the object dictionary of a node can be read from
+//! its EDS or DCF file with [`Eds`].
+//!
+//! Asynchronous versions for tokio are in the [`tokio`](self::tokio)
+//! submodule, when the `tokio` feature is enabled.
+
+use crate::{frame::CanDataFrame, CanFrame, EmbeddedFrame, Frame, StandardId};
+use std::{error, fmt, io};
+
+pub mod nmt;
+pub use nmt::{Monitor, NmtMaster};
+
+pub mod eds;
+pub use eds::Eds;
+
+pub mod od;
+pub use od::ObjectDictionary;
+
+pub mod pdo;
+pub use pdo::{Pdo, PdoExchange, ProcessImage};
+
+pub mod sdo;
+pub use sdo::{AbortCode, SdoClient, SdoServer};
+
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+/// The node ID used in NMT commands to address all the nodes.
+pub const ALL_NODES: u8 = 0;
+
+/// The COB-ID of the NMT commands.
+pub const NMT_COB_ID: u16 = 0x000;
+
+/// The function code of the NMT error control (heartbeat, boot-up, and
+/// node guarding) messages, which is added to the node ID.
+pub const HEARTBEAT_BASE: u16 = 0x700;
+
+/// Creates a data frame with a standard ID.
+pub(crate) fn frame(id: u16, data: &[u8]) -> CanFrame {
+    let id = StandardId::new(id).expect("CANopen COB-ID");
+    CanFrame::Data(CanDataFrame::new(id, data).expect("CANopen frame data"))
+}
+
+/// Gets the COB-ID of a data frame with a standard ID.
+pub(crate) fn cob_id(frame: &CanFrame) -> Option<u16> {
+    match frame {
+        CanFrame::Data(frame) if !frame.is_extended() => Some(frame.raw_id() as u16),
+        _ => None,
+    }
+}
+
+/// Checks that a node ID is in the range 1 - 127.
+pub(crate) fn check_node(node: u8) -> Result<u8, Error> {
+    match node {
+        1..=127 => Ok(node),
+        _ => Err(Error::InvalidNodeId(node)),
+    }
+}
+
+// ===== NodeState =====
+
+/// The NMT state of a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeState {
+    /// The node just booted, and is going to pre-operational
+    BootUp,
+    /// Only NMT and error control are active
+    Stopped,
+    /// All the services are active
+    Operational,
+    /// All the services but PDOs are active
+    PreOperational,
+    /// Another state value
+    Other(u8),
+}
+
+impl From<u8> for NodeState {
+    fn from(code: u8) -> Self {
+        use NodeState::*;
+        match code {
+            0x00 => BootUp,
+            0x04 => Stopped,
+            0x05 => Operational,
+            0x7F => PreOperational,
+            code => Other(code),
+        }
+    }
+}
+
+impl From<NodeState> for u8 {
+    fn from(state: NodeState) -> Self {
+        use NodeState::*;
+        match state {
+            BootUp => 0x00,
+            Stopped => 0x04,
+            Operational => 0x05,
+            PreOperational => 0x7F,
+            Other(code) => code,
+        }
+    }
+}
+
+impl fmt::Display for NodeState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use NodeState::*;
+        match *self {
+            BootUp => f.write_str("boot-up"),
+            Stopped => f.write_str("stopped"),
+            Operational => f.write_str("operational"),
+            PreOperational => f.write_str("pre-operational"),
+            Other(code) => write!(f, "state 0x{:02X}", code),
+        }
+    }
+}
+
+// ===== NmtCommand =====
+
+/// An NMT command to change the state of nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NmtCommand {
+    /// Go to operational
+    Start,
+    /// Go to stopped
+    Stop,
+    /// Go to pre-operational
+    EnterPreOperational,
+    /// Reset the application and communication parameters
+    ResetNode,
+    /// Reset the communication parameters
+    ResetCommunication,
+}
+
+impl From<NmtCommand> for u8 {
+    fn from(cmd: NmtCommand) -> Self {
+        use NmtCommand::*;
+        match cmd {
+            Start => 0x01,
+            Stop => 0x02,
+            EnterPreOperational => 0x80,
+            ResetNode => 0x81,
+            ResetCommunication => 0x82,
+        }
+    }
+}
+
+// ===== Error =====
+
+/// A CANopen error.
+#[derive(Debug)]
+pub enum Error {
+    /// An I/O error on the socket
+    Io(io::Error),
+    /// The node ID isn't in the range 1 - 127
+    InvalidNodeId(u8),
+    /// An SDO transfer was aborted, by either side
+    Aborted(AbortCode),
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+        match self {
+            Io(err) => write!(f, "I/O error: {}", err),
+            InvalidNodeId(node) => write!(f, "invalid node ID: {}", node),
+            Aborted(code) => write!(f, "SDO transfer aborted: {}", code),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_state() {
+        for code in 0..=0xFF {
+            assert_eq!(u8::from(NodeState::from(code)), code);
+        }
+        assert_eq!(NodeState::from(0x7F), NodeState::PreOperational);
+        assert_eq!(NodeState::Operational.to_string(), "operational");
+    }
+
+    #[test]
+    fn test_check_node() {
+        assert_eq!(check_node(1).unwrap(), 1);
+        assert_eq!(check_node(127).unwrap(), 127);
+        assert!(matches!(check_node(0), Err(Error::InvalidNodeId(0))));
+        assert!(matches!(check_node(128), Err(Error::InvalidNodeId(128))));
+    }
+}
@@ -0,0 +1,502 @@
+// socketcan/src/canopen/nmt.rs
+//
+// The CANopen NMT master.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! The CANopen NMT master, with the heartbeat consumer and node guarding.
+
+use super::{
+    check_node, cob_id, frame, Error, NmtCommand, NodeState, ALL_NODES, HEARTBEAT_BASE, NMT_COB_ID,
+};
+use crate::{
+    frame::CAN_EFF_FLAG, CanFilter, CanFrame, CanSocket, EmbeddedFrame, Socket, SocketOptions,
+    StandardId,
+};
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    time::{Duration, Instant},
+};
+
+/// A callback for a node, with its node ID.
+pub type NodeCallback = Box<dyn FnMut(u8) + Send>;
+
+/// A callback for the state change of a node, with its node ID.
+pub type StateCallback = Box<dyn FnMut(u8, NodeState) + Send>;
+
+/// Creates the frame of an NMT command.
+///
+/// The node can be [`ALL_NODES`] to send the command to every node.
+pub fn command_frame(cmd: NmtCommand, node: u8) -> Result<CanFrame, Error> {
+    if node != ALL_NODES {
+        check_node(node)?;
+    }
+    Ok(frame(NMT_COB_ID, &[cmd.into(), node]))
+}
+
+/// A heartbeat consumer for a node
+struct Heartbeat {
+    timeout: Duration,
+    /// When the last heartbeat arrived. The timer starts with the first one.
+    last: Option<Instant>,
+    expired: bool,
+    on_timeout: NodeCallback,
+}
+
+/// Node guarding of a node
+struct Guard {
+    guard_time: Duration,
+    life_time: Duration,
+    next_rtr: Option<Instant>,
+    /// When the node has to answer by
+    deadline: Option<Instant>,
+    /// The expected toggle bit of the next answer
+    toggle: bool,
+    expired: bool,
+    on_timeout: NodeCallback,
+}
+
+/// Monitors the states of the nodes on the network.
+///
+/// This tracks the state of each node from its NMT error control messages,
+/// runs the heartbeat consumer and node guarding for the nodes that are
+/// registered for them, and calls back on boot-up, state changes, and
+/// timeouts.
+///
+/// It's driven by [`NmtMaster`], or it can be driven with the received
+/// frames and the current time by other means.
+#[derive(Default)]
+pub struct Monitor {
+    states: BTreeMap<u8, NodeState>,
+    heartbeats: BTreeMap<u8, Heartbeat>,
+    guards: BTreeMap<u8, Guard>,
+    on_boot_up: Option<NodeCallback>,
+    on_state_change: Option<StateCallback>,
+}
+
+impl Monitor {
+    /// Creates a monitor that only tracks the node states.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Consumes the heartbeat of a node.
+    ///
+    /// Monitoring starts with the first heartbeat of the node. If the next
+    /// one doesn't arrive within the timeout, the callback is called, once,
+    /// until the heartbeat resumes.
+    pub fn heartbeat<F>(mut self, node: u8, timeout: Duration, on_timeout: F) -> Self
+    where
+        F: FnMut(u8) + Send + 'static,
+    {
+        self.heartbeats.insert(
+            node,
+            Heartbeat {
+                timeout,
+                last: None,
+                expired: false,
+                on_timeout: Box::new(on_timeout),
+            },
+        );
+        self
+    }
+
+    /// Guards a node that doesn't send heartbeats.
+    ///
+    /// The node is polled with a remote frame every guard time. If it
+    /// doesn't answer with the right toggle bit within the life time,
+    /// which is the guard time multiplied by the life time factor, the
+    /// callback is called, once, until it answers again.
+    pub fn node_guarding<F>(
+        mut self,
+        node: u8,
+        guard_time: Duration,
+        life_time_factor: u32,
+        on_timeout: F,
+    ) -> Self
+    where
+        F: FnMut(u8) + Send + 'static,
+    {
+        self.guards.insert(
+            node,
+            Guard {
+                guard_time,
+                life_time: guard_time * life_time_factor.max(1),
+                next_rtr: None,
+                deadline: None,
+                toggle: false,
+                expired: false,
+                on_timeout: Box::new(on_timeout),
+            },
+        );
+        self
+    }
+
+    /// Sets a callback for the boot-up message of any node.
+    pub fn on_boot_up<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(u8) + Send + 'static,
+    {
+        self.on_boot_up = Some(Box::new(f));
+        self
+    }
+
+    /// Sets a callback for the change of the state of any node.
+    pub fn on_state_change<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(u8, NodeState) + Send + 'static,
+    {
+        self.on_state_change = Some(Box::new(f));
+        self
+    }
+
+    /// Gets the last known state of a node.
+    pub fn state(&self, node: u8) -> Option<NodeState> {
+        self.states.get(&node).copied()
+    }
+
+    /// Gets the last known states of all the nodes that were heard from.
+    pub fn states(&self) -> &BTreeMap<u8, NodeState> {
+        &self.states
+    }
+
+    /// Handles a received frame.
+    ///
+    /// Returns `true` if it was an NMT error control message.
+    pub fn handle_frame(&mut self, frame: &CanFrame, now: Instant) -> bool {
+        let id = match cob_id(frame) {
+            Some(id) if id & 0x780 == HEARTBEAT_BASE => id,
+            _ => return false,
+        };
+        let node = (id & 0x7F) as u8;
+        let byte = match frame.data().first() {
+            Some(&byte) if node != 0 => byte,
+            _ => return false,
+        };
+        let state = NodeState::from(byte & 0x7F);
+
+        if let Some(hb) = self.heartbeats.get_mut(&node) {
+            hb.last = Some(now);
+            hb.expired = false;
+        }
+        if let Some(guard) = self.guards.get_mut(&node) {
+            if state == NodeState::BootUp {
+                guard.toggle = false;
+            } else if (byte & 0x80 != 0) == guard.toggle {
+                guard.toggle = !guard.toggle;
+                guard.deadline = Some(now + guard.life_time);
+                guard.expired = false;
+            } else {
+                log::debug!("Node guarding toggle error from node {}", node);
+            }
+        }
+
+        if state == NodeState::BootUp {
+            if let Some(f) = self.on_boot_up.as_mut() {
+                f(node);
+            }
+        }
+        if self.states.insert(node, state) != Some(state) {
+            if let Some(f) = self.on_state_change.as_mut() {
+                f(node, state);
+            }
+        }
+        true
+    }
+
+    /// Checks the timers, calling back for the nodes that timed out.
+    ///
+    /// This returns the node guarding remote frames that need to be sent.
+    pub fn poll(&mut self, now: Instant) -> Vec<CanFrame> {
+        for (&node, hb) in &mut self.heartbeats {
+            if let Some(last) = hb.last {
+                if !hb.expired && now >= last + hb.timeout {
+                    hb.expired = true;
+                    (hb.on_timeout)(node);
+                }
+            }
+        }
+
+        let mut frames = Vec::new();
+        for (&node, guard) in &mut self.guards {
+            let deadline = *guard.deadline.get_or_insert(now + guard.life_time);
+            if !guard.expired && now >= deadline {
+                guard.expired = true;
+                (guard.on_timeout)(node);
+            }
+            let next = guard.next_rtr.unwrap_or(now);
+            if next <= now {
+                let id = StandardId::new(HEARTBEAT_BASE + u16::from(node)).expect("COB-ID");
+                frames.push(CanFrame::new_remote(id, 1).expect("remote frame"));
+                guard.next_rtr = Some(now + guard.guard_time);
+            }
+        }
+        frames
+    }
+
+    /// Gets the next time that [`poll()`](Self::poll) needs to be called.
+    pub fn deadline(&self) -> Option<Instant> {
+        let heartbeats = self
+            .heartbeats
+            .values()
+            .filter(|hb| !hb.expired)
+            .filter_map(|hb| hb.last.map(|last| last + hb.timeout));
+        let guards = self.guards.values().flat_map(|guard| {
+            let deadline = guard.deadline.filter(|_| !guard.expired);
+            [guard.next_rtr, deadline].into_iter().flatten()
+        });
+        heartbeats.chain(guards).min()
+    }
+}
+
+impl fmt::Debug for Monitor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Monitor")
+            .field("states", &self.states)
+            .field("heartbeats", &self.heartbeats.keys())
+            .field("guards", &self.guards.keys())
+            .finish()
+    }
+}
+
+// ===== NmtMaster =====
+
+/// A blocking NMT master over a CAN socket.
+///
+/// The monitor only runs while [`process()`](Self::process) or
+/// [`run()`](Self::run) is being called.
+#[derive(Debug)]
+pub struct NmtMaster {
+    sock: CanSocket,
+    monitor: Monitor,
+}
+
+impl NmtMaster {
+    /// Opens an NMT master on the named CAN interface.
+    ///
+    /// The socket only receives the NMT error control messages.
+    pub fn open(ifname: &str, monitor: Monitor) -> io::Result<Self> {
+        let sock = CanSocket::open(ifname)?;
+        sock.set_filters(&[error_control_filter()])?;
+        Ok(Self::new(sock, monitor))
+    }
+
+    /// Creates an NMT master on an open CAN socket.
+    pub fn new(sock: CanSocket, monitor: Monitor) -> Self {
+        Self { sock, monitor }
+    }
+
+    /// Gets a reference to the underlying CAN socket.
+    pub fn socket(&self) -> &CanSocket {
+        &self.sock
+    }
+
+    /// Gets the monitor of the node states.
+    pub fn monitor(&self) -> &Monitor {
+        &self.monitor
+    }
+
+    /// Gets the last known state of a node.
+    pub fn state(&self, node: u8) -> Option<NodeState> {
+        self.monitor.state(node)
+    }
+
+    /// Sends an NMT command to a node, or to [`ALL_NODES`].
+    pub fn send_command(&self, cmd: NmtCommand, node: u8) -> Result<(), Error> {
+        self.sock.write_frame_insist(&command_frame(cmd, node)?)?;
+        Ok(())
+    }
+
+    /// Puts a node into the operational state.
+    pub fn start_node(&self, node: u8) -> Result<(), Error> {
+        self.send_command(NmtCommand::Start, node)
+    }
+
+    /// Puts a node into the stopped state.
+    pub fn stop_node(&self, node: u8) -> Result<(), Error> {
+        self.send_command(NmtCommand::Stop, node)
+    }
+
+    /// Puts a node into the pre-operational state.
+    pub fn enter_pre_operational(&self, node: u8) -> Result<(), Error> {
+        self.send_command(NmtCommand::EnterPreOperational, node)
+    }
+
+    /// Resets a node.
+    pub fn reset_node(&self, node: u8) -> Result<(), Error> {
+        self.send_command(NmtCommand::ResetNode, node)
+    }
+
+    /// Resets the communication of a node.
+    pub fn reset_communication(&self, node: u8) -> Result<(), Error> {
+        self.send_command(NmtCommand::ResetCommunication, node)
+    }
+
+    /// Runs the monitor for a time.
+    pub fn process(&mut self, dur: Duration) -> Result<(), Error> {
+        let end = Instant::now() + dur;
+        loop {
+            let now = Instant::now();
+            for frame in self.monitor.poll(now) {
+                self.sock.write_frame_insist(&frame)?;
+            }
+            if now >= end {
+                return Ok(());
+            }
+
+            let wake = self.monitor.deadline().map_or(end, |t| t.min(end));
+            let timeout = wake
+                .saturating_duration_since(now)
+                .max(Duration::from_millis(1));
+            match self.sock.read_frame_timeout(timeout) {
+                Ok(frame) => {
+                    self.monitor.handle_frame(&frame, Instant::now());
+                }
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                    ) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// Runs the monitor until an error occurs.
+    pub fn run(&mut self) -> Result<(), Error> {
+        loop {
+            self.process(Duration::from_secs(3600))?;
+        }
+    }
+}
+
+/// A filter for the NMT error control messages, with standard IDs.
+pub(crate) fn error_control_filter() -> CanFilter {
+    CanFilter::new(HEARTBEAT_BASE.into(), 0x780 | CAN_EFF_FLAG)
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Frame;
+    use std::sync::{Arc, Mutex};
+
+    fn heartbeat(node: u8, state: u8) -> CanFrame {
+        frame(HEARTBEAT_BASE + u16::from(node), &[state])
+    }
+
+    fn recorder() -> (Arc<Mutex<Vec<u8>>>, impl FnMut(u8) + Send + 'static) {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let log2 = Arc::clone(&log);
+        (log, move |node| log2.lock().unwrap().push(node))
+    }
+
+    #[test]
+    fn test_command_frame() {
+        let frame = command_frame(NmtCommand::Start, 5).unwrap();
+        assert_eq!(cob_id(&frame), Some(0));
+        assert_eq!(frame.data(), &[0x01, 5]);
+
+        let frame = command_frame(NmtCommand::ResetCommunication, ALL_NODES).unwrap();
+        assert_eq!(frame.data(), &[0x82, 0]);
+
+        assert!(matches!(
+            command_frame(NmtCommand::Stop, 128),
+            Err(Error::InvalidNodeId(128))
+        ));
+    }
+
+    #[test]
+    fn test_heartbeat() {
+        let now = Instant::now();
+        let ms = Duration::from_millis;
+        let (boots, on_boot) = recorder();
+        let (timeouts, on_timeout) = recorder();
+        let states = Arc::new(Mutex::new(Vec::new()));
+        let states2 = Arc::clone(&states);
+
+        let mut mon = Monitor::new()
+            .on_boot_up(on_boot)
+            .on_state_change(move |node, state| states2.lock().unwrap().push((node, state)))
+            .heartbeat(5, ms(100), on_timeout);
+
+        // Nothing times out before the first heartbeat
+        assert!(mon.deadline().is_none());
+        mon.poll(now + ms(500));
+        assert!(timeouts.lock().unwrap().is_empty());
+
+        assert!(mon.handle_frame(&heartbeat(5, 0x00), now));
+        assert!(mon.handle_frame(&heartbeat(5, 0x7F), now + ms(50)));
+        assert!(mon.handle_frame(&heartbeat(5, 0x7F), now + ms(100)));
+        assert!(mon.handle_frame(&heartbeat(6, 0x05), now + ms(100)));
+        assert!(!mon.handle_frame(&frame(0x585, &[0; 8]), now));
+
+        assert_eq!(*boots.lock().unwrap(), &[5]);
+        assert_eq!(
+            *states.lock().unwrap(),
+            &[
+                (5, NodeState::BootUp),
+                (5, NodeState::PreOperational),
+                (6, NodeState::Operational)
+            ]
+        );
+        assert_eq!(mon.state(5), Some(NodeState::PreOperational));
+
+        // The timeout is reported once
+        assert_eq!(mon.deadline(), Some(now + ms(200)));
+        mon.poll(now + ms(199));
+        assert!(timeouts.lock().unwrap().is_empty());
+        mon.poll(now + ms(200));
+        mon.poll(now + ms(300));
+        assert_eq!(*timeouts.lock().unwrap(), &[5]);
+        assert!(mon.deadline().is_none());
+
+        // Until the node is back
+        mon.handle_frame(&heartbeat(5, 0x05), now + ms(400));
+        mon.poll(now + ms(500));
+        assert_eq!(*timeouts.lock().unwrap(), &[5, 5]);
+    }
+
+    #[test]
+    fn test_node_guarding() {
+        let now = Instant::now();
+        let ms = Duration::from_millis;
+        let (timeouts, on_timeout) = recorder();
+        let mut mon = Monitor::new().node_guarding(9, ms(100), 3, on_timeout);
+
+        // The first poll sends a remote frame
+        let frames = mon.poll(now);
+        assert_eq!(frames.len(), 1);
+        assert!(matches!(frames[0], CanFrame::Remote(_)));
+        assert_eq!(frames[0].raw_id(), 0x709);
+        assert!(mon.poll(now + ms(50)).is_empty());
+        assert_eq!(mon.deadline(), Some(now + ms(100)));
+
+        // Answers with alternating toggle bits keep it alive
+        mon.handle_frame(&heartbeat(9, 0x05), now + ms(10));
+        mon.poll(now + ms(100));
+        mon.handle_frame(&heartbeat(9, 0x85), now + ms(110));
+        assert_eq!(mon.state(9), Some(NodeState::Operational));
+        mon.poll(now + ms(400));
+        assert!(timeouts.lock().unwrap().is_empty());
+
+        // A repeated toggle bit doesn't count
+        mon.handle_frame(&heartbeat(9, 0x85), now + ms(400));
+        mon.poll(now + ms(410));
+        assert_eq!(*timeouts.lock().unwrap(), &[9]);
+        mon.poll(now + ms(800));
+        assert_eq!(*timeouts.lock().unwrap(), &[9]);
+
+        mon.handle_frame(&heartbeat(9, 0x05), now + ms(900));
+        mon.poll(now + ms(1100));
+        assert_eq!(*timeouts.lock().unwrap(), &[9]);
+    }
+}
@@ -0,0 +1,568 @@
+// socketcan/src/canopen/od.rs
+//
+// The CANopen object dictionary.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! The object dictionary of a CANopen node.
+//!
+//! The [`SdoServer`](super::SdoServer) serves any type that implements
+//! [`ObjectDictionary`], such as an application's own parameters. The
+//! [`Dictionary`] is a simple one that holds the values in memory.
+//!
+//! The entries hold raw bytes. A [`DataType`] converts them to and from a
+//! typed [`Value`].
+
+use super::AbortCode;
+use std::{collections::BTreeMap, fmt};
+
+/// An object dictionary that can be read and written by SDO.
+pub trait ObjectDictionary {
+    /// Reads the value of an entry.
+    fn read(&mut self, index: u16, sub: u8) -> Result<Vec<u8>, AbortCode>;
+
+    /// Writes the value of an entry.
+    fn write(&mut self, index: u16, sub: u8, data: &[u8]) -> Result<(), AbortCode>;
+}
+
+// ===== DataType =====
+
+/// The data type of an entry, with its CiA 301 index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DataType {
+    /// A boolean, in one bit of a PDO
+    Boolean,
+    /// A signed 8-bit integer
+    Integer8,
+    /// A signed 16-bit integer
+    Integer16,
+    /// A signed 24-bit integer
+    Integer24,
+    /// A signed 32-bit integer
+    Integer32,
+    /// A signed 64-bit integer
+    Integer64,
+    /// An unsigned 8-bit integer
+    Unsigned8,
+    /// An unsigned 16-bit integer
+    Unsigned16,
+    /// An unsigned 24-bit integer
+    Unsigned24,
+    /// An unsigned 32-bit integer
+    #[default]
+    Unsigned32,
+    /// An unsigned 64-bit integer
+    Unsigned64,
+    /// A 32-bit float
+    Real32,
+    /// A 64-bit float
+    Real64,
+    /// A string of visible ASCII characters
+    VisibleString,
+    /// A string of bytes
+    OctetString,
+    /// A string of UTF-16 characters
+    UnicodeString,
+    /// Any amount of data
+    Domain,
+    /// Another type, such as a structure
+    Other(u16),
+}
+
+impl From<u16> for DataType {
+    fn from(code: u16) -> Self {
+        use DataType::*;
+        match code {
+            0x0001 => Boolean,
+            0x0002 => Integer8,
+            0x0003 => Integer16,
+            0x0004 => Integer32,
+            0x0005 => Unsigned8,
+            0x0006 => Unsigned16,
+            0x0007 => Unsigned32,
+            0x0008 => Real32,
+            0x0009 => VisibleString,
+            0x000A => OctetString,
+            0x000B => UnicodeString,
+            0x000F => Domain,
+            0x0010 => Integer24,
+            0x0011 => Real64,
+            0x0015 => Integer64,
+            0x0016 => Unsigned24,
+            0x001B => Unsigned64,
+            code => Other(code),
+        }
+    }
+}
+
+impl From<DataType> for u16 {
+    fn from(data_type: DataType) -> Self {
+        use DataType::*;
+        match data_type {
+            Boolean => 0x0001,
+            Integer8 => 0x0002,
+            Integer16 => 0x0003,
+            Integer32 => 0x0004,
+            Unsigned8 => 0x0005,
+            Unsigned16 => 0x0006,
+            Unsigned32 => 0x0007,
+            Real32 => 0x0008,
+            VisibleString => 0x0009,
+            OctetString => 0x000A,
+            UnicodeString => 0x000B,
+            Domain => 0x000F,
+            Integer24 => 0x0010,
+            Real64 => 0x0011,
+            Integer64 => 0x0015,
+            Unsigned24 => 0x0016,
+            Unsigned64 => 0x001B,
+            Other(code) => code,
+        }
+    }
+}
+
+impl DataType {
+    /// Gets the size of a value in bytes, for the fixed size types.
+    pub fn size(&self) -> Option<usize> {
+        use DataType::*;
+        match self {
+            Boolean | Integer8 | Unsigned8 => Some(1),
+            Integer16 | Unsigned16 => Some(2),
+            Integer24 | Unsigned24 => Some(3),
+            Integer32 | Unsigned32 | Real32 => Some(4),
+            Integer64 | Unsigned64 | Real64 => Some(8),
+            _ => None,
+        }
+    }
+
+    /// Gets the number of bits that a value takes in a PDO, for the fixed
+    /// size types.
+    pub fn bits(&self) -> Option<u8> {
+        match self {
+            DataType::Boolean => Some(1),
+            _ => self.size().map(|size| 8 * size as u8),
+        }
+    }
+
+    /// Determines if this is a signed integer type.
+    pub fn is_signed(&self) -> bool {
+        use DataType::*;
+        matches!(
+            self,
+            Integer8 | Integer16 | Integer24 | Integer32 | Integer64
+        )
+    }
+
+    /// Gets the value for zero, or an empty value.
+    pub fn default_value(&self) -> Value {
+        self.decode_raw(0, 0)
+    }
+
+    /// Decodes a value from the little-endian bytes of an entry.
+    pub fn decode(&self, data: &[u8]) -> Option<Value> {
+        use DataType::*;
+        match (self, self.size()) {
+            (_, Some(size)) => {
+                let mut buf = [0u8; 8];
+                buf[..size].copy_from_slice(data.get(..size)?);
+                Some(self.decode_raw(u64::from_le_bytes(buf), 8 * size as u32))
+            }
+            (VisibleString, _) => {
+                let s = String::from_utf8_lossy(data);
+                Some(Value::String(s.trim_end_matches('\0').to_string()))
+            }
+            _ => Some(Value::Bytes(data.to_vec())),
+        }
+    }
+
+    /// Encodes a value into the little-endian bytes of an entry.
+    ///
+    /// Numeric values are converted to the type, so an integer can be
+    /// written to a float entry and the other way around. This returns
+    /// `None` if the value can't be converted.
+    pub fn encode(&self, value: &Value) -> Option<Vec<u8>> {
+        match self.size() {
+            Some(size) => Some(self.encode_raw(value)?.to_le_bytes()[..size].to_vec()),
+            None => match value {
+                Value::String(s) => Some(s.as_bytes().to_vec()),
+                Value::Bytes(data) => Some(data.clone()),
+                _ => None,
+            },
+        }
+    }
+
+    /// Parses a value in the format of EDS files.
+    ///
+    /// Integers are decimal, or hexadecimal with a `0x` prefix, and can be
+    /// added to `$NODEID` for the node ID, like `$NODEID+0x180`. Octet
+    /// strings are hexadecimal bytes.
+    pub fn parse(&self, s: &str, node_id: Option<u8>) -> Option<Value> {
+        use DataType::*;
+        let s = s.trim();
+        match self {
+            Real32 | Real64 => s.parse().ok().map(Value::Real),
+            VisibleString => Some(Value::String(s.to_string())),
+            OctetString | Domain | UnicodeString | Other(_) => {
+                let s: String = s.split_whitespace().collect();
+                let hex = s.trim_start_matches("0x");
+                (0..hex.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+                    .collect::<Option<_>>()
+                    .map(Value::Bytes)
+            }
+            _ => {
+                let n = s.split('+').try_fold(0i64, |sum, term| {
+                    let term = term.trim();
+                    let n = if term.eq_ignore_ascii_case("$NODEID") {
+                        i64::from(node_id.unwrap_or(0))
+                    } else {
+                        parse_int(term)?
+                    };
+                    Some(sum.wrapping_add(n))
+                })?;
+                Some(self.decode_raw(n as u64, 64))
+            }
+        }
+    }
+
+    /// Converts the raw bits of a fixed size value.
+    pub(crate) fn decode_raw(&self, raw: u64, bits: u32) -> Value {
+        use DataType::*;
+        match self {
+            Boolean => Value::Bool(raw != 0),
+            Real32 => Value::Real(f64::from(f32::from_bits(raw as u32))),
+            Real64 => Value::Real(f64::from_bits(raw)),
+            _ if self.is_signed() => {
+                let shift = 64 - bits.clamp(1, 64);
+                Value::Int(((raw << shift) as i64) >> shift)
+            }
+            _ if self.size().is_some() => Value::Uint(raw),
+            VisibleString => {
+                let data = raw.to_le_bytes();
+                let s = String::from_utf8_lossy(&data[..(bits as usize / 8).min(8)]);
+                Value::String(s.trim_end_matches('\0').to_string())
+            }
+            _ => Value::Bytes(raw.to_le_bytes()[..(bits as usize / 8).min(8)].to_vec()),
+        }
+    }
+
+    /// Converts a value to the raw bits of the type, for up to 64 bits.
+    pub(crate) fn encode_raw(&self, value: &Value) -> Option<u64> {
+        use DataType::*;
+        let raw = match (self, value) {
+            (Real32, _) => u64::from((value.as_f64()? as f32).to_bits()),
+            (Real64, _) => value.as_f64()?.to_bits(),
+            (_, Value::Bool(b)) => u64::from(*b),
+            (_, Value::Int(n)) => *n as u64,
+            (_, Value::Uint(n)) => *n,
+            (_, Value::Real(x)) => *x as i64 as u64,
+            (_, Value::String(s)) => bytes_to_raw(s.as_bytes()),
+            (_, Value::Bytes(data)) => bytes_to_raw(data),
+        };
+        Some(raw)
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataType::Other(code) => write!(f, "type 0x{:04X}", code),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+/// Gets the first eight bytes as a little-endian number.
+fn bytes_to_raw(data: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    let n = data.len().min(8);
+    buf[..n].copy_from_slice(&data[..n]);
+    u64::from_le_bytes(buf)
+}
+
+/// Parses an integer, in decimal or in hexadecimal with a `0x` prefix.
+pub(crate) fn parse_int(s: &str) -> Option<i64> {
+    let (neg, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let n = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()? as i64,
+        None => s.parse().ok()?,
+    };
+    Some(if neg { n.wrapping_neg() } else { n })
+}
+
+// ===== Value =====
+
+/// A typed value of an entry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// A boolean
+    Bool(bool),
+    /// A signed integer
+    Int(i64),
+    /// An unsigned integer
+    Uint(u64),
+    /// A float
+    Real(f64),
+    /// A string
+    String(String),
+    /// Raw bytes
+    Bytes(Vec<u8>),
+}
+
+impl Value {
+    /// Gets a numeric value as a float.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Bool(b) => Some(f64::from(u8::from(b))),
+            Value::Int(n) => Some(n as f64),
+            Value::Uint(n) => Some(n as f64),
+            Value::Real(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// Gets an integer value as unsigned.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Value::Bool(b) => Some(u64::from(b)),
+            Value::Int(n) => u64::try_from(n).ok(),
+            Value::Uint(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Gets an integer value as signed.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::Bool(b) => Some(i64::from(b)),
+            Value::Int(n) => Some(n),
+            Value::Uint(n) => i64::try_from(n).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Value::*;
+        match self {
+            Bool(b) => write!(f, "{}", b),
+            Int(n) => write!(f, "{}", n),
+            Uint(n) => write!(f, "{}", n),
+            Real(x) => write!(f, "{}", x),
+            String(s) => write!(f, "{:?}", s),
+            Bytes(data) => write!(f, "{:02X?}", data),
+        }
+    }
+}
+
+// ===== Dictionary =====
+
+/// The access to an entry over SDO.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Access {
+    /// Read only
+    ReadOnly,
+    /// Write only
+    WriteOnly,
+    /// Read and write
+    #[default]
+    ReadWrite,
+    /// Read only, and never changes
+    Const,
+}
+
+impl Access {
+    /// Determines if the entry can be read.
+    pub fn is_readable(&self) -> bool {
+        !matches!(self, Access::WriteOnly)
+    }
+
+    /// Determines if the entry can be written.
+    pub fn is_writable(&self) -> bool {
+        matches!(self, Access::WriteOnly | Access::ReadWrite)
+    }
+}
+
+/// An entry in a [`Dictionary`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Entry {
+    /// The access to the entry
+    pub access: Access,
+    /// The value, in little-endian byte order
+    pub value: Vec<u8>,
+}
+
+/// An object dictionary held in memory.
+///
+/// ```
+/// use socketcan::canopen::od::{Access, Dictionary, ObjectDictionary};
+///
+/// let mut od = Dictionary::new()
+///     .entry(0x1008, 0, Access::Const, b"Motor controller".to_vec())
+///     .entry(0x1017, 0, Access::ReadWrite, 1000u16.to_le_bytes().to_vec());
+///
+/// od.write(0x1017, 0, &500u16.to_le_bytes()).unwrap();
+/// assert_eq!(od.get(0x1017, 0), Some(&500u16.to_le_bytes()[..]));
+/// assert!(od.write(0x1008, 0, b"Other").is_err());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dictionary {
+    entries: BTreeMap<(u16, u8), Entry>,
+}
+
+impl Dictionary {
+    /// Creates an empty dictionary.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an entry.
+    pub fn entry(mut self, index: u16, sub: u8, access: Access, value: Vec<u8>) -> Self {
+        self.insert(index, sub, Entry { access, value });
+        self
+    }
+
+    /// Inserts or replaces an entry.
+    pub fn insert(&mut self, index: u16, sub: u8, entry: Entry) {
+        self.entries.insert((index, sub), entry);
+    }
+
+    /// Gets an entry.
+    pub fn get_entry(&self, index: u16, sub: u8) -> Option<&Entry> {
+        self.entries.get(&(index, sub))
+    }
+
+    /// Gets the value of an entry, regardless of its access.
+    pub fn get(&self, index: u16, sub: u8) -> Option<&[u8]> {
+        self.get_entry(index, sub)
+            .map(|entry| entry.value.as_slice())
+    }
+
+    /// Sets the value of an existing entry, regardless of its access.
+    ///
+    /// Returns `false` if there's no such entry.
+    pub fn set(&mut self, index: u16, sub: u8, value: impl Into<Vec<u8>>) -> bool {
+        match self.entries.get_mut(&(index, sub)) {
+            Some(entry) => {
+                entry.value = value.into();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Gets an iterator over the entries, in order.
+    pub fn iter(&self) -> impl Iterator<Item = ((u16, u8), &Entry)> {
+        self.entries.iter().map(|(&key, entry)| (key, entry))
+    }
+
+    /// Gets an entry for an SDO access, or the reason it doesn't exist.
+    fn lookup(&mut self, index: u16, sub: u8) -> Result<&mut Entry, AbortCode> {
+        if !self.entries.keys().any(|&(i, _)| i == index) {
+            return Err(AbortCode::ObjectNotFound);
+        }
+        self.entries
+            .get_mut(&(index, sub))
+            .ok_or(AbortCode::SubIndexNotFound)
+    }
+}
+
+impl ObjectDictionary for Dictionary {
+    fn read(&mut self, index: u16, sub: u8) -> Result<Vec<u8>, AbortCode> {
+        let entry = self.lookup(index, sub)?;
+        if !entry.access.is_readable() {
+            return Err(AbortCode::WriteOnly);
+        }
+        Ok(entry.value.clone())
+    }
+
+    fn write(&mut self, index: u16, sub: u8, data: &[u8]) -> Result<(), AbortCode> {
+        let entry = self.lookup(index, sub)?;
+        if !entry.access.is_writable() {
+            return Err(AbortCode::ReadOnly);
+        }
+        entry.value = data.to_vec();
+        Ok(())
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_type() {
+        for code in 0..0x30 {
+            assert_eq!(u16::from(DataType::from(code)), code);
+        }
+        assert_eq!(DataType::Boolean.bits(), Some(1));
+        assert_eq!(DataType::Integer24.bits(), Some(24));
+        assert_eq!(DataType::VisibleString.bits(), None);
+
+        let int16 = DataType::Integer16;
+        assert_eq!(int16.decode(&[0xFE, 0xFF]), Some(Value::Int(-2)));
+        assert_eq!(int16.decode(&[0xFE]), None);
+        assert_eq!(int16.encode(&Value::Int(-2)), Some(vec![0xFE, 0xFF]));
+        assert_eq!(
+            DataType::Real32.encode(&Value::Uint(1)),
+            Some(1.0f32.to_le_bytes().to_vec())
+        );
+        assert_eq!(
+            DataType::VisibleString.decode(b"abc\0"),
+            Some(Value::String("abc".into()))
+        );
+        assert_eq!(
+            DataType::Integer24.decode_raw(0xFF_FFFF, 24),
+            Value::Int(-1)
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let u32 = DataType::Unsigned32;
+        assert_eq!(u32.parse("0x180", None), Some(Value::Uint(0x180)));
+        assert_eq!(
+            u32.parse("$NODEID+0x180", Some(5)),
+            Some(Value::Uint(0x185))
+        );
+        assert_eq!(
+            u32.parse("0x200 + $NodeId", Some(5)),
+            Some(Value::Uint(0x205))
+        );
+        assert_eq!(u32.parse("", None), None);
+        assert_eq!(DataType::Integer8.parse("-5", None), Some(Value::Int(-5)));
+        assert_eq!(DataType::Boolean.parse("1", None), Some(Value::Bool(true)));
+        assert_eq!(DataType::Real32.parse("1.5", None), Some(Value::Real(1.5)));
+        assert_eq!(
+            DataType::OctetString.parse("0102 0A", None),
+            Some(Value::Bytes(vec![1, 2, 10]))
+        );
+    }
+
+    #[test]
+    fn test_dictionary() {
+        let mut od = Dictionary::new()
+            .entry(0x1000, 0, Access::Const, vec![0x91, 0x01, 0, 0])
+            .entry(0x2000, 1, Access::WriteOnly, vec![]);
+
+        assert_eq!(od.read(0x1000, 0), Ok(vec![0x91, 0x01, 0, 0]));
+        assert_eq!(od.write(0x1000, 0, &[0]), Err(AbortCode::ReadOnly));
+        assert_eq!(od.read(0x2000, 1), Err(AbortCode::WriteOnly));
+        assert_eq!(od.read(0x2000, 2), Err(AbortCode::SubIndexNotFound));
+        assert_eq!(od.read(0x3000, 0), Err(AbortCode::ObjectNotFound));
+        assert_eq!(od.write(0x2000, 1, &[1, 2]), Ok(()));
+        assert_eq!(od.get(0x2000, 1), Some(&[1, 2][..]));
+    }
+}
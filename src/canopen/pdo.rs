@@ -0,0 +1,802 @@
+// socketcan/src/canopen/pdo.rs
+//
+// CANopen process data objects (PDO) and SYNC.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! CANopen process data objects (PDO), and the SYNC producer and consumer.
+//!
+//! A PDO is a frame with the values of one or more entries of the object
+//! dictionary packed into it, as described by its mapping. Each node has
+//! receive PDOs (RPDO) and transmit PDOs (TPDO). The TPDOs of a slave
+//! are received by the master, and the master sends the RPDOs of the
+//! slaves. A PDO is sent when a value changes, on an event timer, or after
+//! every _n_ SYNC messages.
+//!
+//! The [`ProcessImage`] holds the values, by name, for the PDOs that are
+//! received and transmitted. It decodes the received PDOs, and encodes and
+//! schedules the transmitted ones, optionally producing the SYNC. The
+//! [`PdoExchange`] runs it over a [`CanSocket`]:
+//!
+//! ```no_run
+//! use socketcan::canopen::{
+//!     eds::Eds,
+//!     od::Value,
+//!     pdo::{PdoExchange, ProcessImage, TPDO_COMM_BASE},
+//! };
+//! use std::time::Duration;
+//!
+//! let eds = Eds::from_file("io.dcf").unwrap();
+//!
+//! // The first TPDO of the slave is received by the master
+//! let tpdo = eds.pdo(TPDO_COMM_BASE).unwrap();
+//! let image = ProcessImage::new()
+//!     .receive(tpdo)
+//!     .sync_producer(Duration::from_millis(10));
+//!
+//! let mut exchange = PdoExchange::open("can0", image).unwrap();
+//! exchange.process(Duration::from_secs(1)).unwrap();
+//! println!("{:?}", exchange.image().get("Read input 8-bit.Read input 8-bit1"));
+//! ```
+
+use super::{
+    cob_id,
+    eds::Eds,
+    frame,
+    od::{DataType, Value},
+    Error, SdoClient,
+};
+use crate::{CanFrame, CanSocket, EmbeddedFrame, Socket};
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    time::{Duration, Instant},
+};
+
+/// The COB-ID of the SYNC message.
+pub const SYNC_COB_ID: u16 = 0x080;
+
+/// The index of the communication parameters of the first RPDO.
+pub const RPDO_COMM_BASE: u16 = 0x1400;
+
+/// The index of the mapping of the first RPDO.
+pub const RPDO_MAP_BASE: u16 = 0x1600;
+
+/// The index of the communication parameters of the first TPDO.
+pub const TPDO_COMM_BASE: u16 = 0x1800;
+
+/// The index of the mapping of the first TPDO.
+pub const TPDO_MAP_BASE: u16 = 0x1A00;
+
+/// The offset from the communication parameters to the mapping of a PDO
+const MAP_OFFSET: u16 = 0x200;
+
+/// The bit in a PDO COB-ID that disables it
+const COB_ID_INVALID: u32 = 0x8000_0000;
+
+/// The bits of a COB-ID that hold the 11-bit CAN ID
+const COB_ID_MASK: u16 = 0x7FF;
+
+/// The largest number of bits in a PDO
+const MAX_BITS: u32 = 64;
+
+// ===== Transmission =====
+
+/// When a PDO is sent, from its transmission type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Transmission {
+    /// At the next SYNC, when a value changed
+    Acyclic,
+    /// Every _n_ SYNCs, from 1 to 240
+    Sync(u8),
+    /// At the next SYNC after a remote request
+    RtrSync,
+    /// On a remote request
+    Rtr,
+    /// On an event specific to the manufacturer, or on the event timer
+    ManufacturerEvent,
+    /// On an event from the device profile, such as a changed value, or on
+    /// the event timer
+    #[default]
+    Event,
+    /// A reserved transmission type
+    Reserved(u8),
+}
+
+impl From<u8> for Transmission {
+    fn from(code: u8) -> Self {
+        use Transmission::*;
+        match code {
+            0 => Acyclic,
+            1..=240 => Sync(code),
+            252 => RtrSync,
+            253 => Rtr,
+            254 => ManufacturerEvent,
+            255 => Event,
+            code => Reserved(code),
+        }
+    }
+}
+
+impl From<Transmission> for u8 {
+    fn from(transmission: Transmission) -> Self {
+        use Transmission::*;
+        match transmission {
+            Acyclic => 0,
+            Sync(n) => n,
+            RtrSync => 252,
+            Rtr => 253,
+            ManufacturerEvent => 254,
+            Event => 255,
+            Reserved(code) => code,
+        }
+    }
+}
+
+impl Transmission {
+    /// Determines if the PDO is sent on an event, rather than on SYNC or
+    /// a remote request.
+    pub fn is_event(&self) -> bool {
+        matches!(self, Transmission::ManufacturerEvent | Transmission::Event)
+    }
+}
+
+// ===== Pdo =====
+
+/// An entry mapped into a PDO.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PdoMapping {
+    /// The name of the value in the process image
+    pub name: String,
+    /// The index of the entry
+    pub index: u16,
+    /// The sub-index of the entry
+    pub sub: u8,
+    /// The number of bits in the PDO
+    pub bits: u8,
+    /// The data type of the entry
+    pub data_type: DataType,
+}
+
+impl PdoMapping {
+    /// Gets the value for the mapping parameter object.
+    pub fn entry(&self) -> u32 {
+        (u32::from(self.index) << 16) | (u32::from(self.sub) << 8) | u32::from(self.bits)
+    }
+}
+
+/// The configuration of a PDO: its COB-ID, transmission, and mapping.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pdo {
+    /// The COB-ID
+    pub cob_id: u16,
+    /// When it's sent
+    pub transmission: Transmission,
+    /// The time between event-driven transmissions, if any
+    pub event_timer: Option<Duration>,
+    /// The mapped entries, in order from the least significant bit
+    pub mappings: Vec<PdoMapping>,
+}
+
+impl Pdo {
+    /// Creates an event-driven PDO, with no mapped entries.
+    ///
+    /// Only the 11-bit CAN ID in the low bits of the COB-ID is used.
+    pub fn new(cob_id: u16) -> Self {
+        Self {
+            cob_id: cob_id & COB_ID_MASK,
+            transmission: Transmission::default(),
+            event_timer: None,
+            mappings: Vec::new(),
+        }
+    }
+
+    /// Sets when the PDO is sent.
+    pub fn transmission(mut self, transmission: Transmission) -> Self {
+        self.transmission = transmission;
+        self
+    }
+
+    /// Sets the time between event-driven transmissions.
+    pub fn event_timer(mut self, period: Duration) -> Self {
+        self.event_timer = Some(period);
+        self
+    }
+
+    /// Maps the next entry, with the number of bits of its data type.
+    ///
+    /// Entries without a fixed size, such as strings, take eight bits.
+    pub fn map(self, name: &str, index: u16, sub: u8, data_type: DataType) -> Self {
+        let bits = data_type.bits().unwrap_or(8);
+        self.map_bits(name, index, sub, bits, data_type)
+    }
+
+    /// Maps the next entry, with a number of bits.
+    pub fn map_bits(
+        mut self,
+        name: &str,
+        index: u16,
+        sub: u8,
+        bits: u8,
+        data_type: DataType,
+    ) -> Self {
+        self.mappings.push(PdoMapping {
+            name: name.to_string(),
+            index,
+            sub,
+            bits,
+            data_type,
+        });
+        self
+    }
+
+    /// Gets the number of data bytes in the PDO frame.
+    pub fn len(&self) -> usize {
+        let bits: u32 = self.mappings.iter().map(|m| u32::from(m.bits)).sum();
+        (bits.min(MAX_BITS) as usize + 7) / 8
+    }
+
+    /// Determines if there are no mapped entries.
+    pub fn is_empty(&self) -> bool {
+        self.mappings.is_empty()
+    }
+
+    /// Decodes the values of the entries from the data of a PDO frame.
+    ///
+    /// Returns `None` if the frame is too short. Entries past 64 bits are
+    /// ignored.
+    pub fn decode(&self, data: &[u8]) -> Option<Vec<(&str, Value)>> {
+        if data.len() < self.len() {
+            return None;
+        }
+        let mut buf = [0u8; 8];
+        let n = data.len().min(8);
+        buf[..n].copy_from_slice(&data[..n]);
+        let raw = u64::from_le_bytes(buf);
+
+        let mut values = Vec::new();
+        let mut pos = 0;
+        for m in &self.mappings {
+            let bits = u32::from(m.bits);
+            if bits == 0 || pos + bits > MAX_BITS {
+                break;
+            }
+            let value = (raw >> pos) & mask(bits);
+            values.push((m.name.as_str(), m.data_type.decode_raw(value, bits)));
+            pos += bits;
+        }
+        Some(values)
+    }
+
+    /// Encodes the data of a PDO frame from the values of the entries.
+    ///
+    /// Entries with no value, or one that can't be converted to their data
+    /// type, are zero.
+    pub fn encode(&self, values: &BTreeMap<String, Value>) -> Vec<u8> {
+        let mut raw = 0u64;
+        let mut pos = 0;
+        for m in &self.mappings {
+            let bits = u32::from(m.bits);
+            if bits == 0 || pos + bits > MAX_BITS {
+                break;
+            }
+            let value = values
+                .get(&m.name)
+                .and_then(|value| m.data_type.encode_raw(value))
+                .unwrap_or(0);
+            raw |= (value & mask(bits)) << pos;
+            pos += bits;
+        }
+        raw.to_le_bytes()[..self.len()].to_vec()
+    }
+
+    /// Reads the configuration of a PDO from an EDS or DCF, from the index
+    /// of its communication parameters, like [`TPDO_COMM_BASE`].
+    ///
+    /// The entries are named as in [`Eds::name()`]. This returns `None` if
+    /// the PDO isn't in the file, or is disabled.
+    pub fn from_eds(eds: &Eds, comm_index: u16) -> Option<Self> {
+        let value = |index, sub| eds.variable(index, sub)?.value(eds.node_id)?.as_u64();
+
+        let cob_id = value(comm_index, 1)? as u32;
+        if cob_id & COB_ID_INVALID != 0 {
+            return None;
+        }
+        let mut pdo = Pdo::new(cob_id as u16);
+        if let Some(code) = value(comm_index, 2) {
+            pdo.transmission = Transmission::from(code as u8);
+        }
+        pdo.event_timer = value(comm_index, 5)
+            .filter(|&ms| ms != 0)
+            .map(Duration::from_millis);
+
+        let map_index = comm_index + MAP_OFFSET;
+        let count = value(map_index, 0)?;
+        for sub in 1..=count.min(64) as u8 {
+            let entry = value(map_index, sub)? as u32;
+            let (index, sub, bits) = ((entry >> 16) as u16, (entry >> 8) as u8, entry as u8);
+            let name = eds
+                .name(index, sub)
+                .unwrap_or_else(|| format!("0x{:04X}sub{}", index, sub));
+            let data_type = match eds.variable(index, sub) {
+                Some(var) => var.data_type,
+                None => DataType::from(index),
+            };
+            pdo = pdo.map_bits(&name, index, sub, bits, data_type);
+        }
+        Some(pdo)
+    }
+
+    /// Writes the configuration of the PDO to a node with SDO, to the
+    /// index of its communication parameters, like [`RPDO_COMM_BASE`].
+    ///
+    /// The PDO is disabled while the mapping is changed, and enabled
+    /// again at the end.
+    pub fn configure(&self, client: &SdoClient, comm_index: u16) -> Result<(), Error> {
+        let map_index = comm_index + MAP_OFFSET;
+        let cob_id = u32::from(self.cob_id);
+
+        client.download(comm_index, 1, &(cob_id | COB_ID_INVALID).to_le_bytes())?;
+        client.download(comm_index, 2, &[self.transmission.into()])?;
+        if let Some(period) = self.event_timer {
+            let ms = period.as_millis().min(u128::from(u16::MAX)) as u16;
+            client.download(comm_index, 5, &ms.to_le_bytes())?;
+        }
+        client.download(map_index, 0, &[0])?;
+        for (i, m) in self.mappings.iter().enumerate() {
+            client.download(map_index, i as u8 + 1, &m.entry().to_le_bytes())?;
+        }
+        client.download(map_index, 0, &[self.mappings.len() as u8])?;
+        client.download(comm_index, 1, &cob_id.to_le_bytes())
+    }
+}
+
+impl Eds {
+    /// Reads the configuration of a PDO, from the index of its
+    /// communication parameters.
+    ///
+    /// See [`Pdo::from_eds()`].
+    pub fn pdo(&self, comm_index: u16) -> Option<Pdo> {
+        Pdo::from_eds(self, comm_index)
+    }
+}
+
+/// The mask for the low bits of a value.
+fn mask(bits: u32) -> u64 {
+    match bits {
+        64.. => u64::MAX,
+        _ => (1 << bits) - 1,
+    }
+}
+
+// ===== ProcessImage =====
+
+/// A callback with the COB-ID of a PDO that was received
+pub type PdoCallback = Box<dyn FnMut(u16) + Send + 'static>;
+
+/// A PDO that is transmitted, with its schedule
+#[derive(Debug)]
+struct Tpdo {
+    pdo: Pdo,
+    /// The number of SYNCs since it was last sent
+    syncs: u8,
+    /// The next time the event timer expires
+    next_event: Option<Instant>,
+    /// Whether an acyclic PDO changed since the last SYNC
+    changed: bool,
+    /// Whether it needs to be sent
+    pending: bool,
+}
+
+/// The SYNC producer
+#[derive(Debug)]
+struct SyncProducer {
+    period: Duration,
+    next: Option<Instant>,
+}
+
+/// The values of the entries in the PDOs that are received and
+/// transmitted.
+///
+/// It's driven by [`PdoExchange`], or it can be driven with the received
+/// frames and the current time by other means.
+///
+/// A transmitted PDO with an event transmission type is sent when one of
+/// its values changes, and on its event timer. One with a SYNC
+/// transmission type is sent after the SYNC, every time for acyclic ones
+/// that have changed. PDOs sent on a remote request aren't sent.
+#[derive(Default)]
+pub struct ProcessImage {
+    values: BTreeMap<String, Value>,
+    rpdos: Vec<Pdo>,
+    tpdos: Vec<Tpdo>,
+    sync: Option<SyncProducer>,
+    on_receive: Option<PdoCallback>,
+}
+
+impl ProcessImage {
+    /// Creates an empty process image.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a PDO to receive.
+    pub fn receive(mut self, pdo: Pdo) -> Self {
+        self.add_values(&pdo);
+        self.rpdos.push(pdo);
+        self
+    }
+
+    /// Adds a PDO to transmit.
+    pub fn transmit(mut self, pdo: Pdo) -> Self {
+        self.add_values(&pdo);
+        self.tpdos.push(Tpdo {
+            pdo,
+            syncs: 0,
+            next_event: None,
+            changed: false,
+            pending: false,
+        });
+        self
+    }
+
+    /// Produces the SYNC, with the period.
+    pub fn sync_producer(mut self, period: Duration) -> Self {
+        self.sync = Some(SyncProducer { period, next: None });
+        self
+    }
+
+    /// Sets a callback for each PDO that's received, after its values are
+    /// updated.
+    pub fn on_receive<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(u16) + Send + 'static,
+    {
+        self.on_receive = Some(Box::new(f));
+        self
+    }
+
+    fn add_values(&mut self, pdo: &Pdo) {
+        for m in &pdo.mappings {
+            self.values
+                .entry(m.name.clone())
+                .or_insert_with(|| m.data_type.default_value());
+        }
+    }
+
+    /// Gets a value.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.values.get(name)
+    }
+
+    /// Gets all the values, by name.
+    pub fn values(&self) -> &BTreeMap<String, Value> {
+        &self.values
+    }
+
+    /// Sets a value to transmit.
+    ///
+    /// If it changed, the event-driven and acyclic PDOs with the value are
+    /// sent. Returns `false` if the value isn't in any PDO.
+    pub fn set(&mut self, name: &str, value: Value) -> bool {
+        let old = match self.values.get_mut(name) {
+            Some(old) => old,
+            None => return false,
+        };
+        if *old != value {
+            *old = value;
+            for tpdo in &mut self.tpdos {
+                if tpdo.pdo.mappings.iter().any(|m| m.name == name) {
+                    match tpdo.pdo.transmission {
+                        Transmission::Acyclic => tpdo.changed = true,
+                        t if t.is_event() => tpdo.pending = true,
+                        _ => {}
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// Handles a received frame.
+    ///
+    /// Returns `true` if it was a SYNC or a received PDO.
+    pub fn handle_frame(&mut self, frame: &CanFrame) -> bool {
+        let id = match cob_id(frame) {
+            Some(id) => id,
+            None => return false,
+        };
+        if id == SYNC_COB_ID {
+            self.on_sync();
+            return true;
+        }
+
+        let pdo = match self.rpdos.iter().find(|pdo| pdo.cob_id == id) {
+            Some(pdo) => pdo,
+            None => return false,
+        };
+        let values = match pdo.decode(frame.data()) {
+            Some(values) => values,
+            None => {
+                log::debug!("Short PDO 0x{:03X}", id);
+                return false;
+            }
+        };
+        for (name, value) in values {
+            self.values.insert(name.to_string(), value);
+        }
+        if let Some(f) = self.on_receive.as_mut() {
+            f(id);
+        }
+        true
+    }
+
+    /// Schedules the PDOs that are sent on SYNC.
+    fn on_sync(&mut self) {
+        for tpdo in &mut self.tpdos {
+            match tpdo.pdo.transmission {
+                Transmission::Sync(n) => {
+                    tpdo.syncs += 1;
+                    if tpdo.syncs >= n {
+                        tpdo.syncs = 0;
+                        tpdo.pending = true;
+                    }
+                }
+                Transmission::Acyclic if tpdo.changed => {
+                    tpdo.changed = false;
+                    tpdo.pending = true;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Checks the timers, returning the SYNC and PDO frames that need to
+    /// be sent.
+    pub fn poll(&mut self, now: Instant) -> Vec<CanFrame> {
+        let mut frames = Vec::new();
+        if let Some(sync) = self.sync.as_mut() {
+            let next = *sync.next.get_or_insert(now);
+            if now >= next {
+                frames.push(frame(SYNC_COB_ID, &[]));
+                sync.next = Some((next + sync.period).max(now));
+                self.on_sync();
+            }
+        }
+
+        for tpdo in &mut self.tpdos {
+            let timer = tpdo
+                .pdo
+                .event_timer
+                .filter(|_| tpdo.pdo.transmission.is_event());
+            if let Some(period) = timer {
+                if now >= *tpdo.next_event.get_or_insert(now + period) {
+                    tpdo.pending = true;
+                }
+            }
+            if tpdo.pending {
+                tpdo.pending = false;
+                let id = tpdo.pdo.cob_id & COB_ID_MASK;
+                frames.push(frame(id, &tpdo.pdo.encode(&self.values)));
+                tpdo.next_event = timer.map(|period| now + period);
+            }
+        }
+        frames
+    }
+
+    /// Gets the next time that [`poll()`](Self::poll) needs to be called.
+    pub fn deadline(&self) -> Option<Instant> {
+        if self.tpdos.iter().any(|tpdo| tpdo.pending) {
+            return Some(Instant::now());
+        }
+        let sync = self.sync.as_ref().and_then(|sync| sync.next);
+        let events = self.tpdos.iter().filter_map(|tpdo| tpdo.next_event);
+        sync.into_iter().chain(events).min()
+    }
+}
+
+impl fmt::Debug for ProcessImage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProcessImage")
+            .field("values", &self.values)
+            .field("rpdos", &self.rpdos)
+            .field("tpdos", &self.tpdos)
+            .field("sync", &self.sync)
+            .finish()
+    }
+}
+
+// ===== PdoExchange =====
+
+/// A blocking exchange of PDOs over a CAN socket.
+///
+/// The PDOs are only received and sent while
+/// [`process()`](Self::process) or [`run()`](Self::run) is being called.
+#[derive(Debug)]
+pub struct PdoExchange {
+    sock: CanSocket,
+    image: ProcessImage,
+}
+
+impl PdoExchange {
+    /// Opens a PDO exchange on the named CAN interface.
+    pub fn open(ifname: &str, image: ProcessImage) -> io::Result<Self> {
+        let sock = CanSocket::open(ifname)?;
+        Ok(Self::new(sock, image))
+    }
+
+    /// Creates a PDO exchange on an open CAN socket.
+    pub fn new(sock: CanSocket, image: ProcessImage) -> Self {
+        Self { sock, image }
+    }
+
+    /// Gets a reference to the underlying CAN socket.
+    pub fn socket(&self) -> &CanSocket {
+        &self.sock
+    }
+
+    /// Gets the process image.
+    pub fn image(&self) -> &ProcessImage {
+        &self.image
+    }
+
+    /// Gets the process image, to set the values to transmit.
+    pub fn image_mut(&mut self) -> &mut ProcessImage {
+        &mut self.image
+    }
+
+    /// Receives and sends the PDOs for a time.
+    pub fn process(&mut self, dur: Duration) -> Result<(), Error> {
+        let end = Instant::now() + dur;
+        loop {
+            let now = Instant::now();
+            for frame in self.image.poll(now) {
+                self.sock.write_frame_insist(&frame)?;
+            }
+            if now >= end {
+                return Ok(());
+            }
+
+            let wake = self.image.deadline().map_or(end, |t| t.min(end));
+            let timeout = wake
+                .saturating_duration_since(now)
+                .max(Duration::from_millis(1));
+            match self.sock.read_frame_timeout(timeout) {
+                Ok(frame) => {
+                    self.image.handle_frame(&frame);
+                }
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                    ) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// Receives and sends the PDOs until an error occurs.
+    pub fn run(&mut self) -> Result<(), Error> {
+        loop {
+            self.process(Duration::from_secs(3600))?;
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{canopen::eds::tests::DCF, EmbeddedFrame};
+
+    fn io_pdo() -> Pdo {
+        Pdo::new(0x185)
+            .map("Inputs", 0x6000, 1, DataType::Unsigned8)
+            .map("Flag", 0x6000, 2, DataType::Boolean)
+            .map("Analog", 0x6401, 1, DataType::Integer16)
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let pdo = io_pdo();
+        assert_eq!(pdo.len(), 4);
+
+        let mut values = BTreeMap::new();
+        values.insert("Inputs".to_string(), Value::Uint(0xA5));
+        values.insert("Flag".to_string(), Value::Bool(true));
+        values.insert("Analog".to_string(), Value::Int(-2));
+        let data = pdo.encode(&values);
+        assert_eq!(data, [0xA5, 0xFD, 0xFF, 0x01]);
+
+        let decoded = pdo.decode(&data).unwrap();
+        assert_eq!(decoded[0], ("Inputs", Value::Uint(0xA5)));
+        assert_eq!(decoded[1], ("Flag", Value::Bool(true)));
+        assert_eq!(decoded[2], ("Analog", Value::Int(-2)));
+        assert!(pdo.decode(&data[..3]).is_none());
+
+        let pdo = Pdo::new(0x201).map("Float", 0x2000, 0, DataType::Real32);
+        values.insert("Float".to_string(), Value::Real(1.5));
+        assert_eq!(pdo.encode(&values), 1.5f32.to_le_bytes());
+        assert_eq!(pdo.mappings[0].entry(), 0x2000_0020);
+    }
+
+    #[test]
+    fn test_from_eds() {
+        let eds = Eds::parse(DCF).unwrap();
+        let pdo = eds.pdo(TPDO_COMM_BASE).unwrap();
+        assert_eq!(pdo.cob_id, 0x185);
+        assert_eq!(pdo.transmission, Transmission::Sync(1));
+        assert_eq!(pdo.event_timer, Some(Duration::from_millis(100)));
+        assert_eq!(pdo.mappings.len(), 3);
+        assert_eq!(pdo.mappings[1].name, "Read input 8-bit.Read input 8-bit2");
+        assert_eq!(pdo.mappings[1].bits, 1);
+        assert_eq!(pdo.mappings[2].data_type, DataType::Integer16);
+        assert_eq!(pdo.len(), 4);
+        assert!(eds.pdo(RPDO_COMM_BASE).is_none());
+    }
+
+    #[test]
+    fn test_process_image() {
+        let now = Instant::now();
+        let ms = Duration::from_millis;
+
+        // Only the CAN ID bits of the COB-ID are used
+        let tpdo = Pdo::new(0xC205)
+            .map("Outputs", 0x6200, 1, DataType::Unsigned8)
+            .event_timer(ms(100));
+        assert_eq!(tpdo.cob_id, 0x205);
+        let sync_pdo = Pdo::new(0x305).transmission(Transmission::Sync(2)).map(
+            "Setpoint",
+            0x6411,
+            1,
+            DataType::Integer16,
+        );
+        let mut image = ProcessImage::new()
+            .receive(io_pdo())
+            .transmit(tpdo)
+            .transmit(sync_pdo)
+            .sync_producer(ms(10));
+        assert_eq!(image.get("Analog"), Some(&Value::Int(0)));
+
+        // The first SYNC
+        let frames = image.poll(now);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(cob_id(&frames[0]), Some(SYNC_COB_ID));
+        assert_eq!(image.deadline(), Some(now + ms(10)));
+
+        // A changed value is sent at once
+        assert!(image.set("Outputs", Value::Uint(3)));
+        assert!(!image.set("Other", Value::Uint(3)));
+        let frames = image.poll(now + ms(1));
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].data(), &[3]);
+        assert_eq!(image.deadline(), Some(now + ms(10)));
+
+        // Every second SYNC sends the synchronous PDO
+        image.set("Setpoint", Value::Int(-1));
+        let frames = image.poll(now + ms(10));
+        assert_eq!(frames.len(), 2);
+        assert_eq!(cob_id(&frames[1]), Some(0x305));
+        assert_eq!(frames[1].data(), &[0xFF, 0xFF]);
+        assert_eq!(image.poll(now + ms(20)).len(), 1);
+
+        // The event timer
+        let frames = image.poll(now + ms(101));
+        assert!(frames.iter().any(|frame| cob_id(frame) == Some(0x205)));
+
+        // A received PDO
+        let rpdo = frame(0x185, &[0x01, 0x01, 0x10, 0x00]);
+        assert!(image.handle_frame(&rpdo));
+        assert_eq!(image.get("Inputs"), Some(&Value::Uint(1)));
+        assert_eq!(image.get("Flag"), Some(&Value::Bool(true)));
+        assert_eq!(image.get("Analog"), Some(&Value::Int(0x1000 >> 1)));
+        assert!(!image.handle_frame(&frame(0x186, &[0; 4])));
+        assert!(!image.handle_frame(&frame(0x185, &[0; 2])));
+    }
+}
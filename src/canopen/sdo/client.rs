@@ -0,0 +1,462 @@
+// socketcan/src/canopen/sdo/client.rs
+//
+// A CANopen SDO client.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! An SDO client, to read and write the object dictionary of a node.
+
+use super::{
+    abort_code, abort_frame, data_u32, multiplexer, sdo_data, sdo_filter, sdo_frame, segment,
+    segment_data, AbortCode, BlockReceiver, BlockSender, DEFAULT_TIMEOUT, MAX_BLOCK_SIZE,
+    SDO_RX_BASE, SDO_TX_BASE,
+};
+use crate::{
+    canopen::{check_node, frame, Error},
+    CanSocket, Socket, SocketOptions,
+};
+use std::{
+    io, mem,
+    time::{Duration, Instant},
+};
+
+/// The frames to send and the result, after a step of a transfer.
+#[derive(Debug, Default)]
+pub(crate) struct Progress {
+    pub(crate) send: Vec<[u8; 8]>,
+    pub(crate) result: Option<Result<Vec<u8>, AbortCode>>,
+}
+
+impl Progress {
+    fn send(frames: Vec<[u8; 8]>) -> Self {
+        Self {
+            send: frames,
+            result: None,
+        }
+    }
+}
+
+/// The state of a transfer
+#[derive(Debug)]
+enum State {
+    UploadInit,
+    Upload {
+        toggle: bool,
+        size: Option<usize>,
+        data: Vec<u8>,
+    },
+    DownloadInit {
+        data: Vec<u8>,
+        expedited: bool,
+    },
+    Download {
+        toggle: bool,
+        data: Vec<u8>,
+        pos: usize,
+    },
+    BlockUploadInit {
+        block_size: u8,
+    },
+    BlockUpload {
+        crc: bool,
+        size: Option<usize>,
+        rx: BlockReceiver,
+    },
+    BlockUploadEnd {
+        crc: bool,
+        size: Option<usize>,
+        rx: BlockReceiver,
+    },
+    BlockDownloadInit {
+        data: Vec<u8>,
+    },
+    BlockDownload {
+        crc: bool,
+        tx: BlockSender,
+    },
+    BlockDownloadEnd,
+    Done,
+}
+
+/// The client side of a single SDO transfer.
+///
+/// It's driven with the frames from the server, and returns the frames to
+/// send back, until the transfer is done.
+#[derive(Debug)]
+pub(crate) struct Transfer {
+    index: u16,
+    sub: u8,
+    state: State,
+}
+
+impl Transfer {
+    /// Starts an upload (read) of an entry, returning the first frame.
+    pub(crate) fn upload(index: u16, sub: u8) -> (Self, [u8; 8]) {
+        let frame = sdo_frame(0x40, index, sub, &[]);
+        (Self::new(index, sub, State::UploadInit), frame)
+    }
+
+    /// Starts a download (write) of an entry, returning the first frame.
+    ///
+    /// Data of up to four bytes is sent with an expedited transfer.
+    pub(crate) fn download(index: u16, sub: u8, data: Vec<u8>) -> (Self, [u8; 8]) {
+        let expedited = (1..=4).contains(&data.len());
+        let frame = if expedited {
+            let unused = (4 - data.len()) as u8;
+            sdo_frame(0x23 | (unused << 2), index, sub, &data)
+        } else {
+            sdo_frame(0x21, index, sub, &(data.len() as u32).to_le_bytes())
+        };
+        let state = State::DownloadInit { data, expedited };
+        (Self::new(index, sub, state), frame)
+    }
+
+    /// Starts a block upload of an entry, returning the first frame.
+    pub(crate) fn block_upload(index: u16, sub: u8, block_size: u8) -> (Self, [u8; 8]) {
+        let block_size = block_size.clamp(1, MAX_BLOCK_SIZE);
+        let frame = sdo_frame(0xA4, index, sub, &[block_size, 0]);
+        let state = State::BlockUploadInit { block_size };
+        (Self::new(index, sub, state), frame)
+    }
+
+    /// Starts a block download of an entry, returning the first frame.
+    pub(crate) fn block_download(index: u16, sub: u8, data: Vec<u8>) -> (Self, [u8; 8]) {
+        let frame = sdo_frame(0xC6, index, sub, &(data.len() as u32).to_le_bytes());
+        (
+            Self::new(index, sub, State::BlockDownloadInit { data }),
+            frame,
+        )
+    }
+
+    fn new(index: u16, sub: u8, state: State) -> Self {
+        Self { index, sub, state }
+    }
+
+    /// Aborts the transfer, such as when the server doesn't answer.
+    pub(crate) fn abort(&mut self, code: AbortCode) -> Progress {
+        self.state = State::Done;
+        Progress {
+            send: vec![abort_frame(self.index, self.sub, code)],
+            result: Some(Err(code)),
+        }
+    }
+
+    fn done(&mut self, data: Vec<u8>, send: Vec<[u8; 8]>) -> Progress {
+        self.state = State::Done;
+        Progress {
+            send,
+            result: Some(Ok(data)),
+        }
+    }
+
+    /// Checks that a response is for the entry being transferred.
+    fn is_for(&self, frame: &[u8; 8]) -> bool {
+        multiplexer(frame) == (self.index, self.sub)
+    }
+
+    /// Handles a frame from the server.
+    pub(crate) fn handle(&mut self, frame: &[u8; 8]) -> Progress {
+        use State::*;
+
+        if let Some(code) = abort_code(frame) {
+            if !matches!(self.state, Done) {
+                self.state = Done;
+                return Progress {
+                    send: Vec::new(),
+                    result: Some(Err(code)),
+                };
+            }
+        }
+
+        let cmd = frame[0];
+        match mem::replace(&mut self.state, Done) {
+            UploadInit => {
+                if cmd & 0xE0 != 0x40 || !self.is_for(frame) {
+                    return self.abort(AbortCode::InvalidCommand);
+                }
+                if cmd & 0x02 != 0 {
+                    let len = if cmd & 0x01 != 0 {
+                        4 - usize::from((cmd >> 2) & 0x03)
+                    } else {
+                        4
+                    };
+                    self.done(frame[4..4 + len].to_vec(), Vec::new())
+                } else {
+                    let size = (cmd & 0x01 != 0).then(|| data_u32(frame) as usize);
+                    self.state = Upload {
+                        toggle: false,
+                        size,
+                        data: Vec::new(),
+                    };
+                    Progress::send(vec![[0x60, 0, 0, 0, 0, 0, 0, 0]])
+                }
+            }
+            Upload {
+                toggle,
+                size,
+                mut data,
+            } => {
+                if cmd & 0xE0 != 0x00 {
+                    return self.abort(AbortCode::InvalidCommand);
+                }
+                if (cmd & 0x10 != 0) != toggle {
+                    return self.abort(AbortCode::ToggleBit);
+                }
+                let (chunk, last) = segment_data(frame);
+                data.extend_from_slice(chunk);
+                if !last {
+                    self.state = Upload {
+                        toggle: !toggle,
+                        size,
+                        data,
+                    };
+                    let cmd = 0x60 | (u8::from(!toggle) << 4);
+                    Progress::send(vec![[cmd, 0, 0, 0, 0, 0, 0, 0]])
+                } else if size.map_or(false, |size| size != data.len()) {
+                    self.abort(AbortCode::LengthMismatch)
+                } else {
+                    self.done(data, Vec::new())
+                }
+            }
+            DownloadInit { data, expedited } => {
+                if cmd != 0x60 || !self.is_for(frame) {
+                    return self.abort(AbortCode::InvalidCommand);
+                }
+                if expedited {
+                    return self.done(Vec::new(), Vec::new());
+                }
+                let (seg, len) = segment(0x00, false, &data);
+                self.state = Download {
+                    toggle: false,
+                    data,
+                    pos: len,
+                };
+                Progress::send(vec![seg])
+            }
+            Download { toggle, data, pos } => {
+                if cmd & 0xE0 != 0x20 {
+                    return self.abort(AbortCode::InvalidCommand);
+                }
+                if (cmd & 0x10 != 0) != toggle {
+                    return self.abort(AbortCode::ToggleBit);
+                }
+                if pos >= data.len() {
+                    return self.done(Vec::new(), Vec::new());
+                }
+                let (seg, len) = segment(0x00, !toggle, &data[pos..]);
+                self.state = Download {
+                    toggle: !toggle,
+                    data,
+                    pos: pos + len,
+                };
+                Progress::send(vec![seg])
+            }
+            BlockUploadInit { block_size } => {
+                if cmd & 0xE1 != 0xC0 || !self.is_for(frame) {
+                    return self.abort(AbortCode::InvalidCommand);
+                }
+                self.state = BlockUpload {
+                    crc: cmd & 0x04 != 0,
+                    size: (cmd & 0x02 != 0).then(|| data_u32(frame) as usize),
+                    rx: BlockReceiver::new(block_size),
+                };
+                Progress::send(vec![[0xA3, 0, 0, 0, 0, 0, 0, 0]])
+            }
+            BlockUpload { crc, size, mut rx } => {
+                let ack = rx.segment(frame, 0xA0);
+                self.state = if rx.done {
+                    BlockUploadEnd { crc, size, rx }
+                } else {
+                    BlockUpload { crc, size, rx }
+                };
+                Progress::send(ack.into_iter().collect())
+            }
+            BlockUploadEnd { crc, size, rx } => {
+                if cmd & 0xE3 != 0xC1 {
+                    return self.abort(AbortCode::InvalidCommand);
+                }
+                match rx.finish(frame, crc) {
+                    Ok(data) if size.map_or(false, |size| size != data.len()) => {
+                        self.abort(AbortCode::LengthMismatch)
+                    }
+                    Ok(data) => self.done(data, vec![[0xA1, 0, 0, 0, 0, 0, 0, 0]]),
+                    Err(code) => self.abort(code),
+                }
+            }
+            BlockDownloadInit { data } => {
+                if cmd & 0xE3 != 0xA0 || !self.is_for(frame) {
+                    return self.abort(AbortCode::InvalidCommand);
+                }
+                let block_size = frame[4];
+                if !(1..=MAX_BLOCK_SIZE).contains(&block_size) {
+                    return self.abort(AbortCode::InvalidBlockSize);
+                }
+                let mut tx = BlockSender::new(data, block_size);
+                let frames = tx.block();
+                self.state = BlockDownload {
+                    crc: cmd & 0x04 != 0,
+                    tx,
+                };
+                Progress::send(frames)
+            }
+            BlockDownload { crc, mut tx } => {
+                if cmd != 0xA2 {
+                    return self.abort(AbortCode::InvalidCommand);
+                }
+                match tx.ack(frame[1], frame[2]) {
+                    Ok(true) => {
+                        let end = tx.end_frame(0xC0, crc);
+                        self.state = BlockDownloadEnd;
+                        Progress::send(vec![end])
+                    }
+                    Ok(false) => {
+                        let frames = tx.block();
+                        self.state = BlockDownload { crc, tx };
+                        Progress::send(frames)
+                    }
+                    Err(code) => self.abort(code),
+                }
+            }
+            BlockDownloadEnd => {
+                if cmd != 0xA1 {
+                    return self.abort(AbortCode::InvalidCommand);
+                }
+                self.done(Vec::new(), Vec::new())
+            }
+            Done => Progress::default(),
+        }
+    }
+}
+
+// ===== SdoClient =====
+
+/// A blocking SDO client for the server on a node.
+///
+/// Each transfer waits for the server to answer, and aborts it with
+/// [`AbortCode::Timeout`] if the server doesn't answer in time.
+#[derive(Debug)]
+pub struct SdoClient {
+    sock: CanSocket,
+    node: u8,
+    timeout: Duration,
+    block_size: u8,
+}
+
+impl SdoClient {
+    /// Opens a client for the node on the named CAN interface.
+    ///
+    /// The socket only receives the responses of the node.
+    pub fn open(ifname: &str, node: u8) -> Result<Self, Error> {
+        let node = check_node(node)?;
+        let sock = CanSocket::open(ifname)?;
+        sock.set_filters(&[sdo_filter(SDO_TX_BASE + u16::from(node))])?;
+        Self::new(sock, node)
+    }
+
+    /// Creates a client for the node on an open CAN socket.
+    pub fn new(sock: CanSocket, node: u8) -> Result<Self, Error> {
+        Ok(Self {
+            sock,
+            node: check_node(node)?,
+            timeout: DEFAULT_TIMEOUT,
+            block_size: MAX_BLOCK_SIZE,
+        })
+    }
+
+    /// Sets the time to wait for each response of the server.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the number of segments in each block of a block upload, from
+    /// 1 to 127.
+    pub fn block_size(mut self, block_size: u8) -> Self {
+        self.block_size = block_size.clamp(1, MAX_BLOCK_SIZE);
+        self
+    }
+
+    /// Gets the ID of the node.
+    pub fn node(&self) -> u8 {
+        self.node
+    }
+
+    /// Gets a reference to the underlying CAN socket.
+    pub fn socket(&self) -> &CanSocket {
+        &self.sock
+    }
+
+    /// Reads an entry, with an expedited or segmented transfer.
+    pub fn upload(&self, index: u16, sub: u8) -> Result<Vec<u8>, Error> {
+        self.run(Transfer::upload(index, sub))
+    }
+
+    /// Writes an entry, with an expedited or segmented transfer.
+    pub fn download(&self, index: u16, sub: u8, data: &[u8]) -> Result<(), Error> {
+        self.run(Transfer::download(index, sub, data.to_vec()))?;
+        Ok(())
+    }
+
+    /// Reads an entry with a block transfer.
+    pub fn block_upload(&self, index: u16, sub: u8) -> Result<Vec<u8>, Error> {
+        self.run(Transfer::block_upload(index, sub, self.block_size))
+    }
+
+    /// Writes an entry with a block transfer.
+    pub fn block_download(&self, index: u16, sub: u8, data: &[u8]) -> Result<(), Error> {
+        self.run(Transfer::block_download(index, sub, data.to_vec()))?;
+        Ok(())
+    }
+
+    fn write(&self, data: &[u8; 8]) -> Result<(), Error> {
+        let id = SDO_RX_BASE + u16::from(self.node);
+        self.sock.write_frame_insist(&frame(id, data))?;
+        Ok(())
+    }
+
+    /// Runs a transfer to the end.
+    fn run(&self, (mut transfer, first): (Transfer, [u8; 8])) -> Result<Vec<u8>, Error> {
+        let id = SDO_TX_BASE + u16::from(self.node);
+        self.write(&first)?;
+
+        let mut deadline = Instant::now() + self.timeout;
+        loop {
+            let now = Instant::now();
+            let progress = if now >= deadline {
+                transfer.abort(AbortCode::Timeout)
+            } else {
+                let timeout = (deadline - now).max(Duration::from_millis(1));
+                match self.sock.read_frame_timeout(timeout) {
+                    Ok(frame) => match sdo_data(&frame, id) {
+                        Some(data) => {
+                            deadline = Instant::now() + self.timeout;
+                            transfer.handle(&data)
+                        }
+                        None => continue,
+                    },
+                    Err(err)
+                        if matches!(
+                            err.kind(),
+                            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                        ) =>
+                    {
+                        continue
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+            };
+
+            for data in &progress.send {
+                self.write(data)?;
+            }
+            if let Some(res) = progress.result {
+                return res.map_err(Error::Aborted);
+            }
+        }
+    }
+}
@@ -0,0 +1,532 @@
+// socketcan/src/canopen/sdo/mod.rs
+//
+// CANopen service data objects (SDO).
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! CANopen service data objects (SDO), to read and write the object
+//! dictionary of a node.
+//!
+//! An SDO client reads (uploads) and writes (downloads) the entries in the
+//! object dictionary of the SDO server on a node, addressed by a 16-bit
+//! index and an 8-bit sub-index. Values of up to four bytes are sent in a
+//! single frame with an _expedited_ transfer. Longer ones are sent with a
+//! _segmented_ transfer, seven bytes at a time with each one confirmed, or
+//! with a faster _block_ transfer, with up to 127 segments sent at once
+//! and checked with a CRC.
+//!
+//! Either side can abort a transfer, with an [`AbortCode`] that's returned
+//! as [`Error::Aborted`](super::Error::Aborted).
+//!
+//! ```no_run
+//! use socketcan::canopen::SdoClient;
+//!
+//! let client = SdoClient::open("can0", 5).unwrap();
+//!
+//! // The device name
+//! let name = client.upload(0x1008, 0).unwrap();
+//! println!("{}", String::from_utf8_lossy(&name));
+//!
+//! // The producer heartbeat time, in ms
+//! client.download(0x1017, 0, &1000u16.to_le_bytes()).unwrap();
+//! ```
+
+use super::cob_id;
+use crate::{frame::CAN_EFF_FLAG, CanFilter, CanFrame, EmbeddedFrame};
+use std::{fmt, time::Duration};
+
+pub mod client;
+pub use client::SdoClient;
+
+pub mod server;
+pub use server::SdoServer;
+
+/// The function code of the SDO responses from a server to a client,
+/// which is added to the node ID.
+pub const SDO_TX_BASE: u16 = 0x580;
+
+/// The function code of the SDO requests from a client to a server, which
+/// is added to the node ID.
+pub const SDO_RX_BASE: u16 = 0x600;
+
+/// The default time to wait for the other side of a transfer.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The largest number of segments in a block.
+pub const MAX_BLOCK_SIZE: u8 = 127;
+
+/// The abort command byte
+const ABORT: u8 = 0x80;
+
+/// The data bytes in a segment
+const SEGMENT_LEN: usize = 7;
+
+// ===== AbortCode =====
+
+/// The reason that an SDO transfer was aborted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AbortCode {
+    /// Toggle bit not alternated
+    ToggleBit,
+    /// SDO protocol timed out
+    Timeout,
+    /// Client/server command specifier not valid or unknown
+    InvalidCommand,
+    /// Invalid block size
+    InvalidBlockSize,
+    /// Invalid sequence number
+    InvalidSequence,
+    /// CRC error
+    CrcError,
+    /// Out of memory
+    OutOfMemory,
+    /// Unsupported access to an object
+    UnsupportedAccess,
+    /// Attempt to read a write only object
+    WriteOnly,
+    /// Attempt to write a read only object
+    ReadOnly,
+    /// Object does not exist in the object dictionary
+    ObjectNotFound,
+    /// Object cannot be mapped to the PDO
+    NotMappable,
+    /// The number and length of the objects to be mapped would exceed the
+    /// PDO length
+    PdoLengthExceeded,
+    /// General parameter incompatibility
+    ParameterIncompatibility,
+    /// General internal incompatibility in the device
+    InternalIncompatibility,
+    /// Access failed due to a hardware error
+    HardwareError,
+    /// Data type does not match, length of service parameter does not
+    /// match
+    LengthMismatch,
+    /// Data type does not match, length of service parameter too high
+    LengthTooHigh,
+    /// Data type does not match, length of service parameter too low
+    LengthTooLow,
+    /// Sub-index does not exist
+    SubIndexNotFound,
+    /// Invalid value for parameter
+    InvalidValue,
+    /// Value of parameter written too high
+    ValueTooHigh,
+    /// Value of parameter written too low
+    ValueTooLow,
+    /// Maximum value is less than minimum value
+    MaxLessThanMin,
+    /// Resource not available: SDO connection
+    ResourceNotAvailable,
+    /// General error
+    GeneralError,
+    /// Data cannot be transferred or stored to the application
+    DataTransfer,
+    /// Data cannot be transferred or stored to the application because of
+    /// local control
+    LocalControl,
+    /// Data cannot be transferred or stored to the application because of
+    /// the present device state
+    DeviceState,
+    /// Object dictionary dynamic generation fails or no object dictionary
+    /// is present
+    NoDictionary,
+    /// No data available
+    NoData,
+    /// Another abort code
+    Other(u32),
+}
+
+impl From<u32> for AbortCode {
+    fn from(code: u32) -> Self {
+        use AbortCode::*;
+        match code {
+            0x0503_0000 => ToggleBit,
+            0x0504_0000 => Timeout,
+            0x0504_0001 => InvalidCommand,
+            0x0504_0002 => InvalidBlockSize,
+            0x0504_0003 => InvalidSequence,
+            0x0504_0004 => CrcError,
+            0x0504_0005 => OutOfMemory,
+            0x0601_0000 => UnsupportedAccess,
+            0x0601_0001 => WriteOnly,
+            0x0601_0002 => ReadOnly,
+            0x0602_0000 => ObjectNotFound,
+            0x0604_0041 => NotMappable,
+            0x0604_0042 => PdoLengthExceeded,
+            0x0604_0043 => ParameterIncompatibility,
+            0x0604_0047 => InternalIncompatibility,
+            0x0606_0000 => HardwareError,
+            0x0607_0010 => LengthMismatch,
+            0x0607_0012 => LengthTooHigh,
+            0x0607_0013 => LengthTooLow,
+            0x0609_0011 => SubIndexNotFound,
+            0x0609_0030 => InvalidValue,
+            0x0609_0031 => ValueTooHigh,
+            0x0609_0032 => ValueTooLow,
+            0x0609_0036 => MaxLessThanMin,
+            0x060A_0023 => ResourceNotAvailable,
+            0x0800_0000 => GeneralError,
+            0x0800_0020 => DataTransfer,
+            0x0800_0021 => LocalControl,
+            0x0800_0022 => DeviceState,
+            0x0800_0023 => NoDictionary,
+            0x0800_0024 => NoData,
+            code => Other(code),
+        }
+    }
+}
+
+impl From<AbortCode> for u32 {
+    fn from(code: AbortCode) -> Self {
+        use AbortCode::*;
+        match code {
+            ToggleBit => 0x0503_0000,
+            Timeout => 0x0504_0000,
+            InvalidCommand => 0x0504_0001,
+            InvalidBlockSize => 0x0504_0002,
+            InvalidSequence => 0x0504_0003,
+            CrcError => 0x0504_0004,
+            OutOfMemory => 0x0504_0005,
+            UnsupportedAccess => 0x0601_0000,
+            WriteOnly => 0x0601_0001,
+            ReadOnly => 0x0601_0002,
+            ObjectNotFound => 0x0602_0000,
+            NotMappable => 0x0604_0041,
+            PdoLengthExceeded => 0x0604_0042,
+            ParameterIncompatibility => 0x0604_0043,
+            InternalIncompatibility => 0x0604_0047,
+            HardwareError => 0x0606_0000,
+            LengthMismatch => 0x0607_0010,
+            LengthTooHigh => 0x0607_0012,
+            LengthTooLow => 0x0607_0013,
+            SubIndexNotFound => 0x0609_0011,
+            InvalidValue => 0x0609_0030,
+            ValueTooHigh => 0x0609_0031,
+            ValueTooLow => 0x0609_0032,
+            MaxLessThanMin => 0x0609_0036,
+            ResourceNotAvailable => 0x060A_0023,
+            GeneralError => 0x0800_0000,
+            DataTransfer => 0x0800_0020,
+            LocalControl => 0x0800_0021,
+            DeviceState => 0x0800_0022,
+            NoDictionary => 0x0800_0023,
+            NoData => 0x0800_0024,
+            Other(code) => code,
+        }
+    }
+}
+
+impl fmt::Display for AbortCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use AbortCode::*;
+        let msg = match *self {
+            ToggleBit => "toggle bit not alternated",
+            Timeout => "SDO protocol timed out",
+            InvalidCommand => "command specifier not valid or unknown",
+            InvalidBlockSize => "invalid block size",
+            InvalidSequence => "invalid sequence number",
+            CrcError => "CRC error",
+            OutOfMemory => "out of memory",
+            UnsupportedAccess => "unsupported access to an object",
+            WriteOnly => "attempt to read a write only object",
+            ReadOnly => "attempt to write a read only object",
+            ObjectNotFound => "object does not exist",
+            NotMappable => "object cannot be mapped to the PDO",
+            PdoLengthExceeded => "mapped objects would exceed the PDO length",
+            ParameterIncompatibility => "general parameter incompatibility",
+            InternalIncompatibility => "general internal incompatibility in the device",
+            HardwareError => "access failed due to a hardware error",
+            LengthMismatch => "length of service parameter does not match",
+            LengthTooHigh => "length of service parameter too high",
+            LengthTooLow => "length of service parameter too low",
+            SubIndexNotFound => "sub-index does not exist",
+            InvalidValue => "invalid value for parameter",
+            ValueTooHigh => "value of parameter written too high",
+            ValueTooLow => "value of parameter written too low",
+            MaxLessThanMin => "maximum value is less than minimum value",
+            ResourceNotAvailable => "resource not available: SDO connection",
+            GeneralError => "general error",
+            DataTransfer => "data cannot be transferred or stored to the application",
+            LocalControl => "data cannot be transferred because of local control",
+            DeviceState => "data cannot be transferred because of the device state",
+            NoDictionary => "no object dictionary present",
+            NoData => "no data available",
+            Other(code) => return write!(f, "abort code 0x{:08X}", code),
+        };
+        f.write_str(msg)
+    }
+}
+
+// ===== Protocol helpers =====
+
+/// The CRC of a block transfer (CRC-16-CCITT, with an initial value of
+/// zero).
+pub(crate) fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |crc, &b| {
+        (0..8).fold(crc ^ (u16::from(b) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Gets the data of an SDO frame with the COB-ID.
+///
+/// Short frames are padded with zeros.
+pub(crate) fn sdo_data(frame: &CanFrame, id: u16) -> Option<[u8; 8]> {
+    if cob_id(frame) != Some(id) {
+        return None;
+    }
+    let mut data = [0u8; 8];
+    let n = frame.data().len().min(8);
+    data[..n].copy_from_slice(&frame.data()[..n]);
+    Some(data)
+}
+
+/// A filter for the SDO frames with the COB-ID.
+pub(crate) fn sdo_filter(id: u16) -> CanFilter {
+    CanFilter::new(id.into(), 0x7FF | CAN_EFF_FLAG)
+}
+
+/// Builds an SDO frame with the command byte, index, and sub-index, with
+/// up to four bytes of data.
+pub(crate) fn sdo_frame(cmd: u8, index: u16, sub: u8, data: &[u8]) -> [u8; 8] {
+    let [i0, i1] = index.to_le_bytes();
+    let mut frame = [cmd, i0, i1, sub, 0, 0, 0, 0];
+    frame[4..4 + data.len()].copy_from_slice(data);
+    frame
+}
+
+/// Builds an abort frame.
+pub(crate) fn abort_frame(index: u16, sub: u8, code: AbortCode) -> [u8; 8] {
+    sdo_frame(ABORT, index, sub, &u32::from(code).to_le_bytes())
+}
+
+/// Gets the abort code from an abort frame.
+pub(crate) fn abort_code(frame: &[u8; 8]) -> Option<AbortCode> {
+    if frame[0] == ABORT {
+        let code = u32::from_le_bytes([frame[4], frame[5], frame[6], frame[7]]);
+        Some(code.into())
+    } else {
+        None
+    }
+}
+
+/// Gets the index and sub-index of a frame.
+pub(crate) fn multiplexer(frame: &[u8; 8]) -> (u16, u8) {
+    (u16::from_le_bytes([frame[1], frame[2]]), frame[3])
+}
+
+/// Gets the 32-bit value in the data bytes of a frame.
+pub(crate) fn data_u32(frame: &[u8; 8]) -> u32 {
+    u32::from_le_bytes([frame[4], frame[5], frame[6], frame[7]])
+}
+
+/// Builds a segment of a segmented transfer, returning it with the
+/// number of data bytes in it.
+pub(crate) fn segment(cmd: u8, toggle: bool, data: &[u8]) -> ([u8; 8], usize) {
+    let len = data.len().min(SEGMENT_LEN);
+    let last = len == data.len();
+    let mut frame = [0u8; 8];
+    frame[0] = cmd | (u8::from(toggle) << 4) | (((SEGMENT_LEN - len) as u8) << 1) | u8::from(last);
+    frame[1..1 + len].copy_from_slice(&data[..len]);
+    (frame, len)
+}
+
+/// The data in a segment of a segmented transfer, and whether it's the
+/// last one.
+pub(crate) fn segment_data(frame: &[u8; 8]) -> (&[u8], bool) {
+    let unused = usize::from((frame[0] >> 1) & 0x07);
+    (&frame[1..8 - unused], frame[0] & 0x01 != 0)
+}
+
+/// Sends the data of a block transfer, one block of segments at a time.
+#[derive(Debug)]
+pub(crate) struct BlockSender {
+    data: Vec<u8>,
+    /// The position of the first segment of the current block
+    pos: usize,
+    block_size: u8,
+    /// The number of segments in the current block
+    sent: u8,
+}
+
+impl BlockSender {
+    pub(crate) fn new(data: Vec<u8>, block_size: u8) -> Self {
+        Self {
+            data,
+            pos: 0,
+            block_size,
+            sent: 0,
+        }
+    }
+
+    /// Gets the segments of the next block.
+    pub(crate) fn block(&mut self) -> Vec<[u8; 8]> {
+        let mut frames = Vec::new();
+        let mut pos = self.pos;
+        for seq in 1..=self.block_size {
+            let len = (self.data.len() - pos).min(SEGMENT_LEN);
+            let last = pos + len == self.data.len();
+            let mut frame = [0u8; 8];
+            frame[0] = (u8::from(last) << 7) | seq;
+            frame[1..1 + len].copy_from_slice(&self.data[pos..pos + len]);
+            frames.push(frame);
+            pos += len;
+            if last {
+                break;
+            }
+        }
+        self.sent = frames.len() as u8;
+        frames
+    }
+
+    /// Handles the acknowledgement of a block.
+    ///
+    /// Returns `true` if all the data was received.
+    pub(crate) fn ack(&mut self, ack_seq: u8, block_size: u8) -> Result<bool, AbortCode> {
+        if ack_seq > self.sent {
+            return Err(AbortCode::InvalidSequence);
+        }
+        if !(1..=MAX_BLOCK_SIZE).contains(&block_size) {
+            return Err(AbortCode::InvalidBlockSize);
+        }
+        let end = self.pos + usize::from(ack_seq) * SEGMENT_LEN;
+        let done = end >= self.data.len() && ack_seq == self.sent;
+        self.pos = end.min(self.data.len());
+        self.block_size = block_size;
+        Ok(done)
+    }
+
+    /// Gets the frame that ends the transfer, with the command specifier.
+    pub(crate) fn end_frame(&self, cmd: u8, crc: bool) -> [u8; 8] {
+        let last_len = match self.data.len() {
+            0 => 0,
+            len => (len - 1) % SEGMENT_LEN + 1,
+        };
+        let unused = (SEGMENT_LEN - last_len) as u8;
+        let crc = if crc { crc16(&self.data) } else { 0 };
+        let [c0, c1] = crc.to_le_bytes();
+        [cmd | (unused << 2) | 0x01, c0, c1, 0, 0, 0, 0, 0]
+    }
+}
+
+/// Receives the data of a block transfer.
+#[derive(Debug)]
+pub(crate) struct BlockReceiver {
+    pub(crate) data: Vec<u8>,
+    pub(crate) block_size: u8,
+    /// The sequence number of the last segment received in order
+    seq: u8,
+    /// Whether the last segment of the transfer was received
+    pub(crate) done: bool,
+}
+
+impl BlockReceiver {
+    pub(crate) fn new(block_size: u8) -> Self {
+        Self {
+            data: Vec::new(),
+            block_size,
+            seq: 0,
+            done: false,
+        }
+    }
+
+    /// Handles a segment, returning the acknowledgement to send at the end
+    /// of a block.
+    ///
+    /// Segments that are out of order are dropped, and the sender repeats
+    /// them after the acknowledgement.
+    pub(crate) fn segment(&mut self, frame: &[u8; 8], cmd: u8) -> Option<[u8; 8]> {
+        let seq = frame[0] & 0x7F;
+        let last = frame[0] & 0x80 != 0;
+        if seq == self.seq + 1 && !self.done {
+            self.seq = seq;
+            self.data.extend_from_slice(&frame[1..]);
+            self.done = last;
+        }
+        if seq >= self.block_size || last {
+            let ack = [cmd | 0x02, self.seq, self.block_size, 0, 0, 0, 0, 0];
+            self.seq = 0;
+            Some(ack)
+        } else {
+            None
+        }
+    }
+
+    /// Handles the frame that ends the transfer, returning the data.
+    pub(crate) fn finish(mut self, frame: &[u8; 8], crc: bool) -> Result<Vec<u8>, AbortCode> {
+        let unused = usize::from((frame[0] >> 2) & 0x07);
+        let len = self.data.len().saturating_sub(unused);
+        self.data.truncate(len);
+        if crc && u16::from_le_bytes([frame[1], frame[2]]) != crc16(&self.data) {
+            return Err(AbortCode::CrcError);
+        }
+        Ok(self.data)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_abort_code() {
+        assert_eq!(AbortCode::from(0x0602_0000), AbortCode::ObjectNotFound);
+        assert_eq!(u32::from(AbortCode::SubIndexNotFound), 0x0609_0011);
+        assert_eq!(AbortCode::from(0x1234), AbortCode::Other(0x1234));
+        assert_eq!(u32::from(AbortCode::Other(0x1234)), 0x1234);
+
+        let frame = abort_frame(0x1017, 0, AbortCode::ReadOnly);
+        assert_eq!(frame, [0x80, 0x17, 0x10, 0x00, 0x02, 0x00, 0x01, 0x06]);
+        assert_eq!(abort_code(&frame), Some(AbortCode::ReadOnly));
+    }
+
+    #[test]
+    fn test_crc() {
+        assert_eq!(crc16(b"123456789"), 0x31C3);
+        assert_eq!(crc16(&[]), 0);
+    }
+
+    #[test]
+    fn test_blocks() {
+        let data: Vec<u8> = (0..30).collect();
+        let mut tx = BlockSender::new(data.clone(), 3);
+        let mut rx = BlockReceiver::new(3);
+
+        // A lost segment is repeated
+        let block = tx.block();
+        assert_eq!(block.len(), 3);
+        assert!(rx.segment(&block[0], 0xA0).is_none());
+        let ack = rx.segment(&block[2], 0xA0).unwrap();
+        assert_eq!(ack[..3], [0xA2, 1, 3]);
+        assert!(!tx.ack(ack[1], ack[2]).unwrap());
+
+        let mut acks = 0;
+        while !rx.done {
+            for frame in tx.block() {
+                if let Some(ack) = rx.segment(&frame, 0xA0) {
+                    acks += 1;
+                    let done = tx.ack(ack[1], ack[2]).unwrap();
+                    assert_eq!(done, rx.done);
+                }
+            }
+        }
+        assert_eq!(acks, 2);
+
+        let end = tx.end_frame(0xC0, true);
+        assert_eq!(end[0], 0xC1 | (5 << 2));
+        assert_eq!(rx.finish(&end, true).unwrap(), data);
+    }
+}
@@ -0,0 +1,632 @@
+// socketcan/src/canopen/sdo/server.rs
+//
+// A CANopen SDO server.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! An SDO server, to give clients access to the object dictionary of a
+//! node.
+
+use super::{
+    abort_code, abort_frame, data_u32, multiplexer, sdo_data, sdo_filter, sdo_frame, segment,
+    segment_data, AbortCode, BlockReceiver, BlockSender, DEFAULT_TIMEOUT, MAX_BLOCK_SIZE,
+    SDO_RX_BASE, SDO_TX_BASE,
+};
+use crate::{
+    canopen::{check_node, frame, od::ObjectDictionary, Error},
+    CanSocket, Socket, SocketOptions,
+};
+use std::{
+    io, mem,
+    time::{Duration, Instant},
+};
+
+/// The state of the transfer being served
+#[derive(Debug, Default)]
+enum State {
+    #[default]
+    Idle,
+    Download {
+        toggle: bool,
+        size: Option<usize>,
+        data: Vec<u8>,
+    },
+    Upload {
+        toggle: bool,
+        data: Vec<u8>,
+        pos: usize,
+    },
+    BlockDownload {
+        crc: bool,
+        size: Option<usize>,
+        rx: BlockReceiver,
+    },
+    BlockDownloadEnd {
+        crc: bool,
+        size: Option<usize>,
+        rx: BlockReceiver,
+    },
+    BlockUploadInit {
+        crc: bool,
+        tx: BlockSender,
+    },
+    BlockUpload {
+        crc: bool,
+        tx: BlockSender,
+    },
+    BlockUploadEnd,
+}
+
+/// The server side of the SDO transfers.
+///
+/// It's driven with the frames from the client, and returns the frames to
+/// send back. A server only runs one transfer at a time; a new request
+/// replaces the transfer in progress.
+#[derive(Debug)]
+pub(crate) struct Handler {
+    state: State,
+    index: u16,
+    sub: u8,
+    timeout: Duration,
+    block_size: u8,
+    deadline: Option<Instant>,
+}
+
+impl Default for Handler {
+    fn default() -> Self {
+        Self {
+            state: State::Idle,
+            index: 0,
+            sub: 0,
+            timeout: DEFAULT_TIMEOUT,
+            block_size: MAX_BLOCK_SIZE,
+            deadline: None,
+        }
+    }
+}
+
+impl Handler {
+    /// Sets the time to wait for the client during a transfer.
+    pub(crate) fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Sets the number of segments in each block of a block download.
+    pub(crate) fn set_block_size(&mut self, block_size: u8) {
+        self.block_size = block_size.clamp(1, MAX_BLOCK_SIZE);
+    }
+
+    /// Gets the time that the transfer in progress times out.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Aborts the transfer in progress if the client stopped answering.
+    pub(crate) fn poll(&mut self, now: Instant) -> Vec<[u8; 8]> {
+        match self.deadline {
+            Some(deadline) if now >= deadline => vec![self.abort(AbortCode::Timeout)],
+            _ => Vec::new(),
+        }
+    }
+
+    fn abort(&mut self, code: AbortCode) -> [u8; 8] {
+        self.state = State::Idle;
+        self.deadline = None;
+        abort_frame(self.index, self.sub, code)
+    }
+
+    /// Handles a frame from the client, returning the frames to send back.
+    pub(crate) fn handle<D>(&mut self, od: &mut D, frame: &[u8; 8], now: Instant) -> Vec<[u8; 8]>
+    where
+        D: ObjectDictionary + ?Sized,
+    {
+        if abort_code(frame).is_some() {
+            self.state = State::Idle;
+            self.deadline = None;
+            return Vec::new();
+        }
+        let res = self.step(od, frame);
+        self.deadline = match self.state {
+            State::Idle => None,
+            _ => Some(now + self.timeout),
+        };
+        match res {
+            Ok(frames) => frames,
+            Err(code) => vec![self.abort(code)],
+        }
+    }
+
+    fn step<D>(&mut self, od: &mut D, frame: &[u8; 8]) -> Result<Vec<[u8; 8]>, AbortCode>
+    where
+        D: ObjectDictionary + ?Sized,
+    {
+        use State::*;
+
+        let cmd = frame[0];
+        match mem::take(&mut self.state) {
+            BlockDownload { crc, size, mut rx } => {
+                let ack = rx.segment(frame, 0xA0);
+                self.state = if rx.done {
+                    BlockDownloadEnd { crc, size, rx }
+                } else {
+                    BlockDownload { crc, size, rx }
+                };
+                return Ok(ack.into_iter().collect());
+            }
+            BlockDownloadEnd { crc, size, rx } if cmd & 0xE3 == 0xC1 => {
+                let data = rx.finish(frame, crc)?;
+                if size.map_or(false, |size| size != data.len()) {
+                    return Err(AbortCode::LengthMismatch);
+                }
+                od.write(self.index, self.sub, &data)?;
+                return Ok(vec![[0xA1, 0, 0, 0, 0, 0, 0, 0]]);
+            }
+            Download {
+                toggle,
+                size,
+                mut data,
+            } if cmd & 0xE0 == 0x00 => {
+                if (cmd & 0x10 != 0) != toggle {
+                    return Err(AbortCode::ToggleBit);
+                }
+                let (chunk, last) = segment_data(frame);
+                data.extend_from_slice(chunk);
+                if last {
+                    if size.map_or(false, |size| size != data.len()) {
+                        return Err(AbortCode::LengthMismatch);
+                    }
+                    od.write(self.index, self.sub, &data)?;
+                } else {
+                    self.state = Download {
+                        toggle: !toggle,
+                        size,
+                        data,
+                    };
+                }
+                let cmd = 0x20 | (u8::from(toggle) << 4);
+                return Ok(vec![[cmd, 0, 0, 0, 0, 0, 0, 0]]);
+            }
+            Upload { toggle, data, pos } if cmd & 0xE0 == 0x60 => {
+                if (cmd & 0x10 != 0) != toggle {
+                    return Err(AbortCode::ToggleBit);
+                }
+                let (seg, len) = segment(0x00, toggle, &data[pos..]);
+                if pos + len < data.len() {
+                    self.state = Upload {
+                        toggle: !toggle,
+                        data,
+                        pos: pos + len,
+                    };
+                }
+                return Ok(vec![seg]);
+            }
+            BlockUploadInit { crc, mut tx } if cmd == 0xA3 => {
+                let frames = tx.block();
+                self.state = BlockUpload { crc, tx };
+                return Ok(frames);
+            }
+            BlockUpload { crc, mut tx } if cmd == 0xA2 => {
+                return if tx.ack(frame[1], frame[2])? {
+                    let end = tx.end_frame(0xC0, crc);
+                    self.state = BlockUploadEnd;
+                    Ok(vec![end])
+                } else {
+                    let frames = tx.block();
+                    self.state = BlockUpload { crc, tx };
+                    Ok(frames)
+                };
+            }
+            BlockUploadEnd if cmd == 0xA1 => return Ok(Vec::new()),
+            _ => {}
+        }
+
+        // Anything else has to start a new transfer
+        let (index, sub) = multiplexer(frame);
+        self.index = index;
+        self.sub = sub;
+
+        match cmd & 0xE0 {
+            // Initiate download
+            0x20 => {
+                let sized = cmd & 0x01 != 0;
+                if cmd & 0x02 != 0 {
+                    let len = if sized {
+                        4 - usize::from((cmd >> 2) & 0x03)
+                    } else {
+                        4
+                    };
+                    od.write(index, sub, &frame[4..4 + len])?;
+                } else {
+                    self.state = Download {
+                        toggle: false,
+                        size: sized.then(|| data_u32(frame) as usize),
+                        data: Vec::new(),
+                    };
+                }
+                Ok(vec![sdo_frame(0x60, index, sub, &[])])
+            }
+            // Initiate upload
+            0x40 => {
+                let data = od.read(index, sub)?;
+                if (1..=4).contains(&data.len()) {
+                    let unused = (4 - data.len()) as u8;
+                    Ok(vec![sdo_frame(0x43 | (unused << 2), index, sub, &data)])
+                } else {
+                    let size = (data.len() as u32).to_le_bytes();
+                    self.state = Upload {
+                        toggle: false,
+                        data,
+                        pos: 0,
+                    };
+                    Ok(vec![sdo_frame(0x41, index, sub, &size)])
+                }
+            }
+            // Initiate block upload
+            0xA0 if cmd & 0x03 == 0 => {
+                let block_size = frame[4];
+                if !(1..=MAX_BLOCK_SIZE).contains(&block_size) {
+                    return Err(AbortCode::InvalidBlockSize);
+                }
+                let data = od.read(index, sub)?;
+                let crc = cmd & 0x04 != 0;
+                let size = (data.len() as u32).to_le_bytes();
+                self.state = BlockUploadInit {
+                    crc,
+                    tx: BlockSender::new(data, block_size),
+                };
+                let cmd = 0xC2 | (u8::from(crc) << 2);
+                Ok(vec![sdo_frame(cmd, index, sub, &size)])
+            }
+            // Initiate block download
+            0xC0 if cmd & 0x01 == 0 => {
+                self.state = BlockDownload {
+                    crc: cmd & 0x04 != 0,
+                    size: (cmd & 0x02 != 0).then(|| data_u32(frame) as usize),
+                    rx: BlockReceiver::new(self.block_size),
+                };
+                Ok(vec![sdo_frame(0xA4, index, sub, &[self.block_size])])
+            }
+            _ => Err(AbortCode::InvalidCommand),
+        }
+    }
+}
+
+// ===== SdoServer =====
+
+/// A blocking SDO server for an object dictionary.
+///
+/// The server only answers while [`serve_one()`](Self::serve_one) or
+/// [`serve()`](Self::serve) is being called.
+#[derive(Debug)]
+pub struct SdoServer<D> {
+    sock: CanSocket,
+    node: u8,
+    od: D,
+    handler: Handler,
+}
+
+impl<D: ObjectDictionary> SdoServer<D> {
+    /// Opens a server for the node on the named CAN interface.
+    ///
+    /// The socket only receives the requests to the node.
+    pub fn open(ifname: &str, node: u8, od: D) -> Result<Self, Error> {
+        let node = check_node(node)?;
+        let sock = CanSocket::open(ifname)?;
+        sock.set_filters(&[sdo_filter(SDO_RX_BASE + u16::from(node))])?;
+        Self::new(sock, node, od)
+    }
+
+    /// Creates a server for the node on an open CAN socket.
+    pub fn new(sock: CanSocket, node: u8, od: D) -> Result<Self, Error> {
+        Ok(Self {
+            sock,
+            node: check_node(node)?,
+            od,
+            handler: Handler::default(),
+        })
+    }
+
+    /// Sets the time to wait for the client during a transfer.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.handler.set_timeout(timeout);
+        self
+    }
+
+    /// Sets the number of segments in each block of a block download, from
+    /// 1 to 127.
+    pub fn block_size(mut self, block_size: u8) -> Self {
+        self.handler.set_block_size(block_size);
+        self
+    }
+
+    /// Gets the ID of the node.
+    pub fn node(&self) -> u8 {
+        self.node
+    }
+
+    /// Gets a reference to the underlying CAN socket.
+    pub fn socket(&self) -> &CanSocket {
+        &self.sock
+    }
+
+    /// Gets a reference to the object dictionary.
+    pub fn dictionary(&self) -> &D {
+        &self.od
+    }
+
+    /// Gets a mutable reference to the object dictionary.
+    pub fn dictionary_mut(&mut self) -> &mut D {
+        &mut self.od
+    }
+
+    fn write(&self, data: &[u8; 8]) -> Result<(), Error> {
+        let id = SDO_TX_BASE + u16::from(self.node);
+        self.sock.write_frame_insist(&frame(id, data))?;
+        Ok(())
+    }
+
+    /// Waits for a request and answers it.
+    ///
+    /// Returns `false` if no request arrived within the timeout. With no
+    /// timeout, this waits until one does.
+    pub fn serve_one(&mut self, timeout: Option<Duration>) -> Result<bool, Error> {
+        let id = SDO_RX_BASE + u16::from(self.node);
+        let end = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let now = Instant::now();
+            for data in self.handler.poll(now) {
+                self.write(&data)?;
+            }
+            if end.map_or(false, |end| now >= end) {
+                return Ok(false);
+            }
+
+            let wake = [end, self.handler.deadline()].into_iter().flatten().min();
+            let res = match wake {
+                Some(wake) => {
+                    let timeout = wake
+                        .saturating_duration_since(now)
+                        .max(Duration::from_millis(1));
+                    self.sock.read_frame_timeout(timeout)
+                }
+                None => self.sock.read_frame(),
+            };
+            match res {
+                Ok(frame) => {
+                    if let Some(data) = sdo_data(&frame, id) {
+                        for data in self.handler.handle(&mut self.od, &data, Instant::now()) {
+                            self.write(&data)?;
+                        }
+                        return Ok(true);
+                    }
+                }
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                    ) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// Answers requests until an error occurs.
+    pub fn serve(&mut self) -> Result<(), Error> {
+        loop {
+            self.serve_one(None)?;
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canopen::{
+        od::{Access, Dictionary},
+        sdo::client::{Progress, Transfer},
+    };
+
+    fn dictionary() -> Dictionary {
+        Dictionary::new()
+            .entry(0x1008, 0, Access::Const, b"Motor controller X".to_vec())
+            .entry(0x1017, 0, Access::ReadWrite, vec![0xE8, 0x03])
+            .entry(0x2000, 1, Access::ReadWrite, Vec::new())
+            .entry(0x2001, 0, Access::WriteOnly, vec![0; 4])
+    }
+
+    /// Runs a transfer against a server, dropping the frames to the
+    /// server for which `drop` returns true.
+    fn run<F>(
+        server: &mut Handler,
+        od: &mut Dictionary,
+        (mut transfer, first): (Transfer, [u8; 8]),
+        mut drop: F,
+    ) -> Result<Vec<u8>, AbortCode>
+    where
+        F: FnMut(&[u8; 8]) -> bool,
+    {
+        let now = Instant::now();
+        let mut requests = vec![first];
+        for _ in 0..1000 {
+            let mut responses = Vec::new();
+            for req in requests.drain(..) {
+                if !drop(&req) {
+                    responses.extend(server.handle(od, &req, now));
+                }
+            }
+            for resp in responses {
+                let Progress { send, result } = transfer.handle(&resp);
+                requests.extend(send);
+                if let Some(res) = result {
+                    for req in requests.drain(..) {
+                        server.handle(od, &req, now);
+                    }
+                    return res;
+                }
+            }
+        }
+        panic!("Transfer didn't finish");
+    }
+
+    #[test]
+    fn test_expedited() {
+        let mut server = Handler::default();
+        let mut od = dictionary();
+
+        let (_, first) = Transfer::upload(0x1017, 0);
+        assert_eq!(first, [0x40, 0x17, 0x10, 0, 0, 0, 0, 0]);
+        let resp = server.handle(&mut od, &first, Instant::now());
+        assert_eq!(resp, vec![[0x4B, 0x17, 0x10, 0, 0xE8, 0x03, 0, 0]]);
+
+        let data = 500u16.to_le_bytes().to_vec();
+        let (_, first) = Transfer::download(0x1017, 0, data.clone());
+        assert_eq!(first, [0x2B, 0x17, 0x10, 0, 0xF4, 0x01, 0, 0]);
+
+        let res = run(
+            &mut server,
+            &mut od,
+            Transfer::download(0x1017, 0, data.clone()),
+            |_| false,
+        );
+        assert_eq!(res, Ok(vec![]));
+        assert_eq!(od.get(0x1017, 0), Some(&data[..]));
+
+        let res = run(&mut server, &mut od, Transfer::upload(0x1017, 0), |_| false);
+        assert_eq!(res, Ok(data));
+    }
+
+    #[test]
+    fn test_segmented() {
+        let mut server = Handler::default();
+        let mut od = dictionary();
+
+        let res = run(&mut server, &mut od, Transfer::upload(0x1008, 0), |_| false);
+        assert_eq!(res.unwrap(), b"Motor controller X");
+
+        for len in [0, 5, 7, 14, 15, 100] {
+            let data: Vec<u8> = (0..len).collect();
+            let res = run(
+                &mut server,
+                &mut od,
+                Transfer::download(0x2000, 1, data.clone()),
+                |_| false,
+            );
+            assert_eq!(res, Ok(vec![]));
+            assert_eq!(od.get(0x2000, 1), Some(&data[..]));
+
+            let res = run(&mut server, &mut od, Transfer::upload(0x2000, 1), |_| false);
+            assert_eq!(res, Ok(data));
+        }
+    }
+
+    #[test]
+    fn test_block() {
+        let mut server = Handler::default();
+        server.set_block_size(4);
+        let mut od = dictionary();
+
+        for len in [0, 1, 7, 28, 29, 300] {
+            let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let res = run(
+                &mut server,
+                &mut od,
+                Transfer::block_download(0x2000, 1, data.clone()),
+                |_| false,
+            );
+            assert_eq!(res, Ok(vec![]));
+            assert_eq!(od.get(0x2000, 1), Some(&data[..]));
+
+            let res = run(
+                &mut server,
+                &mut od,
+                Transfer::block_upload(0x2000, 1, 5),
+                |_| false,
+            );
+            assert_eq!(res, Ok(data));
+        }
+
+        // A lost segment is repeated
+        let data = vec![0x55; 100];
+        let mut dropped = false;
+        let res = run(
+            &mut server,
+            &mut od,
+            Transfer::block_download(0x2000, 1, data.clone()),
+            |frame| {
+                let lose = !dropped && frame[0] == 3;
+                dropped |= lose;
+                lose
+            },
+        );
+        assert!(dropped);
+        assert_eq!(res, Ok(vec![]));
+        assert_eq!(od.get(0x2000, 1), Some(&data[..]));
+
+        // A bad CRC aborts
+        let mut rx = BlockReceiver::new(4);
+        let mut tx = BlockSender::new(vec![1; 10], 4);
+        for seg in tx.block() {
+            rx.segment(&seg, 0xA0);
+        }
+        let mut end = tx.end_frame(0xC0, true);
+        end[1] ^= 0xFF;
+        assert_eq!(rx.finish(&end, true), Err(AbortCode::CrcError));
+    }
+
+    #[test]
+    fn test_aborts() {
+        let mut server = Handler::default();
+        let mut od = dictionary();
+
+        let res = run(&mut server, &mut od, Transfer::upload(0x3000, 0), |_| false);
+        assert_eq!(res, Err(AbortCode::ObjectNotFound));
+        let res = run(&mut server, &mut od, Transfer::upload(0x1017, 1), |_| false);
+        assert_eq!(res, Err(AbortCode::SubIndexNotFound));
+        let res = run(&mut server, &mut od, Transfer::upload(0x2001, 0), |_| false);
+        assert_eq!(res, Err(AbortCode::WriteOnly));
+        let res = run(
+            &mut server,
+            &mut od,
+            Transfer::download(0x1008, 0, vec![1; 20]),
+            |_| false,
+        );
+        assert_eq!(res, Err(AbortCode::ReadOnly));
+        let res = run(
+            &mut server,
+            &mut od,
+            Transfer::block_download(0x1008, 0, vec![1; 20]),
+            |_| false,
+        );
+        assert_eq!(res, Err(AbortCode::ReadOnly));
+
+        // A toggle error
+        let now = Instant::now();
+        let (_, first) = Transfer::upload(0x1008, 0);
+        server.handle(&mut od, &first, now);
+        let resp = server.handle(&mut od, &[0x70, 0, 0, 0, 0, 0, 0, 0], now);
+        assert_eq!(abort_code(&resp[0]), Some(AbortCode::ToggleBit));
+
+        // The server times out
+        server.handle(&mut od, &first, now);
+        assert!(server.poll(now).is_empty());
+        let resp = server.poll(now + DEFAULT_TIMEOUT);
+        assert_eq!(resp, vec![abort_frame(0x1008, 0, AbortCode::Timeout)]);
+        assert!(server.deadline().is_none());
+
+        // The client aborts
+        let (mut transfer, _) = Transfer::upload(0x1008, 0);
+        let progress = transfer.abort(AbortCode::Timeout);
+        assert_eq!(
+            progress.send,
+            vec![abort_frame(0x1008, 0, AbortCode::Timeout)]
+        );
+        assert_eq!(progress.result, Some(Err(AbortCode::Timeout)));
+    }
+}
@@ -0,0 +1,393 @@
+// socketcan/src/canopen/tokio.rs
+//
+// Asynchronous CANopen NMT master and SDO client and server for tokio.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! Asynchronous CANopen NMT master and SDO client and server for tokio.
+//!
+//! These are the same as the blocking [`NmtMaster`](super::NmtMaster),
+//! [`SdoClient`](super::SdoClient), and [`SdoServer`](super::SdoServer),
+//! over the tokio [`CanSocket`]:
+//!
+//! ```no_run
+//! use socketcan::canopen::{tokio::NmtMaster, Monitor};
+//! use std::time::Duration;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let monitor = Monitor::new().on_boot_up(|node| println!("Node {} booted", node));
+//!     let mut master = NmtMaster::open("vcan0", monitor)?;
+//!
+//!     master.reset_node(0).await?;
+//!     master.process(Duration::from_secs(5)).await?;
+//!     println!("{:?}", master.monitor().states());
+//!     Ok(())
+//! }
+//! ```
+
+use super::{
+    check_node, frame,
+    nmt::{command_frame, error_control_filter},
+    sdo::{
+        client::Transfer, sdo_data, sdo_filter, server::Handler, DEFAULT_TIMEOUT, MAX_BLOCK_SIZE,
+        SDO_RX_BASE, SDO_TX_BASE,
+    },
+    AbortCode, Error, Monitor, NmtCommand, NodeState, ObjectDictionary,
+};
+use crate::{tokio::CanSocket, CanFrame, SocketOptions};
+use futures::StreamExt;
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+/// Converts an error from the tokio CAN sockets.
+pub(crate) fn io_error(err: crate::Error) -> Error {
+    match err {
+        crate::Error::Io(err) => Error::Io(err),
+        crate::Error::Can(err) => Error::Io(io::Error::new(io::ErrorKind::Other, err)),
+    }
+}
+
+/// Sends a frame on a tokio CAN socket.
+pub(crate) async fn write_frame(sock: &CanSocket, frame: CanFrame) -> Result<(), Error> {
+    sock.write_frame(frame).map_err(io_error)?.await?;
+    Ok(())
+}
+
+/// An asynchronous NMT master over a tokio CAN socket.
+///
+/// The monitor only runs while [`process()`](Self::process) or
+/// [`run()`](Self::run) is being awaited.
+#[derive(Debug)]
+pub struct NmtMaster {
+    sock: CanSocket,
+    monitor: Monitor,
+}
+
+impl NmtMaster {
+    /// Opens an NMT master on the named CAN interface.
+    ///
+    /// The socket only receives the NMT error control messages.
+    pub fn open(ifname: &str, monitor: Monitor) -> io::Result<Self> {
+        let sock = CanSocket::open(ifname)?;
+        sock.set_filters(&[error_control_filter()])?;
+        Ok(Self::new(sock, monitor))
+    }
+
+    /// Creates an NMT master on an open CAN socket.
+    pub fn new(sock: CanSocket, monitor: Monitor) -> Self {
+        Self { sock, monitor }
+    }
+
+    /// Gets a reference to the underlying CAN socket.
+    pub fn socket(&self) -> &CanSocket {
+        &self.sock
+    }
+
+    /// Gets the monitor of the node states.
+    pub fn monitor(&self) -> &Monitor {
+        &self.monitor
+    }
+
+    /// Gets the last known state of a node.
+    pub fn state(&self, node: u8) -> Option<NodeState> {
+        self.monitor.state(node)
+    }
+
+    /// Sends an NMT command to a node, or to
+    /// [`ALL_NODES`](super::ALL_NODES).
+    pub async fn send_command(&self, cmd: NmtCommand, node: u8) -> Result<(), Error> {
+        write_frame(&self.sock, command_frame(cmd, node)?).await
+    }
+
+    /// Puts a node into the operational state.
+    pub async fn start_node(&self, node: u8) -> Result<(), Error> {
+        self.send_command(NmtCommand::Start, node).await
+    }
+
+    /// Puts a node into the stopped state.
+    pub async fn stop_node(&self, node: u8) -> Result<(), Error> {
+        self.send_command(NmtCommand::Stop, node).await
+    }
+
+    /// Puts a node into the pre-operational state.
+    pub async fn enter_pre_operational(&self, node: u8) -> Result<(), Error> {
+        self.send_command(NmtCommand::EnterPreOperational, node)
+            .await
+    }
+
+    /// Resets a node.
+    pub async fn reset_node(&self, node: u8) -> Result<(), Error> {
+        self.send_command(NmtCommand::ResetNode, node).await
+    }
+
+    /// Resets the communication of a node.
+    pub async fn reset_communication(&self, node: u8) -> Result<(), Error> {
+        self.send_command(NmtCommand::ResetCommunication, node)
+            .await
+    }
+
+    /// Runs the monitor for a time.
+    pub async fn process(&mut self, dur: Duration) -> Result<(), Error> {
+        let end = Instant::now() + dur;
+        loop {
+            let now = Instant::now();
+            for frame in self.monitor.poll(now) {
+                write_frame(&self.sock, frame).await?;
+            }
+            if now >= end {
+                return Ok(());
+            }
+
+            let wake = self.monitor.deadline().map_or(end, |t| t.min(end));
+            match tokio::time::timeout_at(wake.into(), self.sock.next()).await {
+                Ok(Some(res)) => {
+                    let frame = res.map_err(io_error)?;
+                    self.monitor.handle_frame(&frame, Instant::now());
+                }
+                Ok(None) => return Err(Error::Io(io::ErrorKind::UnexpectedEof.into())),
+                Err(_) => {}
+            }
+        }
+    }
+
+    /// Runs the monitor until an error occurs.
+    pub async fn run(&mut self) -> Result<(), Error> {
+        loop {
+            self.process(Duration::from_secs(3600)).await?;
+        }
+    }
+}
+
+// ===== SdoClient =====
+
+/// An asynchronous SDO client for the server on a node.
+#[derive(Debug)]
+pub struct SdoClient {
+    sock: CanSocket,
+    node: u8,
+    timeout: Duration,
+    block_size: u8,
+}
+
+impl SdoClient {
+    /// Opens a client for the node on the named CAN interface.
+    ///
+    /// The socket only receives the responses of the node.
+    pub fn open(ifname: &str, node: u8) -> Result<Self, Error> {
+        let node = check_node(node)?;
+        let sock = CanSocket::open(ifname)?;
+        sock.set_filters(&[sdo_filter(SDO_TX_BASE + u16::from(node))])?;
+        Self::new(sock, node)
+    }
+
+    /// Creates a client for the node on an open CAN socket.
+    pub fn new(sock: CanSocket, node: u8) -> Result<Self, Error> {
+        Ok(Self {
+            sock,
+            node: check_node(node)?,
+            timeout: DEFAULT_TIMEOUT,
+            block_size: MAX_BLOCK_SIZE,
+        })
+    }
+
+    /// Sets the time to wait for each response of the server.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the number of segments in each block of a block upload, from
+    /// 1 to 127.
+    pub fn block_size(mut self, block_size: u8) -> Self {
+        self.block_size = block_size.clamp(1, MAX_BLOCK_SIZE);
+        self
+    }
+
+    /// Gets the ID of the node.
+    pub fn node(&self) -> u8 {
+        self.node
+    }
+
+    /// Gets a reference to the underlying CAN socket.
+    pub fn socket(&self) -> &CanSocket {
+        &self.sock
+    }
+
+    /// Reads an entry, with an expedited or segmented transfer.
+    pub async fn upload(&mut self, index: u16, sub: u8) -> Result<Vec<u8>, Error> {
+        self.run(Transfer::upload(index, sub)).await
+    }
+
+    /// Writes an entry, with an expedited or segmented transfer.
+    pub async fn download(&mut self, index: u16, sub: u8, data: &[u8]) -> Result<(), Error> {
+        self.run(Transfer::download(index, sub, data.to_vec()))
+            .await?;
+        Ok(())
+    }
+
+    /// Reads an entry with a block transfer.
+    pub async fn block_upload(&mut self, index: u16, sub: u8) -> Result<Vec<u8>, Error> {
+        self.run(Transfer::block_upload(index, sub, self.block_size))
+            .await
+    }
+
+    /// Writes an entry with a block transfer.
+    pub async fn block_download(&mut self, index: u16, sub: u8, data: &[u8]) -> Result<(), Error> {
+        self.run(Transfer::block_download(index, sub, data.to_vec()))
+            .await?;
+        Ok(())
+    }
+
+    async fn write(&self, data: &[u8; 8]) -> Result<(), Error> {
+        write_frame(&self.sock, frame(SDO_RX_BASE + u16::from(self.node), data)).await
+    }
+
+    /// Runs a transfer to the end.
+    async fn run(&mut self, (mut transfer, first): (Transfer, [u8; 8])) -> Result<Vec<u8>, Error> {
+        let id = SDO_TX_BASE + u16::from(self.node);
+        self.write(&first).await?;
+
+        let mut deadline = Instant::now() + self.timeout;
+        loop {
+            let progress = match tokio::time::timeout_at(deadline.into(), self.sock.next()).await {
+                Ok(Some(res)) => match sdo_data(&res.map_err(io_error)?, id) {
+                    Some(data) => {
+                        deadline = Instant::now() + self.timeout;
+                        transfer.handle(&data)
+                    }
+                    None => continue,
+                },
+                Ok(None) => return Err(Error::Io(io::ErrorKind::UnexpectedEof.into())),
+                Err(_) => transfer.abort(AbortCode::Timeout),
+            };
+
+            for data in &progress.send {
+                self.write(data).await?;
+            }
+            if let Some(res) = progress.result {
+                return res.map_err(Error::Aborted);
+            }
+        }
+    }
+}
+
+// ===== SdoServer =====
+
+/// An asynchronous SDO server for an object dictionary.
+#[derive(Debug)]
+pub struct SdoServer<D> {
+    sock: CanSocket,
+    node: u8,
+    od: D,
+    handler: Handler,
+}
+
+impl<D: ObjectDictionary> SdoServer<D> {
+    /// Opens a server for the node on the named CAN interface.
+    ///
+    /// The socket only receives the requests to the node.
+    pub fn open(ifname: &str, node: u8, od: D) -> Result<Self, Error> {
+        let node = check_node(node)?;
+        let sock = CanSocket::open(ifname)?;
+        sock.set_filters(&[sdo_filter(SDO_RX_BASE + u16::from(node))])?;
+        Self::new(sock, node, od)
+    }
+
+    /// Creates a server for the node on an open CAN socket.
+    pub fn new(sock: CanSocket, node: u8, od: D) -> Result<Self, Error> {
+        Ok(Self {
+            sock,
+            node: check_node(node)?,
+            od,
+            handler: Handler::default(),
+        })
+    }
+
+    /// Sets the time to wait for the client during a transfer.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.handler.set_timeout(timeout);
+        self
+    }
+
+    /// Sets the number of segments in each block of a block download, from
+    /// 1 to 127.
+    pub fn block_size(mut self, block_size: u8) -> Self {
+        self.handler.set_block_size(block_size);
+        self
+    }
+
+    /// Gets the ID of the node.
+    pub fn node(&self) -> u8 {
+        self.node
+    }
+
+    /// Gets a reference to the underlying CAN socket.
+    pub fn socket(&self) -> &CanSocket {
+        &self.sock
+    }
+
+    /// Gets a reference to the object dictionary.
+    pub fn dictionary(&self) -> &D {
+        &self.od
+    }
+
+    /// Gets a mutable reference to the object dictionary.
+    pub fn dictionary_mut(&mut self) -> &mut D {
+        &mut self.od
+    }
+
+    async fn write(&self, data: &[u8; 8]) -> Result<(), Error> {
+        write_frame(&self.sock, frame(SDO_TX_BASE + u16::from(self.node), data)).await
+    }
+
+    /// Waits for a request and answers it.
+    ///
+    /// Returns `false` if no request arrived within the timeout. With no
+    /// timeout, this waits until one does.
+    pub async fn serve_one(&mut self, timeout: Option<Duration>) -> Result<bool, Error> {
+        let id = SDO_RX_BASE + u16::from(self.node);
+        let end = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let now = Instant::now();
+            for data in self.handler.poll(now) {
+                self.write(&data).await?;
+            }
+            if end.map_or(false, |end| now >= end) {
+                return Ok(false);
+            }
+
+            let res = match [end, self.handler.deadline()].into_iter().flatten().min() {
+                Some(wake) => match tokio::time::timeout_at(wake.into(), self.sock.next()).await {
+                    Ok(res) => res,
+                    Err(_) => continue,
+                },
+                None => self.sock.next().await,
+            };
+            let frame = match res {
+                Some(res) => res.map_err(io_error)?,
+                None => return Err(Error::Io(io::ErrorKind::UnexpectedEof.into())),
+            };
+            if let Some(data) = sdo_data(&frame, id) {
+                for data in self.handler.handle(&mut self.od, &data, Instant::now()) {
+                    self.write(&data).await?;
+                }
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Answers requests until an error occurs.
+    pub async fn serve(&mut self) -> Result<(), Error> {
+        loop {
+            self.serve_one(None).await?;
+        }
+    }
+}
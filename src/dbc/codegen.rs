@@ -0,0 +1,786 @@
+// socketcan/src/dbc/codegen.rs
+//
+// Generates Rust types for the messages in a DBC database.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! Code generation for the messages in a DBC database.
+//!
+//! This creates Rust source with a type for each message in a database,
+//! so that applications can work with named, typed signal accessors rather
+//! than bit offsets. It's meant to be run from a build script, with this
+//! crate as a build dependency:
+//!
+//! ```no_run
+//! // build.rs
+//! use socketcan::dbc::Codegen;
+//! use std::{env, path::Path};
+//!
+//! fn main() {
+//!     let out = Path::new(&env::var("OUT_DIR").unwrap()).join("vehicle.rs");
+//!     Codegen::new().generate_file("vehicle.dbc", out).unwrap();
+//!     println!("cargo:rerun-if-changed=vehicle.dbc");
+//! }
+//! ```
+//!
+//! and then included into a module of the application:
+//!
+//! ```ignore
+//! mod vehicle {
+//!     include!(concat!(env!("OUT_DIR"), "/vehicle.rs"));
+//! }
+//! ```
+//!
+//! Each message type holds the payload of the message, and has:
+//!
+//! - Constants for the `ID`, `NAME`, and `SIZE` of the message, and a
+//!   [`SignalSpec`](crate::SignalSpec) constant for each signal.
+//! - A getter and a `set_` method for each signal. Signals that are
+//!   integers with no scaling use the smallest Rust integer type that
+//!   holds them; scaled signals use `f64`. Setters check the value
+//!   against the range of the signal, returning
+//!   [`MessageError::OutOfRange`](super::MessageError::OutOfRange).
+//!   Multiplexed signals return `None` when they're not present, and
+//!   their setters also set the multiplexor switch.
+//! - Constants for the value descriptions of integer signals.
+//! - `TryFrom` the frame types, checking the ID and payload size, and
+//!   `From` the message to `CanFdFrame`, and to `CanFrame` for messages
+//!   of up to 8 bytes.
+
+use super::{Database, Message, Multiplex, ParseError, Signal, ValueType};
+use libc::{CAN_EFF_FLAG, CAN_EFF_MASK};
+use std::{collections::BTreeSet, fmt::Write, fs, path::Path};
+
+/// Rust keywords that can't be used as identifiers.
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+    "where", "while", "abstract", "become", "box", "do", "final", "macro", "override", "priv",
+    "try", "typeof", "unsized", "virtual", "yield",
+];
+
+/// The names of the methods and constants of the message types, which
+/// signals can't use.
+const RESERVED: &[&str] = &[
+    "id",
+    "name",
+    "size",
+    "data",
+    "from_data",
+    "default",
+    "clone",
+    "eq",
+    "fmt",
+    "try_from",
+    "from",
+    "into",
+];
+
+/// Generates Rust source for the messages in a DBC database.
+#[derive(Debug, Clone)]
+pub struct Codegen {
+    /// The path to this crate in the generated code
+    crate_path: String,
+}
+
+impl Default for Codegen {
+    fn default() -> Self {
+        Self {
+            crate_path: "::socketcan".into(),
+        }
+    }
+}
+
+impl Codegen {
+    /// Creates a code generator with the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the path used to refer to this crate in the generated code.
+    ///
+    /// The default is `::socketcan`. This is needed if the crate is
+    /// renamed in the manifest, or re-exported by another crate.
+    pub fn crate_path(mut self, path: &str) -> Self {
+        self.crate_path = path.into();
+        self
+    }
+
+    /// Reads a DBC file and writes the generated source to a file.
+    pub fn generate_file<P, Q>(&self, dbc: P, out: Q) -> Result<(), ParseError>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let db = Database::from_file(&dbc)?;
+        let header = format!(
+            "// Generated from '{}' by socketcan::dbc::Codegen. Do not edit.\n\n",
+            dbc.as_ref()
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+        );
+        fs::write(out, header + &self.generate(&db)?)?;
+        Ok(())
+    }
+
+    /// Generates the source for the messages in the database.
+    ///
+    /// A signal that doesn't fit in the payload of its message gives a
+    /// [`ParseError::SignalOutOfBounds`] error.
+    pub fn generate(&self, db: &Database) -> Result<String, ParseError> {
+        let mut out = String::new();
+        for msg in db.messages.values() {
+            let size = msg.size.min(64) as usize;
+            if let Some(sig) = msg.signals.iter().find(|sig| !sig.spec().fits(size)) {
+                return Err(ParseError::SignalOutOfBounds {
+                    message: msg.name.clone(),
+                    signal: sig.name.clone(),
+                });
+            }
+            // Writing to a String can't fail
+            self.message(&mut out, msg).unwrap();
+        }
+        Ok(out)
+    }
+
+    /// Writes the type for a message.
+    fn message(&self, out: &mut String, msg: &Message) -> std::fmt::Result {
+        let krate = &self.crate_path;
+        let ty = camel_case(&msg.name);
+        let size = msg.size.min(64);
+        let raw_id = msg.id & CAN_EFF_MASK;
+        let id_type = if msg.id & CAN_EFF_FLAG != 0 {
+            "ExtendedId"
+        } else {
+            "StandardId"
+        };
+
+        let sigs: Vec<_> = msg
+            .signals
+            .iter()
+            .map(|sig| SignalGen::new(msg, sig))
+            .collect();
+
+        writeln!(out, "/// {}, with ID 0x{:X}", msg.name, raw_id)?;
+        write_comment(out, "", msg.comment.as_deref())?;
+        // Default is only derived for arrays of up to 32 elements
+        if size <= 32 {
+            writeln!(out, "#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]")?;
+        } else {
+            writeln!(out, "#[derive(Debug, Clone, Copy, PartialEq, Eq)]")?;
+        }
+        writeln!(out, "pub struct {} {{", ty)?;
+        writeln!(out, "    data: [u8; {}],", size)?;
+        writeln!(out, "}}\n")?;
+
+        writeln!(out, "impl {} {{", ty)?;
+        writeln!(
+            out,
+            "    /// The ID word, with the `CAN_EFF_FLAG` bit set for an extended ID"
+        )?;
+        writeln!(out, "    pub const ID: u32 = 0x{:X};", msg.id)?;
+        writeln!(out, "    /// The name of the message")?;
+        writeln!(out, "    pub const NAME: &'static str = {:?};", msg.name)?;
+        writeln!(out, "    /// The size of the payload, in bytes")?;
+        writeln!(out, "    pub const SIZE: usize = {};", size)?;
+        for sig in &sigs {
+            sig.constants(out, krate)?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "    /// Gets the CAN ID of the message.")?;
+        writeln!(out, "    pub fn id() -> {}::Id {{", krate)?;
+        writeln!(
+            out,
+            "        {}::{}::new(0x{:X}).unwrap().into()",
+            krate, id_type, raw_id
+        )?;
+        writeln!(out, "    }}\n")?;
+        writeln!(out, "    /// Creates a message from a data payload.")?;
+        writeln!(out, "    ///")?;
+        writeln!(
+            out,
+            "    /// Any bytes past the size of the message are ignored."
+        )?;
+        writeln!(
+            out,
+            "    pub fn from_data(data: &[u8]) -> Result<Self, {}::dbc::MessageError> {{",
+            krate
+        )?;
+        writeln!(out, "        if data.len() < Self::SIZE {{")?;
+        writeln!(
+            out,
+            "            return Err({}::dbc::MessageError::TooShort(data.len()));",
+            krate
+        )?;
+        writeln!(out, "        }}")?;
+        writeln!(out, "        let mut msg = Self::default();")?;
+        writeln!(
+            out,
+            "        msg.data.copy_from_slice(&data[..Self::SIZE]);"
+        )?;
+        writeln!(out, "        Ok(msg)")?;
+        writeln!(out, "    }}\n")?;
+        writeln!(out, "    /// Gets the data payload of the message.")?;
+        writeln!(out, "    pub fn data(&self) -> &[u8] {{")?;
+        writeln!(out, "        &self.data")?;
+        writeln!(out, "    }}")?;
+        for sig in &sigs {
+            writeln!(out)?;
+            sig.accessors(out, krate)?;
+        }
+        writeln!(out, "}}\n")?;
+
+        if size > 32 {
+            writeln!(out, "impl Default for {} {{", ty)?;
+            writeln!(out, "    fn default() -> Self {{")?;
+            writeln!(out, "        Self {{ data: [0; {}] }}", size)?;
+            writeln!(out, "    }}")?;
+            writeln!(out, "}}\n")?;
+        }
+
+        let mut frame_types = vec!["CanFdFrame"];
+        if size <= 8 {
+            frame_types.insert(0, "CanFrame");
+        }
+
+        for frame in ["CanFrame", "CanFdFrame"] {
+            writeln!(
+                out,
+                "impl ::core::convert::TryFrom<&{}::{}> for {} {{",
+                krate, frame, ty
+            )?;
+            writeln!(out, "    type Error = {}::dbc::MessageError;\n", krate)?;
+            writeln!(
+                out,
+                "    fn try_from(frame: &{}::{}) -> Result<Self, Self::Error> {{",
+                krate, frame
+            )?;
+            writeln!(out, "        let id = {}::Frame::id_word(frame);", krate)?;
+            writeln!(out, "        if id != Self::ID {{")?;
+            writeln!(
+                out,
+                "            return Err({}::dbc::MessageError::WrongId(id));",
+                krate
+            )?;
+            writeln!(out, "        }}")?;
+            writeln!(
+                out,
+                "        Self::from_data({}::EmbeddedFrame::data(frame))",
+                krate
+            )?;
+            writeln!(out, "    }}")?;
+            writeln!(out, "}}\n")?;
+
+            writeln!(
+                out,
+                "impl ::core::convert::TryFrom<{}::{}> for {} {{",
+                krate, frame, ty
+            )?;
+            writeln!(out, "    type Error = {}::dbc::MessageError;\n", krate)?;
+            writeln!(
+                out,
+                "    fn try_from(frame: {}::{}) -> Result<Self, Self::Error> {{",
+                krate, frame
+            )?;
+            writeln!(out, "        Self::try_from(&frame)")?;
+            writeln!(out, "    }}")?;
+            writeln!(out, "}}\n")?;
+        }
+
+        for frame in frame_types {
+            writeln!(out, "impl From<{}> for {}::{} {{", ty, krate, frame)?;
+            writeln!(out, "    fn from(msg: {}) -> Self {{", ty)?;
+            writeln!(
+                out,
+                "        {}::EmbeddedFrame::new({}::id(), &msg.data).unwrap()",
+                krate, ty
+            )?;
+            writeln!(out, "    }}")?;
+            writeln!(out, "}}\n")?;
+        }
+        Ok(())
+    }
+}
+
+// ===== Signals =====
+
+/// The Rust type used for the value of a signal.
+#[derive(Clone, Copy, PartialEq)]
+enum ValueRepr {
+    /// An unscaled integer, with the type name and whether it's signed
+    Int(&'static str, bool),
+    /// An unscaled 32-bit float
+    F32,
+    /// An unscaled 64-bit float
+    F64,
+    /// A scaled value, as `f64`
+    Scaled,
+}
+
+/// Code generation for a signal in a message.
+struct SignalGen<'a> {
+    sig: &'a Signal,
+    /// The name of the getter
+    name: String,
+    /// The prefix for the signal's constants
+    konst: String,
+    repr: ValueRepr,
+    /// The conditions on multiplexor switches for the signal to be present
+    conds: Vec<String>,
+    /// The multiplexor switch to set along with the signal, and its value
+    switch: Option<(String, u64)>,
+}
+
+impl<'a> SignalGen<'a> {
+    fn new(msg: &'a Message, sig: &'a Signal) -> Self {
+        let mut name = snake_case(&sig.name);
+        if RESERVED.contains(&name.as_str()) {
+            name = format!("sig_{}", name);
+        }
+        let konst = name.to_uppercase();
+
+        let repr = if sig.factor != 1.0 || sig.offset != 0.0 {
+            ValueRepr::Scaled
+        } else {
+            match sig.value_type {
+                ValueType::Float32 => ValueRepr::F32,
+                ValueType::Float64 => ValueRepr::F64,
+                vt => {
+                    let signed = vt == ValueType::Signed;
+                    let bits = match sig.size {
+                        0..=8 => 8,
+                        9..=16 => 16,
+                        17..=32 => 32,
+                        _ => 64,
+                    };
+                    let ty = match (signed, bits) {
+                        (false, 8) => "u8",
+                        (false, 16) => "u16",
+                        (false, 32) => "u32",
+                        (false, _) => "u64",
+                        (true, 8) => "i8",
+                        (true, 16) => "i16",
+                        (true, 32) => "i32",
+                        (true, _) => "i64",
+                    };
+                    ValueRepr::Int(ty, signed)
+                }
+            }
+        };
+
+        let mut conds = Vec::new();
+        mux_conditions(msg, sig, &mut conds, msg.signals.len());
+
+        let switch = match (sig.multiplex, &sig.extended_multiplex) {
+            (Multiplex::Multiplexed(n) | Multiplex::MultiplexedMultiplexor(n), None) => {
+                msg.multiplexor().map(|mux| (const_name(&mux.name), n))
+            }
+            _ => None,
+        };
+
+        Self {
+            sig,
+            name,
+            konst,
+            repr,
+            conds,
+            switch,
+        }
+    }
+
+    /// Gets the Rust type of the value.
+    fn value_type(&self) -> &'static str {
+        match self.repr {
+            ValueRepr::Int(ty, _) => ty,
+            ValueRepr::F32 => "f32",
+            ValueRepr::F64 | ValueRepr::Scaled => "f64",
+        }
+    }
+
+    /// Writes the constants for the signal.
+    fn constants(&self, out: &mut String, krate: &str) -> std::fmt::Result {
+        let sig = self.sig;
+        let order = format!("{:?}", sig.byte_order);
+        let vtype = format!("{:?}", sig.value_type);
+
+        writeln!(out, "    /// The layout of the `{}` signal", sig.name)?;
+        write!(
+            out,
+            "    pub const {}: {k}::SignalSpec = {k}::SignalSpec::new({}, {}, {k}::ByteOrder::{}, {k}::ValueType::{})",
+            self.konst,
+            sig.start_bit,
+            sig.size,
+            order,
+            vtype,
+            k = krate
+        )?;
+        if self.repr == ValueRepr::Scaled {
+            write!(out, ".scaled({:?}, {:?})", sig.factor, sig.offset)?;
+        }
+        writeln!(out, ";")?;
+
+        if sig.min < sig.max {
+            writeln!(
+                out,
+                "    /// The minimum value of the `{}` signal",
+                sig.name
+            )?;
+            writeln!(
+                out,
+                "    pub const {}_MIN: f64 = {:?};",
+                self.konst, sig.min
+            )?;
+            writeln!(
+                out,
+                "    /// The maximum value of the `{}` signal",
+                sig.name
+            )?;
+            writeln!(
+                out,
+                "    pub const {}_MAX: f64 = {:?};",
+                self.konst, sig.max
+            )?;
+        }
+
+        if let ValueRepr::Int(ty, _) = self.repr {
+            let mut seen = BTreeSet::new();
+            for (val, desc) in &sig.value_descriptions {
+                let desc_name = words(desc).join("_").to_uppercase();
+                let name = format!("{}_{}", self.konst, desc_name);
+                if desc_name.is_empty()
+                    || !in_range(*val, sig.size, self.repr)
+                    || !seen.insert(name.clone())
+                {
+                    continue;
+                }
+                writeln!(out, "    /// `{}` value: {}", sig.name, desc)?;
+                writeln!(out, "    pub const {}: {} = {};", name, ty, val)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the getter and setter for the signal.
+    fn accessors(&self, out: &mut String, krate: &str) -> std::fmt::Result {
+        let sig = self.sig;
+        let ty = self.value_type();
+        let raw = format!("Self::{}.extract(&self.data).unwrap()", self.konst);
+
+        // ----- Getter -----
+
+        let mut doc = format!("Gets the `{}` signal", sig.name);
+        if !sig.unit.is_empty() {
+            write!(doc, ", in {}", sig.unit)?;
+        }
+        writeln!(out, "    /// {}.", doc)?;
+        write_comment(out, "    ", sig.comment.as_deref())?;
+
+        let val = match self.repr {
+            ValueRepr::Int(ty, false) => cast(&raw, "u64", ty),
+            ValueRepr::Int(ty, true) if sig.size < 64 => {
+                let shift = 64 - sig.size;
+                format!("((({} << {}) as i64) >> {}) as {}", raw, shift, shift, ty)
+            }
+            ValueRepr::Int(_, true) => format!("{} as i64", raw),
+            ValueRepr::F32 => format!("f32::from_bits({} as u32)", raw),
+            ValueRepr::F64 => format!("f64::from_bits({})", raw),
+            ValueRepr::Scaled => format!("Self::{}.to_physical({})", self.konst, raw),
+        };
+
+        if self.conds.is_empty() {
+            writeln!(out, "    pub fn {}(&self) -> {} {{", self.name, ty)?;
+            writeln!(out, "        {}", val)?;
+        } else {
+            writeln!(out, "    ///")?;
+            writeln!(
+                out,
+                "    /// This is `None` if the signal is not present in the message."
+            )?;
+            writeln!(out, "    pub fn {}(&self) -> Option<{}> {{", self.name, ty)?;
+            writeln!(out, "        if {} {{", self.conds.join(" && "))?;
+            writeln!(out, "            Some({})", val)?;
+            writeln!(out, "        }} else {{")?;
+            writeln!(out, "            None")?;
+            writeln!(out, "        }}")?;
+        }
+        writeln!(out, "    }}\n")?;
+
+        // ----- Setter -----
+
+        writeln!(out, "    /// Sets the `{}` signal.", sig.name)?;
+        if let Some((switch, n)) = &self.switch {
+            writeln!(out, "    ///")?;
+            writeln!(
+                out,
+                "    /// This also sets the multiplexor, `{}`, to {}.",
+                switch, n
+            )?;
+        }
+        writeln!(
+            out,
+            "    pub fn set_{}(&mut self, value: {}) -> Result<(), {}::dbc::MessageError> {{",
+            self.name, ty, krate
+        )?;
+
+        let mut checks = Vec::new();
+        if let ValueRepr::Int(ty, signed) = self.repr {
+            let bits: u32 = ty[1..].parse().unwrap();
+            if sig.size < bits {
+                if signed {
+                    checks.push(format!(
+                        "!({}..={}).contains(&value)",
+                        -(1i64 << (sig.size - 1)),
+                        (1i64 << (sig.size - 1)) - 1
+                    ));
+                } else {
+                    checks.push(format!("value > {}", u64::MAX >> (64 - sig.size)));
+                }
+            }
+        }
+        if sig.min < sig.max {
+            checks.push(format!(
+                "!(Self::{k}_MIN..=Self::{k}_MAX).contains({v})",
+                k = self.konst,
+                v = if ty == "f64" {
+                    "&value".to_string()
+                } else {
+                    format!("&({} as f64)", "value")
+                },
+            ));
+        }
+        if !checks.is_empty() {
+            writeln!(out, "        if {} {{", checks.join(" || "))?;
+            writeln!(
+                out,
+                "            return Err({}::dbc::MessageError::OutOfRange {{",
+                krate
+            )?;
+            writeln!(out, "                signal: {:?},", sig.name)?;
+            if ty == "f64" {
+                writeln!(out, "                value,")?;
+            } else {
+                writeln!(out, "                value: value as f64,")?;
+            }
+            writeln!(out, "            }});")?;
+            writeln!(out, "        }}")?;
+        }
+
+        let raw = match self.repr {
+            ValueRepr::Int(_, false) => cast("value", ty, "u64"),
+            ValueRepr::Int(_, true) => format!("{} as u64", cast("value", ty, "i64")),
+            ValueRepr::F32 => "u64::from(value.to_bits())".to_string(),
+            ValueRepr::F64 => "value.to_bits()".to_string(),
+            ValueRepr::Scaled => format!("Self::{}.to_raw(value)", self.konst),
+        };
+        if let Some((switch, n)) = &self.switch {
+            writeln!(
+                out,
+                "        Self::{}.insert(&mut self.data, {}).unwrap();",
+                switch, n
+            )?;
+        }
+        writeln!(
+            out,
+            "        Self::{}.insert(&mut self.data, {}).unwrap();",
+            self.konst, raw
+        )?;
+        writeln!(out, "        Ok(())")?;
+        writeln!(out, "    }}")?;
+        Ok(())
+    }
+}
+
+/// Collects the conditions on the multiplexor switches for a signal to be
+/// present, as Rust expressions.
+fn mux_conditions(msg: &Message, sig: &Signal, conds: &mut Vec<String>, depth: usize) {
+    let (switch, test) = if let Some(ext) = &sig.extended_multiplex {
+        let ranges: Vec<_> = ext
+            .ranges
+            .iter()
+            .map(|&(lo, hi)| {
+                if lo == hi {
+                    lo.to_string()
+                } else {
+                    format!("{}..={}", lo, hi)
+                }
+            })
+            .collect();
+        (
+            msg.signal(&ext.switch),
+            format!("matches!({{}}, {})", ranges.join(" | ")),
+        )
+    } else {
+        match sig.multiplex {
+            Multiplex::Multiplexed(n) | Multiplex::MultiplexedMultiplexor(n) => {
+                (msg.multiplexor(), format!("{{}} == {}", n))
+            }
+            _ => return,
+        }
+    };
+
+    match switch {
+        Some(switch) if depth > 0 => {
+            mux_conditions(msg, switch, conds, depth - 1);
+            let raw = format!(
+                "Self::{}.extract(&self.data).unwrap()",
+                const_name(&switch.name)
+            );
+            conds.push(test.replace("{}", &raw));
+        }
+        _ => conds.push("false".into()),
+    }
+}
+
+/// Casts an expression to another type, if it's not the same type.
+fn cast(expr: &str, from: &str, to: &str) -> String {
+    if from == to {
+        expr.into()
+    } else {
+        format!("{} as {}", expr, to)
+    }
+}
+
+/// Determines if a value description applies to an integer signal.
+fn in_range(val: i64, size: u32, repr: ValueRepr) -> bool {
+    match repr {
+        ValueRepr::Int(_, true) => {
+            let lim = 1i128 << (size - 1);
+            (-lim..lim).contains(&i128::from(val))
+        }
+        _ => val >= 0 && (val as u128) < (1u128 << size),
+    }
+}
+
+// ===== Identifiers =====
+
+/// Splits a name into words, at underscores, spaces, and case changes.
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut cur = String::new();
+    let chars: Vec<char> = name.chars().collect();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_ascii_alphanumeric() {
+            if !cur.is_empty() {
+                words.push(std::mem::take(&mut cur));
+            }
+            continue;
+        }
+        if c.is_ascii_uppercase() && !cur.is_empty() {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).map_or(false, |c| c.is_ascii_lowercase());
+            if prev.is_ascii_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_ascii_uppercase() && next_lower)
+            {
+                words.push(std::mem::take(&mut cur));
+            }
+        }
+        cur.push(c);
+    }
+    if !cur.is_empty() {
+        words.push(cur);
+    }
+    words
+}
+
+/// Makes a valid identifier from a name.
+fn ident(mut name: String) -> String {
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    if KEYWORDS.contains(&name.as_str()) {
+        name.push('_');
+    }
+    name
+}
+
+/// Converts a name to `snake_case`.
+fn snake_case(name: &str) -> String {
+    ident(words(name).join("_").to_lowercase())
+}
+
+/// Converts a name to `UpperCamelCase`.
+fn camel_case(name: &str) -> String {
+    let s: String = words(name)
+        .iter()
+        .map(|w| {
+            let mut cs = w.chars();
+            let first = cs.next().unwrap().to_ascii_uppercase();
+            let rest: String = if w.chars().all(|c| !c.is_ascii_lowercase()) {
+                cs.as_str().to_lowercase()
+            } else {
+                cs.as_str().into()
+            };
+            format!("{}{}", first, rest)
+        })
+        .collect();
+    ident(s)
+}
+
+/// Converts a name to `SCREAMING_SNAKE_CASE`, for the constants of a
+/// signal.
+fn const_name(name: &str) -> String {
+    let mut name = snake_case(name);
+    if RESERVED.contains(&name.as_str()) {
+        name = format!("sig_{}", name);
+    }
+    name.to_uppercase()
+}
+
+/// Writes a comment from the DBC file as doc comment lines.
+fn write_comment(out: &mut String, indent: &str, comment: Option<&str>) -> std::fmt::Result {
+    if let Some(comment) = comment {
+        writeln!(out, "{}///", indent)?;
+        for line in comment.lines() {
+            writeln!(out, "{}/// {}", indent, line.trim_end())?;
+        }
+    }
+    Ok(())
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(snake_case("EngineData"), "engine_data");
+        assert_eq!(snake_case("VehSpd_kph"), "veh_spd_kph");
+        assert_eq!(snake_case("ABSStatus"), "abs_status");
+        assert_eq!(snake_case("Type"), "type_");
+        assert_eq!(snake_case("2ndGear"), "_2nd_gear");
+        assert_eq!(camel_case("ENGINE_DATA"), "EngineData");
+        assert_eq!(camel_case("engineData"), "EngineData");
+        assert_eq!(camel_case("ABSStatus"), "AbsStatus");
+        assert_eq!(const_name("Data"), "SIG_DATA");
+        assert_eq!(const_name("Not available"), "NOT_AVAILABLE");
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        let db: Database = "BO_ 256 Short: 2 ECU\n \
+             SG_ Ok : 0|8@1+ (1,0) [0|255] \"\" ECU\n \
+             SG_ Late : 12|8@1+ (1,0) [0|255] \"\" ECU\n"
+            .parse()
+            .unwrap();
+
+        match Codegen::new().generate(&db) {
+            Err(ParseError::SignalOutOfBounds { message, signal }) => {
+                assert_eq!(message, "Short");
+                assert_eq!(signal, "Late");
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+}
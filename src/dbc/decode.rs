@@ -0,0 +1,501 @@
+// socketcan/src/dbc/decode.rs
+//
+// Decoding frames into messages and signal values with a DBC database.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! Decoding frames into messages and signal values.
+//!
+//! [`Database::decode()`] decodes a single frame into a [`DecodedMessage`]
+//! with the physical values of its signals. Only the signals that are
+//! present in the frame are decoded: multiplexed signals are included only
+//! when their multiplexor switch has a matching value, and signals that
+//! extend past the end of a short payload are left out.
+//!
+//! A [`Decoder`] wraps an iterator or `Stream` of frames, and yields each
+//! one as [`Decoded`], which is either the frame with its decoded message,
+//! or just the frame if its ID is not in the database. Items that are a
+//! `Result` have their frame decoded in place, so errors pass through.
+//! This works for the blocking and async sockets, and the `dump` readers,
+//! which yield `(timestamp, frame)` tuples:
+//!
+//! ```no_run
+//! use socketcan::{dbc::Database, CanSocket, Socket};
+//! use std::iter;
+//!
+//! let db = Database::from_file("vehicle.dbc").unwrap();
+//! let sock = CanSocket::open("can0").unwrap();
+//!
+//! for item in db.decoder(iter::from_fn(|| Some(sock.read_frame()))) {
+//!     println!("{}", item.unwrap());
+//! }
+//! ```
+
+use super::{Database, Message, Multiplex, Signal};
+use crate::{CanAnyFrame, Frame};
+use libc::{canid_t, CAN_EFF_FLAG, CAN_EFF_MASK};
+use std::fmt;
+
+#[cfg(feature = "futures")]
+use futures::{
+    task::{Context, Poll},
+    Stream,
+};
+#[cfg(feature = "futures")]
+use std::pin::Pin;
+
+// ===== Decodable =====
+
+/// A frame, or a value carrying one, that can be decoded.
+pub trait Decodable {
+    /// Gets the ID word and data payload of the frame.
+    fn id_and_data(&self) -> (canid_t, &[u8]);
+}
+
+impl<F: Frame> Decodable for F {
+    fn id_and_data(&self) -> (canid_t, &[u8]) {
+        (self.id_word(), self.data())
+    }
+}
+
+impl Decodable for CanAnyFrame {
+    fn id_and_data(&self) -> (canid_t, &[u8]) {
+        use CanAnyFrame::*;
+        match self {
+            Normal(frame) => frame.id_and_data(),
+            Remote(frame) => frame.id_and_data(),
+            Error(frame) => frame.id_and_data(),
+            Fd(frame) => frame.id_and_data(),
+        }
+    }
+}
+
+impl<T: Decodable> Decodable for (u64, T) {
+    fn id_and_data(&self) -> (canid_t, &[u8]) {
+        self.1.id_and_data()
+    }
+}
+
+/// An item from a frame iterator or stream that can be decoded.
+///
+/// This is implemented for anything [`Decodable`], and for a `Result` of
+/// one, in which case the error is passed through.
+pub trait DecodeItem<'a> {
+    /// The decoded item
+    type Output;
+
+    /// Decodes the item with the database.
+    fn decode_with(self, db: &'a Database) -> Self::Output;
+}
+
+impl<'a, T: Decodable> DecodeItem<'a> for T {
+    type Output = Decoded<'a, T>;
+
+    fn decode_with(self, db: &'a Database) -> Self::Output {
+        match db.decode(&self) {
+            Some(msg) => Decoded::Message(self, msg),
+            None => Decoded::Unknown(self),
+        }
+    }
+}
+
+impl<'a, T: Decodable, E> DecodeItem<'a> for Result<T, E> {
+    type Output = Result<Decoded<'a, T>, E>;
+
+    fn decode_with(self, db: &'a Database) -> Self::Output {
+        self.map(|item| item.decode_with(db))
+    }
+}
+
+// ===== Decoded values =====
+
+/// The decoded value of a signal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SignalValue<'a> {
+    /// The signal definition
+    pub signal: &'a Signal,
+    /// The raw bits from the payload
+    pub raw: u64,
+    /// The physical value
+    pub value: f64,
+}
+
+impl<'a> SignalValue<'a> {
+    /// Gets the name of the signal.
+    pub fn name(&self) -> &'a str {
+        &self.signal.name
+    }
+
+    /// Gets the unit of the physical value.
+    pub fn unit(&self) -> &'a str {
+        &self.signal.unit
+    }
+
+    /// Gets the description of the raw value, if there is one.
+    ///
+    /// Descriptions for signed signals are looked up by the signed value,
+    /// and then by the unsigned raw bits, as tools differ in how they
+    /// write them.
+    pub fn description(&self) -> Option<&'a str> {
+        let val = self.signal.spec().raw_value(self.raw) as i64;
+        self.signal
+            .describe(val)
+            .or_else(|| self.signal.describe(self.raw as i64))
+    }
+}
+
+impl fmt::Display for SignalValue<'_> {
+    /// Formats the value as `name=value unit`, or `name=description`
+    /// if the raw value has one.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.description() {
+            Some(desc) => write!(f, "{}={}", self.name(), desc),
+            None if self.unit().is_empty() => write!(f, "{}={}", self.name(), self.value),
+            None => write!(f, "{}={} {}", self.name(), self.value, self.unit()),
+        }
+    }
+}
+
+/// A message decoded from a frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedMessage<'a> {
+    /// The message definition
+    pub message: &'a Message,
+    /// The values of the signals present in the frame
+    pub signals: Vec<SignalValue<'a>>,
+}
+
+impl<'a> DecodedMessage<'a> {
+    /// Gets the name of the message.
+    pub fn name(&self) -> &'a str {
+        &self.message.name
+    }
+
+    /// Gets the decoded value of a signal, if it's present.
+    pub fn signal(&self, name: &str) -> Option<&SignalValue<'a>> {
+        self.signals.iter().find(|val| val.name() == name)
+    }
+
+    /// Gets the physical value of a signal, if it's present.
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.signal(name).map(|val| val.value)
+    }
+}
+
+impl fmt::Display for DecodedMessage<'_> {
+    /// Formats the message as `name(signal=value, ...)`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}(", self.name())?;
+        for (i, val) in self.signals.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", val)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// An item from a [`Decoder`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decoded<'a, T> {
+    /// The item, with the message decoded from its frame
+    Message(T, DecodedMessage<'a>),
+    /// An item with a frame whose ID is not in the database
+    Unknown(T),
+}
+
+impl<'a, T> Decoded<'a, T> {
+    /// Gets the original item.
+    pub fn item(&self) -> &T {
+        match self {
+            Decoded::Message(item, _) | Decoded::Unknown(item) => item,
+        }
+    }
+
+    /// Gets the decoded message, if the ID was known.
+    pub fn message(&self) -> Option<&DecodedMessage<'a>> {
+        match self {
+            Decoded::Message(_, msg) => Some(msg),
+            Decoded::Unknown(_) => None,
+        }
+    }
+
+    /// Converts into the original item.
+    pub fn into_item(self) -> T {
+        match self {
+            Decoded::Message(item, _) | Decoded::Unknown(item) => item,
+        }
+    }
+}
+
+impl<T: Decodable> fmt::Display for Decoded<'_, T> {
+    /// Formats the ID and the decoded message, or the raw data for an
+    /// unknown ID.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (id, data) = self.item().id_and_data();
+        if id & CAN_EFF_FLAG != 0 {
+            write!(f, "{:08X} ", id & CAN_EFF_MASK)?;
+        } else {
+            write!(f, "{:03X} ", id & CAN_EFF_MASK)?;
+        }
+        match self {
+            Decoded::Message(_, msg) => write!(f, "{}", msg),
+            Decoded::Unknown(_) => write!(f, "[{}]", hex::encode_upper(data)),
+        }
+    }
+}
+
+// ===== Database decoding =====
+
+impl Database {
+    /// Decodes a frame into its message and signal values.
+    ///
+    /// Returns `None` if the ID of the frame is not in the database.
+    /// Remote and error frames are never decoded.
+    pub fn decode<T: Decodable + ?Sized>(&self, frame: &T) -> Option<DecodedMessage<'_>> {
+        let (id, data) = frame.id_and_data();
+        let message = self.messages.get(&id)?;
+
+        let mut signals = Vec::with_capacity(message.signals.len());
+        for sig in &message.signals {
+            if !is_present(message, sig, data, message.signals.len()) {
+                continue;
+            }
+            let spec = sig.spec();
+            if let Ok(raw) = spec.extract(data) {
+                let value = spec.to_physical(raw);
+                signals.push(SignalValue {
+                    signal: sig,
+                    raw,
+                    value,
+                });
+            }
+        }
+        Some(DecodedMessage { message, signals })
+    }
+
+    /// Creates a decoder over an iterator or stream of frames.
+    pub fn decoder<I>(&self, inner: I) -> Decoder<'_, I> {
+        Decoder::new(self, inner)
+    }
+}
+
+/// Determines if a signal is present in the payload, from the values of
+/// the multiplexor switches it depends on.
+///
+/// The `depth` limits the recursion through nested switches, in case the
+/// database has a cycle.
+fn is_present(msg: &Message, sig: &Signal, data: &[u8], depth: usize) -> bool {
+    if depth == 0 {
+        return false;
+    }
+
+    if let Some(ext) = &sig.extended_multiplex {
+        return match switch_value(msg, msg.signal(&ext.switch), data, depth) {
+            Some(val) => ext.ranges.iter().any(|&(lo, hi)| lo <= val && val <= hi),
+            None => false,
+        };
+    }
+
+    match sig.multiplex {
+        Multiplex::None | Multiplex::Multiplexor => true,
+        Multiplex::Multiplexed(n) | Multiplex::MultiplexedMultiplexor(n) => {
+            switch_value(msg, msg.multiplexor(), data, depth) == Some(n)
+        }
+    }
+}
+
+/// Gets the raw value of a multiplexor switch, if it's present.
+fn switch_value(msg: &Message, switch: Option<&Signal>, data: &[u8], depth: usize) -> Option<u64> {
+    let switch = switch?;
+    if !is_present(msg, switch, data, depth - 1) {
+        return None;
+    }
+    switch.spec().extract(data).ok()
+}
+
+// ===== Decoder =====
+
+/// An iterator or stream adapter that decodes frames with a database.
+///
+/// Each item of the inner iterator or stream is decoded with
+/// [`DecodeItem::decode_with()`].
+#[derive(Debug)]
+pub struct Decoder<'a, I> {
+    db: &'a Database,
+    inner: I,
+}
+
+impl<'a, I> Decoder<'a, I> {
+    /// Creates a decoder over an iterator or stream of frames.
+    pub fn new(db: &'a Database, inner: I) -> Self {
+        Self { db, inner }
+    }
+
+    /// Gets the database used for decoding.
+    pub fn database(&self) -> &'a Database {
+        self.db
+    }
+
+    /// Gets a reference to the inner iterator or stream.
+    pub fn get_ref(&self) -> &I {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the inner iterator or stream.
+    pub fn get_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    /// Converts into the inner iterator or stream.
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+}
+
+impl<'a, I> Iterator for Decoder<'a, I>
+where
+    I: Iterator,
+    I::Item: DecodeItem<'a>,
+{
+    type Item = <I::Item as DecodeItem<'a>>::Output;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let db = self.db;
+        self.inner.next().map(|item| item.decode_with(db))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<'a, S> Stream for Decoder<'a, S>
+where
+    S: Stream + Unpin,
+    S::Item: DecodeItem<'a>,
+{
+    type Item = <S::Item as DecodeItem<'a>>::Output;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let db = self.db;
+        Pin::new(&mut self.inner)
+            .poll_next(cx)
+            .map(|item| item.map(|item| item.decode_with(db)))
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dbc::tests::DBC, CanFrame, EmbeddedFrame, ExtendedId, StandardId};
+
+    fn diag(data: &[u8]) -> CanFrame {
+        CanFrame::new(ExtendedId::new(0x18FEF1FE).unwrap(), data).unwrap()
+    }
+
+    fn names<'a>(msg: &'a DecodedMessage) -> Vec<&'a str> {
+        msg.signals.iter().map(|val| val.name()).collect()
+    }
+
+    #[test]
+    fn test_decode() {
+        let db = Database::parse(DBC).unwrap();
+
+        let frame = CanFrame::new(StandardId::new(0x100).unwrap(), &[0x10, 0x27, 0xFF]).unwrap();
+        let msg = db.decode(&frame).unwrap();
+        assert_eq!(msg.name(), "EngineData");
+        // The short payload only holds the first two signals
+        assert_eq!(names(&msg), ["Rpm", "Temp"]);
+        assert_eq!(msg.get("Rpm"), Some(2500.0));
+        assert_eq!(msg.signal("Temp").unwrap().description(), Some("Invalid"));
+        assert_eq!(msg.to_string(), "EngineData(Rpm=2500 rpm, Temp=Invalid)");
+
+        let frame = CanFrame::new(StandardId::new(0x200).unwrap(), &[1]).unwrap();
+        assert!(db.decode(&frame).is_none());
+
+        // Remote frames are not decoded
+        let frame = CanFrame::new_remote(StandardId::new(0x100).unwrap(), 8).unwrap();
+        assert!(db.decode(&frame).is_none());
+    }
+
+    #[test]
+    fn test_multiplex() {
+        let db = Database::parse(DBC).unwrap();
+
+        let msg = db.decode(&diag(&[1, 0xE8, 0x03, 0])).unwrap();
+        assert_eq!(names(&msg), ["Mode", "Voltage"]);
+        assert_eq!(msg.get("Voltage"), Some(1.0));
+        assert_eq!(msg.signal("Mode").unwrap().to_string(), "Mode=Voltage");
+
+        let msg = db.decode(&diag(&[2, 0x9C, 0xFF, 0])).unwrap();
+        assert_eq!(names(&msg), ["Mode", "Current"]);
+        assert_eq!(msg.get("Current"), Some(-1.0));
+
+        // Nested: Deep is present when SubMode is 0 or 2-4
+        let msg = db.decode(&diag(&[3, 3, 42])).unwrap();
+        assert_eq!(names(&msg), ["Mode", "SubMode", "Deep"]);
+        assert_eq!(msg.get("Deep"), Some(42.0));
+
+        let msg = db.decode(&diag(&[3, 1, 42])).unwrap();
+        assert_eq!(names(&msg), ["Mode", "SubMode"]);
+
+        let msg = db.decode(&diag(&[1, 0, 42])).unwrap();
+        assert_eq!(names(&msg), ["Mode", "Voltage"]);
+    }
+
+    #[test]
+    fn test_decoder() {
+        let db = Database::parse(DBC).unwrap();
+
+        let frames = vec![
+            Ok(CanFrame::new(StandardId::new(0x100).unwrap(), &[0x10, 0x27]).unwrap()),
+            Err("oops"),
+            Ok(CanFrame::new(StandardId::new(0x7FF).unwrap(), &[0xAB, 0xCD]).unwrap()),
+        ];
+        let items: Vec<_> = db.decoder(frames.into_iter()).collect();
+        assert_eq!(items.len(), 3);
+        assert_eq!(
+            items[0].as_ref().unwrap().to_string(),
+            "100 EngineData(Rpm=2500 rpm)"
+        );
+        assert_eq!(items[1].as_ref().unwrap_err(), &"oops");
+        let item = items[2].as_ref().unwrap();
+        assert!(item.message().is_none());
+        assert_eq!(item.to_string(), "7FF [ABCD]");
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn test_stream() {
+        use futures::{executor::block_on, stream, StreamExt};
+
+        let db = Database::parse(DBC).unwrap();
+
+        let frames = stream::iter(vec![diag(&[2, 0x9C, 0xFF]), diag(&[])]);
+        let items: Vec<_> = block_on(db.decoder(frames).collect());
+        assert_eq!(items[0].message().unwrap().get("Current"), Some(-1.0));
+        assert_eq!(items[1].message().unwrap().signals, []);
+    }
+
+    #[cfg(feature = "dump")]
+    #[test]
+    fn test_decode_records() {
+        let db = Database::parse(DBC).unwrap();
+
+        let log: &[u8] = b"(1.000000) can0 18FEF1FE#01E803\n";
+        let mut rdr = crate::dump::Reader::from_reader(log);
+        let item = db.decoder(rdr.records()).next().unwrap().unwrap();
+        assert_eq!(item.item().0, 1_000_000);
+        assert_eq!(item.message().unwrap().get("Voltage"), Some(1.0));
+    }
+}
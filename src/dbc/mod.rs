@@ -0,0 +1,662 @@
+// socketcan/src/dbc/mod.rs
+//
+// Implements a CAN database from Vector DBC files.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! CAN databases from Vector DBC files.
+//!
+//! A DBC file describes the messages on a CAN network, and how the signals
+//! in each message are packed into the frame payload. This module parses a
+//! DBC file into a [`Database`] of [`Message`]s, each with its
+//! [`Signal`]s, along with the value tables, comments, and attributes
+//! attached to them.
+//!
+//! Messages are keyed by the SocketCAN ID word, `canid_t`, with the
+//! `CAN_EFF_FLAG` bit set for extended IDs. This happens to be the same
+//! encoding used in DBC files, so messages can be looked up directly for a
+//! received frame:
+//!
+//! ```
+//! use socketcan::{dbc::Database, CanFrame, EmbeddedFrame, StandardId};
+//!
+//! let db: Database = r#"
+//! BO_ 256 EngineData: 8 ECU
+//!  SG_ Rpm : 0|16@1+ (0.25,0) [0|16383.75] "rpm" Dash
+//! "#.parse().unwrap();
+//!
+//! let frame = CanFrame::new(StandardId::new(0x100).unwrap(), &[0; 8]).unwrap();
+//! let msg = db.message_for_frame(&frame).unwrap();
+//! assert_eq!(msg.name, "EngineData");
+//! assert_eq!(msg.signal("Rpm").unwrap().unit, "rpm");
+//! ```
+//!
+//! The parser is lenient about the parts of the format that don't affect
+//! the message layout. Sections like environment variables and signal
+//! groups are skipped, and comments or attributes that refer to unknown
+//! messages or signals are ignored.
+
+use crate::{
+    frame::{id_to_canid_t, Frame},
+    Id, SignalError, SignalSpec,
+};
+use embedded_can::{ExtendedId, StandardId};
+use libc::{canid_t, CAN_EFF_FLAG, CAN_EFF_MASK};
+use std::{collections::BTreeMap, error, fmt, fs, io, path::Path, str::FromStr};
+
+mod parser;
+
+pub mod codegen;
+pub use codegen::Codegen;
+
+pub mod decode;
+pub use decode::{Decodable, DecodeItem, Decoded, DecodedMessage, Decoder, SignalValue};
+
+pub use crate::signal::{ByteOrder, ValueType};
+
+/// The name used in DBC files for a missing node
+pub const NO_NODE: &str = "Vector__XXX";
+
+/// Descriptions for raw signal values, like `0 => "Off"`.
+pub type ValueDescriptions = BTreeMap<i64, String>;
+
+/// A set of named attribute values.
+pub type Attributes = BTreeMap<String, AttributeValue>;
+
+// ===== Database =====
+
+/// An in-memory CAN database, parsed from a DBC file.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Database {
+    /// The version string
+    pub version: String,
+    /// The nodes (ECUs) on the network
+    pub nodes: Vec<Node>,
+    /// The named value tables
+    pub value_tables: BTreeMap<String, ValueDescriptions>,
+    /// The messages, keyed by the ID word
+    pub messages: BTreeMap<canid_t, Message>,
+    /// The comment for the network
+    pub comment: Option<String>,
+    /// The attribute definitions
+    pub attribute_defs: BTreeMap<String, AttributeDef>,
+    /// The attribute values for the network
+    pub attributes: Attributes,
+}
+
+impl Database {
+    /// Parses a database from the contents of a DBC file.
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        parser::parse(s)
+    }
+
+    /// Reads and parses a DBC file.
+    ///
+    /// DBC files are often written in the Windows-1252 encoding, rather
+    /// than UTF-8. If the file is not valid UTF-8, it's read as Latin-1,
+    /// which matches Windows-1252 for most printable characters.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+        let buf = fs::read(path)?;
+        match String::from_utf8(buf) {
+            Ok(s) => Self::parse(&s),
+            Err(err) => {
+                let s: String = err.as_bytes().iter().map(|&b| b as char).collect();
+                Self::parse(&s)
+            }
+        }
+    }
+
+    /// Gets the message with the specified ID.
+    pub fn message(&self, id: impl Into<Id>) -> Option<&Message> {
+        self.messages.get(&id_to_canid_t(id))
+    }
+
+    /// Gets the message for a frame, matching its ID.
+    pub fn message_for_frame<F: Frame>(&self, frame: &F) -> Option<&Message> {
+        self.messages
+            .get(&(frame.id_word() & (CAN_EFF_FLAG | CAN_EFF_MASK)))
+    }
+
+    /// Gets the message with the specified name.
+    pub fn message_by_name(&self, name: &str) -> Option<&Message> {
+        self.messages.values().find(|msg| msg.name == name)
+    }
+
+    /// Gets a node by name.
+    pub fn node(&self, name: &str) -> Option<&Node> {
+        self.nodes.iter().find(|node| node.name == name)
+    }
+
+    /// Gets the default value of an attribute, from its definition.
+    pub fn attribute_default(&self, name: &str) -> Option<&AttributeValue> {
+        self.attribute_defs.get(name)?.default.as_ref()
+    }
+}
+
+impl FromStr for Database {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+// ===== Node =====
+
+/// A node (ECU) on the network.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Node {
+    /// The name of the node
+    pub name: String,
+    /// The comment for the node
+    pub comment: Option<String>,
+    /// The attribute values for the node
+    pub attributes: Attributes,
+}
+
+// ===== Message =====
+
+/// A message definition.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Message {
+    /// The ID word, with the `CAN_EFF_FLAG` bit set for an extended ID
+    pub id: canid_t,
+    /// The name of the message
+    pub name: String,
+    /// The size of the payload, in bytes
+    pub size: u32,
+    /// The node that transmits the message, if any
+    pub transmitter: Option<String>,
+    /// Additional transmitting nodes, from `BO_TX_BU_`
+    pub senders: Vec<String>,
+    /// The signals in the message
+    pub signals: Vec<Signal>,
+    /// The comment for the message
+    pub comment: Option<String>,
+    /// The attribute values for the message
+    pub attributes: Attributes,
+}
+
+impl Message {
+    /// Gets the CAN ID of the message.
+    pub fn id(&self) -> Id {
+        if self.is_extended() {
+            ExtendedId::new(self.raw_id()).unwrap().into()
+        } else {
+            StandardId::new(self.raw_id() as u16).unwrap().into()
+        }
+    }
+
+    /// Gets the numeric ID, without the extended flag.
+    pub fn raw_id(&self) -> canid_t {
+        self.id & CAN_EFF_MASK
+    }
+
+    /// Determines if the message has an extended ID.
+    pub fn is_extended(&self) -> bool {
+        self.id & CAN_EFF_FLAG != 0
+    }
+
+    /// Gets a signal by name.
+    pub fn signal(&self, name: &str) -> Option<&Signal> {
+        self.signals.iter().find(|sig| sig.name == name)
+    }
+
+    /// Gets the multiplexor signal at the top level of the message, if any.
+    pub fn multiplexor(&self) -> Option<&Signal> {
+        self.signals
+            .iter()
+            .find(|sig| sig.multiplex == Multiplex::Multiplexor)
+    }
+
+    /// Determines if the message contains multiplexed signals.
+    pub fn is_multiplexed(&self) -> bool {
+        self.multiplexor().is_some()
+    }
+
+    fn signal_mut(&mut self, name: &str) -> Option<&mut Signal> {
+        self.signals.iter_mut().find(|sig| sig.name == name)
+    }
+}
+
+// ===== Signal =====
+
+/// How a signal takes part in multiplexing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Multiplex {
+    /// A plain signal, which is always present (no indicator)
+    None,
+    /// The multiplexor switch of the message (`M`)
+    Multiplexor,
+    /// A signal present when the multiplexor has this value (`m3`)
+    Multiplexed(u64),
+    /// A signal present when the multiplexor has this value, which is
+    /// itself the multiplexor for other signals (`m3M`)
+    MultiplexedMultiplexor(u64),
+}
+
+/// An extended multiplexing entry, from `SG_MUL_VAL_`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtendedMultiplex {
+    /// The name of the multiplexor switch signal
+    pub switch: String,
+    /// The inclusive ranges of switch values for which the signal is present
+    pub ranges: Vec<(u64, u64)>,
+}
+
+/// A signal definition.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Signal {
+    /// The name of the signal
+    pub name: String,
+    /// The start bit, as defined for the byte order in the DBC format
+    pub start_bit: u32,
+    /// The size in bits
+    pub size: u32,
+    /// The byte order
+    pub byte_order: ByteOrder,
+    /// The type of the raw value
+    pub value_type: ValueType,
+    /// The scale factor from raw to physical value
+    pub factor: f64,
+    /// The offset from raw to physical value
+    pub offset: f64,
+    /// The minimum physical value
+    pub min: f64,
+    /// The maximum physical value
+    pub max: f64,
+    /// The unit of the physical value
+    pub unit: String,
+    /// The nodes that receive the signal
+    pub receivers: Vec<String>,
+    /// How the signal takes part in multiplexing
+    pub multiplex: Multiplex,
+    /// Extended multiplexing, if defined
+    pub extended_multiplex: Option<ExtendedMultiplex>,
+    /// The descriptions for raw values
+    pub value_descriptions: ValueDescriptions,
+    /// The comment for the signal
+    pub comment: Option<String>,
+    /// The attribute values for the signal
+    pub attributes: Attributes,
+}
+
+impl Signal {
+    /// Gets the layout and scaling of the signal in the payload.
+    pub fn spec(&self) -> SignalSpec {
+        SignalSpec::new(self.start_bit, self.size, self.byte_order, self.value_type)
+            .scaled(self.factor, self.offset)
+    }
+
+    /// Gets the physical value of the signal from a frame.
+    pub fn decode<F: Frame>(&self, frame: &F) -> Result<f64, SignalError> {
+        frame.get_signal(&self.spec())
+    }
+
+    /// Sets the physical value of the signal in a frame.
+    pub fn encode<F: Frame>(&self, frame: &mut F, val: f64) -> Result<(), SignalError> {
+        frame.set_signal(&self.spec(), val)
+    }
+
+    /// Gets the description of a raw value, if there is one.
+    pub fn describe(&self, raw: i64) -> Option<&str> {
+        self.value_descriptions.get(&raw).map(|s| s.as_str())
+    }
+}
+
+// ===== Attributes =====
+
+/// The type of object an attribute applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AttributeObject {
+    /// The network (database) itself
+    Network,
+    /// Nodes (`BU_`)
+    Node,
+    /// Messages (`BO_`)
+    Message,
+    /// Signals (`SG_`)
+    Signal,
+    /// Environment variables (`EV_`)
+    EnvVar,
+}
+
+/// The type and allowed values of an attribute.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AttributeKind {
+    /// An integer in the range
+    Int(i64, i64),
+    /// An integer in the range, displayed in hex
+    Hex(i64, i64),
+    /// A float in the range
+    Float(f64, f64),
+    /// A string
+    String,
+    /// One of a list of names, with the value being the index
+    Enum(Vec<String>),
+}
+
+/// An attribute definition.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AttributeDef {
+    /// The type of object the attribute applies to
+    pub object: AttributeObject,
+    /// The type of the attribute
+    pub kind: AttributeKind,
+    /// The default value, from `BA_DEF_DEF_`
+    pub default: Option<AttributeValue>,
+}
+
+/// The value of an attribute.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AttributeValue {
+    /// An integer or hex value, or the index of an enum value
+    Int(i64),
+    /// A float value
+    Float(f64),
+    /// A string value
+    String(String),
+}
+
+// ===== ParseError =====
+
+/// An error reading or parsing a DBC file.
+#[derive(Debug)]
+pub enum ParseError {
+    /// An I/O error reading the file
+    Io(io::Error),
+    /// A syntax error at the specified line
+    Syntax {
+        /// The line number, starting at 1
+        line: usize,
+        /// A description of the error
+        msg: String,
+    },
+    /// A signal that doesn't fit in the payload of its message, which
+    /// code can't be generated for
+    SignalOutOfBounds {
+        /// The name of the message
+        message: String,
+        /// The name of the signal
+        signal: String,
+    },
+}
+
+impl error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ParseError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParseError::*;
+        match self {
+            Io(err) => write!(f, "I/O error: {}", err),
+            Syntax { line, msg } => write!(f, "DBC syntax error at line {}: {}", line, msg),
+            SignalOutOfBounds { message, signal } => {
+                write!(
+                    f,
+                    "Signal '{}' doesn't fit in message '{}'",
+                    signal, message
+                )
+            }
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(err: io::Error) -> Self {
+        ParseError::Io(err)
+    }
+}
+
+// ===== MessageError =====
+
+/// An error converting between a frame and a typed message, or setting a
+/// signal in one.
+///
+/// This is used by the message types created with the [`codegen`] module.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageError {
+    /// The ID word of the frame doesn't match the message
+    WrongId(canid_t),
+    /// The payload of the frame is shorter than the message, in bytes
+    TooShort(usize),
+    /// A signal value is outside of its range
+    OutOfRange {
+        /// The name of the signal
+        signal: &'static str,
+        /// The value that was rejected
+        value: f64,
+    },
+}
+
+impl error::Error for MessageError {}
+
+impl fmt::Display for MessageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use MessageError::*;
+        match *self {
+            WrongId(id) => write!(f, "wrong ID for the message: 0x{:X}", id),
+            TooShort(n) => write!(f, "payload too short for the message: {} bytes", n),
+            OutOfRange { signal, value } => {
+                write!(f, "value out of range for signal '{}': {}", signal, value)
+            }
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CanFrame, EmbeddedFrame};
+
+    pub(crate) const DBC: &str = r#"VERSION "1.0"
+
+NS_ :
+	NS_DESC_
+	CM_
+	BA_DEF_
+	BA_
+	VAL_
+	SIG_VALTYPE_
+	SG_MUL_VAL_
+
+BS_:
+
+BU_: Engine Dash Gateway
+
+VAL_TABLE_ OnOff 1 "On" 0 "Off" ;
+
+BO_ 256 EngineData: 8 Engine
+ SG_ Rpm : 0|16@1+ (0.25,0) [0|16383.75] "rpm" Dash,Gateway
+ SG_ Temp : 16|8@1- (1,-40) [-40|215] "degC" Dash
+ SG_ Pressure : 39|16@0+ (0.1,0) [0|6553.5] "kPa" Dash
+ SG_ Ratio : 32|32@1- (1,0) [-1E+038|1E+038] "" Vector__XXX
+
+BO_ 2566844926 Diag: 8 Gateway
+ SG_ Mode M : 0|8@1+ (1,0) [0|255] "" Engine
+ SG_ Voltage m1 : 8|16@1+ (0.001,0) [0|65.535] "V" Engine
+ SG_ Current m2 : 8|16@1- (0.01,0) [-327.68|327.67] "A" Engine
+ SG_ SubMode m3M : 8|8@1+ (1,0) [0|255] "" Engine
+ SG_ Deep m0 : 16|8@1+ (1,0) [0|255] "" Engine
+
+BO_TX_BU_ 256 : Engine,Gateway;
+
+CM_ "The test network";
+CM_ BU_ Engine "The engine controller";
+CM_ BO_ 256 "Engine status";
+CM_ SG_ 256 Rpm "Engine speed,
+over two lines";
+CM_ SG_ 999 Missing "Ignored";
+
+BA_DEF_ BO_ "GenMsgCycleTime" INT 0 65535;
+BA_DEF_ SG_ "GenSigStartValue" FLOAT -1E+38 1E+38;
+BA_DEF_ "BusType" STRING ;
+BA_DEF_ BU_ "NodeLayer" ENUM "App","Diag";
+BA_DEF_DEF_ "GenMsgCycleTime" 100;
+BA_DEF_DEF_ "BusType" "CAN";
+BA_ "BusType" "CAN FD";
+BA_ "GenMsgCycleTime" BO_ 256 20;
+BA_ "GenSigStartValue" SG_ 256 Temp 40.5;
+BA_ "NodeLayer" BU_ Dash 1;
+
+VAL_ 256 Temp 255 "Invalid" 254 "Error" ;
+VAL_ 2566844926 Mode 1 "Voltage" 2 "Current" 3 "Sub" ;
+
+SIG_VALTYPE_ 256 Ratio : 1;
+
+SG_MUL_VAL_ 2566844926 Deep SubMode 0-0, 2-4;
+"#;
+
+    #[test]
+    fn test_parse() {
+        let db: Database = DBC.parse().unwrap();
+
+        assert_eq!(db.version, "1.0");
+        assert_eq!(db.comment.as_deref(), Some("The test network"));
+        assert_eq!(db.nodes.len(), 3);
+        assert_eq!(
+            db.node("Engine").unwrap().comment.as_deref(),
+            Some("The engine controller")
+        );
+        assert_eq!(db.value_tables["OnOff"][&1], "On");
+        assert_eq!(db.messages.len(), 2);
+
+        let msg = db.message(StandardId::new(0x100).unwrap()).unwrap();
+        assert_eq!(msg.name, "EngineData");
+        assert_eq!(msg.size, 8);
+        assert_eq!(msg.transmitter.as_deref(), Some("Engine"));
+        assert_eq!(msg.senders, ["Engine", "Gateway"]);
+        assert_eq!(msg.comment.as_deref(), Some("Engine status"));
+        assert!(!msg.is_multiplexed());
+
+        let sig = msg.signal("Rpm").unwrap();
+        assert_eq!((sig.start_bit, sig.size), (0, 16));
+        assert_eq!(sig.byte_order, ByteOrder::LittleEndian);
+        assert_eq!(sig.value_type, ValueType::Unsigned);
+        assert_eq!((sig.factor, sig.offset), (0.25, 0.0));
+        assert_eq!((sig.min, sig.max), (0.0, 16383.75));
+        assert_eq!(sig.unit, "rpm");
+        assert_eq!(sig.receivers, ["Dash", "Gateway"]);
+        assert_eq!(
+            sig.comment.as_deref(),
+            Some("Engine speed,\nover two lines")
+        );
+
+        let sig = msg.signal("Temp").unwrap();
+        assert_eq!(sig.value_type, ValueType::Signed);
+        assert_eq!(sig.offset, -40.0);
+        assert_eq!(sig.describe(255), Some("Invalid"));
+        assert_eq!(sig.spec().to_physical(100), 60.0);
+
+        let frame = CanFrame::new(StandardId::new(0x100).unwrap(), &[0x10, 0x27, 0xEC]).unwrap();
+        assert_eq!(msg.signal("Rpm").unwrap().decode(&frame).unwrap(), 2500.0);
+        assert_eq!(sig.decode(&frame).unwrap(), -60.0);
+
+        let sig = msg.signal("Pressure").unwrap();
+        assert_eq!(sig.byte_order, ByteOrder::BigEndian);
+
+        let sig = msg.signal("Ratio").unwrap();
+        assert_eq!(sig.value_type, ValueType::Float32);
+        assert_eq!(sig.min, -1e38);
+        assert!(sig.receivers.is_empty());
+    }
+
+    #[test]
+    fn test_multiplex() {
+        let db = Database::parse(DBC).unwrap();
+
+        let msg = db.message(ExtendedId::new(0x18FEF1FE).unwrap()).unwrap();
+        assert_eq!(msg.id, 0x18FEF1FE | CAN_EFF_FLAG);
+        assert!(msg.is_extended());
+        assert_eq!(msg.id(), Id::Extended(ExtendedId::new(0x18FEF1FE).unwrap()));
+        assert_eq!(msg.multiplexor().unwrap().name, "Mode");
+        assert_eq!(msg.signal("Mode").unwrap().describe(2), Some("Current"));
+        assert_eq!(
+            msg.signal("Voltage").unwrap().multiplex,
+            Multiplex::Multiplexed(1)
+        );
+        assert_eq!(
+            msg.signal("SubMode").unwrap().multiplex,
+            Multiplex::MultiplexedMultiplexor(3)
+        );
+        assert_eq!(
+            msg.signal("Deep").unwrap().extended_multiplex,
+            Some(ExtendedMultiplex {
+                switch: "SubMode".into(),
+                ranges: vec![(0, 0), (2, 4)],
+            })
+        );
+
+        let frame = CanFrame::new(ExtendedId::new(0x18FEF1FE).unwrap(), &[1]).unwrap();
+        assert_eq!(db.message_for_frame(&frame).unwrap().name, "Diag");
+        assert_eq!(db.message_by_name("Diag").unwrap().id, msg.id);
+    }
+
+    #[test]
+    fn test_attributes() {
+        let db = Database::parse(DBC).unwrap();
+
+        assert_eq!(
+            db.attributes["BusType"],
+            AttributeValue::String("CAN FD".into())
+        );
+        assert_eq!(
+            db.attribute_default("GenMsgCycleTime"),
+            Some(&AttributeValue::Int(100))
+        );
+        assert_eq!(
+            db.attribute_defs["NodeLayer"].kind,
+            AttributeKind::Enum(vec!["App".into(), "Diag".into()])
+        );
+        assert_eq!(db.attribute_defs["NodeLayer"].object, AttributeObject::Node);
+        assert_eq!(
+            db.node("Dash").unwrap().attributes["NodeLayer"],
+            AttributeValue::Int(1)
+        );
+
+        let msg = db.message_by_name("EngineData").unwrap();
+        assert_eq!(msg.attributes["GenMsgCycleTime"], AttributeValue::Int(20));
+        assert_eq!(
+            msg.signal("Temp").unwrap().attributes["GenSigStartValue"],
+            AttributeValue::Float(40.5)
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = Database::parse("BO_ 256 Msg: 8 Node\n SG_ Sig : 0|8@2+ (1,0) [0|1] \"\" X\n")
+            .unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 2, .. }));
+
+        let err = Database::parse("BO_ 4096 Msg: 8 Node\n").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 1, .. }));
+
+        let err = Database::parse("CM_ \"unterminated;\n").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { .. }));
+
+        assert!(Database::parse("BOGUS_ 1 2 3;").is_err());
+    }
+}
@@ -0,0 +1,751 @@
+// socketcan/src/dbc/parser.rs
+//
+// The parser for Vector DBC files.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! The parser for Vector DBC files.
+//!
+//! The file is first split into tokens (identifiers, numbers, quoted
+//! strings, and punctuation), each tagged with its line number, and then
+//! parsed statement by statement. Statements that refer to a message or
+//! signal apply directly to the database, so they must follow the `BO_`
+//! definitions they refer to, as they always do in files written by the
+//! common tools.
+
+use super::{
+    AttributeDef, AttributeKind, AttributeObject, AttributeValue, Attributes, ByteOrder, Database,
+    ExtendedMultiplex, Message, Multiplex, Node, ParseError, Signal, ValueDescriptions, ValueType,
+    NO_NODE,
+};
+use libc::{canid_t, CAN_EFF_FLAG, CAN_EFF_MASK, CAN_SFF_MASK};
+
+/// Statements which are recognized, but skipped.
+const SKIPPED: &[&str] = &[
+    "EV_",
+    "ENVVAR_DATA_",
+    "SGTYPE_",
+    "SGTYPE_VAL_",
+    "SIG_TYPE_REF_",
+    "SIG_GROUP_",
+    "SIGTYPE_VALTYPE_",
+    "BA_DEF_SGTYPE_",
+    "BA_SGTYPE_",
+    "BA_DEF_REL_",
+    "BA_DEF_DEF_REL_",
+    "BA_REL_",
+    "BU_SG_REL_",
+    "BU_EV_REL_",
+    "BU_BO_REL_",
+    "CAT_DEF_",
+    "CAT_",
+    "FILTER",
+];
+
+// ===== Lexer =====
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    Num(String),
+    Str(String),
+    Punct(char),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    tok: Tok,
+    line: usize,
+}
+
+fn syntax_err(line: usize, msg: impl Into<String>) -> ParseError {
+    ParseError::Syntax {
+        line,
+        msg: msg.into(),
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, ParseError> {
+    let mut toks = Vec::new();
+    let mut chars = s.chars().peekable();
+    let mut line = 1;
+
+    while let Some(c) = chars.next() {
+        let tok = match c {
+            '\n' => {
+                line += 1;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.peek().map_or(false, |&c| c != '\n') {
+                    chars.next();
+                }
+                continue;
+            }
+            '"' => {
+                let start = line;
+                let mut val = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => {
+                                if c == '\n' {
+                                    line += 1;
+                                }
+                                val.push(c)
+                            }
+                            None => return Err(syntax_err(start, "unterminated string")),
+                        },
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            val.push(c)
+                        }
+                        None => return Err(syntax_err(start, "unterminated string")),
+                    }
+                }
+                // Strings can span lines, but are tagged with the starting line
+                toks.push(Token {
+                    tok: Tok::Str(val.replace("\r\n", "\n")),
+                    line: start,
+                });
+                continue;
+            }
+            c if c.is_ascii_digit() => {
+                let mut val = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    let exp = val.ends_with(['e', 'E']);
+                    if c.is_ascii_digit()
+                        || c == '.'
+                        || ((c == 'e' || c == 'E') && !val.contains(['e', 'E']))
+                        || ((c == '+' || c == '-') && exp)
+                    {
+                        val.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                Tok::Num(val)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut val = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    if c.is_alphanumeric() || c == '_' {
+                        val.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                Tok::Ident(val)
+            }
+            c => Tok::Punct(c),
+        };
+        toks.push(Token { tok, line });
+    }
+    Ok(toks)
+}
+
+// ===== Parser =====
+
+struct Parser {
+    toks: Vec<Token>,
+    pos: usize,
+    db: Database,
+    // The ID of the message for subsequent `SG_` lines, or None if the
+    // message is being dropped.
+    cur_msg: Option<canid_t>,
+}
+
+/// Parses the contents of a DBC file into a database.
+pub(super) fn parse(s: &str) -> Result<Database, ParseError> {
+    let mut p = Parser {
+        toks: tokenize(s)?,
+        pos: 0,
+        db: Database::default(),
+        cur_msg: None,
+    };
+    while p.pos < p.toks.len() {
+        p.statement()?;
+    }
+    Ok(p.db)
+}
+
+impl Parser {
+    // ----- Token helpers -----
+
+    fn peek(&self) -> Option<&Tok> {
+        self.toks.get(self.pos).map(|t| &t.tok)
+    }
+
+    fn line(&self) -> usize {
+        self.toks
+            .get(self.pos)
+            .or_else(|| self.toks.last())
+            .map_or(1, |t| t.line)
+    }
+
+    fn err(&self, msg: impl Into<String>) -> ParseError {
+        syntax_err(self.line(), msg)
+    }
+
+    fn next(&mut self) -> Result<Tok, ParseError> {
+        let tok = self
+            .toks
+            .get(self.pos)
+            .map(|t| t.tok.clone())
+            .ok_or_else(|| self.err("unexpected end of file"))?;
+        self.pos += 1;
+        Ok(tok)
+    }
+
+    fn is_punct(&self, c: char) -> bool {
+        self.peek() == Some(&Tok::Punct(c))
+    }
+
+    fn eat_punct(&mut self, c: char) -> bool {
+        let found = self.is_punct(c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn punct(&mut self, c: char) -> Result<(), ParseError> {
+        if self.eat_punct(c) {
+            Ok(())
+        } else {
+            Err(self.err(format!("expected '{}'", c)))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some(Tok::Ident(_)) => match self.next()? {
+                Tok::Ident(s) => Ok(s),
+                _ => unreachable!(),
+            },
+            _ => Err(self.err("expected an identifier")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some(Tok::Str(_)) => match self.next()? {
+                Tok::Str(s) => Ok(s),
+                _ => unreachable!(),
+            },
+            _ => Err(self.err("expected a string")),
+        }
+    }
+
+    /// Reads a number, with an optional sign, as a string.
+    fn number(&mut self) -> Result<String, ParseError> {
+        let neg = if self.eat_punct('-') {
+            true
+        } else {
+            self.eat_punct('+');
+            false
+        };
+        match self.peek() {
+            Some(Tok::Num(_)) => match self.next()? {
+                Tok::Num(s) if neg => Ok(format!("-{}", s)),
+                Tok::Num(s) => Ok(s),
+                _ => unreachable!(),
+            },
+            _ => Err(self.err("expected a number")),
+        }
+    }
+
+    fn float(&mut self) -> Result<f64, ParseError> {
+        let s = self.number()?;
+        s.parse()
+            .map_err(|_| self.err(format!("invalid number '{}'", s)))
+    }
+
+    fn int(&mut self) -> Result<i64, ParseError> {
+        let s = self.number()?;
+        s.parse()
+            .map_err(|_| self.err(format!("invalid integer '{}'", s)))
+    }
+
+    fn uint(&mut self) -> Result<u64, ParseError> {
+        match self.peek() {
+            Some(Tok::Num(_)) => {
+                let s = self.number()?;
+                s.parse()
+                    .map_err(|_| self.err(format!("invalid integer '{}'", s)))
+            }
+            _ => Err(self.err("expected an unsigned integer")),
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32, ParseError> {
+        let n = self.uint()?;
+        u32::try_from(n).map_err(|_| self.err(format!("value out of range: {}", n)))
+    }
+
+    /// Reads a message ID, validating the range for a standard ID.
+    fn msg_id(&mut self) -> Result<canid_t, ParseError> {
+        let id = self.u32()?;
+        if id & CAN_EFF_FLAG == 0 && id > CAN_SFF_MASK {
+            return Err(self.err(format!("invalid standard ID: {}", id)));
+        }
+        Ok(id)
+    }
+
+    /// Skips the remainder of a statement, up to and including the `;`.
+    fn skip_statement(&mut self) -> Result<(), ParseError> {
+        while self.next()? != Tok::Punct(';') {}
+        Ok(())
+    }
+
+    /// Skips the remaining tokens on the specified line.
+    fn skip_line(&mut self, line: usize) {
+        while self.pos < self.toks.len() && self.toks[self.pos].line == line {
+            self.pos += 1;
+        }
+    }
+
+    /// Reads the identifiers on the specified line, optionally separated
+    /// by commas.
+    fn idents_on_line(&mut self, line: usize) -> Result<Vec<String>, ParseError> {
+        let mut v = Vec::new();
+        while self.pos < self.toks.len() && self.toks[self.pos].line == line {
+            if !self.eat_punct(',') {
+                v.push(self.ident()?);
+            }
+        }
+        Ok(v)
+    }
+
+    /// Reads a list of value descriptions, up to and including the `;`.
+    fn value_descriptions(&mut self) -> Result<ValueDescriptions, ParseError> {
+        let mut vals = ValueDescriptions::new();
+        while !self.eat_punct(';') {
+            let val = self.int()?;
+            vals.insert(val, self.string()?);
+        }
+        Ok(vals)
+    }
+
+    // ----- Database lookups -----
+
+    fn node_mut(&mut self, name: &str) -> Option<&mut Node> {
+        self.db.nodes.iter_mut().find(|node| node.name == name)
+    }
+
+    fn signal_mut(&mut self, id: canid_t, name: &str) -> Option<&mut Signal> {
+        self.db.messages.get_mut(&id)?.signal_mut(name)
+    }
+
+    // ----- Statements -----
+
+    fn statement(&mut self) -> Result<(), ParseError> {
+        let line = self.line();
+        let kw = self.ident()?;
+
+        match kw.as_str() {
+            "VERSION" => self.db.version = self.string()?,
+            "NS_" => {
+                self.eat_punct(':');
+                while let Some(tok) = self.peek() {
+                    match tok {
+                        Tok::Ident(s) if s == "BS_" || s == "BU_" || s == "BO_" => break,
+                        _ => self.pos += 1,
+                    }
+                }
+            }
+            "BS_" => self.skip_line(line),
+            "BU_" => {
+                self.punct(':')?;
+                for name in self.idents_on_line(line)? {
+                    self.db.nodes.push(Node {
+                        name,
+                        ..Node::default()
+                    });
+                }
+            }
+            "VAL_TABLE_" => {
+                let name = self.ident()?;
+                let vals = self.value_descriptions()?;
+                self.db.value_tables.insert(name, vals);
+            }
+            "BO_" => self.message()?,
+            "SG_" => self.signal()?,
+            "BO_TX_BU_" => {
+                let id = self.u32()?;
+                self.punct(':')?;
+                let mut senders = Vec::new();
+                while !self.eat_punct(';') {
+                    if !self.eat_punct(',') {
+                        senders.push(self.ident()?);
+                    }
+                }
+                if let Some(msg) = self.db.messages.get_mut(&id) {
+                    msg.senders = senders;
+                }
+            }
+            "CM_" => self.comment()?,
+            "BA_DEF_" => self.attribute_def()?,
+            "BA_DEF_DEF_" => {
+                let name = self.string()?;
+                let val = self.attribute_value(&name)?;
+                self.punct(';')?;
+                if let Some(def) = self.db.attribute_defs.get_mut(&name) {
+                    def.default = Some(val);
+                }
+            }
+            "BA_" => self.attribute()?,
+            "VAL_" => {
+                if matches!(self.peek(), Some(Tok::Ident(_))) {
+                    // Values for an environment variable
+                    self.skip_statement()?;
+                } else {
+                    let id = self.u32()?;
+                    let name = self.ident()?;
+                    let vals = self.value_descriptions()?;
+                    if let Some(sig) = self.signal_mut(id, &name) {
+                        sig.value_descriptions = vals;
+                    }
+                }
+            }
+            "SIG_VALTYPE_" => {
+                let id = self.u32()?;
+                let name = self.ident()?;
+                self.eat_punct(':');
+                let value_type = match self.uint()? {
+                    0 => None,
+                    1 => Some(ValueType::Float32),
+                    2 => Some(ValueType::Float64),
+                    n => return Err(self.err(format!("invalid signal value type: {}", n))),
+                };
+                self.punct(';')?;
+                if let (Some(sig), Some(value_type)) = (self.signal_mut(id, &name), value_type) {
+                    sig.value_type = value_type;
+                }
+            }
+            "SG_MUL_VAL_" => {
+                let id = self.u32()?;
+                let name = self.ident()?;
+                let switch = self.ident()?;
+                let mut ranges = Vec::new();
+                while !self.eat_punct(';') {
+                    if !self.eat_punct(',') {
+                        let lo = self.uint()?;
+                        self.punct('-')?;
+                        ranges.push((lo, self.uint()?));
+                    }
+                }
+                if let Some(sig) = self.signal_mut(id, &name) {
+                    sig.extended_multiplex = Some(ExtendedMultiplex { switch, ranges });
+                }
+            }
+            kw if SKIPPED.contains(&kw) => self.skip_statement()?,
+            kw => return Err(syntax_err(line, format!("unknown keyword '{}'", kw))),
+        }
+
+        if kw != "SG_" && kw != "BO_" {
+            self.cur_msg = None;
+        }
+        Ok(())
+    }
+
+    /// Parses a message definition:
+    /// `BO_ <id> <name>: <size> <transmitter>`
+    fn message(&mut self) -> Result<(), ParseError> {
+        let id = self.msg_id()?;
+        let name = self.ident()?;
+        self.punct(':')?;
+        let size = self.u32()?;
+        let transmitter = Some(self.ident()?).filter(|s| s != NO_NODE);
+
+        // Pseudo-messages, like the one used by some tools to hold
+        // unassigned signals, use the RTR or ERR bits of the ID.
+        if id & !(CAN_EFF_FLAG | CAN_EFF_MASK) != 0 {
+            self.cur_msg = None;
+            return Ok(());
+        }
+
+        self.cur_msg = Some(id);
+        self.db.messages.insert(
+            id,
+            Message {
+                id,
+                name,
+                size,
+                transmitter,
+                senders: Vec::new(),
+                signals: Vec::new(),
+                comment: None,
+                attributes: Attributes::new(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Parses a signal definition:
+    /// `SG_ <name> [mux] : <start>|<size>@<order><sign> (<factor>,<offset>)
+    ///    [<min>|<max>] "<unit>" <receivers>`
+    fn signal(&mut self) -> Result<(), ParseError> {
+        let name = self.ident()?;
+
+        let multiplex = if self.is_punct(':') {
+            Multiplex::None
+        } else {
+            let mux = self.ident()?;
+            parse_multiplex(&mux)
+                .ok_or_else(|| self.err(format!("invalid multiplexer '{}'", mux)))?
+        };
+        self.punct(':')?;
+
+        let start_bit = self.u32()?;
+        self.punct('|')?;
+        let size = self.u32()?;
+        self.punct('@')?;
+        let byte_order = match self.uint()? {
+            0 => ByteOrder::BigEndian,
+            1 => ByteOrder::LittleEndian,
+            n => return Err(self.err(format!("invalid byte order: {}", n))),
+        };
+        let value_type = if self.eat_punct('-') {
+            ValueType::Signed
+        } else {
+            self.punct('+')?;
+            ValueType::Unsigned
+        };
+
+        self.punct('(')?;
+        let factor = self.float()?;
+        self.punct(',')?;
+        let offset = self.float()?;
+        self.punct(')')?;
+        self.punct('[')?;
+        let min = self.float()?;
+        self.punct('|')?;
+        let max = self.float()?;
+        self.punct(']')?;
+
+        let line = self.line();
+        let unit = self.string()?;
+        let receivers = self
+            .idents_on_line(line)?
+            .into_iter()
+            .filter(|s| s != NO_NODE)
+            .collect();
+
+        let sig = Signal {
+            name,
+            start_bit,
+            size,
+            byte_order,
+            value_type,
+            factor,
+            offset,
+            min,
+            max,
+            unit,
+            receivers,
+            multiplex,
+            extended_multiplex: None,
+            value_descriptions: ValueDescriptions::new(),
+            comment: None,
+            attributes: Attributes::new(),
+        };
+
+        if let Some(msg) = self.cur_msg.and_then(|id| self.db.messages.get_mut(&id)) {
+            msg.signals.push(sig);
+        }
+        Ok(())
+    }
+
+    /// Parses a comment for the network or an object.
+    fn comment(&mut self) -> Result<(), ParseError> {
+        let line = self.line();
+        let obj = match self.peek() {
+            Some(Tok::Ident(_)) => Some(self.ident()?),
+            _ => None,
+        };
+
+        match obj.as_deref() {
+            None => self.db.comment = Some(self.string()?),
+            Some("BU_") => {
+                let name = self.ident()?;
+                let cmt = self.string()?;
+                if let Some(node) = self.node_mut(&name) {
+                    node.comment = Some(cmt);
+                }
+            }
+            Some("BO_") => {
+                let id = self.u32()?;
+                let cmt = self.string()?;
+                if let Some(msg) = self.db.messages.get_mut(&id) {
+                    msg.comment = Some(cmt);
+                }
+            }
+            Some("SG_") => {
+                let id = self.u32()?;
+                let name = self.ident()?;
+                let cmt = self.string()?;
+                if let Some(sig) = self.signal_mut(id, &name) {
+                    sig.comment = Some(cmt);
+                }
+            }
+            Some("EV_") => {
+                self.ident()?;
+                self.string()?;
+            }
+            Some(s) => return Err(syntax_err(line, format!("invalid comment type '{}'", s))),
+        }
+        self.punct(';')
+    }
+
+    /// Parses an attribute definition.
+    fn attribute_def(&mut self) -> Result<(), ParseError> {
+        let object = match self.peek() {
+            Some(Tok::Ident(s)) => {
+                let obj = match s.as_str() {
+                    "BU_" => AttributeObject::Node,
+                    "BO_" => AttributeObject::Message,
+                    "SG_" => AttributeObject::Signal,
+                    "EV_" => AttributeObject::EnvVar,
+                    s => return Err(self.err(format!("invalid attribute object '{}'", s))),
+                };
+                self.pos += 1;
+                obj
+            }
+            _ => AttributeObject::Network,
+        };
+        let name = self.string()?;
+
+        let kind = match self.ident()?.as_str() {
+            "INT" => AttributeKind::Int(self.int()?, self.int()?),
+            "HEX" => AttributeKind::Hex(self.int()?, self.int()?),
+            "FLOAT" => AttributeKind::Float(self.float()?, self.float()?),
+            "STRING" => AttributeKind::String,
+            "ENUM" => {
+                let mut names = Vec::new();
+                while !self.is_punct(';') {
+                    if !self.eat_punct(',') {
+                        names.push(self.string()?);
+                    }
+                }
+                AttributeKind::Enum(names)
+            }
+            s => return Err(self.err(format!("invalid attribute type '{}'", s))),
+        };
+        self.punct(';')?;
+
+        self.db.attribute_defs.insert(
+            name,
+            AttributeDef {
+                object,
+                kind,
+                default: None,
+            },
+        );
+        Ok(())
+    }
+
+    /// Parses an attribute value, converting it to the type from the
+    /// definition, if there is one.
+    fn attribute_value(&mut self, name: &str) -> Result<AttributeValue, ParseError> {
+        let kind = self.db.attribute_defs.get(name).map(|def| def.kind.clone());
+
+        let val = match self.peek() {
+            Some(Tok::Str(_)) => {
+                let s = self.string()?;
+                match kind {
+                    Some(AttributeKind::Enum(names)) => match names.iter().position(|n| *n == s) {
+                        Some(i) => AttributeValue::Int(i as i64),
+                        None => AttributeValue::String(s),
+                    },
+                    _ => AttributeValue::String(s),
+                }
+            }
+            _ => {
+                let s = self.number()?;
+                let float = match kind {
+                    Some(AttributeKind::Float(..)) => true,
+                    Some(_) => false,
+                    None => s.contains(['.', 'e', 'E']),
+                };
+                if float {
+                    AttributeValue::Float(
+                        s.parse()
+                            .map_err(|_| self.err(format!("invalid number '{}'", s)))?,
+                    )
+                } else {
+                    AttributeValue::Int(
+                        s.parse()
+                            .map_err(|_| self.err(format!("invalid integer '{}'", s)))?,
+                    )
+                }
+            }
+        };
+        Ok(val)
+    }
+
+    /// Parses an attribute value for the network or an object.
+    fn attribute(&mut self) -> Result<(), ParseError> {
+        let name = self.string()?;
+
+        let target = match self.peek() {
+            Some(Tok::Ident(_)) => match self.ident()?.as_str() {
+                "BU_" => Target::Node(self.ident()?),
+                "BO_" => Target::Message(self.u32()?),
+                "SG_" => Target::Signal(self.u32()?, self.ident()?),
+                "EV_" => {
+                    self.ident()?;
+                    Target::EnvVar
+                }
+                s => return Err(self.err(format!("invalid attribute object '{}'", s))),
+            },
+            _ => Target::Network,
+        };
+        let val = self.attribute_value(&name)?;
+        self.punct(';')?;
+
+        let attrs = match target {
+            Target::Network => Some(&mut self.db.attributes),
+            Target::Node(node) => self.node_mut(&node).map(|node| &mut node.attributes),
+            Target::Message(id) => self.db.messages.get_mut(&id).map(|msg| &mut msg.attributes),
+            Target::Signal(id, sig) => self.signal_mut(id, &sig).map(|sig| &mut sig.attributes),
+            Target::EnvVar => None,
+        };
+        if let Some(attrs) = attrs {
+            attrs.insert(name, val);
+        }
+        Ok(())
+    }
+}
+
+/// The object that an attribute value applies to.
+enum Target {
+    Network,
+    Node(String),
+    Message(canid_t),
+    Signal(canid_t, String),
+    EnvVar,
+}
+
+/// Parses the multiplex indicator of a signal: `M`, `m<n>`, or `m<n>M`
+fn parse_multiplex(s: &str) -> Option<Multiplex> {
+    if s == "M" {
+        return Some(Multiplex::Multiplexor);
+    }
+    let s = s.strip_prefix('m')?;
+    match s.strip_suffix('M') {
+        Some(n) => n.parse().ok().map(Multiplex::MultiplexedMultiplexor),
+        None => s.parse().ok().map(Multiplex::Multiplexed),
+    }
+}
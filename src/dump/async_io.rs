@@ -0,0 +1,154 @@
+// socketcan/src/dump/async_io.rs
+//
+// Implements asynchronous candump format parsing for async-io runtimes.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! Asynchronous candump parsing for async-io based runtimes.
+//!
+//! This works with any reader that implements the `futures` I/O traits,
+//! which includes the types from [async-std](https://crates.io/crates/async-std)
+//! and [smol](https://crates.io/crates/smol). It can be polled for
+//! individual records, or used as a `Stream`:
+//!
+//! ```no_run
+//! use async_std::net::TcpStream;
+//! use futures::stream::StreamExt;
+//! use socketcan::dump::async_io::Reader;
+//!
+//! #[async_std::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let mut reader = Reader::from_reader(TcpStream::connect("logger:28700").await?);
+//!
+//!     while let Some(rec) = reader.next().await {
+//!         let (t_us, frame) = rec?;
+//!         println!("{}: {:X}", t_us, frame);
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use super::{CanDumpRecord, LineBuffer, ParseError};
+use crate::CanAnyFrame;
+use futures::{
+    io::{AsyncBufRead, AsyncRead, BufReader},
+    ready, Stream,
+};
+use std::{
+    future, io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// An asynchronous CAN log reader for async-io based runtimes.
+#[derive(Debug)]
+pub struct Reader<R> {
+    rdr: R,
+    line: LineBuffer,
+}
+
+impl<R: AsyncRead + Unpin> Reader<R> {
+    /// Creates an I/O buffered reader from an asynchronous CAN log reader.
+    pub fn from_reader(rdr: R) -> Reader<BufReader<R>> {
+        Reader::new(BufReader::new(rdr))
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> Reader<R> {
+    /// Creates a reader from an already-buffered asynchronous reader.
+    pub fn new(rdr: R) -> Self {
+        Self {
+            rdr,
+            line: LineBuffer::default(),
+        }
+    }
+
+    /// Polls to read the next full line into the line buffer.
+    ///
+    /// This resolves to `false` at EOF. A partial line is kept in the
+    /// buffer across calls, so the read can be safely cancelled.
+    fn poll_line(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<bool>> {
+        loop {
+            let avail = ready!(Pin::new(&mut self.rdr).poll_fill_buf(cx))?;
+            let (n, line) = self.line.fill(avail);
+            Pin::new(&mut self.rdr).consume(n);
+            if let Some(line) = line {
+                return Poll::Ready(Ok(line));
+            }
+        }
+    }
+
+    /// Advance state, returning next record.
+    ///
+    /// The record borrows the device name from the reader's line buffer, so
+    /// no allocation is made per line.
+    pub async fn next_record(&mut self) -> Result<Option<CanDumpRecord<'_>>, ParseError> {
+        if !future::poll_fn(|cx| self.poll_line(cx)).await? {
+            return Ok(None);
+        }
+        self.line.record().map(Some)
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> Stream for Reader<R> {
+    type Item = Result<(u64, CanAnyFrame), ParseError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let res = ready!(this.poll_line(cx));
+        Poll::Ready(this.line.stream_item(res))
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EmbeddedFrame, Frame};
+    use futures::StreamExt;
+
+    const INPUT: &[u8] = b"(1469439874.299591) can1 080#\n\
+                           (1469439874.299654) can1 701#7F\n\
+                           (1469439874.299700) can0 123##1DEADBEEF";
+
+    #[async_std::test]
+    async fn test_next_record() {
+        let mut reader = Reader::from_reader(INPUT);
+
+        let rec = reader.next_record().await.unwrap().unwrap();
+        assert_eq!(rec.t_us, 1469439874299591);
+        assert_eq!(rec.device, "can1");
+        assert!(matches!(rec.frame, CanAnyFrame::Normal(frame) if frame.raw_id() == 0x080));
+
+        let rec = reader.next_record().await.unwrap().unwrap();
+        assert!(matches!(rec.frame, CanAnyFrame::Normal(frame) if frame.data() == [0x7F]));
+
+        let rec = reader.next_record().await.unwrap().unwrap();
+        assert_eq!(rec.device, "can0");
+        assert!(matches!(rec.frame, CanAnyFrame::Fd(_)));
+
+        assert!(reader.next_record().await.unwrap().is_none());
+    }
+
+    #[async_std::test]
+    async fn test_stream() {
+        let reader = Reader::from_reader(INPUT);
+        let recs: Vec<_> = reader.map(|rec| rec.unwrap()).collect().await;
+
+        assert_eq!(recs.len(), 3);
+        assert_eq!(recs[1].0, 1469439874299654);
+        match recs[2].1 {
+            CanAnyFrame::Fd(frame) => {
+                assert!(frame.is_brs());
+                assert_eq!(frame.data(), &[0xDE, 0xAD, 0xBE, 0xEF]);
+            }
+            _ => panic!("Expected FD frame"),
+        }
+    }
+}
@@ -0,0 +1,543 @@
+// socketcan/src/dump/compress.rs
+//
+// Implements transparent compression for CAN log files.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! Transparent compression for CAN log files.
+//!
+//! Log files can be compressed with gzip, zstd, or xz, each of which is
+//! enabled by the crate feature of the same name. When reading, the format
+//! is detected from the magic bytes at the start of the file, so a
+//! [`LogFile`] can be opened without knowing how it was written. When
+//! writing, the format is normally chosen from the file extension
+//! (`.gz`, `.zst`, or `.xz`).
+//!
+//! A compressed file can't be seeked directly. Instead, a `LogFile` keeps
+//! track of the position in the decompressed data and seeks by decoding
+//! forward, restarting from the beginning of the file to go backward. This
+//! lets an [`Index`](super::Index) and `seek_to_time()` work the same on
+//! compressed logs, although each seek costs more than on a plain file.
+
+use std::{
+    fmt, fs,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// The compression format of a log file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Plain, uncompressed text
+    #[default]
+    None,
+    /// gzip (requires the "gzip" feature)
+    Gzip,
+    /// Zstandard (requires the "zstd" feature)
+    Zstd,
+    /// xz (requires the "xz" feature)
+    Xz,
+}
+
+impl Compression {
+    /// Detects the compression format from the first bytes of a file.
+    ///
+    /// Anything that doesn't start with a known magic number is assumed to
+    /// be plain text.
+    pub fn from_magic(buf: &[u8]) -> Self {
+        use Compression::*;
+        match buf {
+            [0x1F, 0x8B, ..] => Gzip,
+            [0x28, 0xB5, 0x2F, 0xFD, ..] => Zstd,
+            [0xFD, b'7', b'z', b'X', b'Z', 0x00, ..] => Xz,
+            _ => None,
+        }
+    }
+
+    /// Gets the compression format from the extension of a file name.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        use Compression::*;
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Gzip,
+            Some("zst") => Zstd,
+            Some("xz") => Xz,
+            _ => None,
+        }
+    }
+
+    /// Gets the usual file extension for the format, if any.
+    pub fn extension(&self) -> Option<&'static str> {
+        use Compression::*;
+        match self {
+            None => Option::None,
+            Gzip => Some("gz"),
+            Zstd => Some("zst"),
+            Xz => Some("xz"),
+        }
+    }
+
+    /// Determines if support for the format was compiled into the library.
+    pub fn is_supported(&self) -> bool {
+        use Compression::*;
+        match self {
+            None => true,
+            Gzip => cfg!(feature = "gzip"),
+            Zstd => cfg!(feature = "zstd"),
+            Xz => cfg!(feature = "xz"),
+        }
+    }
+
+    // Gets an error for a format that isn't compiled in.
+    #[allow(dead_code)]
+    fn unsupported(&self) -> io::Error {
+        let feature = self.extension().unwrap_or_default();
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("compressed log support requires the '{}' feature", feature),
+        )
+    }
+}
+
+// ===== LogFile =====
+
+// The decoder for a log file being read.
+#[allow(clippy::large_enum_variant)]
+enum Decoder {
+    Plain(fs::File),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::read::MultiGzDecoder<io::BufReader<fs::File>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::read::Decoder<'static, io::BufReader<fs::File>>),
+    #[cfg(feature = "xz")]
+    Xz(xz2::read::XzDecoder<io::BufReader<fs::File>>),
+}
+
+impl Decoder {
+    // Creates a decoder for a file positioned at the start.
+    fn new(file: fs::File, compression: Compression) -> io::Result<Self> {
+        #[allow(unused_imports)]
+        use io::BufReader;
+        match compression {
+            Compression::None => Ok(Decoder::Plain(file)),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Ok(Decoder::Gzip(flate2::read::MultiGzDecoder::new(
+                BufReader::new(file),
+            ))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Decoder::Zstd(zstd::stream::read::Decoder::new(file)?)),
+            #[cfg(feature = "xz")]
+            Compression::Xz => Ok(Decoder::Xz(xz2::read::XzDecoder::new_multi_decoder(
+                BufReader::new(file),
+            ))),
+            #[allow(unreachable_patterns)]
+            _ => Err(compression.unsupported()),
+        }
+    }
+}
+
+impl Read for Decoder {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Decoder::Plain(file) => file.read(buf),
+            #[cfg(feature = "gzip")]
+            Decoder::Gzip(dec) => dec.read(buf),
+            #[cfg(feature = "zstd")]
+            Decoder::Zstd(dec) => dec.read(buf),
+            #[cfg(feature = "xz")]
+            Decoder::Xz(dec) => dec.read(buf),
+        }
+    }
+}
+
+/// A log file opened for reading, which may be compressed.
+///
+/// This reads the decompressed contents of the file. See the
+/// [module documentation](self) for how seeking works on a compressed file.
+pub struct LogFile {
+    // The file, kept to restart the decoder
+    file: fs::File,
+    // The format of the file
+    compression: Compression,
+    // The decoder reading from a clone of the file
+    dec: Decoder,
+    // The position in the decompressed data
+    pos: u64,
+}
+
+impl LogFile {
+    /// Opens a log file, detecting the compression from its contents.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut file = fs::File::open(path)?;
+
+        let mut magic = [0u8; 6];
+        let mut n = 0;
+        while n < magic.len() {
+            match file.read(&mut magic[n..])? {
+                0 => break,
+                k => n += k,
+            }
+        }
+        file.rewind()?;
+
+        let compression = Compression::from_magic(&magic[..n]);
+        let dec = Decoder::new(file.try_clone()?, compression)?;
+        Ok(Self {
+            file,
+            compression,
+            dec,
+            pos: 0,
+        })
+    }
+
+    /// Gets the compression format of the file.
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    // Restarts decoding from the beginning of the file.
+    fn restart(&mut self) -> io::Result<()> {
+        let mut file = self.file.try_clone()?;
+        file.rewind()?;
+        self.dec = Decoder::new(file, self.compression)?;
+        self.pos = 0;
+        Ok(())
+    }
+}
+
+impl Read for LogFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.dec.read(buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for LogFile {
+    /// Seeks to a position in the decompressed data.
+    ///
+    /// Seeking from the end is only possible for plain files.
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        #[allow(irrefutable_let_patterns)]
+        if let Decoder::Plain(file) = &mut self.dec {
+            self.pos = file.seek(pos)?;
+            return Ok(self.pos);
+        }
+
+        let target = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::Current(off) if off < 0 => self.pos.checked_sub(off.unsigned_abs()),
+            SeekFrom::Current(off) => self.pos.checked_add(off as u64),
+            SeekFrom::End(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "can't seek from the end of a compressed log",
+                ))
+            }
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid seek position"))?;
+
+        if target < self.pos {
+            self.restart()?;
+        }
+        let n = target - self.pos;
+        io::copy(&mut self.by_ref().take(n), &mut io::sink())?;
+        Ok(self.pos)
+    }
+}
+
+impl fmt::Debug for LogFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LogFile")
+            .field("file", &self.file)
+            .field("compression", &self.compression)
+            .field("pos", &self.pos)
+            .finish()
+    }
+}
+
+// ===== LogWriter =====
+
+// The encoder for a log file being written.
+#[allow(clippy::large_enum_variant)]
+enum Encoder {
+    Plain(io::BufWriter<fs::File>),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<fs::File>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, fs::File>),
+    #[cfg(feature = "xz")]
+    Xz(xz2::write::XzEncoder<fs::File>),
+}
+
+impl Encoder {
+    // Creates an encoder writing to the file.
+    fn new(file: fs::File, compression: Compression) -> io::Result<Self> {
+        match compression {
+            Compression::None => Ok(Encoder::Plain(io::BufWriter::new(file))),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Ok(Encoder::Gzip(flate2::write::GzEncoder::new(
+                file,
+                flate2::Compression::default(),
+            ))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Encoder::Zstd(zstd::stream::write::Encoder::new(
+                file,
+                zstd::DEFAULT_COMPRESSION_LEVEL,
+            )?)),
+            #[cfg(feature = "xz")]
+            Compression::Xz => Ok(Encoder::Xz(xz2::write::XzEncoder::new(file, 6))),
+            #[allow(unreachable_patterns)]
+            _ => Err(compression.unsupported()),
+        }
+    }
+
+    // Completes the compressed stream, returning the file.
+    fn finish(self) -> io::Result<fs::File> {
+        match self {
+            Encoder::Plain(wtr) => wtr.into_inner().map_err(|err| err.into_error()),
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(enc) => enc.finish(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(enc) => enc.finish(),
+            #[cfg(feature = "xz")]
+            Encoder::Xz(enc) => enc.finish(),
+        }
+    }
+
+    // Gets the file being written.
+    fn file(&self) -> &fs::File {
+        match self {
+            Encoder::Plain(wtr) => wtr.get_ref(),
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(enc) => enc.get_ref(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(enc) => enc.get_ref(),
+            #[cfg(feature = "xz")]
+            Encoder::Xz(enc) => enc.get_ref(),
+        }
+    }
+
+    // Gets the writer for the encoder.
+    fn as_write(&mut self) -> &mut dyn Write {
+        match self {
+            Encoder::Plain(wtr) => wtr,
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(enc) => enc,
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(enc) => enc,
+            #[cfg(feature = "xz")]
+            Encoder::Xz(enc) => enc,
+        }
+    }
+}
+
+/// A log file opened for writing, which may be compressed.
+///
+/// The compressed stream is completed when the writer is dropped, but any
+/// error doing so is lost. Call [`finish()`](LogWriter::finish) to check
+/// that the file was written successfully.
+pub struct LogWriter {
+    // The compression format
+    compression: Compression,
+    // The encoder, which is only `None` after it's finished
+    enc: Option<Encoder>,
+}
+
+impl LogWriter {
+    /// Creates a log file, choosing the compression from its extension.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let compression = Compression::from_path(&path);
+        Self::create_with(path, compression)
+    }
+
+    /// Creates a log file with the specified compression.
+    pub fn create_with<P: AsRef<Path>>(path: P, compression: Compression) -> io::Result<Self> {
+        if !compression.is_supported() {
+            return Err(compression.unsupported());
+        }
+        let enc = Encoder::new(fs::File::create(path)?, compression)?;
+        Ok(Self {
+            compression,
+            enc: Some(enc),
+        })
+    }
+
+    /// Creates a new log file with the specified compression, failing with
+    /// `AlreadyExists` rather than truncating an existing file.
+    pub(crate) fn create_new<P: AsRef<Path>>(
+        path: P,
+        compression: Compression,
+    ) -> io::Result<Self> {
+        if !compression.is_supported() {
+            return Err(compression.unsupported());
+        }
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        Ok(Self {
+            compression,
+            enc: Some(Encoder::new(file, compression)?),
+        })
+    }
+
+    /// Gets the compression format of the file.
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Gets a reference to the underlying file.
+    pub fn get_ref(&self) -> &fs::File {
+        self.encoder().file()
+    }
+
+    /// Flushes all the data written so far through to the disk.
+    ///
+    /// For a compressed file, this flushes the compressor, which may cost
+    /// some compression ratio if done frequently.
+    pub fn sync_data(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.get_ref().sync_data()
+    }
+
+    /// Completes the compressed stream and flushes it to the file.
+    pub fn finish(mut self) -> io::Result<fs::File> {
+        self.enc
+            .take()
+            .expect("log writer already finished")
+            .finish()
+    }
+
+    fn encoder(&self) -> &Encoder {
+        self.enc.as_ref().expect("log writer already finished")
+    }
+
+    fn encoder_mut(&mut self) -> &mut Encoder {
+        self.enc.as_mut().expect("log writer already finished")
+    }
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoder_mut().as_write().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder_mut().as_write().flush()
+    }
+}
+
+impl Drop for LogWriter {
+    fn drop(&mut self) {
+        if let Some(enc) = self.enc.take() {
+            let _ = enc.finish();
+        }
+    }
+}
+
+impl fmt::Debug for LogWriter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LogWriter")
+            .field("compression", &self.compression)
+            .finish()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dump::{Index, Reader, Writer};
+    use crate::{CanAnyFrame, CanFrame, EmbeddedFrame, StandardId};
+
+    // Gets a path for a temporary log file.
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("socketcan-{}-{}", std::process::id(), name))
+    }
+
+    // Writes a log, then reads it back, seeking into the middle
+    fn roundtrip(name: &str, compression: Compression) {
+        let path = temp_path(name);
+        assert_eq!(Compression::from_path(&path), compression);
+
+        let mut wtr = Writer::create(&path).unwrap();
+        for i in 0..100u16 {
+            let frame = CanFrame::new(StandardId::new(i).unwrap(), &[i as u8]).unwrap();
+            wtr.write_record(1_000_000 + u64::from(i), "can0", &frame.into())
+                .unwrap();
+        }
+        wtr.finish().unwrap();
+
+        let mut magic = [0u8; 6];
+        fs::File::open(&path)
+            .unwrap()
+            .read_exact(&mut magic)
+            .unwrap();
+        assert_eq!(Compression::from_magic(&magic), compression);
+
+        let mut rdr = Reader::from_log_file(&path).unwrap();
+        assert_eq!(rdr.records().count(), 100);
+
+        let index = Index::from_file(&path, 256).unwrap();
+        rdr.seek_to_time(&index, 1_000_050).unwrap();
+        let rec = rdr.next_record().unwrap().unwrap();
+        assert_eq!(rec.t_us, 1_000_050);
+        assert!(matches!(rec.frame, CanAnyFrame::Normal(frame) if frame.data() == [50]));
+
+        rdr.seek_to_time(&index, 1_000_010).unwrap();
+        assert_eq!(rdr.next_record().unwrap().unwrap().t_us, 1_000_010);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_from_magic() {
+        assert_eq!(
+            Compression::from_magic(b"(1469439874.299591)"),
+            Compression::None
+        );
+        assert_eq!(Compression::from_magic(&[0x1F, 0x8B, 8]), Compression::Gzip);
+        assert_eq!(
+            Compression::from_magic(&[0x28, 0xB5, 0x2F, 0xFD]),
+            Compression::Zstd
+        );
+        assert_eq!(Compression::from_magic(b"\xFD7zXZ\x00"), Compression::Xz);
+        assert_eq!(Compression::from_magic(&[0x1F]), Compression::None);
+    }
+
+    #[test]
+    fn test_plain() {
+        roundtrip("plain.log", Compression::None);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip() {
+        roundtrip("log.gz", Compression::Gzip);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd() {
+        roundtrip("log.zst", Compression::Zstd);
+    }
+
+    #[cfg(feature = "xz")]
+    #[test]
+    fn test_xz() {
+        roundtrip("log.xz", Compression::Xz);
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn test_unsupported() {
+        let err = LogWriter::create(temp_path("unsupported.zst")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+}
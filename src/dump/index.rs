@@ -0,0 +1,362 @@
+// socketcan/src/dump/index.rs
+//
+// Implements a time index over CAN log files.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! Time index for CAN log files.
+//!
+//! An [`Index`] is a sparse map from timestamps to byte offsets in a log
+//! file. It records the position of the first line at roughly every
+//! `interval` bytes, so it stays small even for very large logs, and a
+//! lookup only needs to scan forward a short distance to land on an exact
+//! record.
+//!
+//! The index is normally kept in a sidecar file next to the log, named by
+//! appending `.idx` to the log file name. It is rebuilt automatically by
+//! [`Index::load_or_build`] when the log file has changed size.
+//!
+//! ```no_run
+//! use socketcan::dump::{Index, Reader, DEFAULT_INDEX_INTERVAL};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let index = Index::load_or_build("candump.log", DEFAULT_INDEX_INTERVAL)?;
+//! let mut reader = Reader::from_log_file("candump.log")?;
+//!
+//! // Read one second of traffic, starting at 1469439874.0
+//! let (start, end) = (1_469_439_874_000_000, 1_469_439_875_000_000);
+//! reader.seek_to_time(&index, start)?;
+//!
+//! while let Some(rec) = reader.next_record()? {
+//!     if rec.t_us >= end {
+//!         break;
+//!     }
+//!     println!("{} {} {:X}", rec.t_us, rec.device, rec.frame);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Logs in formats other than candump can be indexed with
+//! [`Index::build_with`], given a function to pull the timestamp from a
+//! line.
+
+use super::{parse_timestamp, LogFile};
+use std::{
+    fs,
+    io::{self, BufRead, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// The default distance, in bytes, between index entries.
+pub const DEFAULT_INDEX_INTERVAL: u64 = 1 << 20;
+
+/// The magic bytes at the start of an index sidecar file.
+const MAGIC: &[u8; 8] = b"SCANIDX1";
+
+/// A single index entry.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// The timestamp of the line, in microseconds
+    pub t_us: u64,
+    /// The byte offset to the start of the line in the log file
+    pub offset: u64,
+}
+
+/// A sparse time to byte offset index over a CAN log file.
+///
+/// The log is assumed to be in time order, as produced by `candump`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Index {
+    /// The length of the log when the index was built
+    src_len: u64,
+    /// The entries, in file order
+    entries: Vec<IndexEntry>,
+}
+
+impl Index {
+    /// Builds an index over a candump log.
+    ///
+    /// An entry is made for the first line with a valid timestamp at or
+    /// after every `interval` bytes of the log.
+    pub fn build<R: BufRead>(rdr: R, interval: u64) -> io::Result<Self> {
+        Self::build_with(rdr, interval, |line| parse_timestamp(line).ok())
+    }
+
+    /// Builds an index over a line-oriented log in any format.
+    ///
+    /// The function `f` should return the timestamp of a line, in
+    /// microseconds, or `None` if the line does not have one.
+    pub fn build_with<R, F>(mut rdr: R, interval: u64, mut f: F) -> io::Result<Self>
+    where
+        R: BufRead,
+        F: FnMut(&[u8]) -> Option<u64>,
+    {
+        let mut index = Self::default();
+        let mut line = Vec::new();
+        let mut offset = 0;
+        let mut next_mark = 0;
+
+        loop {
+            line.clear();
+            let n = rdr.read_until(b'\n', &mut line)? as u64;
+            if n == 0 {
+                break;
+            }
+
+            if offset >= next_mark {
+                if let Some(t_us) = f(&line) {
+                    index.entries.push(IndexEntry { t_us, offset });
+                    next_mark = offset + interval.max(1);
+                }
+            }
+            offset += n;
+        }
+
+        index.src_len = offset;
+        Ok(index)
+    }
+
+    /// Builds an index over a candump log file.
+    ///
+    /// A compressed log is indexed by offsets into its decompressed
+    /// contents, to match the [`LogFile`] used by `Reader::from_log_file()`.
+    /// The source length is always the size of the file on disk.
+    pub fn from_file<P: AsRef<Path>>(path: P, interval: u64) -> io::Result<Self> {
+        let path = path.as_ref();
+        let mut index = Self::build(io::BufReader::new(LogFile::open(path)?), interval)?;
+        index.src_len = fs::metadata(path)?.len();
+        Ok(index)
+    }
+
+    /// Gets the entries in the index.
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// Gets the length, in bytes, of the log file when it was indexed.
+    pub fn source_len(&self) -> u64 {
+        self.src_len
+    }
+
+    /// Gets the offset from which to scan for the first line at or after
+    /// the time `t_us`.
+    ///
+    /// This is the offset of the last entry earlier than the time, or the
+    /// start of the file if there isn't one.
+    pub fn offset_for(&self, t_us: u64) -> u64 {
+        match self.entries.partition_point(|e| e.t_us < t_us) {
+            0 => 0,
+            i => self.entries[i - 1].offset,
+        }
+    }
+
+    /// Writes the index in its binary sidecar format.
+    ///
+    /// This is the magic bytes, followed by the source length, the number
+    /// of entries, and then the entries, all as little-endian `u64` values.
+    pub fn write_to<W: Write>(&self, mut wtr: W) -> io::Result<()> {
+        wtr.write_all(MAGIC)?;
+        wtr.write_all(&self.src_len.to_le_bytes())?;
+        wtr.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        for e in &self.entries {
+            wtr.write_all(&e.t_us.to_le_bytes())?;
+            wtr.write_all(&e.offset.to_le_bytes())?;
+        }
+        wtr.flush()
+    }
+
+    /// Reads an index in the binary sidecar format.
+    pub fn read_from<R: Read>(mut rdr: R) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        rdr.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a CAN log index file",
+            ));
+        }
+
+        let src_len = read_u64(&mut rdr)?;
+        let n = read_u64(&mut rdr)?;
+
+        let mut entries = Vec::new();
+        for _ in 0..n {
+            let t_us = read_u64(&mut rdr)?;
+            let offset = read_u64(&mut rdr)?;
+            entries.push(IndexEntry { t_us, offset });
+        }
+        Ok(Self { src_len, entries })
+    }
+
+    /// Gets the path of the sidecar index file for a log file.
+    pub fn sidecar_path<P: AsRef<Path>>(log_path: P) -> PathBuf {
+        let mut path = log_path.as_ref().as_os_str().to_owned();
+        path.push(".idx");
+        path.into()
+    }
+
+    /// Saves the index to a file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_to(io::BufWriter::new(fs::File::create(path)?))
+    }
+
+    /// Loads an index from a file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read_from(io::BufReader::new(fs::File::open(path)?))
+    }
+
+    /// Loads the sidecar index for a candump log file, building it if
+    /// needed.
+    ///
+    /// The index is rebuilt if the sidecar file is missing, unreadable, or
+    /// was made when the log was a different size. A newly built index is
+    /// saved to the sidecar file if possible, but failing to write it, such
+    /// as for a log in a read-only directory, is not an error.
+    pub fn load_or_build<P: AsRef<Path>>(log_path: P, interval: u64) -> io::Result<Self> {
+        let log_path = log_path.as_ref();
+        let log_len = fs::metadata(log_path)?.len();
+        let idx_path = Self::sidecar_path(log_path);
+
+        match Self::load(&idx_path) {
+            Ok(index) if index.src_len == log_len => Ok(index),
+            _ => {
+                let index = Self::from_file(log_path, interval)?;
+                let _ = index.save(&idx_path);
+                Ok(index)
+            }
+        }
+    }
+}
+
+// Reads a little-endian u64
+fn read_u64<R: Read>(rdr: &mut R) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    rdr.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dump::Reader, CanAnyFrame, Frame};
+    use std::io::Cursor;
+
+    // Makes a log with one frame per millisecond, starting at t=1s.
+    // Each line is 23 bytes long.
+    fn make_log(n: u64) -> Vec<u8> {
+        let mut log = Vec::new();
+        for i in 0..n {
+            writeln!(
+                log,
+                "(1.{:06}) can0 {:03X}#{:02X}",
+                i * 1000,
+                i % 0x800,
+                i % 0x100
+            )
+            .unwrap();
+        }
+        log
+    }
+
+    #[test]
+    fn test_build() {
+        let log = make_log(100);
+        let index = Index::build(Cursor::new(&log), 230).unwrap();
+
+        assert_eq!(index.source_len(), log.len() as u64);
+        assert_eq!(index.entries().len(), 10);
+        assert_eq!(
+            index.entries()[1],
+            IndexEntry {
+                t_us: 1_010_000,
+                offset: 230
+            }
+        );
+
+        assert_eq!(index.offset_for(0), 0);
+        assert_eq!(index.offset_for(1_010_000), 0);
+        assert_eq!(index.offset_for(1_010_001), 230);
+        assert_eq!(index.offset_for(u64::MAX), 2070);
+    }
+
+    #[test]
+    fn test_build_with() {
+        let log = b"# header\n10 a\n20 b\n30 c\n";
+        let index = Index::build_with(&log[..], 1, |line| {
+            std::str::from_utf8(line)
+                .ok()?
+                .split(' ')
+                .next()?
+                .parse()
+                .ok()
+        })
+        .unwrap();
+
+        let offsets: Vec<_> = index.entries().iter().map(|e| e.offset).collect();
+        assert_eq!(offsets, [9, 14, 19]);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let index = Index::build(Cursor::new(make_log(50)), 100).unwrap();
+
+        let mut buf = Vec::new();
+        index.write_to(&mut buf).unwrap();
+        assert_eq!(buf.len(), 24 + 16 * index.entries().len());
+        assert_eq!(Index::read_from(&buf[..]).unwrap(), index);
+
+        buf[0] = b'X';
+        assert!(Index::read_from(&buf[..]).is_err());
+    }
+
+    #[test]
+    fn test_seek_to_time() {
+        let log = make_log(100);
+        let index = Index::build(Cursor::new(&log), 256).unwrap();
+        let mut reader = Reader::from_reader(Cursor::new(&log));
+
+        reader.seek_to_time(&index, 1_042_500).unwrap();
+        let rec = reader.next_record().unwrap().unwrap();
+        assert_eq!(rec.t_us, 1_043_000);
+        assert!(matches!(rec.frame, CanAnyFrame::Normal(frame) if frame.raw_id() == 43));
+
+        let rec = reader.next_record().unwrap().unwrap();
+        assert_eq!(rec.t_us, 1_044_000);
+
+        // Seeking backwards works as well
+        reader.seek_to_time(&index, 0).unwrap();
+        assert_eq!(reader.next_record().unwrap().unwrap().t_us, 1_000_000);
+
+        reader.seek_to_time(&index, 2_000_000).unwrap();
+        assert!(reader.next_record().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_load_or_build() {
+        let dir = std::env::temp_dir().join(format!("socketcan-index-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log_path = dir.join("candump.log");
+        let idx_path = Index::sidecar_path(&log_path);
+        assert_eq!(idx_path, dir.join("candump.log.idx"));
+
+        fs::write(&log_path, make_log(20)).unwrap();
+        let index = Index::load_or_build(&log_path, 64).unwrap();
+        assert_eq!(Index::load(&idx_path).unwrap(), index);
+
+        // A changed log is re-indexed
+        fs::write(&log_path, make_log(40)).unwrap();
+        let index = Index::load_or_build(&log_path, 64).unwrap();
+        assert_eq!(index.source_len(), 40 * 23);
+        assert_eq!(Index::load(&idx_path).unwrap(), index);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,714 @@
+// socketcan/src/dump/mod.rs
+//
+// Implements candump format parsing.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! candump format parsing
+//!
+//! Parses the text format emitted by the `candump` utility, which is part of
+//! [can-utils](https://github.com/linux-can/can-utils).
+//!
+//! Example:
+//!
+//! ```text
+//! (1469439874.299654) can1 701#7F
+//! ```
+//!
+//! Can be parsed by a `Reader` object. The API is inspired by the
+//! [csv](https://crates.io/crates/csv) crate.
+//!
+//! Individual lines can also be parsed with [`parse_record`], which decodes
+//! the frame in place without any heap allocation. This is the parser used
+//! by all of the readers.
+//!
+//! Logs can be written in the same format with a [`Writer`]. Both the
+//! reader and writer handle files compressed with gzip, zstd, or xz when
+//! the crate feature of the same name is enabled. See the [`compress`]
+//! module.
+//!
+//! For long-running capture, a [`Recorder`] reads from any number of
+//! interfaces into a series of log files, rotated by size or time.
+//!
+//! Large log files can be indexed by time with an [`Index`], which is
+//! normally kept in a sidecar file next to the log. A `Reader` can then
+//! `seek_to_time()` to read a window of the log without scanning through
+//! the whole file.
+//!
+//! For applications that tail live candump output, such as from a pipe or a
+//! network connection, there are asynchronous readers in the [`tokio`] and
+//! [`async_io`] submodules (with the corresponding crate features) which
+//! produce a `Stream` of records.
+
+use crate::{frame::parse_frame, CanAnyFrame, ConstructionError, FrameParseError};
+use std::{error, fmt, fs, io, path};
+
+pub mod compress;
+pub use compress::{Compression, LogFile, LogWriter};
+
+pub mod index;
+pub use index::{Index, DEFAULT_INDEX_INTERVAL};
+
+pub mod recorder;
+pub use recorder::{Recorder, Segment, SyncPolicy};
+
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+#[cfg(any(feature = "async-io", feature = "async-std", feature = "smol"))]
+pub mod async_io;
+
+// cannot be generic, because from_str_radix is not part of any Trait
+fn parse_raw(bytes: &[u8], radix: u32) -> Option<u64> {
+    ::std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|s| u64::from_str_radix(s, radix).ok())
+}
+
+// Parses the "(seconds.micros)" timestamp field into microseconds.
+fn parse_time_field(f: &[u8]) -> Result<u64, ParseError> {
+    if f.len() < 3 || f[0] != b'(' || f[f.len() - 1] != b')' {
+        return Err(ParseError::InvalidTimestamp);
+    }
+
+    let inner = &f[1..f.len() - 1];
+
+    // split at dot, read both parts
+    let dot = inner
+        .iter()
+        .position(|&c| c == b'.')
+        .ok_or(ParseError::InvalidTimestamp)?;
+
+    let (num, mant) = inner.split_at(dot);
+
+    // parse number and multiply
+    let n_num: u64 = parse_raw(num, 10).ok_or(ParseError::InvalidTimestamp)?;
+    let n_mant: u64 = parse_raw(&mant[1..], 10).ok_or(ParseError::InvalidTimestamp)?;
+    Ok(n_num.saturating_mul(1_000_000).saturating_add(n_mant))
+}
+
+/// Parses just the timestamp, in microseconds, from a line of candump log
+/// output.
+///
+/// This is much cheaper than parsing the full record, and is what the
+/// [`index`] builder uses to scan a log file.
+pub fn parse_timestamp(line: &[u8]) -> Result<u64, ParseError> {
+    let f = line
+        .split(|&c| c == b' ')
+        .next()
+        .ok_or(ParseError::UnexpectedEndOfLine)?;
+    parse_time_field(f)
+}
+
+/// Parses a single line of candump log output into a record.
+///
+/// This is the zero-allocation parse path: the payload is decoded directly
+/// into the frame and the device name is borrowed from the line. A trailing
+/// line ending, if present, is ignored.
+pub fn parse_record(line: &[u8]) -> Result<CanDumpRecord<'_>, ParseError> {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+    let mut field_iter = line.split(|&c| c == b' ');
+
+    // parse time field
+    let f = field_iter.next().ok_or(ParseError::UnexpectedEndOfLine)?;
+    let t_us = parse_time_field(f)?;
+
+    let f = field_iter.next().ok_or(ParseError::UnexpectedEndOfLine)?;
+
+    // device name
+    let device = ::std::str::from_utf8(f).map_err(|_| ParseError::InvalidDeviceName)?;
+
+    // parse packet
+    let can_raw = field_iter.next().ok_or(ParseError::UnexpectedEndOfLine)?;
+    let frame = parse_frame(can_raw)?;
+
+    Ok(CanDumpRecord {
+        t_us,
+        device,
+        frame,
+    })
+}
+
+/// The line assembly state shared by the asynchronous readers.
+///
+/// This is I/O free: the readers feed it the results of their
+/// `poll_fill_buf()` calls, and consume the number of bytes it took. A
+/// partial line is kept across calls, so a read can be safely cancelled.
+#[cfg(any(
+    feature = "tokio",
+    feature = "async-io",
+    feature = "async-std",
+    feature = "smol"
+))]
+#[derive(Debug, Default)]
+pub(crate) struct LineBuffer {
+    buf: Vec<u8>,
+    // Whether the buffer holds a complete line from the last read
+    done: bool,
+}
+
+#[cfg(any(
+    feature = "tokio",
+    feature = "async-io",
+    feature = "async-std",
+    feature = "smol"
+))]
+impl LineBuffer {
+    /// Moves bytes from a filled read buffer into the line buffer, up to
+    /// and including the next newline. An empty buffer marks EOF.
+    ///
+    /// Returns the number of bytes taken from `avail`, and, once the read
+    /// is finished, whether there is a line to parse.
+    pub(crate) fn fill(&mut self, avail: &[u8]) -> (usize, Option<bool>) {
+        if self.done {
+            self.buf.clear();
+            self.done = false;
+        }
+
+        if avail.is_empty() {
+            // EOF, possibly with an unterminated last line
+            self.done = !self.buf.is_empty();
+            return (0, Some(self.done));
+        }
+
+        match avail.iter().position(|&c| c == b'\n') {
+            Some(i) => {
+                self.buf.extend_from_slice(&avail[..=i]);
+                self.done = true;
+                (i + 1, Some(true))
+            }
+            None => {
+                self.buf.extend_from_slice(avail);
+                (avail.len(), None)
+            }
+        }
+    }
+
+    /// Parses the line in the buffer.
+    pub(crate) fn record(&self) -> Result<CanDumpRecord<'_>, ParseError> {
+        parse_record(&self.buf)
+    }
+
+    /// Converts the result of a line read into a `Stream` item.
+    pub(crate) fn stream_item(
+        &self,
+        res: io::Result<bool>,
+    ) -> Option<Result<(u64, CanAnyFrame), ParseError>> {
+        match res {
+            Ok(true) => Some(self.record().map(|rec| (rec.t_us, rec.frame))),
+            Ok(false) => None,
+            Err(err) => Some(Err(err.into())),
+        }
+    }
+}
+
+#[derive(Debug)]
+/// A CAN log reader.
+pub struct Reader<R> {
+    rdr: R,
+    line_buf: Vec<u8>,
+    // The line buffer holds a line that was read, but not yet returned
+    pending: bool,
+}
+
+impl<R: io::Read> Reader<R> {
+    /// Creates an I/O buffered reader from a CAN log reader.
+    pub fn from_reader(rdr: R) -> Reader<io::BufReader<R>> {
+        Reader {
+            rdr: io::BufReader::new(rdr),
+            line_buf: Vec::new(),
+            pending: false,
+        }
+    }
+}
+
+impl Reader<fs::File> {
+    /// Creates an I/O buffered reader from a file.
+    pub fn from_file<P>(path: P) -> io::Result<Reader<io::BufReader<fs::File>>>
+    where
+        P: AsRef<path::Path>,
+    {
+        Ok(Reader::from_reader(fs::File::open(path)?))
+    }
+}
+
+impl Reader<LogFile> {
+    /// Creates an I/O buffered reader from a log file that may be
+    /// compressed.
+    ///
+    /// A compressed file is decompressed transparently, if support for its
+    /// format is enabled. See the [`compress`] module.
+    pub fn from_log_file<P>(path: P) -> io::Result<Reader<io::BufReader<LogFile>>>
+    where
+        P: AsRef<path::Path>,
+    {
+        Ok(Reader::from_reader(LogFile::open(path)?))
+    }
+}
+
+/// A CAN log writer.
+///
+/// This writes records in the `candump -L` log format, which can be read
+/// back with a [`Reader`], or replayed with the `canplayer` utility.
+#[derive(Debug)]
+pub struct Writer<W: io::Write> {
+    wtr: W,
+}
+
+impl<W: io::Write> Writer<W> {
+    /// Creates a CAN log writer.
+    ///
+    /// Each record is written with a separate call to the writer, so it
+    /// should normally be buffered.
+    pub fn new(wtr: W) -> Self {
+        Self { wtr }
+    }
+
+    /// Writes a record to the log.
+    pub fn write_record(&mut self, t_us: u64, device: &str, frame: &CanAnyFrame) -> io::Result<()> {
+        writeln!(
+            self.wtr,
+            "({}.{:06}) {} {}",
+            t_us / 1_000_000,
+            t_us % 1_000_000,
+            device,
+            frame
+        )
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.wtr.flush()
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.wtr
+    }
+
+    /// Gets a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.wtr
+    }
+
+    /// Flushes the writer and returns the underlying writer.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.wtr.flush()?;
+        Ok(self.wtr)
+    }
+}
+
+impl Writer<LogWriter> {
+    /// Creates a log file writer.
+    ///
+    /// The file is compressed if it has a `.gz`, `.zst`, or `.xz`
+    /// extension. See the [`compress`] module.
+    pub fn create<P: AsRef<path::Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(LogWriter::create(path)?))
+    }
+
+    /// Creates a log file writer with the specified compression.
+    pub fn create_with<P: AsRef<path::Path>>(
+        path: P,
+        compression: Compression,
+    ) -> io::Result<Self> {
+        Ok(Self::new(LogWriter::create_with(path, compression)?))
+    }
+
+    /// Completes writing the log file.
+    ///
+    /// This finishes any compressed stream and flushes it to the file.
+    pub fn finish(self) -> io::Result<()> {
+        self.wtr.finish().map(|_| ())
+    }
+}
+
+/// Record iterator
+#[derive(Debug)]
+pub struct CanDumpRecords<'a, R: 'a> {
+    src: &'a mut Reader<R>,
+}
+
+/// Recorded CAN frame.
+#[derive(Debug)]
+pub struct CanDumpRecord<'a> {
+    /// The timestamp
+    pub t_us: u64,
+    /// The name of the device
+    pub device: &'a str,
+    /// The parsed frame
+    pub frame: CanAnyFrame,
+}
+
+#[derive(Debug)]
+/// candump line parse error
+pub enum ParseError {
+    /// I/O Error
+    Io(io::Error),
+    /// Unexpected end of line
+    UnexpectedEndOfLine,
+    /// Invalid time stamp
+    InvalidTimestamp,
+    /// Invalid device name
+    InvalidDeviceName,
+    /// Invalid CAN frame
+    InvalidCanFrame,
+    /// Error creating the frame
+    ConstructionError(ConstructionError),
+}
+
+impl error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ParseError::Io(err) => Some(err),
+            ParseError::ConstructionError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParseError::*;
+        match self {
+            Io(err) => write!(f, "I/O error: {}", err),
+            UnexpectedEndOfLine => write!(f, "unexpected end of line"),
+            InvalidTimestamp => write!(f, "invalid timestamp"),
+            InvalidDeviceName => write!(f, "invalid device name"),
+            InvalidCanFrame => write!(f, "invalid CAN frame"),
+            ConstructionError(err) => write!(f, "frame construction error: {}", err),
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> ParseError {
+        ParseError::Io(e)
+    }
+}
+
+impl From<FrameParseError> for ParseError {
+    fn from(e: FrameParseError) -> ParseError {
+        match e {
+            FrameParseError::InvalidFormat => ParseError::InvalidCanFrame,
+            FrameParseError::ConstructionError(e) => ParseError::ConstructionError(e),
+        }
+    }
+}
+
+impl From<ConstructionError> for ParseError {
+    fn from(e: ConstructionError) -> ParseError {
+        ParseError::ConstructionError(e)
+    }
+}
+
+impl<R: io::BufRead> Reader<R> {
+    /// Returns an iterator over all records
+    pub fn records(&mut self) -> CanDumpRecords<'_, R> {
+        CanDumpRecords { src: self }
+    }
+
+    /// Advance state, returning next record.
+    pub fn next_record(&mut self) -> Result<Option<CanDumpRecord<'_>>, ParseError> {
+        if !self.pending && !self.read_line()? {
+            return Ok(None);
+        }
+        self.pending = false;
+        parse_record(&self.line_buf).map(Some)
+    }
+
+    // Reads the next line into the line buffer, returning false at EOF.
+    fn read_line(&mut self) -> io::Result<bool> {
+        self.line_buf.clear();
+        let bytes_read = self.rdr.read_until(b'\n', &mut self.line_buf)?;
+        Ok(bytes_read != 0)
+    }
+}
+
+impl<R: io::BufRead + io::Seek> Reader<R> {
+    /// Moves the reader to the first record at or after the time `t_us`.
+    ///
+    /// The index is used to jump close to the requested time, after which
+    /// the log is scanned forward to the exact record. The next call to
+    /// `next_record()` returns that record, or `None` if the time is past
+    /// the end of the log. Lines without a valid timestamp are skipped
+    /// while scanning.
+    ///
+    /// The log is assumed to be in time order, as produced by `candump`.
+    pub fn seek_to_time(&mut self, index: &Index, t_us: u64) -> Result<(), ParseError> {
+        self.rdr.seek(io::SeekFrom::Start(index.offset_for(t_us)))?;
+        self.pending = false;
+
+        while self.read_line()? {
+            if matches!(parse_timestamp(&self.line_buf), Ok(t) if t >= t_us) {
+                self.pending = true;
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a, R: io::Read> Iterator for CanDumpRecords<'a, io::BufReader<R>> {
+    type Item = Result<(u64, CanAnyFrame), ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // lift Option:
+        match self.src.next_record() {
+            Ok(Some(CanDumpRecord { t_us, frame, .. })) => Some(Ok((t_us, frame))),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{CanAnyFrame, Frame};
+    use embedded_can::Frame as EmbeddedFrame;
+
+    #[test]
+    fn test_simple_example() {
+        let input: &[u8] = b"(1469439874.299591) can1 080#\n\
+                             (1469439874.299654) can1 701#7F";
+
+        let mut reader = Reader::from_reader(input);
+
+        {
+            let rec1 = reader.next_record().unwrap().unwrap();
+
+            assert_eq!(rec1.t_us, 1469439874299591);
+            assert_eq!(rec1.device, "can1");
+
+            if let CanAnyFrame::Normal(frame) = rec1.frame {
+                assert_eq!(frame.raw_id(), 0x080);
+                assert!(!frame.is_remote_frame());
+                assert!(!frame.is_error_frame());
+                assert!(!frame.is_extended());
+                assert_eq!(frame.data(), &[] as &[u8]);
+            } else {
+                panic!("Expected Normal frame, got FD");
+            }
+        }
+
+        {
+            let rec2 = reader.next_record().unwrap().unwrap();
+            assert_eq!(rec2.t_us, 1469439874299654);
+            assert_eq!(rec2.device, "can1");
+
+            if let CanAnyFrame::Normal(frame) = rec2.frame {
+                assert_eq!(frame.raw_id(), 0x701);
+                assert!(!frame.is_remote_frame());
+                assert!(!frame.is_error_frame());
+                assert!(!frame.is_extended());
+                assert_eq!(frame.data(), &[0x7F]);
+            } else {
+                panic!("Expected Normal frame, got FD");
+            }
+        }
+
+        assert!(reader.next_record().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_extended_example() {
+        let input: &[u8] = b"(1469439874.299591) can1 080080#\n\
+                             (1469439874.299654) can1 053701#7F";
+
+        let mut reader = Reader::from_reader(input);
+
+        {
+            let rec1 = reader.next_record().unwrap().unwrap();
+
+            assert_eq!(rec1.t_us, 1469439874299591);
+            assert_eq!(rec1.device, "can1");
+
+            if let CanAnyFrame::Normal(frame) = rec1.frame {
+                assert_eq!(frame.raw_id(), 0x080080);
+                assert!(!frame.is_remote_frame());
+                assert!(!frame.is_error_frame());
+                assert!(frame.is_extended());
+                assert_eq!(frame.data(), &[] as &[u8]);
+            } else {
+                panic!("Expected Normal frame, got FD");
+            }
+        }
+
+        {
+            let rec2 = reader.next_record().unwrap().unwrap();
+            assert_eq!(rec2.t_us, 1469439874299654);
+            assert_eq!(rec2.device, "can1");
+
+            if let CanAnyFrame::Normal(frame) = rec2.frame {
+                assert_eq!(frame.raw_id(), 0x053701);
+                assert!(!frame.is_remote_frame());
+                assert!(!frame.is_error_frame());
+                assert!(frame.is_extended());
+                assert_eq!(frame.data(), &[0x7F]);
+            } else {
+                panic!("Expected Normal frame, got FD");
+            }
+        }
+
+        assert!(reader.next_record().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_fd() {
+        let input: &[u8] = b"(1469439874.299591) can1 080##0\n\
+                             (1469439874.299654) can1 701##17F";
+
+        let mut reader = Reader::from_reader(input);
+
+        {
+            let rec1 = reader.next_record().unwrap().unwrap();
+
+            assert_eq!(rec1.t_us, 1469439874299591);
+            assert_eq!(rec1.device, "can1");
+            if let CanAnyFrame::Fd(frame) = rec1.frame {
+                assert_eq!(frame.raw_id(), 0x080);
+                assert!(!frame.is_remote_frame());
+                assert!(!frame.is_error_frame());
+                assert!(!frame.is_extended());
+                assert!(!frame.is_brs());
+                assert!(!frame.is_esi());
+                assert_eq!(frame.data(), &[] as &[u8]);
+            } else {
+                panic!("Expected FD frame, got Normal");
+            }
+        }
+
+        {
+            let rec2 = reader.next_record().unwrap().unwrap();
+            assert_eq!(rec2.t_us, 1469439874299654);
+            assert_eq!(rec2.device, "can1");
+            if let CanAnyFrame::Fd(frame) = rec2.frame {
+                assert_eq!(frame.raw_id(), 0x701);
+                assert!(!frame.is_remote_frame());
+                assert!(!frame.is_error_frame());
+                assert!(!frame.is_extended());
+                assert!(frame.is_brs());
+                assert!(!frame.is_esi());
+                assert_eq!(frame.data(), &[0x7F]);
+            } else {
+                panic!("Expected FD frame, got Normal");
+            }
+        }
+
+        assert!(reader.next_record().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_record() {
+        let rec = parse_record(b"(1469439874.299654) vcan0 123#R\n").unwrap();
+        assert_eq!(rec.t_us, 1469439874299654);
+        assert_eq!(rec.device, "vcan0");
+        match rec.frame {
+            CanAnyFrame::Remote(frame) => {
+                assert_eq!(frame.raw_id(), 0x123);
+                assert_eq!(frame.dlc(), 0);
+            }
+            _ => panic!("Expected Remote frame"),
+        }
+
+        let rec = parse_record(b"(1469439874.299654) vcan0 1F334455#R3").unwrap();
+        match rec.frame {
+            CanAnyFrame::Remote(frame) => {
+                assert_eq!(frame.raw_id(), 0x1F334455);
+                assert!(frame.is_extended());
+                assert_eq!(frame.dlc(), 3);
+            }
+            _ => panic!("Expected Remote frame"),
+        }
+
+        let rec = parse_record(b"(0.000001) can0 20000080#0000000000000000").unwrap();
+        assert_eq!(rec.t_us, 1);
+        match rec.frame {
+            CanAnyFrame::Error(frame) => assert_eq!(frame.error_bits(), 0x0080),
+            _ => panic!("Expected Error frame"),
+        }
+
+        let rec = parse_record(b"(1.000000) can0 7FF#11.22.33\r\n").unwrap();
+        match rec.frame {
+            CanAnyFrame::Normal(frame) => {
+                assert_eq!(frame.raw_id(), 0x7FF);
+                assert!(!frame.is_extended());
+                assert_eq!(frame.data(), &[0x11, 0x22, 0x33]);
+            }
+            _ => panic!("Expected Normal frame"),
+        }
+    }
+
+    #[test]
+    fn test_parse_record_errors() {
+        assert!(matches!(
+            parse_record(b"(1.000000) can0 123#112233445566778899"),
+            Err(ParseError::ConstructionError(
+                ConstructionError::TooMuchData
+            ))
+        ));
+        assert!(matches!(
+            parse_record(b"(1.000000) can0 123#1"),
+            Err(ParseError::InvalidCanFrame)
+        ));
+        assert!(matches!(
+            parse_record(b"(1.000000) can0 123#XY"),
+            Err(ParseError::InvalidCanFrame)
+        ));
+        assert!(matches!(
+            parse_record(b"(1.000000) can0"),
+            Err(ParseError::UnexpectedEndOfLine)
+        ));
+        assert!(matches!(
+            parse_record(b"1.000000 can0 123#00"),
+            Err(ParseError::InvalidTimestamp)
+        ));
+    }
+
+    #[test]
+    fn test_writer() {
+        let input: &[u8] = b"(1469439874.299591) can1 080#\n\
+                             (1469439874.000054) can1 12345678#11223344\n\
+                             (1469439874.299700) can0 123#R\n\
+                             (1469439874.299701) can0 00000123#R5\n\
+                             (1469439874.299800) can0 20000004#0000000000000000\n\
+                             (1469439874.299900) can0 123##1DEADBEEF\n";
+
+        let mut wtr = Writer::new(Vec::new());
+        for rec in input.split_inclusive(|&c| c == b'\n') {
+            let rec = parse_record(rec).unwrap();
+            wtr.write_record(rec.t_us, rec.device, &rec.frame).unwrap();
+        }
+        assert_eq!(wtr.into_inner().unwrap(), input);
+    }
+
+    #[cfg(any(
+        feature = "tokio",
+        feature = "async-io",
+        feature = "async-std",
+        feature = "smol"
+    ))]
+    #[test]
+    fn test_line_buffer() {
+        let mut line = LineBuffer::default();
+
+        // A line split across reads, with the start of the next one
+        assert_eq!(line.fill(b"(1.000000) can0 1"), (17, None));
+        assert_eq!(line.fill(b"23#00\n(2.0"), (6, Some(true)));
+        assert_eq!(line.record().unwrap().t_us, 1_000_000);
+
+        // An unterminated last line at EOF
+        assert_eq!(line.fill(b"(2.000000) can0 7FF#"), (20, None));
+        assert_eq!(line.fill(b""), (0, Some(true)));
+        assert_eq!(line.record().unwrap().device, "can0");
+        assert_eq!(line.fill(b""), (0, Some(false)));
+    }
+}
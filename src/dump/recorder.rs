@@ -0,0 +1,635 @@
+// socketcan/src/dump/recorder.rs
+//
+// Implements a rotating CAN log recorder.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! A rotating CAN log recorder.
+//!
+//! A [`Recorder`] captures traffic from one or more CAN interfaces into
+//! candump log files. The log is split into segments, each in its own
+//! file, which are rotated when they reach a maximum size, after they have
+//! been open for a maximum duration, or at wall-clock boundaries, like at
+//! the top of every hour.
+//!
+//! Segment files are named from a template, which can contain these
+//! placeholders:
+//!
+//! - `{seq}` - The sequence number of the segment, starting at zero,
+//!   as at least four digits.
+//! - `{secs}` - The start time of the segment, as seconds since the epoch.
+//! - `{date}` - The UTC start date of the segment, as `YYYYMMDD`.
+//! - `{time}` - The UTC start time of the segment, as `HHMMSS`.
+//!
+//! An existing file is never overwritten. If the name of a new segment is
+//! already taken, as when two segments start in the same second with no
+//! `{seq}` in the template, a suffix like `-1` is added ahead of the
+//! extension.
+//!
+//! The files are compressed according to the template extension, as
+//! described in the [`compress`](super::compress) module, unless set
+//! explicitly.
+//!
+//! ```no_run
+//! use socketcan::dump::{Recorder, SyncPolicy};
+//! use std::{sync::atomic::AtomicBool, time::Duration};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let running = AtomicBool::new(true);
+//!
+//! let mut recorder = Recorder::new("/var/log/can/{date}-{time}.log.zst")
+//!     .max_size(64 << 20)
+//!     .wall_clock(Duration::from_secs(3600))
+//!     .sync_policy(SyncPolicy::Interval(Duration::from_secs(10)))
+//!     .on_segment_closed(|seg| println!("Closed {}", seg.path.display()));
+//!
+//! recorder.capture(&["can0", "can1"], &running)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use super::{Compression, LogWriter, Writer};
+use crate::{CanAnyFrame, CanFdSocket, Socket};
+use nix::poll::{poll, PollFd, PollFlags};
+use std::{
+    fmt,
+    io::{self, Write},
+    os::unix::io::AsRawFd,
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, SystemTime},
+};
+
+/// The time, in milliseconds, that `capture()` waits for traffic before
+/// checking whether to stop or rotate.
+const POLL_TIMEOUT_MS: i32 = 100;
+
+/// When to flush a segment file through to the disk.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Never explicitly sync. Leave it to the OS.
+    Never,
+    /// Sync the file when the segment is closed.
+    #[default]
+    OnClose,
+    /// Sync the file when this much time has passed since the last sync,
+    /// and when the segment is closed.
+    Interval(Duration),
+    /// Sync the file after every record.
+    EveryRecord,
+}
+
+/// Information about a closed log segment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    /// The path to the segment file
+    pub path: PathBuf,
+    /// The sequence number of the segment
+    pub seq: u64,
+    /// The time the segment was opened, in microseconds since the epoch
+    pub start_us: u64,
+    /// The time of the last record in the segment, in microseconds
+    pub end_us: u64,
+    /// The number of records in the segment
+    pub records: u64,
+    /// The size of the log text, before any compression
+    pub size: u64,
+}
+
+/// A callback to receive closed segments.
+type SegmentCallback = Box<dyn FnMut(&Segment) + Send>;
+
+// The segment currently being written.
+#[derive(Debug)]
+struct OpenSegment {
+    wtr: Writer<LogWriter>,
+    info: Segment,
+    last_sync_us: u64,
+}
+
+/// A rotating CAN log recorder.
+///
+/// See the [module documentation](self) for details.
+pub struct Recorder {
+    template: String,
+    compression: Option<Compression>,
+    max_size: Option<u64>,
+    max_duration: Option<Duration>,
+    wall_clock: Option<Duration>,
+    sync_policy: SyncPolicy,
+    on_closed: Option<SegmentCallback>,
+    seq: u64,
+    seg: Option<OpenSegment>,
+}
+
+impl Recorder {
+    /// Creates a recorder that writes segment files named by the template.
+    ///
+    /// By default, segments are never rotated.
+    pub fn new<S: Into<String>>(template: S) -> Self {
+        Self {
+            template: template.into(),
+            compression: None,
+            max_size: None,
+            max_duration: None,
+            wall_clock: None,
+            sync_policy: SyncPolicy::default(),
+            on_closed: None,
+            seq: 0,
+            seg: None,
+        }
+    }
+
+    /// Rotates segments when their size, before compression, would
+    /// exceed this many bytes.
+    pub fn max_size(mut self, size: u64) -> Self {
+        self.max_size = Some(size);
+        self
+    }
+
+    /// Rotates segments when they have been open for this long.
+    pub fn max_duration(mut self, dur: Duration) -> Self {
+        self.max_duration = Some(dur);
+        self
+    }
+
+    /// Rotates segments at wall-clock boundaries.
+    ///
+    /// The boundaries are multiples of the period from the epoch, so a
+    /// period of one hour rotates at the top of every hour (UTC).
+    pub fn wall_clock(mut self, period: Duration) -> Self {
+        self.wall_clock = Some(period);
+        self
+    }
+
+    /// Sets the compression for the segment files, overriding the
+    /// template extension.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Sets when the segment files are flushed through to the disk.
+    pub fn sync_policy(mut self, policy: SyncPolicy) -> Self {
+        self.sync_policy = policy;
+        self
+    }
+
+    /// Sets a callback to receive each segment after it is closed.
+    pub fn on_segment_closed<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(&Segment) + Send + 'static,
+    {
+        self.on_closed = Some(Box::new(f));
+        self
+    }
+
+    /// Gets the path of the segment file currently being written, if any.
+    pub fn current_path(&self) -> Option<&PathBuf> {
+        self.seg.as_ref().map(|seg| &seg.info.path)
+    }
+
+    /// Records a frame, rotating to a new segment first, if needed.
+    ///
+    /// A segment is opened on the first record after the recorder is
+    /// created or the previous segment was closed.
+    pub fn record(&mut self, t_us: u64, device: &str, frame: &CanAnyFrame) -> io::Result<()> {
+        let mut line = Vec::with_capacity(64);
+        Writer::new(&mut line).write_record(t_us, device, frame)?;
+        let len = line.len() as u64;
+
+        if let Some(seg) = &self.seg {
+            let full = matches!(self.max_size, Some(max)
+                if seg.info.records > 0 && seg.info.size + len > max);
+            if full || self.is_expired(&seg.info, t_us) {
+                self.close()?;
+            }
+        }
+
+        if self.seg.is_none() {
+            self.open(t_us)?;
+        }
+
+        let policy = self.sync_policy;
+        let seg = self.seg.as_mut().expect("no open segment");
+        seg.wtr.get_mut().write_all(&line)?;
+        seg.info.end_us = t_us;
+        seg.info.records += 1;
+        seg.info.size += len;
+
+        match policy {
+            SyncPolicy::EveryRecord => seg.wtr.get_mut().sync_data()?,
+            SyncPolicy::Interval(dur) if t_us.saturating_sub(seg.last_sync_us) >= micros(dur) => {
+                seg.wtr.get_mut().sync_data()?;
+                seg.last_sync_us = t_us;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Closes the current segment if it is due to be rotated at the time
+    /// `t_us`, even though no record has arrived.
+    ///
+    /// This lets time-based rotation happen on a quiet bus. It is called
+    /// periodically by `capture()`.
+    pub fn tick(&mut self, t_us: u64) -> io::Result<()> {
+        match &self.seg {
+            Some(seg) if self.is_expired(&seg.info, t_us) => self.close(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Closes the current segment, if any, and reports it to the callback.
+    ///
+    /// The next record opens a new segment.
+    pub fn close(&mut self) -> io::Result<()> {
+        let seg = match self.seg.take() {
+            Some(seg) => seg,
+            None => return Ok(()),
+        };
+
+        let file = seg.wtr.into_inner()?.finish()?;
+        if self.sync_policy != SyncPolicy::Never {
+            file.sync_all()?;
+        }
+
+        if let Some(f) = self.on_closed.as_mut() {
+            f(&seg.info);
+        }
+        Ok(())
+    }
+
+    /// Captures traffic from the interfaces until `running` is cleared.
+    ///
+    /// Frames are timestamped with the system time when they are read.
+    /// An interface that fails, as when its link goes down, is logged and
+    /// dropped from the capture, which carries on with the others. Once
+    /// every interface has failed, this returns the last error.
+    ///
+    /// The current segment is closed before returning, whether capture
+    /// stopped normally or on an error.
+    pub fn capture<S: AsRef<str>>(&mut self, ifaces: &[S], running: &AtomicBool) -> io::Result<()> {
+        let res = self.capture_frames(ifaces, running);
+        let close_res = self.close();
+        res.and(close_res)
+    }
+
+    fn capture_frames<S: AsRef<str>>(
+        &mut self,
+        ifaces: &[S],
+        running: &AtomicBool,
+    ) -> io::Result<()> {
+        let mut socks = ifaces
+            .iter()
+            .map(|iface| {
+                let iface = iface.as_ref();
+                CanFdSocket::open(iface).map(|sock| (iface, sock))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        while running.load(Ordering::Relaxed) {
+            let mut fds: Vec<_> = socks
+                .iter()
+                .map(|(_, sock)| PollFd::new(sock.as_raw_fd(), PollFlags::POLLIN))
+                .collect();
+
+            match poll(&mut fds, POLL_TIMEOUT_MS) {
+                Ok(_) => (),
+                Err(nix::errno::Errno::EINTR) => continue,
+                Err(err) => return Err(err.into()),
+            }
+
+            let mut failed = Vec::new();
+            for (i, fd) in fds.iter().enumerate() {
+                let ev = fd.revents().unwrap_or(PollFlags::empty());
+                let (iface, sock) = &socks[i];
+
+                if ev.intersects(PollFlags::POLLHUP | PollFlags::POLLNVAL) {
+                    failed.push((i, io::Error::from(io::ErrorKind::NotConnected)));
+                } else if ev.intersects(PollFlags::POLLIN | PollFlags::POLLERR) {
+                    // A pending socket error is returned by the read
+                    match sock.read_frame() {
+                        Ok(frame) => self.record(now_us(), iface, &frame)?,
+                        Err(err)
+                            if matches!(
+                                err.kind(),
+                                io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+                            ) => {}
+                        Err(err) => failed.push((i, err)),
+                    }
+                }
+            }
+
+            for (i, err) in failed.into_iter().rev() {
+                let (iface, _) = socks.remove(i);
+                log::warn!("Dropping {} from the capture: {}", iface, err);
+                if socks.is_empty() {
+                    return Err(err);
+                }
+            }
+            self.tick(now_us())?;
+        }
+        Ok(())
+    }
+
+    // Determines if a segment is due to be rotated by time.
+    fn is_expired(&self, info: &Segment, t_us: u64) -> bool {
+        let too_long = matches!(self.max_duration, Some(dur)
+            if t_us.saturating_sub(info.start_us) >= micros(dur));
+
+        let crossed = match self.wall_clock.map(micros) {
+            Some(period) if period > 0 => t_us / period != info.start_us / period,
+            _ => false,
+        };
+
+        too_long || crossed
+    }
+
+    // Opens a new segment starting at the time `t_us`.
+    fn open(&mut self, t_us: u64) -> io::Result<()> {
+        let name = render_template(&self.template, self.seq, t_us);
+        let compression = self
+            .compression
+            .unwrap_or_else(|| Compression::from_path(&name));
+
+        // A template without `{seq}` can name two segments the same, so
+        // never truncate an existing file, but add a suffix to the name.
+        let mut path = PathBuf::from(&name);
+        let mut n = 0;
+        let wtr = loop {
+            match LogWriter::create_new(&path, compression) {
+                Ok(wtr) => break Writer::new(wtr),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    n += 1;
+                    path = PathBuf::from(disambiguate(&name, n));
+                }
+                Err(err) => return Err(err),
+            }
+        };
+
+        self.seg = Some(OpenSegment {
+            wtr,
+            info: Segment {
+                path,
+                seq: self.seq,
+                start_us: t_us,
+                end_us: t_us,
+                records: 0,
+                size: 0,
+            },
+            last_sync_us: t_us,
+        });
+        self.seq += 1;
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Recorder")
+            .field("template", &self.template)
+            .field("compression", &self.compression)
+            .field("max_size", &self.max_size)
+            .field("max_duration", &self.max_duration)
+            .field("wall_clock", &self.wall_clock)
+            .field("sync_policy", &self.sync_policy)
+            .field("seq", &self.seq)
+            .field("seg", &self.seg)
+            .finish()
+    }
+}
+
+// ===== helper functions =====
+
+// Gets a duration in microseconds.
+fn micros(dur: Duration) -> u64 {
+    dur.as_micros() as u64
+}
+
+// Gets the current system time in microseconds since the epoch.
+fn now_us() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, micros)
+}
+
+// Gets the UTC (year, month, day) for days since the epoch.
+// See: http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    (y, m, d)
+}
+
+// Fills in the placeholders of a segment file name template.
+fn render_template(template: &str, seq: u64, t_us: u64) -> String {
+    let secs = t_us / 1_000_000;
+    let (y, m, d) = civil_from_days((secs / 86_400) as i64);
+    let tod = secs % 86_400;
+
+    template
+        .replace("{seq}", &format!("{:04}", seq))
+        .replace("{secs}", &secs.to_string())
+        .replace("{date}", &format!("{:04}{:02}{:02}", y, m, d))
+        .replace(
+            "{time}",
+            &format!("{:02}{:02}{:02}", tod / 3600, tod / 60 % 60, tod % 60),
+        )
+}
+
+// Adds a numeric suffix to a segment file name, ahead of its extensions,
+// like "0900.log.gz" to "0900-1.log.gz".
+fn disambiguate(name: &str, n: u32) -> String {
+    let base = name.rfind('/').map_or(0, |i| i + 1);
+    let ext = name[base..]
+        .char_indices()
+        .skip(1)
+        .find(|&(_, c)| c == '.')
+        .map_or(name.len(), |(i, _)| base + i);
+    format!("{}-{}{}", &name[..ext], n, &name[ext..])
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dump::Reader, CanFrame, EmbeddedFrame, StandardId};
+    use std::{
+        fs,
+        path::Path,
+        sync::{Arc, Mutex},
+    };
+
+    // 2024-10-18 15:30:00 UTC
+    const T0: u64 = 1_729_265_400_000_000;
+
+    fn frame(id: u16) -> CanAnyFrame {
+        CanFrame::new(StandardId::new(id).unwrap(), &[1, 2, 3, 4])
+            .unwrap()
+            .into()
+    }
+
+    // Makes an empty temporary directory for a test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("socketcan-rec-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // Makes a recorder that collects the closed segments
+    fn recorder(dir: &Path, template: &str) -> (Recorder, Arc<Mutex<Vec<Segment>>>) {
+        let segs = Arc::new(Mutex::new(Vec::new()));
+        let closed = segs.clone();
+        let template = dir.join(template).to_str().unwrap().to_string();
+        let rec = Recorder::new(template)
+            .sync_policy(SyncPolicy::Never)
+            .on_segment_closed(move |seg| closed.lock().unwrap().push(seg.clone()));
+        (rec, segs)
+    }
+
+    #[test]
+    fn test_render_template() {
+        assert_eq!(
+            render_template("can-{date}T{time}-{seq}.log", 7, T0),
+            "can-20241018T153000-0007.log"
+        );
+        assert_eq!(
+            render_template("{secs}.log", 0, T0 + 999_999),
+            "1729265400.log"
+        );
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+    }
+
+    #[test]
+    fn test_rotate_size() {
+        let dir = temp_dir("size");
+        // Each record is 38 bytes
+        let (rec, segs) = recorder(&dir, "{seq}.log");
+        let mut rec = rec.max_size(100);
+
+        for i in 0..5 {
+            rec.record(T0 + i, "can0", &frame(0x100)).unwrap();
+        }
+        rec.close().unwrap();
+
+        let segs = segs.lock().unwrap();
+        let records: Vec<_> = segs.iter().map(|seg| seg.records).collect();
+        assert_eq!(records, [2, 2, 1]);
+        assert_eq!(segs[1].path, dir.join("0001.log"));
+        assert_eq!(segs[1].start_us, T0 + 2);
+        assert_eq!(segs[1].end_us, T0 + 3);
+        assert_eq!(fs::metadata(&segs[0].path).unwrap().len(), segs[0].size);
+
+        let mut rdr = Reader::from_file(&segs[2].path).unwrap();
+        assert_eq!(rdr.next_record().unwrap().unwrap().t_us, T0 + 4);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_name_collision() {
+        let dir = temp_dir("collide");
+        let (rec, segs) = recorder(&dir, "{secs}.log");
+        let mut rec = rec.max_size(100);
+
+        // All three segments start in the same second
+        for i in 0..5 {
+            rec.record(T0 + i, "can0", &frame(0x100)).unwrap();
+        }
+        rec.close().unwrap();
+
+        let segs = segs.lock().unwrap();
+        let names: Vec<_> = segs
+            .iter()
+            .map(|seg| seg.path.file_name().unwrap().to_owned())
+            .collect();
+        assert_eq!(
+            names,
+            ["1729265400.log", "1729265400-1.log", "1729265400-2.log"]
+        );
+        for seg in segs.iter() {
+            assert_eq!(fs::metadata(&seg.path).unwrap().len(), seg.size);
+        }
+        assert_eq!(
+            disambiguate("/var/log/can/0900.log.gz", 3),
+            "/var/log/can/0900-3.log.gz"
+        );
+        assert_eq!(disambiguate("./.hidden", 1), "./.hidden-1");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotate_time() {
+        let dir = temp_dir("time");
+        let (rec, segs) = recorder(&dir, "{time}.log");
+        let mut rec = rec
+            .max_duration(Duration::from_secs(60))
+            .wall_clock(Duration::from_secs(3600));
+
+        let min = 60_000_000;
+        rec.record(T0 + 10 * min, "can0", &frame(1)).unwrap();
+        rec.record(T0 + 10 * min + 1, "can1", &frame(2)).unwrap();
+        // Max duration
+        rec.record(T0 + 11 * min, "can0", &frame(3)).unwrap();
+        // Wall clock, at 16:00
+        rec.record(T0 + 30 * min, "can0", &frame(4)).unwrap();
+        // Nothing received, but the duration passes
+        rec.tick(T0 + 31 * min).unwrap();
+        assert!(rec.current_path().is_none());
+        rec.tick(T0 + 32 * min).unwrap();
+
+        let names: Vec<_> = segs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|seg| seg.path.file_name().unwrap().to_owned())
+            .collect();
+        assert_eq!(names, ["154000.log", "154100.log", "160000.log"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_compressed() {
+        let dir = temp_dir("zstd");
+        let (rec, segs) = recorder(&dir, "{seq}.log.zst");
+        let mut rec = rec.sync_policy(SyncPolicy::EveryRecord);
+
+        for i in 0..10 {
+            rec.record(T0 + i, "can0", &frame(i as u16)).unwrap();
+        }
+        drop(rec);
+
+        let path = &segs.lock().unwrap()[0].path;
+        let rdr = crate::dump::LogFile::open(path).unwrap();
+        assert_eq!(rdr.compression(), Compression::Zstd);
+        assert_eq!(Reader::from_log_file(path).unwrap().records().count(), 10);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -91,7 +91,7 @@ pub type IoError = io::Error;
 pub type IoErrorKind = io::ErrorKind;
 
 /// An I/O specific result
-pub type IoResult<T> = std::io::Result<T>;
+pub type IoResult<T> = io::Result<T>;
 
 // ===== CanError ====
 
@@ -110,6 +110,7 @@ pub type IoResult<T> = std::io::Result<T>;
 /// (`CAN_ERR_FLAG`) is set. But there are additional types to handle any
 /// problems decoding the error frame.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CanError {
     /// TX timeout (by netdevice driver)
     TransmitTimeout,
@@ -219,6 +220,7 @@ impl From<CanErrorFrame> for CanError {
 ///
 /// This is derived from `data[1]` of an error frame
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum ControllerProblem {
     /// unspecified
@@ -283,6 +285,7 @@ impl TryFrom<u8> for ControllerProblem {
 ///
 /// This is derived from `data[2]` of an error frame.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum ViolationType {
     /// Unspecified Violation
@@ -352,6 +355,7 @@ impl TryFrom<u8> for ViolationType {
 ///
 /// This is derived from `data[3]` of an error frame.
 #[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum Location {
     /// Unspecified
@@ -461,6 +465,7 @@ impl TryFrom<u8> for Location {
 ///
 /// This is derived from `data[4]` of an error frame.
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum TransceiverError {
     /// Unsecified
@@ -529,6 +534,7 @@ impl<T: Frame> ControllerSpecificErrorInformation for T {
 
 /// Error decoding a CanError from a CanErrorFrame.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CanErrorDecodingFailure {
     /// The supplied CANFrame did not have the error bit set.
     NotAnError,
@@ -594,6 +600,81 @@ impl fmt::Display for ConstructionError {
     }
 }
 
+// ===== FrameParseError =====
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// Error that occurs when parsing a CAN frame from text
+pub enum FrameParseError {
+    /// The text is not in the cansend/candump frame notation
+    InvalidFormat,
+    /// The frame could not be created from the parsed values
+    ConstructionError(ConstructionError),
+}
+
+impl error::Error for FrameParseError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            FrameParseError::ConstructionError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FrameParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use FrameParseError::*;
+        match *self {
+            InvalidFormat => write!(f, "Invalid CAN frame format"),
+            ConstructionError(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<ConstructionError> for FrameParseError {
+    fn from(err: ConstructionError) -> Self {
+        FrameParseError::ConstructionError(err)
+    }
+}
+
+// ===== SignalError =====
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// Error that occurs when reading or writing a signal in a frame payload
+pub enum SignalError {
+    /// The size of the signal is not valid for its value type
+    InvalidSize(u32),
+    /// The signal extends past the end of the payload
+    OutOfBounds,
+    /// The frame could not be updated with the new payload
+    ConstructionError(ConstructionError),
+}
+
+impl error::Error for SignalError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            SignalError::ConstructionError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SignalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use SignalError::*;
+        match *self {
+            InvalidSize(n) => write!(f, "Invalid signal size: {} bits", n),
+            OutOfBounds => write!(f, "Signal extends past the end of the payload"),
+            ConstructionError(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<ConstructionError> for SignalError {
+    fn from(err: ConstructionError) -> Self {
+        SignalError::ConstructionError(err)
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
@@ -621,4 +702,24 @@ mod tests {
             panic!("Wrong error conversion");
         }
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        use crate::errors::{CanError, Location, ViolationType};
+
+        let err = CanError::ProtocolViolation {
+            vtype: ViolationType::BitStuffingError,
+            location: Location::CrcSequence,
+        };
+        let json = serde_json::to_string(&err).unwrap();
+        let err: CanError = serde_json::from_str(&json).unwrap();
+        assert!(matches!(
+            err,
+            CanError::ProtocolViolation {
+                vtype: ViolationType::BitStuffingError,
+                location: Location::CrcSequence
+            }
+        ));
+    }
 }
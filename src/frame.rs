@@ -15,29 +15,41 @@
 //! CAN frames as low-level structs that are binary compatible with the C
 //! data types sent to and from the kernel:
 //! - [can_frame](https://docs.rs/libc/latest/libc/struct.can_frame.html)
-//! The Classic CAN 2.0 frame with up to 8 bytes of data.
+//!   The Classic CAN 2.0 frame with up to 8 bytes of data.
 //! - [canfd_frame](https://docs.rs/libc/latest/libc/struct.canfd_frame.html)
-//! The CAN Flexible Data Rate frame with up to 64 bytes of data.
+//!   The CAN Flexible Data Rate frame with up to 64 bytes of data.
 //!
 //! The classic frame represents three possibilities:
 //! - `CanDataFrame` - A standard CAN frame that can contain up to 8 bytes of
-//! data.
+//!   data.
 //! - `CanRemoteFrame` - A CAN Remote frame which is meant to request a
-//! transmission by another node on the bus. It contain no data.
+//!   transmission by another node on the bus. It contain no data.
 //! - `CanErrorFrame` - This is an incoming (only) frame that contains
-//! information about a problem on the bus or in the driver. Error frames
-//! can not be sent to the bus, but can be converted to standard Rust
-//! [Error](https://doc.rust-lang.org/std/error/trait.Error.html) types.
+//!   information about a problem on the bus or in the driver. Error frames
+//!   can not be sent to the bus, but can be converted to standard Rust
+//!   [Error](https://doc.rust-lang.org/std/error/trait.Error.html) types.
+//!
+//! All of the frame types implement `Display` in the compact notation used
+//! by the `cansend` and `candump` utilities, and `CanFrame`, `CanFdFrame`,
+//! and `CanAnyFrame` can be parsed from it with `FromStr`:
+//!
+//! ```
+//! use socketcan::{CanFrame, EmbeddedFrame};
+//!
+//! let frame: CanFrame = "1F334455#11.22".parse().unwrap();
+//! assert_eq!(frame.data(), &[0x11, 0x22]);
+//! assert_eq!(frame.to_string(), "1F334455#1122");
+//! ```
 //!
 
-use crate::{CanError, ConstructionError};
+use crate::{CanError, ConstructionError, FrameParseError, SignalError, SignalSpec};
 use bitflags::bitflags;
 use embedded_can::{ExtendedId, Frame as EmbeddedFrame, Id, StandardId};
 use itertools::Itertools;
 use libc::{can_frame, canfd_frame, canid_t};
 use std::{
     ffi::c_void,
-    {convert::TryFrom, fmt, matches, mem},
+    {convert::TryFrom, fmt, matches, mem, mem::size_of, str::FromStr},
 };
 
 pub use libc::{
@@ -130,7 +142,7 @@ pub trait AsPtr {
 
     /// The size of the inner type
     fn size(&self) -> usize {
-        std::mem::size_of::<Self::Inner>()
+        size_of::<Self::Inner>()
     }
 
     /// Gets a byte slice to the inner type
@@ -223,6 +235,24 @@ pub trait Frame: EmbeddedFrame {
 
     /// Sets the data payload of the frame.
     fn set_data(&mut self, data: &[u8]) -> Result<(), ConstructionError>;
+
+    /// Gets the physical value of a signal in the data payload.
+    fn get_signal(&self, spec: &SignalSpec) -> Result<f64, SignalError> {
+        spec.decode(self.data())
+    }
+
+    /// Sets the physical value of a signal in the data payload.
+    ///
+    /// The signal must fit within the current payload; the length of the
+    /// payload is not changed.
+    fn set_signal(&mut self, spec: &SignalSpec, val: f64) -> Result<(), SignalError> {
+        let mut buf = [0u8; CANFD_MAX_DLEN];
+        let data = &mut buf[..self.data().len()];
+        data.copy_from_slice(self.data());
+        spec.encode(data, val)?;
+        self.set_data(data)?;
+        Ok(())
+    }
 }
 
 // ===== CanAnyFrame =====
@@ -251,6 +281,7 @@ impl From<canfd_frame> for CanRawFrame {
 
 /// Any frame type.
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CanAnyFrame {
     /// A classic CAN 2.0 frame, with up to 8-bytes of data
     Normal(CanDataFrame),
@@ -349,6 +380,7 @@ impl AsPtr for CanAnyFrame {
 
 /// The classic CAN 2.0 frame with up to 8-bytes of data.
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CanFrame {
     /// A data frame
     Data(CanDataFrame),
@@ -890,7 +922,7 @@ impl CanErrorFrame {
     /// - The error flag is forced on
     /// - The other, non-error, flags are forced off
     /// - The frame data is always padded with zero's to 8 bytes,
-    /// regardless of the length of the `data` parameter provided.
+    ///   regardless of the length of the `data` parameter provided.
     pub fn new_error(can_id: canid_t, data: &[u8]) -> Result<Self, ConstructionError> {
         match data.len() {
             n if n <= CAN_MAX_DLEN => {
@@ -1070,6 +1102,16 @@ impl AsRef<can_frame> for CanErrorFrame {
 
 // ===== CanFdFrame =====
 
+/// The valid data lengths of a CAN FD frame
+#[cfg(any(feature = "isotp", feature = "xcp"))]
+const FD_LENGTHS: [usize; 8] = [8, 12, 16, 20, 24, 32, 48, 64];
+
+/// Gets the smallest valid CAN FD frame length that can hold `len` bytes.
+#[cfg(any(feature = "isotp", feature = "xcp"))]
+pub(crate) fn fd_len(len: usize) -> usize {
+    FD_LENGTHS.iter().copied().find(|&n| n >= len).unwrap_or(64)
+}
+
 /// The CAN flexible data rate frame with up to 64-bytes of data.
 ///
 /// This is highly compatible with the `canfd_frame` from libc.
@@ -1269,6 +1311,429 @@ impl AsRef<canfd_frame> for CanFdFrame {
     }
 }
 
+// ===== Text format =====
+
+// Gets the value of a single ASCII hex digit
+fn hex_nibble(c: u8) -> Result<u8, FrameParseError> {
+    match c {
+        b'0'..=b'9' => Ok(c - b'0'),
+        b'a'..=b'f' => Ok(c - b'a' + 10),
+        b'A'..=b'F' => Ok(c - b'A' + 10),
+        _ => Err(FrameParseError::InvalidFormat),
+    }
+}
+
+// Decodes a hex payload string into the buffer, returning the number of
+// bytes written. The '.' separators accepted by `cansend` are skipped.
+fn decode_hex_data(src: &[u8], buf: &mut [u8]) -> Result<usize, FrameParseError> {
+    let mut n = 0;
+    let mut digits = src.iter().copied().filter(|&c| c != b'.');
+
+    while let Some(hi) = digits.next() {
+        let lo = digits.next().ok_or(FrameParseError::InvalidFormat)?;
+        if n == buf.len() {
+            return Err(ConstructionError::TooMuchData.into());
+        }
+        buf[n] = (hex_nibble(hi)? << 4) | hex_nibble(lo)?;
+        n += 1;
+    }
+    Ok(n)
+}
+
+/// Parses a frame in the compact candump/cansend notation.
+///
+/// This handles classic data frames (`123#DEADBEEF`), remote frames
+/// (`123#R`, with an optional DLC digit), FD frames (`123##1DEADBEEF`) and
+/// error frames (an ID with the `CAN_ERR_FLAG` bit set). The payload is
+/// decoded straight into the frame.
+pub(crate) fn parse_frame(s: &[u8]) -> Result<CanAnyFrame, FrameParseError> {
+    let sep_idx = s
+        .iter()
+        .position(|&c| c == b'#')
+        .ok_or(FrameParseError::InvalidFormat)?;
+    let (can_id, can_data) = s.split_at(sep_idx);
+
+    if can_id.is_empty() {
+        return Err(FrameParseError::InvalidFormat);
+    }
+
+    let mut raw_id = 0u64;
+    for &c in can_id {
+        raw_id = raw_id
+            .checked_mul(16)
+            .ok_or(ConstructionError::IDTooLarge)?
+            + u64::from(hex_nibble(c)?);
+    }
+
+    // Error frames are written with the error flag in the ID. Otherwise,
+    // the ID is extended if it's out of the standard range, or written
+    // with more than three digits, as candump does.
+    let id_word = if raw_id & CAN_ERR_FLAG as u64 != 0 && raw_id <= canid_t::MAX as u64 {
+        raw_id as canid_t
+    } else if raw_id > CAN_EFF_MASK as u64 {
+        return Err(ConstructionError::IDTooLarge.into());
+    } else if raw_id > CAN_SFF_MASK as u64 || can_id.len() > 3 {
+        raw_id as canid_t | CAN_EFF_FLAG
+    } else {
+        raw_id as canid_t
+    };
+
+    match can_data {
+        [b'#', b'#', flags, data @ ..] => {
+            let mut frame = canfd_frame_default();
+            frame.can_id = id_word;
+            frame.flags = FdFlags::from_bits_truncate(hex_nibble(*flags)?).bits();
+            frame.len = decode_hex_data(data, &mut frame.data)? as u8;
+            Ok(CanFdFrame::from(frame).into())
+        }
+        [b'#', b'R', dlc @ ..] => {
+            let dlc = match dlc {
+                [] => 0,
+                [n] => match hex_nibble(*n)? {
+                    n if n as usize <= CAN_MAX_DLEN => n,
+                    _ => return Err(ConstructionError::TooMuchData.into()),
+                },
+                _ => return Err(FrameParseError::InvalidFormat),
+            };
+            let mut frame = can_frame_default();
+            frame.can_id = id_word | CAN_RTR_FLAG;
+            frame.can_dlc = dlc;
+            Ok(CanFrame::from(frame).into())
+        }
+        [b'#', data @ ..] => {
+            let mut frame = can_frame_default();
+            frame.can_id = id_word;
+            frame.can_dlc = decode_hex_data(data, &mut frame.data)? as u8;
+            Ok(CanFrame::from(frame).into())
+        }
+        _ => Err(FrameParseError::InvalidFormat),
+    }
+}
+
+// Writes the ID word in cansend notation: three digits for a standard ID,
+// and eight for an extended ID or an error frame.
+fn fmt_id(id_word: canid_t, f: &mut fmt::Formatter) -> fmt::Result {
+    if id_word & CAN_ERR_FLAG != 0 {
+        write!(f, "{:08X}", id_word & (CAN_ERR_FLAG | CAN_ERR_MASK))
+    } else if id_word & CAN_EFF_FLAG != 0 {
+        write!(f, "{:08X}", id_word & CAN_EFF_MASK)
+    } else {
+        write!(f, "{:03X}", id_word & CAN_SFF_MASK)
+    }
+}
+
+// Writes the data bytes as contiguous hex digits.
+fn fmt_data(data: &[u8], f: &mut fmt::Formatter) -> fmt::Result {
+    data.iter().try_for_each(|b| write!(f, "{:02X}", b))
+}
+
+impl fmt::Display for CanDataFrame {
+    /// Writes the frame in cansend notation, like `123#DEADBEEF`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_id(self.id_word(), f)?;
+        f.write_str("#")?;
+        fmt_data(self.data(), f)
+    }
+}
+
+impl fmt::Display for CanRemoteFrame {
+    /// Writes the frame in cansend notation, like `123#R`, or `123#R4`
+    /// with a DLC.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_id(self.id_word(), f)?;
+        f.write_str("#R")?;
+        match self.dlc() {
+            0 => Ok(()),
+            n => write!(f, "{:X}", n),
+        }
+    }
+}
+
+impl fmt::Display for CanErrorFrame {
+    /// Writes the frame in candump notation, with the error flag in the ID,
+    /// like `20000004#0000000000000000`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_id(self.id_word(), f)?;
+        f.write_str("#")?;
+        fmt_data(self.data(), f)
+    }
+}
+
+impl fmt::Display for CanFdFrame {
+    /// Writes the frame in cansend notation, with the flags digit after
+    /// the double separator, like `123##1DEADBEEF`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_id(self.id_word(), f)?;
+        write!(f, "##{:X}", self.flags().bits())?;
+        fmt_data(self.data(), f)
+    }
+}
+
+impl fmt::Display for CanFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use CanFrame::*;
+        match self {
+            Data(frame) => frame.fmt(f),
+            Remote(frame) => frame.fmt(f),
+            Error(frame) => frame.fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for CanAnyFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use CanAnyFrame::*;
+        match self {
+            Normal(frame) => frame.fmt(f),
+            Remote(frame) => frame.fmt(f),
+            Error(frame) => frame.fmt(f),
+            Fd(frame) => frame.fmt(f),
+        }
+    }
+}
+
+impl FromStr for CanAnyFrame {
+    type Err = FrameParseError;
+
+    /// Parses a frame of any type from cansend notation.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_frame(s.as_bytes())
+    }
+}
+
+impl FromStr for CanFrame {
+    type Err = FrameParseError;
+
+    /// Parses a classic data, remote, or error frame from cansend notation.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use CanAnyFrame::*;
+        match parse_frame(s.as_bytes())? {
+            Normal(frame) => Ok(frame.into()),
+            Remote(frame) => Ok(frame.into()),
+            Error(frame) => Ok(frame.into()),
+            Fd(_) => Err(ConstructionError::WrongFrameType.into()),
+        }
+    }
+}
+
+impl FromStr for CanFdFrame {
+    type Err = FrameParseError;
+
+    /// Parses an FD frame from cansend notation, like `123##1DEADBEEF`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_frame(s.as_bytes())? {
+            CanAnyFrame::Fd(frame) => Ok(frame),
+            _ => Err(ConstructionError::WrongFrameType.into()),
+        }
+    }
+}
+
+// ===== serde =====
+
+/// Serde helpers to represent a CAN ID word as a readable hex string,
+/// like `"0x123"`.
+///
+/// When deserializing, the `0x` prefix is optional, and a plain integer
+/// is also accepted.
+#[cfg(feature = "serde")]
+pub(crate) mod hex_id {
+    use libc::canid_t;
+    use serde::{de, Deserializer, Serializer};
+    use std::{convert::TryFrom, fmt};
+
+    // Human-readable formats get a hex string, and compact ones, which may
+    // not be self-describing, get a plain integer.
+    pub fn serialize<S: Serializer>(id: &canid_t, ser: S) -> Result<S::Ok, S::Error> {
+        if ser.is_human_readable() {
+            ser.collect_str(&format_args!("{:#X}", id))
+        } else {
+            ser.serialize_u32(*id)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(de: D) -> Result<canid_t, D::Error> {
+        if de.is_human_readable() {
+            de.deserialize_any(HexIdVisitor)
+        } else {
+            de.deserialize_u32(HexIdVisitor)
+        }
+    }
+
+    struct HexIdVisitor;
+
+    impl<'de> de::Visitor<'de> for HexIdVisitor {
+        type Value = canid_t;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a CAN ID as a hex string or an integer")
+        }
+
+        fn visit_str<E: de::Error>(self, s: &str) -> Result<canid_t, E> {
+            let digits = s
+                .strip_prefix("0x")
+                .or_else(|| s.strip_prefix("0X"))
+                .unwrap_or(s);
+            canid_t::from_str_radix(digits, 16).map_err(E::custom)
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<canid_t, E> {
+            canid_t::try_from(v).map_err(E::custom)
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<canid_t, E> {
+            canid_t::try_from(v).map_err(E::custom)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+    use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+    use std::borrow::Cow;
+
+    // The common representation of data, remote, and FD frames.
+    #[derive(Serialize, Deserialize)]
+    struct FrameRepr<'a> {
+        #[serde(with = "hex_id")]
+        id: canid_t,
+        // Inferred from the ID if missing
+        #[serde(default)]
+        extended: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        dlc: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        brs: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        esi: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        data: Option<Cow<'a, [u8]>>,
+    }
+
+    impl<'a> FrameRepr<'a> {
+        fn new<F: Frame>(frame: &'a F) -> Self {
+            Self {
+                id: frame.raw_id(),
+                extended: Some(frame.is_extended()),
+                dlc: None,
+                brs: None,
+                esi: None,
+                data: Some(Cow::Borrowed(frame.data())),
+            }
+        }
+
+        fn id<E: serde::de::Error>(&self) -> Result<Id, E> {
+            let ext = self.extended.unwrap_or(self.id > CAN_SFF_MASK);
+            let id = if ext {
+                ExtendedId::new(self.id).map(Id::Extended)
+            } else {
+                u16::try_from(self.id)
+                    .ok()
+                    .and_then(StandardId::new)
+                    .map(Id::Standard)
+            };
+            id.ok_or_else(|| E::custom(ConstructionError::IDTooLarge))
+        }
+
+        fn data(&self) -> &[u8] {
+            self.data.as_deref().unwrap_or_default()
+        }
+
+        // Formats that aren't self-describing can't tell that a field was
+        // skipped, so every field is written to them.
+        fn write<S: Serializer>(mut self, ser: S) -> Result<S::Ok, S::Error> {
+            if !ser.is_human_readable() {
+                self.dlc.get_or_insert(0);
+                self.brs.get_or_insert(false);
+                self.esi.get_or_insert(false);
+                self.data.get_or_insert(Cow::Borrowed(&[]));
+            }
+            self.serialize(ser)
+        }
+    }
+
+    // The representation of an error frame.
+    #[derive(Serialize, Deserialize)]
+    struct ErrorFrameRepr<'a> {
+        #[serde(with = "hex_id")]
+        id: canid_t,
+        data: Cow<'a, [u8]>,
+    }
+
+    impl Serialize for CanDataFrame {
+        fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+            FrameRepr::new(self).write(ser)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for CanDataFrame {
+        fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+            let repr = FrameRepr::deserialize(de)?;
+            Self::new(repr.id()?, repr.data())
+                .ok_or_else(|| D::Error::custom(ConstructionError::TooMuchData))
+        }
+    }
+
+    impl Serialize for CanRemoteFrame {
+        fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+            FrameRepr {
+                dlc: Some(self.dlc()),
+                data: None,
+                ..FrameRepr::new(self)
+            }
+            .write(ser)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for CanRemoteFrame {
+        fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+            let repr = FrameRepr::deserialize(de)?;
+            Self::new_remote(repr.id()?, repr.dlc.unwrap_or_default())
+                .ok_or_else(|| D::Error::custom(ConstructionError::TooMuchData))
+        }
+    }
+
+    impl Serialize for CanErrorFrame {
+        /// The ID is the error class bits, without the error flag.
+        fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+            ErrorFrameRepr {
+                id: self.error_bits(),
+                data: Cow::Borrowed(self.data()),
+            }
+            .serialize(ser)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for CanErrorFrame {
+        fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+            let repr = ErrorFrameRepr::deserialize(de)?;
+            Self::new_error(repr.id, &repr.data).map_err(D::Error::custom)
+        }
+    }
+
+    impl Serialize for CanFdFrame {
+        fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+            FrameRepr {
+                brs: Some(self.is_brs()),
+                esi: Some(self.is_esi()),
+                ..FrameRepr::new(self)
+            }
+            .write(ser)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for CanFdFrame {
+        fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+            let repr = FrameRepr::deserialize(de)?;
+            let mut flags = FdFlags::empty();
+            flags.set(FdFlags::BRS, repr.brs.unwrap_or_default());
+            flags.set(FdFlags::ESI, repr.esi.unwrap_or_default());
+            Self::with_flags(repr.id()?, repr.data(), flags)
+                .ok_or_else(|| D::Error::custom(ConstructionError::TooMuchData))
+        }
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
@@ -1493,7 +1958,7 @@ mod tests {
                 assert_eq!(location, errors::Location::Id0400);
             }
             _ => {
-                assert!(false);
+                panic!("Wrong error type");
             }
         }
     }
@@ -1539,4 +2004,176 @@ mod tests {
         assert!(!frame.is_error_frame());
         assert_eq!(DATA, frame.data());
     }
+
+    #[test]
+    fn test_from_str() {
+        let frame: CanFrame = "123#DEADBEEF".parse().unwrap();
+        assert_eq!(frame.raw_id(), 0x123);
+        assert!(!frame.is_extended());
+        assert_eq!(frame.data(), &[0xDE, 0xAD, 0xBE, 0xEF]);
+
+        let frame: CanFrame = "1F334455#11.22".parse().unwrap();
+        assert_eq!(frame.raw_id(), 0x1F334455);
+        assert!(frame.is_extended());
+        assert_eq!(frame.data(), &[0x11, 0x22]);
+
+        let frame: CanFrame = "123#R".parse().unwrap();
+        assert!(frame.is_remote_frame());
+        assert_eq!(frame.dlc(), 0);
+
+        let frame: CanFdFrame = "123##3112233".parse().unwrap();
+        assert_eq!(frame.raw_id(), 0x123);
+        assert!(frame.is_brs());
+        assert!(frame.is_esi());
+        assert_eq!(frame.data(), &[0x11, 0x22, 0x33]);
+
+        let frame: CanAnyFrame = "20000004#0000000000000000".parse().unwrap();
+        assert!(matches!(frame, CanAnyFrame::Error(_)));
+    }
+
+    #[test]
+    fn test_from_str_errors() {
+        use FrameParseError::*;
+
+        assert_eq!("123".parse::<CanFrame>().unwrap_err(), InvalidFormat);
+        assert_eq!("#00".parse::<CanFrame>().unwrap_err(), InvalidFormat);
+        assert_eq!("12G#00".parse::<CanFrame>().unwrap_err(), InvalidFormat);
+        assert_eq!("123#001".parse::<CanFrame>().unwrap_err(), InvalidFormat);
+        assert_eq!(
+            "123#001122334455667788".parse::<CanFrame>().unwrap_err(),
+            ConstructionError(errors::ConstructionError::TooMuchData)
+        );
+        assert_eq!(
+            "40000000#00".parse::<CanFrame>().unwrap_err(),
+            ConstructionError(errors::ConstructionError::IDTooLarge)
+        );
+        assert_eq!(
+            "123##100".parse::<CanFrame>().unwrap_err(),
+            ConstructionError(errors::ConstructionError::WrongFrameType)
+        );
+        assert_eq!(
+            "123#00".parse::<CanFdFrame>().unwrap_err(),
+            ConstructionError(errors::ConstructionError::WrongFrameType)
+        );
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for s in [
+            "123#DEADBEEF",
+            "1F334455#1122",
+            "00000123#",
+            "123#R",
+            "00000123#R5",
+            "123##3112233",
+            "1F334455##0",
+            "20000004#0000000000000000",
+        ] {
+            let frame: CanAnyFrame = s.parse().unwrap();
+            assert_eq!(frame.to_string(), s);
+        }
+
+        let frame = CanFrame::new(STD_ID, DATA).unwrap();
+        assert_eq!(frame.to_string(), "7FF#00010203");
+        assert_eq!(frame.to_string().parse::<CanFrame>().unwrap().id(), STD_ID);
+
+        let frame = CanFdFrame::new(EXT_LOW_ID, DATA).unwrap();
+        assert_eq!(frame.to_string(), "000007FF##000010203");
+        assert_eq!(
+            frame.to_string().parse::<CanFdFrame>().unwrap().id(),
+            EXT_LOW_ID
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        use serde_json::{from_str, json, to_value};
+
+        let frame = CanFrame::new(STD_ID, DATA).unwrap();
+        assert_eq!(
+            to_value(frame).unwrap(),
+            json!({"Data": {"id": "0x7FF", "extended": false, "data": [0, 1, 2, 3]}})
+        );
+
+        let frame = CanRemoteFrame::new_remote(EXT_ID, 2).unwrap();
+        let val = to_value(frame).unwrap();
+        assert_eq!(val, json!({"id": "0x1FFFFFFF", "extended": true, "dlc": 2}));
+        let frame: CanRemoteFrame = serde_json::from_value(val).unwrap();
+        assert_eq!(frame.id(), EXT_ID);
+        assert_eq!(frame.dlc(), 2);
+
+        let frame = CanFdFrame::with_flags(EXT_LOW_ID, DATA, FdFlags::BRS).unwrap();
+        let val = to_value(CanAnyFrame::from(frame)).unwrap();
+        assert_eq!(val["Fd"]["id"], "0x7FF");
+        match serde_json::from_value(val).unwrap() {
+            CanAnyFrame::Fd(frame) => {
+                assert_eq!(frame.id(), EXT_LOW_ID);
+                assert!(frame.is_brs());
+                assert!(!frame.is_esi());
+                assert_eq!(frame.data(), DATA);
+            }
+            _ => panic!("Expected FD frame"),
+        }
+
+        let frame = CanErrorFrame::new_error(0x04, &[0, 0x08]).unwrap();
+        let val = to_value(frame).unwrap();
+        assert_eq!(val["id"], "0x4");
+        let frame: CanErrorFrame = serde_json::from_value(val).unwrap();
+        assert_eq!(frame.error_bits(), 0x04);
+
+        // The ID can be hex, with or without the prefix, or an integer,
+        // and the extended flag is inferred if missing.
+        let frame: CanDataFrame = from_str(r#"{"id": "123", "data": [1]}"#).unwrap();
+        assert_eq!(frame.raw_id(), 0x123);
+        let frame: CanDataFrame = from_str(r#"{"id": 291}"#).unwrap();
+        assert_eq!(frame.raw_id(), 0x123);
+        assert!(frame.data().is_empty());
+        let frame: CanDataFrame = from_str(r#"{"id": "0x12345"}"#).unwrap();
+        assert!(frame.is_extended());
+
+        assert!(from_str::<CanDataFrame>(r#"{"id": "0x800", "extended": false}"#).is_err());
+        assert!(from_str::<CanDataFrame>(r#"{"id": "0x1", "data": [0,0,0,0,0,0,0,0,0]}"#).is_err());
+        assert!(from_str::<CanDataFrame>(r#"{"id": "xyz"}"#).is_err());
+
+        let filter = crate::CanFilter::new(0x100 | CAN_EFF_FLAG, 0x700);
+        let val = to_value(filter).unwrap();
+        assert_eq!(val, json!({"id": "0x80000100", "mask": "0x700"}));
+        assert_eq!(
+            serde_json::from_value::<crate::CanFilter>(val).unwrap(),
+            filter
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_compact() {
+        // bincode isn't self-describing, and gets the ID as an integer
+        let frame = CanFrame::new(STD_ID, DATA).unwrap();
+        let buf = bincode::serialize(&frame).unwrap();
+        assert_eq!(&buf[4..8], &0x7FFu32.to_le_bytes());
+
+        let frames: Vec<CanAnyFrame> = vec![
+            frame.into(),
+            CanFrame::Remote(CanRemoteFrame::new_remote(EXT_ID, 2).unwrap()).into(),
+            CanFdFrame::with_flags(EXT_LOW_ID, DATA, FdFlags::BRS)
+                .unwrap()
+                .into(),
+            CanFrame::Error(CanErrorFrame::new_error(0x04, &[0, 0x08]).unwrap()).into(),
+        ];
+        let buf = bincode::serialize(&frames).unwrap();
+        let decoded: Vec<CanAnyFrame> = bincode::deserialize(&buf).unwrap();
+        let strings = |frames: &[CanAnyFrame]| -> Vec<String> {
+            frames.iter().map(|f| format!("{:?}", f)).collect()
+        };
+        assert_eq!(strings(&decoded), strings(&frames));
+
+        let filter = crate::CanFilter::new(0x100 | CAN_EFF_FLAG, 0x700);
+        let buf = bincode::serialize(&filter).unwrap();
+        assert_eq!(buf.len(), 8);
+        assert_eq!(
+            bincode::deserialize::<crate::CanFilter>(&buf).unwrap(),
+            filter
+        );
+    }
 }
@@ -0,0 +1,719 @@
+// socketcan/src/isotp/mod.rs
+//
+// A userspace implementation of the ISO-TP transport protocol.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! The ISO-TP (ISO 15765-2) transport protocol, in userspace.
+//!
+//! ISO-TP carries messages of up to 4095 bytes (or up to 4 GiB with the
+//! 2016 revision of the standard) over CAN, by splitting them into a
+//! _first frame_ and a number of _consecutive frames_, with the receiver
+//! pacing the sender through _flow control_ frames. It's the transport
+//! used by diagnostic protocols like UDS and OBD-II.
+//!
+//! The Linux kernel has its own ISO-TP sockets, in the `can-isotp` module,
+//! but that isn't loaded on every system. This is an implementation that
+//! runs entirely in userspace over a raw [`CanSocket`](crate::CanSocket)
+//! or [`CanFdSocket`](crate::CanFdSocket), or any other [`Link`], so it
+//! also works on a `vcan` interface, or over the in-process [`VirtualBus`].
+//!
+//! It supports:
+//!
+//! - Single, first, consecutive, and flow control frames
+//! - Block size and minimum separation time (STmin) flow control
+//! - Frame padding
+//! - Normal, extended, and mixed addressing
+//! - CAN FD frames with up to 64 bytes, and messages longer than 4095
+//!   bytes
+//! - The N_As, N_Ar, N_Bs, and N_Cr timeouts
+//!
+//! Each [`IsoTpSocket`] is a connection between a pair of CAN IDs:
+//!
+//! ```no_run
+//! use socketcan::{isotp::{IsoTpSocket, Options}, CanSocket, StandardId};
+//!
+//! let opts = Options::new(StandardId::new(0x7E0).unwrap(), StandardId::new(0x7E8).unwrap())
+//!     .padding(Some(0xAA));
+//! let mut sock = IsoTpSocket::<CanSocket>::open("vcan0", opts).unwrap();
+//!
+//! sock.send(&[0x22, 0xF1, 0x90]).unwrap();
+//! let resp = sock.recv().unwrap();
+//! println!("{:02X?}", resp);
+//! ```
+//!
+//! The transport is half-duplex: a socket is either sending or receiving a
+//! message at any one time. Frames from other messages arriving while a
+//! socket is sending are dropped.
+//!
+//! An asynchronous version for tokio is in the [`tokio`](self::tokio)
+//! submodule, when the `tokio` feature is enabled.
+
+use crate::{
+    frame::{fd_len, id_to_canid_t},
+    CanAnyFrame, CanFilter, Id, IoResult, Socket, SocketOptions,
+};
+use libc::{CAN_EFF_FLAG, CAN_EFF_MASK, CAN_RTR_FLAG, CAN_SFF_MASK};
+use std::{
+    error, fmt, io, thread,
+    time::{Duration, Instant},
+};
+
+pub(crate) mod pdu;
+use pdu::{Codec, Receiver, RxAction, Transmitter, TxAction};
+
+pub use crate::link::{Link, VirtualBus, VirtualLink};
+
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+/// The default for each of the protocol timeouts
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(1000);
+
+// ===== Options =====
+
+/// The addressing format of an ISO-TP connection.
+///
+/// This determines whether the first byte of each frame holds an address,
+/// in addition to the CAN ID.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Addressing {
+    /// The CAN ID alone identifies the connection.
+    #[default]
+    Normal,
+    /// The first byte of each frame is the target address.
+    Extended {
+        /// The address sent in the first byte of transmitted frames
+        tx_addr: u8,
+        /// The address expected in the first byte of received frames
+        rx_addr: u8,
+    },
+    /// The first byte of each frame is an address extension, the same in
+    /// both directions.
+    Mixed {
+        /// The address extension
+        ae: u8,
+    },
+}
+
+/// The options for an ISO-TP connection.
+///
+/// This is created with the pair of CAN IDs for the connection, and the
+/// rest of the options can be set with the builder-style methods.
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    tx_id: Id,
+    rx_id: Id,
+    addressing: Addressing,
+    tx_dl: usize,
+    brs: bool,
+    padding: Option<u8>,
+    block_size: u8,
+    st_min: Duration,
+    wft_max: u8,
+    max_rx_len: usize,
+    n_as: Duration,
+    n_ar: Duration,
+    n_bs: Duration,
+    n_cr: Duration,
+}
+
+impl Options {
+    /// Creates options for a connection that sends frames with the
+    /// `tx_id` and receives frames with the `rx_id`.
+    ///
+    /// The defaults are classic CAN frames with normal addressing and no
+    /// padding, no block size or STmin limits, a 4095 byte receive limit,
+    /// and the standard one second timeouts.
+    pub fn new(tx_id: impl Into<Id>, rx_id: impl Into<Id>) -> Self {
+        Self {
+            tx_id: tx_id.into(),
+            rx_id: rx_id.into(),
+            addressing: Addressing::Normal,
+            tx_dl: 8,
+            brs: false,
+            padding: None,
+            block_size: 0,
+            st_min: Duration::ZERO,
+            wft_max: 10,
+            max_rx_len: 4095,
+            n_as: DEFAULT_TIMEOUT,
+            n_ar: DEFAULT_TIMEOUT,
+            n_bs: DEFAULT_TIMEOUT,
+            n_cr: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Gets the CAN ID of transmitted frames.
+    pub fn tx_id(&self) -> Id {
+        self.tx_id
+    }
+
+    /// Gets the CAN ID of received frames.
+    pub fn rx_id(&self) -> Id {
+        self.rx_id
+    }
+
+    /// Sets the addressing format.
+    pub fn addressing(mut self, addressing: Addressing) -> Self {
+        self.addressing = addressing;
+        self
+    }
+
+    /// Sets the maximum data length of transmitted frames.
+    ///
+    /// A length of 8 sends classic CAN frames. Anything longer sends CAN FD
+    /// frames, with the length rounded up to a valid CAN FD frame length,
+    /// up to 64. Lengths below 8 are raised to 8, as the protocol needs
+    /// full classic frames. Received frames can be either type.
+    pub fn tx_dl(mut self, tx_dl: usize) -> Self {
+        self.tx_dl = fd_len(tx_dl);
+        self
+    }
+
+    /// Sets whether transmitted CAN FD frames use the bit rate switch.
+    pub fn brs(mut self, on: bool) -> Self {
+        self.brs = on;
+        self
+    }
+
+    /// Sets the byte used to pad transmitted frames to the full 8 bytes.
+    ///
+    /// When `None`, classic frames are sent with only as many bytes as
+    /// needed. CAN FD frames longer than 8 bytes are always padded to a
+    /// valid length, using 0xCC if no padding byte was set.
+    pub fn padding(mut self, padding: Option<u8>) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Sets the block size sent to the peer in flow control frames.
+    ///
+    /// This is the number of consecutive frames the peer can send before
+    /// waiting for the next flow control frame. Zero means no limit.
+    pub fn block_size(mut self, block_size: u8) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Sets the minimum separation time sent to the peer in flow control
+    /// frames.
+    ///
+    /// This is the time the peer should wait between consecutive frames.
+    /// It's rounded down to what can be represented in the frame: whole
+    /// milliseconds up to 127 ms, or multiples of 100 µs under 1 ms.
+    pub fn st_min(mut self, st_min: Duration) -> Self {
+        self.st_min = st_min;
+        self
+    }
+
+    /// Sets the maximum number of consecutive wait flow control frames
+    /// accepted from the peer when sending.
+    pub fn wft_max(mut self, wft_max: u8) -> Self {
+        self.wft_max = wft_max;
+        self
+    }
+
+    /// Sets the length of the longest message that will be received.
+    ///
+    /// Longer messages are refused with an overflow flow control frame.
+    pub fn max_rx_len(mut self, max_rx_len: usize) -> Self {
+        self.max_rx_len = max_rx_len;
+        self
+    }
+
+    /// Sets the N_As timeout, the time to transmit a frame when sending.
+    pub fn n_as(mut self, timeout: Duration) -> Self {
+        self.n_as = timeout;
+        self
+    }
+
+    /// Sets the N_Ar timeout, the time to transmit a flow control frame
+    /// when receiving.
+    pub fn n_ar(mut self, timeout: Duration) -> Self {
+        self.n_ar = timeout;
+        self
+    }
+
+    /// Sets the N_Bs timeout, the time to wait for a flow control frame
+    /// when sending.
+    pub fn n_bs(mut self, timeout: Duration) -> Self {
+        self.n_bs = timeout;
+        self
+    }
+
+    /// Sets the N_Cr timeout, the time to wait for the next consecutive
+    /// frame when receiving.
+    pub fn n_cr(mut self, timeout: Duration) -> Self {
+        self.n_cr = timeout;
+        self
+    }
+
+    /// Gets a kernel filter that passes only the frames for the
+    /// connection.
+    pub fn filter(&self) -> CanFilter {
+        let id = id_to_canid_t(self.rx_id);
+        let mask = match self.rx_id {
+            Id::Standard(_) => CAN_SFF_MASK,
+            Id::Extended(_) => CAN_EFF_MASK,
+        };
+        CanFilter::new(id, mask | CAN_EFF_FLAG | CAN_RTR_FLAG)
+    }
+}
+
+// ===== Error =====
+
+/// The ISO-TP protocol timers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timer {
+    /// The time to transmit a frame, when sending
+    As,
+    /// The time to transmit a frame, when receiving
+    Ar,
+    /// The time until a flow control frame is received, when sending
+    Bs,
+    /// The time until a consecutive frame is received, when receiving
+    Cr,
+}
+
+impl fmt::Display for Timer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Timer::*;
+        let s = match self {
+            As => "N_As",
+            Ar => "N_Ar",
+            Bs => "N_Bs",
+            Cr => "N_Cr",
+        };
+        f.write_str(s)
+    }
+}
+
+/// An error sending or receiving an ISO-TP message.
+#[derive(Debug)]
+pub enum Error {
+    /// An I/O error on the underlying link
+    Io(io::Error),
+    /// One of the protocol timers expired
+    Timeout(Timer),
+    /// A consecutive frame arrived out of order
+    WrongSequenceNumber {
+        /// The sequence number that was expected
+        expected: u8,
+        /// The sequence number that was received
+        received: u8,
+    },
+    /// The message is longer than the receiver accepts
+    Overflow,
+    /// The peer sent more wait flow control frames than allowed
+    WaitLimit,
+    /// A flow control frame had an invalid flow status
+    InvalidFlowStatus(u8),
+    /// The message is empty, or too long for the protocol
+    InvalidLength(usize),
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+        match *self {
+            Io(ref err) => write!(f, "I/O error: {}", err),
+            Timeout(timer) => write!(f, "ISO-TP {} timeout", timer),
+            WrongSequenceNumber { expected, received } => write!(
+                f,
+                "wrong sequence number: expected {}, received {}",
+                expected, received
+            ),
+            Overflow => f.write_str("message too long for the receiver"),
+            WaitLimit => f.write_str("too many wait flow control frames"),
+            InvalidFlowStatus(fs) => write!(f, "invalid flow status: {}", fs),
+            InvalidLength(n) => write!(f, "invalid ISO-TP message length: {}", n),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Io(err) => err,
+            Error::Timeout(_) => io::Error::new(io::ErrorKind::TimedOut, err),
+            _ => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+// ===== IsoTpSocket =====
+
+/// An ISO-TP connection over a CAN link.
+#[derive(Debug)]
+pub struct IsoTpSocket<L> {
+    link: L,
+    opts: Options,
+    codec: Codec,
+}
+
+impl<L: Link + Socket + SocketOptions> IsoTpSocket<L> {
+    /// Opens a raw CAN socket on the named interface for the connection.
+    ///
+    /// This sets a kernel filter on the socket to receive only the frames
+    /// for the connection, and uses the N_As timeout as the socket's write
+    /// timeout.
+    pub fn open(ifname: &str, opts: Options) -> IoResult<Self> {
+        let sock = L::open(ifname)?;
+        sock.set_filters(&[opts.filter()])?;
+        sock.set_write_timeout(opts.n_as)?;
+        Ok(Self::new(sock, opts))
+    }
+}
+
+impl<L: Link> IsoTpSocket<L> {
+    /// Creates an ISO-TP connection over an existing link.
+    pub fn new(link: L, opts: Options) -> Self {
+        let codec = Codec::new(&opts);
+        Self { link, opts, codec }
+    }
+
+    /// Gets the options for the connection.
+    pub fn options(&self) -> &Options {
+        &self.opts
+    }
+
+    /// Gets a reference to the underlying link.
+    pub fn link(&self) -> &L {
+        &self.link
+    }
+
+    /// Consumes the connection, returning the underlying link.
+    pub fn into_link(self) -> L {
+        self.link
+    }
+
+    /// Sends a message, blocking until the whole message was sent.
+    pub fn send(&mut self, data: &[u8]) -> Result<(), Error> {
+        let mut tx = Transmitter::new(&self.codec, data, &self.opts)?;
+        loop {
+            match tx.next_action() {
+                TxAction::Send(frame, delay) => {
+                    if !delay.is_zero() {
+                        thread::sleep(delay);
+                    }
+                    self.write(&frame, Timer::As, self.opts.n_as)?;
+                }
+                TxAction::AwaitFlowControl => {
+                    let deadline = Instant::now() + self.opts.n_bs;
+                    loop {
+                        let frame = self
+                            .read(Some(deadline))?
+                            .ok_or(Error::Timeout(Timer::Bs))?;
+                        if tx.flow_control(&frame)? {
+                            break;
+                        }
+                    }
+                }
+                TxAction::Done => return Ok(()),
+            }
+        }
+    }
+
+    /// Receives a message, blocking until a whole message arrives.
+    pub fn recv(&mut self) -> Result<Vec<u8>, Error> {
+        self.recv_until(None)
+            .map(|data| data.expect("blocking receive"))
+    }
+
+    /// Receives a message, waiting for up to `timeout` for it to start.
+    ///
+    /// Returns `None` if no message started within the timeout. Once a
+    /// message starts, the N_Cr timeout applies to the rest of it.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<Vec<u8>>, Error> {
+        self.recv_until(Some(Instant::now() + timeout))
+    }
+
+    /// Receives a message, waiting until the deadline for it to start.
+    fn recv_until(&mut self, deadline: Option<Instant>) -> Result<Option<Vec<u8>>, Error> {
+        let mut rx = Receiver::new(&self.codec, &self.opts);
+        let mut cr_deadline = Instant::now();
+
+        loop {
+            let wait = if rx.in_progress() {
+                Some(cr_deadline)
+            } else {
+                deadline
+            };
+            let frame = match self.read(wait)? {
+                Some(frame) => frame,
+                None if rx.in_progress() => return Err(Error::Timeout(Timer::Cr)),
+                None => return Ok(None),
+            };
+
+            match rx.on_frame(&frame)? {
+                RxAction::Ignore => {}
+                RxAction::Continue(fc) => {
+                    if let Some(fc) = fc {
+                        self.write(&fc, Timer::Ar, self.opts.n_ar)?;
+                    }
+                    cr_deadline = Instant::now() + self.opts.n_cr;
+                }
+                RxAction::Done(data) => return Ok(Some(data)),
+                RxAction::Overflow(fc) => {
+                    self.write(&fc, Timer::Ar, self.opts.n_ar)?;
+                    return Err(Error::Overflow);
+                }
+            }
+        }
+    }
+
+    /// Reads the next frame, waiting until the deadline, if any.
+    fn read(&self, deadline: Option<Instant>) -> Result<Option<CanAnyFrame>, Error> {
+        let timeout = match deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(timeout) if !timeout.is_zero() => Some(timeout),
+                _ => return Ok(None),
+            },
+            None => None,
+        };
+        Ok(self.link.recv(timeout)?)
+    }
+
+    /// Writes a frame, checking that it went out within the timeout.
+    fn write(&self, frame: &CanAnyFrame, timer: Timer, timeout: Duration) -> Result<(), Error> {
+        let start = Instant::now();
+        match self.link.send(frame) {
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                ) =>
+            {
+                Err(Error::Timeout(timer))
+            }
+            Err(err) => Err(Error::Io(err)),
+            Ok(()) if start.elapsed() > timeout => Err(Error::Timeout(timer)),
+            Ok(()) => Ok(()),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StandardId;
+    use pdu::FlowStatus;
+    use std::thread::JoinHandle;
+
+    const TESTER_ID: u16 = 0x7E0;
+    const ECU_ID: u16 = 0x7E8;
+
+    fn id(id: u16) -> StandardId {
+        StandardId::new(id).unwrap()
+    }
+
+    fn message(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    // Creates a tester and ECU connection on a new bus, with the same
+    // options applied to each.
+    fn pair(
+        f: impl Fn(Options) -> Options,
+    ) -> (IsoTpSocket<VirtualLink>, IsoTpSocket<VirtualLink>) {
+        let bus = VirtualBus::new();
+        let tester = IsoTpSocket::new(bus.connect(), f(Options::new(id(TESTER_ID), id(ECU_ID))));
+        let ecu = IsoTpSocket::new(bus.connect(), f(Options::new(id(ECU_ID), id(TESTER_ID))));
+        (tester, ecu)
+    }
+
+    fn spawn_recv(mut sock: IsoTpSocket<VirtualLink>) -> JoinHandle<Result<Vec<u8>, Error>> {
+        thread::spawn(move || sock.recv())
+    }
+
+    #[test]
+    fn test_single_frame() {
+        let (mut tester, mut ecu) = pair(|opts| opts);
+        tester.send(&[0x3E, 0x00]).unwrap();
+        assert_eq!(ecu.recv().unwrap(), &[0x3E, 0x00]);
+
+        assert!(matches!(tester.send(&[]), Err(Error::InvalidLength(0))));
+    }
+
+    #[test]
+    fn test_multi_frame() {
+        for len in [8, 100, 4095] {
+            let (mut tester, ecu) = pair(|opts| opts.padding(Some(0xAA)));
+            let ecu = spawn_recv(ecu);
+            tester.send(&message(len)).unwrap();
+            assert_eq!(ecu.join().unwrap().unwrap(), message(len));
+        }
+    }
+
+    #[test]
+    fn test_block_size() {
+        let (mut tester, ecu) = pair(|opts| opts.block_size(4).st_min(Duration::from_micros(500)));
+        let ecu = spawn_recv(ecu);
+        tester.send(&message(200)).unwrap();
+        assert_eq!(ecu.join().unwrap().unwrap(), message(200));
+    }
+
+    #[test]
+    fn test_st_min() {
+        let (mut tester, ecu) = pair(|opts| opts.st_min(Duration::from_millis(5)));
+        let ecu = spawn_recv(ecu);
+
+        // Five consecutive frames, with four gaps between them
+        let start = Instant::now();
+        tester.send(&message(40)).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(ecu.join().unwrap().unwrap(), message(40));
+    }
+
+    #[test]
+    fn test_addressing() {
+        let bus = VirtualBus::new();
+        let ext = Addressing::Extended {
+            tx_addr: 0x10,
+            rx_addr: 0xF1,
+        };
+        let opts = Options::new(id(0x6F1), id(0x610)).addressing(ext);
+        let mut tester = IsoTpSocket::new(bus.connect(), opts);
+
+        let ext = Addressing::Extended {
+            tx_addr: 0xF1,
+            rx_addr: 0x10,
+        };
+        let opts = Options::new(id(0x610), id(0x6F1)).addressing(ext);
+        let ecu = spawn_recv(IsoTpSocket::new(bus.connect(), opts));
+
+        // Another ECU on the same IDs, with a different address
+        let ext = Addressing::Extended {
+            tx_addr: 0xF1,
+            rx_addr: 0x20,
+        };
+        let opts = Options::new(id(0x610), id(0x6F1)).addressing(ext);
+        let mut other = IsoTpSocket::new(bus.connect(), opts);
+
+        tester.send(&message(50)).unwrap();
+        assert_eq!(ecu.join().unwrap().unwrap(), message(50));
+        assert!(other
+            .recv_timeout(Duration::from_millis(10))
+            .unwrap()
+            .is_none());
+
+        let (mut tester, mut ecu) = pair(|opts| opts.addressing(Addressing::Mixed { ae: 0x42 }));
+        let ecu = thread::spawn(move || (ecu.recv().unwrap(), ecu.recv().unwrap()));
+        tester.send(&message(6)).unwrap();
+        tester.send(&message(30)).unwrap();
+        assert_eq!(ecu.join().unwrap(), (message(6), message(30)));
+    }
+
+    #[test]
+    fn test_fd() {
+        let (mut tester, ecu) = pair(|opts| opts.tx_dl(64).brs(true));
+        let ecu = spawn_recv(ecu);
+        tester.send(&message(62)).unwrap();
+        assert_eq!(ecu.join().unwrap().unwrap(), message(62));
+
+        let (mut tester, ecu) = pair(|opts| opts.tx_dl(64).max_rx_len(10_000));
+        let ecu = spawn_recv(ecu);
+        tester.send(&message(9000)).unwrap();
+        assert_eq!(ecu.join().unwrap().unwrap(), message(9000));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let (mut tester, ecu) = pair(|opts| opts.max_rx_len(100));
+        let ecu = spawn_recv(ecu);
+        assert!(matches!(tester.send(&message(200)), Err(Error::Overflow)));
+        assert!(matches!(ecu.join().unwrap(), Err(Error::Overflow)));
+    }
+
+    #[test]
+    fn test_timeouts() {
+        // Nobody sends a flow control frame
+        let (mut tester, _ecu) = pair(|opts| opts.n_bs(Duration::from_millis(20)));
+        assert!(matches!(
+            tester.send(&message(20)),
+            Err(Error::Timeout(Timer::Bs))
+        ));
+
+        // The sender stops after the first frame
+        let bus = VirtualBus::new();
+        let link = bus.connect();
+        let opts = Options::new(id(ECU_ID), id(TESTER_ID)).n_cr(Duration::from_millis(20));
+        let mut ecu = IsoTpSocket::new(bus.connect(), opts);
+
+        let ff = Codec::new(&Options::new(id(TESTER_ID), id(ECU_ID)))
+            .segmenter(&message(20))
+            .first_frame();
+        link.send(&ff).unwrap();
+        assert!(matches!(ecu.recv(), Err(Error::Timeout(Timer::Cr))));
+
+        assert!(ecu
+            .recv_timeout(Duration::from_millis(10))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_wait_frames() {
+        let bus = VirtualBus::new();
+        let link = bus.connect();
+        let opts = Options::new(id(TESTER_ID), id(ECU_ID)).wft_max(2);
+        let mut tester = IsoTpSocket::new(bus.connect(), opts);
+
+        // The "ECU" keeps asking the tester to wait
+        let ecu = thread::spawn(move || {
+            let codec = Codec::new(&Options::new(id(ECU_ID), id(TESTER_ID)));
+            link.recv(None).unwrap();
+            for _ in 0..3 {
+                let wait = codec.flow_control(FlowStatus::Wait, 0, Duration::ZERO);
+                link.send(&wait).unwrap();
+            }
+        });
+        assert!(matches!(tester.send(&message(20)), Err(Error::WaitLimit)));
+        ecu.join().unwrap();
+    }
+
+    #[test]
+    fn test_wrong_sequence() {
+        let bus = VirtualBus::new();
+        let link = bus.connect();
+        let mut ecu = IsoTpSocket::new(bus.connect(), Options::new(id(ECU_ID), id(TESTER_ID)));
+
+        let codec = Codec::new(&Options::new(id(TESTER_ID), id(ECU_ID)));
+        let msg = message(20);
+        let mut seg = codec.segmenter(&msg);
+        link.send(&seg.first_frame()).unwrap();
+        seg.next_frame();
+        link.send(&seg.next_frame().unwrap()).unwrap();
+
+        assert!(matches!(
+            ecu.recv(),
+            Err(Error::WrongSequenceNumber {
+                expected: 1,
+                received: 2
+            })
+        ));
+    }
+}
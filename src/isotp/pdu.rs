@@ -0,0 +1,872 @@
+// socketcan/src/isotp/pdu.rs
+//
+// ISO-TP protocol data units and message segmentation.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! ISO-TP protocol data units and message segmentation.
+//!
+//! This is the I/O-free core of the transport, shared by the blocking and
+//! asynchronous drivers. It converts between CAN frames and the network
+//! layer PDUs, splits and reassembles the messages carried by them, and
+//! runs the send and receive state machines. The drivers only move the
+//! frames and keep the timers.
+
+use super::{Addressing, Error, Options};
+use crate::{
+    frame::{fd_len, id_to_canid_t, FdFlags},
+    CanAnyFrame, CanDataFrame, CanFdFrame, Frame,
+};
+use embedded_can::Frame as EmbeddedFrame;
+use libc::{CAN_EFF_FLAG, CAN_EFF_MASK};
+use std::time::Duration;
+
+/// The largest message length that fits the 12-bit first frame length.
+const MAX_SHORT_LEN: usize = 0xFFF;
+
+/// The default byte for padding CAN FD frames that must be padded to a
+/// valid data length.
+const FD_PADDING: u8 = 0xCC;
+
+/// The flow status of a flow control frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FlowStatus {
+    /// The sender may continue to send consecutive frames.
+    ContinueToSend,
+    /// The sender should wait for another flow control frame.
+    Wait,
+    /// The receiver can't take a message that long.
+    Overflow,
+}
+
+impl FlowStatus {
+    /// Gets the flow status from the low nibble of the PCI byte.
+    pub(crate) fn from_nibble(fs: u8) -> Result<Self, Error> {
+        match fs {
+            0 => Ok(Self::ContinueToSend),
+            1 => Ok(Self::Wait),
+            2 => Ok(Self::Overflow),
+            _ => Err(Error::InvalidFlowStatus(fs)),
+        }
+    }
+}
+
+/// A network layer protocol data unit.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Pdu<'a> {
+    /// A complete message in a single frame.
+    Single(&'a [u8]),
+    /// The first frame of a segmented message, with the total length.
+    First(usize, &'a [u8]),
+    /// A consecutive frame, with its sequence number.
+    Consecutive(u8, &'a [u8]),
+    /// A flow control frame with the raw flow status, block size, and
+    /// STmin.
+    FlowControl(u8, u8, Duration),
+}
+
+impl<'a> Pdu<'a> {
+    /// Parses the PDU from the data of a frame, after any address byte.
+    ///
+    /// Returns `None` if the data is not a valid PDU. A single frame in a
+    /// CAN FD frame may have its length escaped into the second byte.
+    pub(crate) fn parse(data: &'a [u8], fd: bool) -> Option<Self> {
+        let pci = *data.first()?;
+
+        match pci >> 4 {
+            0 => {
+                let (start, len) = match pci & 0x0F {
+                    0 if fd => (2, *data.get(1)? as usize),
+                    n => (1, n as usize),
+                };
+                if len == 0 || (start == 1 && len > 7) {
+                    return None;
+                }
+                data.get(start..start + len).map(Pdu::Single)
+            }
+            1 => {
+                let len = ((pci as usize & 0x0F) << 8) | *data.get(1)? as usize;
+                let (len, start) = if len == 0 {
+                    let len = data.get(2..6)?;
+                    let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+                    // The escape is only valid for lengths that need it
+                    if len <= MAX_SHORT_LEN {
+                        return None;
+                    }
+                    (len, 6)
+                } else {
+                    (len, 2)
+                };
+                // A message that fits in a single frame can't be segmented
+                let max_single = data.len() - if data.len() > 8 { 2 } else { 1 };
+                if len <= max_single {
+                    return None;
+                }
+                Some(Pdu::First(len, &data[start..]))
+            }
+            2 => Some(Pdu::Consecutive(pci & 0x0F, &data[1..])),
+            3 if data.len() >= 3 => Some(Pdu::FlowControl(
+                pci & 0x0F,
+                data[1],
+                st_min_from_byte(data[2]),
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Gets the separation time from the STmin byte of a flow control frame.
+///
+/// Values 0x00-0x7F are milliseconds, and 0xF1-0xF9 are 100-900 µs. The
+/// reserved values are taken as the longest time, 127 ms.
+pub(crate) fn st_min_from_byte(b: u8) -> Duration {
+    match b {
+        0x00..=0x7F => Duration::from_millis(b as u64),
+        0xF1..=0xF9 => Duration::from_micros(100 * (b - 0xF0) as u64),
+        _ => Duration::from_millis(127),
+    }
+}
+
+/// Gets the STmin byte for a flow control frame from a separation time.
+///
+/// Times that can't be represented exactly are rounded down, with
+/// anything longer than 127 ms clamped to 127 ms.
+pub(crate) fn st_min_to_byte(st_min: Duration) -> u8 {
+    if st_min >= Duration::from_millis(1) {
+        st_min.as_millis().min(0x7F) as u8
+    } else if st_min >= Duration::from_micros(100) {
+        0xF0 + (st_min.as_micros() / 100) as u8
+    } else {
+        0
+    }
+}
+
+// ===== Codec =====
+
+/// Converts PDUs to and from CAN frames for a particular connection.
+#[derive(Debug, Clone)]
+pub(crate) struct Codec {
+    /// The raw ID for transmitted frames
+    tx_id: u32,
+    /// The raw ID of received frames
+    rx_id: u32,
+    /// The address byte in front of transmitted frames, if any
+    tx_addr: Option<u8>,
+    /// The address byte expected in front of received frames, if any
+    rx_addr: Option<u8>,
+    /// The maximum transmitted data length (8 for classic CAN)
+    tx_dl: usize,
+    /// Whether to set the bit rate switch on CAN FD frames
+    brs: bool,
+    /// The padding byte, if frames are padded
+    padding: Option<u8>,
+}
+
+impl Codec {
+    /// Creates a codec for the connection described by the options.
+    pub(crate) fn new(opts: &Options) -> Self {
+        let (tx_addr, rx_addr) = match opts.addressing {
+            Addressing::Normal => (None, None),
+            Addressing::Extended { tx_addr, rx_addr } => (Some(tx_addr), Some(rx_addr)),
+            Addressing::Mixed { ae } => (Some(ae), Some(ae)),
+        };
+        Self {
+            tx_id: id_to_canid_t(opts.tx_id) & (CAN_EFF_FLAG | CAN_EFF_MASK),
+            rx_id: id_to_canid_t(opts.rx_id) & (CAN_EFF_FLAG | CAN_EFF_MASK),
+            tx_addr,
+            rx_addr,
+            // The capacity math needs a valid length of at least 8
+            tx_dl: fd_len(opts.tx_dl),
+            brs: opts.brs,
+            padding: opts.padding,
+        }
+    }
+
+    /// Whether frames are sent in CAN FD format.
+    pub(crate) fn is_fd(&self) -> bool {
+        self.tx_dl > 8
+    }
+
+    /// The number of bytes in each frame available for PCI and data.
+    fn capacity(&self) -> usize {
+        self.tx_dl - self.tx_addr.map_or(0, |_| 1)
+    }
+
+    /// The largest payload that fits in a single frame.
+    pub(crate) fn max_single(&self) -> usize {
+        match self.capacity() {
+            n if n > 8 => n - 2,
+            n => n - 1,
+        }
+    }
+
+    /// Builds a frame from the PCI bytes and the payload.
+    pub(crate) fn frame(&self, pci: &[u8], payload: &[u8]) -> CanAnyFrame {
+        let mut data = [0u8; 64];
+        let mut len = 0;
+
+        if let Some(addr) = self.tx_addr {
+            data[0] = addr;
+            len = 1;
+        }
+        data[len..len + pci.len()].copy_from_slice(pci);
+        len += pci.len();
+        data[len..len + payload.len()].copy_from_slice(payload);
+        len += payload.len();
+
+        let pad_len = match (self.padding, len) {
+            (_, n) if n > 8 => fd_len(n),
+            (Some(_), _) => 8,
+            (None, n) => n,
+        };
+        data[len..pad_len].fill(self.padding.unwrap_or(FD_PADDING));
+
+        if self.is_fd() {
+            let flags = if self.brs {
+                FdFlags::BRS
+            } else {
+                FdFlags::empty()
+            };
+            CanFdFrame::init(self.tx_id, &data[..pad_len], flags)
+                .expect("valid ISO-TP frame length")
+                .into()
+        } else {
+            CanAnyFrame::Normal(
+                CanDataFrame::init(self.tx_id, &data[..pad_len])
+                    .expect("valid ISO-TP frame length"),
+            )
+        }
+    }
+
+    /// Builds a single frame, if the payload fits in one.
+    pub(crate) fn single_frame(&self, payload: &[u8]) -> Option<CanAnyFrame> {
+        match payload.len() {
+            n if n <= 7 && n < self.capacity() => Some(self.frame(&[n as u8], payload)),
+            n if n <= self.max_single() => Some(self.frame(&[0, n as u8], payload)),
+            _ => None,
+        }
+    }
+
+    /// Builds a flow control frame.
+    pub(crate) fn flow_control(&self, fs: FlowStatus, bs: u8, st_min: Duration) -> CanAnyFrame {
+        let fs = match fs {
+            FlowStatus::ContinueToSend => 0,
+            FlowStatus::Wait => 1,
+            FlowStatus::Overflow => 2,
+        };
+        self.frame(&[0x30 | fs, bs, st_min_to_byte(st_min)], &[])
+    }
+
+    /// Gets the PDU from a received frame.
+    ///
+    /// Returns `None` if the frame is not for this connection, or doesn't
+    /// contain a valid PDU.
+    pub(crate) fn parse<'a>(&self, frame: &'a CanAnyFrame) -> Option<Pdu<'a>> {
+        let (id, data, fd) = match frame {
+            CanAnyFrame::Normal(frame) => (frame.id_word(), frame.data(), false),
+            CanAnyFrame::Fd(frame) => (frame.id_word(), frame.data(), true),
+            _ => return None,
+        };
+
+        if id & (CAN_EFF_FLAG | CAN_EFF_MASK) != self.rx_id {
+            return None;
+        }
+
+        let data = match self.rx_addr {
+            Some(addr) if data.first() == Some(&addr) => &data[1..],
+            Some(_) => return None,
+            None => data,
+        };
+        Pdu::parse(data, fd)
+    }
+
+    /// Creates a segmenter to send a message that doesn't fit in a single
+    /// frame.
+    pub(crate) fn segmenter<'a>(&'a self, data: &'a [u8]) -> Segmenter<'a> {
+        Segmenter {
+            codec: self,
+            data,
+            pos: 0,
+            sn: 0,
+        }
+    }
+}
+
+// ===== Segmenter =====
+
+/// Splits a message into a first frame and consecutive frames.
+#[derive(Debug)]
+pub(crate) struct Segmenter<'a> {
+    codec: &'a Codec,
+    data: &'a [u8],
+    pos: usize,
+    sn: u8,
+}
+
+impl<'a> Segmenter<'a> {
+    /// Gets the first frame of the message.
+    pub(crate) fn first_frame(&mut self) -> CanAnyFrame {
+        let len = self.data.len();
+        let (pci, pci_len) = if len <= MAX_SHORT_LEN {
+            ([0x10 | (len >> 8) as u8, len as u8, 0, 0, 0, 0], 2)
+        } else {
+            let n = (len as u32).to_be_bytes();
+            ([0x10, 0, n[0], n[1], n[2], n[3]], 6)
+        };
+        let n = self.codec.capacity() - pci_len;
+        self.pos = n;
+        self.sn = 1;
+        self.codec.frame(&pci[..pci_len], &self.data[..n])
+    }
+
+    /// Gets the next consecutive frame, or `None` when the whole message
+    /// was sent.
+    pub(crate) fn next_frame(&mut self) -> Option<CanAnyFrame> {
+        if self.is_done() {
+            return None;
+        }
+        let end = self.data.len().min(self.pos + self.codec.capacity() - 1);
+        let frame = self
+            .codec
+            .frame(&[0x20 | self.sn], &self.data[self.pos..end]);
+        self.pos = end;
+        self.sn = (self.sn + 1) & 0x0F;
+        Some(frame)
+    }
+
+    /// Whether the whole message was sent.
+    pub(crate) fn is_done(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+}
+
+// ===== Reassembler =====
+
+/// Collects the data from a first frame and its consecutive frames.
+#[derive(Debug)]
+pub(crate) struct Reassembler {
+    buf: Vec<u8>,
+    len: usize,
+    sn: u8,
+    block_size: u8,
+    block_count: u8,
+}
+
+impl Reassembler {
+    /// Starts a message from the length and the data of the first frame.
+    pub(crate) fn new(len: usize, data: &[u8], block_size: u8) -> Self {
+        let mut buf = Vec::with_capacity(len);
+        buf.extend_from_slice(&data[..data.len().min(len)]);
+        Self {
+            buf,
+            len,
+            sn: 1,
+            block_size,
+            block_count: 0,
+        }
+    }
+
+    /// Adds the data from the next consecutive frame.
+    ///
+    /// Any padding past the end of the message is dropped.
+    pub(crate) fn push(&mut self, sn: u8, data: &[u8]) -> Result<(), Error> {
+        if sn != self.sn {
+            return Err(Error::WrongSequenceNumber {
+                expected: self.sn,
+                received: sn,
+            });
+        }
+        let n = data.len().min(self.len - self.buf.len());
+        self.buf.extend_from_slice(&data[..n]);
+        self.sn = (self.sn + 1) & 0x0F;
+        self.block_count = self.block_count.wrapping_add(1);
+        Ok(())
+    }
+
+    /// Whether the whole message has arrived.
+    pub(crate) fn is_done(&self) -> bool {
+        self.buf.len() >= self.len
+    }
+
+    /// Whether the block is complete, and the sender is waiting for
+    /// another flow control frame.
+    ///
+    /// This resets the block count.
+    pub(crate) fn block_done(&mut self) -> bool {
+        if self.block_size != 0 && self.block_count == self.block_size && !self.is_done() {
+            self.block_count = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Gets the reassembled message.
+    pub(crate) fn into_data(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+// ===== Transmitter =====
+
+/// The next step in sending a message.
+#[derive(Debug)]
+pub(crate) enum TxAction {
+    /// Send the frame, after waiting for the separation time.
+    Send(CanAnyFrame, Duration),
+    /// Wait for a flow control frame, within the N_Bs timeout, and pass it
+    /// to [`Transmitter::flow_control()`].
+    AwaitFlowControl,
+    /// The whole message was sent.
+    Done,
+}
+
+/// The state machine for sending a message.
+#[derive(Debug)]
+pub(crate) struct Transmitter<'a> {
+    codec: &'a Codec,
+    /// The single or first frame, until it's sent
+    first: Option<CanAnyFrame>,
+    /// The rest of a segmented message
+    seg: Option<Segmenter<'a>>,
+    /// Whether the receiver allowed more consecutive frames
+    clear_to_send: bool,
+    block_size: u8,
+    st_min: Duration,
+    /// The consecutive frames sent in the current block
+    sent: usize,
+    wft_max: u8,
+    waits: u8,
+}
+
+impl<'a> Transmitter<'a> {
+    /// Starts sending a message.
+    pub(crate) fn new(codec: &'a Codec, data: &'a [u8], opts: &Options) -> Result<Self, Error> {
+        if data.is_empty() || data.len() > u32::MAX as usize {
+            return Err(Error::InvalidLength(data.len()));
+        }
+        let (first, seg) = match codec.single_frame(data) {
+            Some(frame) => (frame, None),
+            None => {
+                let mut seg = codec.segmenter(data);
+                (seg.first_frame(), Some(seg))
+            }
+        };
+        Ok(Self {
+            codec,
+            first: Some(first),
+            seg,
+            clear_to_send: false,
+            block_size: 0,
+            st_min: Duration::ZERO,
+            sent: 0,
+            wft_max: opts.wft_max,
+            waits: 0,
+        })
+    }
+
+    /// Gets the next step.
+    pub(crate) fn next_action(&mut self) -> TxAction {
+        if let Some(frame) = self.first.take() {
+            return TxAction::Send(frame, Duration::ZERO);
+        }
+        let seg = match self.seg.as_mut() {
+            Some(seg) if !seg.is_done() => seg,
+            _ => return TxAction::Done,
+        };
+        if !self.clear_to_send {
+            return TxAction::AwaitFlowControl;
+        }
+
+        let frame = seg.next_frame().expect("consecutive frame");
+        let delay = if self.sent > 0 {
+            self.st_min
+        } else {
+            Duration::ZERO
+        };
+        self.sent += 1;
+        if self.sent == self.block_size as usize {
+            self.clear_to_send = false;
+        }
+        TxAction::Send(frame, delay)
+    }
+
+    /// Handles a frame received while waiting for flow control.
+    ///
+    /// Returns whether it was a flow control frame, which restarts the
+    /// N_Bs timer.
+    pub(crate) fn flow_control(&mut self, frame: &CanAnyFrame) -> Result<bool, Error> {
+        let (fs, bs, st_min) = match self.codec.parse(frame) {
+            Some(Pdu::FlowControl(fs, bs, st_min)) => (fs, bs, st_min),
+            _ => return Ok(false),
+        };
+        match FlowStatus::from_nibble(fs)? {
+            FlowStatus::ContinueToSend => {
+                self.clear_to_send = true;
+                self.block_size = bs;
+                self.st_min = st_min;
+                self.sent = 0;
+                self.waits = 0;
+            }
+            FlowStatus::Wait => {
+                if self.waits >= self.wft_max {
+                    return Err(Error::WaitLimit);
+                }
+                self.waits += 1;
+            }
+            FlowStatus::Overflow => return Err(Error::Overflow),
+        }
+        Ok(true)
+    }
+}
+
+// ===== Receiver =====
+
+/// The result of a frame passed to the [`Receiver`].
+#[derive(Debug)]
+pub(crate) enum RxAction {
+    /// The frame isn't part of a message.
+    Ignore,
+    /// A message is in progress. Send the flow control frame, if any, and
+    /// restart the N_Cr timer.
+    Continue(Option<CanAnyFrame>),
+    /// The message is complete.
+    Done(Vec<u8>),
+    /// The message is too long. Send the overflow flow control frame, and
+    /// fail with [`Error::Overflow`].
+    Overflow(CanAnyFrame),
+}
+
+/// The state machine for receiving a message.
+#[derive(Debug)]
+pub(crate) struct Receiver<'a> {
+    codec: &'a Codec,
+    opts: &'a Options,
+    rx: Option<Reassembler>,
+}
+
+impl<'a> Receiver<'a> {
+    /// Starts waiting for a message.
+    pub(crate) fn new(codec: &'a Codec, opts: &'a Options) -> Self {
+        Self {
+            codec,
+            opts,
+            rx: None,
+        }
+    }
+
+    /// Whether a segmented message has started, so the N_Cr timeout
+    /// applies.
+    pub(crate) fn in_progress(&self) -> bool {
+        self.rx.is_some()
+    }
+
+    /// Handles a received frame.
+    pub(crate) fn on_frame(&mut self, frame: &CanAnyFrame) -> Result<RxAction, Error> {
+        match self.codec.parse(frame) {
+            // A new message aborts one in progress
+            Some(Pdu::Single(data)) => Ok(RxAction::Done(data.to_vec())),
+            Some(Pdu::First(len, data)) => {
+                if len > self.opts.max_rx_len {
+                    self.rx = None;
+                    let fc = self
+                        .codec
+                        .flow_control(FlowStatus::Overflow, 0, Duration::ZERO);
+                    return Ok(RxAction::Overflow(fc));
+                }
+                self.rx = Some(Reassembler::new(len, data, self.opts.block_size));
+                Ok(RxAction::Continue(Some(self.continue_to_send())))
+            }
+            Some(Pdu::Consecutive(sn, data)) => {
+                let msg = match self.rx.as_mut() {
+                    Some(msg) => msg,
+                    None => return Ok(RxAction::Ignore),
+                };
+                msg.push(sn, data)?;
+                if msg.is_done() {
+                    let msg = self.rx.take().expect("message in progress");
+                    return Ok(RxAction::Done(msg.into_data()));
+                }
+                let fc = msg.block_done().then(|| self.continue_to_send());
+                Ok(RxAction::Continue(fc))
+            }
+            _ => Ok(RxAction::Ignore),
+        }
+    }
+
+    /// Builds a "continue to send" flow control frame.
+    fn continue_to_send(&self) -> CanAnyFrame {
+        self.codec.flow_control(
+            FlowStatus::ContinueToSend,
+            self.opts.block_size,
+            self.opts.st_min,
+        )
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CanFrame, Id, StandardId};
+
+    fn opts() -> Options {
+        Options::new(
+            StandardId::new(0x7E0).unwrap(),
+            StandardId::new(0x7E8).unwrap(),
+        )
+    }
+
+    fn data(frame: &CanAnyFrame) -> &[u8] {
+        match frame {
+            CanAnyFrame::Normal(frame) => frame.data(),
+            CanAnyFrame::Fd(frame) => frame.data(),
+            _ => panic!("unexpected frame"),
+        }
+    }
+
+    #[test]
+    fn test_st_min() {
+        assert_eq!(st_min_from_byte(0x00), Duration::ZERO);
+        assert_eq!(st_min_from_byte(0x14), Duration::from_millis(20));
+        assert_eq!(st_min_from_byte(0xF3), Duration::from_micros(300));
+        assert_eq!(st_min_from_byte(0x80), Duration::from_millis(127));
+        assert_eq!(st_min_from_byte(0xFA), Duration::from_millis(127));
+
+        assert_eq!(st_min_to_byte(Duration::from_millis(20)), 0x14);
+        assert_eq!(st_min_to_byte(Duration::from_micros(300)), 0xF3);
+        assert_eq!(st_min_to_byte(Duration::from_micros(50)), 0x00);
+        assert_eq!(st_min_to_byte(Duration::from_secs(1)), 0x7F);
+    }
+
+    #[test]
+    fn test_single_frame() {
+        let codec = Codec::new(&opts());
+        let frame = codec.single_frame(&[0x3E, 0x00]).unwrap();
+        assert_eq!(data(&frame), &[0x02, 0x3E, 0x00]);
+        assert!(codec.single_frame(&[0; 8]).is_none());
+
+        let codec = Codec::new(&opts().padding(Some(0xAA)));
+        let frame = codec.single_frame(&[0x3E, 0x00]).unwrap();
+        assert_eq!(
+            data(&frame),
+            &[0x02, 0x3E, 0x00, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]
+        );
+
+        let codec = Codec::new(&opts().addressing(Addressing::Mixed { ae: 0x55 }));
+        let frame = codec.single_frame(&[1, 2, 3, 4, 5, 6]).unwrap();
+        assert_eq!(data(&frame), &[0x55, 0x06, 1, 2, 3, 4, 5, 6]);
+        assert!(codec.single_frame(&[0; 7]).is_none());
+    }
+
+    #[test]
+    fn test_tx_dl() {
+        for (tx_dl, max_single) in [(0, 7), (5, 7), (8, 7), (9, 10), (63, 62), (1000, 62)] {
+            let codec = Codec::new(&opts().tx_dl(tx_dl));
+            assert_eq!(codec.max_single(), max_single, "tx_dl {}", tx_dl);
+        }
+        let codec = Codec::new(&opts().tx_dl(0).addressing(Addressing::Mixed { ae: 0x55 }));
+        assert_eq!(codec.max_single(), 6);
+    }
+
+    #[test]
+    fn test_fd_single_frame() {
+        let codec = Codec::new(&opts().tx_dl(64));
+        assert_eq!(codec.max_single(), 62);
+
+        let frame = codec.single_frame(&[0x11; 20]).unwrap();
+        assert!(matches!(frame, CanAnyFrame::Fd(_)));
+        let buf = data(&frame);
+        assert_eq!(buf.len(), 24);
+        assert_eq!(&buf[..2], &[0x00, 20]);
+        assert_eq!(&buf[22..], &[FD_PADDING, FD_PADDING]);
+
+        match Pdu::parse(buf, true) {
+            Some(Pdu::Single(d)) => assert_eq!(d, &[0x11; 20]),
+            pdu => panic!("unexpected PDU: {:?}", pdu),
+        }
+        // The escape is only valid for FD frames
+        assert_eq!(Pdu::parse(&[0x00, 2, 1, 2], false), None);
+    }
+
+    #[test]
+    fn test_segments() {
+        let codec = Codec::new(&opts());
+        let msg: Vec<u8> = (0..20).collect();
+        let mut seg = codec.segmenter(&msg);
+
+        assert_eq!(data(&seg.first_frame()), &[0x10, 20, 0, 1, 2, 3, 4, 5]);
+        assert_eq!(
+            data(&seg.next_frame().unwrap()),
+            &[0x21, 6, 7, 8, 9, 10, 11, 12]
+        );
+        assert_eq!(
+            data(&seg.next_frame().unwrap()),
+            &[0x22, 13, 14, 15, 16, 17, 18, 19]
+        );
+        assert!(seg.is_done());
+        assert!(seg.next_frame().is_none());
+    }
+
+    #[test]
+    fn test_long_first_frame() {
+        let codec = Codec::new(&opts().tx_dl(64));
+        let msg = vec![0x42; 5000];
+        let mut seg = codec.segmenter(&msg);
+        let frame = seg.first_frame();
+        let buf = data(&frame);
+        assert_eq!(buf.len(), 64);
+        assert_eq!(&buf[..6], &[0x10, 0, 0, 0, 0x13, 0x88]);
+
+        match Pdu::parse(buf, true) {
+            Some(Pdu::First(len, d)) => {
+                assert_eq!(len, 5000);
+                assert_eq!(d.len(), 58);
+            }
+            pdu => panic!("unexpected PDU: {:?}", pdu),
+        }
+    }
+
+    #[test]
+    fn test_invalid_first_frame() {
+        // The length fits in a single frame, or is an escaped zero
+        assert_eq!(Pdu::parse(&[0x10, 7, 1, 2, 3, 4, 5, 6], false), None);
+        assert_eq!(Pdu::parse(&[0x10, 0, 0, 0, 0, 0, 1, 2], false), None);
+        assert!(matches!(
+            Pdu::parse(&[0x10, 8, 1, 2, 3, 4, 5, 6], false),
+            Some(Pdu::First(8, _))
+        ));
+
+        let mut buf = [0u8; 64];
+        buf[..2].copy_from_slice(&[0x10, 62]);
+        assert_eq!(Pdu::parse(&buf, true), None);
+        buf[1] = 63;
+        assert!(matches!(Pdu::parse(&buf, true), Some(Pdu::First(63, _))));
+
+        // An escaped length of zero, or one that didn't need the escape
+        buf[..6].copy_from_slice(&[0x10, 0, 0, 0, 0, 0]);
+        assert_eq!(Pdu::parse(&buf, true), None);
+        buf[..6].copy_from_slice(&[0x10, 0, 0, 0, 0x0F, 0xFF]);
+        assert_eq!(Pdu::parse(&buf, true), None);
+    }
+
+    #[test]
+    fn test_reassemble() {
+        let mut rx = Reassembler::new(10, &[0, 1, 2, 3, 4, 5], 1);
+        assert!(!rx.is_done());
+        rx.push(1, &[6, 7, 8, 9, 0xAA, 0xAA, 0xAA]).unwrap();
+        assert!(rx.is_done());
+        assert!(!rx.block_done());
+        assert_eq!(rx.into_data(), (0..10).collect::<Vec<u8>>());
+
+        let mut rx = Reassembler::new(100, &[0; 6], 0);
+        assert!(matches!(
+            rx.push(2, &[0; 7]),
+            Err(Error::WrongSequenceNumber {
+                expected: 1,
+                received: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_state_machines() {
+        let tx_opts = opts().st_min(Duration::from_millis(5));
+        let rx_opts = Options::new(
+            StandardId::new(0x7E8).unwrap(),
+            StandardId::new(0x7E0).unwrap(),
+        )
+        .block_size(2);
+        let (tx_codec, rx_codec) = (Codec::new(&tx_opts), Codec::new(&rx_opts));
+
+        let msg: Vec<u8> = (0..30).collect();
+        let mut tx = Transmitter::new(&tx_codec, &msg, &tx_opts).unwrap();
+        let mut rx = Receiver::new(&rx_codec, &rx_opts);
+
+        // Each block of two consecutive frames needs a flow control frame
+        let mut fcs = 0;
+        let data = loop {
+            match tx.next_action() {
+                TxAction::Send(frame, delay) => {
+                    assert!(delay.is_zero() || delay == Duration::from_millis(5));
+                    match rx.on_frame(&frame).unwrap() {
+                        RxAction::Continue(Some(fc)) => {
+                            fcs += 1;
+                            assert!(tx.flow_control(&fc).unwrap());
+                        }
+                        RxAction::Continue(None) => {}
+                        RxAction::Done(data) => break data,
+                        action => panic!("unexpected action: {:?}", action),
+                    }
+                }
+                action => panic!("unexpected action: {:?}", action),
+            }
+        };
+        assert_eq!(data, msg);
+        assert_eq!(fcs, 2);
+        assert!(!rx.in_progress());
+        assert!(matches!(tx.next_action(), TxAction::Done));
+
+        assert!(matches!(
+            Transmitter::new(&tx_codec, &[], &tx_opts),
+            Err(Error::InvalidLength(0))
+        ));
+
+        // Too many wait frames
+        let mut tx = Transmitter::new(&tx_codec, &msg, &tx_opts.wft_max(1)).unwrap();
+        assert!(matches!(tx.next_action(), TxAction::Send(..)));
+        assert!(matches!(tx.next_action(), TxAction::AwaitFlowControl));
+        let wait = rx_codec.flow_control(FlowStatus::Wait, 0, Duration::ZERO);
+        assert!(tx.flow_control(&wait).unwrap());
+        assert!(matches!(tx.next_action(), TxAction::AwaitFlowControl));
+        assert!(matches!(tx.flow_control(&wait), Err(Error::WaitLimit)));
+
+        // The largest limit doesn't overflow the count
+        let mut tx = Transmitter::new(&tx_codec, &msg, &tx_opts.wft_max(255)).unwrap();
+        assert!(matches!(tx.next_action(), TxAction::Send(..)));
+        for _ in 0..255 {
+            assert!(tx.flow_control(&wait).unwrap());
+        }
+        assert!(matches!(tx.flow_control(&wait), Err(Error::WaitLimit)));
+    }
+
+    #[test]
+    fn test_parse_frames() {
+        let codec = Codec::new(&opts().addressing(Addressing::Extended {
+            tx_addr: 0xF1,
+            rx_addr: 0x10,
+        }));
+
+        let frame = CanAnyFrame::from(
+            CanFrame::new(StandardId::new(0x7E8).unwrap(), &[0x10, 0x30, 0x00, 0x14]).unwrap(),
+        );
+        match codec.parse(&frame) {
+            Some(Pdu::FlowControl(0, 0, st)) => {
+                assert_eq!(st, Duration::from_millis(20))
+            }
+            pdu => panic!("unexpected PDU: {:?}", pdu),
+        }
+
+        // Wrong target address
+        let frame = CanAnyFrame::from(
+            CanFrame::new(StandardId::new(0x7E8).unwrap(), &[0x11, 0x30, 0x00, 0x14]).unwrap(),
+        );
+        assert!(codec.parse(&frame).is_none());
+
+        // Wrong ID
+        let frame = CanAnyFrame::from(
+            CanFrame::new(
+                Id::Standard(StandardId::new(0x7E9).unwrap()),
+                &[0x10, 0x01, 0x3E],
+            )
+            .unwrap(),
+        );
+        assert!(codec.parse(&frame).is_none());
+    }
+}
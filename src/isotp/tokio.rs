@@ -0,0 +1,269 @@
+// socketcan/src/isotp/tokio.rs
+//
+// Asynchronous ISO-TP connections for tokio.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! Asynchronous ISO-TP connections for tokio.
+//!
+//! This is the same protocol as the blocking [`IsoTpSocket`](super::IsoTpSocket),
+//! over the tokio CAN sockets:
+//!
+//! ```no_run
+//! use socketcan::{isotp::{tokio::IsoTpSocket, Options}, tokio::CanSocket, StandardId};
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let opts = Options::new(StandardId::new(0x7E0).unwrap(), StandardId::new(0x7E8).unwrap());
+//!     let mut sock = IsoTpSocket::<CanSocket>::open("vcan0", opts)?;
+//!
+//!     sock.send(&[0x22, 0xF1, 0x90]).await?;
+//!     println!("{:02X?}", sock.recv().await?);
+//!     Ok(())
+//! }
+//! ```
+
+use super::{Error, Options, Timer};
+use crate::{
+    isotp::pdu::{Codec, Receiver, RxAction, Transmitter, TxAction},
+    tokio::AsyncCanSocket,
+    CanAnyFrame, IoResult, Socket, SocketOptions,
+};
+use std::{
+    os::unix::io::OwnedFd,
+    time::{Duration, Instant},
+};
+
+pub use crate::link::tokio::Link;
+
+/// An asynchronous ISO-TP connection over a CAN link.
+#[derive(Debug)]
+pub struct IsoTpSocket<L> {
+    link: L,
+    opts: Options,
+    codec: Codec,
+}
+
+impl<T> IsoTpSocket<AsyncCanSocket<T>>
+where
+    T: Socket + From<OwnedFd>,
+    AsyncCanSocket<T>: Link,
+{
+    /// Opens a CAN socket on the named interface for the connection.
+    ///
+    /// This sets a kernel filter on the socket to receive only the frames
+    /// for the connection.
+    pub fn open(ifname: &str, opts: Options) -> IoResult<Self> {
+        let sock = AsyncCanSocket::<T>::open(ifname)?;
+        sock.set_filters(&[opts.filter()])?;
+        Ok(Self::new(sock, opts))
+    }
+}
+
+impl<L: Link> IsoTpSocket<L> {
+    /// Creates an ISO-TP connection over an existing link.
+    pub fn new(link: L, opts: Options) -> Self {
+        let codec = Codec::new(&opts);
+        Self { link, opts, codec }
+    }
+
+    /// Gets the options for the connection.
+    pub fn options(&self) -> &Options {
+        &self.opts
+    }
+
+    /// Gets a reference to the underlying link.
+    pub fn link(&self) -> &L {
+        &self.link
+    }
+
+    /// Consumes the connection, returning the underlying link.
+    pub fn into_link(self) -> L {
+        self.link
+    }
+
+    /// Sends a message.
+    pub async fn send(&mut self, data: &[u8]) -> Result<(), Error> {
+        let codec = self.codec.clone();
+        let mut tx = Transmitter::new(&codec, data, &self.opts)?;
+        loop {
+            match tx.next_action() {
+                TxAction::Send(frame, delay) => {
+                    if !delay.is_zero() {
+                        tokio::time::sleep(delay).await;
+                    }
+                    self.write(frame, Timer::As, self.opts.n_as).await?;
+                }
+                TxAction::AwaitFlowControl => {
+                    let deadline = Instant::now() + self.opts.n_bs;
+                    loop {
+                        let frame = self
+                            .read(Some(deadline))
+                            .await?
+                            .ok_or(Error::Timeout(Timer::Bs))?;
+                        if tx.flow_control(&frame)? {
+                            break;
+                        }
+                    }
+                }
+                TxAction::Done => return Ok(()),
+            }
+        }
+    }
+
+    /// Receives a message.
+    pub async fn recv(&mut self) -> Result<Vec<u8>, Error> {
+        self.recv_until(None)
+            .await
+            .map(|data| data.expect("blocking receive"))
+    }
+
+    /// Receives a message, waiting for up to `timeout` for it to start.
+    ///
+    /// Returns `None` if no message started within the timeout. Once a
+    /// message starts, the N_Cr timeout applies to the rest of it.
+    pub async fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<Vec<u8>>, Error> {
+        self.recv_until(Some(Instant::now() + timeout)).await
+    }
+
+    /// Receives a message, waiting until the deadline for it to start.
+    async fn recv_until(&mut self, deadline: Option<Instant>) -> Result<Option<Vec<u8>>, Error> {
+        let (codec, opts) = (self.codec.clone(), self.opts);
+        let mut rx = Receiver::new(&codec, &opts);
+        let mut cr_deadline = Instant::now();
+
+        loop {
+            let wait = if rx.in_progress() {
+                Some(cr_deadline)
+            } else {
+                deadline
+            };
+            let frame = match self.read(wait).await? {
+                Some(frame) => frame,
+                None if rx.in_progress() => return Err(Error::Timeout(Timer::Cr)),
+                None => return Ok(None),
+            };
+
+            match rx.on_frame(&frame)? {
+                RxAction::Ignore => {}
+                RxAction::Continue(fc) => {
+                    if let Some(fc) = fc {
+                        self.write(fc, Timer::Ar, opts.n_ar).await?;
+                    }
+                    cr_deadline = Instant::now() + opts.n_cr;
+                }
+                RxAction::Done(data) => return Ok(Some(data)),
+                RxAction::Overflow(fc) => {
+                    self.write(fc, Timer::Ar, opts.n_ar).await?;
+                    return Err(Error::Overflow);
+                }
+            }
+        }
+    }
+
+    /// Reads the next frame, waiting until the deadline, if any.
+    async fn read(&mut self, deadline: Option<Instant>) -> Result<Option<CanAnyFrame>, Error> {
+        match deadline {
+            Some(deadline) => {
+                let deadline = tokio::time::Instant::from_std(deadline);
+                match tokio::time::timeout_at(deadline, self.link.recv()).await {
+                    Ok(res) => Ok(Some(res?)),
+                    Err(_) => Ok(None),
+                }
+            }
+            None => Ok(Some(self.link.recv().await?)),
+        }
+    }
+
+    /// Writes a frame, failing if it doesn't go out within the timeout.
+    async fn write(
+        &mut self,
+        frame: CanAnyFrame,
+        timer: Timer,
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        match tokio::time::timeout(timeout, self.link.send(frame)).await {
+            Ok(res) => Ok(res?),
+            Err(_) => Err(Error::Timeout(timer)),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(feature = "vcan_tests")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tokio::{CanFdSocket, CanSocket},
+        StandardId,
+    };
+    use serial_test::serial;
+
+    fn message(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    fn opts(tx_id: u16, rx_id: u16) -> Options {
+        Options::new(
+            StandardId::new(tx_id).unwrap(),
+            StandardId::new(rx_id).unwrap(),
+        )
+    }
+
+    #[serial]
+    #[tokio::test]
+    async fn test_send_recv() {
+        let mut tester = IsoTpSocket::<CanSocket>::open("vcan0", opts(0x7E0, 0x7E8)).unwrap();
+        let mut ecu =
+            IsoTpSocket::<CanSocket>::open("vcan0", opts(0x7E8, 0x7E0).block_size(2)).unwrap();
+
+        let msg = message(100);
+        let (sent, rcvd) = tokio::join!(tester.send(&msg), ecu.recv());
+        sent.unwrap();
+        assert_eq!(rcvd.unwrap(), msg);
+
+        let (sent, rcvd) = tokio::join!(ecu.send(&[0x7E, 0x00]), tester.recv());
+        sent.unwrap();
+        assert_eq!(rcvd.unwrap(), &[0x7E, 0x00]);
+    }
+
+    #[serial]
+    #[tokio::test]
+    async fn test_fd() {
+        let mut tester =
+            IsoTpSocket::<CanFdSocket>::open("vcan0", opts(0x7E0, 0x7E8).tx_dl(64)).unwrap();
+        let mut ecu =
+            IsoTpSocket::<CanFdSocket>::open("vcan0", opts(0x7E8, 0x7E0).tx_dl(64)).unwrap();
+
+        let msg = message(1000);
+        let (sent, rcvd) = tokio::join!(tester.send(&msg), ecu.recv());
+        sent.unwrap();
+        assert_eq!(rcvd.unwrap(), msg);
+    }
+
+    #[serial]
+    #[tokio::test]
+    async fn test_timeout() {
+        let mut tester = IsoTpSocket::<CanSocket>::open(
+            "vcan0",
+            opts(0x7E0, 0x7E8).n_bs(Duration::from_millis(50)),
+        )
+        .unwrap();
+        assert!(matches!(
+            tester.send(&message(20)).await,
+            Err(Error::Timeout(Timer::Bs))
+        ));
+        assert!(tester
+            .recv_timeout(Duration::from_millis(10))
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
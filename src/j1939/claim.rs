@@ -0,0 +1,258 @@
+// socketcan/src/j1939/claim.rs
+//
+// The J1939-81 address claim procedure.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! The J1939-81 address claim state machine.
+//!
+//! This is driven by the caller with the claims from other nodes and the
+//! current time, and returns the frames to send.
+
+use super::{frame, ClaimState, J1939Id, Name, Pgn, GLOBAL_ADDRESS, NULL_ADDRESS};
+use crate::CanFrame;
+use std::{
+    collections::BTreeMap,
+    ops::RangeInclusive,
+    time::{Duration, Instant},
+};
+
+/// The time to wait for contending claims before using an address
+const CLAIM_TIME: Duration = Duration::from_millis(250);
+
+/// The priority of address claims
+const CLAIM_PRIORITY: u8 = 6;
+
+/// The addresses that arbitrary address capable nodes pick from
+const ARBITRARY_ADDRESSES: RangeInclusive<u8> = 128..=247;
+
+/// The address claim state machine for a node.
+#[derive(Debug)]
+pub(crate) struct AddressClaimer {
+    name: Name,
+    preferred: u8,
+    state: ClaimState,
+    /// The address being claimed, or that was claimed
+    address: u8,
+    /// When the claim completes, or when to send a delayed
+    /// "cannot claim"
+    deadline: Option<Instant>,
+    /// The addresses claimed by other nodes
+    claimed: BTreeMap<u8, Name>,
+}
+
+impl AddressClaimer {
+    /// Creates the state machine for a node with the preferred address.
+    pub(crate) fn new(name: Name, preferred: u8) -> Self {
+        Self {
+            name,
+            preferred,
+            state: ClaimState::Idle,
+            address: preferred,
+            deadline: None,
+            claimed: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn name(&self) -> Name {
+        self.name
+    }
+
+    pub(crate) fn state(&self) -> ClaimState {
+        self.state
+    }
+
+    /// Gets the address, once it's claimed.
+    pub(crate) fn address(&self) -> Option<u8> {
+        match self.state {
+            ClaimState::Claimed(addr) => Some(addr),
+            _ => None,
+        }
+    }
+
+    /// Gets the next time that [`poll()`](Self::poll) needs to be called.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Starts claiming the preferred address, or if it's taken by a node
+    /// with a lower NAME, another one.
+    pub(crate) fn start(&mut self, now: Instant) -> CanFrame {
+        match self.claimed.get(&self.preferred) {
+            Some(other) if *other < self.name => self.claim_other(now),
+            _ => self.claim(self.preferred, now),
+        }
+    }
+
+    /// Handles an address claim, or "cannot claim", from another node.
+    pub(crate) fn handle_claim(
+        &mut self,
+        source: u8,
+        name: Name,
+        now: Instant,
+    ) -> Option<CanFrame> {
+        if source == NULL_ADDRESS || name == self.name {
+            return None;
+        }
+        self.claimed.retain(|_, other| *other != name);
+        self.claimed.insert(source, name);
+
+        let contending = matches!(self.state, ClaimState::Claiming | ClaimState::Claimed(_));
+        if !contending || source != self.address {
+            None
+        } else if self.name < name {
+            // We win, and repeat the claim
+            self.claimed.remove(&source);
+            Some(self.claim_frame(self.address))
+        } else {
+            Some(self.claim_other(now))
+        }
+    }
+
+    /// Handles a request for the address claim.
+    pub(crate) fn handle_request(&mut self, now: Instant) -> Option<CanFrame> {
+        match self.state {
+            ClaimState::Idle => None,
+            ClaimState::CannotClaim => {
+                // Delay the response by a pseudo-random 0 - 153 ms, so
+                // the responses of unclaimed nodes don't collide
+                let delay = Duration::from_micros((self.name.0 % 154) * 1000 + 600);
+                self.deadline = Some(now + delay);
+                None
+            }
+            _ => Some(self.claim_frame(self.address)),
+        }
+    }
+
+    /// Checks the timers, returning a frame to send, if any.
+    pub(crate) fn poll(&mut self, now: Instant) -> Option<CanFrame> {
+        match self.deadline {
+            Some(deadline) if deadline <= now => {
+                self.deadline = None;
+                match self.state {
+                    ClaimState::Claiming => {
+                        self.state = ClaimState::Claimed(self.address);
+                        None
+                    }
+                    ClaimState::CannotClaim => Some(self.claim_frame(NULL_ADDRESS)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Starts claiming an address.
+    fn claim(&mut self, address: u8, now: Instant) -> CanFrame {
+        self.state = ClaimState::Claiming;
+        self.address = address;
+        self.deadline = Some(now + CLAIM_TIME);
+        self.claim_frame(address)
+    }
+
+    /// Claims a free address after losing one, or gives up if there's
+    /// none, or the node can't pick its own address.
+    fn claim_other(&mut self, now: Instant) -> CanFrame {
+        let free = if self.name.is_arbitrary_address_capable() {
+            ARBITRARY_ADDRESSES
+                .into_iter()
+                .find(|addr| !self.claimed.contains_key(addr))
+        } else {
+            None
+        };
+        match free {
+            Some(addr) => self.claim(addr, now),
+            None => {
+                self.state = ClaimState::CannotClaim;
+                self.address = NULL_ADDRESS;
+                self.deadline = None;
+                self.claim_frame(NULL_ADDRESS)
+            }
+        }
+    }
+
+    /// Builds an address claim frame from the address.
+    fn claim_frame(&self, source: u8) -> CanFrame {
+        let id = J1939Id::new(CLAIM_PRIORITY, Pgn::ADDRESS_CLAIMED, source, GLOBAL_ADDRESS);
+        frame(id, &self.name.to_bytes())
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EmbeddedFrame;
+
+    fn source(frame: &CanFrame) -> u8 {
+        J1939Id::from_frame(frame).unwrap().source
+    }
+
+    #[test]
+    fn test_claim() {
+        let now = Instant::now();
+        let mut claimer = AddressClaimer::new(Name(0x100), 0x80);
+
+        let frame = claimer.start(now);
+        assert_eq!(source(&frame), 0x80);
+        assert_eq!(frame.data(), &Name(0x100).to_bytes());
+        assert_eq!(claimer.state(), ClaimState::Claiming);
+        assert_eq!(claimer.address(), None);
+
+        // A contending claim from a node with a higher NAME loses
+        let frame = claimer.handle_claim(0x80, Name(0x200), now).unwrap();
+        assert_eq!(source(&frame), 0x80);
+
+        assert!(claimer.poll(now + Duration::from_millis(100)).is_none());
+        assert_eq!(claimer.state(), ClaimState::Claiming);
+        assert!(claimer.poll(now + CLAIM_TIME).is_none());
+        assert_eq!(claimer.address(), Some(0x80));
+
+        // A request for the claim is answered
+        let frame = claimer.handle_request(now).unwrap();
+        assert_eq!(source(&frame), 0x80);
+    }
+
+    #[test]
+    fn test_arbitrary_address() {
+        let now = Instant::now();
+        let name = Name(0x300).with_arbitrary_address_capable(true);
+        let mut claimer = AddressClaimer::new(name, 0x80);
+
+        claimer.handle_claim(0x80, Name(0x100), now);
+        claimer.handle_claim(0x81, Name(0x101), now);
+        assert_eq!(source(&claimer.start(now)), 0x82);
+
+        // Losing the address moves on to the next free one
+        let frame = claimer.handle_claim(0x82, Name(0x200), now).unwrap();
+        assert_eq!(source(&frame), 0x83);
+        claimer.poll(now + CLAIM_TIME);
+        assert_eq!(claimer.address(), Some(0x83));
+    }
+
+    #[test]
+    fn test_cannot_claim() {
+        let now = Instant::now();
+        let mut claimer = AddressClaimer::new(Name(0x300), 0x80);
+        claimer.start(now);
+
+        let frame = claimer.handle_claim(0x80, Name(0x100), now).unwrap();
+        assert_eq!(source(&frame), NULL_ADDRESS);
+        assert_eq!(claimer.state(), ClaimState::CannotClaim);
+        assert!(claimer.poll(now + CLAIM_TIME).is_none());
+        assert_eq!(claimer.address(), None);
+
+        // A request is answered after a delay
+        assert!(claimer.handle_request(now).is_none());
+        let deadline = claimer.deadline().unwrap();
+        assert!(deadline <= now + Duration::from_millis(154));
+        let frame = claimer.poll(deadline).unwrap();
+        assert_eq!(source(&frame), NULL_ADDRESS);
+    }
+}
@@ -0,0 +1,416 @@
+// socketcan/src/j1939/dm.rs
+//
+// J1939-73 diagnostic messages.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! The J1939-73 diagnostic messages with trouble codes.
+//!
+//! Nodes broadcast their active diagnostic trouble codes (DTCs) in DM1
+//! messages once a second, and send the previously active ones in a DM2
+//! message when it's requested. Both have the same layout, decoded as a
+//! [`DtcList`]: the status of the warning lamps followed by the DTCs. With
+//! more than one DTC the message is longer than a frame, and is sent with
+//! BAM, which the [`J1939Socket`](super::J1939Socket) reassembles.
+//!
+//! The active and previously active DTCs are cleared by requesting DM11
+//! and DM3, with [`J1939Socket::clear_active_dtcs()`](super::J1939Socket::clear_active_dtcs)
+//! and [`J1939Socket::clear_previously_active_dtcs()`](super::J1939Socket::clear_previously_active_dtcs).
+//!
+//! ```no_run
+//! use socketcan::j1939::{DtcList, J1939Socket, Name, Pgn};
+//!
+//! let mut sock = J1939Socket::open("can0", Name(0x1234), 0xF9).unwrap();
+//! sock.claim().unwrap();
+//!
+//! loop {
+//!     let msg = sock.recv().unwrap();
+//!     if msg.id.pgn == Pgn::DM1 {
+//!         let dm1 = DtcList::from_bytes(&msg.data).unwrap();
+//!         for dtc in dm1.dtcs {
+//!             println!("0x{:02X}: {}", msg.id.source, dtc);
+//!         }
+//!     }
+//! }
+//! ```
+
+use super::Error;
+use std::fmt;
+
+// ===== Lamps =====
+
+/// The state of a warning lamp.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LampState {
+    /// The lamp is off
+    #[default]
+    Off,
+    /// The lamp is on
+    On,
+    /// Reserved value
+    Reserved,
+    /// The node doesn't have the lamp
+    NotAvailable,
+}
+
+impl From<u8> for LampState {
+    fn from(bits: u8) -> Self {
+        use LampState::*;
+        match bits & 0x03 {
+            0 => Off,
+            1 => On,
+            2 => Reserved,
+            _ => NotAvailable,
+        }
+    }
+}
+
+impl From<LampState> for u8 {
+    fn from(state: LampState) -> Self {
+        use LampState::*;
+        match state {
+            Off => 0,
+            On => 1,
+            Reserved => 2,
+            NotAvailable => 3,
+        }
+    }
+}
+
+/// How a warning lamp flashes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum FlashState {
+    /// Slow flash, once a second
+    Slow,
+    /// Fast flash, twice a second
+    Fast,
+    /// Reserved value
+    Reserved,
+    /// The lamp doesn't flash
+    #[default]
+    Off,
+}
+
+impl From<u8> for FlashState {
+    fn from(bits: u8) -> Self {
+        use FlashState::*;
+        match bits & 0x03 {
+            0 => Slow,
+            1 => Fast,
+            2 => Reserved,
+            _ => Off,
+        }
+    }
+}
+
+impl From<FlashState> for u8 {
+    fn from(state: FlashState) -> Self {
+        use FlashState::*;
+        match state {
+            Slow => 0,
+            Fast => 1,
+            Reserved => 2,
+            Off => 3,
+        }
+    }
+}
+
+/// The status of a warning lamp.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Lamp {
+    /// Whether the lamp is on
+    pub state: LampState,
+    /// How the lamp flashes
+    pub flash: FlashState,
+}
+
+impl Lamp {
+    /// Determines if the lamp is on.
+    pub fn is_on(&self) -> bool {
+        self.state == LampState::On
+    }
+}
+
+/// The status of the warning lamps of a node.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Lamps {
+    /// The malfunction indicator lamp (MIL), for emissions related faults
+    pub malfunction: Lamp,
+    /// The red stop lamp, for faults that need the vehicle to stop
+    pub red_stop: Lamp,
+    /// The amber warning lamp, for faults that don't need the vehicle to
+    /// stop right away
+    pub amber_warning: Lamp,
+    /// The protect lamp, for faults that aren't electronic
+    pub protect: Lamp,
+}
+
+impl Lamps {
+    /// Decodes the lamp status from the first two bytes of the message.
+    pub fn from_bytes(b: [u8; 2]) -> Self {
+        let lamp = |shift: u8| Lamp {
+            state: (b[0] >> shift).into(),
+            flash: (b[1] >> shift).into(),
+        };
+        Self {
+            malfunction: lamp(6),
+            red_stop: lamp(4),
+            amber_warning: lamp(2),
+            protect: lamp(0),
+        }
+    }
+
+    /// Gets the two bytes of the lamp status.
+    pub fn to_bytes(&self) -> [u8; 2] {
+        let lamps = [
+            (self.malfunction, 6),
+            (self.red_stop, 4),
+            (self.amber_warning, 2),
+            (self.protect, 0),
+        ];
+        lamps.iter().fold([0, 0], |[state, flash], (lamp, shift)| {
+            [
+                state | (u8::from(lamp.state) << shift),
+                flash | (u8::from(lamp.flash) << shift),
+            ]
+        })
+    }
+}
+
+// ===== Dtc =====
+
+/// A J1939 diagnostic trouble code.
+///
+/// This is the suspect parameter number (SPN) of the faulty parameter,
+/// with the failure mode identifier (FMI) describing the fault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Dtc {
+    /// The suspect parameter number (19 bits)
+    pub spn: u32,
+    /// The failure mode identifier (5 bits)
+    pub fmi: u8,
+    /// The number of times the fault went from inactive to active
+    /// (7 bits), or 0x7F if not available
+    pub occurrences: u8,
+    /// The SPN conversion method bit, set by nodes with a version of
+    /// J1939-73 before 2003, which might have the SPN bytes in another
+    /// order.
+    pub conversion_method: bool,
+}
+
+impl Dtc {
+    /// Creates a DTC with the current conversion method.
+    pub fn new(spn: u32, fmi: u8, occurrences: u8) -> Self {
+        Self {
+            spn: spn & 0x7FFFF,
+            fmi: fmi & 0x1F,
+            occurrences: occurrences & 0x7F,
+            conversion_method: false,
+        }
+    }
+
+    /// Decodes the DTC from the four bytes in a message.
+    pub fn from_bytes(b: [u8; 4]) -> Self {
+        Self {
+            spn: u32::from(b[0]) | (u32::from(b[1]) << 8) | (u32::from(b[2] & 0xE0) << 11),
+            fmi: b[2] & 0x1F,
+            occurrences: b[3] & 0x7F,
+            conversion_method: (b[3] & 0x80) != 0,
+        }
+    }
+
+    /// Gets the four bytes of the DTC in a message.
+    pub fn to_bytes(&self) -> [u8; 4] {
+        [
+            self.spn as u8,
+            (self.spn >> 8) as u8,
+            ((self.spn >> 11) as u8 & 0xE0) | (self.fmi & 0x1F),
+            (u8::from(self.conversion_method) << 7) | (self.occurrences & 0x7F),
+        ]
+    }
+
+    /// Gets a description of the failure mode, if it's defined.
+    pub fn fmi_description(&self) -> Option<&'static str> {
+        fmi_description(self.fmi)
+    }
+}
+
+impl fmt::Display for Dtc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SPN {} FMI {}", self.spn, self.fmi)?;
+        if self.occurrences != 0x7F {
+            write!(f, " (x{})", self.occurrences)?;
+        }
+        Ok(())
+    }
+}
+
+/// Gets the description of a failure mode identifier (FMI), if it's
+/// defined.
+pub fn fmi_description(fmi: u8) -> Option<&'static str> {
+    let desc = match fmi {
+        0 => "Data valid but above normal operational range - most severe level",
+        1 => "Data valid but below normal operational range - most severe level",
+        2 => "Data erratic, intermittent, or incorrect",
+        3 => "Voltage above normal, or shorted to high source",
+        4 => "Voltage below normal, or shorted to low source",
+        5 => "Current below normal, or open circuit",
+        6 => "Current above normal, or grounded circuit",
+        7 => "Mechanical system not responding or out of adjustment",
+        8 => "Abnormal frequency, pulse width, or period",
+        9 => "Abnormal update rate",
+        10 => "Abnormal rate of change",
+        11 => "Root cause not known",
+        12 => "Bad intelligent device or component",
+        13 => "Out of calibration",
+        14 => "Special instructions",
+        15 => "Data valid but above normal operating range - least severe level",
+        16 => "Data valid but above normal operating range - moderately severe level",
+        17 => "Data valid but below normal operating range - least severe level",
+        18 => "Data valid but below normal operating range - moderately severe level",
+        19 => "Received network data in error",
+        20 => "Data drifted high",
+        21 => "Data drifted low",
+        31 => "Condition exists",
+        _ => return None,
+    };
+    Some(desc)
+}
+
+// ===== DtcList =====
+
+/// The lamp status and trouble codes of a node, from a DM1 or DM2
+/// message.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DtcList {
+    /// The status of the warning lamps
+    pub lamps: Lamps,
+    /// The trouble codes
+    pub dtcs: Vec<Dtc>,
+}
+
+impl DtcList {
+    /// Creates a list of trouble codes.
+    pub fn new(lamps: Lamps, dtcs: impl Into<Vec<Dtc>>) -> Self {
+        Self {
+            lamps,
+            dtcs: dtcs.into(),
+        }
+    }
+
+    /// Decodes the data of a DM1 or DM2 message.
+    ///
+    /// The all-zero DTC that's sent when there are no faults, and any
+    /// padding, are skipped.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < 2 {
+            return Err(Error::InvalidLength(data.len()));
+        }
+        let dtcs = data[2..]
+            .chunks_exact(4)
+            .map(|b| Dtc::from_bytes([b[0], b[1], b[2], b[3]]))
+            .filter(|dtc| dtc.spn != 0 || dtc.fmi != 0)
+            .collect();
+        Ok(Self {
+            lamps: Lamps::from_bytes([data[0], data[1]]),
+            dtcs,
+        })
+    }
+
+    /// Gets the data of the message.
+    ///
+    /// With no trouble codes, this has the all-zero DTC. Messages that fit
+    /// in a frame are padded to eight bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = self.lamps.to_bytes().to_vec();
+        if self.dtcs.is_empty() {
+            data.extend([0; 4]);
+        }
+        for dtc in &self.dtcs {
+            data.extend(dtc.to_bytes());
+        }
+        if data.len() < 8 {
+            data.resize(8, 0xFF);
+        }
+        data
+    }
+
+    /// Determines if there are no trouble codes.
+    pub fn is_empty(&self) -> bool {
+        self.dtcs.is_empty()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dtc() {
+        // SPN 520192 (0x7F000), FMI 31, 3 occurrences
+        let dtc = Dtc::from_bytes([0x00, 0xF0, 0xFF, 0x03]);
+        assert_eq!(dtc, Dtc::new(0x7F000, 31, 3));
+        assert_eq!(dtc.to_bytes(), [0x00, 0xF0, 0xFF, 0x03]);
+        assert_eq!(dtc.fmi_description(), Some("Condition exists"));
+        assert_eq!(dtc.to_string(), "SPN 520192 FMI 31 (x3)");
+
+        // Engine coolant temperature, above normal
+        let dtc = Dtc::from_bytes([0x6E, 0x00, 0x00, 0xFF]);
+        assert_eq!(dtc.spn, 110);
+        assert_eq!(dtc.fmi, 0);
+        assert!(dtc.conversion_method);
+        assert_eq!(dtc.to_string(), "SPN 110 FMI 0");
+    }
+
+    #[test]
+    fn test_lamps() {
+        let lamps = Lamps::from_bytes([0x44, 0xF3]);
+        assert_eq!(lamps.malfunction.state, LampState::On);
+        assert_eq!(lamps.malfunction.flash, FlashState::Off);
+        assert_eq!(lamps.red_stop.state, LampState::Off);
+        assert!(lamps.amber_warning.is_on());
+        assert_eq!(lamps.amber_warning.flash, FlashState::Slow);
+        assert_eq!(lamps.protect.state, LampState::Off);
+        assert_eq!(lamps.to_bytes(), [0x44, 0xF3]);
+    }
+
+    #[test]
+    fn test_dtc_list() {
+        // No faults
+        let dm1 = DtcList::from_bytes(&[0x00, 0xFF, 0, 0, 0, 0, 0xFF, 0xFF]).unwrap();
+        assert!(dm1.is_empty());
+        assert_eq!(dm1.to_bytes(), &[0x00, 0xFF, 0, 0, 0, 0, 0xFF, 0xFF]);
+
+        // One fault fits in a frame
+        let data = [0x04, 0xFF, 0x6E, 0x00, 0x00, 0x01, 0xFF, 0xFF];
+        let dm1 = DtcList::from_bytes(&data).unwrap();
+        assert!(dm1.lamps.amber_warning.is_on());
+        assert_eq!(dm1.dtcs, &[Dtc::new(110, 0, 1)]);
+        assert_eq!(dm1.to_bytes(), &data);
+
+        // Several faults, as reassembled from a BAM
+        let dm2 = DtcList::new(
+            Lamps::default(),
+            vec![
+                Dtc::new(110, 0, 1),
+                Dtc::new(100, 1, 2),
+                Dtc::new(0x7FFFF, 4, 5),
+            ],
+        );
+        let data = dm2.to_bytes();
+        assert_eq!(data.len(), 14);
+        assert_eq!(DtcList::from_bytes(&data).unwrap(), dm2);
+
+        assert!(matches!(
+            DtcList::from_bytes(&[0x00]),
+            Err(Error::InvalidLength(1))
+        ));
+    }
+}
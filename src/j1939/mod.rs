@@ -0,0 +1,574 @@
+// socketcan/src/j1939/mod.rs
+//
+// SAE J1939 in userspace.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! SAE J1939, implemented in userspace over a raw [`CanSocket`](crate::CanSocket).
+//!
+//! J1939 is the higher-layer protocol used on heavy vehicles. It runs over
+//! CAN frames with 29-bit IDs, which hold the priority, the parameter
+//! group number (PGN) of the message, the source address of the sender,
+//! and for some PGNs, the destination address. See [`J1939Id`].
+//!
+//! This doesn't need the kernel's `can-j1939` module. The
+//! [`J1939Socket`] runs the J1939-81 address claim procedure for its
+//! [`Name`], including the "cannot claim" case, and the J1939-21 transport
+//! protocols for messages longer than a frame: BAM for broadcasts, RTS/CTS
+//! for messages to a single destination up to 1785 bytes, and the
+//! extended transport protocol (ETP) for longer ones. It also answers
+//! requests for PGNs with registered responders, and requests for the
+//! address claim.
+//!
+//! ```no_run
+//! use socketcan::j1939::{J1939Socket, Name, Pgn, GLOBAL_ADDRESS};
+//!
+//! let name = Name::default()
+//!     .with_identity_number(0x1234)
+//!     .with_manufacturer_code(0x123)
+//!     .with_arbitrary_address_capable(true);
+//! let mut sock = J1939Socket::open("can0", name, 0x80).unwrap();
+//!
+//! // Answer requests for the software identification
+//! sock.respond_to(Pgn(0xFEDA), |_req| Some(b"\x01v1.2.3*".to_vec()));
+//!
+//! let addr = sock.claim().unwrap();
+//! println!("Claimed address 0x{:02X}", addr);
+//!
+//! sock.send(Pgn(0xFEF1), 6, GLOBAL_ADDRESS, &[0xFF; 8]).unwrap();
+//! let msg = sock.recv().unwrap();
+//! println!("{:?}", msg);
+//! ```
+
+use crate::{frame::CanDataFrame, CanFrame, EmbeddedFrame, ExtendedId, Frame};
+use std::{error, fmt, io};
+
+pub mod dm;
+pub use dm::{Dtc, DtcList};
+
+pub mod name;
+pub use name::Name;
+
+pub mod socket;
+pub use socket::J1939Socket;
+
+mod claim;
+mod stack;
+mod transport;
+
+/// The null address, used as the source by nodes without an address.
+pub const NULL_ADDRESS: u8 = 0xFE;
+
+/// The global address, for messages to all nodes.
+pub const GLOBAL_ADDRESS: u8 = 0xFF;
+
+/// The maximum length of a message with the transport protocol (TP).
+pub const MAX_TP_LEN: usize = 1785;
+
+/// The maximum length of a message with the extended transport protocol
+/// (ETP).
+pub const MAX_ETP_LEN: usize = 117_440_505;
+
+/// The default priority of messages
+pub const DEFAULT_PRIORITY: u8 = 6;
+
+// ===== Pgn =====
+
+/// A J1939 parameter group number (PGN).
+///
+/// This is the 18-bit number that identifies the contents of a message.
+/// For PDU1 format PGNs, which are sent to a specific destination, the low
+/// byte (PDU specific) is zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pgn(pub u32);
+
+impl Pgn {
+    /// Request
+    pub const REQUEST: Pgn = Pgn(0xEA00);
+    /// Address claimed, or cannot claim address
+    pub const ADDRESS_CLAIMED: Pgn = Pgn(0xEE00);
+    /// Acknowledgement
+    pub const ACKNOWLEDGEMENT: Pgn = Pgn(0xE800);
+    /// Transport protocol, connection management
+    pub const TP_CM: Pgn = Pgn(0xEC00);
+    /// Transport protocol, data transfer
+    pub const TP_DT: Pgn = Pgn(0xEB00);
+    /// Extended transport protocol, connection management
+    pub const ETP_CM: Pgn = Pgn(0xC800);
+    /// Extended transport protocol, data transfer
+    pub const ETP_DT: Pgn = Pgn(0xC700);
+    /// DM1, the active diagnostic trouble codes
+    pub const DM1: Pgn = Pgn(0xFECA);
+    /// DM2, the previously active diagnostic trouble codes
+    pub const DM2: Pgn = Pgn(0xFECB);
+    /// DM3, clear the previously active diagnostic trouble codes
+    pub const DM3: Pgn = Pgn(0xFECC);
+    /// DM11, clear the active diagnostic trouble codes
+    pub const DM11: Pgn = Pgn(0xFED3);
+
+    /// Gets the PDU format (PF) field.
+    pub fn pdu_format(&self) -> u8 {
+        (self.0 >> 8) as u8
+    }
+
+    /// Gets the PDU specific (PS) field.
+    ///
+    /// This is the group extension for PDU2 format PGNs.
+    pub fn pdu_specific(&self) -> u8 {
+        self.0 as u8
+    }
+
+    /// Determines if this is a PDU1 format PGN, sent to a specific
+    /// destination address.
+    pub fn is_pdu1(&self) -> bool {
+        self.pdu_format() < 240
+    }
+
+    /// Gets the PGN from the three bytes used in requests and transport
+    /// messages, least significant first.
+    pub fn from_bytes(b: [u8; 3]) -> Self {
+        Self(u32::from_le_bytes([b[0], b[1], b[2], 0]) & 0x3FFFF)
+    }
+
+    /// Gets the three bytes of the PGN used in requests and transport
+    /// messages, least significant first.
+    pub fn to_bytes(&self) -> [u8; 3] {
+        let b = self.0.to_le_bytes();
+        [b[0], b[1], b[2]]
+    }
+}
+
+impl fmt::Display for Pgn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (0x{:05X})", self.0, self.0)
+    }
+}
+
+// ===== J1939Id =====
+
+/// The fields of a J1939 29-bit CAN ID.
+///
+/// For PDU1 format PGNs the destination address is in the ID, and for
+/// PDU2 format ones the destination is always the [`GLOBAL_ADDRESS`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct J1939Id {
+    /// The priority, 0 (highest) to 7 (lowest)
+    pub priority: u8,
+    /// The parameter group number
+    pub pgn: Pgn,
+    /// The source address
+    pub source: u8,
+    /// The destination address
+    pub destination: u8,
+}
+
+impl J1939Id {
+    /// Creates an ID from the fields.
+    ///
+    /// The destination is ignored for PDU2 format PGNs.
+    pub fn new(priority: u8, pgn: Pgn, source: u8, destination: u8) -> Self {
+        let (pgn, destination) = if pgn.is_pdu1() {
+            (Pgn(pgn.0 & 0x3FF00), destination)
+        } else {
+            (Pgn(pgn.0 & 0x3FFFF), GLOBAL_ADDRESS)
+        };
+        Self {
+            priority: priority & 0x07,
+            pgn,
+            source,
+            destination,
+        }
+    }
+
+    /// Gets the fields from a raw 29-bit CAN ID.
+    pub fn from_raw(id: u32) -> Self {
+        let priority = ((id >> 26) & 0x07) as u8;
+        let pgn = Pgn((id >> 8) & 0x3FFFF);
+        Self::new(priority, pgn, id as u8, pgn.pdu_specific())
+    }
+
+    /// Gets the raw 29-bit CAN ID.
+    pub fn as_raw(&self) -> u32 {
+        let ps = if self.pgn.is_pdu1() {
+            self.destination as u32
+        } else {
+            self.pgn.pdu_specific() as u32
+        };
+        ((self.priority as u32 & 0x07) << 26)
+            | ((self.pgn.0 & 0x3FF00) << 8)
+            | (ps << 8)
+            | self.source as u32
+    }
+
+    /// Gets the ID of a frame, if it's an extended data frame.
+    pub fn from_frame<F: Frame>(frame: &F) -> Option<Self> {
+        if frame.is_extended() && frame.is_data_frame() && !frame.is_error_frame() {
+            Some(Self::from_raw(frame.raw_id()))
+        } else {
+            None
+        }
+    }
+
+    /// Determines if the message is sent to all nodes.
+    pub fn is_broadcast(&self) -> bool {
+        self.destination == GLOBAL_ADDRESS
+    }
+}
+
+impl From<J1939Id> for ExtendedId {
+    fn from(id: J1939Id) -> Self {
+        // The raw value is always a valid 29-bit ID
+        ExtendedId::new(id.as_raw()).unwrap()
+    }
+}
+
+impl From<ExtendedId> for J1939Id {
+    fn from(id: ExtendedId) -> Self {
+        Self::from_raw(id.as_raw())
+    }
+}
+
+/// Creates a frame for the ID.
+///
+/// The data must be no longer than eight bytes.
+pub(crate) fn frame(id: J1939Id, data: &[u8]) -> CanFrame {
+    CanFrame::Data(CanDataFrame::new(ExtendedId::from(id), data).expect("J1939 frame data"))
+}
+
+// ===== Message =====
+
+/// A J1939 message, which might have been sent with a transport protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    /// The ID of the message
+    pub id: J1939Id,
+    /// The data in the message
+    pub data: Vec<u8>,
+}
+
+impl Message {
+    /// Creates a message.
+    pub fn new(id: J1939Id, data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            id,
+            data: data.into(),
+        }
+    }
+}
+
+// ===== Acknowledgement =====
+
+/// The control byte of an acknowledgement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AckControl {
+    /// Positive acknowledgement
+    Ack,
+    /// Negative acknowledgement
+    Nack,
+    /// Access denied
+    AccessDenied,
+    /// Cannot respond, because the node is busy
+    CannotRespond,
+    /// Another control byte
+    Other(u8),
+}
+
+impl From<u8> for AckControl {
+    fn from(code: u8) -> Self {
+        use AckControl::*;
+        match code {
+            0 => Ack,
+            1 => Nack,
+            2 => AccessDenied,
+            3 => CannotRespond,
+            code => Other(code),
+        }
+    }
+}
+
+impl From<AckControl> for u8 {
+    fn from(ctrl: AckControl) -> Self {
+        use AckControl::*;
+        match ctrl {
+            Ack => 0,
+            Nack => 1,
+            AccessDenied => 2,
+            CannotRespond => 3,
+            Other(code) => code,
+        }
+    }
+}
+
+/// An acknowledgement of a request or command.
+///
+/// This is the data of an [`Pgn::ACKNOWLEDGEMENT`] message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Acknowledgement {
+    /// Whether the request was accepted
+    pub control: AckControl,
+    /// The group function value, or 0xFF if not applicable
+    pub group_function: u8,
+    /// The address of the node that sent the request
+    pub address: u8,
+    /// The PGN of the request
+    pub pgn: Pgn,
+}
+
+impl Acknowledgement {
+    /// Creates an acknowledgement of a request for a PGN from a node.
+    pub fn new(control: AckControl, address: u8, pgn: Pgn) -> Self {
+        Self {
+            control,
+            group_function: 0xFF,
+            address,
+            pgn,
+        }
+    }
+
+    /// Decodes the acknowledgement from the data of a message.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < 8 {
+            return Err(Error::InvalidLength(data.len()));
+        }
+        Ok(Self {
+            control: data[0].into(),
+            group_function: data[1],
+            address: data[4],
+            pgn: Pgn::from_bytes([data[5], data[6], data[7]]),
+        })
+    }
+
+    /// Gets the data of the acknowledgement message.
+    pub fn to_bytes(&self) -> [u8; 8] {
+        let [g0, g1, g2] = self.pgn.to_bytes();
+        [
+            self.control.into(),
+            self.group_function,
+            0xFF,
+            0xFF,
+            self.address,
+            g0,
+            g1,
+            g2,
+        ]
+    }
+}
+
+// ===== ClaimState =====
+
+/// The state of the address claim of a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClaimState {
+    /// The claim hasn't started
+    Idle,
+    /// The claim was sent, and is waiting for contending claims
+    Claiming,
+    /// The node claimed the address
+    Claimed(u8),
+    /// The node couldn't claim an address
+    CannotClaim,
+}
+
+// ===== AbortReason =====
+
+/// The reason a transport protocol session was aborted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AbortReason {
+    /// The node is already in a session and can't support another
+    AlreadyInSession,
+    /// System resources were needed for another task
+    NoResources,
+    /// A timeout occurred
+    Timeout,
+    /// A CTS was received while a data transfer was in progress
+    CtsWhileTransferring,
+    /// The maximum retransmit request limit was reached
+    MaxRetransmit,
+    /// An unexpected data transfer packet arrived
+    UnexpectedDataTransfer,
+    /// A packet had a bad sequence number
+    BadSequence,
+    /// A packet had a duplicate sequence number
+    DuplicateSequence,
+    /// The message is too long for the protocol
+    MessageTooLarge,
+    /// Another reason code
+    Other(u8),
+}
+
+impl From<u8> for AbortReason {
+    fn from(code: u8) -> Self {
+        use AbortReason::*;
+        match code {
+            1 => AlreadyInSession,
+            2 => NoResources,
+            3 => Timeout,
+            4 => CtsWhileTransferring,
+            5 => MaxRetransmit,
+            6 => UnexpectedDataTransfer,
+            7 => BadSequence,
+            8 => DuplicateSequence,
+            9 => MessageTooLarge,
+            code => Other(code),
+        }
+    }
+}
+
+impl From<AbortReason> for u8 {
+    fn from(reason: AbortReason) -> Self {
+        use AbortReason::*;
+        match reason {
+            AlreadyInSession => 1,
+            NoResources => 2,
+            Timeout => 3,
+            CtsWhileTransferring => 4,
+            MaxRetransmit => 5,
+            UnexpectedDataTransfer => 6,
+            BadSequence => 7,
+            DuplicateSequence => 8,
+            MessageTooLarge => 9,
+            Other(code) => code,
+        }
+    }
+}
+
+impl fmt::Display for AbortReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use AbortReason::*;
+        let msg = match *self {
+            AlreadyInSession => "already in a session",
+            NoResources => "resources needed for another task",
+            Timeout => "timeout",
+            CtsWhileTransferring => "CTS received while transferring data",
+            MaxRetransmit => "maximum retransmit requests reached",
+            UnexpectedDataTransfer => "unexpected data transfer packet",
+            BadSequence => "bad sequence number",
+            DuplicateSequence => "duplicate sequence number",
+            MessageTooLarge => "message too large",
+            Other(code) => return write!(f, "abort reason {}", code),
+        };
+        f.write_str(msg)
+    }
+}
+
+// ===== Error =====
+
+/// A J1939 error.
+#[derive(Debug)]
+pub enum Error {
+    /// An I/O error on the socket
+    Io(io::Error),
+    /// The node hasn't claimed an address to send from
+    NoAddress,
+    /// The node couldn't claim an address
+    CannotClaim,
+    /// A transfer to the destination is already in progress
+    Busy,
+    /// The message is too long to send to the destination
+    InvalidLength(usize),
+    /// The transfer was aborted
+    Aborted(AbortReason),
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+        match self {
+            Io(err) => write!(f, "I/O error: {}", err),
+            NoAddress => f.write_str("no address claimed"),
+            CannotClaim => f.write_str("cannot claim an address"),
+            Busy => f.write_str("a transfer to the destination is in progress"),
+            InvalidLength(len) => write!(f, "invalid message length: {}", len),
+            Aborted(reason) => write!(f, "transfer aborted: {}", reason),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_id() {
+        // EEC1 from address 0x00, priority 3
+        let id = J1939Id::from_raw(0x0CF00400);
+        assert_eq!(id.priority, 3);
+        assert_eq!(id.pgn, Pgn(61444));
+        assert_eq!(id.source, 0x00);
+        assert_eq!(id.destination, GLOBAL_ADDRESS);
+        assert!(id.is_broadcast());
+        assert_eq!(id.as_raw(), 0x0CF00400);
+
+        // A request from 0xF9 to 0x17
+        let id = J1939Id::from_raw(0x18EA17F9);
+        assert_eq!(id.pgn, Pgn::REQUEST);
+        assert_eq!(id.source, 0xF9);
+        assert_eq!(id.destination, 0x17);
+        assert_eq!(id.as_raw(), 0x18EA17F9);
+
+        let id = J1939Id::new(7, Pgn::TP_CM, 0x80, 0x25);
+        assert_eq!(ExtendedId::from(id).as_raw(), 0x1CEC2580);
+        assert_eq!(J1939Id::from(ExtendedId::new(0x1CEC2580).unwrap()), id);
+
+        // The destination of a PDU2 PGN is always global
+        let id = J1939Id::new(6, Pgn(0xFEF1), 0x00, 0x25);
+        assert_eq!(id.destination, GLOBAL_ADDRESS);
+        assert_eq!(id.as_raw(), 0x18FEF100);
+
+        let frame = frame(id, &[1, 2, 3]);
+        assert_eq!(J1939Id::from_frame(&frame), Some(id));
+    }
+
+    #[test]
+    fn test_pgn() {
+        let pgn = Pgn(0x1FEDA);
+        assert_eq!(pgn.to_bytes(), [0xDA, 0xFE, 0x01]);
+        assert_eq!(Pgn::from_bytes([0xDA, 0xFE, 0x01]), pgn);
+        assert!(!pgn.is_pdu1());
+        assert!(Pgn::REQUEST.is_pdu1());
+        assert_eq!(Pgn::REQUEST.to_string(), "59904 (0x0EA00)");
+    }
+
+    #[test]
+    fn test_abort_reason() {
+        for code in 0..=0xFF {
+            assert_eq!(u8::from(AbortReason::from(code)), code);
+        }
+    }
+
+    #[test]
+    fn test_acknowledgement() {
+        let data = [0x00, 0xFF, 0xFF, 0xFF, 0xF9, 0xD3, 0xFE, 0x00];
+        let ack = Acknowledgement::from_bytes(&data).unwrap();
+        assert_eq!(ack, Acknowledgement::new(AckControl::Ack, 0xF9, Pgn::DM11));
+        assert_eq!(ack.to_bytes(), data);
+
+        assert_eq!(AckControl::from(3), AckControl::CannotRespond);
+        assert!(matches!(
+            Acknowledgement::from_bytes(&data[..4]),
+            Err(Error::InvalidLength(4))
+        ));
+    }
+}
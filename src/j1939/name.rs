@@ -0,0 +1,210 @@
+// socketcan/src/j1939/name.rs
+//
+// The J1939 NAME of a node.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! The 64-bit J1939 NAME that identifies a node.
+//!
+//! The NAME is sent in address claims, and also decides which of two
+//! nodes gets an address that both try to claim: the one with the lower
+//! NAME wins.
+
+use std::fmt;
+
+/// The 64-bit NAME of a J1939 node.
+///
+/// The fields can be set with the builder-style `with_` methods:
+///
+/// ```
+/// use socketcan::j1939::Name;
+///
+/// let name = Name::default()
+///     .with_identity_number(0x1234)
+///     .with_manufacturer_code(0x123)
+///     .with_function(0x81)
+///     .with_industry_group(1)
+///     .with_arbitrary_address_capable(true);
+///
+/// assert_eq!(name.manufacturer_code(), 0x123);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Name(pub u64);
+
+impl Name {
+    /// Creates a NAME from the eight bytes in an address claim.
+    pub fn from_bytes(b: [u8; 8]) -> Self {
+        Self(u64::from_le_bytes(b))
+    }
+
+    /// Gets the eight bytes for an address claim.
+    pub fn to_bytes(&self) -> [u8; 8] {
+        self.0.to_le_bytes()
+    }
+
+    /// Gets a field of the given number of bits.
+    fn field(&self, shift: u32, bits: u32) -> u64 {
+        (self.0 >> shift) & ((1 << bits) - 1)
+    }
+
+    /// Sets a field of the given number of bits.
+    fn with_field(self, shift: u32, bits: u32, val: u64) -> Self {
+        let mask = ((1 << bits) - 1) << shift;
+        Self((self.0 & !mask) | ((val << shift) & mask))
+    }
+
+    /// Gets the identity number, unique for the manufacturer (21 bits).
+    pub fn identity_number(&self) -> u32 {
+        self.field(0, 21) as u32
+    }
+
+    /// Sets the identity number.
+    pub fn with_identity_number(self, val: u32) -> Self {
+        self.with_field(0, 21, val.into())
+    }
+
+    /// Gets the manufacturer code (11 bits).
+    pub fn manufacturer_code(&self) -> u16 {
+        self.field(21, 11) as u16
+    }
+
+    /// Sets the manufacturer code.
+    pub fn with_manufacturer_code(self, val: u16) -> Self {
+        self.with_field(21, 11, val.into())
+    }
+
+    /// Gets the ECU instance (3 bits).
+    pub fn ecu_instance(&self) -> u8 {
+        self.field(32, 3) as u8
+    }
+
+    /// Sets the ECU instance.
+    pub fn with_ecu_instance(self, val: u8) -> Self {
+        self.with_field(32, 3, val.into())
+    }
+
+    /// Gets the function instance (5 bits).
+    pub fn function_instance(&self) -> u8 {
+        self.field(35, 5) as u8
+    }
+
+    /// Sets the function instance.
+    pub fn with_function_instance(self, val: u8) -> Self {
+        self.with_field(35, 5, val.into())
+    }
+
+    /// Gets the function.
+    pub fn function(&self) -> u8 {
+        self.field(40, 8) as u8
+    }
+
+    /// Sets the function.
+    pub fn with_function(self, val: u8) -> Self {
+        self.with_field(40, 8, val.into())
+    }
+
+    /// Gets the vehicle system (7 bits).
+    pub fn vehicle_system(&self) -> u8 {
+        self.field(49, 7) as u8
+    }
+
+    /// Sets the vehicle system.
+    pub fn with_vehicle_system(self, val: u8) -> Self {
+        self.with_field(49, 7, val.into())
+    }
+
+    /// Gets the vehicle system instance (4 bits).
+    pub fn vehicle_system_instance(&self) -> u8 {
+        self.field(56, 4) as u8
+    }
+
+    /// Sets the vehicle system instance.
+    pub fn with_vehicle_system_instance(self, val: u8) -> Self {
+        self.with_field(56, 4, val.into())
+    }
+
+    /// Gets the industry group (3 bits).
+    pub fn industry_group(&self) -> u8 {
+        self.field(60, 3) as u8
+    }
+
+    /// Sets the industry group.
+    pub fn with_industry_group(self, val: u8) -> Self {
+        self.with_field(60, 3, val.into())
+    }
+
+    /// Determines if the node can pick another address when it loses an
+    /// address claim.
+    pub fn is_arbitrary_address_capable(&self) -> bool {
+        self.field(63, 1) != 0
+    }
+
+    /// Sets whether the node can pick another address when it loses an
+    /// address claim.
+    pub fn with_arbitrary_address_capable(self, on: bool) -> Self {
+        self.with_field(63, 1, on.into())
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016X}", self.0)
+    }
+}
+
+impl From<u64> for Name {
+    fn from(val: u64) -> Self {
+        Self(val)
+    }
+}
+
+impl From<Name> for u64 {
+    fn from(name: Name) -> Self {
+        name.0
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields() {
+        let name = Name::default()
+            .with_identity_number(0x1F_FFFF)
+            .with_manufacturer_code(0x2AB)
+            .with_ecu_instance(5)
+            .with_function_instance(0x13)
+            .with_function(0x81)
+            .with_vehicle_system(0x7F)
+            .with_vehicle_system_instance(0x0A)
+            .with_industry_group(2)
+            .with_arbitrary_address_capable(true);
+
+        assert_eq!(name.identity_number(), 0x1F_FFFF);
+        assert_eq!(name.manufacturer_code(), 0x2AB);
+        assert_eq!(name.ecu_instance(), 5);
+        assert_eq!(name.function_instance(), 0x13);
+        assert_eq!(name.function(), 0x81);
+        assert_eq!(name.vehicle_system(), 0x7F);
+        assert_eq!(name.vehicle_system_instance(), 0x0A);
+        assert_eq!(name.industry_group(), 2);
+        assert!(name.is_arbitrary_address_capable());
+        assert_eq!(name.0, 0xAAFE_819D_557F_FFFF);
+
+        // Fields are masked to their width
+        let name = name.with_ecu_instance(0xFF);
+        assert_eq!(name.ecu_instance(), 7);
+        assert_eq!(name.function_instance(), 0x13);
+
+        assert_eq!(Name::from_bytes(name.to_bytes()), name);
+        assert_eq!(name.to_bytes()[0], 0xFF);
+    }
+}
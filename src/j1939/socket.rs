@@ -0,0 +1,273 @@
+// socketcan/src/j1939/socket.rs
+//
+// A blocking J1939 socket.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! A blocking J1939 node over a raw CAN socket.
+
+use super::{
+    stack::Stack, Acknowledgement, ClaimState, Error, J1939Id, Message, Name, Pgn,
+    DEFAULT_PRIORITY, GLOBAL_ADDRESS,
+};
+use crate::{frame::CAN_EFF_FLAG, CanFilter, CanFrame, CanSocket, Socket, SocketOptions};
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+/// The time to wait for the response to a request
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(1250);
+
+/// A J1939 node over a raw CAN socket.
+///
+/// The protocol runs in the calling thread, so the node only takes part
+/// in the bus, like answering address claims and requests, or receiving
+/// multi-packet messages, while one of the blocking calls is running.
+#[derive(Debug)]
+pub struct J1939Socket {
+    sock: CanSocket,
+    stack: Stack,
+}
+
+impl J1939Socket {
+    /// Opens a node on the named CAN interface, which will claim the
+    /// preferred address.
+    ///
+    /// The socket only receives frames with extended IDs. The address
+    /// isn't claimed until [`claim()`](Self::claim) is called.
+    pub fn open(ifname: &str, name: Name, address: u8) -> io::Result<Self> {
+        let sock = CanSocket::open(ifname)?;
+        sock.set_filters(&[CanFilter::new(CAN_EFF_FLAG, CAN_EFF_FLAG)])?;
+        Ok(Self::new(sock, name, address))
+    }
+
+    /// Creates a node on an open CAN socket, which will claim the
+    /// preferred address.
+    pub fn new(sock: CanSocket, name: Name, address: u8) -> Self {
+        Self {
+            sock,
+            stack: Stack::new(name, address),
+        }
+    }
+
+    /// Gets a reference to the underlying CAN socket.
+    pub fn socket(&self) -> &CanSocket {
+        &self.sock
+    }
+
+    /// Gets the NAME of the node.
+    pub fn name(&self) -> Name {
+        self.stack.name()
+    }
+
+    /// Gets the state of the address claim.
+    pub fn state(&self) -> ClaimState {
+        self.stack.state()
+    }
+
+    /// Gets the claimed address, if any.
+    pub fn address(&self) -> Option<u8> {
+        self.stack.address()
+    }
+
+    /// Sets the function to answer requests for a PGN.
+    ///
+    /// It gets the ID of the request, and returns the data of the
+    /// response. If it returns `None` for a request to this node, the
+    /// request gets a negative acknowledgement.
+    pub fn respond_to<F>(&mut self, pgn: Pgn, f: F)
+    where
+        F: FnMut(&J1939Id) -> Option<Vec<u8>> + Send + 'static,
+    {
+        self.stack.respond_to(pgn, Box::new(f));
+    }
+
+    /// Claims the address, blocking until the claim succeeds or fails.
+    ///
+    /// On success, this returns the claimed address, which might not be
+    /// the preferred one if the node is arbitrary address capable.
+    pub fn claim(&mut self) -> Result<u8, Error> {
+        self.stack.start(Instant::now());
+        self.run_until(None, |stack| !matches!(stack.state(), ClaimState::Claiming))?;
+        match self.stack.state() {
+            ClaimState::Claimed(addr) => Ok(addr),
+            _ => Err(Error::CannotClaim),
+        }
+    }
+
+    /// Sends a message, blocking until it's sent.
+    ///
+    /// Messages longer than eight bytes are sent with a transport
+    /// protocol, which fails with [`Error::Aborted`] if the transfer is
+    /// aborted or times out.
+    pub fn send(
+        &mut self,
+        pgn: Pgn,
+        priority: u8,
+        destination: u8,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        if !self
+            .stack
+            .send(pgn, priority, destination, data, Instant::now())?
+        {
+            return self.flush();
+        }
+        let dest = J1939Id::new(priority, pgn, 0, destination).destination;
+        let mut res = None;
+        self.run_until(None, |stack| {
+            res = stack.take_result(dest);
+            res.is_some()
+        })?;
+        res.unwrap_or(Ok(()))
+    }
+
+    /// Sends a request for a PGN to a node, or to all nodes.
+    ///
+    /// The responses are received as messages.
+    pub fn request(&mut self, pgn: Pgn, destination: u8) -> Result<(), Error> {
+        self.send(Pgn::REQUEST, DEFAULT_PRIORITY, destination, &pgn.to_bytes())
+    }
+
+    /// Clears the active trouble codes of a node, or of all nodes, with a
+    /// DM11 request.
+    ///
+    /// This returns the acknowledgements received, with the address of
+    /// the node that sent each one. A request to a single node returns as
+    /// soon as it answers, while a global request collects the answers
+    /// until the response timeout.
+    pub fn clear_active_dtcs(
+        &mut self,
+        destination: u8,
+    ) -> Result<Vec<(u8, Acknowledgement)>, Error> {
+        self.clear_dtcs(Pgn::DM11, destination)
+    }
+
+    /// Clears the previously active trouble codes of a node, or of all
+    /// nodes, with a DM3 request.
+    ///
+    /// See [`clear_active_dtcs()`](Self::clear_active_dtcs).
+    pub fn clear_previously_active_dtcs(
+        &mut self,
+        destination: u8,
+    ) -> Result<Vec<(u8, Acknowledgement)>, Error> {
+        self.clear_dtcs(Pgn::DM3, destination)
+    }
+
+    /// Sends a request to clear trouble codes and collects the
+    /// acknowledgements.
+    fn clear_dtcs(
+        &mut self,
+        pgn: Pgn,
+        destination: u8,
+    ) -> Result<Vec<(u8, Acknowledgement)>, Error> {
+        self.request(pgn, destination)?;
+        let address = self.address().ok_or(Error::NoAddress)?;
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+
+        let is_ack = |msg: &Message| {
+            msg.id.pgn == Pgn::ACKNOWLEDGEMENT
+                && (destination == GLOBAL_ADDRESS || msg.id.source == destination)
+                && Acknowledgement::from_bytes(&msg.data)
+                    .map(|ack| ack.pgn == pgn && ack.address == address)
+                    .unwrap_or(false)
+        };
+
+        let mut acks = Vec::new();
+        self.run_until(Some(deadline), |stack| {
+            while let Some(msg) = stack.take_message(is_ack) {
+                if let Ok(ack) = Acknowledgement::from_bytes(&msg.data) {
+                    acks.push((msg.id.source, ack));
+                }
+            }
+            destination != GLOBAL_ADDRESS && !acks.is_empty()
+        })?;
+        Ok(acks)
+    }
+
+    /// Receives the next message for this node, blocking until one
+    /// arrives.
+    pub fn recv(&mut self) -> Result<Message, Error> {
+        loop {
+            if let Some(msg) = self.recv_timeout(Duration::from_secs(3600))? {
+                return Ok(msg);
+            }
+        }
+    }
+
+    /// Receives the next message for this node, waiting up to the
+    /// timeout.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<Message>, Error> {
+        let deadline = Instant::now() + timeout;
+        self.run_until(Some(deadline), |stack| stack.has_message())?;
+        Ok(self.stack.pop_message())
+    }
+
+    /// Sends the queued frames.
+    fn flush(&mut self) -> Result<(), Error> {
+        while let Some(frame) = self.stack.pop_frame() {
+            self.sock.write_frame_insist(&frame)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the stack until the condition is met, or the deadline passes.
+    fn run_until<F>(&mut self, deadline: Option<Instant>, mut cond: F) -> Result<(), Error>
+    where
+        F: FnMut(&mut Stack) -> bool,
+    {
+        loop {
+            self.flush()?;
+            if cond(&mut self.stack) {
+                return Ok(());
+            }
+
+            let now = Instant::now();
+            let wake = match (deadline, self.stack.deadline()) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            if let Some(d) = deadline {
+                if d <= now {
+                    return Ok(());
+                }
+            }
+
+            let timeout = wake.map(|wake| wake.saturating_duration_since(now));
+            if let Some(frame) = read_socket(&self.sock, timeout)? {
+                self.stack.handle_frame(&frame, Instant::now());
+            }
+            self.stack.poll(Instant::now());
+        }
+    }
+}
+
+/// Reads a frame from the socket, returning `None` on timeout.
+pub(crate) fn read_socket(
+    sock: &CanSocket,
+    timeout: Option<Duration>,
+) -> io::Result<Option<CanFrame>> {
+    // The socket's timeout has millisecond resolution
+    let res = match timeout {
+        Some(timeout) => sock.read_frame_timeout(timeout.max(Duration::from_millis(1))),
+        None => sock.read_frame(),
+    };
+    match res {
+        Ok(frame) => Ok(Some(frame)),
+        Err(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
@@ -0,0 +1,517 @@
+// socketcan/src/j1939/stack.rs
+//
+// The J1939 protocol stack of a node.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! The J1939 protocol stack of a node, without any I/O.
+//!
+//! This combines the address claim, the transport protocols, and the
+//! handling of requests. It's driven with the received frames and the
+//! current time, and queues up the frames to send and the messages for
+//! the application.
+
+use super::{
+    claim::AddressClaimer, frame, transport::Transport, AckControl, Acknowledgement, ClaimState,
+    Error, J1939Id, Message, Name, Pgn, DEFAULT_PRIORITY, GLOBAL_ADDRESS,
+};
+use crate::{CanFrame, EmbeddedFrame};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    time::Instant,
+};
+
+/// A handler for requests of a PGN.
+///
+/// It gets the ID of the request, and returns the data of the response, or
+/// `None` to send a negative acknowledgement.
+pub(crate) type Responder = Box<dyn FnMut(&J1939Id) -> Option<Vec<u8>> + Send>;
+
+/// The J1939 stack of a node.
+pub(crate) struct Stack {
+    claimer: AddressClaimer,
+    transport: Transport,
+    responders: BTreeMap<Pgn, Responder>,
+    out: VecDeque<CanFrame>,
+    messages: VecDeque<Message>,
+}
+
+impl Stack {
+    /// Creates the stack of a node, which will claim the preferred
+    /// address.
+    pub(crate) fn new(name: Name, preferred: u8) -> Self {
+        Self {
+            claimer: AddressClaimer::new(name, preferred),
+            transport: Transport::default(),
+            responders: BTreeMap::new(),
+            out: VecDeque::new(),
+            messages: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn name(&self) -> Name {
+        self.claimer.name()
+    }
+
+    pub(crate) fn state(&self) -> ClaimState {
+        self.claimer.state()
+    }
+
+    pub(crate) fn address(&self) -> Option<u8> {
+        self.claimer.address()
+    }
+
+    /// Starts the address claim.
+    pub(crate) fn start(&mut self, now: Instant) {
+        let frame = self.claimer.start(now);
+        self.out.push_back(frame);
+    }
+
+    /// Sets the responder for requests of a PGN.
+    pub(crate) fn respond_to(&mut self, pgn: Pgn, responder: Responder) {
+        self.responders.insert(pgn, responder);
+    }
+
+    /// Sends a message from the claimed address.
+    ///
+    /// Returns `true` if the message started a transport protocol
+    /// session, which is finished when
+    /// [`take_result()`](Self::take_result) returns the result for the
+    /// destination.
+    pub(crate) fn send(
+        &mut self,
+        pgn: Pgn,
+        priority: u8,
+        destination: u8,
+        data: &[u8],
+        now: Instant,
+    ) -> Result<bool, Error> {
+        let source = self.address().ok_or(Error::NoAddress)?;
+        let id = J1939Id::new(priority, pgn, source, destination);
+        if data.len() <= 8 {
+            self.out.push_back(frame(id, data));
+            Ok(false)
+        } else {
+            self.transport.send(id, data.to_vec(), now)?;
+            self.drain();
+            Ok(true)
+        }
+    }
+
+    /// Takes the result of the last transport protocol session to the
+    /// destination, once it's finished.
+    pub(crate) fn take_result(&mut self, destination: u8) -> Option<Result<(), Error>> {
+        self.transport
+            .take_result(destination)
+            .map(|res| res.map_err(Error::Aborted))
+    }
+
+    /// Handles a received frame.
+    pub(crate) fn handle_frame(&mut self, frame: &CanFrame, now: Instant) {
+        let id = match J1939Id::from_frame(frame) {
+            Some(id) => id,
+            None => return,
+        };
+        let data = frame.data();
+        let address = self.address();
+        let for_us = id.is_broadcast() || Some(id.destination) == address;
+
+        match id.pgn {
+            Pgn::ADDRESS_CLAIMED if data.len() >= 8 => {
+                let mut name = [0u8; 8];
+                name.copy_from_slice(&data[..8]);
+                if let Some(frame) =
+                    self.claimer
+                        .handle_claim(id.source, Name::from_bytes(name), now)
+                {
+                    self.out.push_back(frame);
+                }
+            }
+            Pgn::REQUEST if for_us && data.len() >= 3 => {
+                let pgn = Pgn::from_bytes([data[0], data[1], data[2]]);
+                self.handle_request(&id, pgn, now);
+            }
+            Pgn::TP_CM | Pgn::TP_DT | Pgn::ETP_CM | Pgn::ETP_DT => {
+                self.transport.handle(&id, data, address, now);
+                self.drain();
+            }
+            _ if for_us => self.messages.push_back(Message::new(id, data)),
+            _ => {}
+        }
+    }
+
+    /// Handles a request for a PGN.
+    fn handle_request(&mut self, req: &J1939Id, pgn: Pgn, now: Instant) {
+        if pgn == Pgn::ADDRESS_CLAIMED {
+            if let Some(frame) = self.claimer.handle_request(now) {
+                self.out.push_back(frame);
+            }
+            return;
+        }
+
+        let address = match self.address() {
+            Some(addr) => addr,
+            None => return,
+        };
+        let resp = self.responders.get_mut(&pgn).and_then(|f| f(req));
+        match resp {
+            Some(data) => {
+                let dest = if req.is_broadcast() {
+                    GLOBAL_ADDRESS
+                } else {
+                    req.source
+                };
+                if let Err(err) = self.send(pgn, DEFAULT_PRIORITY, dest, &data, now) {
+                    log::debug!("Failed to respond to request for PGN {}: {}", pgn, err);
+                }
+            }
+            // Only requests to this node get a NACK
+            None if !req.is_broadcast() => {
+                let data = Acknowledgement::new(AckControl::Nack, req.source, pgn).to_bytes();
+                let id = J1939Id::new(
+                    DEFAULT_PRIORITY,
+                    Pgn::ACKNOWLEDGEMENT,
+                    address,
+                    GLOBAL_ADDRESS,
+                );
+                self.out.push_back(frame(id, &data));
+            }
+            None => {}
+        }
+    }
+
+    /// Checks the timers.
+    pub(crate) fn poll(&mut self, now: Instant) {
+        if let Some(frame) = self.claimer.poll(now) {
+            self.out.push_back(frame);
+        }
+        self.transport.poll(now);
+        self.drain();
+    }
+
+    /// Gets the next time that [`poll()`](Self::poll) needs to be called.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        match (self.claimer.deadline(), self.transport.deadline()) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Takes the next frame to send.
+    pub(crate) fn pop_frame(&mut self) -> Option<CanFrame> {
+        self.out.pop_front()
+    }
+
+    /// Determines if there's a received message waiting.
+    pub(crate) fn has_message(&self) -> bool {
+        !self.messages.is_empty()
+    }
+
+    /// Takes the next received message.
+    pub(crate) fn pop_message(&mut self) -> Option<Message> {
+        self.messages.pop_front()
+    }
+
+    /// Takes the first received message that matches the predicate,
+    /// leaving the others in the queue.
+    pub(crate) fn take_message<F>(&mut self, f: F) -> Option<Message>
+    where
+        F: FnMut(&Message) -> bool,
+    {
+        let i = self.messages.iter().position(f)?;
+        self.messages.remove(i)
+    }
+
+    /// Moves the frames and messages out of the transport.
+    fn drain(&mut self) {
+        self.out.append(&mut self.transport.out);
+        self.messages.append(&mut self.transport.messages);
+    }
+}
+
+impl fmt::Debug for Stack {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Stack")
+            .field("claimer", &self.claimer)
+            .field("transport", &self.transport)
+            .field("responders", &self.responders.keys())
+            .finish()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::j1939::{AbortReason, MAX_TP_LEN, NULL_ADDRESS};
+    use std::time::Duration;
+
+    const TICK: Duration = Duration::from_millis(10);
+
+    /// A simulated bus of nodes, with a simulated clock
+    struct Bus {
+        nodes: Vec<Stack>,
+        now: Instant,
+    }
+
+    impl Bus {
+        fn new(nodes: Vec<Stack>) -> Self {
+            Self {
+                nodes,
+                now: Instant::now(),
+            }
+        }
+
+        // Delivers the pending frames to all the other nodes, until the bus
+        // is quiet. Frames matching the filter are lost.
+        fn deliver(&mut self, drop: &dyn Fn(&J1939Id, &[u8]) -> bool) {
+            loop {
+                let mut sent = false;
+                for i in 0..self.nodes.len() {
+                    while let Some(frame) = self.nodes[i].pop_frame() {
+                        sent = true;
+                        let id = J1939Id::from_frame(&frame).unwrap();
+                        if drop(&id, frame.data()) {
+                            continue;
+                        }
+                        for (j, node) in self.nodes.iter_mut().enumerate() {
+                            if j != i {
+                                node.handle_frame(&frame, self.now);
+                            }
+                        }
+                    }
+                }
+                if !sent {
+                    return;
+                }
+            }
+        }
+
+        // Runs the bus for a time.
+        fn run_for(&mut self, dur: Duration, drop: &dyn Fn(&J1939Id, &[u8]) -> bool) {
+            let end = self.now + dur;
+            while self.now < end {
+                self.deliver(drop);
+                self.now += TICK;
+                for node in &mut self.nodes {
+                    node.poll(self.now);
+                }
+            }
+            self.deliver(drop);
+        }
+
+        fn run(&mut self, dur: Duration) {
+            self.run_for(dur, &|_, _| false);
+        }
+
+        fn claim_all(&mut self) {
+            for node in &mut self.nodes {
+                node.start(self.now);
+            }
+            self.run(Duration::from_millis(300));
+        }
+    }
+
+    fn node(name: u64, addr: u8) -> Stack {
+        Stack::new(Name(name), addr)
+    }
+
+    #[test]
+    fn test_address_claim() {
+        let aac = Name(0).with_arbitrary_address_capable(true).0;
+        let mut bus = Bus::new(vec![
+            node(0x200, 0x80),
+            node(0x100, 0x80),
+            node(aac | 0x300, 0x81),
+            node(0x400, 0x81),
+        ]);
+        bus.claim_all();
+
+        assert_eq!(bus.nodes[0].state(), ClaimState::CannotClaim);
+        assert_eq!(bus.nodes[1].address(), Some(0x80));
+        // The arbitrary address capable bit is the top one of the NAME, so
+        // that node loses, and moves to the next free address
+        assert_eq!(bus.nodes[2].address(), Some(0x82));
+        assert_eq!(bus.nodes[3].address(), Some(0x81));
+        assert!(matches!(
+            bus.nodes[0].send(Pgn(0xFEF1), 6, GLOBAL_ADDRESS, &[0; 8], bus.now),
+            Err(Error::NoAddress)
+        ));
+
+        // A new node with a lower NAME takes the address from it again
+        bus.nodes.push(node(0x050, 0x82));
+        let now = bus.now;
+        bus.nodes[4].start(now);
+        bus.run(Duration::from_millis(300));
+        assert_eq!(bus.nodes[4].address(), Some(0x82));
+        assert_eq!(bus.nodes[2].address(), Some(0x83));
+
+        // A request for the claim gets answers from every node
+        let id = J1939Id::new(6, Pgn::REQUEST, NULL_ADDRESS, GLOBAL_ADDRESS);
+        let req = frame(id, &Pgn::ADDRESS_CLAIMED.to_bytes());
+        let mut answers = Vec::new();
+        for node in &mut bus.nodes {
+            node.handle_frame(&req, now);
+            node.poll(now + Duration::from_millis(160));
+            while let Some(frame) = node.pop_frame() {
+                answers.push(J1939Id::from_frame(&frame).unwrap().source);
+            }
+        }
+        assert_eq!(answers, vec![NULL_ADDRESS, 0x80, 0x83, 0x81, 0x82]);
+    }
+
+    #[test]
+    fn test_single_frame() {
+        let mut bus = Bus::new(vec![node(0x100, 0x10), node(0x200, 0x20)]);
+        bus.claim_all();
+
+        let now = bus.now;
+        let tp = bus.nodes[0].send(Pgn(0xEF00), 3, 0x20, &[1, 2, 3], now);
+        assert!(!tp.unwrap());
+        bus.nodes[0].send(Pgn(0xEF00), 3, 0x30, &[4], now).unwrap();
+        bus.run(TICK);
+
+        let msg = bus.nodes[1].pop_message().unwrap();
+        assert_eq!(msg.id, J1939Id::new(3, Pgn(0xEF00), 0x10, 0x20));
+        assert_eq!(msg.data, &[1, 2, 3]);
+        // Messages to other nodes are filtered out
+        assert!(bus.nodes[1].pop_message().is_none());
+    }
+
+    #[test]
+    fn test_bam() {
+        let mut bus = Bus::new(vec![
+            node(0x100, 0x10),
+            node(0x200, 0x20),
+            node(0x300, 0x30),
+        ]);
+        bus.claim_all();
+
+        let data: Vec<u8> = (0..100).collect();
+        let now = bus.now;
+        assert!(bus.nodes[0]
+            .send(Pgn(0xFECA), 6, GLOBAL_ADDRESS, &data, now)
+            .unwrap());
+        assert!(matches!(
+            bus.nodes[0].send(Pgn(0xFECA), 6, GLOBAL_ADDRESS, &data, now),
+            Err(Error::Busy)
+        ));
+
+        // 15 packets, 50ms apart
+        bus.run(Duration::from_millis(800));
+        assert!(matches!(
+            bus.nodes[0].take_result(GLOBAL_ADDRESS),
+            Some(Ok(()))
+        ));
+        for node in &mut bus.nodes[1..] {
+            let msg = node.pop_message().unwrap();
+            assert_eq!(msg.id.pgn, Pgn(0xFECA));
+            assert_eq!(msg.id.source, 0x10);
+            assert_eq!(msg.data, data);
+        }
+    }
+
+    #[test]
+    fn test_rts_cts() {
+        let mut bus = Bus::new(vec![node(0x100, 0x10), node(0x200, 0x20)]);
+        bus.claim_all();
+
+        let data: Vec<u8> = (0..MAX_TP_LEN).map(|i| i as u8).collect();
+        let now = bus.now;
+        bus.nodes[0].send(Pgn(0xEF00), 6, 0x20, &data, now).unwrap();
+        bus.run(TICK);
+
+        assert!(matches!(bus.nodes[0].take_result(0x20), Some(Ok(()))));
+        let msg = bus.nodes[1].pop_message().unwrap();
+        assert_eq!(msg.id, J1939Id::new(7, Pgn(0xEF00), 0x10, 0x20));
+        assert_eq!(msg.data, data);
+    }
+
+    #[test]
+    fn test_etp() {
+        let mut bus = Bus::new(vec![node(0x100, 0x10), node(0x200, 0x20)]);
+        bus.claim_all();
+
+        let data: Vec<u8> = (0..5000).map(|i| (i % 251) as u8).collect();
+        let now = bus.now;
+        bus.nodes[1].send(Pgn(0xDA00), 6, 0x10, &data, now).unwrap();
+        bus.run(TICK);
+
+        assert!(matches!(bus.nodes[1].take_result(0x10), Some(Ok(()))));
+        let msg = bus.nodes[0].pop_message().unwrap();
+        assert_eq!(msg.id.pgn, Pgn(0xDA00));
+        assert_eq!(msg.data, data);
+    }
+
+    #[test]
+    fn test_transport_errors() {
+        let mut bus = Bus::new(vec![node(0x100, 0x10), node(0x200, 0x20)]);
+        bus.claim_all();
+        let data = [0x55; 100];
+
+        // A lost packet aborts the transfer with a bad sequence number
+        let now = bus.now;
+        bus.nodes[0].send(Pgn(0xEF00), 6, 0x20, &data, now).unwrap();
+        let dt = Pgn::TP_DT;
+        bus.run_for(TICK, &|id, data| id.pgn == dt && data[0] == 3);
+        assert!(matches!(
+            bus.nodes[0].take_result(0x20),
+            Some(Err(Error::Aborted(AbortReason::BadSequence)))
+        ));
+        assert!(bus.nodes[1].pop_message().is_none());
+
+        // A receiver that doesn't answer times out
+        let now = bus.now;
+        bus.nodes[0].send(Pgn(0xEF00), 6, 0x20, &data, now).unwrap();
+        let cm = Pgn::TP_CM;
+        bus.run_for(Duration::from_millis(1300), &|id, _| {
+            id.pgn == cm && id.source == 0x20
+        });
+        assert!(matches!(
+            bus.nodes[0].take_result(0x20),
+            Some(Err(Error::Aborted(AbortReason::Timeout)))
+        ));
+
+        // Messages too long for the destination
+        let now = bus.now;
+        assert!(matches!(
+            bus.nodes[0].send(Pgn(0xFECA), 6, GLOBAL_ADDRESS, &[0; MAX_TP_LEN + 1], now),
+            Err(Error::InvalidLength(_))
+        ));
+    }
+
+    #[test]
+    fn test_requests() {
+        let mut bus = Bus::new(vec![node(0x100, 0x10), node(0x200, 0x20)]);
+        bus.nodes[1].respond_to(Pgn(0xFEDA), Box::new(|req| Some(vec![req.source; 20])));
+        bus.claim_all();
+
+        // A request with a long response, over BAM for the PDU2 PGN
+        let now = bus.now;
+        bus.nodes[0]
+            .send(Pgn::REQUEST, 6, 0x20, &Pgn(0xFEDA).to_bytes(), now)
+            .unwrap();
+        bus.run(Duration::from_millis(300));
+        let msg = bus.nodes[0].pop_message().unwrap();
+        assert_eq!(msg.id.pgn, Pgn(0xFEDA));
+        assert_eq!(msg.data, vec![0x10; 20]);
+
+        // A request for an unsupported PGN is NACKed
+        let now = bus.now;
+        bus.nodes[0]
+            .send(Pgn::REQUEST, 6, 0x20, &Pgn(0xFEEB).to_bytes(), now)
+            .unwrap();
+        bus.run(TICK);
+        let msg = bus.nodes[0].pop_message().unwrap();
+        assert_eq!(msg.id.pgn, Pgn::ACKNOWLEDGEMENT);
+        let nack = u8::from(AckControl::Nack);
+        assert_eq!(msg.data, &[nack, 0xFF, 0xFF, 0xFF, 0x10, 0xEB, 0xFE, 0x00]);
+    }
+}
@@ -0,0 +1,580 @@
+// socketcan/src/j1939/transport.rs
+//
+// The J1939-21 transport protocols.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! The J1939-21 transport protocols for messages longer than a frame.
+//!
+//! - BAM (broadcast announce message) sends a message to all nodes, with
+//!   the data packets paced by the sender.
+//! - RTS/CTS sends a message of up to 1785 bytes to one node, which
+//!   controls the flow with "clear to send" messages and acknowledges
+//!   the whole message at the end.
+//! - The extended transport protocol (ETP) works like RTS/CTS, for
+//!   messages of up to 117,440,505 bytes, with a "data packet offset"
+//!   message in front of each window of packets.
+//!
+//! The sessions are driven by the caller with the received frames and the
+//! current time, and queue up the frames to send and the messages that
+//! were received.
+
+use super::{
+    frame, AbortReason, Error, J1939Id, Message, Pgn, GLOBAL_ADDRESS, MAX_ETP_LEN, MAX_TP_LEN,
+};
+use crate::CanFrame;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// The time between the packets of a BAM
+const BAM_GAP: Duration = Duration::from_millis(50);
+
+/// The time to wait for the next data packet
+const T1: Duration = Duration::from_millis(750);
+
+/// The time to wait for the first data packet after a CTS
+const T2: Duration = Duration::from_millis(1250);
+
+/// The time to wait for a CTS or the end of message acknowledgement
+const T3: Duration = Duration::from_millis(1250);
+
+/// The time to wait for a CTS after a CTS that holds the connection open
+const T4: Duration = Duration::from_millis(1050);
+
+/// The priority of the transport protocol frames
+const TP_PRIORITY: u8 = 7;
+
+/// The number of data bytes in each packet
+const PACKET_LEN: usize = 7;
+
+// The connection management control bytes
+const RTS: u8 = 16;
+const CTS: u8 = 17;
+const EOMA: u8 = 19;
+const BAM: u8 = 32;
+const ETP_RTS: u8 = 20;
+const ETP_CTS: u8 = 21;
+const ETP_DPO: u8 = 22;
+const ETP_EOMA: u8 = 23;
+const ABORT: u8 = 255;
+
+/// The transport protocol of a session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Bam,
+    Tp,
+    Etp,
+}
+
+impl Kind {
+    /// The connection management PGN
+    fn cm(self) -> Pgn {
+        match self {
+            Kind::Etp => Pgn::ETP_CM,
+            _ => Pgn::TP_CM,
+        }
+    }
+
+    /// The data transfer PGN
+    fn dt(self) -> Pgn {
+        match self {
+            Kind::Etp => Pgn::ETP_DT,
+            _ => Pgn::TP_DT,
+        }
+    }
+}
+
+/// Gets the number of packets for a message length.
+fn num_packets(len: usize) -> usize {
+    (len + PACKET_LEN - 1) / PACKET_LEN
+}
+
+/// Builds a connection management frame.
+fn cm_frame(kind: Kind, source: u8, destination: u8, data: [u8; 8]) -> CanFrame {
+    frame(
+        J1939Id::new(TP_PRIORITY, kind.cm(), source, destination),
+        &data,
+    )
+}
+
+/// Builds a connection management frame with a control byte, five bytes
+/// of parameters, and the PGN.
+fn cm(kind: Kind, source: u8, dest: u8, ctrl: u8, params: [u8; 4], pgn: Pgn) -> CanFrame {
+    let [p0, p1, p2, p3] = params;
+    let [g0, g1, g2] = pgn.to_bytes();
+    cm_frame(kind, source, dest, [ctrl, p0, p1, p2, p3, g0, g1, g2])
+}
+
+/// Builds an abort frame.
+fn abort(kind: Kind, source: u8, dest: u8, reason: AbortReason, pgn: Pgn) -> CanFrame {
+    cm(
+        kind,
+        source,
+        dest,
+        ABORT,
+        [reason.into(), 0xFF, 0xFF, 0xFF],
+        pgn,
+    )
+}
+
+/// Gets the little-endian bytes of a 24-bit value.
+fn u24_bytes(val: usize) -> [u8; 3] {
+    let b = (val as u32).to_le_bytes();
+    [b[0], b[1], b[2]]
+}
+
+// ===== Sessions =====
+
+/// The state of a sending session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TxState {
+    /// Sending BAM data packets
+    Sending,
+    /// Waiting for a CTS
+    WaitCts,
+    /// Waiting for the end of message acknowledgement
+    WaitEoma,
+}
+
+/// A message being sent
+#[derive(Debug)]
+struct TxSession {
+    kind: Kind,
+    id: J1939Id,
+    data: Vec<u8>,
+    packets: usize,
+    /// The index of the next packet to send
+    next: usize,
+    state: TxState,
+    deadline: Instant,
+}
+
+impl TxSession {
+    /// Builds the frame for a data packet.
+    fn data_frame(&self, index: usize, seq: usize) -> CanFrame {
+        let mut buf = [0xFF; 8];
+        buf[0] = seq as u8;
+        let start = index * PACKET_LEN;
+        let end = (start + PACKET_LEN).min(self.data.len());
+        buf[1..1 + end - start].copy_from_slice(&self.data[start..end]);
+
+        let id = J1939Id::new(
+            TP_PRIORITY,
+            self.kind.dt(),
+            self.id.source,
+            self.id.destination,
+        );
+        frame(id, &buf)
+    }
+}
+
+/// A message being received
+#[derive(Debug)]
+struct RxSession {
+    kind: Kind,
+    id: J1939Id,
+    size: usize,
+    packets: usize,
+    /// The maximum packets per CTS that the sender accepts
+    max_window: usize,
+    data: Vec<u8>,
+    /// The number of packets received
+    received: usize,
+    /// The end of the packets cleared to send
+    end: usize,
+    /// The packet offset of the current ETP window
+    offset: usize,
+    deadline: Instant,
+}
+
+// ===== Transport =====
+
+/// The transport protocol sessions of a node.
+#[derive(Debug, Default)]
+pub(crate) struct Transport {
+    tx: Vec<TxSession>,
+    rx: Vec<RxSession>,
+    /// The frames to send
+    pub(crate) out: VecDeque<CanFrame>,
+    /// The messages received
+    pub(crate) messages: VecDeque<Message>,
+    /// The results of the finished transfers, by destination
+    results: Vec<(u8, Result<(), AbortReason>)>,
+}
+
+impl Transport {
+    /// Starts sending a message that's longer than a frame.
+    ///
+    /// Broadcasts are sent with BAM, and messages to a single destination
+    /// with RTS/CTS or ETP, depending on the length.
+    pub(crate) fn send(&mut self, id: J1939Id, data: Vec<u8>, now: Instant) -> Result<(), Error> {
+        let len = data.len();
+        let kind = match len {
+            _ if len <= 8 => return Err(Error::InvalidLength(len)),
+            _ if id.destination == GLOBAL_ADDRESS && len <= MAX_TP_LEN => Kind::Bam,
+            _ if id.destination == GLOBAL_ADDRESS => return Err(Error::InvalidLength(len)),
+            _ if len <= MAX_TP_LEN => Kind::Tp,
+            _ if len <= MAX_ETP_LEN => Kind::Etp,
+            _ => return Err(Error::InvalidLength(len)),
+        };
+        if self.tx.iter().any(|s| s.id.destination == id.destination) {
+            return Err(Error::Busy);
+        }
+        self.results.retain(|(dest, _)| *dest != id.destination);
+
+        let packets = num_packets(len);
+        let [s0, s1, s2, s3] = (len as u32).to_le_bytes();
+        let (ctrl, params) = match kind {
+            Kind::Bam => (BAM, [s0, s1, packets as u8, 0xFF]),
+            Kind::Tp => (RTS, [s0, s1, packets as u8, 0xFF]),
+            Kind::Etp => (ETP_RTS, [s0, s1, s2, s3]),
+        };
+        self.out
+            .push_back(cm(kind, id.source, id.destination, ctrl, params, id.pgn));
+
+        let (state, timeout) = match kind {
+            Kind::Bam => (TxState::Sending, BAM_GAP),
+            _ => (TxState::WaitCts, T3),
+        };
+        self.tx.push(TxSession {
+            kind,
+            id,
+            data,
+            packets,
+            next: 0,
+            state,
+            deadline: now + timeout,
+        });
+        Ok(())
+    }
+
+    /// Takes the result of the last transfer to the destination, once
+    /// it's finished.
+    pub(crate) fn take_result(&mut self, dest: u8) -> Option<Result<(), AbortReason>> {
+        let i = self.results.iter().position(|(d, _)| *d == dest)?;
+        Some(self.results.remove(i).1)
+    }
+
+    /// Gets the next time that [`poll()`](Self::poll) needs to be called.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        let tx = self.tx.iter().map(|s| s.deadline);
+        let rx = self.rx.iter().map(|s| s.deadline);
+        tx.chain(rx).min()
+    }
+
+    /// Handles a transport protocol frame.
+    ///
+    /// Frames that aren't broadcasts, or sent to the node's `address`, are
+    /// ignored.
+    pub(crate) fn handle(&mut self, id: &J1939Id, data: &[u8], address: Option<u8>, now: Instant) {
+        if data.len() < 8 || !(id.is_broadcast() || Some(id.destination) == address) {
+            return;
+        }
+        match id.pgn {
+            Pgn::TP_CM => self.handle_cm(false, id, data, now),
+            Pgn::ETP_CM => self.handle_cm(true, id, data, now),
+            Pgn::TP_DT if id.is_broadcast() => self.handle_dt(Kind::Bam, id, data, now),
+            Pgn::TP_DT => self.handle_dt(Kind::Tp, id, data, now),
+            Pgn::ETP_DT => self.handle_dt(Kind::Etp, id, data, now),
+            _ => {}
+        }
+    }
+
+    /// Handles a connection management frame.
+    fn handle_cm(&mut self, etp: bool, id: &J1939Id, data: &[u8], now: Instant) {
+        let pgn = Pgn::from_bytes([data[5], data[6], data[7]]);
+        let src = id.source;
+        let size16 = u16::from_le_bytes([data[1], data[2]]) as usize;
+        let size32 = u32::from_le_bytes([data[1], data[2], data[3], data[4]]) as usize;
+        let u24 = |b: &[u8]| u32::from_le_bytes([b[0], b[1], b[2], 0]) as usize;
+
+        match (etp, data[0]) {
+            (false, BAM) if id.is_broadcast() => {
+                let packets = data[3] as usize;
+                if size16 > 8 && size16 <= MAX_TP_LEN && packets == num_packets(size16) {
+                    self.start_rx(Kind::Bam, id, pgn, size16, packets, now);
+                }
+            }
+            (false, RTS) if !id.is_broadcast() => {
+                let packets = data[3] as usize;
+                if size16 > MAX_TP_LEN {
+                    let reason = AbortReason::MessageTooLarge;
+                    self.out
+                        .push_back(abort(Kind::Tp, id.destination, src, reason, pgn));
+                } else if size16 > 8 && packets == num_packets(size16) {
+                    let max = match data[4] {
+                        0 => 0xFF,
+                        n => n as usize,
+                    };
+                    self.start_rx(Kind::Tp, id, pgn, size16, max, now);
+                    self.send_cts(self.rx.len() - 1, now);
+                }
+            }
+            (true, ETP_RTS) if !id.is_broadcast() => {
+                if size32 > MAX_ETP_LEN {
+                    let reason = AbortReason::MessageTooLarge;
+                    self.out
+                        .push_back(abort(Kind::Etp, id.destination, src, reason, pgn));
+                } else if size32 > MAX_TP_LEN {
+                    self.start_rx(Kind::Etp, id, pgn, size32, 0xFF, now);
+                    self.send_cts(self.rx.len() - 1, now);
+                }
+            }
+            (false, CTS) => self.handle_cts(Kind::Tp, src, pgn, data[1], data[2] as usize, now),
+            (true, ETP_CTS) => self.handle_cts(Kind::Etp, src, pgn, data[1], u24(&data[2..5]), now),
+            (false, EOMA) => self.finish_tx(Kind::Tp, src, pgn, Ok(())),
+            (true, ETP_EOMA) => self.finish_tx(Kind::Etp, src, pgn, Ok(())),
+            (true, ETP_DPO) => {
+                let (count, offset) = (data[1] as usize, u24(&data[2..5]));
+                if let Some(s) = self.rx_session(Kind::Etp, src) {
+                    if s.id.pgn == pgn && offset == s.received {
+                        s.offset = offset;
+                        s.end = s.end.min(offset + count);
+                        s.deadline = now + T1;
+                    }
+                }
+            }
+            (_, ABORT) => {
+                let kind = if etp { Kind::Etp } else { Kind::Tp };
+                self.finish_tx(kind, src, pgn, Err(AbortReason::from(data[1])));
+                self.rx
+                    .retain(|s| !(s.kind == kind && s.id.source == src && s.id.pgn == pgn));
+            }
+            _ => {}
+        }
+    }
+
+    /// Starts receiving a message, replacing any other one from the same
+    /// sender with the same protocol.
+    fn start_rx(
+        &mut self,
+        kind: Kind,
+        id: &J1939Id,
+        pgn: Pgn,
+        size: usize,
+        max_window: usize,
+        now: Instant,
+    ) {
+        self.rx
+            .retain(|s| !(s.kind == kind && s.id.source == id.source));
+        let packets = num_packets(size);
+        self.rx.push(RxSession {
+            kind,
+            id: J1939Id::new(id.priority, pgn, id.source, id.destination),
+            size,
+            packets,
+            max_window,
+            data: Vec::with_capacity(size.min(MAX_TP_LEN)),
+            received: 0,
+            end: if kind == Kind::Bam { packets } else { 0 },
+            offset: 0,
+            deadline: now + T1,
+        });
+    }
+
+    /// Gets the receive session from a sender.
+    fn rx_session(&mut self, kind: Kind, source: u8) -> Option<&mut RxSession> {
+        self.rx
+            .iter_mut()
+            .find(|s| s.kind == kind && s.id.source == source)
+    }
+
+    /// Sends a CTS for the next window of packets of a receive session.
+    fn send_cts(&mut self, i: usize, now: Instant) {
+        let s = &mut self.rx[i];
+        let count = (s.packets - s.received).min(s.max_window).min(0xFF);
+        s.end = s.received + count;
+        s.deadline = now + T2;
+
+        let next = s.received + 1;
+        let (ctrl, params) = match s.kind {
+            Kind::Etp => {
+                let [n0, n1, n2] = u24_bytes(next);
+                (ETP_CTS, [count as u8, n0, n1, n2])
+            }
+            _ => (CTS, [count as u8, next as u8, 0xFF, 0xFF]),
+        };
+        let frame = cm(
+            s.kind,
+            s.id.destination,
+            s.id.source,
+            ctrl,
+            params,
+            s.id.pgn,
+        );
+        self.out.push_back(frame);
+    }
+
+    /// Handles a data transfer frame.
+    fn handle_dt(&mut self, kind: Kind, id: &J1939Id, data: &[u8], now: Instant) {
+        let i = match self
+            .rx
+            .iter()
+            .position(|s| s.kind == kind && s.id.source == id.source)
+        {
+            Some(i) => i,
+            None => return,
+        };
+
+        let s = &mut self.rx[i];
+        let seq = data[0] as usize;
+        let index = match kind {
+            Kind::Etp => (s.offset + seq).wrapping_sub(1),
+            _ => seq.wrapping_sub(1),
+        };
+        if seq == 0 || index != s.received || index >= s.end {
+            let s = self.rx.remove(i);
+            if kind != Kind::Bam {
+                let reason = if index < s.received {
+                    AbortReason::DuplicateSequence
+                } else {
+                    AbortReason::BadSequence
+                };
+                let frame = abort(kind, s.id.destination, s.id.source, reason, s.id.pgn);
+                self.out.push_back(frame);
+            }
+            return;
+        }
+
+        let len = (s.size - s.data.len()).min(PACKET_LEN);
+        s.data.extend_from_slice(&data[1..1 + len]);
+        s.received += 1;
+
+        if s.received == s.packets {
+            let s = self.rx.remove(i);
+            let [s0, s1, s2, s3] = (s.size as u32).to_le_bytes();
+            let eoma = match kind {
+                Kind::Bam => None,
+                Kind::Tp => Some((EOMA, [s0, s1, s.packets as u8, 0xFF])),
+                Kind::Etp => Some((ETP_EOMA, [s0, s1, s2, s3])),
+            };
+            if let Some((ctrl, params)) = eoma {
+                let frame = cm(kind, s.id.destination, s.id.source, ctrl, params, s.id.pgn);
+                self.out.push_back(frame);
+            }
+            self.messages.push_back(Message::new(s.id, s.data));
+        } else if s.received == s.end {
+            self.send_cts(i, now);
+        } else {
+            s.deadline = now + T1;
+        }
+    }
+
+    /// Handles a CTS for a sending session, sending the packets that it
+    /// clears.
+    fn handle_cts(&mut self, kind: Kind, src: u8, pgn: Pgn, count: u8, next: usize, now: Instant) {
+        let s = match self
+            .tx
+            .iter_mut()
+            .find(|s| s.kind == kind && s.id.destination == src && s.id.pgn == pgn)
+        {
+            Some(s) => s,
+            None => return,
+        };
+
+        if count == 0 {
+            // The receiver holds the connection open
+            s.state = TxState::WaitCts;
+            s.deadline = now + T4;
+            return;
+        }
+        if next == 0 || next > s.packets {
+            let reason = AbortReason::BadSequence;
+            let frame = abort(kind, s.id.source, src, reason, pgn);
+            self.out.push_back(frame);
+            self.finish_tx(kind, src, pgn, Err(reason));
+            return;
+        }
+
+        let start = next - 1;
+        let end = (start + count as usize).min(s.packets);
+        if kind == Kind::Etp {
+            let [o0, o1, o2] = u24_bytes(start);
+            let params = [(end - start) as u8, o0, o1, o2];
+            let frame = cm(kind, s.id.source, src, ETP_DPO, params, pgn);
+            self.out.push_back(frame);
+        }
+        for index in start..end {
+            let seq = match kind {
+                Kind::Etp => index - start + 1,
+                _ => index + 1,
+            };
+            self.out.push_back(s.data_frame(index, seq));
+        }
+        s.next = end;
+        s.state = if end == s.packets {
+            TxState::WaitEoma
+        } else {
+            TxState::WaitCts
+        };
+        s.deadline = now + T3;
+    }
+
+    /// Ends a sending session, recording the result.
+    fn finish_tx(&mut self, kind: Kind, dest: u8, pgn: Pgn, res: Result<(), AbortReason>) {
+        if let Some(i) = self
+            .tx
+            .iter()
+            .position(|s| s.kind == kind && s.id.destination == dest && s.id.pgn == pgn)
+        {
+            self.tx.remove(i);
+            self.results.push((dest, res));
+        }
+    }
+
+    /// Checks the timers, sending the paced BAM packets and aborting the
+    /// sessions that timed out.
+    pub(crate) fn poll(&mut self, now: Instant) {
+        let mut i = 0;
+        while i < self.tx.len() {
+            let s = &mut self.tx[i];
+            if s.deadline > now {
+                i += 1;
+                continue;
+            }
+            if s.state == TxState::Sending {
+                let frame = s.data_frame(s.next, s.next + 1);
+                self.out.push_back(frame);
+                s.next += 1;
+                if s.next < s.packets {
+                    s.deadline = now + BAM_GAP;
+                    i += 1;
+                    continue;
+                }
+                let s = self.tx.remove(i);
+                self.results.push((s.id.destination, Ok(())));
+            } else {
+                let s = self.tx.remove(i);
+                let reason = AbortReason::Timeout;
+                let frame = abort(s.kind, s.id.source, s.id.destination, reason, s.id.pgn);
+                self.out.push_back(frame);
+                self.results.push((s.id.destination, Err(reason)));
+            }
+        }
+
+        let out = &mut self.out;
+        self.rx.retain(|s| {
+            if s.deadline > now {
+                return true;
+            }
+            if s.kind != Kind::Bam {
+                let reason = AbortReason::Timeout;
+                out.push_back(abort(
+                    s.kind,
+                    s.id.destination,
+                    s.id.source,
+                    reason,
+                    s.id.pgn,
+                ));
+            }
+            false
+        });
+    }
+}
@@ -71,6 +71,62 @@
 //!   dependencies like [anyhow](https://docs.rs/anyhow/latest/anyhow/) and
 //!   [clap](https://docs.rs/clap/latest/clap/)
 //!
+//! * **gzip**, **zstd**, **xz** -
+//!   Whether to read and write log files in the `dump` module that are
+//!   compressed with the corresponding format. When reading, compressed
+//!   files are detected by their magic bytes.
+//!
+//! * **serde** -
+//!   Implement [serde](https://crates.io/crates/serde) `Serialize` and
+//!   `Deserialize` for the frame, error, filter, and netlink interface
+//!   types. CAN IDs are represented as hex strings, like `"0x123"`, in
+//!   human-readable formats, and as integers in compact ones.
+//!
+//! * **dbc** -
+//!   Whether to include the parser for Vector DBC database files, with
+//!   decoding of frames and code generation for typed messages.
+//!
+//! * **isotp** -
+//!   Whether to include a userspace implementation of the ISO-TP
+//!   (ISO 15765-2) transport protocol, which doesn't need the kernel's
+//!   `can-isotp` module.
+//!
+//! * **uds** -
+//!   Whether to include the UDS (ISO 14229) diagnostic protocol over
+//!   ISO-TP. This enables the `isotp` feature.
+//!
+//! * **obd** -
+//!   Whether to include the OBD-II (SAE J1979) query client over ISO-TP.
+//!   This enables the `isotp` feature.
+//!
+//! * **j1939** -
+//!   Whether to include the userspace SAE J1939 stack, with the address
+//!   claim and the transport protocols, over a raw CAN socket.
+//!
+//! * **nmea2000** -
+//!   Whether to include the NMEA 2000 fast-packet protocol. This enables
+//!   the `j1939` feature.
+//!
+//! * **canopen** -
+//!   Whether to include the CANopen (CiA 301) NMT master, with the
+//!   heartbeat consumer and node guarding, the SDO client and server, PDOs
+//!   and SYNC, and EDS/DCF parsing.
+//!
+//! * **xcp** -
+//!   Whether to include the XCP-on-CAN master, for measurement and
+//!   calibration with direct memory access and DAQ lists.
+//!
+//! * **slcan** -
+//!   Whether to include the userspace driver for slcan (Lawicel)
+//!   serial-line CAN adapters, which reads and writes frames over the
+//!   adapter's serial tty. With the `netlink` feature, it can also attach
+//!   the tty to the kernel slcan line discipline, like `slcand`.
+//!
+//! * **socketcand** -
+//!   Whether to include the socketcand network protocol, with a server
+//!   that exposes the local CAN interfaces over TCP, its discovery
+//!   beacons, and a client for remote buses.
+//!
 //! * **tokio** -
 //!   Include support for async/await using [tokio](https://crates.io/crates/tokio).
 //!
@@ -103,7 +159,7 @@
     unsafe_op_in_unsafe_fn
 )]
 
-use std::io::ErrorKind;
+use std::{io::ErrorKind, mem::size_of};
 
 // Re-export the embedded_can crate so that applications can rely on
 // finding the same version we use.
@@ -114,8 +170,8 @@ pub use embedded_can::{
 
 pub mod errors;
 pub use errors::{
-    CanError, CanErrorDecodingFailure, ConstructionError, Error, IoError, IoErrorKind, IoResult,
-    Result,
+    CanError, CanErrorDecodingFailure, ConstructionError, Error, FrameParseError, IoError,
+    IoErrorKind, IoResult, Result, SignalError,
 };
 
 pub mod addr;
@@ -127,12 +183,47 @@ pub use frame::{
     Frame,
 };
 
+pub mod signal;
+pub use signal::{ByteOrder, SignalSpec, ValueType};
+
 #[cfg(feature = "dump")]
 pub mod dump;
 
+#[cfg(feature = "dbc")]
+pub mod dbc;
+
+#[cfg(feature = "isotp")]
+pub mod isotp;
+
+#[cfg(feature = "uds")]
+pub mod uds;
+
+#[cfg(feature = "obd")]
+pub mod obd;
+
+#[cfg(feature = "j1939")]
+pub mod j1939;
+
+#[cfg(feature = "nmea2000")]
+pub mod nmea2000;
+
+#[cfg(feature = "canopen")]
+pub mod canopen;
+
+#[cfg(feature = "xcp")]
+pub mod xcp;
+
+#[cfg(feature = "slcan")]
+pub mod slcan;
+
+#[cfg(feature = "socketcand")]
+pub mod socketcand;
+
 pub mod socket;
 pub use socket::{CanFdSocket, CanFilter, CanSocket, ShouldRetry, Socket, SocketOptions};
 
+pub mod link;
+
 #[cfg(feature = "netlink")]
 pub mod nl;
 
@@ -166,13 +257,13 @@ pub mod async_std {
 /// Note that this should normally be unsafe, but since we're only
 /// using it internally for types sent to the kernel, it's OK.
 pub(crate) fn as_bytes<T: Sized>(val: &T) -> &[u8] {
-    let sz = std::mem::size_of::<T>();
+    let sz = size_of::<T>();
     unsafe { std::slice::from_raw_parts::<'_, u8>(val as *const _ as *const u8, sz) }
 }
 
 /// Gets a mutable byte slice for any sized variable.
 pub(crate) fn as_bytes_mut<T: Sized>(val: &mut T) -> &mut [u8] {
-    let sz = std::mem::size_of::<T>();
+    let sz = size_of::<T>();
     unsafe { std::slice::from_raw_parts_mut(val as *mut _ as *mut u8, sz) }
 }
 
@@ -233,7 +324,7 @@ impl embedded_can::nb::Can for CanSocket {
                     ErrorKind::WouldBlock => Err(nb::Error::WouldBlock),
                     // TODO: How to indicate buffer is full?
                     // ErrorKind::StorageFull => Ok(frame),
-                    _ => Err(crate::Error::from(err).into()),
+                    _ => Err(Error::from(err).into()),
                 }
             }
         }
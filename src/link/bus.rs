@@ -0,0 +1,168 @@
+// socketcan/src/link/bus.rs
+//
+// An in-process virtual CAN bus.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! An in-process virtual CAN bus.
+//!
+//! This connects any number of links within a single process, without a
+//! kernel CAN interface. It's mainly intended for testing the protocols
+//! that run over a [`Link`], like ISO-TP, where a `vcan` interface might
+//! not be available.
+//!
+//! ```
+//! use socketcan::{link::{Link, VirtualBus}, CanFrame, EmbeddedFrame, StandardId};
+//! use std::time::Duration;
+//!
+//! let bus = VirtualBus::new();
+//! let tester = bus.connect();
+//! let ecu = bus.connect();
+//!
+//! let frame = CanFrame::new(StandardId::new(0x7E0).unwrap(), &[0x02, 0x10, 0x01]).unwrap();
+//! tester.send(&frame.into()).unwrap();
+//! let frame = ecu.recv(Some(Duration::from_secs(1))).unwrap().unwrap();
+//! assert_eq!(frame.to_string(), "7E0#021001");
+//! ```
+
+use super::Link;
+use crate::CanAnyFrame;
+use std::{
+    io,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// The ports connected to a bus, by their ID
+type Ports = Vec<(usize, Sender<CanAnyFrame>)>;
+
+/// An in-process virtual CAN bus.
+///
+/// Every frame sent by one link is delivered to all of the other links on
+/// the bus, but not back to the sender. Clones of the bus refer to the same
+/// set of links.
+#[derive(Debug, Clone, Default)]
+pub struct VirtualBus {
+    ports: Arc<Mutex<(usize, Ports)>>,
+}
+
+impl VirtualBus {
+    /// Creates a new, empty bus.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Connects a new link to the bus.
+    pub fn connect(&self) -> VirtualLink {
+        let (tx, rx) = mpsc::channel();
+        let mut ports = self.ports.lock().unwrap();
+        let id = ports.0;
+        ports.0 += 1;
+        ports.1.push((id, tx));
+
+        VirtualLink {
+            id,
+            bus: self.clone(),
+            rx: Mutex::new(rx),
+        }
+    }
+
+    /// Gets the number of links connected to the bus.
+    pub fn len(&self) -> usize {
+        self.ports.lock().unwrap().1.len()
+    }
+
+    /// Determines if there are no links connected to the bus.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A connection to an in-process [`VirtualBus`].
+///
+/// The link is removed from the bus when it's dropped. It can be shared
+/// between threads, like a socket.
+#[derive(Debug)]
+pub struct VirtualLink {
+    id: usize,
+    bus: VirtualBus,
+    rx: Mutex<Receiver<CanAnyFrame>>,
+}
+
+impl VirtualLink {
+    /// Gets the bus that the link is connected to.
+    pub fn bus(&self) -> &VirtualBus {
+        &self.bus
+    }
+}
+
+impl Link for VirtualLink {
+    fn send(&self, frame: &CanAnyFrame) -> io::Result<()> {
+        let ports = self.bus.ports.lock().unwrap();
+        for (_, tx) in ports.1.iter().filter(|(id, _)| *id != self.id) {
+            // A link might be dropping on another thread
+            let _ = tx.send(*frame);
+        }
+        Ok(())
+    }
+
+    fn recv(&self, timeout: Option<Duration>) -> io::Result<Option<CanAnyFrame>> {
+        // The bus holds our own sender, so the channel can't disconnect
+        let rx = self.rx.lock().unwrap();
+        match timeout {
+            Some(timeout) => match rx.recv_timeout(timeout) {
+                Ok(frame) => Ok(Some(frame)),
+                Err(RecvTimeoutError::Timeout) => Ok(None),
+                Err(RecvTimeoutError::Disconnected) => Err(io::ErrorKind::NotConnected.into()),
+            },
+            None => rx
+                .recv()
+                .map(Some)
+                .map_err(|_| io::ErrorKind::NotConnected.into()),
+        }
+    }
+}
+
+impl Drop for VirtualLink {
+    fn drop(&mut self) {
+        if let Ok(mut ports) = self.bus.ports.lock() {
+            ports.1.retain(|(id, _)| *id != self.id);
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CanFrame, EmbeddedFrame, StandardId};
+
+    #[test]
+    fn test_bus() {
+        let bus = VirtualBus::new();
+        let a = bus.connect();
+        let b = bus.connect();
+        let c = bus.connect();
+        assert_eq!(bus.len(), 3);
+
+        let frame = CanFrame::new(StandardId::new(0x100).unwrap(), &[1, 2]).unwrap();
+        a.send(&frame.into()).unwrap();
+
+        let timeout = Some(Duration::from_millis(10));
+        assert!(a.recv(timeout).unwrap().is_none());
+        assert!(b.recv(timeout).unwrap().is_some());
+        assert!(c.recv(timeout).unwrap().is_some());
+
+        drop(c);
+        assert_eq!(bus.len(), 2);
+    }
+}
@@ -0,0 +1,96 @@
+// socketcan/src/link/mod.rs
+//
+// A CAN link that the userspace protocols run over.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! A CAN link that the userspace protocols run over.
+//!
+//! The [`Link`] trait is the minimal interface to send and receive single
+//! frames that the userspace protocols need, like ISO-TP, UDS, and XCP.
+//! It's implemented for the raw [`CanSocket`] and [`CanFdSocket`], for the
+//! in-process [`VirtualBus`], and for the slcan and socketcand drivers, so
+//! each protocol runs over any of them.
+//!
+//! An asynchronous version for tokio is in the [`tokio`](self::tokio)
+//! submodule, when the `tokio` feature is enabled.
+
+use crate::{CanAnyFrame, CanFdSocket, CanFrame, CanSocket, Socket};
+use std::{io, time::Duration};
+
+pub mod bus;
+pub use bus::{VirtualBus, VirtualLink};
+
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+/// A CAN link that sends and receives single frames.
+///
+/// This is implemented for the raw CAN sockets and for links on an
+/// in-process [`VirtualBus`].
+pub trait Link {
+    /// Sends a single frame.
+    fn send(&self, frame: &CanAnyFrame) -> io::Result<()>;
+
+    /// Receives a single frame.
+    ///
+    /// This blocks until a frame arrives, or until the timeout expires, in
+    /// which case it returns `None`.
+    fn recv(&self, timeout: Option<Duration>) -> io::Result<Option<CanAnyFrame>>;
+}
+
+/// Reads a frame from a socket, mapping a timeout to `None`.
+fn read_socket<S: Socket>(sock: &S, timeout: Option<Duration>) -> io::Result<Option<S::FrameType>> {
+    // The socket's timeout has millisecond resolution
+    let res = match timeout {
+        Some(timeout) => sock.read_frame_timeout(timeout.max(Duration::from_millis(1))),
+        None => sock.read_frame(),
+    };
+    match res {
+        Ok(frame) => Ok(Some(frame)),
+        Err(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+impl Link for CanSocket {
+    /// Sends a single frame.
+    ///
+    /// A classic CAN socket can't send CAN FD frames, so this fails with
+    /// an `InvalidInput` error for them.
+    fn send(&self, frame: &CanAnyFrame) -> io::Result<()> {
+        let frame = match *frame {
+            CanAnyFrame::Normal(frame) => CanFrame::Data(frame),
+            CanAnyFrame::Remote(frame) => CanFrame::Remote(frame),
+            CanAnyFrame::Error(frame) => CanFrame::Error(frame),
+            CanAnyFrame::Fd(_) => return Err(io::ErrorKind::InvalidInput.into()),
+        };
+        self.write_frame_insist(&frame)
+    }
+
+    fn recv(&self, timeout: Option<Duration>) -> io::Result<Option<CanAnyFrame>> {
+        read_socket(self, timeout).map(|frame| frame.map(CanAnyFrame::from))
+    }
+}
+
+impl Link for CanFdSocket {
+    fn send(&self, frame: &CanAnyFrame) -> io::Result<()> {
+        self.write_frame_insist(frame)
+    }
+
+    fn recv(&self, timeout: Option<Duration>) -> io::Result<Option<CanAnyFrame>> {
+        read_socket(self, timeout)
+    }
+}
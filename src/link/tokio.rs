@@ -0,0 +1,90 @@
+// socketcan/src/link/tokio.rs
+//
+// An asynchronous CAN link for tokio.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! An asynchronous CAN link for tokio.
+//!
+//! This is the asynchronous version of the [`Link`](super::Link), that the
+//! tokio versions of the userspace protocols run over.
+
+use crate::{
+    tokio::{CanFdSocket, CanSocket},
+    CanAnyFrame, CanFdFrame, CanFrame,
+};
+use futures::{future::BoxFuture, SinkExt, StreamExt};
+use std::io;
+
+/// Converts an error from the tokio CAN sockets into an I/O error.
+fn io_error(err: crate::Error) -> io::Error {
+    match err {
+        crate::Error::Io(err) => err,
+        crate::Error::Can(err) => io::Error::new(io::ErrorKind::Other, err),
+    }
+}
+
+/// An asynchronous CAN link that sends and receives single frames.
+///
+/// This is implemented for the tokio CAN sockets.
+pub trait Link: Send {
+    /// Sends a single frame.
+    fn send(&mut self, frame: CanAnyFrame) -> BoxFuture<'_, io::Result<()>>;
+
+    /// Receives a single frame.
+    fn recv(&mut self) -> BoxFuture<'_, io::Result<CanAnyFrame>>;
+}
+
+impl Link for CanSocket {
+    /// Sends a single frame.
+    ///
+    /// A classic CAN socket can't send CAN FD frames, so this fails with
+    /// an `InvalidInput` error for them.
+    fn send(&mut self, frame: CanAnyFrame) -> BoxFuture<'_, io::Result<()>> {
+        Box::pin(async move {
+            let frame = match frame {
+                CanAnyFrame::Normal(frame) => CanFrame::Data(frame),
+                CanAnyFrame::Remote(frame) => CanFrame::Remote(frame),
+                CanAnyFrame::Error(frame) => CanFrame::Error(frame),
+                CanAnyFrame::Fd(_) => return Err(io::ErrorKind::InvalidInput.into()),
+            };
+            SinkExt::send(self, frame).await.map_err(io_error)
+        })
+    }
+
+    fn recv(&mut self) -> BoxFuture<'_, io::Result<CanAnyFrame>> {
+        Box::pin(async move {
+            match self.next().await {
+                Some(res) => res.map(CanAnyFrame::from).map_err(io_error),
+                None => Err(io::ErrorKind::UnexpectedEof.into()),
+            }
+        })
+    }
+}
+
+impl Link for CanFdSocket {
+    fn send(&mut self, frame: CanAnyFrame) -> BoxFuture<'_, io::Result<()>> {
+        Box::pin(async move {
+            let frame = match frame {
+                CanAnyFrame::Normal(frame) => CanFdFrame::from(frame),
+                CanAnyFrame::Fd(frame) => frame,
+                _ => return Err(io::ErrorKind::InvalidInput.into()),
+            };
+            SinkExt::send(self, frame).await.map_err(io_error)
+        })
+    }
+
+    fn recv(&mut self) -> BoxFuture<'_, io::Result<CanAnyFrame>> {
+        Box::pin(async move {
+            match self.next().await {
+                Some(res) => res.map_err(io_error),
+                None => Err(io::ErrorKind::UnexpectedEof.into()),
+            }
+        })
+    }
+}
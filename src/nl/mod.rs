@@ -100,6 +100,7 @@ pub type CanBerrCounter = rt::can_berr_counter;
 /// `CanInterface::details()` function.
 #[allow(missing_copy_implementations)]
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InterfaceDetails {
     /// The name of the interface
     pub name: Option<String>,
@@ -126,6 +127,7 @@ impl InterfaceDetails {
 /// The MTU size for the interface
 ///
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u32)]
 pub enum Mtu {
     /// Standard CAN frame, 8-byte data (16-byte total)
@@ -149,6 +151,7 @@ impl TryFrom<u32> for Mtu {
 /// The CAN-specific parameters for the interface.
 #[allow(missing_copy_implementations)]
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InterfaceCanParams {
     /// The CAN bit timing parameters
     pub bit_timing: Option<CanBitTiming>,
@@ -234,6 +237,7 @@ impl TryFrom<&Rtattr<Ifla, Buffer>> for InterfaceCanParams {
 /// Note that these correspond to the bit _numbers_ for the control mode bits.
 #[repr(u32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CanCtrlMode {
     /// Loopback mode
     Loopback,
@@ -264,6 +268,7 @@ impl CanCtrlMode {
 
 /// The collection of control modes
 #[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CanCtrlModes(can_ctrlmode);
 
 impl CanCtrlModes {
@@ -471,6 +476,25 @@ impl CanInterface {
         Self::send_info_msg(Rtm::Newlink, info, &[])
     }
 
+    /// Renames the interface.
+    ///
+    /// The interface must be down. Note that the length of the name is
+    /// capped by ```libc::IFNAMSIZ```, including the terminating null.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    ///
+    pub fn set_name(&self, name: &str) -> NlResult<()> {
+        if name.len() >= libc::IFNAMSIZ {
+            return Err(NlError::Msg("Interface name too long".into()));
+        }
+        let info = self.info_msg({
+            let mut buffer = RtBuffer::new();
+            buffer.push(Rtattr::new(None, Ifla::Ifname, name)?);
+            buffer
+        });
+        Self::send_info_msg(Rtm::Newlink, info, &[])
+    }
+
     /// Create a virtual CAN (VCAN) interface.
     ///
     /// Useful for testing applications when a physical CAN interface and
@@ -849,6 +873,7 @@ impl CanInterface {
 
 #[cfg(feature = "netlink_tests")]
 #[cfg(test)]
+/// Helpers for the tests that need a real network interface.
 pub mod tests {
     use super::*;
     use serial_test::serial;
@@ -875,6 +900,7 @@ pub mod tests {
     }
 
     impl TemporaryInterface {
+        /// Creates a vcan interface with the name.
         #[allow(unused)]
         pub fn new(name: &str) -> NlResult<Self> {
             Ok(Self {
@@ -27,7 +27,7 @@ use neli::{
 };
 use std::{
     io::{self, Cursor, Read, Write},
-    mem,
+    mem::{self, size_of},
 };
 
 pub const EXT_FILTER_VF: c_uint = 1 << 0;
@@ -47,6 +47,7 @@ pub const EXT_FILTER_MST: c_uint = 1 << 7;
 ///
 #[repr(C)]
 #[derive(Debug, Default, Clone, Copy, FromBytes, ToBytes, Size)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct can_bittiming {
     pub bitrate: u32,      // Bit-rate in bits/second
     pub sample_point: u32, // Sample point in one-tenth of a percent
@@ -65,6 +66,7 @@ pub struct can_bittiming {
 ///
 #[repr(C)]
 #[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct can_bittiming_const {
     pub name: [c_char; 16], // Name of the CAN controller hardware
     pub tseg1_min: u32,     // Time segment 1 = prop_seg + phase_seg1
@@ -94,7 +96,7 @@ impl<'a> FromBytes<'a> for can_bittiming_const {
 
 impl Size for can_bittiming_const {
     fn unpadded_size(&self) -> usize {
-        std::mem::size_of::<can_bittiming_const>()
+        size_of::<can_bittiming_const>()
     }
 }
 
@@ -102,6 +104,7 @@ impl Size for can_bittiming_const {
 ///
 #[repr(C)]
 #[derive(Debug, Default, Clone, Copy, FromBytes, ToBytes, Size)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct can_clock {
     pub freq: u32, // CAN system clock frequency in Hz
 }
@@ -110,6 +113,7 @@ pub struct can_clock {
 ///
 #[repr(u32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CanState {
     /// RX/TX error count < 96
     ErrorActive,
@@ -147,6 +151,7 @@ impl TryFrom<u32> for CanState {
 ///
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone, FromBytes, ToBytes, Size)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct can_berr_counter {
     pub txerr: u16,
     pub rxerr: u16,
@@ -159,6 +164,7 @@ pub struct can_berr_counter {
 ///
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone, FromBytes, ToBytes, Size)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct can_ctrlmode {
     pub mask: u32,
     pub flags: u32,
@@ -265,7 +271,7 @@ pub mod tests {
             unsafe {
                 std::slice::from_raw_parts::<'_, u8>(
                     &timing as *const _ as *const u8,
-                    std::mem::size_of::<can_bittiming>(),
+                    size_of::<can_bittiming>(),
                 )
             },
             as_bytes(&timing)
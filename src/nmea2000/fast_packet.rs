@@ -0,0 +1,308 @@
+// socketcan/src/nmea2000/fast_packet.rs
+//
+// The NMEA 2000 fast-packet protocol.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! Fragmentation and reassembly of NMEA 2000 fast-packet messages.
+
+use super::{is_fast_packet, Error, Message, Pgn, MAX_FAST_PACKET_LEN};
+use crate::{
+    j1939::{frame, J1939Id},
+    CanFrame, EmbeddedFrame,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{Duration, Instant},
+};
+
+/// The default time allowed for all the frames of a message to arrive.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(750);
+
+/// The data bytes in the first frame of a message
+const FIRST_LEN: usize = 6;
+
+/// The data bytes in the other frames
+const NEXT_LEN: usize = 7;
+
+/// Splits a message into fast-packet frames.
+///
+/// The sequence counter (0 - 7) should be different for each message that
+/// a node sends with the same PGN. The last frame is padded with 0xFF.
+pub fn fragment(id: J1939Id, seq: u8, data: &[u8]) -> Result<Vec<CanFrame>, Error> {
+    if data.len() > MAX_FAST_PACKET_LEN {
+        return Err(Error::InvalidLength(data.len()));
+    }
+    let seq = (seq & 0x07) << 5;
+    let split = data.len().min(FIRST_LEN);
+
+    let mut first = vec![seq, data.len() as u8];
+    first.extend_from_slice(&data[..split]);
+    let mut frames = vec![first];
+
+    for (i, chunk) in data[split..].chunks(NEXT_LEN).enumerate() {
+        let mut buf = vec![seq | (i as u8 + 1)];
+        buf.extend_from_slice(chunk);
+        frames.push(buf);
+    }
+
+    Ok(frames
+        .into_iter()
+        .map(|mut buf| {
+            buf.resize(8, 0xFF);
+            frame(id, &buf)
+        })
+        .collect())
+}
+
+/// A message being reassembled
+#[derive(Debug)]
+struct Partial {
+    seq: u8,
+    next: u8,
+    len: usize,
+    data: Vec<u8>,
+    started: Instant,
+}
+
+/// Reassembles fast-packet messages from the frames of any number of
+/// nodes.
+///
+/// Messages are reassembled for each source address and PGN. A message is
+/// dropped if a frame is lost, or if the rest of its frames don't arrive
+/// within the timeout. Frames with PGNs that aren't fast-packet are
+/// passed through as messages.
+#[derive(Debug)]
+pub struct Reassembler {
+    timeout: Duration,
+    extra_pgns: BTreeSet<Pgn>,
+    partial: BTreeMap<(u8, Pgn), Partial>,
+}
+
+impl Default for Reassembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Reassembler {
+    /// Creates a reassembler for the standard fast-packet PGNs, with the
+    /// default timeout.
+    pub fn new() -> Self {
+        Self {
+            timeout: DEFAULT_TIMEOUT,
+            extra_pgns: BTreeSet::new(),
+            partial: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the time allowed for all the frames of a message to arrive.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Adds a PGN to reassemble as fast-packet, such as a proprietary one
+    /// outside of the standard range.
+    pub fn fast_packet_pgn(mut self, pgn: Pgn) -> Self {
+        self.extra_pgns.insert(pgn);
+        self
+    }
+
+    /// Determines if messages with the PGN are reassembled.
+    pub fn is_fast_packet(&self, pgn: Pgn) -> bool {
+        is_fast_packet(pgn) || self.extra_pgns.contains(&pgn)
+    }
+
+    /// Handles a received frame, returning a message when one is
+    /// complete.
+    pub fn handle_frame(&mut self, frame: &CanFrame, now: Instant) -> Option<Message> {
+        let id = J1939Id::from_frame(frame)?;
+        self.handle(&Message::new(id, frame.data()), now)
+    }
+
+    /// Handles a single frame message, returning a message when one is
+    /// complete.
+    ///
+    /// This is for messages from a [`J1939Socket`](crate::j1939::J1939Socket).
+    /// Messages with PGNs that aren't fast-packet are returned as they
+    /// are.
+    pub fn handle(&mut self, msg: &Message, now: Instant) -> Option<Message> {
+        self.expire(now);
+        if !self.is_fast_packet(msg.id.pgn) {
+            return Some(msg.clone());
+        }
+
+        let data = &msg.data;
+        if data.is_empty() {
+            return None;
+        }
+        let key = (msg.id.source, msg.id.pgn);
+        let seq = data[0] >> 5;
+        let counter = data[0] & 0x1F;
+
+        let partial = if counter == 0 {
+            if data.len() < 2 {
+                return None;
+            }
+            let len = usize::from(data[1]).min(MAX_FAST_PACKET_LEN);
+            let end = data.len().min(2 + FIRST_LEN);
+            self.partial.insert(
+                key,
+                Partial {
+                    seq,
+                    next: 1,
+                    len,
+                    data: data[2..end].to_vec(),
+                    started: now,
+                },
+            );
+            self.partial.get_mut(&key)?
+        } else {
+            match self.partial.get_mut(&key) {
+                Some(p) if p.seq == seq && p.next == counter => {
+                    p.next += 1;
+                    p.data.extend_from_slice(&data[1..]);
+                    p
+                }
+                Some(_) => {
+                    log::debug!("Lost fast-packet frame from 0x{:02X}", msg.id.source);
+                    self.partial.remove(&key);
+                    return None;
+                }
+                None => return None,
+            }
+        };
+
+        if partial.data.len() < partial.len {
+            return None;
+        }
+        let mut partial = self.partial.remove(&key)?;
+        partial.data.truncate(partial.len);
+        Some(Message::new(msg.id, partial.data))
+    }
+
+    /// Drops the messages that timed out.
+    pub fn expire(&mut self, now: Instant) {
+        let timeout = self.timeout;
+        self.partial
+            .retain(|_, p| now.saturating_duration_since(p.started) < timeout);
+    }
+
+    /// Gets the number of messages being reassembled.
+    pub fn pending(&self) -> usize {
+        self.partial.len()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        j1939::GLOBAL_ADDRESS,
+        nmea2000::pgn::{ENGINE_RAPID, GNSS_POSITION_DATA},
+    };
+
+    fn id(source: u8) -> J1939Id {
+        J1939Id::new(3, GNSS_POSITION_DATA, source, GLOBAL_ADDRESS)
+    }
+
+    #[test]
+    fn test_fragment() {
+        let data: Vec<u8> = (0..43).collect();
+        let frames = fragment(id(0x10), 2, &data).unwrap();
+        assert_eq!(frames.len(), 7);
+        assert_eq!(frames[0].data(), &[0x40, 43, 0, 1, 2, 3, 4, 5]);
+        assert_eq!(frames[1].data(), &[0x41, 6, 7, 8, 9, 10, 11, 12]);
+        assert_eq!(
+            frames[6].data(),
+            &[0x46, 41, 42, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]
+        );
+
+        // 6 + 31 * 7
+        let frames = fragment(id(0x10), 0, &[0; MAX_FAST_PACKET_LEN]).unwrap();
+        assert_eq!(frames.len(), 32);
+        assert_eq!(frames[31].data()[0], 31);
+        assert!(matches!(
+            fragment(id(0x10), 0, &[0; 224]),
+            Err(Error::InvalidLength(224))
+        ));
+    }
+
+    #[test]
+    fn test_reassemble() {
+        let now = Instant::now();
+        let mut reasm = Reassembler::new();
+
+        let data1: Vec<u8> = (0..43).collect();
+        let data2 = vec![0x55; 20];
+        let frames1 = fragment(id(0x10), 1, &data1).unwrap();
+        let frames2 = fragment(id(0x20), 1, &data2).unwrap();
+
+        // Interleaved messages from two nodes
+        let mut msgs = Vec::new();
+        for (i, frame) in frames1.iter().enumerate() {
+            msgs.extend(reasm.handle_frame(frame, now));
+            if let Some(frame) = frames2.get(i) {
+                msgs.extend(reasm.handle_frame(frame, now));
+            }
+        }
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0], Message::new(id(0x20), data2));
+        assert_eq!(msgs[1], Message::new(id(0x10), data1));
+        assert_eq!(reasm.pending(), 0);
+
+        // Single frame PGNs pass through
+        let rapid = J1939Id::new(2, ENGINE_RAPID, 0x30, GLOBAL_ADDRESS);
+        let msg = reasm.handle_frame(&frame(rapid, &[1, 2, 3, 4, 5, 6, 7, 8]), now);
+        assert_eq!(msg.unwrap().data, &[1, 2, 3, 4, 5, 6, 7, 8]);
+
+        // Short messages fit in the first frame
+        let frames = fragment(id(0x10), 3, &[9, 8, 7]).unwrap();
+        let msg = reasm.handle_frame(&frames[0], now).unwrap();
+        assert_eq!(msg.data, &[9, 8, 7]);
+    }
+
+    #[test]
+    fn test_lost_frames() {
+        let now = Instant::now();
+        let mut reasm = Reassembler::new().timeout(Duration::from_millis(100));
+        let frames = fragment(id(0x10), 4, &[0xAA; 30]).unwrap();
+
+        // A missing frame drops the message
+        assert!(reasm.handle_frame(&frames[0], now).is_none());
+        assert!(reasm.handle_frame(&frames[2], now).is_none());
+        assert_eq!(reasm.pending(), 0);
+        assert!(reasm.handle_frame(&frames[3], now).is_none());
+
+        // So does a timeout
+        for frame in &frames[..3] {
+            assert!(reasm.handle_frame(frame, now).is_none());
+        }
+        assert_eq!(reasm.pending(), 1);
+        let later = now + Duration::from_millis(100);
+        assert!(reasm.handle_frame(&frames[3], later).is_none());
+        assert_eq!(reasm.pending(), 0);
+
+        // A new sequence restarts the message
+        let frames2 = fragment(id(0x10), 5, &[0xBB; 10]).unwrap();
+        reasm.handle_frame(&frames[0], now);
+        reasm.handle_frame(&frames2[0], now);
+        let msg = reasm.handle_frame(&frames2[1], now).unwrap();
+        assert_eq!(msg.data, &[0xBB; 10]);
+
+        // Proprietary PGNs can be added
+        let pgn = Pgn(0xEF00);
+        let mut reasm = Reassembler::new().fast_packet_pgn(pgn);
+        let frames = fragment(J1939Id::new(6, pgn, 0x10, 0x20), 0, &[1; 12]).unwrap();
+        assert!(reasm.handle_frame(&frames[0], now).is_none());
+        assert_eq!(reasm.handle_frame(&frames[1], now).unwrap().data, &[1; 12]);
+    }
+}
@@ -0,0 +1,138 @@
+// socketcan/src/nmea2000/mod.rs
+//
+// NMEA 2000 over J1939.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! NMEA 2000, the marine network built on J1939.
+//!
+//! NMEA 2000 uses the J1939 29-bit IDs, address claim, and transport
+//! protocol, so the [`J1939Id`](crate::j1939::J1939Id), [`Pgn`], and
+//! [`Message`] types from the [`j1939`](crate::j1939) module are used
+//! here too. What it adds is the _fast-packet_ protocol, which sends
+//! messages of up to 223 bytes as a burst of up to 32 frames, without the
+//! handshake of the J1939 transport protocol.
+//!
+//! Each fast-packet frame starts with a byte holding a 3-bit sequence
+//! counter, which changes with each message, and a 5-bit frame counter.
+//! The first frame also has the total length, and six bytes of data,
+//! while the rest have seven. Nothing in the frames says that they're
+//! fast-packet, so that depends on the PGN: see [`is_fast_packet()`].
+//!
+//! The [`Nmea2000Socket`] receives and sends messages over a raw
+//! [`CanSocket`](crate::CanSocket), reassembling the fast-packet
+//! messages:
+//!
+//! ```no_run
+//! use socketcan::nmea2000::{Nmea2000Socket, pgn};
+//!
+//! let mut sock = Nmea2000Socket::open("can0").unwrap();
+//! loop {
+//!     let msg = sock.recv().unwrap();
+//!     if msg.id.pgn == pgn::GNSS_POSITION_DATA {
+//!         println!("GNSS position from 0x{:02X}: {:02X?}", msg.id.source, msg.data);
+//!     }
+//! }
+//! ```
+//!
+//! To also claim an address, the [`Reassembler`] can be fed the single
+//! frame messages from a [`J1939Socket`](crate::j1939::J1939Socket), and
+//! [`fragment()`] splits a message into the frames to send.
+
+pub use crate::j1939::{Error, Message, Pgn};
+
+pub mod fast_packet;
+pub use fast_packet::{fragment, Reassembler};
+
+pub mod socket;
+pub use socket::Nmea2000Socket;
+
+/// The maximum length of a fast-packet message.
+pub const MAX_FAST_PACKET_LEN: usize = 223;
+
+/// Some of the common NMEA 2000 PGNs.
+pub mod pgn {
+    use super::Pgn;
+
+    /// System time (single frame)
+    pub const SYSTEM_TIME: Pgn = Pgn(126992);
+    /// Product information (fast-packet)
+    pub const PRODUCT_INFORMATION: Pgn = Pgn(126996);
+    /// Vessel heading (single frame)
+    pub const VESSEL_HEADING: Pgn = Pgn(127250);
+    /// Rate of turn (single frame)
+    pub const RATE_OF_TURN: Pgn = Pgn(127251);
+    /// Engine parameters, rapid update (single frame)
+    pub const ENGINE_RAPID: Pgn = Pgn(127488);
+    /// Engine parameters, dynamic (fast-packet)
+    pub const ENGINE_DYNAMIC: Pgn = Pgn(127489);
+    /// Transmission parameters, dynamic (single frame)
+    pub const TRANSMISSION_DYNAMIC: Pgn = Pgn(127493);
+    /// Trip parameters, engine (fast-packet)
+    pub const TRIP_ENGINE: Pgn = Pgn(127497);
+    /// Fluid level (single frame)
+    pub const FLUID_LEVEL: Pgn = Pgn(127505);
+    /// Battery status (single frame)
+    pub const BATTERY_STATUS: Pgn = Pgn(127508);
+    /// Speed, water referenced (single frame)
+    pub const SPEED: Pgn = Pgn(128259);
+    /// Water depth (single frame)
+    pub const WATER_DEPTH: Pgn = Pgn(128267);
+    /// Position, rapid update (single frame)
+    pub const POSITION_RAPID: Pgn = Pgn(129025);
+    /// COG and SOG, rapid update (single frame)
+    pub const COG_SOG_RAPID: Pgn = Pgn(129026);
+    /// GNSS position data (fast-packet)
+    pub const GNSS_POSITION_DATA: Pgn = Pgn(129029);
+    /// GNSS DOPs (single frame)
+    pub const GNSS_DOPS: Pgn = Pgn(129539);
+    /// GNSS satellites in view (fast-packet)
+    pub const GNSS_SATELLITES: Pgn = Pgn(129540);
+    /// Wind data (single frame)
+    pub const WIND_DATA: Pgn = Pgn(130306);
+    /// Environmental parameters (single frame)
+    pub const ENVIRONMENTAL: Pgn = Pgn(130311);
+}
+
+/// The standard PGNs that are sent with the fast-packet protocol.
+///
+/// These are the group function, alert, product information, engine,
+/// electrical, navigation, GNSS, AIS, route, and environmental PGNs with
+/// more than eight bytes of data.
+pub const FAST_PACKET_PGNS: &[u32] = &[
+    126208, 126464, 126720, 126983, 126984, 126985, 126986, 126987, 126988, 126996, 126998, 127233,
+    127237, 127489, 127496, 127497, 127498, 127503, 127504, 127506, 127507, 127509, 128275, 128520,
+    129029, 129038, 129039, 129040, 129041, 129044, 129045, 129284, 129285, 129540, 129541, 129542,
+    129545, 129547, 129793, 129794, 129795, 129796, 129797, 129798, 129801, 129802, 129803, 129804,
+    129805, 129806, 129807, 129808, 129809, 129810, 130052, 130053, 130054, 130060, 130061, 130064,
+    130065, 130066, 130067, 130068, 130069, 130070, 130071, 130072, 130073, 130074, 130320, 130321,
+    130322, 130323, 130324, 130567, 130577, 130578,
+];
+
+/// Determines if the standard PGN is sent with the fast-packet protocol.
+///
+/// The proprietary fast-packet range (0x1FF00 - 0x1FFFF) is included.
+pub fn is_fast_packet(pgn: Pgn) -> bool {
+    (0x1FF00..=0x1FFFF).contains(&pgn.0) || FAST_PACKET_PGNS.contains(&pgn.0)
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_fast_packet() {
+        assert!(is_fast_packet(pgn::GNSS_POSITION_DATA));
+        assert!(is_fast_packet(pgn::ENGINE_DYNAMIC));
+        assert!(is_fast_packet(Pgn(0x1FF10)));
+        assert!(!is_fast_packet(pgn::ENGINE_RAPID));
+        assert!(!is_fast_packet(pgn::POSITION_RAPID));
+    }
+}
@@ -0,0 +1,126 @@
+// socketcan/src/nmea2000/socket.rs
+//
+// A blocking NMEA 2000 socket.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! A blocking NMEA 2000 socket over a raw CAN socket.
+
+use super::{fragment, Error, Message, Pgn, Reassembler};
+use crate::{
+    frame::CAN_EFF_FLAG,
+    j1939::{frame, socket::read_socket, J1939Id},
+    CanFilter, CanSocket, Socket, SocketOptions,
+};
+use std::{
+    collections::BTreeMap,
+    io,
+    time::{Duration, Instant},
+};
+
+/// An NMEA 2000 socket, which reassembles and fragments the fast-packet
+/// messages.
+///
+/// This doesn't claim an address, so it's meant for listening to the
+/// bus, or for sending from an address claimed by other means.
+#[derive(Debug)]
+pub struct Nmea2000Socket {
+    sock: CanSocket,
+    reasm: Reassembler,
+    /// The next fast-packet sequence counter for each PGN
+    seqs: BTreeMap<Pgn, u8>,
+}
+
+impl Nmea2000Socket {
+    /// Opens a socket on the named CAN interface.
+    ///
+    /// The socket only receives frames with extended IDs.
+    pub fn open(ifname: &str) -> io::Result<Self> {
+        let sock = CanSocket::open(ifname)?;
+        sock.set_filters(&[CanFilter::new(CAN_EFF_FLAG, CAN_EFF_FLAG)])?;
+        Ok(Self::new(sock))
+    }
+
+    /// Creates an NMEA 2000 socket from an open CAN socket.
+    pub fn new(sock: CanSocket) -> Self {
+        Self {
+            sock,
+            reasm: Reassembler::new(),
+            seqs: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the reassembler, to change the timeout or the fast-packet
+    /// PGNs.
+    pub fn reassembler(mut self, reasm: Reassembler) -> Self {
+        self.reasm = reasm;
+        self
+    }
+
+    /// Gets a reference to the underlying CAN socket.
+    pub fn socket(&self) -> &CanSocket {
+        &self.sock
+    }
+
+    /// Receives the next message, blocking until one arrives.
+    pub fn recv(&mut self) -> Result<Message, Error> {
+        loop {
+            if let Some(msg) = self.recv_inner(None)? {
+                return Ok(msg);
+            }
+        }
+    }
+
+    /// Receives the next message, waiting up to the timeout.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<Message>, Error> {
+        self.recv_inner(Some(Instant::now() + timeout))
+    }
+
+    fn recv_inner(&mut self, deadline: Option<Instant>) -> Result<Option<Message>, Error> {
+        loop {
+            let timeout = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if deadline <= now {
+                        return Ok(None);
+                    }
+                    Some(deadline - now)
+                }
+                None => None,
+            };
+            if let Some(frame) = read_socket(&self.sock, timeout)? {
+                if let Some(msg) = self.reasm.handle_frame(&frame, Instant::now()) {
+                    return Ok(Some(msg));
+                }
+            }
+        }
+    }
+
+    /// Sends a message.
+    ///
+    /// Messages with fast-packet PGNs are fragmented, with a sequence
+    /// counter that's incremented for each message of the PGN. Others must
+    /// fit in a single frame.
+    pub fn send(&mut self, id: J1939Id, data: &[u8]) -> Result<(), Error> {
+        if !self.reasm.is_fast_packet(id.pgn) {
+            if data.len() > 8 {
+                return Err(Error::InvalidLength(data.len()));
+            }
+            self.sock.write_frame_insist(&frame(id, data))?;
+            return Ok(());
+        }
+
+        let seq = self.seqs.entry(id.pgn).or_insert(0);
+        let frames = fragment(id, *seq, data)?;
+        *seq = (*seq + 1) & 0x07;
+        for frame in frames {
+            self.sock.write_frame_insist(&frame)?;
+        }
+        Ok(())
+    }
+}
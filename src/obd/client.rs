@@ -0,0 +1,584 @@
+// socketcan/src/obd/client.rs
+//
+// The OBD-II query client.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! The OBD-II query client, or scan tool.
+//!
+//! Since the responses to a functional request come from any number of
+//! ECUs, each on its own response ID, the [`Client`] runs the receiving side
+//! of ISO-TP for each responding ECU itself, directly on a CAN [`Link`],
+//! rather than over an [`IsoTpSocket`](crate::isotp::IsoTpSocket) bound to
+//! a single pair of IDs.
+
+use super::{mode, pid, Dtc, Error, Response, Value, INFO_VIN};
+use crate::{
+    isotp::{
+        self,
+        pdu::{Codec, FlowStatus, Pdu, Reassembler},
+        Link, Options,
+    },
+    CanAnyFrame, EmbeddedFrame, ExtendedId, Id, StandardId,
+};
+use std::time::{Duration, Instant};
+
+/// The default time to wait for responses
+const DEFAULT_P2: Duration = Duration::from_millis(100);
+
+/// The time to wait for a response after a "response pending"
+const P2_STAR: Duration = Duration::from_millis(5000);
+
+/// The time to wait for each consecutive frame of a response
+const N_CR: Duration = Duration::from_millis(1000);
+
+/// The largest response accepted
+const MAX_RESPONSE_LEN: usize = 4095;
+
+/// The negative response service ID
+const NEGATIVE_RESPONSE: u8 = 0x7F;
+
+/// The "response pending" negative response code
+const RESPONSE_PENDING: u8 = 0x78;
+
+/// The 11-bit functional request ID
+const FUNCTIONAL_ID: u16 = 0x7DF;
+
+/// The 29-bit functional request ID
+const FUNCTIONAL_EXT_ID: u32 = 0x18DB_33F1;
+
+/// The 29-bit response ID, without the ECU address
+const RESPONSE_EXT_ID: u32 = 0x18DA_F100;
+
+/// The 29-bit physical request ID, without the ECU address
+const REQUEST_EXT_ID: u32 = 0x18DA_00F1;
+
+/// A response being received from one ECU
+struct Rx {
+    ecu: Id,
+    codec: Codec,
+    msg: Option<Reassembler>,
+    cr_deadline: Instant,
+    // The time to wait for, after a "response pending"
+    pending: Option<Instant>,
+}
+
+impl Rx {
+    /// Gets the time to wait for the rest of the response, if it's still
+    /// in progress.
+    fn deadline(&self) -> Option<Instant> {
+        match self.msg {
+            Some(_) => Some(self.cr_deadline),
+            None => self.pending,
+        }
+    }
+}
+
+/// An OBD-II client, sending requests to the ECUs on a CAN link.
+///
+/// This uses 11-bit IDs by default, or 29-bit IDs if created with
+/// [`new_extended()`](Self::new_extended). The ECUs are identified by the
+/// CAN ID of their responses.
+#[derive(Debug)]
+pub struct Client<L> {
+    link: L,
+    extended: bool,
+    timeout: Duration,
+    padding: Option<u8>,
+}
+
+impl<L: Link> Client<L> {
+    /// Creates a client using 11-bit IDs.
+    pub fn new(link: L) -> Self {
+        Self {
+            link,
+            extended: false,
+            timeout: DEFAULT_P2,
+            padding: Some(0x00),
+        }
+    }
+
+    /// Creates a client using 29-bit IDs.
+    pub fn new_extended(link: L) -> Self {
+        Self {
+            extended: true,
+            ..Self::new(link)
+        }
+    }
+
+    /// Sets the time to wait for the responses to a request.
+    ///
+    /// The default is 100ms. The wait is extended when an ECU replies that
+    /// the response is pending.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the padding byte for the request frames.
+    ///
+    /// The requests are padded to eight bytes with zeros by default, as
+    /// ISO 15765-4 requires.
+    pub fn padding(mut self, padding: Option<u8>) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Gets a reference to the underlying link.
+    pub fn link(&self) -> &L {
+        &self.link
+    }
+
+    /// Consumes the client, returning the underlying link.
+    pub fn into_link(self) -> L {
+        self.link
+    }
+
+    /// Gets the functional request ID.
+    pub fn functional_id(&self) -> Id {
+        if self.extended {
+            ExtendedId::new(FUNCTIONAL_EXT_ID).unwrap().into()
+        } else {
+            StandardId::new(FUNCTIONAL_ID).unwrap().into()
+        }
+    }
+
+    /// Gets the physical request ID of the ECU with the response ID, if
+    /// it's an OBD-II response ID.
+    pub fn request_id(&self, ecu: Id) -> Option<Id> {
+        match ecu {
+            Id::Standard(id) if !self.extended && (0x7E8..=0x7EF).contains(&id.as_raw()) => {
+                StandardId::new(id.as_raw() - 8).map(Id::from)
+            }
+            Id::Extended(id) if self.extended && id.as_raw() & !0xFF == RESPONSE_EXT_ID => {
+                let addr = id.as_raw() & 0xFF;
+                ExtendedId::new(REQUEST_EXT_ID | (addr << 8)).map(Id::from)
+            }
+            _ => None,
+        }
+    }
+
+    // ----- Requests -----
+
+    /// Sends a functional request, returning the positive responses from
+    /// all the ECUs that replied within the timeout.
+    ///
+    /// The responses are the data after the service ID. ECUs that don't
+    /// support a request usually don't reply at all, and any negative
+    /// responses are skipped.
+    pub fn request(&mut self, req: &[u8]) -> Result<Vec<Response<Vec<u8>>>, Error> {
+        self.transfer(self.functional_id(), None, req)
+    }
+
+    /// Sends a physical request to the ECU with the response ID, returning
+    /// the data of its response after the service ID.
+    pub fn request_ecu(&mut self, ecu: Id, req: &[u8]) -> Result<Vec<u8>, Error> {
+        let tx_id = self.request_id(ecu).ok_or(Error::NoResponse)?;
+        self.transfer(tx_id, Some(ecu), req)?
+            .pop()
+            .map(|resp| resp.value)
+            .ok_or(Error::NoResponse)
+    }
+
+    /// Sends a request and collects the responses.
+    ///
+    /// For a physical request to the `ecu`, this returns as soon as it
+    /// replies.
+    fn transfer(
+        &mut self,
+        tx_id: Id,
+        ecu: Option<Id>,
+        req: &[u8],
+    ) -> Result<Vec<Response<Vec<u8>>>, Error> {
+        let service = *req.first().ok_or(isotp::Error::InvalidLength(req.len()))?;
+        let frame = Codec::new(&self.options(tx_id, tx_id))
+            .single_frame(req)
+            .ok_or(isotp::Error::InvalidLength(req.len()))?;
+        self.link.send(&frame)?;
+
+        let mut rxs: Vec<Rx> = Vec::new();
+        let mut resps = Vec::new();
+        let deadline = Instant::now() + self.timeout;
+
+        loop {
+            // Wait for the responses, and for any that are still arriving
+            let until = rxs
+                .iter()
+                .filter_map(Rx::deadline)
+                .fold(deadline, Instant::max);
+            let timeout = match until.checked_duration_since(Instant::now()) {
+                Some(timeout) if !timeout.is_zero() => timeout,
+                _ => break,
+            };
+            let frame = match self.link.recv(Some(timeout))? {
+                Some(frame) => frame,
+                None => continue,
+            };
+
+            let id = match frame_id(&frame) {
+                Some(id) if ecu.map_or(true, |ecu| ecu == id) => id,
+                _ => continue,
+            };
+            let rx = match rxs.iter().position(|rx| rx.ecu == id) {
+                Some(i) => &mut rxs[i],
+                None => match self.request_id(id) {
+                    Some(req_id) => {
+                        rxs.push(Rx {
+                            ecu: id,
+                            codec: Codec::new(&self.options(req_id, id)),
+                            msg: None,
+                            cr_deadline: Instant::now(),
+                            pending: None,
+                        });
+                        rxs.last_mut().unwrap()
+                    }
+                    None => continue,
+                },
+            };
+
+            let data = match rx.codec.parse(&frame) {
+                Some(Pdu::Single(data)) => data.to_vec(),
+                Some(Pdu::First(len, data)) => {
+                    let fs = if len > MAX_RESPONSE_LEN {
+                        rx.msg = None;
+                        FlowStatus::Overflow
+                    } else {
+                        rx.msg = Some(Reassembler::new(len, data, 0));
+                        FlowStatus::ContinueToSend
+                    };
+                    self.link
+                        .send(&rx.codec.flow_control(fs, 0, Duration::ZERO))?;
+                    rx.cr_deadline = Instant::now() + N_CR;
+                    continue;
+                }
+                Some(Pdu::Consecutive(sn, data)) => {
+                    let done = rx
+                        .msg
+                        .as_mut()
+                        .map(|msg| msg.push(sn, data).map(|_| msg.is_done()));
+                    match done {
+                        Some(Ok(true)) => rx.msg.take().unwrap().into_data(),
+                        Some(Ok(false)) => {
+                            rx.cr_deadline = Instant::now() + N_CR;
+                            continue;
+                        }
+                        // A sequence error drops the response
+                        _ => {
+                            rx.msg = None;
+                            continue;
+                        }
+                    }
+                }
+                _ => continue,
+            };
+
+            rx.pending = None;
+            match *data {
+                [NEGATIVE_RESPONSE, sid, RESPONSE_PENDING] if sid == service => {
+                    rx.pending = Some(Instant::now() + P2_STAR);
+                }
+                [NEGATIVE_RESPONSE, sid, nrc] if sid == service && ecu.is_some() => {
+                    return Err(Error::Negative { service, nrc });
+                }
+                [sid, ..] if sid == service.wrapping_add(0x40) => {
+                    resps.push(Response {
+                        ecu: id,
+                        value: data[1..].to_vec(),
+                    });
+                    if ecu.is_some() {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(resps)
+    }
+
+    /// Gets the ISO-TP options for a pair of IDs.
+    fn options(&self, tx_id: Id, rx_id: Id) -> Options {
+        Options::new(tx_id, rx_id).padding(self.padding)
+    }
+
+    // ----- Services -----
+
+    /// Requests the current data for a PID (mode 01), returning the
+    /// decoded value from each ECU.
+    pub fn current_data(&mut self, pid: u8) -> Result<Vec<Response<Value>>, Error> {
+        Ok(self
+            .request(&[mode::CURRENT_DATA, pid])?
+            .into_iter()
+            .filter(|resp| resp.value.first() == Some(&pid))
+            .map(|resp| Response {
+                ecu: resp.ecu,
+                value: pid::decode(pid, &resp.value[1..]),
+            })
+            .collect())
+    }
+
+    /// Gets the mode 01 PIDs supported by each ECU.
+    ///
+    /// This walks the "PIDs supported" ranges for as long as any ECU
+    /// reports support for the next one.
+    pub fn supported_pids(&mut self) -> Result<Vec<Response<Vec<u8>>>, Error> {
+        let mut supported: Vec<Response<Vec<u8>>> = Vec::new();
+        let mut base = pid::SUPPORTED_01_20;
+
+        loop {
+            for resp in self.current_data(base)? {
+                let pids = match resp.value {
+                    Value::SupportedPids(pids) => pids,
+                    _ => continue,
+                };
+                match supported.iter_mut().find(|s| s.ecu == resp.ecu) {
+                    Some(s) => s.value.extend(pids),
+                    None => supported.push(Response {
+                        ecu: resp.ecu,
+                        value: pids,
+                    }),
+                }
+            }
+
+            base = match base.checked_add(0x20) {
+                Some(next) if supported.iter().any(|s| s.value.contains(&next)) => next,
+                _ => return Ok(supported),
+            };
+        }
+    }
+
+    /// Gets the stored, confirmed DTCs (mode 03) from each ECU.
+    pub fn stored_dtcs(&mut self) -> Result<Vec<Response<Vec<Dtc>>>, Error> {
+        self.dtcs(mode::STORED_DTCS)
+    }
+
+    /// Gets the pending DTCs (mode 07) from each ECU.
+    pub fn pending_dtcs(&mut self) -> Result<Vec<Response<Vec<Dtc>>>, Error> {
+        self.dtcs(mode::PENDING_DTCS)
+    }
+
+    /// Gets the permanent DTCs (mode 0A) from each ECU.
+    pub fn permanent_dtcs(&mut self) -> Result<Vec<Response<Vec<Dtc>>>, Error> {
+        self.dtcs(mode::PERMANENT_DTCS)
+    }
+
+    /// Requests one of the lists of DTCs.
+    fn dtcs(&mut self, mode: u8) -> Result<Vec<Response<Vec<Dtc>>>, Error> {
+        Ok(self
+            .request(&[mode])?
+            .into_iter()
+            .map(|resp| Response {
+                ecu: resp.ecu,
+                value: Dtc::parse_list(&resp.value),
+            })
+            .collect())
+    }
+
+    /// Clears the DTCs and stored diagnostic values (mode 04), returning
+    /// the ECUs that confirmed it.
+    pub fn clear_dtcs(&mut self) -> Result<Vec<Id>, Error> {
+        Ok(self
+            .request(&[mode::CLEAR_DTCS])?
+            .into_iter()
+            .map(|resp| resp.ecu)
+            .collect())
+    }
+
+    /// Gets the vehicle identification number (mode 09) from each ECU that
+    /// reports it.
+    pub fn vin(&mut self) -> Result<Vec<Response<String>>, Error> {
+        Ok(self
+            .request(&[mode::VEHICLE_INFO, INFO_VIN])?
+            .into_iter()
+            .filter(|resp| resp.value.first() == Some(&INFO_VIN))
+            .map(|resp| {
+                // On CAN, the VIN follows the number of data items
+                let data = match &resp.value[1..] {
+                    [_, vin @ ..] if vin.len() == 17 => vin,
+                    vin => vin,
+                };
+                let vin = String::from_utf8_lossy(data)
+                    .trim_matches(char::from(0))
+                    .to_string();
+                Response {
+                    ecu: resp.ecu,
+                    value: vin,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Gets the ID of a data frame.
+fn frame_id(frame: &CanAnyFrame) -> Option<Id> {
+    match frame {
+        CanAnyFrame::Normal(frame) => Some(frame.id()),
+        CanAnyFrame::Fd(frame) => Some(frame.id()),
+        _ => None,
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        frame::id_to_canid_t,
+        isotp::{IsoTpSocket, VirtualBus},
+    };
+    use std::thread::{self, JoinHandle};
+
+    fn id(id: u16) -> Id {
+        StandardId::new(id).unwrap().into()
+    }
+
+    // Simulates an ECU with the request ID, answering both functional and
+    // physical requests until the bus goes quiet.
+    fn ecu<F>(bus: &VirtualBus, req_id: u16, mut handler: F) -> JoinHandle<()>
+    where
+        F: FnMut(&[u8]) -> Vec<Vec<u8>> + Send + 'static,
+    {
+        let mut func = IsoTpSocket::new(bus.connect(), Options::new(id(req_id + 8), id(0x7DF)));
+        let mut phys = IsoTpSocket::new(bus.connect(), Options::new(id(req_id + 8), id(req_id)));
+
+        thread::spawn(move || {
+            let mut idle = 0;
+            while idle < 50 {
+                let timeout = Duration::from_millis(5);
+                let req = match func.recv_timeout(timeout).unwrap() {
+                    Some(req) => req,
+                    None => match phys.recv_timeout(timeout).unwrap() {
+                        Some(req) => req,
+                        None => {
+                            idle += 1;
+                            continue;
+                        }
+                    },
+                };
+                idle = 0;
+                for resp in handler(&req) {
+                    phys.send(&resp).unwrap();
+                }
+            }
+        })
+    }
+
+    fn client(bus: &VirtualBus) -> Client<isotp::VirtualLink> {
+        Client::new(bus.connect()).timeout(Duration::from_millis(50))
+    }
+
+    #[test]
+    fn test_current_data() {
+        let bus = VirtualBus::new();
+        let engine = ecu(&bus, 0x7E0, |req| match req {
+            [0x01, 0x00] => vec![vec![0x41, 0x00, 0x18, 0x18, 0x00, 0x01]],
+            [0x01, 0x20] => vec![vec![0x41, 0x20, 0x00, 0x00, 0x40, 0x00]],
+            [0x01, 0x0C] => vec![vec![0x41, 0x0C, 0x1A, 0xF8]],
+            [0x01, 0x0D] => vec![vec![0x41, 0x0D, 0x3C]],
+            _ => vec![],
+        });
+        let trans = ecu(&bus, 0x7E1, |req| match req {
+            [0x01, 0x00] => vec![vec![0x41, 0x00, 0x00, 0x08, 0x00, 0x00]],
+            [0x01, 0x0D] => vec![vec![0x41, 0x0D, 0x3B]],
+            _ => vec![vec![0x7F, req[0], 0x12]],
+        });
+
+        let mut client = client(&bus);
+        let mut speeds = client.current_data(pid::VEHICLE_SPEED).unwrap();
+        speeds.sort_by_key(|resp| id_to_canid_t(resp.ecu));
+        assert_eq!(
+            speeds,
+            vec![
+                Response {
+                    ecu: id(0x7E8),
+                    value: Value::Quantity(60.0, "km/h")
+                },
+                Response {
+                    ecu: id(0x7E9),
+                    value: Value::Quantity(59.0, "km/h")
+                },
+            ]
+        );
+
+        let rpm = client.current_data(pid::ENGINE_RPM).unwrap();
+        assert_eq!(rpm.len(), 1);
+        assert_eq!(rpm[0].value, Value::Quantity(1726.0, "rpm"));
+
+        let mut pids = client.supported_pids().unwrap();
+        pids.sort_by_key(|resp| id_to_canid_t(resp.ecu));
+        assert_eq!(pids[0].value, vec![0x04, 0x05, 0x0C, 0x0D, 0x20, 0x32]);
+        assert_eq!(pids[1].value, vec![0x0D]);
+
+        // Physical requests
+        assert_eq!(
+            client.request_ecu(id(0x7E8), &[0x01, 0x0D]).unwrap(),
+            &[0x0D, 0x3C]
+        );
+        assert!(matches!(
+            client.request_ecu(id(0x7E9), &[0x01, 0x0C]),
+            Err(Error::Negative {
+                service: 0x01,
+                nrc: 0x12
+            })
+        ));
+        assert!(matches!(
+            client.request_ecu(id(0x7EA), &[0x01, 0x0C]),
+            Err(Error::NoResponse)
+        ));
+
+        engine.join().unwrap();
+        trans.join().unwrap();
+    }
+
+    #[test]
+    fn test_dtcs_and_vin() {
+        let bus = VirtualBus::new();
+        let engine = ecu(&bus, 0x7E0, |req| match req {
+            [0x03] => vec![vec![0x43, 0x02, 0x01, 0x33, 0x41, 0x23]],
+            [0x07] => vec![vec![0x47, 0x00]],
+            // A multi-frame response, after a pending response
+            [0x09, 0x02] => {
+                let mut resp = vec![0x49, 0x02, 0x01];
+                resp.extend_from_slice(b"1G1JC5444R7252367");
+                vec![vec![0x7F, 0x09, 0x78], resp]
+            }
+            _ => vec![],
+        });
+
+        let mut client = client(&bus);
+        let dtcs = client.stored_dtcs().unwrap();
+        assert_eq!(dtcs.len(), 1);
+        assert_eq!(dtcs[0].value, vec![Dtc(0x0133), Dtc(0x4123)]);
+        assert_eq!(dtcs[0].value[1].to_string(), "C0123");
+
+        assert!(client.pending_dtcs().unwrap()[0].value.is_empty());
+        assert!(client.permanent_dtcs().unwrap().is_empty());
+
+        let vin = client.vin().unwrap();
+        assert_eq!(vin.len(), 1);
+        assert_eq!(vin[0].ecu, id(0x7E8));
+        assert_eq!(vin[0].value, "1G1JC5444R7252367");
+
+        engine.join().unwrap();
+    }
+
+    #[test]
+    fn test_extended_ids() {
+        let client = Client::new_extended(VirtualBus::new().connect());
+        let ecu = ExtendedId::new(0x18DA_F110).unwrap().into();
+        assert_eq!(
+            client.request_id(ecu),
+            Some(ExtendedId::new(0x18DA_10F1).unwrap().into())
+        );
+        assert_eq!(client.request_id(id(0x7E8)), None);
+        assert_eq!(
+            client.functional_id(),
+            Id::from(ExtendedId::new(0x18DB_33F1).unwrap())
+        );
+    }
+}
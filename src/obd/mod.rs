@@ -0,0 +1,202 @@
+// socketcan/src/obd/mod.rs
+//
+// OBD-II (SAE J1979) queries over ISO-TP.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! OBD-II (SAE J1979) emissions diagnostics over ISO-TP.
+//!
+//! OBD-II requests are usually sent to the functional address, which every
+//! emissions-related ECU listens to, and each ECU that supports the request
+//! replies from its own response ID. With 11-bit IDs (ISO 15765-4), the
+//! functional request ID is 0x7DF and the ECUs reply on 0x7E8 - 0x7EF. An
+//! ECU can also be addressed on its own, with a physical request to its
+//! request ID, which is eight less than its response ID.
+//!
+//! The [`Client`] sends the requests and collects the responses from all the
+//! ECUs, identified by their response IDs:
+//!
+//! ```no_run
+//! use socketcan::{
+//!     obd::{pid, Client},
+//!     CanSocket, Socket,
+//! };
+//!
+//! let sock = CanSocket::open("can0").unwrap();
+//! let mut client = Client::new(sock);
+//!
+//! for resp in client.current_data(pid::ENGINE_RPM).unwrap() {
+//!     println!("{:?}: {}", resp.ecu, resp.value);
+//! }
+//! for resp in client.stored_dtcs().unwrap() {
+//!     println!("{:?}: {:?}", resp.ecu, resp.value);
+//! }
+//! ```
+
+use crate::{isotp, Id};
+use std::{error, fmt};
+
+pub mod client;
+pub use client::Client;
+
+pub mod pid;
+pub use pid::Value;
+
+/// The OBD-II services, or "modes".
+pub mod mode {
+    /// Show current data
+    pub const CURRENT_DATA: u8 = 0x01;
+    /// Show freeze frame data
+    pub const FREEZE_FRAME: u8 = 0x02;
+    /// Show stored DTCs
+    pub const STORED_DTCS: u8 = 0x03;
+    /// Clear DTCs and stored values
+    pub const CLEAR_DTCS: u8 = 0x04;
+    /// Show pending DTCs
+    pub const PENDING_DTCS: u8 = 0x07;
+    /// Request vehicle information
+    pub const VEHICLE_INFO: u8 = 0x09;
+    /// Show permanent DTCs
+    pub const PERMANENT_DTCS: u8 = 0x0A;
+}
+
+/// The mode 09 vehicle information type for the VIN
+pub const INFO_VIN: u8 = 0x02;
+
+/// The response from one ECU.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response<T> {
+    /// The response CAN ID of the ECU
+    pub ecu: Id,
+    /// The value in the response
+    pub value: T,
+}
+
+// ===== Dtc =====
+
+/// An OBD-II diagnostic trouble code, like "P0301".
+///
+/// The top two bits of the code are the system, P (powertrain),
+/// C (chassis), B (body), or U (network), and the rest are shown as four
+/// hex digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Dtc(pub u16);
+
+impl Dtc {
+    /// Creates a DTC from the two bytes in a response.
+    pub fn from_bytes(b: [u8; 2]) -> Self {
+        Self(u16::from_be_bytes(b))
+    }
+
+    /// Gets the system letter of the code.
+    pub fn system(&self) -> char {
+        ['P', 'C', 'B', 'U'][(self.0 >> 14) as usize]
+    }
+
+    /// Parses the DTCs from a mode 03, 07, or 0A response, after the
+    /// service ID.
+    ///
+    /// On CAN, the data starts with the number of DTCs. Padding codes of
+    /// zero are dropped.
+    pub fn parse_list(data: &[u8]) -> Vec<Self> {
+        let data = if data.len() % 2 == 1 {
+            &data[1..]
+        } else {
+            data
+        };
+        data.chunks_exact(2)
+            .map(|b| Self::from_bytes([b[0], b[1]]))
+            .filter(|dtc| dtc.0 != 0)
+            .collect()
+    }
+}
+
+impl fmt::Display for Dtc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{:04X}", self.system(), self.0 & 0x3FFF)
+    }
+}
+
+// ===== Error =====
+
+/// An error from an OBD-II request.
+#[derive(Debug)]
+pub enum Error {
+    /// An error in the ISO-TP transport
+    IsoTp(isotp::Error),
+    /// The ECU sent a negative response to a physical request
+    Negative {
+        /// The service of the request
+        service: u8,
+        /// The negative response code
+        nrc: u8,
+    },
+    /// No response arrived from the ECU
+    NoResponse,
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::IsoTp(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+        match self {
+            IsoTp(err) => write!(f, "ISO-TP error: {}", err),
+            Negative { service, nrc } => write!(
+                f,
+                "negative response to service 0x{:02X}: 0x{:02X}",
+                service, nrc
+            ),
+            NoResponse => f.write_str("no response from the ECU"),
+        }
+    }
+}
+
+impl From<isotp::Error> for Error {
+    fn from(err: isotp::Error) -> Self {
+        Error::IsoTp(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::IsoTp(err.into())
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dtc() {
+        assert_eq!(Dtc(0x0301).to_string(), "P0301");
+        assert_eq!(Dtc(0x4123).to_string(), "C0123");
+        assert_eq!(Dtc(0x9234).to_string(), "B1234");
+        assert_eq!(Dtc(0xC100).to_string(), "U0100");
+
+        assert_eq!(
+            Dtc::parse_list(&[0x02, 0x01, 0x33, 0x41, 0x23]),
+            vec![Dtc(0x0133), Dtc(0x4123)]
+        );
+        assert!(Dtc::parse_list(&[0x00]).is_empty());
+        assert_eq!(
+            Dtc::parse_list(&[0x03, 0x01, 0x00, 0x00]),
+            vec![Dtc(0x0301)]
+        );
+    }
+}
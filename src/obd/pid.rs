@@ -0,0 +1,391 @@
+// socketcan/src/obd/pid.rs
+//
+// Decoding of OBD-II mode 01 parameter IDs.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! Decoding of the OBD-II mode 01 parameter IDs (PIDs) from SAE J1979.
+//!
+//! The PIDs with a single physical value are decoded into a
+//! [`Value::Quantity`], with the unit of the value. The "PIDs supported"
+//! bitmaps are decoded into a list of the supported PIDs.
+
+use std::fmt;
+
+/// PIDs supported in the range 0x01 - 0x20
+pub const SUPPORTED_01_20: u8 = 0x00;
+/// Monitor status since DTCs cleared
+pub const MONITOR_STATUS: u8 = 0x01;
+/// Calculated engine load
+pub const ENGINE_LOAD: u8 = 0x04;
+/// Engine coolant temperature
+pub const COOLANT_TEMP: u8 = 0x05;
+/// Short term fuel trim, bank 1
+pub const SHORT_FUEL_TRIM_1: u8 = 0x06;
+/// Long term fuel trim, bank 1
+pub const LONG_FUEL_TRIM_1: u8 = 0x07;
+/// Short term fuel trim, bank 2
+pub const SHORT_FUEL_TRIM_2: u8 = 0x08;
+/// Long term fuel trim, bank 2
+pub const LONG_FUEL_TRIM_2: u8 = 0x09;
+/// Fuel pressure (gauge)
+pub const FUEL_PRESSURE: u8 = 0x0A;
+/// Intake manifold absolute pressure
+pub const INTAKE_PRESSURE: u8 = 0x0B;
+/// Engine speed
+pub const ENGINE_RPM: u8 = 0x0C;
+/// Vehicle speed
+pub const VEHICLE_SPEED: u8 = 0x0D;
+/// Timing advance
+pub const TIMING_ADVANCE: u8 = 0x0E;
+/// Intake air temperature
+pub const INTAKE_TEMP: u8 = 0x0F;
+/// Mass air flow sensor air flow rate
+pub const MAF: u8 = 0x10;
+/// Throttle position
+pub const THROTTLE_POS: u8 = 0x11;
+/// Run time since engine start
+pub const RUN_TIME: u8 = 0x1F;
+/// PIDs supported in the range 0x21 - 0x40
+pub const SUPPORTED_21_40: u8 = 0x20;
+/// Distance traveled with the malfunction indicator lamp on
+pub const DISTANCE_WITH_MIL: u8 = 0x21;
+/// Fuel tank level input
+pub const FUEL_LEVEL: u8 = 0x2F;
+/// Distance traveled since DTCs cleared
+pub const DISTANCE_SINCE_CLEAR: u8 = 0x31;
+/// Absolute barometric pressure
+pub const BAROMETRIC_PRESSURE: u8 = 0x33;
+/// PIDs supported in the range 0x41 - 0x60
+pub const SUPPORTED_41_60: u8 = 0x40;
+/// Control module voltage
+pub const CONTROL_MODULE_VOLTAGE: u8 = 0x42;
+/// Ambient air temperature
+pub const AMBIENT_TEMP: u8 = 0x46;
+/// Engine oil temperature
+pub const OIL_TEMP: u8 = 0x5C;
+/// Engine fuel rate
+pub const FUEL_RATE: u8 = 0x5E;
+
+/// The decoded value of a mode 01 PID.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// A physical value, with its unit
+    Quantity(f64, &'static str),
+    /// The PIDs supported from a "PIDs supported" bitmap
+    SupportedPids(Vec<u8>),
+    /// The monitor status since DTCs were cleared
+    MonitorStatus {
+        /// Whether the malfunction indicator lamp is on
+        mil: bool,
+        /// The number of confirmed emissions-related DTCs
+        dtc_count: u8,
+    },
+    /// The data of a PID without a decoder, or with too little data
+    Raw(Vec<u8>),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Value::*;
+        match self {
+            Quantity(val, unit) => write!(f, "{} {}", val, unit),
+            SupportedPids(pids) => write!(f, "{:02X?}", pids),
+            MonitorStatus { mil, dtc_count } => write!(
+                f,
+                "MIL {}, {} DTC(s)",
+                if *mil { "on" } else { "off" },
+                dtc_count
+            ),
+            Raw(data) => write!(f, "{:02X?}", data),
+        }
+    }
+}
+
+/// The description of a PID with a single physical value
+struct Def {
+    pid: u8,
+    name: &'static str,
+    unit: &'static str,
+    len: usize,
+    decode: fn(f64, f64) -> f64,
+}
+
+/// The PIDs with a single physical value, in order.
+/// The decode function gets the data bytes A and B.
+static DEFS: &[Def] = &[
+    Def {
+        pid: ENGINE_LOAD,
+        name: "Calculated engine load",
+        unit: "%",
+        len: 1,
+        decode: |a, _| a * 100.0 / 255.0,
+    },
+    Def {
+        pid: COOLANT_TEMP,
+        name: "Engine coolant temperature",
+        unit: "°C",
+        len: 1,
+        decode: |a, _| a - 40.0,
+    },
+    Def {
+        pid: SHORT_FUEL_TRIM_1,
+        name: "Short term fuel trim, bank 1",
+        unit: "%",
+        len: 1,
+        decode: |a, _| a * 100.0 / 128.0 - 100.0,
+    },
+    Def {
+        pid: LONG_FUEL_TRIM_1,
+        name: "Long term fuel trim, bank 1",
+        unit: "%",
+        len: 1,
+        decode: |a, _| a * 100.0 / 128.0 - 100.0,
+    },
+    Def {
+        pid: SHORT_FUEL_TRIM_2,
+        name: "Short term fuel trim, bank 2",
+        unit: "%",
+        len: 1,
+        decode: |a, _| a * 100.0 / 128.0 - 100.0,
+    },
+    Def {
+        pid: LONG_FUEL_TRIM_2,
+        name: "Long term fuel trim, bank 2",
+        unit: "%",
+        len: 1,
+        decode: |a, _| a * 100.0 / 128.0 - 100.0,
+    },
+    Def {
+        pid: FUEL_PRESSURE,
+        name: "Fuel pressure",
+        unit: "kPa",
+        len: 1,
+        decode: |a, _| a * 3.0,
+    },
+    Def {
+        pid: INTAKE_PRESSURE,
+        name: "Intake manifold absolute pressure",
+        unit: "kPa",
+        len: 1,
+        decode: |a, _| a,
+    },
+    Def {
+        pid: ENGINE_RPM,
+        name: "Engine speed",
+        unit: "rpm",
+        len: 2,
+        decode: |a, b| (256.0 * a + b) / 4.0,
+    },
+    Def {
+        pid: VEHICLE_SPEED,
+        name: "Vehicle speed",
+        unit: "km/h",
+        len: 1,
+        decode: |a, _| a,
+    },
+    Def {
+        pid: TIMING_ADVANCE,
+        name: "Timing advance",
+        unit: "°",
+        len: 1,
+        decode: |a, _| a / 2.0 - 64.0,
+    },
+    Def {
+        pid: INTAKE_TEMP,
+        name: "Intake air temperature",
+        unit: "°C",
+        len: 1,
+        decode: |a, _| a - 40.0,
+    },
+    Def {
+        pid: MAF,
+        name: "Mass air flow rate",
+        unit: "g/s",
+        len: 2,
+        decode: |a, b| (256.0 * a + b) / 100.0,
+    },
+    Def {
+        pid: THROTTLE_POS,
+        name: "Throttle position",
+        unit: "%",
+        len: 1,
+        decode: |a, _| a * 100.0 / 255.0,
+    },
+    Def {
+        pid: RUN_TIME,
+        name: "Run time since engine start",
+        unit: "s",
+        len: 2,
+        decode: |a, b| 256.0 * a + b,
+    },
+    Def {
+        pid: DISTANCE_WITH_MIL,
+        name: "Distance traveled with MIL on",
+        unit: "km",
+        len: 2,
+        decode: |a, b| 256.0 * a + b,
+    },
+    Def {
+        pid: FUEL_LEVEL,
+        name: "Fuel tank level input",
+        unit: "%",
+        len: 1,
+        decode: |a, _| a * 100.0 / 255.0,
+    },
+    Def {
+        pid: DISTANCE_SINCE_CLEAR,
+        name: "Distance traveled since codes cleared",
+        unit: "km",
+        len: 2,
+        decode: |a, b| 256.0 * a + b,
+    },
+    Def {
+        pid: BAROMETRIC_PRESSURE,
+        name: "Absolute barometric pressure",
+        unit: "kPa",
+        len: 1,
+        decode: |a, _| a,
+    },
+    Def {
+        pid: CONTROL_MODULE_VOLTAGE,
+        name: "Control module voltage",
+        unit: "V",
+        len: 2,
+        decode: |a, b| (256.0 * a + b) / 1000.0,
+    },
+    Def {
+        pid: AMBIENT_TEMP,
+        name: "Ambient air temperature",
+        unit: "°C",
+        len: 1,
+        decode: |a, _| a - 40.0,
+    },
+    Def {
+        pid: OIL_TEMP,
+        name: "Engine oil temperature",
+        unit: "°C",
+        len: 1,
+        decode: |a, _| a - 40.0,
+    },
+    Def {
+        pid: FUEL_RATE,
+        name: "Engine fuel rate",
+        unit: "L/h",
+        len: 2,
+        decode: |a, b| (256.0 * a + b) / 20.0,
+    },
+];
+
+/// Determines if the PID is one of the "PIDs supported" bitmaps.
+pub fn is_supported_pids(pid: u8) -> bool {
+    pid % 0x20 == 0
+}
+
+/// Gets the name of a PID, if it's known.
+pub fn name(pid: u8) -> Option<&'static str> {
+    match pid {
+        MONITOR_STATUS => Some("Monitor status since DTCs cleared"),
+        pid if is_supported_pids(pid) => Some("PIDs supported"),
+        pid => DEFS
+            .binary_search_by_key(&pid, |def| def.pid)
+            .ok()
+            .map(|i| DEFS[i].name),
+    }
+}
+
+/// Decodes the data of a PID, as it appears in a mode 01 response after
+/// the PID.
+///
+/// PIDs without a decoder, and data too short for the PID, are returned as
+/// [`Value::Raw`].
+pub fn decode(pid: u8, data: &[u8]) -> Value {
+    match (pid, data) {
+        (pid, [a, b, c, d, ..]) if is_supported_pids(pid) => {
+            Value::SupportedPids(supported(pid, &[*a, *b, *c, *d]))
+        }
+        (MONITOR_STATUS, [a, _, _, _, ..]) => Value::MonitorStatus {
+            mil: a & 0x80 != 0,
+            dtc_count: a & 0x7F,
+        },
+        (pid, data) => match DEFS.binary_search_by_key(&pid, |def| def.pid) {
+            Ok(i) if data.len() >= DEFS[i].len => {
+                let def = &DEFS[i];
+                let b = if def.len > 1 { data[1] } else { 0 };
+                Value::Quantity((def.decode)(data[0] as f64, b as f64), def.unit)
+            }
+            _ => Value::Raw(data.to_vec()),
+        },
+    }
+}
+
+/// Gets the list of PIDs from a "PIDs supported" bitmap.
+///
+/// The most significant bit of the first byte is the PID after `base`.
+pub fn supported(base: u8, bitmap: &[u8; 4]) -> Vec<u8> {
+    let bits = u32::from_be_bytes(*bitmap);
+    (0..32u8)
+        .filter(|i| bits & (0x8000_0000 >> i) != 0)
+        .filter_map(|i| base.checked_add(i + 1))
+        .collect()
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defs_sorted() {
+        assert!(DEFS.windows(2).all(|w| w[0].pid < w[1].pid));
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(
+            decode(ENGINE_RPM, &[0x1A, 0xF8]),
+            Value::Quantity(1726.0, "rpm")
+        );
+        assert_eq!(
+            decode(VEHICLE_SPEED, &[0x3C]),
+            Value::Quantity(60.0, "km/h")
+        );
+        assert_eq!(decode(COOLANT_TEMP, &[0x7B]), Value::Quantity(83.0, "°C"));
+        assert_eq!(
+            decode(CONTROL_MODULE_VOLTAGE, &[0x37, 0x14]),
+            Value::Quantity(14.1, "V")
+        );
+        assert_eq!(
+            decode(MONITOR_STATUS, &[0x82, 0x07, 0x65, 0x00]),
+            Value::MonitorStatus {
+                mil: true,
+                dtc_count: 2
+            }
+        );
+        assert_eq!(decode(ENGINE_RPM, &[0x1A]), Value::Raw(vec![0x1A]));
+        assert_eq!(decode(0x03, &[0x02, 0x00]), Value::Raw(vec![0x02, 0x00]));
+
+        assert_eq!(name(ENGINE_RPM), Some("Engine speed"));
+        assert_eq!(name(SUPPORTED_21_40), Some("PIDs supported"));
+        assert_eq!(name(0x03), None);
+    }
+
+    #[test]
+    fn test_supported() {
+        assert_eq!(
+            decode(SUPPORTED_01_20, &[0xBE, 0x1F, 0xA8, 0x13]),
+            Value::SupportedPids(vec![
+                0x01, 0x03, 0x04, 0x05, 0x06, 0x07, 0x0C, 0x0D, 0x0E, 0x0F, 0x10, 0x11, 0x13, 0x15,
+                0x1C, 0x1F, 0x20
+            ])
+        );
+        assert!(supported(0xE0, &[0, 0, 0, 0x01]).is_empty());
+        assert_eq!(supported(0x20, &[0x80, 0, 0, 0x01]), vec![0x21, 0x40]);
+    }
+}
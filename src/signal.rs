@@ -0,0 +1,391 @@
+// socketcan/src/signal.rs
+//
+// Encoding and decoding signals packed into CAN frame payloads.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! Signals packed into CAN frame payloads.
+//!
+//! A signal is a value packed into a range of bits in the data payload of a
+//! frame. A [`SignalSpec`] describes the layout of the signal, using the
+//! same conventions as DBC files, and the scaling from the raw integer in
+//! the payload to the physical value.
+//!
+//! Signals can be read and written directly on any of the frame types,
+//! classic or FD, through [`Frame::get_signal()`](crate::Frame::get_signal)
+//! and [`Frame::set_signal()`](crate::Frame::set_signal):
+//!
+//! ```
+//! use socketcan::{ByteOrder, CanFrame, EmbeddedFrame, Frame, SignalSpec, StandardId, ValueType};
+//!
+//! const SPEED: SignalSpec =
+//!     SignalSpec::new(8, 16, ByteOrder::LittleEndian, ValueType::Unsigned).scaled(0.01, 0.0);
+//!
+//! let mut frame = CanFrame::new(StandardId::new(0x100).unwrap(), &[0; 8]).unwrap();
+//! frame.set_signal(&SPEED, 123.45).unwrap();
+//! assert_eq!(frame.data()[1..3], [0x39, 0x30]);
+//! assert_eq!(frame.get_signal(&SPEED).unwrap(), 123.45);
+//! ```
+
+use crate::SignalError;
+
+/// The number of bits in the largest payload, of a CAN FD frame.
+const MAX_PAYLOAD_BITS: u32 = 512;
+
+/// The order of the bytes of a signal in the payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ByteOrder {
+    /// Little endian, also called "Intel" (`@1` in DBC files)
+    LittleEndian,
+    /// Big endian, also called "Motorola" (`@0` in DBC files)
+    BigEndian,
+}
+
+/// The type of the raw value of a signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ValueType {
+    /// An unsigned integer
+    Unsigned,
+    /// A two's complement signed integer
+    Signed,
+    /// An IEEE 754 single-precision float (32 bits)
+    Float32,
+    /// An IEEE 754 double-precision float (64 bits)
+    Float64,
+}
+
+/// The layout and scaling of a signal in a frame payload.
+///
+/// The bits are numbered as in DBC files: bit `n` is bit `n % 8` of byte
+/// `n / 8`, with bit 0 being the least significant. For a little endian
+/// signal the start bit is the position of the least significant bit of
+/// the value, and for a big endian signal it is the position of the most
+/// significant bit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignalSpec {
+    /// The start bit
+    pub start_bit: u32,
+    /// The size in bits, 1 to 64
+    pub size: u32,
+    /// The byte order
+    pub byte_order: ByteOrder,
+    /// The type of the raw value
+    pub value_type: ValueType,
+    /// The scale factor from raw to physical value
+    pub factor: f64,
+    /// The offset from raw to physical value
+    pub offset: f64,
+}
+
+impl SignalSpec {
+    /// Creates a signal spec with no scaling.
+    pub const fn new(
+        start_bit: u32,
+        size: u32,
+        byte_order: ByteOrder,
+        value_type: ValueType,
+    ) -> Self {
+        Self {
+            start_bit,
+            size,
+            byte_order,
+            value_type,
+            factor: 1.0,
+            offset: 0.0,
+        }
+    }
+
+    /// Sets the scaling from raw to physical value, as
+    /// `phys = raw * factor + offset`.
+    pub const fn scaled(mut self, factor: f64, offset: f64) -> Self {
+        self.factor = factor;
+        self.offset = offset;
+        self
+    }
+
+    /// Checks that the size is valid for the value type, and that the
+    /// start bit is within the largest payload.
+    fn validate(&self) -> Result<(), SignalError> {
+        if self.start_bit >= MAX_PAYLOAD_BITS {
+            return Err(SignalError::OutOfBounds);
+        }
+        let valid = match self.value_type {
+            ValueType::Unsigned | ValueType::Signed => (1..=64).contains(&self.size),
+            ValueType::Float32 => self.size == 32,
+            ValueType::Float64 => self.size == 64,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(SignalError::InvalidSize(self.size))
+        }
+    }
+
+    /// Gets the payload bit positions of the value, from the least to the
+    /// most significant bit.
+    fn bit_positions(&self) -> impl Iterator<Item = u32> {
+        let (start, size, order) = (self.start_bit, self.size, self.byte_order);
+        // Big endian signals are contiguous when the bits of each byte are
+        // numbered from the most significant, so walk them in that order.
+        let msb = 8 * (start / 8) + 7 - start % 8;
+        (0..size).map(move |i| match order {
+            ByteOrder::LittleEndian => start + i,
+            ByteOrder::BigEndian => {
+                let n = msb + size - 1 - i;
+                8 * (n / 8) + 7 - n % 8
+            }
+        })
+    }
+
+    /// Determines if the signal fits in a payload of the specified length.
+    pub fn fits(&self, len: usize) -> bool {
+        self.validate().is_ok() && self.bit_positions().all(|pos| ((pos / 8) as usize) < len)
+    }
+
+    /// Extracts the raw bits of the signal from a payload.
+    ///
+    /// The value is right-aligned, without sign extension.
+    pub fn extract(&self, data: &[u8]) -> Result<u64, SignalError> {
+        self.validate()?;
+        let mut raw = 0u64;
+        for (i, pos) in self.bit_positions().enumerate() {
+            let byte = data
+                .get((pos / 8) as usize)
+                .ok_or(SignalError::OutOfBounds)?;
+            raw |= u64::from((byte >> (pos % 8)) & 1) << i;
+        }
+        Ok(raw)
+    }
+
+    /// Inserts the raw bits of the signal into a payload.
+    ///
+    /// Only the low `size` bits of the value are used. The other bits of
+    /// the payload are left unchanged. The payload is not modified if the
+    /// signal doesn't fit.
+    pub fn insert(&self, data: &mut [u8], raw: u64) -> Result<(), SignalError> {
+        if !self.fits(data.len()) {
+            self.validate()?;
+            return Err(SignalError::OutOfBounds);
+        }
+        for (i, pos) in self.bit_positions().enumerate() {
+            let byte = &mut data[(pos / 8) as usize];
+            let mask = 1u8 << (pos % 8);
+            if (raw >> i) & 1 != 0 {
+                *byte |= mask;
+            } else {
+                *byte &= !mask;
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the mask for the raw bits of the signal.
+    fn mask(&self) -> u64 {
+        u64::MAX >> (64 - self.size.clamp(1, 64))
+    }
+
+    /// Converts the raw bits of the signal to the unscaled value.
+    pub fn raw_value(&self, raw: u64) -> f64 {
+        match self.value_type {
+            ValueType::Unsigned => (raw & self.mask()) as f64,
+            ValueType::Signed => {
+                let shift = 64 - self.size.clamp(1, 64);
+                (((raw << shift) as i64) >> shift) as f64
+            }
+            ValueType::Float32 => f64::from(f32::from_bits(raw as u32)),
+            ValueType::Float64 => f64::from_bits(raw),
+        }
+    }
+
+    /// Converts the raw bits of the signal to the physical value.
+    pub fn to_physical(&self, raw: u64) -> f64 {
+        self.raw_value(raw) * self.factor + self.offset
+    }
+
+    /// Converts a physical value to the raw bits of the signal.
+    ///
+    /// Integer values are rounded to the nearest integer and saturate at
+    /// the limits of the signal size.
+    pub fn to_raw(&self, phys: f64) -> u64 {
+        let val = (phys - self.offset) / self.factor;
+        match self.value_type {
+            ValueType::Unsigned => (val.round() as u64).min(self.mask()),
+            ValueType::Signed => {
+                let shift = 64 - self.size.clamp(1, 64);
+                let max = i64::MAX >> shift;
+                let min = i64::MIN >> shift;
+                ((val.round() as i64).clamp(min, max) as u64) & self.mask()
+            }
+            ValueType::Float32 => u64::from((val as f32).to_bits()),
+            ValueType::Float64 => val.to_bits(),
+        }
+    }
+
+    /// Decodes the physical value of the signal from a payload.
+    pub fn decode(&self, data: &[u8]) -> Result<f64, SignalError> {
+        self.extract(data).map(|raw| self.to_physical(raw))
+    }
+
+    /// Encodes the physical value of the signal into a payload.
+    pub fn encode(&self, data: &mut [u8], phys: f64) -> Result<(), SignalError> {
+        self.insert(data, self.to_raw(phys))
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CanFdFrame, CanFrame, EmbeddedFrame, Frame, StandardId};
+
+    fn spec(start_bit: u32, size: u32, byte_order: ByteOrder, value_type: ValueType) -> SignalSpec {
+        SignalSpec::new(start_bit, size, byte_order, value_type)
+    }
+
+    #[test]
+    fn test_little_endian() {
+        let data = [0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC, 0xDE, 0xF0];
+
+        let sig = spec(0, 16, ByteOrder::LittleEndian, ValueType::Unsigned);
+        assert_eq!(sig.extract(&data).unwrap(), 0x3412);
+
+        let sig = spec(4, 12, ByteOrder::LittleEndian, ValueType::Unsigned);
+        assert_eq!(sig.extract(&data).unwrap(), 0x341);
+
+        let sig = spec(0, 64, ByteOrder::LittleEndian, ValueType::Unsigned);
+        assert_eq!(sig.extract(&data).unwrap(), 0xF0DEBC9A78563412);
+
+        let sig = spec(60, 4, ByteOrder::LittleEndian, ValueType::Signed);
+        assert_eq!(sig.decode(&data).unwrap(), -1.0);
+    }
+
+    #[test]
+    fn test_big_endian() {
+        let data = [0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC, 0xDE, 0xF0];
+
+        // MSB at bit 7 of byte 0
+        let sig = spec(7, 16, ByteOrder::BigEndian, ValueType::Unsigned);
+        assert_eq!(sig.extract(&data).unwrap(), 0x1234);
+
+        // A 12-bit value starting in the low nibble of byte 0
+        let sig = spec(3, 12, ByteOrder::BigEndian, ValueType::Unsigned);
+        assert_eq!(sig.extract(&data).unwrap(), 0x234);
+
+        let sig = spec(7, 64, ByteOrder::BigEndian, ValueType::Unsigned);
+        assert_eq!(sig.extract(&data).unwrap(), 0x123456789ABCDEF0);
+
+        let sig = spec(7, 4, ByteOrder::BigEndian, ValueType::Unsigned);
+        assert_eq!(sig.extract(&data).unwrap(), 0x1);
+
+        // Crosses the end of the payload
+        let sig = spec(63, 16, ByteOrder::BigEndian, ValueType::Unsigned);
+        assert_eq!(sig.extract(&data), Err(SignalError::OutOfBounds));
+        assert!(!sig.fits(8));
+    }
+
+    #[test]
+    fn test_large_start_bit() {
+        for start in [512, u32::from(u16::MAX), u32::MAX] {
+            for order in [ByteOrder::LittleEndian, ByteOrder::BigEndian] {
+                let sig = spec(start, 64, order, ValueType::Unsigned);
+                assert!(!sig.fits(64));
+                assert_eq!(sig.extract(&[0; 64]), Err(SignalError::OutOfBounds));
+                assert_eq!(sig.insert(&mut [0; 64], 1), Err(SignalError::OutOfBounds));
+            }
+        }
+    }
+
+    #[test]
+    fn test_insert() {
+        for order in [ByteOrder::LittleEndian, ByteOrder::BigEndian] {
+            for (start, size) in [(3, 1), (5, 7), (12, 13), (7, 64), (0, 64), (20, 33)] {
+                let sig = spec(start, size, order, ValueType::Unsigned);
+                if !sig.fits(8) {
+                    continue;
+                }
+                let raw = 0xA5C3_5A3C_9669_F00F & sig.mask();
+                let mut data = [0xFF; 8];
+                sig.insert(&mut data, raw).unwrap();
+                assert_eq!(sig.extract(&data).unwrap(), raw);
+
+                let mut data = [0x00; 8];
+                sig.insert(&mut data, raw).unwrap();
+                assert_eq!(sig.extract(&data).unwrap(), raw);
+                assert_eq!(
+                    data.iter().map(|b| b.count_ones()).sum::<u32>(),
+                    raw.count_ones()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_scaling() {
+        let sig = spec(0, 8, ByteOrder::LittleEndian, ValueType::Signed).scaled(0.5, -10.0);
+        let mut data = [0u8; 1];
+
+        sig.encode(&mut data, -20.0).unwrap();
+        assert_eq!(data, [0xEC]);
+        assert_eq!(sig.decode(&data).unwrap(), -20.0);
+
+        // Saturates at the limits
+        sig.encode(&mut data, 1000.0).unwrap();
+        assert_eq!(data, [0x7F]);
+        sig.encode(&mut data, -1000.0).unwrap();
+        assert_eq!(data, [0x80]);
+
+        let sig = spec(0, 4, ByteOrder::LittleEndian, ValueType::Unsigned);
+        sig.encode(&mut data, -3.0).unwrap();
+        assert_eq!(data, [0x80]);
+        sig.encode(&mut data, 20.0).unwrap();
+        assert_eq!(data, [0x8F]);
+    }
+
+    #[test]
+    fn test_float() {
+        let mut data = [0u8; 12];
+
+        let sig = spec(32, 32, ByteOrder::LittleEndian, ValueType::Float32);
+        sig.encode(&mut data, 1.5).unwrap();
+        assert_eq!(data[4..8], 1.5f32.to_le_bytes());
+        assert_eq!(sig.decode(&data).unwrap(), 1.5);
+
+        let sig = spec(39, 64, ByteOrder::BigEndian, ValueType::Float64);
+        sig.encode(&mut data, -2.25).unwrap();
+        assert_eq!(data[4..12], (-2.25f64).to_be_bytes());
+        assert_eq!(sig.decode(&data).unwrap(), -2.25);
+
+        let sig = spec(0, 16, ByteOrder::LittleEndian, ValueType::Float32);
+        assert_eq!(sig.decode(&data), Err(SignalError::InvalidSize(16)));
+    }
+
+    #[test]
+    fn test_frames() {
+        let id = StandardId::new(0x100).unwrap();
+        let sig = spec(7, 16, ByteOrder::BigEndian, ValueType::Unsigned);
+
+        let mut frame = CanFrame::new(id, &[0; 4]).unwrap();
+        frame.set_signal(&sig, 0xABCD as f64).unwrap();
+        assert_eq!(frame.data(), &[0xAB, 0xCD, 0, 0]);
+        assert_eq!(frame.get_signal(&sig).unwrap(), 0xABCD as f64);
+
+        let mut frame = CanFrame::new(id, &[0; 1]).unwrap();
+        assert_eq!(frame.set_signal(&sig, 1.0), Err(SignalError::OutOfBounds));
+        assert_eq!(frame.data(), &[0]);
+
+        let sig = spec(60 * 8, 32, ByteOrder::LittleEndian, ValueType::Signed);
+        let mut frame = CanFdFrame::new(id, &[0; 64]).unwrap();
+        frame.set_signal(&sig, -2.0).unwrap();
+        assert_eq!(frame.data()[60..], [0xFE, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(frame.get_signal(&sig).unwrap(), -2.0);
+    }
+}
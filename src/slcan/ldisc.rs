@@ -0,0 +1,296 @@
+// socketcan/src/slcan/ldisc.rs
+//
+// Attaching a serial tty to the kernel slcan line discipline.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! Attaching a serial tty to the kernel slcan line discipline.
+//!
+//! This does the work of `slcan_attach` and `slcand`: it configures the
+//! adapter over its tty, then switches the tty to the `N_SLCAN` line
+//! discipline, which makes the kernel create an `slcanN` network
+//! interface for the adapter. Any program can then use that interface
+//! with a [`CanSocket`](crate::CanSocket).
+//!
+//! The interface only lasts as long as the tty stays open, so the
+//! [`SlcanInterface`] holds it, and detaches the line discipline when it
+//! is dropped:
+//!
+//! ```no_run
+//! use socketcan::slcan::{AttachOptions, Bitrate, SlcanInterface};
+//!
+//! let opts = AttachOptions::new().bitrate(Bitrate::Kbps500).name("can7");
+//! let slcan = SlcanInterface::attach("/dev/ttyUSB0", opts).unwrap();
+//! println!("Attached {}", slcan.name());
+//!
+//! // ... use "can7" with a CanSocket ...
+//!
+//! slcan.detach().unwrap();
+//! ```
+//!
+//! Attaching the line discipline and configuring the interface require
+//! the `CAP_NET_ADMIN` capability, and the `slcan` kernel module.
+
+use super::{port::DEFAULT_BAUD_RATE, Bitrate, SlcanPort};
+use crate::{CanInterface, IoError, IoErrorKind, IoResult};
+use std::{
+    fs::File,
+    io::Write,
+    os::{raw::c_int, unix::io::AsRawFd},
+    path::Path,
+};
+
+/// The default tty line discipline
+const N_TTY: c_int = 0;
+
+/// The slcan line discipline
+const N_SLCAN: c_int = 17;
+
+// ===== AttachOptions =====
+
+/// The configuration of an slcan adapter to attach.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttachOptions {
+    baud: u32,
+    bitrate: Option<Bitrate>,
+    btr: Option<(u8, u8)>,
+    listen_only: bool,
+    name: Option<String>,
+    up: bool,
+}
+
+impl Default for AttachOptions {
+    fn default() -> Self {
+        Self {
+            baud: DEFAULT_BAUD_RATE,
+            bitrate: None,
+            btr: None,
+            listen_only: false,
+            name: None,
+            up: true,
+        }
+    }
+}
+
+impl AttachOptions {
+    /// Creates the default options.
+    ///
+    /// These leave the adapter at its current bitrate, open the channel
+    /// in normal mode, and bring the interface up under the kernel's name
+    /// for it.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the baud rate of the serial port.
+    pub fn baud(mut self, baud: u32) -> Self {
+        self.baud = baud;
+        self
+    }
+
+    /// Sets the CAN bitrate, with the `S0` to `S8` commands.
+    pub fn bitrate(mut self, bitrate: Bitrate) -> Self {
+        self.bitrate = Some(bitrate);
+        self.btr = None;
+        self
+    }
+
+    /// Sets the SJA1000 bit timing registers, with the `s` command, for a
+    /// non-standard CAN bitrate.
+    pub fn btr(mut self, btr0: u8, btr1: u8) -> Self {
+        self.btr = Some((btr0, btr1));
+        self.bitrate = None;
+        self
+    }
+
+    /// Opens the channel in listen-only mode.
+    pub fn listen_only(mut self, on: bool) -> Self {
+        self.listen_only = on;
+        self
+    }
+
+    /// Renames the interface from the kernel's `slcanN`.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets whether to bring the interface up once attached.
+    pub fn bring_up(mut self, up: bool) -> Self {
+        self.up = up;
+        self
+    }
+}
+
+/// Sends the configuration commands to the adapter, leaving its channel
+/// open.
+fn configure(port: &SlcanPort, opts: &AttachOptions) -> IoResult<()> {
+    // The channel may have been left open, in which case it must be
+    // closed to change the bitrate. An adapter rejects closing a closed
+    // channel, so any error is expected.
+    let _ = port.close_channel();
+
+    if let Some(bitrate) = opts.bitrate {
+        port.set_bitrate(bitrate)?;
+    }
+    if let Some((btr0, btr1)) = opts.btr {
+        port.set_btr(btr0, btr1)?;
+    }
+    if opts.listen_only {
+        port.open_listen_only()
+    } else {
+        port.open_channel()
+    }
+}
+
+/// Converts a netlink error to an I/O error.
+fn nl_error<E: std::fmt::Display>(err: E) -> IoError {
+    IoError::new(IoErrorKind::Other, err.to_string())
+}
+
+// ===== SlcanInterface =====
+
+/// A serial tty attached to the kernel slcan line discipline, and the
+/// CAN network interface for it.
+#[derive(Debug)]
+pub struct SlcanInterface {
+    file: File,
+    iface: CanInterface,
+    name: String,
+    attached: bool,
+}
+
+impl SlcanInterface {
+    /// Configures the adapter on the tty and attaches it to the slcan line
+    /// discipline, returning the new CAN interface.
+    ///
+    /// PRIVILEGED: This requires root privilege.
+    pub fn attach<P: AsRef<Path>>(path: P, opts: AttachOptions) -> IoResult<Self> {
+        let port = SlcanPort::open_with_baud(path, opts.baud)?;
+        configure(&port, &opts)?;
+        let file = port.into_file();
+        let fd = file.as_raw_fd();
+
+        // SAFETY: The fd is an open tty, and the arguments are the types
+        // that the ioctls expect.
+        if unsafe { libc::ioctl(fd, libc::TIOCSETD, &N_SLCAN) } < 0 {
+            return Err(IoError::last_os_error());
+        }
+
+        let mut buf = [0u8; libc::IFNAMSIZ];
+        if unsafe { libc::ioctl(fd, libc::SIOCGIFNAME as _, buf.as_mut_ptr()) } < 0 {
+            let err = IoError::last_os_error();
+            Self::reset_ldisc(&file);
+            return Err(err);
+        }
+        let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+        let name = String::from_utf8_lossy(&buf[..len]).into_owned();
+
+        let iface = match CanInterface::open(&name) {
+            Ok(iface) => iface,
+            Err(err) => {
+                Self::reset_ldisc(&file);
+                return Err(err.into());
+            }
+        };
+        let mut slcan = Self {
+            file,
+            iface,
+            name,
+            attached: true,
+        };
+
+        if let Some(name) = opts.name.as_deref() {
+            slcan.iface.set_name(name).map_err(nl_error)?;
+            slcan.name = name.into();
+        }
+        if opts.up {
+            slcan.iface.bring_up().map_err(nl_error)?;
+        }
+        Ok(slcan)
+    }
+
+    /// Gets the name of the interface.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gets the network interface.
+    pub fn interface(&self) -> &CanInterface {
+        &self.iface
+    }
+
+    /// Detaches the tty from the line discipline, which removes the
+    /// interface, and closes the adapter's channel.
+    ///
+    /// This is also done, ignoring any errors, when the `SlcanInterface`
+    /// is dropped.
+    pub fn detach(mut self) -> IoResult<()> {
+        self.detach_tty()
+    }
+
+    /// Restores the tty line discipline, and closes the channel.
+    fn detach_tty(&mut self) -> IoResult<()> {
+        if !self.attached {
+            return Ok(());
+        }
+        self.attached = false;
+
+        // SAFETY: The fd is an open tty.
+        if unsafe { libc::ioctl(self.file.as_raw_fd(), libc::TIOCSETD, &N_TTY) } < 0 {
+            return Err(IoError::last_os_error());
+        }
+        (&self.file).write_all(b"C\r")
+    }
+
+    /// Restores the tty line discipline, after a failed attach.
+    fn reset_ldisc(file: &File) {
+        // SAFETY: The fd is an open tty.
+        unsafe { libc::ioctl(file.as_raw_fd(), libc::TIOCSETD, &N_TTY) };
+    }
+}
+
+impl Drop for SlcanInterface {
+    fn drop(&mut self) {
+        let _ = self.detach_tty();
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slcan::port::tests::{adapter, pty};
+
+    #[test]
+    fn test_configure() {
+        // An adapter with the channel closed, which acks everything else
+        let (port, pty) = pty();
+        let adapter = adapter(pty, |cmd| match cmd {
+            "C" => vec![0x07],
+            _ => b"\r".to_vec(),
+        });
+
+        let opts = AttachOptions::new()
+            .bitrate(Bitrate::Kbps250)
+            .listen_only(true);
+        configure(&port, &opts).unwrap();
+        drop(port);
+        assert_eq!(adapter.join().unwrap(), ["C", "S5", "L"]);
+    }
+
+    #[test]
+    fn test_options() {
+        let opts = AttachOptions::new().bitrate(Bitrate::Mbps1).btr(0x00, 0x14);
+        assert_eq!(opts.bitrate, None);
+        assert_eq!(opts.btr, Some((0x00, 0x14)));
+        assert!(opts.up);
+        assert_eq!(opts.baud, DEFAULT_BAUD_RATE);
+    }
+}
@@ -0,0 +1,377 @@
+// socketcan/src/slcan/mod.rs
+//
+// Userspace driver for slcan (Lawicel) serial-line CAN adapters.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! A userspace driver for slcan serial-line CAN adapters.
+//!
+//! Many inexpensive USB-CAN adapters speak the ASCII slcan protocol, from
+//! the Lawicel CAN232 and CANUSB, over a serial port. Each command and
+//! frame is a line of text ending in a carriage return, such as `S6` to
+//! set the bitrate to 500 kbit/s, `O` to open the channel, or
+//! `t1232DEAD` for a standard data frame with ID 0x123 and two bytes of
+//! data.
+//!
+//! The [`SlcanPort`] opens the serial tty of the adapter directly, with no
+//! need for `slcand` or the kernel slcan driver, and reads and writes
+//! frames much like a [`CanSocket`](crate::CanSocket):
+//!
+//! ```no_run
+//! use socketcan::{
+//!     slcan::{Bitrate, SlcanPort},
+//!     CanFrame, EmbeddedFrame, StandardId,
+//! };
+//!
+//! let port = SlcanPort::open("/dev/ttyACM0").unwrap();
+//! port.set_bitrate(Bitrate::Kbps500).unwrap();
+//! port.open_channel().unwrap();
+//!
+//! let frame = CanFrame::new(StandardId::new(0x123).unwrap(), &[0xDE, 0xAD]).unwrap();
+//! port.write_frame(&frame).unwrap();
+//!
+//! let frame = port.read_frame().unwrap();
+//! println!("{}", frame);
+//! port.close_channel().unwrap();
+//! ```
+//!
+//! Along with the classic `t`, `T`, `r`, and `R` frames, the CAN FD
+//! extensions of newer adapters are supported: `d` and `D` for FD frames,
+//! and `b` and `B` for FD frames with the bitrate switch. When timestamps
+//! are enabled with `Z1`, the adapter adds a millisecond count, from 0 to
+//! 59999, to each received frame.
+
+use crate::{
+    frame::{FdFlags, CANFD_MAX_DLEN, CAN_MAX_DLEN},
+    CanAnyFrame, CanDataFrame, CanFdFrame, CanRemoteFrame, ConstructionError, EmbeddedFrame,
+    ExtendedId, FrameParseError, Id, StandardId,
+};
+use std::{convert::TryFrom, fmt::Write, time::Duration};
+
+pub mod port;
+pub use port::SlcanPort;
+
+#[cfg(feature = "netlink")]
+pub mod ldisc;
+#[cfg(feature = "netlink")]
+pub use ldisc::{AttachOptions, SlcanInterface};
+
+/// The period of the adapter timestamps, which wrap around every minute.
+pub const TIMESTAMP_PERIOD: Duration = Duration::from_secs(60);
+
+/// The data lengths of the CAN FD DLC codes above 8.
+const FD_LENGTHS: [usize; 7] = [12, 16, 20, 24, 32, 48, 64];
+
+// ===== Bitrate =====
+
+/// The standard CAN bitrates, set with the `S0` to `S8` commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Bitrate {
+    /// 10 kbit/s (`S0`)
+    Kbps10,
+    /// 20 kbit/s (`S1`)
+    Kbps20,
+    /// 50 kbit/s (`S2`)
+    Kbps50,
+    /// 100 kbit/s (`S3`)
+    Kbps100,
+    /// 125 kbit/s (`S4`)
+    Kbps125,
+    /// 250 kbit/s (`S5`)
+    Kbps250,
+    /// 500 kbit/s (`S6`)
+    Kbps500,
+    /// 800 kbit/s (`S7`)
+    Kbps800,
+    /// 1 Mbit/s (`S8`)
+    Mbps1,
+}
+
+impl Bitrate {
+    /// All of the bitrates, in the order of their command codes.
+    const ALL: [Bitrate; 9] = [
+        Bitrate::Kbps10,
+        Bitrate::Kbps20,
+        Bitrate::Kbps50,
+        Bitrate::Kbps100,
+        Bitrate::Kbps125,
+        Bitrate::Kbps250,
+        Bitrate::Kbps500,
+        Bitrate::Kbps800,
+        Bitrate::Mbps1,
+    ];
+
+    /// Gets the bitrate, in bits per second.
+    pub fn bitrate(&self) -> u32 {
+        use Bitrate::*;
+        match *self {
+            Kbps10 => 10_000,
+            Kbps20 => 20_000,
+            Kbps50 => 50_000,
+            Kbps100 => 100_000,
+            Kbps125 => 125_000,
+            Kbps250 => 250_000,
+            Kbps500 => 500_000,
+            Kbps800 => 800_000,
+            Mbps1 => 1_000_000,
+        }
+    }
+
+    /// Gets the digit of the `S` command for the bitrate.
+    pub fn code(&self) -> u8 {
+        Self::ALL.iter().position(|b| b == self).unwrap_or_default() as u8
+    }
+}
+
+impl TryFrom<u32> for Bitrate {
+    type Error = u32;
+
+    /// Gets the standard bitrate from the rate in bits per second,
+    /// returning the rate back if it isn't one of them.
+    fn try_from(bitrate: u32) -> Result<Self, Self::Error> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|b| b.bitrate() == bitrate)
+            .ok_or(bitrate)
+    }
+}
+
+// ===== Frame encoding =====
+
+/// Gets the CAN FD DLC code for the data length, rounding up.
+fn fd_dlc(len: usize) -> usize {
+    match len {
+        n if n <= CAN_MAX_DLEN => n,
+        n => 9 + FD_LENGTHS.iter().position(|&m| m >= n).unwrap_or(6),
+    }
+}
+
+/// Gets the data length of the CAN FD DLC code.
+fn fd_len(dlc: usize) -> usize {
+    match dlc {
+        n if n <= CAN_MAX_DLEN => n,
+        n => FD_LENGTHS[(n - 9).min(6)],
+    }
+}
+
+/// Writes the command letter and the ID, in three hex digits for a
+/// standard ID, or with the letter in upper case and eight digits for an
+/// extended ID.
+fn push_id(s: &mut String, cmd: char, id: Id) {
+    let _ = match id {
+        Id::Standard(id) => write!(s, "{}{:03X}", cmd, id.as_raw()),
+        Id::Extended(id) => write!(s, "{}{:08X}", cmd.to_ascii_uppercase(), id.as_raw()),
+    };
+}
+
+/// Writes the bytes as contiguous hex digits.
+fn push_hex(s: &mut String, data: &[u8]) {
+    for b in data {
+        let _ = write!(s, "{:02X}", b);
+    }
+}
+
+/// Encodes a frame as an slcan line, without the trailing carriage
+/// return.
+///
+/// Error frames can't be sent to an adapter, so they give a
+/// `WrongFrameType` error. FD frames are padded with zeros to the next
+/// valid FD length.
+pub fn encode_frame(frame: &CanAnyFrame) -> Result<String, ConstructionError> {
+    let mut s = String::with_capacity(2 * CANFD_MAX_DLEN + 10);
+    match frame {
+        CanAnyFrame::Normal(frame) => {
+            push_id(&mut s, 't', frame.id());
+            let _ = write!(s, "{}", frame.dlc());
+            push_hex(&mut s, frame.data());
+        }
+        CanAnyFrame::Remote(frame) => {
+            push_id(&mut s, 'r', frame.id());
+            let _ = write!(s, "{}", frame.dlc());
+        }
+        CanAnyFrame::Fd(frame) => {
+            let cmd = if frame.is_brs() { 'b' } else { 'd' };
+            push_id(&mut s, cmd, frame.id());
+            let dlc = fd_dlc(frame.data().len());
+            let _ = write!(s, "{:X}", dlc);
+            push_hex(&mut s, frame.data());
+            push_hex(
+                &mut s,
+                &[0u8; CANFD_MAX_DLEN][..fd_len(dlc) - frame.data().len()],
+            );
+        }
+        CanAnyFrame::Error(_) => return Err(ConstructionError::WrongFrameType),
+    }
+    Ok(s)
+}
+
+// ===== Frame decoding =====
+
+/// Parses a field of hex digits.
+fn parse_hex(s: &[u8]) -> Result<u32, FrameParseError> {
+    std::str::from_utf8(s)
+        .ok()
+        .filter(|s| !s.is_empty() && s.bytes().all(|c| c.is_ascii_hexdigit()))
+        .and_then(|s| u32::from_str_radix(s, 16).ok())
+        .ok_or(FrameParseError::InvalidFormat)
+}
+
+/// Decodes an slcan frame line, without the trailing carriage return,
+/// into the frame and the adapter timestamp in milliseconds, if it has
+/// one.
+pub fn decode_frame(line: &[u8]) -> Result<(CanAnyFrame, Option<u16>), FrameParseError> {
+    let (&cmd, rest) = line.split_first().ok_or(FrameParseError::InvalidFormat)?;
+
+    let id_len = match cmd {
+        b't' | b'r' | b'd' | b'b' => 3,
+        b'T' | b'R' | b'D' | b'B' => 8,
+        _ => return Err(FrameParseError::InvalidFormat),
+    };
+    if rest.len() < id_len + 1 {
+        return Err(FrameParseError::InvalidFormat);
+    }
+
+    let raw_id = parse_hex(&rest[..id_len])?;
+    let id: Id = if id_len == 3 {
+        StandardId::new(raw_id as u16)
+            .ok_or(ConstructionError::IDTooLarge)?
+            .into()
+    } else {
+        ExtendedId::new(raw_id)
+            .ok_or(ConstructionError::IDTooLarge)?
+            .into()
+    };
+
+    let dlc = parse_hex(&rest[id_len..id_len + 1])? as usize;
+    let fd = matches!(cmd.to_ascii_lowercase(), b'd' | b'b');
+    let len = match (cmd.to_ascii_lowercase(), dlc) {
+        (b'r', _) => 0,
+        (_, n) if fd => fd_len(n),
+        (_, n) if n <= CAN_MAX_DLEN => n,
+        _ => return Err(ConstructionError::TooMuchData.into()),
+    };
+
+    let rest = &rest[id_len + 1..];
+    let timestamp = match rest.len() {
+        n if n == 2 * len => None,
+        n if n == 2 * len + 4 => Some(parse_hex(&rest[2 * len..])? as u16),
+        _ => return Err(FrameParseError::InvalidFormat),
+    };
+
+    let mut data = [0u8; CANFD_MAX_DLEN];
+    for (i, pair) in rest[..2 * len].chunks(2).enumerate() {
+        data[i] = parse_hex(pair)? as u8;
+    }
+    let data = &data[..len];
+
+    let frame = match cmd.to_ascii_lowercase() {
+        b't' => CanDataFrame::new(id, data).map(CanAnyFrame::Normal),
+        b'r' => {
+            if dlc > CAN_MAX_DLEN {
+                return Err(ConstructionError::TooMuchData.into());
+            }
+            CanRemoteFrame::new_remote(id, dlc).map(CanAnyFrame::Remote)
+        }
+        b'b' => CanFdFrame::with_flags(id, data, FdFlags::BRS).map(CanAnyFrame::Fd),
+        _ => CanFdFrame::with_flags(id, data, FdFlags::empty()).map(CanAnyFrame::Fd),
+    }
+    .ok_or(FrameParseError::InvalidFormat)?;
+
+    Ok((frame, timestamp))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Decodes the line, checks that it encodes back the same, and gives
+    // the frame in cansend notation.
+    fn round_trip(line: &str) -> String {
+        let (frame, ts) = decode_frame(line.as_bytes()).unwrap();
+        assert_eq!(ts, None);
+        assert_eq!(encode_frame(&frame).unwrap(), line);
+        frame.to_string()
+    }
+
+    #[test]
+    fn test_bitrate() {
+        assert_eq!(Bitrate::Kbps10.code(), 0);
+        assert_eq!(Bitrate::Kbps500.code(), 6);
+        assert_eq!(Bitrate::Mbps1.code(), 8);
+        assert_eq!(Bitrate::try_from(250_000), Ok(Bitrate::Kbps250));
+        assert_eq!(Bitrate::try_from(83_333), Err(83_333));
+    }
+
+    #[test]
+    fn test_classic() {
+        assert_eq!(round_trip("t1232DEAD"), "123#DEAD");
+        assert_eq!(
+            round_trip("T1ABCDEF381122334455667788"),
+            "1ABCDEF3#1122334455667788"
+        );
+        assert_eq!(round_trip("t7FF0"), "7FF#");
+        assert_eq!(round_trip("r1004"), "100#R4");
+        assert_eq!(round_trip("R000000010"), "00000001#R");
+    }
+
+    #[test]
+    fn test_fd() {
+        let data: String = (0..12).map(|i| format!("{:02X}", i)).collect();
+        assert_eq!(
+            round_trip(&format!("d1239{}", data)),
+            format!("123##0{}", data)
+        );
+        assert_eq!(
+            round_trip(&format!("B01234567F{}", "AA".repeat(64))),
+            format!("01234567##1{}", "AA".repeat(64))
+        );
+
+        // Padded up to the next FD length
+        let frame =
+            CanFdFrame::with_flags(StandardId::new(0x10).unwrap(), &[1; 9], FdFlags::empty())
+                .unwrap();
+        let line = encode_frame(&frame.into()).unwrap();
+        assert_eq!(line, format!("d0109{}{}", "01".repeat(9), "00".repeat(3)));
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let (frame, ts) = decode_frame(b"t1232DEADEA5F").unwrap();
+        assert_eq!(frame.to_string(), "123#DEAD");
+        assert_eq!(ts, Some(59999));
+
+        let (frame, ts) = decode_frame(b"r12380001").unwrap();
+        assert_eq!(frame.to_string(), "123#R8");
+        assert_eq!(ts, Some(1));
+    }
+
+    #[test]
+    fn test_errors() {
+        for line in [
+            "",
+            "x123",
+            "t12",
+            "t1232DEA",
+            "t1232DEADBE",
+            "t12G1AA",
+            "t8001AA",
+            "T2000000011AA",
+            "t1239",
+            "r1239",
+            "d123Z",
+        ] {
+            assert!(decode_frame(line.as_bytes()).is_err(), "{}", line);
+        }
+
+        let err = CanAnyFrame::Error(crate::CanErrorFrame::new_error(0x04, &[]).unwrap());
+        assert_eq!(encode_frame(&err), Err(ConstructionError::WrongFrameType));
+    }
+}
@@ -0,0 +1,664 @@
+// socketcan/src/slcan/port.rs
+//
+// An slcan adapter on a serial tty.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! An slcan adapter on a serial tty.
+
+use super::{decode_frame, encode_frame, Bitrate};
+use crate::{link::Link, CanAnyFrame, IoError, IoErrorKind, IoResult};
+use nix::{
+    poll::{poll, PollFd, PollFlags},
+    sys::termios::{self, BaudRate, SetArg},
+};
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::{Read, Write},
+    os::unix::{
+        fs::OpenOptionsExt,
+        io::{AsRawFd, RawFd},
+    },
+    path::Path,
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+/// The default baud rate of the serial port.
+pub const DEFAULT_BAUD_RATE: u32 = 115_200;
+
+/// The default time to wait for the adapter to acknowledge a command.
+pub const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The carriage return at the end of each line, and the OK response.
+const CR: u8 = b'\r';
+
+/// The bell character that the adapter sends for an error.
+const BELL: u8 = 0x07;
+
+/// A reply line received from the adapter.
+#[derive(Debug)]
+enum Line {
+    /// The response to a command, which is empty for a simple OK, or the
+    /// acknowledgement of a transmitted frame
+    Response(Vec<u8>),
+    /// A command or transmit error
+    Error,
+}
+
+/// The received data that hasn't been returned yet.
+#[derive(Debug, Default)]
+struct RxState {
+    /// The bytes of a partial line
+    buf: Vec<u8>,
+    /// The received frames, with their timestamps
+    frames: VecDeque<(CanAnyFrame, Option<u16>)>,
+    /// The writes awaiting a reply, in the order they were sent, with the
+    /// ID of each command, or `None` for a transmitted frame or a command
+    /// that was given up on
+    awaiting: VecDeque<Option<u64>>,
+    /// The replies to commands, with the command IDs
+    replies: VecDeque<(u64, Line)>,
+    /// The ID of the next command
+    next_id: u64,
+    /// Whether a thread is currently reading the tty
+    reading: bool,
+}
+
+impl RxState {
+    /// Splits the bytes read from the tty into lines, and sorts them.
+    fn receive(&mut self, data: &[u8]) {
+        for &b in data {
+            match b {
+                CR => {
+                    let line = std::mem::take(&mut self.buf);
+                    self.dispatch(line);
+                }
+                BELL => self.dispatch(vec![BELL]),
+                b'\n' => {}
+                b => self.buf.push(b),
+            }
+        }
+    }
+
+    /// Queues a received frame, or matches a reply to the oldest write
+    /// that is awaiting one.
+    ///
+    /// The replies to transmitted frames are dropped, as are any that
+    /// weren't asked for.
+    fn dispatch(&mut self, line: Vec<u8>) {
+        match classify(line) {
+            Err(Some(frame)) => self.frames.push_back(frame),
+            Err(None) => {}
+            Ok(reply) => match self.awaiting.pop_front() {
+                Some(Some(id)) => self.replies.push_back((id, reply)),
+                Some(None) => {
+                    if let Line::Error = reply {
+                        log::debug!("slcan adapter rejected a frame or a late command");
+                    }
+                }
+                None => log::debug!("Unexpected slcan reply: {:?}", reply),
+            },
+        }
+    }
+}
+
+/// An slcan adapter on a serial port.
+///
+/// The adapter is configured with the bitrate and options while the CAN
+/// channel is closed, then the channel is opened to send and receive
+/// frames.
+///
+/// The commands, reads, and writes can be made from multiple threads.
+/// Only one thread reads the tty at a time, and it passes on the frames
+/// and command replies that it receives to the others, so a command can
+/// be sent while another thread is blocked reading frames.
+#[derive(Debug)]
+pub struct SlcanPort {
+    file: File,
+    rx: Mutex<RxState>,
+    rx_cond: Condvar,
+    cmd_timeout: Duration,
+}
+
+impl SlcanPort {
+    /// Opens the serial tty of an adapter, at the default baud rate of
+    /// 115200.
+    ///
+    /// This doesn't send any commands to the adapter.
+    pub fn open<P: AsRef<Path>>(path: P) -> IoResult<Self> {
+        Self::open_with_baud(path, DEFAULT_BAUD_RATE)
+    }
+
+    /// Opens the serial tty of an adapter, at the baud rate.
+    ///
+    /// The tty is put into raw mode. The baud rate doesn't matter for
+    /// most USB adapters, but must match the adapter for a true serial
+    /// port. A non-standard baud rate gives an `InvalidInput` error.
+    pub fn open_with_baud<P: AsRef<Path>>(path: P, baud: u32) -> IoResult<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(path)?;
+
+        let baud = baud_rate(baud).ok_or_else(|| IoError::from(IoErrorKind::InvalidInput))?;
+        let mut tio = termios::tcgetattr(file.as_raw_fd())?;
+        termios::cfmakeraw(&mut tio);
+        termios::cfsetspeed(&mut tio, baud)?;
+        termios::tcsetattr(file.as_raw_fd(), SetArg::TCSANOW, &tio)?;
+        termios::tcflush(file.as_raw_fd(), termios::FlushArg::TCIOFLUSH)?;
+
+        Ok(Self {
+            file,
+            rx: Mutex::new(RxState::default()),
+            rx_cond: Condvar::new(),
+            cmd_timeout: DEFAULT_COMMAND_TIMEOUT,
+        })
+    }
+
+    /// Sets the time to wait for the adapter to acknowledge a command.
+    pub fn command_timeout(mut self, timeout: Duration) -> Self {
+        self.cmd_timeout = timeout;
+        self
+    }
+
+    /// Consumes the port, returning the tty.
+    #[cfg(feature = "netlink")]
+    pub(crate) fn into_file(self) -> File {
+        self.file
+    }
+
+    // ----- Commands -----
+
+    /// Sends a raw command, like `V` or `Y2`, and waits for the response.
+    ///
+    /// Returns the response line, which is empty if the adapter just
+    /// acknowledged the command. A command that the adapter rejects gives
+    /// an `InvalidInput` error, and one that it doesn't acknowledge within
+    /// the command timeout gives a `TimedOut` error.
+    pub fn command(&self, cmd: &str) -> IoResult<String> {
+        let id = {
+            let mut rx = self.rx.lock().unwrap();
+            self.write_line(cmd.as_bytes())?;
+            let id = rx.next_id;
+            rx.next_id += 1;
+            rx.awaiting.push_back(Some(id));
+            id
+        };
+
+        let deadline = Instant::now() + self.cmd_timeout;
+        let reply = self.wait_for(Some(deadline), |rx| {
+            let pos = rx
+                .replies
+                .iter()
+                .position(|(reply_id, _)| *reply_id == id)?;
+            rx.replies.remove(pos).map(|(_, line)| line)
+        });
+        if !matches!(reply, Ok(Some(_))) {
+            // Drop the reply if it ever arrives
+            let mut rx = self.rx.lock().unwrap();
+            if let Some(entry) = rx.awaiting.iter_mut().find(|entry| **entry == Some(id)) {
+                *entry = None;
+            }
+            rx.replies.retain(|(reply_id, _)| *reply_id != id);
+        }
+
+        match reply? {
+            Some(Line::Response(resp)) => Ok(String::from_utf8_lossy(&resp).into_owned()),
+            Some(Line::Error) => Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                format!("slcan command '{}' rejected", cmd),
+            )),
+            None => Err(IoErrorKind::TimedOut.into()),
+        }
+    }
+
+    /// Sets the bitrate of the CAN channel, with the `S0` to `S8`
+    /// commands.
+    pub fn set_bitrate(&self, bitrate: Bitrate) -> IoResult<()> {
+        self.command(&format!("S{}", bitrate.code())).map(|_| ())
+    }
+
+    /// Sets the bit timing registers of an SJA1000 controller at 16 MHz
+    /// directly, with the `s` command, for a non-standard bitrate.
+    pub fn set_btr(&self, btr0: u8, btr1: u8) -> IoResult<()> {
+        self.command(&format!("s{:02X}{:02X}", btr0, btr1))
+            .map(|_| ())
+    }
+
+    /// Turns the timestamps on received frames on or off, with the `Z`
+    /// command.
+    pub fn set_timestamps(&self, on: bool) -> IoResult<()> {
+        self.command(if on { "Z1" } else { "Z0" }).map(|_| ())
+    }
+
+    /// Opens the CAN channel, with the `O` command.
+    pub fn open_channel(&self) -> IoResult<()> {
+        self.command("O").map(|_| ())
+    }
+
+    /// Opens the CAN channel in listen-only mode, with the `L` command.
+    pub fn open_listen_only(&self) -> IoResult<()> {
+        self.command("L").map(|_| ())
+    }
+
+    /// Closes the CAN channel, with the `C` command.
+    pub fn close_channel(&self) -> IoResult<()> {
+        self.command("C").map(|_| ())
+    }
+
+    /// Gets the hardware and software version of the adapter, with the
+    /// `V` command.
+    pub fn version(&self) -> IoResult<String> {
+        self.command("V")
+            .map(|resp| resp.trim_start_matches('V').into())
+    }
+
+    /// Gets the serial number of the adapter, with the `N` command.
+    pub fn serial_number(&self) -> IoResult<String> {
+        self.command("N")
+            .map(|resp| resp.trim_start_matches('N').into())
+    }
+
+    /// Gets the status flags of the adapter, with the `F` command.
+    ///
+    /// The bits are the receive and transmit queue full flags, the error
+    /// warning, data overrun, error passive, arbitration lost, and bus
+    /// error flags, from bit 0 to bit 7.
+    pub fn status_flags(&self) -> IoResult<u8> {
+        let resp = self.command("F")?;
+        resp.strip_prefix('F')
+            .and_then(|flags| u8::from_str_radix(flags, 16).ok())
+            .ok_or_else(|| IoError::new(IoErrorKind::InvalidData, resp))
+    }
+
+    // ----- Frames -----
+
+    /// Blocking read a single frame.
+    pub fn read_frame(&self) -> IoResult<CanAnyFrame> {
+        self.read_timestamped(None).map(|(frame, _)| frame)
+    }
+
+    /// Blocking read a single frame, with a timeout.
+    ///
+    /// This gives a `TimedOut` error if no frame arrived in time.
+    pub fn read_frame_timeout(&self, timeout: Duration) -> IoResult<CanAnyFrame> {
+        self.read_timestamped(Some(timeout)).map(|(frame, _)| frame)
+    }
+
+    /// Reads a single frame, with the adapter's timestamp if timestamps
+    /// are on, waiting for up to the timeout, if any.
+    ///
+    /// The timestamp is the time within the minute, in milliseconds, as
+    /// the adapter's clock wraps every [`TIMESTAMP_PERIOD`](super::TIMESTAMP_PERIOD).
+    pub fn read_timestamped(
+        &self,
+        timeout: Option<Duration>,
+    ) -> IoResult<(CanAnyFrame, Option<Duration>)> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let (frame, ts) = self
+            .wait_for(deadline, |rx| rx.frames.pop_front())?
+            .ok_or_else(|| IoError::from(IoErrorKind::TimedOut))?;
+        let ts = ts.map(|ms| Duration::from_millis(ms.into()));
+        Ok((frame, ts))
+    }
+
+    /// Writes a single frame.
+    ///
+    /// This doesn't wait for the adapter to acknowledge the frame. Its
+    /// reply, `z`, `Z`, or the bell if the adapter rejected the frame, is
+    /// dropped when it arrives, so that it isn't taken as the reply to a
+    /// later command.
+    ///
+    /// Error frames can't be sent, and give an `InvalidInput` error.
+    pub fn write_frame<F>(&self, frame: &F) -> IoResult<()>
+    where
+        F: Into<CanAnyFrame> + Clone,
+    {
+        let line = encode_frame(&frame.clone().into())
+            .map_err(|err| IoError::new(IoErrorKind::InvalidInput, err))?;
+        let mut rx = self.rx.lock().unwrap();
+        self.write_line(line.as_bytes())?;
+        rx.awaiting.push_back(None);
+        Ok(())
+    }
+
+    // ----- I/O -----
+
+    /// Writes a line, adding the carriage return.
+    fn write_line(&self, line: &[u8]) -> IoResult<()> {
+        let mut buf = Vec::with_capacity(line.len() + 1);
+        buf.extend_from_slice(line);
+        buf.push(CR);
+        (&self.file).write_all(&buf)
+    }
+
+    /// Waits until `take` gets a result from the received data, reading
+    /// the tty until the deadline, if any.
+    ///
+    /// Returns `None` if the deadline passed first. Only one thread reads
+    /// the tty at a time, without holding the lock, and the others wait
+    /// for it to sort what it received.
+    fn wait_for<T, F>(&self, deadline: Option<Instant>, mut take: F) -> IoResult<Option<T>>
+    where
+        F: FnMut(&mut RxState) -> Option<T>,
+    {
+        let mut rx = self.rx.lock().unwrap();
+        loop {
+            if let Some(val) = take(&mut rx) {
+                return Ok(Some(val));
+            }
+
+            let timeout = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(timeout) if !timeout.is_zero() => Some(timeout),
+                    _ => return Ok(None),
+                },
+                None => None,
+            };
+
+            if rx.reading {
+                rx = match timeout {
+                    Some(timeout) => self.rx_cond.wait_timeout(rx, timeout).unwrap().0,
+                    None => self.rx_cond.wait(rx).unwrap(),
+                };
+                continue;
+            }
+
+            rx.reading = true;
+            drop(rx);
+            let res = self.read_tty(timeout);
+            rx = self.rx.lock().unwrap();
+            rx.reading = false;
+            self.rx_cond.notify_all();
+
+            if let Some(data) = res? {
+                rx.receive(&data);
+            }
+        }
+    }
+
+    /// Reads whatever is available from the tty, waiting for up to the
+    /// timeout, if any.
+    ///
+    /// Returns `None` if nothing arrived in time.
+    fn read_tty(&self, timeout: Option<Duration>) -> IoResult<Option<Vec<u8>>> {
+        let timeout = match timeout {
+            Some(timeout) => timeout.as_millis().clamp(1, i32::MAX as u128) as i32,
+            None => -1,
+        };
+        let mut fds = [PollFd::new(self.file.as_raw_fd(), PollFlags::POLLIN)];
+        match poll(&mut fds, timeout) {
+            Ok(0) | Err(nix::errno::Errno::EINTR) => return Ok(None),
+            Ok(_) => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        let mut buf = [0u8; 256];
+        let n = (&self.file).read(&mut buf)?;
+        if n == 0 {
+            return Err(IoErrorKind::UnexpectedEof.into());
+        }
+        Ok(Some(buf[..n].to_vec()))
+    }
+}
+
+/// Gets the termios speed for a baud rate.
+fn baud_rate(baud: u32) -> Option<BaudRate> {
+    use BaudRate::*;
+    let rate = match baud {
+        9600 => B9600,
+        19_200 => B19200,
+        38_400 => B38400,
+        57_600 => B57600,
+        115_200 => B115200,
+        230_400 => B230400,
+        460_800 => B460800,
+        500_000 => B500000,
+        576_000 => B576000,
+        921_600 => B921600,
+        1_000_000 => B1000000,
+        1_152_000 => B1152000,
+        1_500_000 => B1500000,
+        2_000_000 => B2000000,
+        2_500_000 => B2500000,
+        3_000_000 => B3000000,
+        3_500_000 => B3500000,
+        4_000_000 => B4000000,
+        _ => return None,
+    };
+    Some(rate)
+}
+
+/// Sorts a line from the adapter into a reply to a command or a
+/// transmitted frame, or else a received frame.
+///
+/// Malformed frames are dropped, giving `Err(None)`.
+fn classify(line: Vec<u8>) -> Result<Line, Option<(CanAnyFrame, Option<u16>)>> {
+    match line.first() {
+        Some(&BELL) => Ok(Line::Error),
+        Some(b't' | b'T' | b'r' | b'R' | b'd' | b'D' | b'b' | b'B') => match decode_frame(&line) {
+            Ok(frame) => Err(Some(frame)),
+            Err(err) => {
+                log::debug!(
+                    "Bad slcan frame '{}': {}",
+                    String::from_utf8_lossy(&line),
+                    err
+                );
+                Err(None)
+            }
+        },
+        _ => Ok(Line::Response(line)),
+    }
+}
+
+impl AsRawFd for SlcanPort {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl Link for SlcanPort {
+    fn send(&self, frame: &CanAnyFrame) -> IoResult<()> {
+        self.write_frame(frame)
+    }
+
+    fn recv(&self, timeout: Option<Duration>) -> IoResult<Option<CanAnyFrame>> {
+        match self.read_timestamped(timeout) {
+            Ok((frame, _)) => Ok(Some(frame)),
+            Err(err) if err.kind() == IoErrorKind::TimedOut => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+/// Helpers for the tests that talk to a scripted adapter on a pty.
+pub(crate) mod tests {
+    use super::*;
+    use crate::{CanFrame, EmbeddedFrame, StandardId};
+    use nix::{
+        fcntl::OFlag,
+        pty::{grantpt, posix_openpt, ptsname_r, unlockpt, PtyMaster},
+    };
+    use std::thread;
+
+    // Opens a pty pair, with the port on the slave side, and the master
+    // side standing in for the adapter.
+    pub(crate) fn pty() -> (SlcanPort, PtyMaster) {
+        let master = posix_openpt(OFlag::O_RDWR | OFlag::O_NOCTTY).unwrap();
+        grantpt(&master).unwrap();
+        unlockpt(&master).unwrap();
+        let path = ptsname_r(&master).unwrap();
+        let port = SlcanPort::open(path)
+            .unwrap()
+            .command_timeout(Duration::from_millis(200));
+        (port, master)
+    }
+
+    // Runs a scripted adapter on the pty, which answers each command line
+    // with the responses from the handler, until the port goes quiet.
+    pub(crate) fn adapter<F>(mut pty: PtyMaster, mut handler: F) -> thread::JoinHandle<Vec<String>>
+    where
+        F: FnMut(&str) -> Vec<u8> + Send + 'static,
+    {
+        thread::spawn(move || {
+            let mut lines = Vec::new();
+            let mut line = Vec::new();
+            let mut buf = [0u8; 256];
+            loop {
+                let mut fds = [PollFd::new(pty.as_raw_fd(), PollFlags::POLLIN)];
+                if poll(&mut fds, 300).unwrap_or(0) == 0 {
+                    return lines;
+                }
+                let n = match pty.read(&mut buf) {
+                    Ok(n) if n > 0 => n,
+                    _ => return lines,
+                };
+                for &b in &buf[..n] {
+                    if b == CR {
+                        let cmd = String::from_utf8(std::mem::take(&mut line)).unwrap();
+                        let resp = handler(&cmd);
+                        pty.write_all(&resp).unwrap();
+                        lines.push(cmd);
+                    } else {
+                        line.push(b);
+                    }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_commands() {
+        let (port, pty) = pty();
+        let adapter = adapter(pty, |cmd| match cmd {
+            "V" => b"V1013\r".to_vec(),
+            "N" => b"NA123\r".to_vec(),
+            "F" => b"F08\r".to_vec(),
+            "S9" => vec![BELL],
+            // A frame arrives ahead of the acknowledgement
+            "O" => b"t1232DEAD\r\r".to_vec(),
+            _ => b"\r".to_vec(),
+        });
+
+        port.set_bitrate(Bitrate::Kbps500).unwrap();
+        port.set_btr(0x03, 0x1C).unwrap();
+        assert_eq!(port.version().unwrap(), "1013");
+        assert_eq!(port.serial_number().unwrap(), "A123");
+        assert_eq!(port.status_flags().unwrap(), 0x08);
+        let err = port.command("S9").unwrap_err();
+        assert_eq!(err.kind(), IoErrorKind::InvalidInput);
+
+        port.set_timestamps(true).unwrap();
+        port.open_channel().unwrap();
+        let frame = port.read_frame_timeout(Duration::from_millis(100)).unwrap();
+        assert_eq!(frame.to_string(), "123#DEAD");
+        port.close_channel().unwrap();
+
+        drop(port);
+        assert_eq!(
+            adapter.join().unwrap(),
+            ["S6", "s031C", "V", "N", "F", "S9", "Z1", "O", "C"]
+        );
+    }
+
+    #[test]
+    fn test_frames() {
+        let (port, pty) = pty();
+        let adapter = adapter(pty, |cmd| match cmd {
+            "O" => b"\r".to_vec(),
+            // Echo the frames back with a timestamp, after the ack
+            cmd => format!("z\rZ\r{}1234\rxyz\r", cmd).into_bytes(),
+        });
+        port.open_channel().unwrap();
+
+        let frame = CanFrame::new(StandardId::new(0x7FF).unwrap(), &[1, 2, 3]).unwrap();
+        port.write_frame(&frame).unwrap();
+        let fd: CanAnyFrame = "12345678##1".parse().unwrap();
+        port.write_frame(&fd).unwrap();
+
+        let (frame, ts) = port
+            .read_timestamped(Some(Duration::from_millis(100)))
+            .unwrap();
+        assert_eq!(frame.to_string(), "7FF#010203");
+        assert_eq!(ts, Some(Duration::from_millis(0x1234)));
+
+        let frame = port.read_frame_timeout(Duration::from_millis(100)).unwrap();
+        assert_eq!(frame.to_string(), "12345678##1");
+
+        let err = port
+            .read_frame_timeout(Duration::from_millis(20))
+            .unwrap_err();
+        assert_eq!(err.kind(), IoErrorKind::TimedOut);
+
+        drop(port);
+        assert_eq!(adapter.join().unwrap(), ["O", "t7FF3010203", "B123456780"]);
+    }
+
+    #[test]
+    fn test_transmit_replies() {
+        let (port, pty) = pty();
+        let adapter = adapter(pty, |cmd| match cmd {
+            // The bus is off, so the frame is rejected
+            "t1231AA" => vec![BELL],
+            "t4561BB" => b"z\r".to_vec(),
+            "V" => b"V1013\r".to_vec(),
+            _ => b"\r".to_vec(),
+        });
+
+        let frame = CanFrame::new(StandardId::new(0x123).unwrap(), &[0xAA]).unwrap();
+        port.write_frame(&frame).unwrap();
+        port.open_channel().unwrap();
+
+        let frame = CanFrame::new(StandardId::new(0x456).unwrap(), &[0xBB]).unwrap();
+        port.write_frame(&frame).unwrap();
+        assert_eq!(port.version().unwrap(), "1013");
+
+        drop(port);
+        assert_eq!(adapter.join().unwrap(), ["t1231AA", "O", "t4561BB", "V"]);
+    }
+
+    #[test]
+    fn test_concurrent_read() {
+        let (port, pty) = pty();
+        let adapter = adapter(pty, |cmd| match cmd {
+            "V" => b"V1013\r".to_vec(),
+            "O" => b"\rt1232DEAD\r".to_vec(),
+            _ => b"\r".to_vec(),
+        });
+        let port = std::sync::Arc::new(port);
+
+        let reader = {
+            let port = port.clone();
+            thread::spawn(move || port.read_frame().unwrap())
+        };
+        thread::sleep(Duration::from_millis(20));
+
+        // The reader is blocked in the tty, but passes on the replies
+        assert_eq!(port.version().unwrap(), "1013");
+        port.open_channel().unwrap();
+        assert_eq!(reader.join().unwrap().to_string(), "123#DEAD");
+
+        drop(port);
+        assert_eq!(adapter.join().unwrap(), ["V", "O"]);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let (port, _pty) = pty();
+        let start = Instant::now();
+        let err = port.open_channel().unwrap_err();
+        assert_eq!(err.kind(), IoErrorKind::TimedOut);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+}
@@ -21,7 +21,7 @@ use socket2::SockAddr;
 use std::{
     fmt,
     io::{Read, Write},
-    mem,
+    mem::{size_of, size_of_val},
     os::{
         raw::{c_int, c_void},
         unix::io::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd},
@@ -112,7 +112,7 @@ pub fn set_socket_option<T>(fd: c_int, level: c_int, name: c_int, val: &T) -> Io
             level,
             name,
             val as *const _ as *const c_void,
-            mem::size_of::<T>() as socklen_t,
+            size_of::<T>() as socklen_t,
         )
     };
 
@@ -140,7 +140,7 @@ pub fn set_socket_option_mult<T>(
                 level,
                 name,
                 values.as_ptr().cast(),
-                mem::size_of_val(values) as socklen_t,
+                size_of_val(values) as socklen_t,
             )
         }
     };
@@ -262,9 +262,6 @@ pub trait Socket: AsRawFd {
     /// Note that this function can fail with an `EAGAIN` error or similar.
     /// Use `write_frame_insist` if you need to be sure that the message got
     /// sent or failed.
-    //fn write_frame(&self, frame: &Self::FrameType) -> IoResult<()>;
-
-    /// Writes a normal CAN 2.0 frame to the socket.
     fn write_frame<F>(&self, frame: &F) -> IoResult<()>
     where
         F: Into<Self::FrameType> + AsPtr;
@@ -313,7 +310,7 @@ pub trait SocketOptions: AsRawFd {
                 level,
                 name,
                 val as *const _ as *const c_void,
-                mem::size_of::<T>() as socklen_t,
+                size_of::<T>() as socklen_t,
             )
         };
 
@@ -335,7 +332,7 @@ pub trait SocketOptions: AsRawFd {
                     level,
                     name,
                     values.as_ptr().cast(),
-                    mem::size_of_val(values) as socklen_t,
+                    size_of_val(values) as socklen_t,
                 )
             }
         };
@@ -591,7 +588,7 @@ impl CanFdSocket {
                 SOL_CAN_RAW,
                 CAN_RAW_FD_FRAMES,
                 &enable as *const _ as *const c_void,
-                mem::size_of::<c_int>() as u32,
+                size_of::<c_int>() as u32,
             )
         };
 
@@ -743,3 +740,32 @@ impl AsRef<libc::can_filter> for CanFilter {
         &self.0
     }
 }
+
+// The serde representation of a filter, with hex ID and mask.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CanFilterRepr {
+    #[serde(with = "crate::frame::hex_id")]
+    id: canid_t,
+    #[serde(with = "crate::frame::hex_id")]
+    mask: canid_t,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for CanFilter {
+    fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        CanFilterRepr {
+            id: self.0.can_id,
+            mask: self.0.can_mask,
+        }
+        .serialize(ser)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for CanFilter {
+    fn deserialize<D: serde::Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
+        let repr = CanFilterRepr::deserialize(de)?;
+        Ok(Self::new(repr.id, repr.mask))
+    }
+}
@@ -0,0 +1,162 @@
+// socketcan/src/socketcand/beacon.rs
+//
+// UDP discovery beacons for socketcand servers.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! UDP discovery beacons for socketcand servers.
+//!
+//! A socketcand server announces itself by broadcasting a small XML
+//! document to UDP port 42000 every couple of seconds, with the URL of
+//! the server and the names of its buses:
+//!
+//! ```text
+//! <CANBeacon name="bench1" type="SocketCAN" description="socketcand">
+//! <URL>can://192.168.1.20:29536</URL>
+//! <Bus name="can0"/>
+//! </CANBeacon>
+//! ```
+//!
+//! Clients like Kayak listen for these to list the servers on the local
+//! network.
+
+use super::BEACON_PORT;
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+    thread,
+    time::Duration,
+};
+
+/// The default time between beacons.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Escapes text for an XML attribute or element.
+fn escape(s: &str) -> String {
+    let mut esc = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => esc.push_str("&amp;"),
+            '<' => esc.push_str("&lt;"),
+            '>' => esc.push_str("&gt;"),
+            '"' => esc.push_str("&quot;"),
+            c => esc.push(c),
+        }
+    }
+    esc
+}
+
+/// The discovery beacon of a socketcand server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Beacon {
+    name: String,
+    description: String,
+    addr: SocketAddr,
+    buses: Vec<String>,
+    interval: Duration,
+}
+
+impl Beacon {
+    /// Creates the beacon for a server with the given name, that clients
+    /// reach at `addr`.
+    pub fn new(name: &str, addr: SocketAddr, buses: &[&str]) -> Self {
+        Self {
+            name: name.into(),
+            description: "socketcand".into(),
+            addr,
+            buses: buses.iter().map(|bus| bus.to_string()).collect(),
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+
+    /// Sets the description of the server.
+    pub fn description(mut self, desc: &str) -> Self {
+        self.description = desc.into();
+        self
+    }
+
+    /// Sets the time between beacons when the beacon is run.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Gets the address that the beacon advertises.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Creates the XML document of the beacon.
+    pub fn to_xml(&self) -> String {
+        let mut xml = format!(
+            "<CANBeacon name=\"{}\" type=\"SocketCAN\" description=\"{}\">\n\
+             <URL>can://{}</URL>\n",
+            escape(&self.name),
+            escape(&self.description),
+            self.addr
+        );
+        for bus in &self.buses {
+            xml.push_str(&format!("<Bus name=\"{}\"/>\n", escape(bus)));
+        }
+        xml.push_str("</CANBeacon>\n");
+        xml
+    }
+
+    /// Sends the beacon once, from the socket to the destination.
+    pub fn send_to<A: ToSocketAddrs>(&self, sock: &UdpSocket, dest: A) -> io::Result<()> {
+        sock.send_to(self.to_xml().as_bytes(), dest).map(|_| ())
+    }
+
+    /// Broadcasts the beacon to the local network, forever.
+    ///
+    /// This blocks the calling thread, so it is normally run on a thread
+    /// of its own. It only returns if a beacon can't be sent.
+    pub fn run(&self) -> io::Result<()> {
+        let sock = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        sock.set_broadcast(true)?;
+        loop {
+            self.send_to(&sock, (Ipv4Addr::BROADCAST, BEACON_PORT))?;
+            thread::sleep(self.interval);
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xml() {
+        let addr: SocketAddr = "192.168.1.20:29536".parse().unwrap();
+        let beacon = Beacon::new("bench<1>", addr, &["can0", "vcan1"]).description("Lab & bench");
+        assert_eq!(
+            beacon.to_xml(),
+            "<CANBeacon name=\"bench&lt;1&gt;\" type=\"SocketCAN\" description=\"Lab &amp; bench\">\n\
+             <URL>can://192.168.1.20:29536</URL>\n\
+             <Bus name=\"can0\"/>\n\
+             <Bus name=\"vcan1\"/>\n\
+             </CANBeacon>\n"
+        );
+    }
+
+    #[test]
+    fn test_send() {
+        let rx = UdpSocket::bind("127.0.0.1:0").unwrap();
+        rx.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        let tx = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        let beacon = Beacon::new("bench1", "127.0.0.1:29536".parse().unwrap(), &["can0"]);
+        beacon.send_to(&tx, rx.local_addr().unwrap()).unwrap();
+
+        let mut buf = [0u8; 512];
+        let n = rx.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], beacon.to_xml().as_bytes());
+    }
+}
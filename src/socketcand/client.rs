@@ -0,0 +1,453 @@
+// socketcan/src/socketcand/client.rs
+//
+// A socketcand client.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! A client for a bus on a socketcand server.
+//!
+//! The [`Client`] opens a bus on a server in raw mode, and then reads and
+//! writes frames much like a local [`CanFdSocket`](crate::CanFdSocket):
+//!
+//! ```no_run
+//! use socketcan::{socketcand::Client, CanFrame, EmbeddedFrame, StandardId};
+//!
+//! let client = Client::connect("bench1:29536", "can0").unwrap();
+//! client.set_filters(&[(0x100, 0x700)]);
+//!
+//! let frame = CanFrame::new(StandardId::new(0x123).unwrap(), &[0xDE, 0xAD]).unwrap();
+//! client.write_frame(frame).unwrap();
+//!
+//! let frame = client.read_frame().unwrap();
+//! println!("{}", frame);
+//! ```
+//!
+//! The protocol has no filters in raw mode, so the server sends every
+//! frame on the bus, and the filters are applied by the client, with the
+//! same rules as the kernel's `CAN_RAW_FILTER`.
+//!
+//! It also implements [`Link`], so the ISO-TP, UDS, and XCP clients run
+//! over a remote bus.
+
+use super::{frame_parts, Elements, Mode, Request, Response};
+use crate::{frame::id_to_canid_t, link::Link, CanAnyFrame, CanFilter};
+use libc::CAN_INV_FILTER;
+use std::{
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// The time to wait for the connection to the server, and for each of its
+/// replies while connecting.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connects to the first of the addresses that accepts, waiting up to
+/// [`CONNECT_TIMEOUT`] for each of them.
+fn connect_stream<A: ToSocketAddrs>(addr: A) -> io::Result<TcpStream> {
+    let mut res = Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "no address to connect to",
+    ));
+    for addr in addr.to_socket_addrs()? {
+        res = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT);
+        if res.is_ok() {
+            break;
+        }
+    }
+    res
+}
+
+/// Determines if a frame passes any of the filters.
+pub(crate) fn filter_matches(filters: &[CanFilter], frame: &CanAnyFrame) -> bool {
+    let id = match frame_parts(frame) {
+        Some((id, _)) => id_to_canid_t(id),
+        None => return false,
+    };
+    filters.iter().any(|filter| {
+        let filter = filter.as_ref();
+        let inverted = filter.can_id & CAN_INV_FILTER != 0;
+        let filter_id = filter.can_id & !CAN_INV_FILTER;
+        ((id & filter.can_mask) == (filter_id & filter.can_mask)) != inverted
+    })
+}
+
+/// The filters that pass every frame.
+pub(crate) fn accept_all() -> Vec<CanFilter> {
+    vec![CanFilter::new(0, 0)]
+}
+
+/// Formats the element to send a frame.
+pub(crate) fn send_element(frame: &CanAnyFrame) -> io::Result<String> {
+    match frame_parts(frame) {
+        Some(_) => Ok(Request::Send(*frame).to_string()),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "only data frames can be sent",
+        )),
+    }
+}
+
+/// Converts an error reply from the server.
+fn server_error(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, msg)
+}
+
+/// Checks the server's reply to a step of the handshake.
+pub(crate) fn expect(elem: &str, expected: &str) -> io::Result<()> {
+    match elem.parse::<Response>() {
+        Ok(Response::Error(msg)) => Err(server_error(msg)),
+        _ if elem == expected => Ok(()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected reply from server: < {} >", elem),
+        )),
+    }
+}
+
+/// The requests that open the bus in raw mode, after the greeting, with
+/// the reply expected for each.
+pub(crate) fn handshake(bus: &str) -> [(String, &'static str); 2] {
+    [
+        (Request::Open(bus.into()).to_string(), "ok"),
+        (Request::Mode(Mode::Raw).to_string(), "ok"),
+    ]
+}
+
+/// Handles an element received in raw mode, returning the frame and its
+/// timestamp if it passes the filters, or the error from the server.
+pub(crate) fn receive(
+    elem: &str,
+    filters: &[CanFilter],
+) -> Option<io::Result<(CanAnyFrame, Duration)>> {
+    match elem.parse::<Response>() {
+        Ok(Response::Frame { frame, timestamp }) => {
+            filter_matches(filters, &frame).then_some(Ok((frame, timestamp)))
+        }
+        Ok(Response::Error(msg)) => Some(Err(server_error(msg))),
+        _ => None,
+    }
+}
+
+// ===== Client =====
+
+/// A client for a bus on a socketcand server, in raw mode.
+#[derive(Debug)]
+pub struct Client {
+    bus: String,
+    rx: Mutex<(TcpStream, Elements)>,
+    tx: Mutex<TcpStream>,
+    filters: Mutex<Vec<CanFilter>>,
+}
+
+impl Client {
+    /// Connects to the server at the address, and opens the named bus.
+    pub fn connect<A: ToSocketAddrs>(addr: A, bus: &str) -> io::Result<Self> {
+        let stream = connect_stream(addr)?;
+        stream.set_nodelay(true)?;
+
+        let client = Self {
+            bus: bus.into(),
+            tx: Mutex::new(stream.try_clone()?),
+            rx: Mutex::new((stream, Elements::default())),
+            filters: Mutex::new(accept_all()),
+        };
+
+        expect(&client.recv_element(CONNECT_TIMEOUT)?, "hi")?;
+        for (req, reply) in handshake(bus) {
+            client.send_element(&req)?;
+            expect(&client.recv_element(CONNECT_TIMEOUT)?, reply)?;
+        }
+        Ok(client)
+    }
+
+    /// Gets the name of the bus on the server.
+    pub fn bus(&self) -> &str {
+        &self.bus
+    }
+
+    /// Sets the filters for the frames to receive.
+    ///
+    /// A frame is received if it passes any of the filters.
+    pub fn set_filters<F>(&self, filters: &[F])
+    where
+        F: Into<CanFilter> + Copy,
+    {
+        *self.filters.lock().unwrap() = filters.iter().map(|&f| f.into()).collect();
+    }
+
+    /// Receives every frame, disabling any filtering.
+    pub fn set_filter_accept_all(&self) {
+        *self.filters.lock().unwrap() = accept_all();
+    }
+
+    /// Receives no frames.
+    pub fn set_filter_drop_all(&self) {
+        self.filters.lock().unwrap().clear();
+    }
+
+    /// Sends an element to the server.
+    fn send_element(&self, elem: &str) -> io::Result<()> {
+        self.tx.lock().unwrap().write_all(elem.as_bytes())
+    }
+
+    /// Reads data from the server until `f` accepts an element, or the
+    /// timeout expires.
+    fn read_until<T, F>(&self, timeout: Option<Duration>, mut f: F) -> io::Result<T>
+    where
+        F: FnMut(String) -> Option<io::Result<T>>,
+    {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut rx = self.rx.lock().unwrap();
+        let (stream, elems) = &mut *rx;
+        let mut buf = [0u8; 1024];
+
+        loop {
+            while let Some(elem) = elems.next_element() {
+                if let Some(res) = f(elem) {
+                    return res;
+                }
+            }
+
+            let wait = match deadline {
+                Some(deadline) => {
+                    let wait = deadline.saturating_duration_since(Instant::now());
+                    if wait.is_zero() {
+                        return Err(io::ErrorKind::TimedOut.into());
+                    }
+                    Some(wait)
+                }
+                None => None,
+            };
+            stream.set_read_timeout(wait)?;
+
+            match stream.read(&mut buf) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => elems.push(&buf[..n]),
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Reads the next element from the server.
+    fn recv_element(&self, timeout: Duration) -> io::Result<String> {
+        self.read_until(Some(timeout), |elem| Some(Ok(elem)))
+    }
+
+    /// Blocking read of the next frame that passes the filters, with the
+    /// time that the server received it, since the Unix epoch.
+    ///
+    /// If the timeout expires first, this fails with a `TimedOut` error.
+    /// An error reported by the server, such as a failure of its bus, is
+    /// returned as an error of kind `Other`.
+    pub fn read_timestamped(
+        &self,
+        timeout: Option<Duration>,
+    ) -> io::Result<(CanAnyFrame, Duration)> {
+        let filters = self.filters.lock().unwrap().clone();
+        self.read_until(timeout, |elem| receive(&elem, &filters))
+    }
+
+    /// Blocking read of the next frame that passes the filters.
+    pub fn read_frame(&self) -> io::Result<CanAnyFrame> {
+        self.read_timestamped(None).map(|(frame, _)| frame)
+    }
+
+    /// Blocking read of the next frame that passes the filters, with a
+    /// timeout.
+    pub fn read_frame_timeout(&self, timeout: Duration) -> io::Result<CanAnyFrame> {
+        self.read_timestamped(Some(timeout)).map(|(frame, _)| frame)
+    }
+
+    /// Writes a data frame to the bus.
+    ///
+    /// Remote and error frames can't be sent over the protocol, so they
+    /// fail with an `InvalidInput` error.
+    pub fn write_frame<F: Into<CanAnyFrame>>(&self, frame: F) -> io::Result<()> {
+        self.send_element(&send_element(&frame.into())?)
+    }
+}
+
+impl Link for Client {
+    fn send(&self, frame: &CanAnyFrame) -> io::Result<()> {
+        self.write_frame(*frame)
+    }
+
+    fn recv(&self, timeout: Option<Duration>) -> io::Result<Option<CanAnyFrame>> {
+        match self.read_timestamped(timeout) {
+            Ok((frame, _)) => Ok(Some(frame)),
+            Err(err) if err.kind() == io::ErrorKind::TimedOut => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        link::{VirtualBus, VirtualLink},
+        socketcand::Server,
+    };
+    use std::{net::SocketAddr, thread};
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// Starts a server for the bus, as `vcan0`.
+    fn start(bus: &VirtualBus) -> SocketAddr {
+        let bus = bus.clone();
+        let server = Server::with_opener("127.0.0.1:0", &["vcan0"], move |_| {
+            Ok(Box::new(bus.connect()) as Box<dyn Link + Send + Sync>)
+        })
+        .unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.serve());
+        addr
+    }
+
+    fn send(node: &VirtualLink, frame: &str) {
+        node.send(&frame.parse().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let frame: CanAnyFrame = "123#00".parse().unwrap();
+        let ext: CanAnyFrame = "00000123#00".parse().unwrap();
+        let remote: CanAnyFrame = "123#R".parse().unwrap();
+
+        assert!(filter_matches(&accept_all(), &frame));
+        assert!(filter_matches(&accept_all(), &ext));
+        assert!(!filter_matches(&accept_all(), &remote));
+        assert!(!filter_matches(&[], &frame));
+
+        let filters = [CanFilter::new(0x100, 0x700)];
+        assert!(filter_matches(&filters, &frame));
+        assert!(filter_matches(&filters, &ext));
+        assert!(!filter_matches(&filters, &"223#00".parse().unwrap()));
+
+        // Matching the EFF flag separates standard and extended IDs
+        let filters = [CanFilter::new(
+            0x123,
+            libc::CAN_EFF_FLAG | libc::CAN_SFF_MASK,
+        )];
+        assert!(filter_matches(&filters, &frame));
+        assert!(!filter_matches(&filters, &ext));
+
+        let filters = [CanFilter::new_inverted(0x123, libc::CAN_SFF_MASK)];
+        assert!(!filter_matches(&filters, &frame));
+        assert!(filter_matches(&filters, &"124#00".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_connect() {
+        let bus = VirtualBus::new();
+        let addr = start(&bus);
+
+        let err = Client::connect(addr, "can0").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+        assert_eq!(err.to_string(), "no such bus");
+
+        let client = Client::connect(addr, "vcan0").unwrap();
+        assert_eq!(client.bus(), "vcan0");
+        assert_eq!(bus.len(), 1);
+
+        // Each address is tried in turn
+        let closed = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let err = Client::connect(closed, "vcan0").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+        let client = Client::connect(&[closed, addr][..], "vcan0").unwrap();
+        assert_eq!(client.bus(), "vcan0");
+
+        let err = Client::connect(&[][..], "vcan0").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_frames() {
+        let bus = VirtualBus::new();
+        let node = bus.connect();
+        let client = Client::connect(start(&bus), "vcan0").unwrap();
+
+        send(&node, "123#R");
+        send(&node, "123#DEADBEEF");
+        send(&node, "12345678##10102");
+        let frame = client.read_frame_timeout(TIMEOUT).unwrap();
+        assert_eq!(frame.to_string(), "123#DEADBEEF");
+        let (frame, ts) = client.read_timestamped(Some(TIMEOUT)).unwrap();
+        assert_eq!(frame.to_string(), "12345678##10102");
+        assert!(ts > Duration::from_secs(1_600_000_000));
+
+        let err = client
+            .read_frame_timeout(Duration::from_millis(50))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        let frame: CanAnyFrame = "321#BEEF".parse().unwrap();
+        client.write_frame(frame).unwrap();
+        let frame = node.recv(Some(TIMEOUT)).unwrap().unwrap();
+        assert_eq!(frame.to_string(), "321#BEEF");
+
+        let remote: CanAnyFrame = "321#R".parse().unwrap();
+        let err = client.write_frame(remote).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_filters() {
+        let bus = VirtualBus::new();
+        let node = bus.connect();
+        let client = Client::connect(start(&bus), "vcan0").unwrap();
+
+        client.set_filters(&[(0x100, 0x700)]);
+        send(&node, "223#01");
+        send(&node, "180#02");
+        let frame = client.read_frame_timeout(TIMEOUT).unwrap();
+        assert_eq!(frame.to_string(), "180#02");
+
+        client.set_filter_drop_all();
+        send(&node, "180#03");
+        assert!(client
+            .read_frame_timeout(Duration::from_millis(50))
+            .is_err());
+
+        client.set_filter_accept_all();
+        send(&node, "223#04");
+        let frame = client.read_frame_timeout(TIMEOUT).unwrap();
+        assert_eq!(frame.to_string(), "223#04");
+    }
+
+    #[test]
+    fn test_link() {
+        let bus = VirtualBus::new();
+        let node = bus.connect();
+        let client = Client::connect(start(&bus), "vcan0").unwrap();
+
+        assert!(client
+            .recv(Some(Duration::from_millis(50)))
+            .unwrap()
+            .is_none());
+        send(&node, "7E8#0241");
+        let frame = client.recv(Some(TIMEOUT)).unwrap().unwrap();
+        assert_eq!(frame.to_string(), "7E8#0241");
+
+        Link::send(&client, &"7E0#0201".parse().unwrap()).unwrap();
+        let frame = node.recv(Some(TIMEOUT)).unwrap().unwrap();
+        assert_eq!(frame.to_string(), "7E0#0201");
+    }
+}
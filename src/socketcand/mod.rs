@@ -0,0 +1,680 @@
+// socketcan/src/socketcand/mod.rs
+//
+// The socketcand network protocol.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! The socketcand protocol, to reach CAN buses over a TCP/IP network.
+//!
+//! [socketcand](https://github.com/linux-can/socketcand) exposes the CAN
+//! interfaces of a host to clients, such as Kayak and python-can, with an
+//! ASCII protocol over TCP. Each message is an element between angle
+//! brackets, like `< open can0 >` or `< frame 123 1700000000.123456 DEAD >`.
+//!
+//! After connecting, the server greets the client with `< hi >`, and the
+//! client opens one of the buses with `< open can0 >`. The session then
+//! starts in BCM mode, in which the client sends single frames with
+//! `< send >`, sets up cyclic transmissions with `< add >`, `< update >`
+//! and `< delete >`, and receives the frames of the IDs it asks for with
+//! `< subscribe >` or `< filter >`. In raw mode, chosen with `< rawmode >`,
+//! the client receives every frame on the bus.
+//!
+//! The [`Server`] runs a socketcand-compatible server over the local CAN
+//! interfaces:
+//!
+//! ```no_run
+//! use socketcan::socketcand::{Server, DEFAULT_PORT};
+//!
+//! let server = Server::bind(("0.0.0.0", DEFAULT_PORT), &["can0", "can1"]).unwrap();
+//!
+//! // Announce the server to clients on the local network
+//! let beacon = server.beacon().unwrap();
+//! std::thread::spawn(move || beacon.run());
+//!
+//! server.serve().unwrap();
+//! ```
+//!
+//! The [`Client`] connects to a bus on a server, in raw mode, and reads
+//! and writes frames like a local socket, so application code can run
+//! against a remote bench. An asynchronous version, which is a `Stream`
+//! and `Sink` of frames, is in `socketcand::tokio` with the `tokio`
+//! feature.
+//!
+//! CAN FD frames aren't part of the socketcand protocol. As an extension,
+//! they are sent with `< fdsend can_id flags [data]* >` and received as
+//! `< fdframe can_id secs.usecs flags data >`, where the flags are those of
+//! the frame in hex. Clients that don't know about CAN FD ignore them.
+
+use crate::{
+    frame::FdFlags, CanAnyFrame, CanFdFrame, CanFrame, EmbeddedFrame, ExtendedId, Id, StandardId,
+};
+use libc::CAN_SFF_MASK;
+use std::{fmt, str::FromStr, time::Duration};
+
+pub mod beacon;
+pub use beacon::Beacon;
+
+pub mod client;
+pub use client::Client;
+
+pub mod server;
+pub use server::Server;
+
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+/// The default TCP port of a socketcand server.
+pub const DEFAULT_PORT: u16 = 29536;
+
+/// The UDP port that discovery beacons are broadcast to.
+pub const BEACON_PORT: u16 = 42000;
+
+/// The longest element that's accepted, to bound the receive buffer.
+const MAX_ELEMENT_LEN: usize = 1024;
+
+/// The longest time interval that's accepted for a cyclic transmission or
+/// a subscription, one day, to keep the timers well within range.
+const MAX_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+// ===== Elements =====
+
+/// A buffer that splits a byte stream into the `< ... >` elements of the
+/// protocol.
+#[derive(Debug, Default)]
+pub(crate) struct Elements {
+    buf: Vec<u8>,
+}
+
+impl Elements {
+    /// Adds data received from the stream.
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Takes the next complete element from the buffer, returning the
+    /// trimmed text between its brackets.
+    ///
+    /// Any data outside of the brackets is discarded.
+    pub fn next_element(&mut self) -> Option<String> {
+        match self.buf.iter().position(|&b| b == b'<') {
+            Some(start) => {
+                self.buf.drain(..start);
+            }
+            None => {
+                self.buf.clear();
+                return None;
+            }
+        }
+
+        match self.buf.iter().position(|&b| b == b'>') {
+            Some(end) => {
+                let elem = String::from_utf8_lossy(&self.buf[1..end])
+                    .trim()
+                    .to_string();
+                self.buf.drain(..=end);
+                Some(elem)
+            }
+            None => {
+                if self.buf.len() > MAX_ELEMENT_LEN {
+                    self.buf.clear();
+                }
+                None
+            }
+        }
+    }
+}
+
+// ===== Field parsing and formatting =====
+
+/// Parses a CAN ID in hex.
+///
+/// Like socketcand, an ID written with eight digits is extended, as is
+/// any ID that doesn't fit in 11 bits.
+fn parse_id(s: &str) -> Option<Id> {
+    let id = u32::from_str_radix(s, 16).ok()?;
+    if s.len() == 8 || id > CAN_SFF_MASK {
+        ExtendedId::new(id).map(Id::Extended)
+    } else {
+        StandardId::new(id as u16).map(Id::Standard)
+    }
+}
+
+/// Formats a CAN ID in hex, with eight digits for an extended ID.
+fn fmt_id(id: Id) -> String {
+    match id {
+        Id::Standard(id) => format!("{:03X}", id.as_raw()),
+        Id::Extended(id) => format!("{:08X}", id.as_raw()),
+    }
+}
+
+/// Parses data bytes in hex, either one per field, or run together in a
+/// single field.
+fn parse_data(fields: &[&str]) -> Option<Vec<u8>> {
+    let mut data = Vec::new();
+    for field in fields {
+        if field.len() <= 2 {
+            data.push(u8::from_str_radix(field, 16).ok()?);
+        } else {
+            data.extend(hex::decode(field).ok()?);
+        }
+    }
+    Some(data)
+}
+
+/// Formats data bytes in hex, either one per field, or run together.
+fn fmt_data(data: &[u8], spaced: bool) -> String {
+    if spaced {
+        data.iter().map(|b| format!("{:02X} ", b)).collect()
+    } else if data.is_empty() {
+        String::new()
+    } else {
+        format!("{} ", hex::encode_upper(data))
+    }
+}
+
+/// Parses a time interval from separate seconds and microseconds fields.
+///
+/// Intervals longer than [`MAX_INTERVAL`] are rejected.
+fn parse_interval(secs: &str, usecs: &str) -> Option<Duration> {
+    let secs = secs.parse().ok()?;
+    let usecs: u32 = usecs.parse().ok()?;
+    Duration::from_secs(secs)
+        .checked_add(Duration::from_micros(usecs.into()))
+        .filter(|interval| *interval <= MAX_INTERVAL)
+}
+
+/// Parses a timestamp in the `secs.usecs` form.
+fn parse_timestamp(s: &str) -> Option<Duration> {
+    let (secs, frac) = s.split_once('.').unwrap_or((s, ""));
+    if frac.len() > 9 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let nanos = if frac.is_empty() {
+        0
+    } else {
+        frac.parse::<u32>().ok()? * 10u32.pow(9 - frac.len() as u32)
+    };
+    Some(Duration::new(secs.parse().ok()?, nanos))
+}
+
+/// Gets the ID and data of a data frame, or `None` for remote and error
+/// frames, which the protocol can't carry.
+pub(crate) fn frame_parts(frame: &CanAnyFrame) -> Option<(Id, &[u8])> {
+    match frame {
+        CanAnyFrame::Normal(frame) => Some((frame.id(), frame.data())),
+        CanAnyFrame::Fd(frame) => Some((frame.id(), frame.data())),
+        _ => None,
+    }
+}
+
+/// Formats a received frame as a `< frame >` or `< fdframe >` element.
+///
+/// In BCM mode, the data bytes are separated by spaces, and in raw mode
+/// they are run together. This returns `None` for remote and error
+/// frames.
+pub(crate) fn frame_element(
+    frame: &CanAnyFrame,
+    timestamp: Duration,
+    spaced: bool,
+) -> Option<String> {
+    let (id, data) = frame_parts(frame)?;
+    let ts = format!("{}.{:06}", timestamp.as_secs(), timestamp.subsec_micros());
+    let elem = match frame {
+        CanAnyFrame::Fd(frame) => format!(
+            "< fdframe {} {} {:X} {}>",
+            fmt_id(id),
+            ts,
+            frame.flags().bits(),
+            fmt_data(data, spaced)
+        ),
+        _ => format!("< frame {} {} {}>", fmt_id(id), ts, fmt_data(data, spaced)),
+    };
+    Some(elem)
+}
+
+/// Creates a classic data frame from parsed fields.
+fn new_frame(id: Id, data: &[u8]) -> Result<CanAnyFrame, &'static str> {
+    CanFrame::new(id, data)
+        .map(CanAnyFrame::from)
+        .ok_or("invalid frame")
+}
+
+/// Creates a CAN FD frame from parsed fields.
+fn new_fd_frame(id: Id, flags: &str, data: &[u8]) -> Result<CanAnyFrame, &'static str> {
+    let flags = u8::from_str_radix(flags, 16).map_err(|_| "invalid flags")?;
+    CanFdFrame::with_flags(id, data, FdFlags::from_bits_truncate(flags))
+        .map(CanAnyFrame::Fd)
+        .ok_or("invalid frame")
+}
+
+// ===== Mode =====
+
+/// The mode of a session, which determines the commands that are
+/// available and the frames that the client receives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Mode {
+    /// Cyclic transmissions and subscriptions to IDs
+    Bcm,
+    /// Every frame on the bus
+    Raw,
+    /// Interface statistics
+    Control,
+    /// ISO-TP transfers
+    IsoTp,
+}
+
+// ===== Request =====
+
+/// A message from a client to the server.
+#[derive(Debug, Clone)]
+pub(crate) enum Request {
+    /// Opens a bus, by its interface name
+    Open(String),
+    /// Switches the session to another mode
+    Mode(Mode),
+    /// Asks the server to echo back
+    Echo,
+    /// Sends a single frame
+    Send(CanAnyFrame),
+    /// Sends a frame cyclically, replacing any job for the same ID
+    Add {
+        /// The time between transmissions
+        interval: Duration,
+        /// The frame to send
+        frame: CanAnyFrame,
+    },
+    /// Updates the data of a cyclic transmission
+    Update(CanAnyFrame),
+    /// Stops a cyclic transmission
+    Delete(Id),
+    /// Subscribes to changes in the masked data of an ID
+    Filter {
+        /// The minimum time between received frames
+        interval: Duration,
+        /// The ID to receive
+        id: Id,
+        /// The mask of the data bytes to watch for changes
+        mask: Vec<u8>,
+    },
+    /// Subscribes to the frames of an ID
+    Subscribe {
+        /// The minimum time between received frames
+        interval: Duration,
+        /// The ID to receive
+        id: Id,
+    },
+    /// Ends a subscription
+    Unsubscribe(Id),
+}
+
+impl FromStr for Request {
+    type Err = &'static str;
+
+    /// Parses the text of an element from a client.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Request::*;
+
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let (&cmd, args) = fields.split_first().ok_or("empty command")?;
+
+        // A classic frame, from the `can_id can_dlc [data]*` fields
+        let frame = |args: &[&str]| -> Result<CanAnyFrame, &'static str> {
+            match args {
+                [id, dlc, data @ ..] => {
+                    let id = parse_id(id).ok_or("invalid id")?;
+                    let dlc: usize = dlc.parse().map_err(|_| "invalid dlc")?;
+                    let data = parse_data(data).ok_or("invalid data")?;
+                    if data.len() != dlc {
+                        return Err("wrong data length");
+                    }
+                    new_frame(id, &data)
+                }
+                _ => Err("missing arguments"),
+            }
+        };
+        let interval = |secs: &str, usecs: &str| parse_interval(secs, usecs).ok_or("invalid time");
+        let id = |s: &str| parse_id(s).ok_or("invalid id");
+
+        let req = match (cmd, args) {
+            ("open", [bus]) => Open(bus.to_string()),
+            ("bcmmode", []) => Mode(self::Mode::Bcm),
+            ("rawmode", []) => Mode(self::Mode::Raw),
+            ("controlmode", []) => Mode(self::Mode::Control),
+            ("isotpmode", []) => Mode(self::Mode::IsoTp),
+            ("echo", []) => Echo,
+            ("send", args) => Send(frame(args)?),
+            ("fdsend", [can_id, flags, data @ ..]) => {
+                let data = parse_data(data).ok_or("invalid data")?;
+                Send(new_fd_frame(id(can_id)?, flags, &data)?)
+            }
+            ("add", [secs, usecs, args @ ..]) => Add {
+                interval: interval(secs, usecs)?,
+                frame: frame(args)?,
+            },
+            ("update", args) => Update(frame(args)?),
+            ("delete", [can_id]) => Delete(id(can_id)?),
+            ("filter", [secs, usecs, can_id, dlc, mask @ ..]) => {
+                let mask = parse_data(mask).ok_or("invalid data")?;
+                if dlc.parse::<usize>() != Ok(mask.len()) {
+                    return Err("wrong data length");
+                }
+                Filter {
+                    interval: interval(secs, usecs)?,
+                    id: id(can_id)?,
+                    mask,
+                }
+            }
+            ("subscribe", [secs, usecs, can_id]) => Subscribe {
+                interval: interval(secs, usecs)?,
+                id: id(can_id)?,
+            },
+            ("unsubscribe", [can_id]) => Unsubscribe(id(can_id)?),
+            ("open" | "bcmmode" | "rawmode" | "controlmode" | "isotpmode" | "echo", _)
+            | ("fdsend" | "add" | "delete" | "filter" | "subscribe" | "unsubscribe", _) => {
+                return Err("wrong number of arguments")
+            }
+            _ => return Err("unknown command"),
+        };
+        Ok(req)
+    }
+}
+
+impl fmt::Display for Request {
+    /// Formats the request as an element.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Request::*;
+
+        // The `can_id can_dlc [data]*` fields of a classic frame
+        let frame_fields = |frame: &CanAnyFrame| match frame_parts(frame) {
+            Some((id, data)) => format!("{} {} {}", fmt_id(id), data.len(), fmt_data(data, true)),
+            None => String::new(),
+        };
+        let micros = |d: &Duration| format!("{} {}", d.as_secs(), d.subsec_micros());
+
+        match self {
+            Open(bus) => write!(f, "< open {} >", bus),
+            Mode(self::Mode::Bcm) => write!(f, "< bcmmode >"),
+            Mode(self::Mode::Raw) => write!(f, "< rawmode >"),
+            Mode(self::Mode::Control) => write!(f, "< controlmode >"),
+            Mode(self::Mode::IsoTp) => write!(f, "< isotpmode >"),
+            Echo => write!(f, "< echo >"),
+            Send(CanAnyFrame::Fd(frame)) => write!(
+                f,
+                "< fdsend {} {:X} {}>",
+                fmt_id(frame.id()),
+                frame.flags().bits(),
+                fmt_data(frame.data(), true)
+            ),
+            Send(frame) => write!(f, "< send {}>", frame_fields(frame)),
+            Add { interval, frame } => {
+                write!(f, "< add {} {}>", micros(interval), frame_fields(frame))
+            }
+            Update(frame) => write!(f, "< update {}>", frame_fields(frame)),
+            Delete(id) => write!(f, "< delete {} >", fmt_id(*id)),
+            Filter { interval, id, mask } => write!(
+                f,
+                "< filter {} {} {} {}>",
+                micros(interval),
+                fmt_id(*id),
+                mask.len(),
+                fmt_data(mask, true)
+            ),
+            Subscribe { interval, id } => {
+                write!(f, "< subscribe {} {} >", micros(interval), fmt_id(*id))
+            }
+            Unsubscribe(id) => write!(f, "< unsubscribe {} >", fmt_id(*id)),
+        }
+    }
+}
+
+// ===== Response =====
+
+/// A message from the server to a client.
+#[derive(Debug, Clone)]
+pub(crate) enum Response {
+    /// The greeting, when a client connects
+    Hi,
+    /// A command succeeded
+    Ok,
+    /// The reply to an echo request
+    Echo,
+    /// A command failed, with the reason
+    Error(String),
+    /// A frame received from the bus
+    Frame {
+        /// The frame
+        frame: CanAnyFrame,
+        /// The time it was received, since the Unix epoch
+        timestamp: Duration,
+    },
+}
+
+impl FromStr for Response {
+    type Err = &'static str;
+
+    /// Parses the text of an element from the server.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+
+        let resp = match fields.as_slice() {
+            ["hi"] => Response::Hi,
+            ["ok"] => Response::Ok,
+            ["echo"] => Response::Echo,
+            ["error", ..] => Response::Error(s["error".len()..].trim().to_string()),
+            ["frame", can_id, ts, data @ ..] => {
+                let id = parse_id(can_id).ok_or("invalid id")?;
+                let data = parse_data(data).ok_or("invalid data")?;
+                Response::Frame {
+                    frame: new_frame(id, &data)?,
+                    timestamp: parse_timestamp(ts).ok_or("invalid timestamp")?,
+                }
+            }
+            ["fdframe", can_id, ts, flags, data @ ..] => {
+                let id = parse_id(can_id).ok_or("invalid id")?;
+                let data = parse_data(data).ok_or("invalid data")?;
+                Response::Frame {
+                    frame: new_fd_frame(id, flags, &data)?,
+                    timestamp: parse_timestamp(ts).ok_or("invalid timestamp")?,
+                }
+            }
+            _ => return Err("unknown response"),
+        };
+        Ok(resp)
+    }
+}
+
+impl fmt::Display for Response {
+    /// Formats the response as an element, with frames in the raw mode
+    /// format.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Response::Hi => write!(f, "< hi >"),
+            Response::Ok => write!(f, "< ok >"),
+            Response::Echo => write!(f, "< echo >"),
+            Response::Error(msg) => write!(f, "< error {} >", msg),
+            Response::Frame { frame, timestamp } => {
+                let elem = frame_element(frame, *timestamp, false).unwrap_or_default();
+                f.write_str(&elem)
+            }
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elements() {
+        let mut elems = Elements::default();
+        elems.push(b"< hi >< open ");
+        assert_eq!(elems.next_element().as_deref(), Some("hi"));
+        assert_eq!(elems.next_element(), None);
+
+        elems.push(b"can0 >junk<ok>");
+        assert_eq!(elems.next_element().as_deref(), Some("open can0"));
+        assert_eq!(elems.next_element().as_deref(), Some("ok"));
+        assert_eq!(elems.next_element(), None);
+
+        // An element that never ends is dropped
+        elems.push(b"< ");
+        elems.push(&[b'x'; MAX_ELEMENT_LEN]);
+        assert_eq!(elems.next_element(), None);
+        elems.push(b" >< echo >");
+        assert_eq!(elems.next_element().as_deref(), Some("echo"));
+    }
+
+    #[test]
+    fn test_fields() {
+        assert_eq!(
+            parse_id("123"),
+            Some(Id::Standard(StandardId::new(0x123).unwrap()))
+        );
+        assert_eq!(
+            parse_id("00000123"),
+            Some(Id::Extended(ExtendedId::new(0x123).unwrap()))
+        );
+        assert_eq!(
+            parse_id("1ABCDE"),
+            Some(Id::Extended(ExtendedId::new(0x1ABCDE).unwrap()))
+        );
+        assert_eq!(parse_id("xyz"), None);
+        assert_eq!(fmt_id(parse_id("00000123").unwrap()), "00000123");
+        assert_eq!(fmt_id(parse_id("7").unwrap()), "007");
+
+        assert_eq!(
+            parse_data(&["1", "22", "3344"]),
+            Some(vec![1, 0x22, 0x33, 0x44])
+        );
+        assert_eq!(parse_data(&["123"]), None);
+        assert_eq!(fmt_data(&[1, 0xAB], true), "01 AB ");
+        assert_eq!(fmt_data(&[1, 0xAB], false), "01AB ");
+
+        assert_eq!(
+            parse_interval("1", "500"),
+            Some(Duration::from_micros(1_000_500))
+        );
+        assert_eq!(parse_interval("86400", "0"), Some(MAX_INTERVAL));
+        assert_eq!(parse_interval("86400", "1"), None);
+        assert_eq!(parse_interval("18446744073709551615", "999999"), None);
+        assert_eq!(parse_interval("0", "-1"), None);
+        assert_eq!(
+            parse_timestamp("1700000000.123456"),
+            Some(Duration::new(1_700_000_000, 123_456_000))
+        );
+        assert_eq!(parse_timestamp("12.5"), Some(Duration::from_millis(12_500)));
+        assert_eq!(parse_timestamp("12"), Some(Duration::from_secs(12)));
+        assert_eq!(parse_timestamp("12.x"), None);
+    }
+
+    #[test]
+    fn test_requests() {
+        let roundtrip = |s: &str| s.parse::<Request>().unwrap().to_string();
+
+        for s in [
+            "< open can0 >",
+            "< rawmode >",
+            "< bcmmode >",
+            "< echo >",
+            "< send 123 3 11 22 33 >",
+            "< send 12345678 0 >",
+            "< fdsend 123 1 11 22 33 44 55 66 77 88 99 00 AA BB >",
+            "< add 1 500 123 2 DE AD >",
+            "< update 123 2 BE EF >",
+            "< delete 123 >",
+            "< filter 0 100000 123 2 FF 00 >",
+            "< subscribe 0 0 7FF >",
+            "< unsubscribe 7FF >",
+        ] {
+            let elem = &s[1..s.len() - 1];
+            assert_eq!(roundtrip(elem), s);
+        }
+
+        match "add 0 10000 123 1 05".parse::<Request>().unwrap() {
+            Request::Add { interval, frame } => {
+                assert_eq!(interval, Duration::from_millis(10));
+                assert_eq!(frame.to_string(), "123#05");
+            }
+            req => panic!("Unexpected request: {:?}", req),
+        }
+
+        assert_eq!(
+            "send 123 2 11".parse::<Request>().err(),
+            Some("wrong data length")
+        );
+        assert_eq!(
+            "send 123 9 11".parse::<Request>().err(),
+            Some("wrong data length")
+        );
+        assert_eq!(
+            "open".parse::<Request>().err(),
+            Some("wrong number of arguments")
+        );
+        assert_eq!(
+            "statistics 100".parse::<Request>().err(),
+            Some("unknown command")
+        );
+        assert_eq!("".parse::<Request>().err(), Some("empty command"));
+        assert_eq!(
+            "add 18446744073709551615 999999 123 0"
+                .parse::<Request>()
+                .err(),
+            Some("invalid time")
+        );
+    }
+
+    #[test]
+    fn test_responses() {
+        let ts = Duration::new(1_700_000_000, 1_000);
+        let frame: CanAnyFrame = "123#DEADBEEF".parse().unwrap();
+        assert_eq!(
+            frame_element(&frame, ts, false).unwrap(),
+            "< frame 123 1700000000.000001 DEADBEEF >"
+        );
+        assert_eq!(
+            frame_element(&frame, ts, true).unwrap(),
+            "< frame 123 1700000000.000001 DE AD BE EF >"
+        );
+
+        let frame: CanAnyFrame = "12345678##1".parse().unwrap();
+        assert_eq!(
+            frame_element(&frame, ts, false).unwrap(),
+            "< fdframe 12345678 1700000000.000001 1 >"
+        );
+
+        let remote: CanAnyFrame = "123#R".parse().unwrap();
+        assert_eq!(frame_element(&remote, ts, false), None);
+
+        for s in [
+            "frame 123 1700000000.000001 DEADBEEF",
+            "frame 123 1700000000.000001 DE AD BE EF",
+        ] {
+            match s.parse::<Response>().unwrap() {
+                Response::Frame { frame, timestamp } => {
+                    assert_eq!(frame.to_string(), "123#DEADBEEF");
+                    assert_eq!(timestamp, ts);
+                }
+                resp => panic!("Unexpected response: {:?}", resp),
+            }
+        }
+
+        match "fdframe 12345678 1.5 1 0102".parse::<Response>().unwrap() {
+            Response::Frame { frame, .. } => assert_eq!(frame.to_string(), "12345678##10102"),
+            resp => panic!("Unexpected response: {:?}", resp),
+        }
+
+        match "error could not open bus".parse::<Response>().unwrap() {
+            Response::Error(msg) => assert_eq!(msg, "could not open bus"),
+            resp => panic!("Unexpected response: {:?}", resp),
+        }
+        assert_eq!(Response::Hi.to_string(), "< hi >");
+        assert!("bogus".parse::<Response>().is_err());
+    }
+}
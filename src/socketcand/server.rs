@@ -0,0 +1,836 @@
+// socketcan/src/socketcand/server.rs
+//
+// A socketcand-compatible server.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! A socketcand-compatible server.
+//!
+//! Each client is served by a thread that reads its commands, and sends
+//! the client's frames. Once the client opens a bus, two more threads run
+//! it: one passes the frames received from the bus to the client,
+//! according to the mode of the session, and the other sleeps until the
+//! next cyclic transmission or throttled frame is due.
+//!
+//! By default, each session opens its interface with a
+//! [`CanFdSocket`], so it receives both classic and CAN FD frames. A
+//! server made with [`Server::with_opener()`] can serve any CAN
+//! [`Link`] instead, like an [`SlcanPort`](crate::slcan::SlcanPort), or a
+//! [`VirtualBus`](crate::link::VirtualBus) for testing.
+//!
+//! Remote and error frames aren't part of the protocol, so they aren't
+//! passed to clients. The control and ISO-TP modes aren't supported, and
+//! the server answers a request for either of them with an error.
+
+use super::{frame_element, frame_parts, Beacon, Elements, Mode, Request, Response, BEACON_PORT};
+use crate::{link::Link, CanAnyFrame, CanFdSocket, Id, Socket};
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// The longest time that the receive thread of a bus waits for a frame,
+/// which bounds the time that it keeps the bus open after the session
+/// closes.
+const CLOSE_CHECK: Duration = Duration::from_millis(250);
+
+/// A function that opens a bus by its interface name.
+pub type Opener = dyn Fn(&str) -> io::Result<Box<dyn Link + Send + Sync>> + Send + Sync;
+
+/// A link to a bus, shared by the threads of a session.
+type SharedLink = Arc<dyn Link + Send + Sync>;
+
+/// The stream to a client, shared by the threads of its session.
+type Writer = Arc<Mutex<TcpStream>>;
+
+/// Writes an element to the client.
+fn write_element(writer: &Writer, elem: &str) -> io::Result<()> {
+    writer.lock().unwrap().write_all(elem.as_bytes())
+}
+
+/// Gets the time since the Unix epoch, to timestamp received frames.
+fn now_timestamp() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Gets the local address that broadcasts are sent from.
+fn broadcast_ip() -> Option<IpAddr> {
+    let sock = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    sock.set_broadcast(true).ok()?;
+    sock.connect((Ipv4Addr::BROADCAST, BEACON_PORT)).ok()?;
+    let ip = sock.local_addr().ok()?.ip();
+    (!ip.is_unspecified()).then_some(ip)
+}
+
+// ===== Server =====
+
+/// A socketcand-compatible server for a set of CAN buses.
+pub struct Server {
+    listener: TcpListener,
+    buses: Arc<Vec<String>>,
+    opener: Arc<Opener>,
+}
+
+impl fmt::Debug for Server {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Server")
+            .field("listener", &self.listener)
+            .field("buses", &self.buses)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Server {
+    /// Creates a server listening on the address, that serves the named
+    /// CAN interfaces.
+    pub fn bind<A: ToSocketAddrs>(addr: A, buses: &[&str]) -> io::Result<Self> {
+        Self::with_opener(addr, buses, |bus| {
+            let sock = CanFdSocket::open(bus)?;
+            Ok(Box::new(sock) as Box<dyn Link + Send + Sync>)
+        })
+    }
+
+    /// Creates a server listening on the address, that serves the named
+    /// buses with links from the `opener`.
+    ///
+    /// The opener is called with the name of the bus each time that a
+    /// client opens one, and each session gets a link of its own, which
+    /// its threads share.
+    pub fn with_opener<A, F>(addr: A, buses: &[&str], opener: F) -> io::Result<Self>
+    where
+        A: ToSocketAddrs,
+        F: Fn(&str) -> io::Result<Box<dyn Link + Send + Sync>> + Send + Sync + 'static,
+    {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            buses: Arc::new(buses.iter().map(|bus| bus.to_string()).collect()),
+            opener: Arc::new(opener),
+        })
+    }
+
+    /// Gets the address that the server is listening on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Gets the names of the buses that the server serves.
+    pub fn buses(&self) -> &[String] {
+        &self.buses
+    }
+
+    /// Creates the discovery beacon for the server, named for the host.
+    ///
+    /// When the server listens on the unspecified address, the beacon
+    /// advertises the address of the interface that broadcasts go out on.
+    pub fn beacon(&self) -> io::Result<Beacon> {
+        let mut addr = self.local_addr()?;
+        if addr.ip().is_unspecified() {
+            if let Some(ip) = broadcast_ip() {
+                addr.set_ip(ip);
+            }
+        }
+        let name = nix::unistd::gethostname()
+            .ok()
+            .and_then(|name| name.into_string().ok())
+            .unwrap_or_else(|| "socketcand".into());
+        let buses: Vec<&str> = self.buses.iter().map(String::as_str).collect();
+        Ok(Beacon::new(&name, addr, &buses))
+    }
+
+    /// Accepts clients and serves each of them on a thread of its own.
+    ///
+    /// This only returns if accepting a connection fails.
+    pub fn serve(&self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let session = self.session();
+            thread::spawn(move || {
+                if let Err(err) = session.run(stream) {
+                    log::debug!("socketcand session failed: {}", err);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Serves a single client on the calling thread, until it
+    /// disconnects.
+    pub fn handle(&self, stream: TcpStream) -> io::Result<()> {
+        self.session().run(stream)
+    }
+
+    /// Creates a new session for a client.
+    fn session(&self) -> Session {
+        Session {
+            buses: Arc::clone(&self.buses),
+            opener: Arc::clone(&self.opener),
+            state: Arc::new(Mutex::new(State::default())),
+            bus: None,
+        }
+    }
+}
+
+// ===== Session state =====
+
+/// A cyclic transmission.
+#[derive(Debug)]
+struct Job {
+    id: Id,
+    frame: CanAnyFrame,
+    interval: Duration,
+    next: Instant,
+}
+
+/// A subscription to the frames of an ID.
+#[derive(Debug)]
+struct Subscription {
+    id: Id,
+    interval: Duration,
+    /// The mask of the data bytes to watch for changes
+    mask: Option<Vec<u8>>,
+    /// The masked data of the last frame passed to the client
+    last_data: Option<Vec<u8>>,
+    /// The time that the last frame was sent to the client
+    last_sent: Option<Instant>,
+    /// The latest frame held back by the throttle
+    pending: Option<String>,
+}
+
+impl Subscription {
+    /// Creates a subscription, with an optional data mask.
+    fn new(id: Id, interval: Duration, mask: Option<Vec<u8>>) -> Self {
+        Self {
+            id,
+            interval,
+            mask,
+            last_data: None,
+            last_sent: None,
+            pending: None,
+        }
+    }
+
+    /// Gets the time that the throttle opens again, if it's closed.
+    fn next_send(&self) -> Option<Instant> {
+        self.last_sent.and_then(|t| t.checked_add(self.interval))
+    }
+
+    /// Handles a frame for the subscription, returning the element for
+    /// the client if it should be sent now.
+    fn receive(&mut self, elem: String, data: &[u8], now: Instant) -> Option<String> {
+        if let Some(mask) = &self.mask {
+            let masked: Vec<u8> = data
+                .iter()
+                .enumerate()
+                .map(|(i, b)| b & mask.get(i).copied().unwrap_or(0))
+                .collect();
+            if self.last_data.as_ref() == Some(&masked) {
+                return None;
+            }
+            self.last_data = Some(masked);
+        }
+
+        if self.interval.is_zero() || self.next_send().map_or(true, |t| now >= t) {
+            self.last_sent = Some(now);
+            self.pending = None;
+            Some(elem)
+        } else {
+            self.pending = Some(elem);
+            None
+        }
+    }
+}
+
+/// The state of a session that's shared with its bus threads.
+#[derive(Debug)]
+struct State {
+    mode: Mode,
+    jobs: Vec<Job>,
+    subs: Vec<Subscription>,
+    /// Wakes the timer thread of the open bus, and closes it when dropped
+    timer: Option<Sender<()>>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Bcm,
+            jobs: Vec::new(),
+            subs: Vec::new(),
+            timer: None,
+        }
+    }
+}
+
+impl State {
+    /// Wakes the timer thread, after a timer is set that might be due
+    /// sooner than the one that it's waiting for.
+    fn wake_timer(&self) {
+        if let Some(timer) = &self.timer {
+            let _ = timer.send(());
+        }
+    }
+
+    /// Switches to a new mode, which ends the jobs and subscriptions.
+    fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+        self.jobs.clear();
+        self.subs.clear();
+    }
+
+    /// Handles a BCM mode command.
+    fn bcm(&mut self, req: Request, now: Instant) -> Result<(), &'static str> {
+        match req {
+            Request::Add { interval, frame } => {
+                let (id, _) = frame_parts(&frame).ok_or("invalid frame")?;
+                if interval.is_zero() {
+                    return Err("invalid interval");
+                }
+                self.jobs.retain(|job| job.id != id);
+                self.jobs.push(Job {
+                    id,
+                    frame,
+                    interval,
+                    next: now,
+                });
+                self.wake_timer();
+            }
+            Request::Update(frame) => {
+                let (id, _) = frame_parts(&frame).ok_or("invalid frame")?;
+                let job = self
+                    .jobs
+                    .iter_mut()
+                    .find(|job| job.id == id)
+                    .ok_or("no such job")?;
+                job.frame = frame;
+            }
+            Request::Delete(id) => {
+                let n = self.jobs.len();
+                self.jobs.retain(|job| job.id != id);
+                if self.jobs.len() == n {
+                    return Err("no such job");
+                }
+            }
+            Request::Filter { interval, id, mask } => {
+                self.subs.retain(|sub| sub.id != id);
+                self.subs.push(Subscription::new(id, interval, Some(mask)));
+            }
+            Request::Subscribe { interval, id } => {
+                self.subs.retain(|sub| sub.id != id);
+                self.subs.push(Subscription::new(id, interval, None));
+            }
+            Request::Unsubscribe(id) => {
+                let n = self.subs.len();
+                self.subs.retain(|sub| sub.id != id);
+                if self.subs.len() == n {
+                    return Err("no such subscription");
+                }
+            }
+            _ => return Err("command not available in this mode"),
+        }
+        Ok(())
+    }
+
+    /// Runs the timers, returning the cyclic frames to send, the held
+    /// back frames for the client, and the time until the next timer, if
+    /// any are set.
+    fn poll(&mut self, now: Instant) -> (Vec<CanAnyFrame>, Vec<String>, Option<Duration>) {
+        let mut frames = Vec::new();
+        let mut elems = Vec::new();
+        let mut next: Option<Instant> = None;
+
+        for job in &mut self.jobs {
+            if now >= job.next {
+                frames.push(job.frame);
+                job.next = match job.next.checked_add(job.interval) {
+                    Some(t) if t >= now => t,
+                    // Don't try to catch up after a stall
+                    _ => now.checked_add(job.interval).unwrap_or(now),
+                };
+            }
+            next = Some(next.map_or(job.next, |t| t.min(job.next)));
+        }
+
+        for sub in &mut self.subs {
+            if let Some(t) = sub.next_send().filter(|_| sub.pending.is_some()) {
+                if now >= t {
+                    elems.extend(sub.pending.take());
+                    sub.last_sent = Some(now);
+                } else {
+                    next = Some(next.map_or(t, |next| next.min(t)));
+                }
+            }
+        }
+
+        (
+            frames,
+            elems,
+            next.map(|t| t.saturating_duration_since(now)),
+        )
+    }
+
+    /// Handles a frame received from the bus, returning the elements for
+    /// the client.
+    fn receive(&mut self, frame: &CanAnyFrame, now: Instant) -> Vec<String> {
+        let ts = now_timestamp();
+        match self.mode {
+            Mode::Raw => frame_element(frame, ts, false).into_iter().collect(),
+            Mode::Bcm => {
+                let (id, data) = match frame_parts(frame) {
+                    Some(parts) => parts,
+                    None => return Vec::new(),
+                };
+                let mut held = false;
+                let elems = self
+                    .subs
+                    .iter_mut()
+                    .filter(|sub| sub.id == id)
+                    .filter_map(|sub| {
+                        let elem = frame_element(frame, ts, true)?;
+                        let idle = sub.pending.is_none();
+                        let elem = sub.receive(elem, data, now);
+                        held |= idle && sub.pending.is_some();
+                        elem
+                    })
+                    .collect();
+                if held {
+                    self.wake_timer();
+                }
+                elems
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+// ===== Session =====
+
+/// The session of a single client.
+struct Session {
+    buses: Arc<Vec<String>>,
+    opener: Arc<Opener>,
+    state: Arc<Mutex<State>>,
+    /// The open bus, and its threads
+    bus: Option<(SharedLink, [JoinHandle<()>; 2])>,
+}
+
+impl Session {
+    /// Serves the client until it disconnects.
+    fn run(mut self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        let writer = Arc::new(Mutex::new(stream.try_clone()?));
+        write_element(&writer, &Response::Hi.to_string())?;
+
+        let res = self.read_requests(&mut stream, &writer);
+
+        // Dropping the timer channel closes the bus threads
+        self.state.lock().unwrap().timer = None;
+        if let Some((_, threads)) = self.bus.take() {
+            for thread in threads {
+                let _ = thread.join();
+            }
+        }
+        res
+    }
+
+    /// Reads and handles requests until the client disconnects.
+    fn read_requests(&mut self, stream: &mut TcpStream, writer: &Writer) -> io::Result<()> {
+        let mut elems = Elements::default();
+        let mut buf = [0u8; 1024];
+        loop {
+            let n = stream.read(&mut buf)?;
+            if n == 0 {
+                return Ok(());
+            }
+            elems.push(&buf[..n]);
+            while let Some(elem) = elems.next_element() {
+                if let Some(resp) = self.request(&elem, writer) {
+                    write_element(writer, &resp.to_string())?;
+                }
+            }
+        }
+    }
+
+    /// Handles a request, returning the response, if it has one.
+    fn request(&mut self, elem: &str, writer: &Writer) -> Option<Response> {
+        let error = |msg: &str| Some(Response::Error(msg.into()));
+
+        let req = match elem.parse::<Request>() {
+            Ok(req) => req,
+            Err(msg) => return error(msg),
+        };
+
+        let link = match (&req, &self.bus) {
+            (Request::Echo, _) => return Some(Response::Echo),
+            (Request::Open(bus), None) => return Some(self.open(bus, writer)),
+            (Request::Open(_), Some(_)) => return error("bus already open"),
+            (_, None) => return error("no bus open"),
+            (_, Some((link, _))) => link,
+        };
+
+        let mut state = self.state.lock().unwrap();
+        match (state.mode, req) {
+            (_, Request::Mode(Mode::IsoTp)) => error("isotp mode is not supported"),
+            (_, Request::Mode(Mode::Control)) => error("control mode is not supported"),
+            (_, Request::Mode(mode)) => {
+                state.set_mode(mode);
+                Some(Response::Ok)
+            }
+            (Mode::Bcm | Mode::Raw, Request::Send(frame)) => match link.send(&frame) {
+                Ok(()) => None,
+                Err(err) => error(&format!("send failed: {}", err)),
+            },
+            (Mode::Bcm, req) => state.bcm(req, Instant::now()).err().and_then(error),
+            _ => error("command not available in this mode"),
+        }
+    }
+
+    /// Opens a bus for the session, and starts its thread.
+    fn open(&mut self, bus: &str, writer: &Writer) -> Response {
+        if !self.buses.iter().any(|name| name == bus) {
+            return Response::Error("no such bus".into());
+        }
+        let link = match (self.opener)(bus) {
+            Ok(link) => link,
+            Err(err) => return Response::Error(format!("could not open bus: {}", err)),
+        };
+
+        let (tx, rx) = mpsc::channel();
+        {
+            let mut state = self.state.lock().unwrap();
+            state.set_mode(Mode::Bcm);
+            state.timer = Some(tx);
+        }
+
+        let link: SharedLink = Arc::from(link);
+        let threads = [
+            {
+                let (link, state, writer) = (link.clone(), self.state.clone(), writer.clone());
+                thread::spawn(move || run_timers(link, rx, state, writer))
+            },
+            {
+                let (link, state, writer) = (link.clone(), self.state.clone(), writer.clone());
+                thread::spawn(move || run_receiver(link, state, writer))
+            },
+        ];
+        self.bus = Some((link, threads));
+        Response::Ok
+    }
+}
+
+/// Reports a failure of the bus to the client.
+fn report(writer: &Writer, err: io::Error) {
+    let msg = format!("< error bus failed: {} >", err);
+    let _ = write_element(writer, &msg);
+}
+
+/// Runs the timers of a session, until the session closes or the bus
+/// fails.
+///
+/// This sleeps until the next timer is due, or until it's woken by a new
+/// one, and with no timers set, until it's woken.
+fn run_timers(link: SharedLink, wake: Receiver<()>, state: Arc<Mutex<State>>, writer: Writer) {
+    loop {
+        let (frames, elems, wait) = state.lock().unwrap().poll(Instant::now());
+        for frame in &frames {
+            if let Err(err) = link.send(frame) {
+                return report(&writer, err);
+            }
+        }
+        for elem in elems {
+            if write_element(&writer, &elem).is_err() {
+                return;
+            }
+        }
+
+        let res = match wait {
+            Some(wait) => wake.recv_timeout(wait),
+            None => wake.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        if let Err(RecvTimeoutError::Disconnected) = res {
+            return;
+        }
+    }
+}
+
+/// Passes the frames received from the bus to the client, until the
+/// session closes or the bus fails.
+fn run_receiver(link: SharedLink, state: Arc<Mutex<State>>, writer: Writer) {
+    loop {
+        match link.recv(Some(CLOSE_CHECK)) {
+            Ok(Some(frame)) => {
+                let elems = state.lock().unwrap().receive(&frame, Instant::now());
+                for elem in elems {
+                    if write_element(&writer, &elem).is_err() {
+                        return;
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(err) => return report(&writer, err),
+        }
+        if state.lock().unwrap().timer.is_none() {
+            return;
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::link::{VirtualBus, VirtualLink};
+
+    /// A socketcand client for the tests.
+    struct Client {
+        stream: TcpStream,
+        elems: Elements,
+    }
+
+    impl Client {
+        fn connect(addr: SocketAddr) -> Self {
+            let stream = TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(2)))
+                .unwrap();
+            let mut client = Self {
+                stream,
+                elems: Elements::default(),
+            };
+            assert_eq!(client.recv(), "hi");
+            client
+        }
+
+        fn send(&mut self, elem: &str) {
+            self.stream.write_all(elem.as_bytes()).unwrap();
+        }
+
+        fn recv(&mut self) -> String {
+            let mut buf = [0u8; 256];
+            loop {
+                if let Some(elem) = self.elems.next_element() {
+                    return elem;
+                }
+                let n = self.stream.read(&mut buf).unwrap();
+                assert!(n > 0, "Server disconnected");
+                self.elems.push(&buf[..n]);
+            }
+        }
+
+        fn command(&mut self, elem: &str) -> String {
+            self.send(elem);
+            self.recv()
+        }
+
+        /// Waits for the server to handle the previous commands.
+        fn sync(&mut self) {
+            assert_eq!(self.command("< echo >"), "echo");
+        }
+    }
+
+    /// Starts a server for the bus, as `vcan0`.
+    fn start(bus: &VirtualBus) -> SocketAddr {
+        let bus = bus.clone();
+        let server = Server::with_opener("127.0.0.1:0", &["vcan0"], move |_| {
+            Ok(Box::new(bus.connect()) as Box<dyn Link + Send + Sync>)
+        })
+        .unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.serve());
+        addr
+    }
+
+    fn send(node: &VirtualLink, frame: &str) {
+        node.send(&frame.parse().unwrap()).unwrap();
+    }
+
+    fn recv(node: &VirtualLink, timeout: Duration) -> Option<String> {
+        node.recv(Some(timeout)).unwrap().map(|f| f.to_string())
+    }
+
+    #[test]
+    fn test_timers() {
+        // With nothing to do, the timer thread sleeps until it's woken
+        let mut state = State::default();
+        let now = Instant::now();
+        assert_eq!(state.poll(now).2, None);
+
+        let frame: CanAnyFrame = "456#05".parse().unwrap();
+        let interval = Duration::from_millis(100);
+        state.bcm(Request::Add { interval, frame }, now).unwrap();
+        let (frames, _, wait) = state.poll(now);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(wait, Some(interval));
+
+        state.set_mode(Mode::Raw);
+        assert_eq!(state.poll(now).2, None);
+    }
+
+    #[test]
+    fn test_session() {
+        let bus = VirtualBus::new();
+        let mut client = Client::connect(start(&bus));
+
+        assert_eq!(client.command("< echo >"), "echo");
+        assert_eq!(client.command("< send 123 0 >"), "error no bus open");
+        assert_eq!(client.command("< open can0 >"), "error no such bus");
+        assert_eq!(client.command("< open vcan0 >"), "ok");
+        assert_eq!(bus.len(), 1);
+        assert_eq!(client.command("< open vcan0 >"), "error bus already open");
+        assert_eq!(client.command("< bogus >"), "error unknown command");
+        assert_eq!(
+            client.command("< send 123 2 11 >"),
+            "error wrong data length"
+        );
+
+        assert_eq!(
+            client.command("< isotpmode >"),
+            "error isotp mode is not supported"
+        );
+        assert_eq!(
+            client.command("< controlmode >"),
+            "error control mode is not supported"
+        );
+        assert_eq!(client.command("< rawmode >"), "ok");
+        assert_eq!(
+            client.command("< subscribe 0 0 123 >"),
+            "error command not available in this mode"
+        );
+
+        // The bus is closed with the session
+        drop(client);
+        for _ in 0..100 {
+            if bus.is_empty() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("Bus wasn't closed");
+    }
+
+    #[test]
+    fn test_raw() {
+        let bus = VirtualBus::new();
+        let node = bus.connect();
+        let mut client = Client::connect(start(&bus));
+
+        assert_eq!(client.command("< open vcan0 >"), "ok");
+        assert_eq!(client.command("< rawmode >"), "ok");
+
+        send(&node, "123#R");
+        send(&node, "123#DEADBEEF");
+        send(&node, "12345678##10102");
+        let elem = client.recv();
+        assert!(elem.starts_with("frame 123 "), "{}", elem);
+        assert!(elem.ends_with(" DEADBEEF"), "{}", elem);
+        let elem = client.recv();
+        assert!(elem.starts_with("fdframe 12345678 "), "{}", elem);
+        assert!(elem.ends_with(" 1 0102"), "{}", elem);
+
+        let ts: f64 = elem.split(' ').nth(2).unwrap().parse().unwrap();
+        let now = now_timestamp().as_secs_f64();
+        assert!((now - ts).abs() < 5.0);
+
+        let timeout = Duration::from_secs(1);
+        client.send("< send 321 2 BE EF >");
+        assert_eq!(recv(&node, timeout).as_deref(), Some("321#BEEF"));
+        client.send("< fdsend 00000321 1 01 02 >");
+        assert_eq!(recv(&node, timeout).as_deref(), Some("00000321##10102"));
+    }
+
+    #[test]
+    fn test_bcm() {
+        let bus = VirtualBus::new();
+        let node = bus.connect();
+        let mut client = Client::connect(start(&bus));
+
+        assert_eq!(client.command("< open vcan0 >"), "ok");
+        client.send("< subscribe 0 0 123 >");
+        client.send("< filter 0 0 200 2 FF 00 >");
+        client.sync();
+
+        send(&node, "100#01");
+        send(&node, "123#DEAD");
+        let elem = client.recv();
+        assert!(elem.starts_with("frame 123 "), "{}", elem);
+        assert!(elem.ends_with(" DE AD"), "{}", elem);
+
+        // Only changes to the masked data are passed on
+        send(&node, "200#0102");
+        send(&node, "200#0103");
+        send(&node, "200#0203");
+        assert!(client.recv().ends_with(" 01 02"));
+        assert!(client.recv().ends_with(" 02 03"));
+
+        client.send("< unsubscribe 123 >");
+        client.sync();
+        assert_eq!(
+            client.command("< unsubscribe 123 >"),
+            "error no such subscription"
+        );
+
+        // A throttled subscription holds back the latest frame
+        client.send("< subscribe 0 200000 300 >");
+        client.sync();
+        let start = Instant::now();
+        send(&node, "300#01");
+        send(&node, "300#02");
+        send(&node, "300#03");
+        assert!(client.recv().ends_with(" 01"));
+        assert!(client.recv().ends_with(" 03"));
+        assert!(start.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[test]
+    fn test_cyclic() {
+        let bus = VirtualBus::new();
+        let node = bus.connect();
+        let mut client = Client::connect(start(&bus));
+        let timeout = Duration::from_secs(1);
+
+        assert_eq!(client.command("< open vcan0 >"), "ok");
+        assert_eq!(client.command("< update 456 1 06 >"), "error no such job");
+        assert_eq!(
+            client.command("< add 18446744073709551615 999999 456 1 05 >"),
+            "error invalid time"
+        );
+        assert_eq!(
+            client.command("< subscribe 86401 0 456 >"),
+            "error invalid time"
+        );
+
+        client.send("< add 0 10000 456 1 05 >");
+        for _ in 0..3 {
+            assert_eq!(recv(&node, timeout).as_deref(), Some("456#05"));
+        }
+
+        client.send("< update 456 1 06 >");
+        while recv(&node, timeout).as_deref() == Some("456#05") {}
+        assert_eq!(recv(&node, timeout).as_deref(), Some("456#06"));
+
+        client.send("< delete 456 >");
+        client.sync();
+        while recv(&node, Duration::from_millis(50)).is_some() {}
+        assert_eq!(client.command("< delete 456 >"), "error no such job");
+
+        // Switching modes also ends the jobs
+        client.send("< add 0 10000 456 1 05 >");
+        assert_eq!(recv(&node, timeout).as_deref(), Some("456#05"));
+        assert_eq!(client.command("< rawmode >"), "ok");
+        while recv(&node, Duration::from_millis(50)).is_some() {}
+    }
+}
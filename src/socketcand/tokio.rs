@@ -0,0 +1,337 @@
+// socketcan/src/socketcand/tokio.rs
+//
+// An asynchronous socketcand client for tokio.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! An asynchronous socketcand client for tokio.
+//!
+//! This is the same as the blocking [`Client`](super::Client), and, like
+//! the tokio [`CanFdSocket`](crate::tokio::CanFdSocket), it is a `Stream`
+//! of the received frames and a `Sink` for the frames to send:
+//!
+//! ```no_run
+//! use futures_util::{SinkExt, StreamExt};
+//! use socketcan::{socketcand::tokio::Client, CanAnyFrame};
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let mut client = Client::connect("bench1:29536", "can0").await?;
+//!     client.set_filters(&[(0x7E8, 0x7F8)]);
+//!
+//!     let request: CanAnyFrame = "7DF#020100".parse()?;
+//!     client.send(request).await?;
+//!
+//!     while let Some(frame) = client.next().await {
+//!         println!("{}", frame?);
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use super::{
+    client::{accept_all, expect, handshake, receive, send_element, CONNECT_TIMEOUT},
+    Elements,
+};
+use crate::{CanAnyFrame, CanFilter, Error, Result};
+use futures::{ready, Sink, Stream, StreamExt};
+use std::{
+    future, io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream, ToSocketAddrs,
+    },
+};
+
+/// The amount of data buffered by the `Sink` before it waits for a flush.
+const MAX_PENDING: usize = 4096;
+
+/// An asynchronous client for a bus on a socketcand server, in raw mode.
+#[derive(Debug)]
+pub struct Client {
+    bus: String,
+    rd: OwnedReadHalf,
+    wr: OwnedWriteHalf,
+    elems: Elements,
+    /// The elements queued by the `Sink`
+    pending: Vec<u8>,
+    filters: Vec<CanFilter>,
+}
+
+impl Client {
+    /// Connects to the server at the address, and opens the named bus.
+    pub async fn connect<A: ToSocketAddrs>(addr: A, bus: &str) -> io::Result<Self> {
+        let stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(addr))
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+        stream.set_nodelay(true)?;
+        let (rd, wr) = stream.into_split();
+
+        let mut client = Self {
+            bus: bus.into(),
+            rd,
+            wr,
+            elems: Elements::default(),
+            pending: Vec::new(),
+            filters: accept_all(),
+        };
+
+        expect(&client.recv_element().await?, "hi")?;
+        for (req, reply) in handshake(bus) {
+            client.wr.write_all(req.as_bytes()).await?;
+            expect(&client.recv_element().await?, reply)?;
+        }
+        Ok(client)
+    }
+
+    /// Gets the name of the bus on the server.
+    pub fn bus(&self) -> &str {
+        &self.bus
+    }
+
+    /// Sets the filters for the frames to receive.
+    ///
+    /// A frame is received if it passes any of the filters.
+    pub fn set_filters<F>(&mut self, filters: &[F])
+    where
+        F: Into<CanFilter> + Copy,
+    {
+        self.filters = filters.iter().map(|&f| f.into()).collect();
+    }
+
+    /// Receives every frame, disabling any filtering.
+    pub fn set_filter_accept_all(&mut self) {
+        self.filters = accept_all();
+    }
+
+    /// Receives no frames.
+    pub fn set_filter_drop_all(&mut self) {
+        self.filters.clear();
+    }
+
+    /// Polls to read more data from the server into the element buffer.
+    ///
+    /// This resolves to `false` when the server closes the connection.
+    fn poll_fill(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<bool>> {
+        let mut buf = [0u8; 1024];
+        let mut buf = ReadBuf::new(&mut buf);
+        ready!(Pin::new(&mut self.rd).poll_read(cx, &mut buf))?;
+        self.elems.push(buf.filled());
+        Poll::Ready(Ok(!buf.filled().is_empty()))
+    }
+
+    /// Reads the next element from the server, with the connect timeout.
+    async fn recv_element(&mut self) -> io::Result<String> {
+        let recv = future::poll_fn(|cx| loop {
+            if let Some(elem) = self.elems.next_element() {
+                return Poll::Ready(Ok(elem));
+            }
+            if !ready!(self.poll_fill(cx))? {
+                return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+            }
+        });
+        tokio::time::timeout(CONNECT_TIMEOUT, recv)
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?
+    }
+
+    /// Polls for the next frame that passes the filters, with its
+    /// timestamp.
+    ///
+    /// This is cancel safe, as any partial element is kept in the buffer.
+    fn poll_timestamped(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<io::Result<(CanAnyFrame, Duration)>>> {
+        loop {
+            while let Some(elem) = self.elems.next_element() {
+                if let Some(res) = receive(&elem, &self.filters) {
+                    return Poll::Ready(Some(res));
+                }
+            }
+            if !ready!(self.poll_fill(cx))? {
+                return Poll::Ready(None);
+            }
+        }
+    }
+
+    /// Reads the next frame that passes the filters, with the time that
+    /// the server received it, since the Unix epoch.
+    pub async fn read_timestamped(&mut self) -> Result<(CanAnyFrame, Duration)> {
+        match future::poll_fn(|cx| self.poll_timestamped(cx)).await {
+            Some(res) => Ok(res?),
+            None => Err(io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+
+    /// Reads the next frame that passes the filters.
+    pub async fn read_frame(&mut self) -> Result<CanAnyFrame> {
+        match self.next().await {
+            Some(res) => res,
+            None => Err(io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+
+    /// Writes a data frame to the bus.
+    ///
+    /// Remote and error frames can't be sent over the protocol, so they
+    /// fail with an `InvalidInput` error.
+    pub async fn write_frame<F: Into<CanAnyFrame>>(&mut self, frame: F) -> Result<()> {
+        let elem = send_element(&frame.into())?;
+        future::poll_fn(|cx| self.poll_write_pending(cx)).await?;
+        self.wr.write_all(elem.as_bytes()).await?;
+        Ok(())
+    }
+
+    /// Polls to write out the elements queued by the `Sink`.
+    fn poll_write_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.pending.is_empty() {
+            let n = ready!(Pin::new(&mut self.wr).poll_write(cx, &self.pending))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.pending.drain(..n);
+        }
+        Pin::new(&mut self.wr).poll_flush(cx)
+    }
+}
+
+impl Stream for Client {
+    type Item = Result<CanAnyFrame>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut()
+            .poll_timestamped(cx)
+            .map(|res| res.map(|res| res.map(|(frame, _)| frame).map_err(Error::from)))
+    }
+}
+
+impl Sink<CanAnyFrame> for Client {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        if this.pending.len() >= MAX_PENDING {
+            ready!(this.poll_write_pending(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: CanAnyFrame) -> Result<()> {
+        let elem = send_element(&item)?;
+        self.get_mut().pending.extend_from_slice(elem.as_bytes());
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_write_pending(cx).map_err(Error::from)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_pending(cx))?;
+        Pin::new(&mut this.wr)
+            .poll_shutdown(cx)
+            .map_err(Error::from)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        link::{Link, VirtualBus},
+        socketcand::Server,
+    };
+    use futures::SinkExt;
+    use std::{net::SocketAddr, thread};
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// Starts a server for the bus, as `vcan0`.
+    fn start(bus: &VirtualBus) -> SocketAddr {
+        let bus = bus.clone();
+        let server = Server::with_opener("127.0.0.1:0", &["vcan0"], move |_| {
+            Ok(Box::new(bus.connect()) as Box<dyn Link + Send + Sync>)
+        })
+        .unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.serve());
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_connect() {
+        let bus = VirtualBus::new();
+        let addr = start(&bus);
+
+        let err = Client::connect(addr, "can0").await.unwrap_err();
+        assert_eq!(err.to_string(), "no such bus");
+
+        let client = Client::connect(addr, "vcan0").await.unwrap();
+        assert_eq!(client.bus(), "vcan0");
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let bus = VirtualBus::new();
+        let node = bus.connect();
+        let mut client = Client::connect(start(&bus), "vcan0").await.unwrap();
+
+        for frame in ["123#R", "123#DEADBEEF", "223#01", "12345678##10102"] {
+            node.send(&frame.parse().unwrap()).unwrap();
+        }
+
+        let frame = client.next().await.unwrap().unwrap();
+        assert_eq!(frame.to_string(), "123#DEADBEEF");
+
+        client.set_filters(&[(0x12345678 | libc::CAN_EFF_FLAG, libc::CAN_EFF_MASK)]);
+        let (frame, ts) = client.read_timestamped().await.unwrap();
+        assert_eq!(frame.to_string(), "12345678##10102");
+        assert!(ts > Duration::from_secs(1_600_000_000));
+
+        // The server closes the stream when the client shuts down its end
+        client.close().await.unwrap();
+        assert!(client.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sink() {
+        let bus = VirtualBus::new();
+        let node = bus.connect();
+        let mut client = Client::connect(start(&bus), "vcan0").await.unwrap();
+
+        let frames: Vec<CanAnyFrame> = ["321#BEEF", "00000321##10102", "7FF#"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        for &frame in &frames[..2] {
+            client.feed(frame).await.unwrap();
+        }
+        client.flush().await.unwrap();
+        client.write_frame(frames[2]).await.unwrap();
+
+        let recv = tokio::task::spawn_blocking(move || {
+            (0..3)
+                .map(|_| node.recv(Some(TIMEOUT)).unwrap().unwrap().to_string())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(recv.await.unwrap(), ["321#BEEF", "00000321##10102", "7FF#"]);
+
+        let remote: CanAnyFrame = "321#R".parse().unwrap();
+        assert!(client.send(remote).await.is_err());
+    }
+}
@@ -0,0 +1,713 @@
+// socketcan/src/uds/client.rs
+//
+// The UDS diagnostic client.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! The UDS diagnostic client, or tester.
+//!
+//! The [`Client`] sends each request and waits for the response, handling
+//! the "response pending" negative responses by extending the wait to the
+//! P2* time. Any other negative response is returned as an
+//! [`Error::Negative`].
+//!
+//! The session is kept open with a [`KeepAlive`], which sends TesterPresent
+//! requests from a background thread over a separate ISO-TP socket, usually
+//! to the functional address:
+//!
+//! ```no_run
+//! use socketcan::{
+//!     isotp::{IsoTpSocket, Options},
+//!     uds::{Client, KeepAlive, Session},
+//!     CanSocket, StandardId,
+//! };
+//! use std::time::Duration;
+//!
+//! let id = |id| StandardId::new(id).unwrap();
+//! let sock = IsoTpSocket::<CanSocket>::open("can0", Options::new(id(0x7E0), id(0x7E8))).unwrap();
+//! let mut client = Client::new(sock);
+//!
+//! let functional = IsoTpSocket::<CanSocket>::open("can0", Options::new(id(0x7DF), id(0x7E8))).unwrap();
+//! let _keep_alive = KeepAlive::start(functional, Duration::from_secs(2));
+//!
+//! client.diagnostic_session_control(Session::Programming).unwrap();
+//! client.security_access(0x01, |_level, seed| seed.iter().map(|b| b ^ 0x5A).collect()).unwrap();
+//! ```
+
+use super::{
+    dtc_report, sid, Dtc, DtcStatus, Error, Nrc, ResetType, RoutineControl, Session,
+    SUPPRESS_POSITIVE_RESPONSE,
+};
+use crate::isotp::{self, IsoTpSocket, Link};
+use std::{
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// The default time to wait for a response
+const DEFAULT_P2: Duration = Duration::from_millis(1000);
+
+/// The default time to wait for a response after a "response pending"
+const DEFAULT_P2_STAR: Duration = Duration::from_millis(5000);
+
+/// The TesterPresent request with the positive response suppressed
+const TESTER_PRESENT_SUPPRESSED: [u8; 2] = [sid::TESTER_PRESENT, SUPPRESS_POSITIVE_RESPONSE];
+
+/// The response timing for a diagnostic session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionTimings {
+    /// The time to wait for a response
+    pub p2: Duration,
+    /// The time to wait for a response after a "response pending"
+    pub p2_star: Duration,
+}
+
+impl SessionTimings {
+    /// Parses the timings from the parameter record of a
+    /// DiagnosticSessionControl response.
+    ///
+    /// P2 is in milliseconds, and P2* in units of 10 ms.
+    pub fn from_bytes(b: &[u8; 4]) -> Self {
+        Self {
+            p2: Duration::from_millis(u16::from_be_bytes([b[0], b[1]]) as u64),
+            p2_star: Duration::from_millis(10 * u16::from_be_bytes([b[2], b[3]]) as u64),
+        }
+    }
+
+    /// Gets the parameter record for a DiagnosticSessionControl response.
+    pub fn to_bytes(&self) -> [u8; 4] {
+        let p2 = (self.p2.as_millis().min(0xFFFF) as u16).to_be_bytes();
+        let p2_star = ((self.p2_star.as_millis() / 10).min(0xFFFF) as u16).to_be_bytes();
+        [p2[0], p2[1], p2_star[0], p2_star[1]]
+    }
+}
+
+impl Default for SessionTimings {
+    fn default() -> Self {
+        Self {
+            p2: DEFAULT_P2,
+            p2_star: DEFAULT_P2_STAR,
+        }
+    }
+}
+
+// ===== Client =====
+
+/// A UDS client over an ISO-TP connection.
+#[derive(Debug)]
+pub struct Client<L> {
+    sock: IsoTpSocket<L>,
+    timings: SessionTimings,
+}
+
+impl<L: Link> Client<L> {
+    /// Creates a client over an ISO-TP connection to the server.
+    pub fn new(sock: IsoTpSocket<L>) -> Self {
+        Self {
+            sock,
+            timings: SessionTimings::default(),
+        }
+    }
+
+    /// Gets the time limits for responses.
+    pub fn timings(&self) -> SessionTimings {
+        self.timings
+    }
+
+    /// Sets the time limits for responses.
+    pub fn set_timings(&mut self, timings: SessionTimings) {
+        self.timings = timings;
+    }
+
+    /// Gets a reference to the ISO-TP connection.
+    pub fn socket(&self) -> &IsoTpSocket<L> {
+        &self.sock
+    }
+
+    /// Consumes the client, returning the ISO-TP connection.
+    pub fn into_socket(self) -> IsoTpSocket<L> {
+        self.sock
+    }
+
+    /// Sends a request without waiting for a response.
+    ///
+    /// This is for requests with the suppress positive response bit set.
+    pub fn send(&mut self, req: &[u8]) -> Result<(), Error> {
+        Ok(self.sock.send(req)?)
+    }
+
+    /// Sends a request and waits for the positive response.
+    ///
+    /// Returns the whole response, starting with the response service ID.
+    /// "Response pending" replies extend the wait to P2*, and any other
+    /// negative response is returned as an error.
+    pub fn request(&mut self, req: &[u8]) -> Result<Vec<u8>, Error> {
+        let service = *req.first().ok_or(isotp::Error::InvalidLength(0))?;
+        self.sock.send(req)?;
+
+        let mut timeout = self.timings.p2;
+        loop {
+            let resp = self.sock.recv_timeout(timeout)?.ok_or(Error::Timeout)?;
+            match resp.first().copied() {
+                Some(b) if b == service.wrapping_add(sid::POSITIVE_RESPONSE) => return Ok(resp),
+                Some(sid::NEGATIVE_RESPONSE) if resp.len() >= 3 => {
+                    // Ignore stale replies to earlier requests
+                    if resp[1] != service {
+                        continue;
+                    }
+                    match Nrc::from(resp[2]) {
+                        Nrc::ResponsePending => timeout = self.timings.p2_star,
+                        nrc => return Err(Error::Negative { service, nrc }),
+                    }
+                }
+                _ => return Err(Error::InvalidResponse(resp)),
+            }
+        }
+    }
+
+    /// Sends a request, checking that the positive response is at least
+    /// `len` bytes long, and that it echoes the bytes after the service ID
+    /// for the first `echo` bytes.
+    fn request_echo(&mut self, req: &[u8], echo: usize, len: usize) -> Result<Vec<u8>, Error> {
+        let resp = self.request(req)?;
+        if resp.len() < len.max(1 + echo) || resp[1..1 + echo] != req[1..1 + echo] {
+            return Err(Error::InvalidResponse(resp));
+        }
+        Ok(resp)
+    }
+
+    /// Changes the diagnostic session (0x10).
+    ///
+    /// Returns the response timings reported by the server. The client's
+    /// time limits are raised to at least those of the server.
+    pub fn diagnostic_session_control(
+        &mut self,
+        session: Session,
+    ) -> Result<SessionTimings, Error> {
+        let resp = self.request_echo(&[sid::DIAGNOSTIC_SESSION_CONTROL, session.into()], 1, 2)?;
+        let timings = match resp.get(2..6) {
+            Some(b) => SessionTimings::from_bytes(&[b[0], b[1], b[2], b[3]]),
+            None => return Ok(self.timings),
+        };
+        self.timings.p2 = self.timings.p2.max(timings.p2);
+        self.timings.p2_star = self.timings.p2_star.max(timings.p2_star);
+        Ok(timings)
+    }
+
+    /// Resets the ECU (0x11).
+    ///
+    /// Returns the power down time, in seconds, if the server reported one.
+    pub fn ecu_reset(&mut self, reset: ResetType) -> Result<Option<u8>, Error> {
+        let resp = self.request_echo(&[sid::ECU_RESET, reset.into()], 1, 2)?;
+        Ok(resp.get(2).copied())
+    }
+
+    /// Requests the seed for a security level (0x27).
+    ///
+    /// The level is the odd "request seed" sub-function, from 0x01 to
+    /// 0x7D.
+    pub fn request_seed(&mut self, level: u8) -> Result<Vec<u8>, Error> {
+        check_security_level(level)?;
+        let resp = self.request_echo(&[sid::SECURITY_ACCESS, level], 1, 2)?;
+        Ok(resp[2..].to_vec())
+    }
+
+    /// Sends the key for a security level (0x27).
+    ///
+    /// The level is the odd "request seed" sub-function, as for
+    /// [`request_seed()`](Self::request_seed). The key is sent with the
+    /// following even sub-function.
+    pub fn send_key(&mut self, level: u8, key: &[u8]) -> Result<(), Error> {
+        check_security_level(level)?;
+        let mut req = vec![sid::SECURITY_ACCESS, level + 1];
+        req.extend_from_slice(key);
+        self.request_echo(&req, 1, 2)?;
+        Ok(())
+    }
+
+    /// Unlocks a security level (0x27), computing the key from the seed
+    /// with the callback.
+    ///
+    /// The callback gets the level and the seed. If the level is already
+    /// unlocked, the server sends a seed of all zeros, and no key is sent.
+    pub fn security_access<F>(&mut self, level: u8, key_fn: F) -> Result<(), Error>
+    where
+        F: FnOnce(u8, &[u8]) -> Vec<u8>,
+    {
+        let seed = self.request_seed(level)?;
+        if seed.iter().all(|&b| b == 0) {
+            return Ok(());
+        }
+        let key = key_fn(level, &seed);
+        self.send_key(level, &key)
+    }
+
+    /// Reads a data identifier (0x22).
+    pub fn read_data_by_identifier(&mut self, did: u16) -> Result<Vec<u8>, Error> {
+        let [hi, lo] = did.to_be_bytes();
+        let resp = self.request_echo(&[sid::READ_DATA_BY_IDENTIFIER, hi, lo], 2, 3)?;
+        Ok(resp[3..].to_vec())
+    }
+
+    /// Writes a data identifier (0x2E).
+    pub fn write_data_by_identifier(&mut self, did: u16, data: &[u8]) -> Result<(), Error> {
+        let [hi, lo] = did.to_be_bytes();
+        let mut req = vec![sid::WRITE_DATA_BY_IDENTIFIER, hi, lo];
+        req.extend_from_slice(data);
+        self.request_echo(&req, 2, 3)?;
+        Ok(())
+    }
+
+    /// Starts or stops a routine, or requests its results (0x31).
+    ///
+    /// Returns the routine status record from the response.
+    pub fn routine_control(
+        &mut self,
+        ctrl: RoutineControl,
+        id: u16,
+        params: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        let [hi, lo] = id.to_be_bytes();
+        let mut req = vec![sid::ROUTINE_CONTROL, ctrl.into(), hi, lo];
+        req.extend_from_slice(params);
+        let resp = self.request_echo(&req, 3, 4)?;
+        Ok(resp[4..].to_vec())
+    }
+
+    /// Requests a download of data to the ECU (0x34).
+    ///
+    /// The address and size are sent as four bytes each. Returns the
+    /// maximum length of each TransferData request, including the service
+    /// ID and the block sequence counter.
+    pub fn request_download(
+        &mut self,
+        data_format: u8,
+        address: u32,
+        size: u32,
+    ) -> Result<usize, Error> {
+        let mut req = vec![sid::REQUEST_DOWNLOAD, data_format, 0x44];
+        req.extend_from_slice(&address.to_be_bytes());
+        req.extend_from_slice(&size.to_be_bytes());
+
+        let resp = self.request(&req)?;
+        let n = (resp.get(1).copied().unwrap_or(0) >> 4) as usize;
+        let max_len = match resp.get(2..2 + n) {
+            Some(len) if (1..=8).contains(&n) => {
+                len.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize)
+            }
+            _ => 0,
+        };
+        // There has to be room for some data after the counter
+        if max_len <= 2 {
+            return Err(Error::InvalidResponse(resp));
+        }
+        Ok(max_len)
+    }
+
+    /// Transfers a block of data (0x36).
+    ///
+    /// Returns the transfer response parameters, if any.
+    pub fn transfer_data(&mut self, counter: u8, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut req = vec![sid::TRANSFER_DATA, counter];
+        req.extend_from_slice(data);
+        let resp = self.request_echo(&req, 1, 2)?;
+        Ok(resp[2..].to_vec())
+    }
+
+    /// Ends a data transfer (0x37).
+    ///
+    /// Returns the transfer response parameters, if any.
+    pub fn request_transfer_exit(&mut self, params: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut req = vec![sid::REQUEST_TRANSFER_EXIT];
+        req.extend_from_slice(params);
+        let resp = self.request(&req)?;
+        Ok(resp[1..].to_vec())
+    }
+
+    /// Downloads a block of memory to the ECU, with RequestDownload,
+    /// TransferData, and RequestTransferExit.
+    ///
+    /// The data is sent uncompressed and unencrypted, in blocks as large as
+    /// the server allows.
+    pub fn download(&mut self, address: u32, data: &[u8]) -> Result<(), Error> {
+        let size = u32::try_from(data.len())
+            .map_err(|_| Error::IsoTp(isotp::Error::InvalidLength(data.len())))?;
+        let max_len = self.request_download(0x00, address, size)?;
+
+        let mut counter = 1u8;
+        for block in data.chunks(max_len - 2) {
+            self.transfer_data(counter, block)?;
+            counter = counter.wrapping_add(1);
+        }
+        self.request_transfer_exit(&[])?;
+        Ok(())
+    }
+
+    /// Tells the server that the tester is still present (0x3E), waiting
+    /// for the response.
+    pub fn tester_present(&mut self) -> Result<(), Error> {
+        self.request_echo(&[sid::TESTER_PRESENT, 0x00], 1, 2)?;
+        Ok(())
+    }
+
+    /// Clears the diagnostic information for a group of DTCs (0x14).
+    ///
+    /// The group 0xFFFFFF is all DTCs.
+    pub fn clear_diagnostic_information(&mut self, group: u32) -> Result<(), Error> {
+        let g = group.to_be_bytes();
+        self.request(&[sid::CLEAR_DIAGNOSTIC_INFORMATION, g[1], g[2], g[3]])?;
+        Ok(())
+    }
+
+    /// Sends a ReadDTCInformation request (0x19) for any report type.
+    ///
+    /// Returns the response after the report type.
+    pub fn read_dtc_information(&mut self, report: u8, params: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut req = vec![sid::READ_DTC_INFORMATION, report];
+        req.extend_from_slice(params);
+        let resp = self.request_echo(&req, 1, 2)?;
+        Ok(resp[2..].to_vec())
+    }
+
+    /// Reads the number of DTCs that match the status mask.
+    pub fn read_dtc_count(&mut self, mask: DtcStatus) -> Result<u16, Error> {
+        let resp =
+            self.read_dtc_information(dtc_report::NUMBER_OF_DTC_BY_STATUS_MASK, &[mask.bits()])?;
+        match resp[..] {
+            [_avail, _format, hi, lo] => Ok(u16::from_be_bytes([hi, lo])),
+            _ => Err(Error::InvalidResponse(resp)),
+        }
+    }
+
+    /// Reads the DTCs with any of the status bits in the mask.
+    pub fn read_dtcs(&mut self, mask: DtcStatus) -> Result<Vec<Dtc>, Error> {
+        let resp = self.read_dtc_information(dtc_report::DTC_BY_STATUS_MASK, &[mask.bits()])?;
+        Self::parse_dtcs(resp)
+    }
+
+    /// Reads all the DTCs supported by the server, with their status.
+    pub fn read_supported_dtcs(&mut self) -> Result<Vec<Dtc>, Error> {
+        let resp = self.read_dtc_information(dtc_report::SUPPORTED_DTC, &[])?;
+        Self::parse_dtcs(resp)
+    }
+
+    /// Parses the DTC records after the status availability mask.
+    fn parse_dtcs(resp: Vec<u8>) -> Result<Vec<Dtc>, Error> {
+        if resp.is_empty() || (resp.len() - 1) % 4 != 0 {
+            return Err(Error::InvalidResponse(resp));
+        }
+        Ok(resp[1..]
+            .chunks(4)
+            .map(|b| Dtc::from_bytes(&[b[0], b[1], b[2], b[3]]))
+            .collect())
+    }
+}
+
+/// Checks that a security access level is an odd "request seed" level,
+/// so that the "send key" level after it is valid.
+fn check_security_level(level: u8) -> Result<(), Error> {
+    if level % 2 == 1 && level < 0x7F {
+        Ok(())
+    } else {
+        Err(Error::InvalidSecurityLevel(level))
+    }
+}
+
+// ===== KeepAlive =====
+
+/// Keeps a diagnostic session open by sending TesterPresent requests from
+/// a background thread.
+///
+/// The requests suppress the positive response, so they can be sent to the
+/// functional address, without interfering with the responses to the
+/// client. They stop when this is dropped.
+#[derive(Debug)]
+pub struct KeepAlive {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl KeepAlive {
+    /// Starts sending TesterPresent requests over the ISO-TP connection,
+    /// at the interval.
+    pub fn start<L>(mut sock: IsoTpSocket<L>, interval: Duration) -> Self
+    where
+        L: Link + Send + 'static,
+    {
+        let (stop, rx) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(interval) {
+                if let Err(err) = sock.send(&TESTER_PRESENT_SUPPRESSED) {
+                    log::warn!("Failed to send TesterPresent: {}", err);
+                }
+            }
+        });
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Stops sending the requests, waiting for the thread to finish.
+    pub fn stop(self) {}
+}
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        isotp::{Options, VirtualBus},
+        StandardId,
+    };
+    use std::time::Instant;
+
+    fn id(id: u16) -> StandardId {
+        StandardId::new(id).unwrap()
+    }
+
+    // Starts a scripted ECU on the bus, which replies to each request with
+    // the responses from the handler, until the client goes quiet.
+    fn ecu<F>(bus: &VirtualBus, mut handler: F) -> JoinHandle<()>
+    where
+        F: FnMut(&[u8]) -> Vec<Vec<u8>> + Send + 'static,
+    {
+        let mut sock = IsoTpSocket::new(bus.connect(), Options::new(id(0x7E8), id(0x7E0)));
+        thread::spawn(move || {
+            while let Ok(Some(req)) = sock.recv_timeout(Duration::from_millis(200)) {
+                for resp in handler(&req) {
+                    sock.send(&resp).unwrap();
+                }
+            }
+        })
+    }
+
+    fn client(bus: &VirtualBus) -> Client<isotp::VirtualLink> {
+        Client::new(IsoTpSocket::new(
+            bus.connect(),
+            Options::new(id(0x7E0), id(0x7E8)),
+        ))
+    }
+
+    #[test]
+    fn test_session_and_reset() {
+        let bus = VirtualBus::new();
+        let ecu = ecu(&bus, |req| match req {
+            [0x10, 0x03] => vec![vec![0x50, 0x03, 0x00, 0x32, 0x07, 0xD0]],
+            [0x11, 0x01] => vec![vec![0x51, 0x01]],
+            _ => vec![vec![0x7F, req[0], 0x11]],
+        });
+
+        let mut client = client(&bus);
+        let timings = client
+            .diagnostic_session_control(Session::Extended)
+            .unwrap();
+        assert_eq!(timings.p2, Duration::from_millis(50));
+        assert_eq!(timings.p2_star, Duration::from_secs(20));
+        assert_eq!(client.timings().p2, DEFAULT_P2);
+        assert_eq!(client.timings().p2_star, Duration::from_secs(20));
+
+        assert_eq!(client.ecu_reset(ResetType::Hard).unwrap(), None);
+
+        let err = client.request(&[0x85, 0x02]).unwrap_err();
+        assert_eq!(err.nrc(), Some(Nrc::ServiceNotSupported));
+        drop(client);
+        ecu.join().unwrap();
+    }
+
+    #[test]
+    fn test_response_pending() {
+        let bus = VirtualBus::new();
+        let ecu = ecu(&bus, |req| {
+            vec![
+                vec![0x7F, req[0], 0x78],
+                vec![0x7F, req[0], 0x78],
+                vec![0x71, 0x01, 0xFF, 0x00, 0x02],
+            ]
+        });
+
+        let mut client = client(&bus);
+        let status = client
+            .routine_control(RoutineControl::Start, 0xFF00, &[0x01])
+            .unwrap();
+        assert_eq!(status, &[0x02]);
+        drop(client);
+        ecu.join().unwrap();
+    }
+
+    #[test]
+    fn test_timeout() {
+        let bus = VirtualBus::new();
+        let mut client = client(&bus);
+        client.set_timings(SessionTimings {
+            p2: Duration::from_millis(20),
+            p2_star: Duration::from_millis(20),
+        });
+
+        let start = Instant::now();
+        assert!(matches!(client.tester_present(), Err(Error::Timeout)));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_security_access() {
+        let bus = VirtualBus::new();
+        let ecu = ecu(&bus, |req| match req {
+            [0x27, 0x01] => vec![vec![0x67, 0x01, 0x12, 0x34]],
+            [0x27, 0x02, 0x48, 0x6E] => vec![vec![0x67, 0x02]],
+            [0x27, 0x02, ..] => vec![vec![0x7F, 0x27, 0x35]],
+            [0x27, 0x03] => vec![vec![0x67, 0x03, 0x00, 0x00]],
+            _ => vec![vec![0x7F, req[0], 0x11]],
+        });
+
+        let mut client = client(&bus);
+        client
+            .security_access(0x01, |level, seed| {
+                assert_eq!(level, 0x01);
+                seed.iter().map(|b| b ^ 0x5A).collect()
+            })
+            .unwrap();
+
+        let err = client.security_access(0x01, |_, _| vec![0, 0]).unwrap_err();
+        assert_eq!(err.nrc(), Some(Nrc::InvalidKey));
+
+        // Already unlocked
+        client
+            .security_access(0x03, |_, _| panic!("no key needed"))
+            .unwrap();
+
+        // Invalid levels, and empty requests, fail without being sent
+        assert!(matches!(
+            client.send_key(0xFF, &[0]),
+            Err(Error::InvalidSecurityLevel(0xFF))
+        ));
+        assert!(matches!(
+            client.request_seed(0x02),
+            Err(Error::InvalidSecurityLevel(0x02))
+        ));
+        assert!(matches!(
+            client.request(&[]),
+            Err(Error::IsoTp(isotp::Error::InvalidLength(0)))
+        ));
+        drop(client);
+        ecu.join().unwrap();
+    }
+
+    #[test]
+    fn test_data_identifiers() {
+        let bus = VirtualBus::new();
+        let ecu = ecu(&bus, |req| match req {
+            [0x22, 0xF1, 0x90] => {
+                let mut resp = vec![0x62, 0xF1, 0x90];
+                resp.extend_from_slice(b"WVWZZZ1JZXW000001");
+                vec![resp]
+            }
+            [0x2E, 0x01, 0x23, ..] => vec![vec![0x6E, 0x01, 0x23]],
+            [0x22, 0x01, 0x24] => vec![vec![0x62, 0x01, 0x23, 0x00]],
+            _ => vec![vec![0x7F, req[0], 0x31]],
+        });
+
+        let mut client = client(&bus);
+        assert_eq!(
+            client.read_data_by_identifier(0xF190).unwrap(),
+            b"WVWZZZ1JZXW000001"
+        );
+        client.write_data_by_identifier(0x0123, &[1, 2, 3]).unwrap();
+
+        let err = client.read_data_by_identifier(0x0001).unwrap_err();
+        assert_eq!(err.nrc(), Some(Nrc::RequestOutOfRange));
+
+        // The response echoes the wrong DID
+        assert!(matches!(
+            client.read_data_by_identifier(0x0124),
+            Err(Error::InvalidResponse(_))
+        ));
+        drop(client);
+        ecu.join().unwrap();
+    }
+
+    #[test]
+    fn test_download() {
+        let bus = VirtualBus::new();
+        let (tx, rx) = mpsc::channel();
+        let ecu = ecu(&bus, move |req| match req[0] {
+            0x34 => {
+                assert_eq!(&req[1..], &[0x00, 0x44, 0, 0, 0x10, 0, 0, 0, 0, 100]);
+                vec![vec![0x74, 0x20, 0x00, 0x22]]
+            }
+            0x36 => {
+                tx.send((req[1], req.len() - 2)).unwrap();
+                vec![vec![0x76, req[1]]]
+            }
+            0x37 => vec![vec![0x77]],
+            _ => vec![vec![0x7F, req[0], 0x11]],
+        });
+
+        let mut client = client(&bus);
+        client.download(0x1000, &[0xAB; 100]).unwrap();
+        drop(client);
+        ecu.join().unwrap();
+
+        let blocks: Vec<_> = rx.iter().collect();
+        assert_eq!(blocks, &[(1, 32), (2, 32), (3, 32), (4, 4)]);
+    }
+
+    #[test]
+    fn test_dtcs() {
+        let bus = VirtualBus::new();
+        let ecu = ecu(&bus, |req| match req {
+            [0x19, 0x01, 0x08] => vec![vec![0x59, 0x01, 0xFF, 0x01, 0x00, 0x02]],
+            [0x19, 0x02, 0x08] => vec![vec![
+                0x59, 0x02, 0xFF, 0x12, 0x34, 0x56, 0x09, 0xC0, 0x01, 0x00, 0x08,
+            ]],
+            [0x14, 0xFF, 0xFF, 0xFF] => vec![vec![0x54]],
+            _ => vec![vec![0x7F, req[0], 0x12]],
+        });
+
+        let mut client = client(&bus);
+        assert_eq!(client.read_dtc_count(DtcStatus::CONFIRMED_DTC).unwrap(), 2);
+        let dtcs = client.read_dtcs(DtcStatus::CONFIRMED_DTC).unwrap();
+        assert_eq!(dtcs, &[Dtc::new(0x123456, 0x09), Dtc::new(0xC00100, 0x08)]);
+        client.clear_diagnostic_information(0xFFFFFF).unwrap();
+        drop(client);
+        ecu.join().unwrap();
+    }
+
+    #[test]
+    fn test_keep_alive() {
+        let bus = VirtualBus::new();
+        let (tx, rx) = mpsc::channel();
+        let ecu = ecu(&bus, move |req| {
+            tx.send(req.to_vec()).unwrap();
+            vec![]
+        });
+
+        let sock = IsoTpSocket::new(bus.connect(), Options::new(id(0x7E0), id(0x7E8)));
+        let keep_alive = KeepAlive::start(sock, Duration::from_millis(10));
+
+        // Wait for the requests to arrive, rather than for a fixed time
+        for _ in 0..3 {
+            let req = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert_eq!(req, TESTER_PRESENT_SUPPRESSED);
+        }
+        keep_alive.stop();
+        ecu.join().unwrap();
+        assert!(rx.iter().all(|req| req == TESTER_PRESENT_SUPPRESSED));
+    }
+}
@@ -0,0 +1,521 @@
+// socketcan/src/uds/mod.rs
+//
+// Unified Diagnostic Services (UDS) over ISO-TP.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! Unified Diagnostic Services (ISO 14229) over ISO-TP.
+//!
+//! UDS is the request/response protocol used to diagnose and program the
+//! ECUs in a vehicle. A tester sends a request starting with a service ID,
+//! and the ECU replies with a positive response, with the service ID plus
+//! 0x40, or a negative response, `7F <service> <code>`.
+//!
+//! The [`Client`] is the tester side of the protocol, running over an
+//! [`IsoTpSocket`](crate::isotp::IsoTpSocket):
+//!
+//! ```no_run
+//! use socketcan::{
+//!     isotp::{IsoTpSocket, Options},
+//!     uds::{Client, Session},
+//!     CanSocket, StandardId,
+//! };
+//!
+//! let opts = Options::new(StandardId::new(0x7E0).unwrap(), StandardId::new(0x7E8).unwrap());
+//! let sock = IsoTpSocket::<CanSocket>::open("can0", opts).unwrap();
+//! let mut client = Client::new(sock);
+//!
+//! client.diagnostic_session_control(Session::Extended).unwrap();
+//! let vin = client.read_data_by_identifier(0xF190).unwrap();
+//! println!("VIN: {}", String::from_utf8_lossy(&vin));
+//! ```
+//!
+//! The [`Server`] is the ECU side, a service dispatcher that can simulate
+//! an ECU for testing testers, on a virtual CAN interface or an in-process
+//! [`VirtualBus`](crate::isotp::VirtualBus).
+
+use crate::isotp;
+use std::{error, fmt};
+
+pub mod client;
+pub use client::{Client, KeepAlive, SessionTimings};
+
+pub mod server;
+pub use server::{Access, Did, Server};
+
+/// The service identifiers for the requests.
+///
+/// The positive response for each service has the ID plus
+/// [`POSITIVE_RESPONSE`](sid::POSITIVE_RESPONSE).
+pub mod sid {
+    /// DiagnosticSessionControl
+    pub const DIAGNOSTIC_SESSION_CONTROL: u8 = 0x10;
+    /// ECUReset
+    pub const ECU_RESET: u8 = 0x11;
+    /// ClearDiagnosticInformation
+    pub const CLEAR_DIAGNOSTIC_INFORMATION: u8 = 0x14;
+    /// ReadDTCInformation
+    pub const READ_DTC_INFORMATION: u8 = 0x19;
+    /// ReadDataByIdentifier
+    pub const READ_DATA_BY_IDENTIFIER: u8 = 0x22;
+    /// SecurityAccess
+    pub const SECURITY_ACCESS: u8 = 0x27;
+    /// WriteDataByIdentifier
+    pub const WRITE_DATA_BY_IDENTIFIER: u8 = 0x2E;
+    /// RoutineControl
+    pub const ROUTINE_CONTROL: u8 = 0x31;
+    /// RequestDownload
+    pub const REQUEST_DOWNLOAD: u8 = 0x34;
+    /// TransferData
+    pub const TRANSFER_DATA: u8 = 0x36;
+    /// RequestTransferExit
+    pub const REQUEST_TRANSFER_EXIT: u8 = 0x37;
+    /// TesterPresent
+    pub const TESTER_PRESENT: u8 = 0x3E;
+    /// The first byte of a negative response
+    pub const NEGATIVE_RESPONSE: u8 = 0x7F;
+    /// The offset added to the service ID for a positive response
+    pub const POSITIVE_RESPONSE: u8 = 0x40;
+}
+
+/// The bit in a sub-function byte that asks the server not to send a
+/// positive response.
+pub const SUPPRESS_POSITIVE_RESPONSE: u8 = 0x80;
+
+// ===== Nrc =====
+
+/// A negative response code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Nrc {
+    /// 0x10
+    GeneralReject,
+    /// 0x11
+    ServiceNotSupported,
+    /// 0x12
+    SubFunctionNotSupported,
+    /// 0x13
+    IncorrectMessageLengthOrInvalidFormat,
+    /// 0x14
+    ResponseTooLong,
+    /// 0x21
+    BusyRepeatRequest,
+    /// 0x22
+    ConditionsNotCorrect,
+    /// 0x24
+    RequestSequenceError,
+    /// 0x25
+    NoResponseFromSubnetComponent,
+    /// 0x26
+    FailurePreventsExecutionOfRequestedAction,
+    /// 0x31
+    RequestOutOfRange,
+    /// 0x33
+    SecurityAccessDenied,
+    /// 0x35
+    InvalidKey,
+    /// 0x36
+    ExceededNumberOfAttempts,
+    /// 0x37
+    RequiredTimeDelayNotExpired,
+    /// 0x70
+    UploadDownloadNotAccepted,
+    /// 0x71
+    TransferDataSuspended,
+    /// 0x72
+    GeneralProgrammingFailure,
+    /// 0x73
+    WrongBlockSequenceCounter,
+    /// 0x78, the request was received, but the response will take longer
+    ResponsePending,
+    /// 0x7E
+    SubFunctionNotSupportedInActiveSession,
+    /// 0x7F
+    ServiceNotSupportedInActiveSession,
+    /// Any other code, including the manufacturer-specific ones
+    Other(u8),
+}
+
+impl From<u8> for Nrc {
+    fn from(code: u8) -> Self {
+        use Nrc::*;
+        match code {
+            0x10 => GeneralReject,
+            0x11 => ServiceNotSupported,
+            0x12 => SubFunctionNotSupported,
+            0x13 => IncorrectMessageLengthOrInvalidFormat,
+            0x14 => ResponseTooLong,
+            0x21 => BusyRepeatRequest,
+            0x22 => ConditionsNotCorrect,
+            0x24 => RequestSequenceError,
+            0x25 => NoResponseFromSubnetComponent,
+            0x26 => FailurePreventsExecutionOfRequestedAction,
+            0x31 => RequestOutOfRange,
+            0x33 => SecurityAccessDenied,
+            0x35 => InvalidKey,
+            0x36 => ExceededNumberOfAttempts,
+            0x37 => RequiredTimeDelayNotExpired,
+            0x70 => UploadDownloadNotAccepted,
+            0x71 => TransferDataSuspended,
+            0x72 => GeneralProgrammingFailure,
+            0x73 => WrongBlockSequenceCounter,
+            0x78 => ResponsePending,
+            0x7E => SubFunctionNotSupportedInActiveSession,
+            0x7F => ServiceNotSupportedInActiveSession,
+            code => Other(code),
+        }
+    }
+}
+
+impl From<Nrc> for u8 {
+    fn from(nrc: Nrc) -> Self {
+        use Nrc::*;
+        match nrc {
+            GeneralReject => 0x10,
+            ServiceNotSupported => 0x11,
+            SubFunctionNotSupported => 0x12,
+            IncorrectMessageLengthOrInvalidFormat => 0x13,
+            ResponseTooLong => 0x14,
+            BusyRepeatRequest => 0x21,
+            ConditionsNotCorrect => 0x22,
+            RequestSequenceError => 0x24,
+            NoResponseFromSubnetComponent => 0x25,
+            FailurePreventsExecutionOfRequestedAction => 0x26,
+            RequestOutOfRange => 0x31,
+            SecurityAccessDenied => 0x33,
+            InvalidKey => 0x35,
+            ExceededNumberOfAttempts => 0x36,
+            RequiredTimeDelayNotExpired => 0x37,
+            UploadDownloadNotAccepted => 0x70,
+            TransferDataSuspended => 0x71,
+            GeneralProgrammingFailure => 0x72,
+            WrongBlockSequenceCounter => 0x73,
+            ResponsePending => 0x78,
+            SubFunctionNotSupportedInActiveSession => 0x7E,
+            ServiceNotSupportedInActiveSession => 0x7F,
+            Other(code) => code,
+        }
+    }
+}
+
+impl fmt::Display for Nrc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Nrc::*;
+        let s = match *self {
+            GeneralReject => "general reject",
+            ServiceNotSupported => "service not supported",
+            SubFunctionNotSupported => "sub-function not supported",
+            IncorrectMessageLengthOrInvalidFormat => "incorrect message length or invalid format",
+            ResponseTooLong => "response too long",
+            BusyRepeatRequest => "busy, repeat request",
+            ConditionsNotCorrect => "conditions not correct",
+            RequestSequenceError => "request sequence error",
+            NoResponseFromSubnetComponent => "no response from subnet component",
+            FailurePreventsExecutionOfRequestedAction => {
+                "failure prevents execution of requested action"
+            }
+            RequestOutOfRange => "request out of range",
+            SecurityAccessDenied => "security access denied",
+            InvalidKey => "invalid key",
+            ExceededNumberOfAttempts => "exceeded number of attempts",
+            RequiredTimeDelayNotExpired => "required time delay not expired",
+            UploadDownloadNotAccepted => "upload/download not accepted",
+            TransferDataSuspended => "transfer data suspended",
+            GeneralProgrammingFailure => "general programming failure",
+            WrongBlockSequenceCounter => "wrong block sequence counter",
+            ResponsePending => "response pending",
+            SubFunctionNotSupportedInActiveSession => {
+                "sub-function not supported in active session"
+            }
+            ServiceNotSupportedInActiveSession => "service not supported in active session",
+            Other(code) => return write!(f, "negative response code 0x{:02X}", code),
+        };
+        f.write_str(s)
+    }
+}
+
+// ===== Sub-functions =====
+
+/// A diagnostic session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Session {
+    /// The default session, 0x01
+    Default,
+    /// The programming session, 0x02
+    Programming,
+    /// The extended diagnostic session, 0x03
+    Extended,
+    /// The safety system diagnostic session, 0x04
+    SafetySystem,
+    /// Any other session type
+    Other(u8),
+}
+
+impl From<u8> for Session {
+    fn from(b: u8) -> Self {
+        match b & !SUPPRESS_POSITIVE_RESPONSE {
+            0x01 => Self::Default,
+            0x02 => Self::Programming,
+            0x03 => Self::Extended,
+            0x04 => Self::SafetySystem,
+            b => Self::Other(b),
+        }
+    }
+}
+
+impl From<Session> for u8 {
+    fn from(session: Session) -> Self {
+        match session {
+            Session::Default => 0x01,
+            Session::Programming => 0x02,
+            Session::Extended => 0x03,
+            Session::SafetySystem => 0x04,
+            Session::Other(b) => b,
+        }
+    }
+}
+
+/// The type of ECU reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResetType {
+    /// A hard reset, like a power cycle, 0x01
+    Hard,
+    /// A reset like turning the ignition off and on, 0x02
+    KeyOffOn,
+    /// A soft reset, restarting the application, 0x03
+    Soft,
+    /// Enables rapid power shutdown, 0x04
+    EnableRapidPowerShutDown,
+    /// Disables rapid power shutdown, 0x05
+    DisableRapidPowerShutDown,
+    /// Any other reset type
+    Other(u8),
+}
+
+impl From<u8> for ResetType {
+    fn from(b: u8) -> Self {
+        match b & !SUPPRESS_POSITIVE_RESPONSE {
+            0x01 => Self::Hard,
+            0x02 => Self::KeyOffOn,
+            0x03 => Self::Soft,
+            0x04 => Self::EnableRapidPowerShutDown,
+            0x05 => Self::DisableRapidPowerShutDown,
+            b => Self::Other(b),
+        }
+    }
+}
+
+impl From<ResetType> for u8 {
+    fn from(reset: ResetType) -> Self {
+        match reset {
+            ResetType::Hard => 0x01,
+            ResetType::KeyOffOn => 0x02,
+            ResetType::Soft => 0x03,
+            ResetType::EnableRapidPowerShutDown => 0x04,
+            ResetType::DisableRapidPowerShutDown => 0x05,
+            ResetType::Other(b) => b,
+        }
+    }
+}
+
+/// The routine control operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RoutineControl {
+    /// Starts a routine, 0x01
+    Start,
+    /// Stops a routine, 0x02
+    Stop,
+    /// Requests the results of a routine, 0x03
+    RequestResults,
+}
+
+impl TryFrom<u8> for RoutineControl {
+    type Error = Nrc;
+
+    fn try_from(b: u8) -> Result<Self, Nrc> {
+        match b & !SUPPRESS_POSITIVE_RESPONSE {
+            0x01 => Ok(Self::Start),
+            0x02 => Ok(Self::Stop),
+            0x03 => Ok(Self::RequestResults),
+            _ => Err(Nrc::SubFunctionNotSupported),
+        }
+    }
+}
+
+impl From<RoutineControl> for u8 {
+    fn from(ctrl: RoutineControl) -> Self {
+        match ctrl {
+            RoutineControl::Start => 0x01,
+            RoutineControl::Stop => 0x02,
+            RoutineControl::RequestResults => 0x03,
+        }
+    }
+}
+
+/// The ReadDTCInformation sub-functions.
+pub mod dtc_report {
+    /// reportNumberOfDTCByStatusMask
+    pub const NUMBER_OF_DTC_BY_STATUS_MASK: u8 = 0x01;
+    /// reportDTCByStatusMask
+    pub const DTC_BY_STATUS_MASK: u8 = 0x02;
+    /// reportSupportedDTC
+    pub const SUPPORTED_DTC: u8 = 0x0A;
+}
+
+// ===== DTCs =====
+
+bitflags::bitflags! {
+    /// The status bits of a diagnostic trouble code.
+    pub struct DtcStatus: u8 {
+        /// The most recent test failed
+        const TEST_FAILED = 0x01;
+        /// A test failed during the current operation cycle
+        const TEST_FAILED_THIS_OPERATION_CYCLE = 0x02;
+        /// The DTC is pending
+        const PENDING_DTC = 0x04;
+        /// The DTC is confirmed
+        const CONFIRMED_DTC = 0x08;
+        /// The test hasn't completed since the DTCs were last cleared
+        const TEST_NOT_COMPLETED_SINCE_LAST_CLEAR = 0x10;
+        /// A test failed since the DTCs were last cleared
+        const TEST_FAILED_SINCE_LAST_CLEAR = 0x20;
+        /// The test hasn't completed during the current operation cycle
+        const TEST_NOT_COMPLETED_THIS_OPERATION_CYCLE = 0x40;
+        /// The warning indicator is requested
+        const WARNING_INDICATOR_REQUESTED = 0x80;
+    }
+}
+
+/// A diagnostic trouble code and its status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Dtc {
+    /// The 24-bit trouble code
+    pub code: u32,
+    /// The status of the code
+    pub status: DtcStatus,
+}
+
+impl Dtc {
+    /// Creates a DTC from a code and the raw status byte.
+    pub fn new(code: u32, status: u8) -> Self {
+        Self {
+            code: code & 0x00FF_FFFF,
+            status: DtcStatus::from_bits_truncate(status),
+        }
+    }
+
+    /// Parses the DTC from the four bytes in a response record.
+    pub fn from_bytes(b: &[u8; 4]) -> Self {
+        Self::new(u32::from_be_bytes([0, b[0], b[1], b[2]]), b[3])
+    }
+
+    /// Gets the four bytes of the DTC for a response record.
+    pub fn to_bytes(&self) -> [u8; 4] {
+        let code = self.code.to_be_bytes();
+        [code[1], code[2], code[3], self.status.bits()]
+    }
+}
+
+impl fmt::Display for Dtc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:06X} ({:02X})", self.code, self.status.bits())
+    }
+}
+
+// ===== Error =====
+
+/// An error from a UDS request.
+#[derive(Debug)]
+pub enum Error {
+    /// An error in the ISO-TP transport
+    IsoTp(isotp::Error),
+    /// The server sent a negative response
+    Negative {
+        /// The service of the request
+        service: u8,
+        /// The negative response code
+        nrc: Nrc,
+    },
+    /// No response arrived within the P2 or P2* time
+    Timeout,
+    /// The response was malformed, or didn't match the request
+    InvalidResponse(Vec<u8>),
+    /// The security access level isn't an odd "request seed" level
+    InvalidSecurityLevel(u8),
+}
+
+impl Error {
+    /// Gets the negative response code, if the error is a negative
+    /// response.
+    pub fn nrc(&self) -> Option<Nrc> {
+        match *self {
+            Error::Negative { nrc, .. } => Some(nrc),
+            _ => None,
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::IsoTp(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+        match self {
+            IsoTp(err) => write!(f, "ISO-TP error: {}", err),
+            Negative { service, nrc } => {
+                write!(f, "negative response to service 0x{:02X}: {}", service, nrc)
+            }
+            Timeout => f.write_str("no response from the server"),
+            InvalidResponse(resp) => write!(f, "invalid response: {:02X?}", resp),
+            InvalidSecurityLevel(level) => {
+                write!(f, "invalid security access level: 0x{:02X}", level)
+            }
+        }
+    }
+}
+
+impl From<isotp::Error> for Error {
+    fn from(err: isotp::Error) -> Self {
+        Error::IsoTp(err)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nrc() {
+        for code in 0..=0xFF {
+            assert_eq!(u8::from(Nrc::from(code)), code);
+        }
+        assert_eq!(Nrc::from(0x78), Nrc::ResponsePending);
+        assert_eq!(Nrc::from(0xF0), Nrc::Other(0xF0));
+        assert_eq!(Nrc::from(0x33).to_string(), "security access denied");
+    }
+
+    #[test]
+    fn test_dtc() {
+        let dtc = Dtc::from_bytes(&[0x12, 0x34, 0x56, 0x09]);
+        assert_eq!(dtc.code, 0x123456);
+        assert_eq!(
+            dtc.status,
+            DtcStatus::TEST_FAILED | DtcStatus::CONFIRMED_DTC
+        );
+        assert_eq!(dtc.to_bytes(), [0x12, 0x34, 0x56, 0x09]);
+        assert_eq!(dtc.to_string(), "123456 (09)");
+    }
+}
@@ -0,0 +1,1203 @@
+// socketcan/src/uds/server.rs
+//
+// A UDS server for simulating ECUs.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! A UDS server, for simulating ECUs.
+//!
+//! The [`Server`] is a service dispatcher that's configured with the data
+//! identifiers, routines, security levels, and memory of the simulated
+//! ECU, along with the session and security rules for each of them. It
+//! keeps the diagnostic session and security state, and answers requests
+//! with the positive or negative responses a real ECU would give.
+//!
+//! ```no_run
+//! use socketcan::{
+//!     isotp::{IsoTpSocket, Options},
+//!     uds::{Access, Did, Nrc, Server, Session},
+//!     CanSocket, StandardId,
+//! };
+//!
+//! let mut server = Server::new()
+//!     .did(0xF190, Did::new(*b"WVWZZZ1JZXW000001"))
+//!     .did(
+//!         0x0101,
+//!         Did::new([0x00, 0x00]).writable(Access::session(Session::Extended).security(0x01)),
+//!     )
+//!     .routine(0xFF00, Access::session(Session::Programming), |_ctrl, _params| Ok(vec![0x00]))
+//!     .security_level(0x01, |seed| seed.iter().map(|b| b ^ 0x5A).collect())
+//!     .memory(0x0800_0000, 0x10000);
+//!
+//! let id = |id| StandardId::new(id).unwrap();
+//! let mut sock = IsoTpSocket::<CanSocket>::open("vcan0", Options::new(id(0x7E8), id(0x7E0))).unwrap();
+//! server.serve(&mut sock).unwrap();
+//! ```
+//!
+//! Requests can also be handled directly, without a transport, with
+//! [`Server::handle()`].
+
+use super::{
+    dtc_report, sid, Dtc, DtcStatus, Nrc, ResetType, RoutineControl, Session, SessionTimings,
+    SUPPRESS_POSITIVE_RESPONSE,
+};
+use crate::isotp::{self, IsoTpSocket, Link};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// The time without a request before a non-default session ends
+const DEFAULT_S3: Duration = Duration::from_secs(5);
+
+/// The number of bad keys before security access is refused
+const MAX_KEY_ATTEMPTS: u8 = 3;
+
+/// The default time that security access is refused after too many bad
+/// keys
+const DEFAULT_SECURITY_DELAY: Duration = Duration::from_secs(10);
+
+/// The default maximum length of a TransferData request
+const DEFAULT_MAX_BLOCK_LEN: u16 = 0x0FFF;
+
+/// A handler to read a data identifier
+type ReadFn = Box<dyn FnMut() -> Result<Vec<u8>, Nrc> + Send>;
+
+/// A handler to write a data identifier
+type WriteFn = Box<dyn FnMut(&[u8]) -> Result<(), Nrc> + Send>;
+
+/// A handler for a routine
+type RoutineFn = Box<dyn FnMut(RoutineControl, &[u8]) -> Result<Vec<u8>, Nrc> + Send>;
+
+/// A function to compute the key for a seed
+type KeyFn = Box<dyn Fn(&[u8]) -> Vec<u8> + Send>;
+
+// ===== Access =====
+
+/// The session and security level that a service, data identifier, or
+/// routine requires.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Access {
+    sessions: Vec<Session>,
+    security: Option<u8>,
+}
+
+impl Access {
+    /// Access in any session, without security.
+    pub fn any() -> Self {
+        Self::default()
+    }
+
+    /// Access only in the specified session.
+    pub fn session(session: Session) -> Self {
+        Self::any().or_session(session)
+    }
+
+    /// Also allows access in another session.
+    pub fn or_session(mut self, session: Session) -> Self {
+        self.sessions.push(session);
+        self
+    }
+
+    /// Requires the security level to be unlocked.
+    ///
+    /// This is the odd "request seed" sub-function of the level.
+    pub fn security(mut self, level: u8) -> Self {
+        self.security = Some(level);
+        self
+    }
+
+    /// Checks the access in the current state, returning the negative
+    /// response code for the session if it's not allowed there.
+    fn check(&self, state: &State, wrong_session: Nrc) -> Result<(), Nrc> {
+        if !self.sessions.is_empty() && !self.sessions.contains(&state.session) {
+            Err(wrong_session)
+        } else if self.security.is_some() && self.security != state.unlocked {
+            Err(Nrc::SecurityAccessDenied)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// ===== Did =====
+
+/// A data identifier on the server.
+///
+/// This either holds a value, which can optionally be written by the
+/// client, or calls handlers to read and write it.
+pub struct Did {
+    value: Vec<u8>,
+    read: Option<ReadFn>,
+    write: Option<WriteFn>,
+    read_access: Access,
+    write_access: Option<Access>,
+}
+
+impl Did {
+    /// Creates a read-only data identifier with a fixed value.
+    pub fn new(value: impl Into<Vec<u8>>) -> Self {
+        Self {
+            value: value.into(),
+            read: None,
+            write: None,
+            read_access: Access::any(),
+            write_access: None,
+        }
+    }
+
+    /// Creates a read-only data identifier that gets its value from a
+    /// handler.
+    pub fn from_fn<F>(f: F) -> Self
+    where
+        F: FnMut() -> Result<Vec<u8>, Nrc> + Send + 'static,
+    {
+        Self {
+            read: Some(Box::new(f)),
+            ..Self::new(Vec::new())
+        }
+    }
+
+    /// Sets the access required to read the identifier.
+    pub fn access(mut self, access: Access) -> Self {
+        self.read_access = access;
+        self
+    }
+
+    /// Allows the identifier to be written with the access.
+    ///
+    /// Without a write handler, a write replaces the value, and has to be
+    /// the same length as the current value.
+    pub fn writable(mut self, access: Access) -> Self {
+        self.write_access = Some(access);
+        self
+    }
+
+    /// Sets a handler for writes to the identifier.
+    ///
+    /// The identifier also has to be [`writable()`](Self::writable). The
+    /// stored value is updated if the handler succeeds.
+    pub fn on_write<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(&[u8]) -> Result<(), Nrc> + Send + 'static,
+    {
+        self.write = Some(Box::new(f));
+        self
+    }
+
+    /// Reads the value.
+    fn read(&mut self) -> Result<Vec<u8>, Nrc> {
+        match self.read.as_mut() {
+            Some(f) => f(),
+            None => Ok(self.value.clone()),
+        }
+    }
+
+    /// Writes the value.
+    fn write(&mut self, data: &[u8]) -> Result<(), Nrc> {
+        match self.write.as_mut() {
+            Some(f) => f(data)?,
+            None if data.len() != self.value.len() => {
+                return Err(Nrc::IncorrectMessageLengthOrInvalidFormat)
+            }
+            None => {}
+        }
+        self.value = data.to_vec();
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Did {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Did")
+            .field("value", &self.value)
+            .field("read_access", &self.read_access)
+            .field("write_access", &self.write_access)
+            .finish()
+    }
+}
+
+// ===== Server state =====
+
+// A routine, with the access it requires
+struct Routine {
+    access: Access,
+    handler: RoutineFn,
+}
+
+// A security level and the key function
+struct SecurityLevel {
+    key_fn: KeyFn,
+}
+
+// The simulated download memory
+#[derive(Debug)]
+struct Memory {
+    address: u32,
+    data: Vec<u8>,
+}
+
+// A download in progress
+#[derive(Debug, Clone, Copy)]
+struct Transfer {
+    // The offset of the next block in the memory
+    offset: usize,
+    // The offset of the end of the download
+    end: usize,
+    // The block sequence counter of the last block written, if any
+    last: Option<u8>,
+}
+
+// The diagnostic state of the server
+#[derive(Debug)]
+struct State {
+    session: Session,
+    unlocked: Option<u8>,
+    // The level and the seed sent to the client, awaiting the key
+    seed: Option<(u8, Vec<u8>)>,
+    failed_attempts: u8,
+    // The end of the delay after too many bad keys
+    locked_until: Option<Instant>,
+    transfer: Option<Transfer>,
+    last_request: Instant,
+    rng: u64,
+}
+
+impl State {
+    fn new() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Self {
+            session: Session::Default,
+            unlocked: None,
+            seed: None,
+            failed_attempts: 0,
+            locked_until: None,
+            transfer: None,
+            last_request: Instant::now(),
+            rng: nanos | 1,
+        }
+    }
+
+    /// Returns to the default session, locking security.
+    fn reset(&mut self) {
+        self.session = Session::Default;
+        self.unlocked = None;
+        self.seed = None;
+        self.transfer = None;
+    }
+
+    /// Gets a new, non-zero seed, from an xorshift generator.
+    fn seed(&mut self) -> Vec<u8> {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        let seed = (self.rng as u32).max(1);
+        seed.to_be_bytes().to_vec()
+    }
+}
+
+// ===== Server =====
+
+/// A UDS server, simulating an ECU.
+///
+/// The server is configured with builder-style methods, then answers
+/// requests with [`handle()`](Self::handle) or over an ISO-TP connection
+/// with [`serve()`](Self::serve).
+///
+/// It supports DiagnosticSessionControl, ECUReset,
+/// ClearDiagnosticInformation, ReadDTCInformation (by status mask and
+/// supported DTCs), ReadDataByIdentifier, SecurityAccess,
+/// WriteDataByIdentifier, RoutineControl, RequestDownload, TransferData,
+/// RequestTransferExit, and TesterPresent. Other services get a "service
+/// not supported" negative response.
+pub struct Server {
+    sessions: Vec<Session>,
+    timings: SessionTimings,
+    s3: Duration,
+    security_delay: Duration,
+    services: BTreeMap<u8, Access>,
+    dids: BTreeMap<u16, Did>,
+    routines: BTreeMap<u16, Routine>,
+    security: BTreeMap<u8, SecurityLevel>,
+    memory: Option<Memory>,
+    max_block_len: u16,
+    dtcs: Vec<Dtc>,
+    state: State,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Server {
+    /// Creates a server with no data identifiers, routines, security
+    /// levels, or memory.
+    ///
+    /// It supports the default, programming, and extended sessions.
+    pub fn new() -> Self {
+        Self {
+            sessions: vec![Session::Default, Session::Programming, Session::Extended],
+            timings: SessionTimings {
+                p2: Duration::from_millis(50),
+                p2_star: Duration::from_millis(5000),
+            },
+            s3: DEFAULT_S3,
+            security_delay: DEFAULT_SECURITY_DELAY,
+            services: BTreeMap::new(),
+            dids: BTreeMap::new(),
+            routines: BTreeMap::new(),
+            security: BTreeMap::new(),
+            memory: None,
+            max_block_len: DEFAULT_MAX_BLOCK_LEN,
+            dtcs: Vec::new(),
+            state: State::new(),
+        }
+    }
+
+    /// Sets the diagnostic sessions that the server supports.
+    pub fn sessions(mut self, sessions: &[Session]) -> Self {
+        self.sessions = sessions.to_vec();
+        self
+    }
+
+    /// Sets the response timings reported for each session.
+    pub fn timings(mut self, timings: SessionTimings) -> Self {
+        self.timings = timings;
+        self
+    }
+
+    /// Sets the S3 time, after which a non-default session ends if no
+    /// requests arrive.
+    pub fn s3(mut self, s3: Duration) -> Self {
+        self.s3 = s3;
+        self
+    }
+
+    /// Sets the delay after too many bad security keys, during which seed
+    /// requests are refused with "required time delay not expired".
+    ///
+    /// When it expires, the count of bad keys starts over.
+    pub fn security_delay(mut self, delay: Duration) -> Self {
+        self.security_delay = delay;
+        self
+    }
+
+    /// Sets the access required for a whole service.
+    ///
+    /// Requests for the service outside of the sessions get a "service not
+    /// supported in active session" response, and without the security
+    /// level, a "security access denied" response.
+    pub fn service_access(mut self, service: u8, access: Access) -> Self {
+        self.services.insert(service, access);
+        self
+    }
+
+    /// Adds a data identifier.
+    pub fn did(mut self, did: u16, value: Did) -> Self {
+        self.dids.insert(did, value);
+        self
+    }
+
+    /// Adds a routine, with the access required to control it.
+    ///
+    /// The handler gets the control type and the option record, and returns
+    /// the status record for the response.
+    pub fn routine<F>(mut self, id: u16, access: Access, f: F) -> Self
+    where
+        F: FnMut(RoutineControl, &[u8]) -> Result<Vec<u8>, Nrc> + Send + 'static,
+    {
+        let handler = Box::new(f);
+        self.routines.insert(id, Routine { access, handler });
+        self
+    }
+
+    /// Adds a security level, with the function that computes the expected
+    /// key from a seed.
+    ///
+    /// The level is the odd "request seed" sub-function.
+    pub fn security_level<F>(mut self, level: u8, key_fn: F) -> Self
+    where
+        F: Fn(&[u8]) -> Vec<u8> + Send + 'static,
+    {
+        let key_fn = Box::new(key_fn);
+        self.security.insert(level, SecurityLevel { key_fn });
+        self
+    }
+
+    /// Adds a block of memory that the client can download to.
+    ///
+    /// The memory starts out erased, as all 0xFF bytes.
+    pub fn memory(mut self, address: u32, size: usize) -> Self {
+        self.memory = Some(Memory {
+            address,
+            data: vec![0xFF; size],
+        });
+        self
+    }
+
+    /// Sets the maximum length of a TransferData request, including the
+    /// service ID and block sequence counter.
+    pub fn max_block_len(mut self, len: u16) -> Self {
+        self.max_block_len = len.max(3);
+        self
+    }
+
+    /// Sets the stored DTCs.
+    pub fn dtcs(mut self, dtcs: Vec<Dtc>) -> Self {
+        self.dtcs = dtcs;
+        self
+    }
+
+    /// Gets the active diagnostic session.
+    pub fn session(&self) -> Session {
+        self.state.session
+    }
+
+    /// Gets the unlocked security level, if any.
+    pub fn unlocked_level(&self) -> Option<u8> {
+        self.state.unlocked
+    }
+
+    /// Gets the stored value of a data identifier.
+    pub fn did_value(&self, did: u16) -> Option<&[u8]> {
+        self.dids.get(&did).map(|did| &did.value[..])
+    }
+
+    /// Gets the contents of the download memory.
+    pub fn memory_contents(&self) -> Option<&[u8]> {
+        self.memory.as_ref().map(|mem| &mem.data[..])
+    }
+
+    /// Gets the stored DTCs.
+    pub fn stored_dtcs(&self) -> &[Dtc] {
+        &self.dtcs
+    }
+
+    /// Handles a request, returning the response.
+    ///
+    /// Returns `None` if the request suppressed the positive response.
+    pub fn handle(&mut self, req: &[u8]) -> Option<Vec<u8>> {
+        let service = *req.first()?;
+
+        if self.state.session != Session::Default && self.state.last_request.elapsed() > self.s3 {
+            self.state.reset();
+        }
+        self.state.last_request = Instant::now();
+
+        match self.dispatch(req) {
+            Ok(_) if Self::is_suppressed(req) => None,
+            Ok(mut resp) => {
+                resp.insert(0, service.wrapping_add(sid::POSITIVE_RESPONSE));
+                Some(resp)
+            }
+            Err(nrc) => Some(vec![sid::NEGATIVE_RESPONSE, service, nrc.into()]),
+        }
+    }
+
+    /// Determines if the request has the suppress positive response bit
+    /// set in a sub-function that allows it.
+    fn is_suppressed(req: &[u8]) -> bool {
+        use sid::*;
+        matches!(
+            req[0],
+            DIAGNOSTIC_SESSION_CONTROL
+                | ECU_RESET
+                | SECURITY_ACCESS
+                | ROUTINE_CONTROL
+                | TESTER_PRESENT
+        ) && req
+            .get(1)
+            .map_or(false, |b| b & SUPPRESS_POSITIVE_RESPONSE != 0)
+    }
+
+    /// Dispatches a request to the service, returning the positive
+    /// response after the service ID.
+    fn dispatch(&mut self, req: &[u8]) -> Result<Vec<u8>, Nrc> {
+        use sid::*;
+
+        let service = req[0];
+        if let Some(access) = self.services.get(&service) {
+            access.check(&self.state, Nrc::ServiceNotSupportedInActiveSession)?;
+        }
+
+        let params = &req[1..];
+        match service {
+            DIAGNOSTIC_SESSION_CONTROL => self.session_control(params),
+            ECU_RESET => self.ecu_reset(params),
+            CLEAR_DIAGNOSTIC_INFORMATION => self.clear_dtcs(params),
+            READ_DTC_INFORMATION => self.read_dtcs(params),
+            READ_DATA_BY_IDENTIFIER => self.read_dids(params),
+            SECURITY_ACCESS => self.security_access(params),
+            WRITE_DATA_BY_IDENTIFIER => self.write_did(params),
+            ROUTINE_CONTROL => self.routine_control(params),
+            REQUEST_DOWNLOAD => self.request_download(params),
+            TRANSFER_DATA => self.transfer_data(params),
+            REQUEST_TRANSFER_EXIT => self.transfer_exit(params),
+            TESTER_PRESENT => match params {
+                [sub] if sub & !SUPPRESS_POSITIVE_RESPONSE == 0 => Ok(vec![0x00]),
+                [_] => Err(Nrc::SubFunctionNotSupported),
+                _ => Err(Nrc::IncorrectMessageLengthOrInvalidFormat),
+            },
+            _ => Err(Nrc::ServiceNotSupported),
+        }
+    }
+
+    fn session_control(&mut self, params: &[u8]) -> Result<Vec<u8>, Nrc> {
+        let sub = match params {
+            [sub] => sub & !SUPPRESS_POSITIVE_RESPONSE,
+            _ => return Err(Nrc::IncorrectMessageLengthOrInvalidFormat),
+        };
+        let session = Session::from(sub);
+        if !self.sessions.contains(&session) {
+            return Err(Nrc::SubFunctionNotSupported);
+        }
+
+        // Any session change locks security again
+        self.state.reset();
+        self.state.session = session;
+
+        let mut resp = vec![sub];
+        resp.extend_from_slice(&self.timings.to_bytes());
+        Ok(resp)
+    }
+
+    fn ecu_reset(&mut self, params: &[u8]) -> Result<Vec<u8>, Nrc> {
+        let sub = match params {
+            [sub] => sub & !SUPPRESS_POSITIVE_RESPONSE,
+            _ => return Err(Nrc::IncorrectMessageLengthOrInvalidFormat),
+        };
+        match ResetType::from(sub) {
+            ResetType::Hard | ResetType::KeyOffOn | ResetType::Soft => {
+                self.state.reset();
+                Ok(vec![sub])
+            }
+            _ => Err(Nrc::SubFunctionNotSupported),
+        }
+    }
+
+    fn clear_dtcs(&mut self, params: &[u8]) -> Result<Vec<u8>, Nrc> {
+        let group = match *params {
+            [a, b, c] => u32::from_be_bytes([0, a, b, c]),
+            _ => return Err(Nrc::IncorrectMessageLengthOrInvalidFormat),
+        };
+        if group == 0xFF_FFFF {
+            self.dtcs.clear();
+        } else if self.dtcs.iter().any(|dtc| dtc.code == group) {
+            self.dtcs.retain(|dtc| dtc.code != group);
+        } else {
+            return Err(Nrc::RequestOutOfRange);
+        }
+        Ok(vec![])
+    }
+
+    fn read_dtcs(&mut self, params: &[u8]) -> Result<Vec<u8>, Nrc> {
+        let avail = 0xFF;
+        let (report, mask) = match *params {
+            [report, mask] => (report, DtcStatus::from_bits_truncate(mask)),
+            [report] => (report, DtcStatus::all()),
+            _ => return Err(Nrc::IncorrectMessageLengthOrInvalidFormat),
+        };
+        let matching = self.dtcs.iter().filter(|dtc| dtc.status.intersects(mask));
+
+        match (report, params.len()) {
+            (dtc_report::NUMBER_OF_DTC_BY_STATUS_MASK, 2) => {
+                // Format 0x01 is ISO 14229-1 DTCs
+                let [hi, lo] = (matching.count() as u16).to_be_bytes();
+                Ok(vec![report, avail, 0x01, hi, lo])
+            }
+            (dtc_report::DTC_BY_STATUS_MASK, 2) => {
+                let mut resp = vec![report, avail];
+                matching.for_each(|dtc| resp.extend_from_slice(&dtc.to_bytes()));
+                Ok(resp)
+            }
+            (dtc_report::SUPPORTED_DTC, 1) => {
+                let mut resp = vec![report, avail];
+                self.dtcs
+                    .iter()
+                    .for_each(|dtc| resp.extend_from_slice(&dtc.to_bytes()));
+                Ok(resp)
+            }
+            (dtc_report::NUMBER_OF_DTC_BY_STATUS_MASK, _)
+            | (dtc_report::DTC_BY_STATUS_MASK, _)
+            | (dtc_report::SUPPORTED_DTC, _) => Err(Nrc::IncorrectMessageLengthOrInvalidFormat),
+            _ => Err(Nrc::SubFunctionNotSupported),
+        }
+    }
+
+    fn read_dids(&mut self, params: &[u8]) -> Result<Vec<u8>, Nrc> {
+        if params.is_empty() || params.len() % 2 != 0 {
+            return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+        }
+
+        let mut resp = Vec::new();
+        for id in params.chunks(2) {
+            let did = self
+                .dids
+                .get_mut(&u16::from_be_bytes([id[0], id[1]]))
+                .ok_or(Nrc::RequestOutOfRange)?;
+            did.read_access.check(&self.state, Nrc::RequestOutOfRange)?;
+            resp.extend_from_slice(id);
+            resp.extend_from_slice(&did.read()?);
+        }
+        Ok(resp)
+    }
+
+    fn write_did(&mut self, params: &[u8]) -> Result<Vec<u8>, Nrc> {
+        if params.len() < 3 {
+            return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+        }
+        let did = self
+            .dids
+            .get_mut(&u16::from_be_bytes([params[0], params[1]]))
+            .ok_or(Nrc::RequestOutOfRange)?;
+        did.write_access
+            .as_ref()
+            .ok_or(Nrc::RequestOutOfRange)?
+            .check(&self.state, Nrc::RequestOutOfRange)?;
+        did.write(&params[2..])?;
+        Ok(params[..2].to_vec())
+    }
+
+    fn security_access(&mut self, params: &[u8]) -> Result<Vec<u8>, Nrc> {
+        let sub = match params.first() {
+            Some(sub) => sub & !SUPPRESS_POSITIVE_RESPONSE,
+            None => return Err(Nrc::IncorrectMessageLengthOrInvalidFormat),
+        };
+
+        // Request seed
+        if sub % 2 == 1 {
+            if params.len() != 1 {
+                return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+            }
+            if !self.security.contains_key(&sub) {
+                return Err(Nrc::SubFunctionNotSupported);
+            }
+            if let Some(until) = self.state.locked_until {
+                if Instant::now() < until {
+                    return Err(Nrc::RequiredTimeDelayNotExpired);
+                }
+                self.state.locked_until = None;
+                self.state.failed_attempts = 0;
+            }
+            let mut resp = vec![sub];
+            if self.state.unlocked == Some(sub) {
+                resp.extend_from_slice(&[0; 4]);
+            } else {
+                let seed = self.state.seed();
+                resp.extend_from_slice(&seed);
+                self.state.seed = Some((sub, seed));
+            }
+            return Ok(resp);
+        }
+
+        // Send key
+        let level = sub.wrapping_sub(1);
+        let level_info = self
+            .security
+            .get(&level)
+            .ok_or(Nrc::SubFunctionNotSupported)?;
+        let seed = match self.state.seed.take() {
+            Some((seed_level, seed)) if seed_level == level => seed,
+            _ => return Err(Nrc::RequestSequenceError),
+        };
+
+        if (level_info.key_fn)(&seed) == params[1..] {
+            self.state.unlocked = Some(level);
+            self.state.failed_attempts = 0;
+            Ok(vec![sub])
+        } else {
+            self.state.failed_attempts += 1;
+            if self.state.failed_attempts >= MAX_KEY_ATTEMPTS {
+                self.state.locked_until = Some(Instant::now() + self.security_delay);
+                Err(Nrc::ExceededNumberOfAttempts)
+            } else {
+                Err(Nrc::InvalidKey)
+            }
+        }
+    }
+
+    fn routine_control(&mut self, params: &[u8]) -> Result<Vec<u8>, Nrc> {
+        if params.len() < 3 {
+            return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+        }
+        let ctrl = RoutineControl::try_from(params[0])?;
+        let routine = self
+            .routines
+            .get_mut(&u16::from_be_bytes([params[1], params[2]]))
+            .ok_or(Nrc::RequestOutOfRange)?;
+        routine.access.check(&self.state, Nrc::RequestOutOfRange)?;
+
+        let mut resp = vec![
+            params[0] & !SUPPRESS_POSITIVE_RESPONSE,
+            params[1],
+            params[2],
+        ];
+        resp.extend((routine.handler)(ctrl, &params[3..])?);
+        Ok(resp)
+    }
+
+    fn request_download(&mut self, params: &[u8]) -> Result<Vec<u8>, Nrc> {
+        if self.state.transfer.is_some() {
+            return Err(Nrc::ConditionsNotCorrect);
+        }
+        let (alfid, rest) = match params {
+            [_format, alfid, rest @ ..] => (*alfid, rest),
+            _ => return Err(Nrc::IncorrectMessageLengthOrInvalidFormat),
+        };
+        let (size_len, addr_len) = ((alfid >> 4) as usize, (alfid & 0x0F) as usize);
+        if !(1..=4).contains(&size_len) || !(1..=4).contains(&addr_len) {
+            return Err(Nrc::RequestOutOfRange);
+        }
+        if rest.len() != addr_len + size_len {
+            return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+        }
+
+        let be = |b: &[u8]| b.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize);
+        let address = be(&rest[..addr_len]);
+        let size = be(&rest[addr_len..]);
+
+        let mem = self.memory.as_ref().ok_or(Nrc::RequestOutOfRange)?;
+        let offset = address
+            .checked_sub(mem.address as usize)
+            .ok_or(Nrc::RequestOutOfRange)?;
+        if size == 0 || offset + size > mem.data.len() {
+            return Err(Nrc::RequestOutOfRange);
+        }
+
+        self.state.transfer = Some(Transfer {
+            offset,
+            end: offset + size,
+            last: None,
+        });
+        let [hi, lo] = self.max_block_len.to_be_bytes();
+        Ok(vec![0x20, hi, lo])
+    }
+
+    fn transfer_data(&mut self, params: &[u8]) -> Result<Vec<u8>, Nrc> {
+        let (counter, data) = match params {
+            [counter, data @ ..] => (*counter, data),
+            _ => return Err(Nrc::IncorrectMessageLengthOrInvalidFormat),
+        };
+        let xfer = self
+            .state
+            .transfer
+            .as_mut()
+            .ok_or(Nrc::RequestSequenceError)?;
+
+        // A repeat of the last block is acknowledged, but not written again
+        if xfer.last == Some(counter) {
+            return Ok(vec![counter]);
+        }
+        // The counter starts at 1, and wraps around to 0
+        if counter != xfer.last.map_or(1, |last| last.wrapping_add(1)) {
+            return Err(Nrc::WrongBlockSequenceCounter);
+        }
+        if data.len() + 2 > self.max_block_len as usize {
+            return Err(Nrc::IncorrectMessageLengthOrInvalidFormat);
+        }
+        if xfer.offset + data.len() > xfer.end {
+            return Err(Nrc::TransferDataSuspended);
+        }
+
+        let mem = self.memory.as_mut().ok_or(Nrc::ConditionsNotCorrect)?;
+        mem.data[xfer.offset..xfer.offset + data.len()].copy_from_slice(data);
+        xfer.offset += data.len();
+        xfer.last = Some(counter);
+        Ok(vec![counter])
+    }
+
+    fn transfer_exit(&mut self, _params: &[u8]) -> Result<Vec<u8>, Nrc> {
+        match self.state.transfer {
+            Some(xfer) if xfer.offset == xfer.end => {
+                self.state.transfer = None;
+                Ok(vec![])
+            }
+            _ => Err(Nrc::RequestSequenceError),
+        }
+    }
+
+    // ----- Transport -----
+
+    /// Waits for a request on the ISO-TP connection and sends the response.
+    ///
+    /// Returns `false` if no request arrived within the timeout. Errors
+    /// receiving a request, like timeouts or sequence errors in the
+    /// transport, are logged and dropped, as a real ECU would; only I/O
+    /// errors are returned.
+    pub fn serve_one<L: Link>(
+        &mut self,
+        sock: &mut IsoTpSocket<L>,
+        timeout: Option<Duration>,
+    ) -> Result<bool, isotp::Error> {
+        let req = match timeout {
+            Some(timeout) => sock.recv_timeout(timeout),
+            None => sock.recv().map(Some),
+        };
+        let req = match req {
+            Ok(Some(req)) => req,
+            Ok(None) => return Ok(false),
+            Err(isotp::Error::Io(err)) => return Err(isotp::Error::Io(err)),
+            Err(err) => {
+                log::debug!("Dropped UDS request: {}", err);
+                return Ok(true);
+            }
+        };
+
+        if let Some(resp) = self.handle(&req) {
+            match sock.send(&resp) {
+                Err(isotp::Error::Io(err)) => return Err(isotp::Error::Io(err)),
+                Err(err) => log::debug!("Failed to send UDS response: {}", err),
+                Ok(()) => {}
+            }
+        }
+        Ok(true)
+    }
+
+    /// Serves requests on the ISO-TP connection until an I/O error occurs.
+    pub fn serve<L: Link>(&mut self, sock: &mut IsoTpSocket<L>) -> Result<(), isotp::Error> {
+        loop {
+            self.serve_one(sock, None)?;
+        }
+    }
+}
+
+impl fmt::Debug for Server {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Server")
+            .field("sessions", &self.sessions)
+            .field("timings", &self.timings)
+            .field("s3", &self.s3)
+            .field("security_delay", &self.security_delay)
+            .field("services", &self.services)
+            .field("dids", &self.dids)
+            .field("routines", &self.routines.keys())
+            .field("security", &self.security.keys())
+            .field("memory", &self.memory)
+            .field("dtcs", &self.dtcs)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        isotp::{Options, VirtualBus},
+        uds::{Client, Error},
+        StandardId,
+    };
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+    };
+
+    fn xor_key(seed: &[u8]) -> Vec<u8> {
+        seed.iter().map(|b| b ^ 0x5A).collect()
+    }
+
+    fn server() -> Server {
+        Server::new()
+            .did(0xF190, Did::new(*b"WVWZZZ1JZXW000001"))
+            .did(0xF18C, Did::from_fn(|| Ok(b"SN0042".to_vec())))
+            .did(
+                0x0101,
+                Did::new([0x00, 0x00]).writable(Access::session(Session::Extended).security(0x01)),
+            )
+            .routine(
+                0xFF00,
+                Access::session(Session::Programming),
+                |ctrl, params| match ctrl {
+                    RoutineControl::Start => Ok(params.to_vec()),
+                    _ => Err(Nrc::RequestSequenceError),
+                },
+            )
+            .security_level(0x01, xor_key)
+            .memory(0x1000, 256)
+            .max_block_len(66)
+            .dtcs(vec![Dtc::new(0x123456, 0x09), Dtc::new(0xC00100, 0x40)])
+    }
+
+    fn nrc(resp: Option<Vec<u8>>) -> Nrc {
+        match resp.as_deref() {
+            Some([0x7F, _, code]) => Nrc::from(*code),
+            resp => panic!("not a negative response: {:02X?}", resp),
+        }
+    }
+
+    #[test]
+    fn test_sessions() {
+        let mut server = server().s3(Duration::from_millis(20));
+        assert_eq!(
+            server.handle(&[0x10, 0x03]).unwrap(),
+            &[0x50, 0x03, 0x00, 0x32, 0x01, 0xF4]
+        );
+        assert_eq!(server.session(), Session::Extended);
+        assert_eq!(
+            nrc(server.handle(&[0x10, 0x04])),
+            Nrc::SubFunctionNotSupported
+        );
+        assert_eq!(
+            nrc(server.handle(&[0x10])),
+            Nrc::IncorrectMessageLengthOrInvalidFormat
+        );
+
+        // Suppressed positive responses
+        assert!(server.handle(&[0x3E, 0x80]).is_none());
+        assert_eq!(server.handle(&[0x3E, 0x00]).unwrap(), &[0x7E, 0x00]);
+
+        // The session times out
+        thread::sleep(Duration::from_millis(30));
+        server.handle(&[0x3E, 0x80]);
+        assert_eq!(server.session(), Session::Default);
+
+        server.handle(&[0x10, 0x02]);
+        assert_eq!(server.handle(&[0x11, 0x01]).unwrap(), &[0x51, 0x01]);
+        assert_eq!(server.session(), Session::Default);
+
+        assert_eq!(nrc(server.handle(&[0x85, 0x01])), Nrc::ServiceNotSupported);
+    }
+
+    #[test]
+    fn test_service_access() {
+        let mut server = server().service_access(
+            sid::ROUTINE_CONTROL,
+            Access::session(Session::Programming).or_session(Session::Extended),
+        );
+        assert_eq!(
+            nrc(server.handle(&[0x31, 0x01, 0xFF, 0x00])),
+            Nrc::ServiceNotSupportedInActiveSession
+        );
+
+        // The routine itself is limited to the programming session
+        server.handle(&[0x10, 0x03]);
+        assert_eq!(
+            nrc(server.handle(&[0x31, 0x01, 0xFF, 0x00])),
+            Nrc::RequestOutOfRange
+        );
+        server.handle(&[0x10, 0x02]);
+        assert_eq!(
+            server.handle(&[0x31, 0x01, 0xFF, 0x00, 0xAA]).unwrap(),
+            &[0x71, 0x01, 0xFF, 0x00, 0xAA]
+        );
+        assert_eq!(
+            nrc(server.handle(&[0x31, 0x02, 0xFF, 0x00])),
+            Nrc::RequestSequenceError
+        );
+    }
+
+    #[test]
+    fn test_dids() {
+        let mut server = server();
+        let resp = server.handle(&[0x22, 0xF1, 0x90, 0xF1, 0x8C]).unwrap();
+        assert_eq!(&resp[..3], &[0x62, 0xF1, 0x90]);
+        assert_eq!(&resp[3..20], b"WVWZZZ1JZXW000001");
+        assert_eq!(&resp[20..], b"\xF1\x8CSN0042");
+
+        assert_eq!(
+            nrc(server.handle(&[0x22, 0x12, 0x34])),
+            Nrc::RequestOutOfRange
+        );
+        assert_eq!(
+            nrc(server.handle(&[0x2E, 0xF1, 0x90, 0x00])),
+            Nrc::RequestOutOfRange
+        );
+
+        // The writable DID needs the extended session and security
+        assert_eq!(
+            nrc(server.handle(&[0x2E, 0x01, 0x01, 0x12, 0x34])),
+            Nrc::RequestOutOfRange
+        );
+        server.handle(&[0x10, 0x03]);
+        assert_eq!(
+            nrc(server.handle(&[0x2E, 0x01, 0x01, 0x12, 0x34])),
+            Nrc::SecurityAccessDenied
+        );
+    }
+
+    #[test]
+    fn test_security() {
+        let mut server = server().security_delay(Duration::from_millis(20));
+        assert_eq!(
+            nrc(server.handle(&[0x27, 0x02, 0, 0])),
+            Nrc::RequestSequenceError
+        );
+
+        let resp = server.handle(&[0x27, 0x01]).unwrap();
+        assert_eq!(&resp[..2], &[0x67, 0x01]);
+        let mut req = vec![0x27, 0x02];
+        req.extend(xor_key(&resp[2..]));
+        assert_eq!(server.handle(&req).unwrap(), &[0x67, 0x02]);
+        assert_eq!(server.unlocked_level(), Some(0x01));
+
+        // Already unlocked
+        assert_eq!(
+            server.handle(&[0x27, 0x01]).unwrap(),
+            &[0x67, 0x01, 0, 0, 0, 0]
+        );
+
+        // Locked out after too many bad keys
+        server.handle(&[0x10, 0x01]);
+        for code in [
+            Nrc::InvalidKey,
+            Nrc::InvalidKey,
+            Nrc::ExceededNumberOfAttempts,
+        ] {
+            server.handle(&[0x27, 0x01]).unwrap();
+            assert_eq!(nrc(server.handle(&[0x27, 0x02, 0, 0, 0, 0])), code);
+        }
+        assert_eq!(
+            nrc(server.handle(&[0x27, 0x01])),
+            Nrc::RequiredTimeDelayNotExpired
+        );
+
+        // Neither a session change nor a reset clears the lockout
+        server.handle(&[0x11, 0x01]);
+        assert_eq!(
+            nrc(server.handle(&[0x27, 0x01])),
+            Nrc::RequiredTimeDelayNotExpired
+        );
+
+        // But the delay timer does
+        thread::sleep(Duration::from_millis(30));
+        let resp = server.handle(&[0x27, 0x01]).unwrap();
+        let mut req = vec![0x27, 0x02];
+        req.extend(xor_key(&resp[2..]));
+        assert_eq!(server.handle(&req).unwrap(), &[0x67, 0x02]);
+    }
+
+    #[test]
+    fn test_dtcs() {
+        let mut server = server();
+        assert_eq!(
+            server.handle(&[0x19, 0x01, 0x08]).unwrap(),
+            &[0x59, 0x01, 0xFF, 0x01, 0x00, 0x01]
+        );
+        assert_eq!(
+            server.handle(&[0x19, 0x0A]).unwrap(),
+            &[0x59, 0x0A, 0xFF, 0x12, 0x34, 0x56, 0x09, 0xC0, 0x01, 0x00, 0x40]
+        );
+        assert_eq!(
+            nrc(server.handle(&[0x19, 0x42, 0x08])),
+            Nrc::SubFunctionNotSupported
+        );
+
+        assert_eq!(server.handle(&[0x14, 0x12, 0x34, 0x56]).unwrap(), &[0x54]);
+        assert_eq!(server.stored_dtcs(), &[Dtc::new(0xC00100, 0x40)]);
+        server.handle(&[0x14, 0xFF, 0xFF, 0xFF]);
+        assert!(server.stored_dtcs().is_empty());
+    }
+
+    #[test]
+    fn test_download() {
+        let mut server = server();
+        assert_eq!(
+            nrc(server.handle(&[0x34, 0x00, 0x44, 0, 0, 0x10, 0xF0, 0, 0, 0, 0x20])),
+            Nrc::RequestOutOfRange
+        );
+        assert_eq!(
+            server
+                .handle(&[0x34, 0x00, 0x22, 0x10, 0x10, 0x00, 0x04])
+                .unwrap(),
+            &[0x74, 0x20, 0x00, 0x42]
+        );
+        assert_eq!(
+            nrc(server.handle(&[0x36, 0x02, 1, 2])),
+            Nrc::WrongBlockSequenceCounter
+        );
+        // Nothing has been written yet, so block 0 isn't a repeat
+        assert_eq!(
+            nrc(server.handle(&[0x36, 0x00, 1, 2])),
+            Nrc::WrongBlockSequenceCounter
+        );
+        assert_eq!(nrc(server.handle(&[0x37])), Nrc::RequestSequenceError);
+        assert_eq!(server.handle(&[0x36, 0x01, 1, 2]).unwrap(), &[0x76, 0x01]);
+        // A repeated block isn't written again
+        assert_eq!(server.handle(&[0x36, 0x01, 1, 2]).unwrap(), &[0x76, 0x01]);
+        assert_eq!(
+            nrc(server.handle(&[0x36, 0x02, 3, 4, 5])),
+            Nrc::TransferDataSuspended
+        );
+        assert_eq!(server.handle(&[0x36, 0x02, 3, 4]).unwrap(), &[0x76, 0x02]);
+        assert_eq!(server.handle(&[0x37]).unwrap(), &[0x77]);
+
+        let mem = server.memory_contents().unwrap();
+        assert_eq!(&mem[0x10..0x14], &[1, 2, 3, 4]);
+        assert_eq!(mem[0x14], 0xFF);
+    }
+
+    #[test]
+    fn test_client_server() {
+        let bus = VirtualBus::new();
+        let id = |id| StandardId::new(id).unwrap();
+
+        let mut sock = IsoTpSocket::new(bus.connect(), Options::new(id(0x7E8), id(0x7E0)));
+        let running = Arc::new(AtomicBool::new(true));
+        let ecu = {
+            let running = running.clone();
+            thread::spawn(move || {
+                let mut server = server();
+                while running.load(Ordering::Relaxed) {
+                    server
+                        .serve_one(&mut sock, Some(Duration::from_millis(10)))
+                        .unwrap();
+                }
+                server
+            })
+        };
+
+        let sock = IsoTpSocket::new(bus.connect(), Options::new(id(0x7E0), id(0x7E8)));
+        let mut client = Client::new(sock);
+
+        assert_eq!(
+            client.read_data_by_identifier(0xF190).unwrap(),
+            b"WVWZZZ1JZXW000001"
+        );
+        client
+            .diagnostic_session_control(Session::Extended)
+            .unwrap();
+        assert!(matches!(
+            client.write_data_by_identifier(0x0101, &[0x12, 0x34]),
+            Err(Error::Negative {
+                nrc: Nrc::SecurityAccessDenied,
+                ..
+            })
+        ));
+        client
+            .security_access(0x01, |_, seed| xor_key(seed))
+            .unwrap();
+        client
+            .write_data_by_identifier(0x0101, &[0x12, 0x34])
+            .unwrap();
+
+        client
+            .diagnostic_session_control(Session::Programming)
+            .unwrap();
+        let image: Vec<u8> = (0..200).map(|i| i as u8).collect();
+        client.download(0x1000, &image).unwrap();
+        assert_eq!(
+            client.read_dtcs(DtcStatus::CONFIRMED_DTC).unwrap(),
+            &[Dtc::new(0x123456, 0x09)]
+        );
+
+        running.store(false, Ordering::Relaxed);
+        let server = ecu.join().unwrap();
+        assert_eq!(server.did_value(0x0101).unwrap(), &[0x12, 0x34]);
+        assert_eq!(&server.memory_contents().unwrap()[..200], &image[..]);
+    }
+}
@@ -0,0 +1,433 @@
+// socketcan/src/xcp/daq.rs
+//
+// XCP data acquisition (DAQ) lists and packets.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! XCP data acquisition (DAQ) lists and packets.
+//!
+//! A [`DaqList`] is a set of memory locations that the slave samples on an
+//! event channel, such as a 10 ms task. The slave sends the samples in
+//! DAQ packets, each of which holds one Object Descriptor Table (ODT) of
+//! the list. The master splits the entries of a list into as many ODTs as
+//! are needed to fit them into the packets, and decodes the packets back
+//! into named values in a [`DaqPacket`].
+
+use super::get_uint;
+use crate::ByteOrder;
+use std::time::Duration;
+
+/// The DAQ list mode bit for timestamps in the packets
+pub(crate) const MODE_TIMESTAMP: u8 = 0x10;
+
+// ===== OdtEntry =====
+
+/// A memory location that is sampled in a DAQ list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OdtEntry {
+    /// The name of the measurement
+    pub name: String,
+    /// The address in the slave's memory
+    pub address: u32,
+    /// The address extension
+    pub extension: u8,
+    /// The size of the measurement, in bytes
+    pub size: u8,
+}
+
+// ===== DaqList =====
+
+/// A DAQ list to configure in the slave.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DaqList {
+    /// The event channel that triggers the sampling
+    pub event_channel: u16,
+    /// The number of events between samples
+    pub prescaler: u8,
+    /// The priority of the list
+    pub priority: u8,
+    /// Whether the slave adds a timestamp to the packets
+    pub timestamp: bool,
+    /// The measurements in the list
+    pub entries: Vec<OdtEntry>,
+}
+
+impl DaqList {
+    /// Creates an empty list, sampled on every event of the channel.
+    pub fn new(event_channel: u16) -> Self {
+        Self {
+            event_channel,
+            prescaler: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the number of events between samples.
+    pub fn prescaler(mut self, prescaler: u8) -> Self {
+        self.prescaler = prescaler.max(1);
+        self
+    }
+
+    /// Sets the priority of the list.
+    pub fn priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Sets whether the slave adds a timestamp to the packets.
+    pub fn timestamp(mut self, timestamp: bool) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// Adds a measurement of `size` bytes at the address.
+    pub fn measure(mut self, name: &str, address: u32, extension: u8, size: u8) -> Self {
+        self.entries.push(OdtEntry {
+            name: name.into(),
+            address,
+            extension,
+            size,
+        });
+        self
+    }
+
+    /// Splits the entries into ODTs that fit in packets of `max_dto`
+    /// bytes.
+    ///
+    /// Each packet starts with the identification field, of `id_size`
+    /// bytes, and the first packet of the list also has the timestamp, of
+    /// `ts_size` bytes. Returns `None` if an entry doesn't fit in a packet.
+    pub(crate) fn odts(
+        &self,
+        max_dto: usize,
+        id_size: usize,
+        ts_size: usize,
+    ) -> Option<Vec<&[OdtEntry]>> {
+        let mut odts = Vec::new();
+        let mut start = 0;
+        let mut avail = max_dto.checked_sub(id_size + ts_size)?;
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            let size = entry.size as usize;
+            if size > avail {
+                if i == start {
+                    return None;
+                }
+                odts.push(&self.entries[start..i]);
+                start = i;
+                avail = max_dto - id_size;
+                if size > avail {
+                    return None;
+                }
+            }
+            avail -= size;
+        }
+        if start < self.entries.len() {
+            odts.push(&self.entries[start..]);
+        }
+        Some(odts)
+    }
+}
+
+// ===== DaqProcessorInfo =====
+
+/// The DAQ capabilities of the slave, from GET_DAQ_PROCESSOR_INFO.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DaqProcessorInfo {
+    /// The DAQ properties bits
+    pub properties: u8,
+    /// The total number of DAQ lists
+    pub max_daq: u16,
+    /// The number of event channels, or zero if unknown
+    pub max_event_channel: u16,
+    /// The number of predefined DAQ lists
+    pub min_daq: u8,
+    /// The optimisation type, address extension type, and identification
+    /// field type
+    pub key_byte: u8,
+}
+
+impl DaqProcessorInfo {
+    /// The property bit for dynamic DAQ configuration
+    pub const DAQ_CONFIG_TYPE: u8 = 0x01;
+    /// The property bit for timestamp support
+    pub const TIMESTAMP_SUPPORTED: u8 = 0x10;
+
+    /// Determines if the slave supports dynamic DAQ configuration.
+    pub fn is_dynamic(&self) -> bool {
+        self.properties & Self::DAQ_CONFIG_TYPE != 0
+    }
+
+    /// Gets the identification field type, from 0 (an absolute ODT
+    /// number) to 3 (a relative ODT number with an aligned, two byte DAQ
+    /// list number).
+    pub fn id_field_type(&self) -> u8 {
+        self.key_byte >> 6
+    }
+
+    /// Gets the size of the identification field of the DAQ packets.
+    pub fn id_field_size(&self) -> usize {
+        match self.id_field_type() {
+            0 => 1,
+            1 => 2,
+            2 => 3,
+            _ => 4,
+        }
+    }
+}
+
+// ===== DaqResolution =====
+
+/// The timestamp resolution of the slave, from GET_DAQ_RESOLUTION_INFO.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DaqResolution {
+    /// The granularity of the size of an ODT entry
+    pub granularity: u8,
+    /// The largest ODT entry, in elements
+    pub max_entry_size: u8,
+    /// The timestamp size, fixed bit, and unit
+    pub timestamp_mode: u8,
+    /// The number of units in a timestamp tick
+    pub timestamp_ticks: u16,
+}
+
+impl DaqResolution {
+    /// Gets the size of the timestamps, in bytes.
+    pub fn timestamp_size(&self) -> usize {
+        match self.timestamp_mode & 0x07 {
+            n @ (1 | 2 | 4) => n as usize,
+            _ => 0,
+        }
+    }
+
+    /// Gets the length of a timestamp tick.
+    pub fn tick(&self) -> Duration {
+        // The unit is 10^n ns, for n in 0..=9
+        let unit = 10u64.pow(((self.timestamp_mode >> 4) as u32).min(9));
+        Duration::from_nanos(unit * u64::from(self.timestamp_ticks))
+    }
+
+    /// Converts a timestamp from the slave to a duration.
+    pub fn duration(&self, timestamp: u32) -> Duration {
+        self.tick() * timestamp
+    }
+}
+
+// ===== DaqPacket =====
+
+/// A decoded DAQ packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DaqPacket {
+    /// The index of the DAQ list in the configuration
+    pub daq: u16,
+    /// The index of the ODT in the list
+    pub odt: u8,
+    /// The timestamp from the slave, in ticks, if the packet has one
+    pub timestamp: Option<u32>,
+    /// The names and raw bytes of the measurements in the packet
+    pub values: Vec<(String, Vec<u8>)>,
+}
+
+impl DaqPacket {
+    /// Gets the raw bytes of a measurement.
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.values
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, data)| data.as_slice())
+    }
+}
+
+// ===== DaqDecoder =====
+
+/// A configured DAQ list, for decoding.
+#[derive(Debug, Clone)]
+struct ListLayout {
+    /// The DAQ list number in the slave
+    number: u16,
+    /// The PID of the first ODT, for absolute identification
+    first_pid: u8,
+    /// The entries of each ODT
+    odts: Vec<Vec<OdtEntry>>,
+    /// Whether the first ODT has a timestamp
+    timestamp: bool,
+}
+
+/// Decodes the DAQ packets of the configured lists.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DaqDecoder {
+    lists: Vec<ListLayout>,
+    id_field_type: u8,
+    ts_size: usize,
+    byte_order: Option<ByteOrder>,
+}
+
+impl DaqDecoder {
+    /// Creates a decoder for the slave's identification field type,
+    /// timestamp size, and byte order.
+    pub(crate) fn new(id_field_type: u8, ts_size: usize, byte_order: ByteOrder) -> Self {
+        Self {
+            lists: Vec::new(),
+            id_field_type,
+            ts_size,
+            byte_order: Some(byte_order),
+        }
+    }
+
+    /// Adds a configured list.
+    pub(crate) fn add(
+        &mut self,
+        number: u16,
+        first_pid: u8,
+        odts: Vec<Vec<OdtEntry>>,
+        timestamp: bool,
+    ) {
+        self.lists.push(ListLayout {
+            number,
+            first_pid,
+            odts,
+            timestamp: timestamp && self.ts_size > 0,
+        });
+    }
+
+    /// Decodes a DAQ packet, if it's from one of the lists.
+    pub(crate) fn decode(&self, data: &[u8]) -> Option<DaqPacket> {
+        let order = self.byte_order?;
+        let (index, odt, mut pos) = match self.id_field_type {
+            0 => {
+                let pid = *data.first()?;
+                self.lists.iter().enumerate().find_map(|(i, list)| {
+                    let odt = pid.checked_sub(list.first_pid)?;
+                    ((odt as usize) < list.odts.len()).then_some((i, odt, 1))
+                })?
+            }
+            n => {
+                let (number, pos) = match n {
+                    1 => (u16::from(*data.get(1)?), 2),
+                    2 => (get_uint(data.get(1..3)?, order) as u16, 3),
+                    _ => (get_uint(data.get(2..4)?, order) as u16, 4),
+                };
+                let i = self.lists.iter().position(|list| list.number == number)?;
+                (i, *data.first()?, pos)
+            }
+        };
+
+        let list = &self.lists[index];
+        let entries = list.odts.get(odt as usize)?;
+
+        let timestamp = if odt == 0 && list.timestamp {
+            let ts = get_uint(data.get(pos..pos + self.ts_size)?, order) as u32;
+            pos += self.ts_size;
+            Some(ts)
+        } else {
+            None
+        };
+
+        let mut values = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let end = pos + entry.size as usize;
+            values.push((entry.name.clone(), data.get(pos..end)?.to_vec()));
+            pos = end;
+        }
+
+        Some(DaqPacket {
+            daq: index as u16,
+            odt,
+            timestamp,
+            values,
+        })
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list() -> DaqList {
+        DaqList::new(1)
+            .timestamp(true)
+            .measure("a", 0x1000, 0, 4)
+            .measure("b", 0x1004, 0, 2)
+            .measure("c", 0x1008, 0, 4)
+            .measure("d", 0x100C, 0, 1)
+    }
+
+    #[test]
+    fn test_odts() {
+        let list = list();
+
+        let odts = list.odts(8, 1, 2).unwrap();
+        let names: Vec<Vec<&str>> = odts
+            .iter()
+            .map(|odt| odt.iter().map(|e| e.name.as_str()).collect())
+            .collect();
+        assert_eq!(names, vec![vec!["a"], vec!["b", "c", "d"]]);
+
+        let odts = list.odts(64, 1, 4).unwrap();
+        assert_eq!(odts.len(), 1);
+        assert_eq!(odts[0].len(), 4);
+
+        assert!(list.odts(4, 1, 0).is_none());
+        assert!(list.odts(6, 1, 2).is_none());
+        assert!(DaqList::new(0).odts(8, 1, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_resolution() {
+        let res = DaqResolution {
+            timestamp_mode: 0x34,
+            timestamp_ticks: 10,
+            ..DaqResolution::default()
+        };
+        assert_eq!(res.timestamp_size(), 4);
+        assert_eq!(res.tick(), Duration::from_micros(10));
+        assert_eq!(res.duration(150), Duration::from_micros(1500));
+    }
+
+    #[test]
+    fn test_decode() {
+        let list = list();
+        let odts: Vec<Vec<OdtEntry>> = list
+            .odts(8, 1, 2)
+            .unwrap()
+            .into_iter()
+            .map(|odt| odt.to_vec())
+            .collect();
+
+        let mut dec = DaqDecoder::new(0, 2, ByteOrder::LittleEndian);
+        dec.add(0, 4, odts, true);
+
+        let pkt = dec.decode(&[4, 0x34, 0x12, 1, 2, 3, 4]).unwrap();
+        assert_eq!(pkt.daq, 0);
+        assert_eq!(pkt.odt, 0);
+        assert_eq!(pkt.timestamp, Some(0x1234));
+        assert_eq!(pkt.get("a"), Some(&[1u8, 2, 3, 4][..]));
+
+        let pkt = dec.decode(&[5, 1, 2, 3, 4, 5, 6, 7]).unwrap();
+        assert_eq!(pkt.odt, 1);
+        assert_eq!(pkt.timestamp, None);
+        assert_eq!(pkt.get("b"), Some(&[1u8, 2][..]));
+        assert_eq!(pkt.get("c"), Some(&[3u8, 4, 5, 6][..]));
+        assert_eq!(pkt.get("d"), Some(&[7u8][..]));
+        assert_eq!(pkt.get("e"), None);
+
+        assert!(dec.decode(&[3, 0, 0, 0, 0, 0, 0, 0]).is_none());
+        assert!(dec.decode(&[6, 0, 0, 0, 0, 0, 0, 0]).is_none());
+        assert!(dec.decode(&[5, 1, 2]).is_none());
+
+        // Relative ODT with a byte DAQ list number
+        let mut dec = DaqDecoder::new(1, 0, ByteOrder::BigEndian);
+        dec.add(3, 0, vec![list.entries[..2].to_vec()], true);
+        let pkt = dec.decode(&[0, 3, 1, 2, 3, 4, 5, 6]).unwrap();
+        assert_eq!(pkt.timestamp, None);
+        assert_eq!(pkt.get("b"), Some(&[5u8, 6][..]));
+        assert!(dec.decode(&[0, 2, 1, 2, 3, 4, 5, 6]).is_none());
+    }
+}
@@ -0,0 +1,937 @@
+// socketcan/src/xcp/master.rs
+//
+// The XCP-on-CAN master.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! The XCP-on-CAN master.
+//!
+//! The master sends one command at a time and waits for the response.
+//! DAQ packets that arrive while it waits are queued, to be returned by
+//! [`XcpMaster::recv_daq`].
+
+use super::{
+    cmd,
+    daq::{DaqDecoder, DaqList, DaqPacket, DaqProcessorInfo, DaqResolution, MODE_TIMESTAMP},
+    get_uint, put_uint, ConnectInfo, Error, ErrorCode, Options, Status, PID_ERR, PID_EV, PID_RES,
+    PID_SERV,
+};
+use crate::{
+    frame::{fd_len, id_to_canid_t, FdFlags},
+    link::Link,
+    ByteOrder, CanAnyFrame, CanDataFrame, CanFdFrame, EmbeddedFrame, Socket, SocketOptions,
+};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// The START_STOP_DAQ_LIST mode to select a list
+const SELECT: u8 = 0x02;
+
+/// The START_STOP_SYNCH mode to stop all lists
+const STOP_ALL: u8 = 0x00;
+
+/// The START_STOP_SYNCH mode to start the selected lists
+const START_SELECTED: u8 = 0x01;
+
+/// The WRITE_DAQ bit offset for a whole element
+const NO_BIT_OFFSET: u8 = 0xFF;
+
+/// An XCP master, connected to one slave over a CAN link.
+#[derive(Debug)]
+pub struct XcpMaster<L> {
+    link: L,
+    opts: Options,
+    info: Option<ConnectInfo>,
+    resolution: Option<DaqResolution>,
+    decoder: DaqDecoder,
+    queue: VecDeque<DaqPacket>,
+}
+
+impl<L: Link + Socket + SocketOptions> XcpMaster<L> {
+    /// Opens a raw CAN socket on the named interface for the master.
+    ///
+    /// This sets a kernel filter on the socket to receive only the frames
+    /// from the slave.
+    pub fn open(ifname: &str, opts: Options) -> Result<Self, Error> {
+        let sock = L::open(ifname)?;
+        sock.set_filters(&[opts.filter()])?;
+        Ok(Self::new(sock, opts))
+    }
+}
+
+impl<L: Link> XcpMaster<L> {
+    /// Creates a master over an existing link.
+    pub fn new(link: L, opts: Options) -> Self {
+        Self {
+            link,
+            opts,
+            info: None,
+            resolution: None,
+            decoder: DaqDecoder::default(),
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Gets the options for the connection.
+    pub fn options(&self) -> &Options {
+        &self.opts
+    }
+
+    /// Gets a reference to the underlying link.
+    pub fn link(&self) -> &L {
+        &self.link
+    }
+
+    /// Consumes the master, returning the underlying link.
+    pub fn into_link(self) -> L {
+        self.link
+    }
+
+    /// Gets the properties of the slave, if connected.
+    pub fn connect_info(&self) -> Option<&ConnectInfo> {
+        self.info.as_ref()
+    }
+
+    /// Gets the timestamp resolution of the slave, once the DAQ lists are
+    /// configured.
+    pub fn resolution(&self) -> Option<&DaqResolution> {
+        self.resolution.as_ref()
+    }
+
+    // ----- Standard commands -----
+
+    /// Connects to the slave, in normal mode.
+    ///
+    /// Only slaves with byte address granularity are supported. For any
+    /// other, this disconnects again and gives an
+    /// [`UnsupportedGranularity`](Error::UnsupportedGranularity) error.
+    pub fn connect(&mut self) -> Result<ConnectInfo, Error> {
+        let resp = self.request(&[cmd::CONNECT, 0x00])?;
+        let info = ConnectInfo::from_bytes(&resp[1..]).ok_or(Error::InvalidResponse(resp))?;
+        // Larger elements need alignment bytes in the memory transfers
+        // and in the DAQ packets
+        if info.address_granularity != 1 {
+            let _ = self.request(&[cmd::DISCONNECT]);
+            return Err(Error::UnsupportedGranularity(info.address_granularity));
+        }
+        self.info = Some(info);
+        Ok(info)
+    }
+
+    /// Disconnects from the slave.
+    pub fn disconnect(&mut self) -> Result<(), Error> {
+        self.command(&[cmd::DISCONNECT])?;
+        self.info = None;
+        self.decoder = DaqDecoder::default();
+        self.queue.clear();
+        Ok(())
+    }
+
+    /// Gets the session status of the slave.
+    pub fn get_status(&mut self) -> Result<Status, Error> {
+        let order = self.byte_order()?;
+        let resp = self.command(&[cmd::GET_STATUS])?;
+        if resp.len() < 6 {
+            return Err(Error::InvalidResponse(resp));
+        }
+        Ok(Status {
+            session: resp[1],
+            protection: resp[2],
+            config_id: get_uint(&resp[4..6], order) as u16,
+        })
+    }
+
+    /// Resynchronizes the command processor of the slave, after a
+    /// timeout.
+    pub fn synch(&mut self) -> Result<(), Error> {
+        match self.command(&[cmd::SYNCH]) {
+            Err(Error::Negative {
+                code: ErrorCode::CmdSynch,
+                ..
+            }) => Ok(()),
+            Err(err) => Err(err),
+            Ok(resp) => Err(Error::InvalidResponse(resp)),
+        }
+    }
+
+    /// Sets the memory transfer address (MTA) for uploads and downloads.
+    pub fn set_mta(&mut self, address: u32, extension: u8) -> Result<(), Error> {
+        let order = self.byte_order()?;
+        let mut req = vec![cmd::SET_MTA, 0, 0, extension];
+        put_uint(&mut req, address.into(), 4, order);
+        self.command(&req).map(|_| ())
+    }
+
+    /// Reads `n` bytes from the MTA, which the slave then advances.
+    ///
+    /// If the data doesn't fit in one response, the slave must support
+    /// block mode uploads.
+    pub fn upload(&mut self, n: u8) -> Result<Vec<u8>, Error> {
+        let info = self.info.ok_or(Error::NotConnected)?;
+        let len = n as usize;
+        let max = info.max_cto as usize - 1;
+        if len > max && !info.slave_block_mode {
+            return Err(Error::InvalidLength(len));
+        }
+
+        let resp = self.command(&[cmd::UPLOAD, n])?;
+        let mut data = resp[1..].to_vec();
+        data.truncate(len);
+
+        // The rest of a block mode upload follows without requests
+        while data.len() < len {
+            let resp = self.response(cmd::UPLOAD)?;
+            if resp.len() < 2 {
+                return Err(Error::InvalidResponse(resp));
+            }
+            let more = (len - data.len()).min(resp.len() - 1);
+            data.extend_from_slice(&resp[1..1 + more]);
+        }
+        Ok(data)
+    }
+
+    /// Reads `n` bytes from the address.
+    pub fn short_upload(&mut self, address: u32, extension: u8, n: u8) -> Result<Vec<u8>, Error> {
+        let info = self.info.ok_or(Error::NotConnected)?;
+        let len = n as usize;
+        if len > info.max_cto as usize - 1 {
+            return Err(Error::InvalidLength(len));
+        }
+
+        let mut req = vec![cmd::SHORT_UPLOAD, n, 0, extension];
+        put_uint(&mut req, address.into(), 4, info.byte_order);
+        let resp = self.command(&req)?;
+        if resp.len() < len + 1 {
+            return Err(Error::InvalidResponse(resp));
+        }
+        Ok(resp[1..len + 1].to_vec())
+    }
+
+    /// Writes the data at the MTA, which the slave then advances.
+    pub fn download(&mut self, data: &[u8]) -> Result<(), Error> {
+        let info = self.info.ok_or(Error::NotConnected)?;
+        if data.is_empty() || data.len() > info.max_cto as usize - 2 {
+            return Err(Error::InvalidLength(data.len()));
+        }
+
+        let mut req = vec![cmd::DOWNLOAD, data.len() as u8];
+        req.extend_from_slice(data);
+        self.command(&req).map(|_| ())
+    }
+
+    /// Writes the data at the address.
+    ///
+    /// The data must fit in a single command packet, which leaves no room
+    /// at all with a classic CAN `MAX_CTO` of 8. Use [`write`](Self::write)
+    /// for that.
+    pub fn short_download(
+        &mut self,
+        address: u32,
+        extension: u8,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let info = self.info.ok_or(Error::NotConnected)?;
+        if data.is_empty() || data.len() > info.max_cto as usize - 8 {
+            return Err(Error::InvalidLength(data.len()));
+        }
+
+        let mut req = vec![cmd::SHORT_DOWNLOAD, data.len() as u8, 0, extension];
+        put_uint(&mut req, address.into(), 4, info.byte_order);
+        req.extend_from_slice(data);
+        self.command(&req).map(|_| ())
+    }
+
+    /// Reads `len` bytes from the address, in as many uploads as needed.
+    pub fn read(&mut self, address: u32, extension: u8, len: usize) -> Result<Vec<u8>, Error> {
+        let info = self.info.ok_or(Error::NotConnected)?;
+        let chunk = info.max_cto as usize - 1;
+        self.set_mta(address, extension)?;
+        let mut data = Vec::with_capacity(len);
+        while data.len() < len {
+            let n = chunk.min(len - data.len());
+            data.extend(self.upload(n as u8)?);
+        }
+        Ok(data)
+    }
+
+    /// Writes the data at the address, in as many downloads as needed.
+    pub fn write(&mut self, address: u32, extension: u8, data: &[u8]) -> Result<(), Error> {
+        let info = self.info.ok_or(Error::NotConnected)?;
+        let chunk = info.max_cto as usize - 2;
+        self.set_mta(address, extension)?;
+        for part in data.chunks(chunk) {
+            self.download(part)?;
+        }
+        Ok(())
+    }
+
+    // ----- DAQ commands -----
+
+    /// Gets the DAQ capabilities of the slave.
+    pub fn get_daq_processor_info(&mut self) -> Result<DaqProcessorInfo, Error> {
+        let order = self.byte_order()?;
+        let resp = self.command(&[cmd::GET_DAQ_PROCESSOR_INFO])?;
+        if resp.len() < 8 {
+            return Err(Error::InvalidResponse(resp));
+        }
+        Ok(DaqProcessorInfo {
+            properties: resp[1],
+            max_daq: get_uint(&resp[2..4], order) as u16,
+            max_event_channel: get_uint(&resp[4..6], order) as u16,
+            min_daq: resp[6],
+            key_byte: resp[7],
+        })
+    }
+
+    /// Gets the timestamp resolution of the slave.
+    pub fn get_daq_resolution_info(&mut self) -> Result<DaqResolution, Error> {
+        let order = self.byte_order()?;
+        let resp = self.command(&[cmd::GET_DAQ_RESOLUTION_INFO])?;
+        if resp.len() < 8 {
+            return Err(Error::InvalidResponse(resp));
+        }
+        Ok(DaqResolution {
+            granularity: resp[1],
+            max_entry_size: resp[2],
+            timestamp_mode: resp[5],
+            timestamp_ticks: get_uint(&resp[6..8], order) as u16,
+        })
+    }
+
+    /// Clears the dynamic DAQ configuration of the slave.
+    pub fn free_daq(&mut self) -> Result<(), Error> {
+        self.command(&[cmd::FREE_DAQ])?;
+        self.decoder = DaqDecoder::default();
+        Ok(())
+    }
+
+    /// Allocates `count` dynamic DAQ lists.
+    pub fn alloc_daq(&mut self, count: u16) -> Result<(), Error> {
+        let order = self.byte_order()?;
+        let mut req = vec![cmd::ALLOC_DAQ, 0];
+        put_uint(&mut req, count.into(), 2, order);
+        self.command(&req).map(|_| ())
+    }
+
+    /// Allocates `count` ODTs in a DAQ list.
+    pub fn alloc_odt(&mut self, daq: u16, count: u8) -> Result<(), Error> {
+        let order = self.byte_order()?;
+        let mut req = vec![cmd::ALLOC_ODT, 0];
+        put_uint(&mut req, daq.into(), 2, order);
+        req.push(count);
+        self.command(&req).map(|_| ())
+    }
+
+    /// Allocates `count` entries in an ODT.
+    pub fn alloc_odt_entry(&mut self, daq: u16, odt: u8, count: u8) -> Result<(), Error> {
+        let order = self.byte_order()?;
+        let mut req = vec![cmd::ALLOC_ODT_ENTRY, 0];
+        put_uint(&mut req, daq.into(), 2, order);
+        req.extend_from_slice(&[odt, count]);
+        self.command(&req).map(|_| ())
+    }
+
+    /// Points to an ODT entry, for writing.
+    pub fn set_daq_ptr(&mut self, daq: u16, odt: u8, entry: u8) -> Result<(), Error> {
+        let order = self.byte_order()?;
+        let mut req = vec![cmd::SET_DAQ_PTR, 0];
+        put_uint(&mut req, daq.into(), 2, order);
+        req.extend_from_slice(&[odt, entry]);
+        self.command(&req).map(|_| ())
+    }
+
+    /// Writes the ODT entry at the pointer, which the slave then advances.
+    pub fn write_daq(&mut self, size: u8, address: u32, extension: u8) -> Result<(), Error> {
+        let order = self.byte_order()?;
+        let mut req = vec![cmd::WRITE_DAQ, NO_BIT_OFFSET, size, extension];
+        put_uint(&mut req, address.into(), 4, order);
+        self.command(&req).map(|_| ())
+    }
+
+    /// Sets the mode of a DAQ list.
+    pub fn set_daq_list_mode(
+        &mut self,
+        mode: u8,
+        daq: u16,
+        event_channel: u16,
+        prescaler: u8,
+        priority: u8,
+    ) -> Result<(), Error> {
+        let order = self.byte_order()?;
+        let mut req = vec![cmd::SET_DAQ_LIST_MODE, mode];
+        put_uint(&mut req, daq.into(), 2, order);
+        put_uint(&mut req, event_channel.into(), 2, order);
+        req.extend_from_slice(&[prescaler, priority]);
+        self.command(&req).map(|_| ())
+    }
+
+    /// Stops (0), starts (1), or selects (2) a DAQ list, returning the PID
+    /// of its first ODT.
+    pub fn start_stop_daq_list(&mut self, mode: u8, daq: u16) -> Result<u8, Error> {
+        let order = self.byte_order()?;
+        let mut req = vec![cmd::START_STOP_DAQ_LIST, mode];
+        put_uint(&mut req, daq.into(), 2, order);
+        let resp = self.command(&req)?;
+        resp.get(1).copied().ok_or(Error::InvalidResponse(resp))
+    }
+
+    /// Stops all (0), starts the selected (1), or stops the selected (2)
+    /// DAQ lists.
+    pub fn start_stop_synch(&mut self, mode: u8) -> Result<(), Error> {
+        self.command(&[cmd::START_STOP_SYNCH, mode]).map(|_| ())
+    }
+
+    /// Gets the current value of the slave's DAQ clock, in ticks.
+    pub fn get_daq_clock(&mut self) -> Result<u32, Error> {
+        let order = self.byte_order()?;
+        let resp = self.command(&[cmd::GET_DAQ_CLOCK])?;
+        if resp.len() < 8 {
+            return Err(Error::InvalidResponse(resp));
+        }
+        Ok(get_uint(&resp[4..8], order) as u32)
+    }
+
+    // ----- Measurement -----
+
+    /// Configures the slave with the dynamic DAQ lists.
+    ///
+    /// This replaces any previous dynamic configuration. The entries of
+    /// each list are split into as many ODTs as needed to fit in the
+    /// slave's DAQ packets. The lists are selected, to be started together
+    /// with [`start_daq`](Self::start_daq).
+    pub fn configure_daq(&mut self, lists: &[DaqList]) -> Result<(), Error> {
+        let info = self.info.ok_or(Error::NotConnected)?;
+        let proc_info = self.get_daq_processor_info()?;
+        let resolution = self.get_daq_resolution_info()?;
+        self.resolution = Some(resolution);
+
+        let id_size = proc_info.id_field_size();
+        let ts_size = resolution.timestamp_size();
+        let max_dto = info.max_dto as usize;
+
+        let mut layouts = Vec::with_capacity(lists.len());
+        for list in lists {
+            let ts = if list.timestamp { ts_size } else { 0 };
+            let odts = list
+                .odts(max_dto, id_size, ts)
+                .ok_or(Error::InvalidLength(max_dto))?;
+            if odts.len() > usize::from(u8::MAX) {
+                return Err(Error::InvalidLength(list.entries.len()));
+            }
+            layouts.push(odts);
+        }
+
+        self.free_daq()?;
+        self.alloc_daq(lists.len() as u16)?;
+
+        let first = u16::from(proc_info.min_daq);
+        for (i, odts) in layouts.iter().enumerate() {
+            self.alloc_odt(first + i as u16, odts.len() as u8)?;
+        }
+        for (i, odts) in layouts.iter().enumerate() {
+            for (j, odt) in odts.iter().enumerate() {
+                self.alloc_odt_entry(first + i as u16, j as u8, odt.len() as u8)?;
+            }
+        }
+
+        let mut decoder = DaqDecoder::new(proc_info.id_field_type(), ts_size, info.byte_order);
+        for (i, (list, odts)) in lists.iter().zip(&layouts).enumerate() {
+            let daq = first + i as u16;
+            for (j, odt) in odts.iter().enumerate() {
+                self.set_daq_ptr(daq, j as u8, 0)?;
+                for entry in odt.iter() {
+                    self.write_daq(entry.size, entry.address, entry.extension)?;
+                }
+            }
+
+            let mode = if list.timestamp { MODE_TIMESTAMP } else { 0 };
+            self.set_daq_list_mode(mode, daq, list.event_channel, list.prescaler, list.priority)?;
+            let first_pid = self.start_stop_daq_list(SELECT, daq)?;
+
+            let odts = odts.iter().map(|odt| odt.to_vec()).collect();
+            decoder.add(daq, first_pid, odts, list.timestamp);
+        }
+        self.decoder = decoder;
+        Ok(())
+    }
+
+    /// Starts the configured DAQ lists.
+    pub fn start_daq(&mut self) -> Result<(), Error> {
+        self.start_stop_synch(START_SELECTED)
+    }
+
+    /// Stops all of the DAQ lists, discarding any queued packets.
+    pub fn stop_daq(&mut self) -> Result<(), Error> {
+        self.start_stop_synch(STOP_ALL)?;
+        self.queue.clear();
+        Ok(())
+    }
+
+    /// Receives the next DAQ packet, waiting for up to `timeout` for one
+    /// to arrive.
+    ///
+    /// Returns `None` if no packet arrived within the timeout.
+    pub fn recv_daq(&mut self, timeout: Duration) -> Result<Option<DaqPacket>, Error> {
+        if let Some(pkt) = self.queue.pop_front() {
+            return Ok(Some(pkt));
+        }
+
+        let deadline = Instant::now() + timeout;
+        while let Some(data) = self.recv_packet(deadline)? {
+            if data.first().map_or(false, |&pid| pid < PID_SERV) {
+                if let Some(pkt) = self.decoder.decode(&data) {
+                    return Ok(Some(pkt));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    // ----- Transport -----
+
+    /// Gets the byte order of the slave.
+    fn byte_order(&self) -> Result<ByteOrder, Error> {
+        self.info
+            .map(|info| info.byte_order)
+            .ok_or(Error::NotConnected)
+    }
+
+    /// Sends a command to a connected slave, and waits for the response.
+    fn command(&mut self, req: &[u8]) -> Result<Vec<u8>, Error> {
+        if self.info.is_none() {
+            return Err(Error::NotConnected);
+        }
+        self.request(req)
+    }
+
+    /// Sends a command, and waits for the response.
+    fn request(&mut self, req: &[u8]) -> Result<Vec<u8>, Error> {
+        let max_frame = if self.opts.fd { 64 } else { 8 };
+        let max_cto = match self.info {
+            Some(info) => max_frame.min(info.max_cto as usize),
+            None => max_frame,
+        };
+        if req.len() > max_cto {
+            return Err(Error::InvalidLength(req.len()));
+        }
+        self.link.send(&self.frame(req))?;
+        self.response(req[0])
+    }
+
+    /// Waits for the response to the command, queueing any DAQ packets
+    /// that arrive first.
+    fn response(&mut self, command: u8) -> Result<Vec<u8>, Error> {
+        let deadline = Instant::now() + self.opts.timeout;
+        while let Some(data) = self.recv_packet(deadline)? {
+            match data.first() {
+                Some(&PID_RES) => return Ok(data),
+                Some(&PID_ERR) => {
+                    let code = ErrorCode::from(data.get(1).copied().unwrap_or(0x31));
+                    return Err(Error::Negative { command, code });
+                }
+                Some(&PID_EV) | Some(&PID_SERV) | None => {}
+                Some(_) => {
+                    if let Some(pkt) = self.decoder.decode(&data) {
+                        self.queue.push_back(pkt);
+                    }
+                }
+            }
+        }
+        Err(Error::Timeout)
+    }
+
+    /// Reads the data of the next frame from the slave, waiting until the
+    /// deadline.
+    fn recv_packet(&self, deadline: Instant) -> Result<Option<Vec<u8>>, Error> {
+        loop {
+            let timeout = match deadline.checked_duration_since(Instant::now()) {
+                Some(timeout) if !timeout.is_zero() => timeout,
+                _ => return Ok(None),
+            };
+            let frame = match self.link.recv(Some(timeout))? {
+                Some(frame) => frame,
+                None => return Ok(None),
+            };
+            let (id, data) = match &frame {
+                CanAnyFrame::Normal(frame) => (frame.id(), frame.data()),
+                CanAnyFrame::Fd(frame) => (frame.id(), frame.data()),
+                _ => continue,
+            };
+            if id == self.opts.res_id {
+                return Ok(Some(data.to_vec()));
+            }
+        }
+    }
+
+    /// Builds a command frame, padded as required.
+    fn frame(&self, data: &[u8]) -> CanAnyFrame {
+        let mut buf = [0u8; 64];
+        buf[..data.len()].copy_from_slice(data);
+
+        let len = match (self.opts.padding, data.len()) {
+            (_, n) if n > 8 => fd_len(n),
+            (Some(_), _) => 8,
+            (None, n) => n,
+        };
+        buf[data.len()..len].fill(self.opts.padding.unwrap_or(0));
+
+        if self.opts.fd {
+            CanFdFrame::init(
+                id_to_canid_t(self.opts.cmd_id),
+                &buf[..len],
+                FdFlags::empty(),
+            )
+            .expect("valid XCP frame length")
+            .into()
+        } else {
+            CanAnyFrame::Normal(
+                CanDataFrame::init(id_to_canid_t(self.opts.cmd_id), &buf[..len])
+                    .expect("valid XCP frame length"),
+            )
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        link::{VirtualBus, VirtualLink},
+        Id, StandardId,
+    };
+    use std::{
+        sync::{Arc, Mutex},
+        thread::{self, JoinHandle},
+    };
+
+    const BASE: u32 = 0x1000;
+
+    fn id(id: u16) -> StandardId {
+        StandardId::new(id).unwrap()
+    }
+
+    // A simulated little endian slave with 256 bytes of memory at BASE,
+    // and dynamic DAQ lists with 2-byte timestamps.
+    #[derive(Default)]
+    struct Slave {
+        mem: Vec<u8>,
+        mta: u32,
+        daq: Vec<Vec<Vec<(u32, u8)>>>,
+        ptr: (usize, usize),
+        ts: Vec<bool>,
+        log: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Slave {
+        fn new(log: Arc<Mutex<Vec<u8>>>) -> Self {
+            Self {
+                mem: (0..=255).collect(),
+                log,
+                ..Self::default()
+            }
+        }
+
+        fn get(&self, addr: u32, n: usize) -> Option<&[u8]> {
+            let off = addr.checked_sub(BASE)? as usize;
+            self.mem.get(off..off + n)
+        }
+
+        fn handle(&mut self, req: &[u8]) -> Vec<Vec<u8>> {
+            let u16_at = |i: usize| u16::from_le_bytes([req[i], req[i + 1]]) as usize;
+            let u32_at =
+                |i: usize| u32::from_le_bytes([req[i], req[i + 1], req[i + 2], req[i + 3]]);
+            let ok = vec![vec![PID_RES]];
+            let err = |code: u8| vec![vec![PID_ERR, code]];
+            self.log.lock().unwrap().push(req[0]);
+
+            match req[0] {
+                cmd::CONNECT => vec![vec![PID_RES, 0x05, 0x00, 8, 8, 0, 1, 1]],
+                cmd::GET_STATUS => {
+                    // A stray DAQ packet arrives ahead of the response
+                    vec![
+                        vec![0, 0x10, 0x00, 9, 9, 9, 9],
+                        vec![PID_RES, 0x40, 0, 0, 0x34, 0x12],
+                    ]
+                }
+                cmd::SYNCH => err(0x00),
+                cmd::SET_MTA => {
+                    self.mta = u32_at(4);
+                    ok
+                }
+                cmd::SHORT_UPLOAD => match self.get(u32_at(4), req[1] as usize) {
+                    Some(data) => vec![[&[PID_RES][..], data].concat()],
+                    None => err(0x24),
+                },
+                cmd::UPLOAD => match self.get(self.mta, req[1] as usize) {
+                    Some(data) => {
+                        let resp = [&[PID_RES][..], data].concat();
+                        self.mta += u32::from(req[1]);
+                        vec![resp]
+                    }
+                    None => err(0x24),
+                },
+                cmd::DOWNLOAD => {
+                    let n = req[1] as usize;
+                    let off = (self.mta - BASE) as usize;
+                    self.mem[off..off + n].copy_from_slice(&req[2..2 + n]);
+                    self.mta += n as u32;
+                    ok
+                }
+                cmd::GET_DAQ_PROCESSOR_INFO => vec![vec![PID_RES, 0x11, 2, 0, 2, 0, 0, 0x00]],
+                cmd::GET_DAQ_RESOLUTION_INFO => vec![vec![PID_RES, 1, 8, 1, 8, 0x32, 10, 0]],
+                cmd::FREE_DAQ => {
+                    self.daq.clear();
+                    self.ts.clear();
+                    ok
+                }
+                cmd::ALLOC_DAQ => {
+                    self.daq = vec![Vec::new(); u16_at(2)];
+                    self.ts = vec![false; u16_at(2)];
+                    ok
+                }
+                cmd::ALLOC_ODT => {
+                    self.daq[u16_at(2)] = vec![Vec::new(); req[4] as usize];
+                    ok
+                }
+                cmd::ALLOC_ODT_ENTRY => {
+                    self.daq[u16_at(2)][req[4] as usize] = Vec::with_capacity(req[5] as usize);
+                    ok
+                }
+                cmd::SET_DAQ_PTR => {
+                    self.ptr = (u16_at(2), req[4] as usize);
+                    ok
+                }
+                cmd::WRITE_DAQ => {
+                    let (daq, odt) = self.ptr;
+                    self.daq[daq][odt].push((u32_at(4), req[2]));
+                    ok
+                }
+                cmd::SET_DAQ_LIST_MODE => {
+                    self.ts[u16_at(2)] = req[1] & MODE_TIMESTAMP != 0;
+                    ok
+                }
+                cmd::START_STOP_DAQ_LIST => {
+                    let first: usize = self.daq[..u16_at(2)].iter().map(Vec::len).sum();
+                    vec![vec![PID_RES, first as u8]]
+                }
+                cmd::START_STOP_SYNCH if req[1] == START_SELECTED => {
+                    let mut resp = ok;
+                    let mut pid = 0;
+                    for (daq, odts) in self.daq.iter().enumerate() {
+                        for (odt, entries) in odts.iter().enumerate() {
+                            let mut pkt = vec![pid];
+                            if odt == 0 && self.ts[daq] {
+                                pkt.extend_from_slice(&[0x64, 0x00]);
+                            }
+                            for &(addr, size) in entries {
+                                pkt.extend_from_slice(self.get(addr, size as usize).unwrap());
+                            }
+                            resp.push(pkt);
+                            pid += 1;
+                        }
+                    }
+                    resp
+                }
+                cmd::START_STOP_SYNCH | cmd::DISCONNECT => ok,
+                _ => err(0x20),
+            }
+        }
+    }
+
+    // Starts the slave on the bus, until the master goes quiet.
+    fn slave(bus: &VirtualBus, log: Arc<Mutex<Vec<u8>>>) -> JoinHandle<()> {
+        let link = bus.connect();
+        let mut slave = Slave::new(log);
+        thread::spawn(move || {
+            while let Ok(Some(frame)) = link.recv(Some(Duration::from_millis(200))) {
+                let data = match frame {
+                    CanAnyFrame::Normal(frame) if frame.id() == Id::from(id(0x7F0)) => {
+                        frame.data().to_vec()
+                    }
+                    _ => continue,
+                };
+                for resp in slave.handle(&data) {
+                    let frame = CanDataFrame::new(id(0x7F1), &resp).unwrap();
+                    link.send(&CanAnyFrame::Normal(frame)).unwrap();
+                }
+            }
+        })
+    }
+
+    fn master(bus: &VirtualBus) -> XcpMaster<VirtualLink> {
+        let opts = Options::new(id(0x7F0), id(0x7F1)).timeout(Duration::from_millis(100));
+        XcpMaster::new(bus.connect(), opts)
+    }
+
+    #[test]
+    fn test_granularity() {
+        // A slave with 2-byte memory elements
+        let bus = VirtualBus::new();
+        let link = bus.connect();
+        let slave = thread::spawn(move || {
+            let mut cmds = Vec::new();
+            while let Ok(Some(CanAnyFrame::Normal(frame))) =
+                link.recv(Some(Duration::from_millis(200)))
+            {
+                cmds.push(frame.data()[0]);
+                let resp: &[u8] = match frame.data()[0] {
+                    cmd::CONNECT => &[PID_RES, 0x05, 0x02, 8, 8, 0, 1, 1],
+                    _ => &[PID_RES],
+                };
+                let frame = CanDataFrame::new(id(0x7F1), resp).unwrap();
+                link.send(&CanAnyFrame::Normal(frame)).unwrap();
+            }
+            cmds
+        });
+
+        let mut master = master(&bus);
+        assert!(matches!(
+            master.connect(),
+            Err(Error::UnsupportedGranularity(2))
+        ));
+        assert!(master.connect_info().is_none());
+        assert!(matches!(master.upload(4), Err(Error::NotConnected)));
+
+        drop(master);
+        assert_eq!(slave.join().unwrap(), [cmd::CONNECT, cmd::DISCONNECT]);
+    }
+
+    #[test]
+    fn test_calibration() {
+        let bus = VirtualBus::new();
+        let slave = slave(&bus, Arc::default());
+        let mut master = master(&bus);
+
+        assert!(matches!(master.get_status(), Err(Error::NotConnected)));
+
+        let info = master.connect().unwrap();
+        assert_eq!(info.byte_order, ByteOrder::LittleEndian);
+        assert_eq!(info.max_cto, 8);
+        assert_eq!(info.max_dto, 8);
+        master.synch().unwrap();
+
+        let status = master.get_status().unwrap();
+        assert!(status.is_daq_running());
+        assert_eq!(status.config_id, 0x1234);
+
+        assert_eq!(
+            master.short_upload(BASE + 0x10, 0, 4).unwrap(),
+            [0x10, 0x11, 0x12, 0x13]
+        );
+        assert!(matches!(
+            master.short_upload(BASE + 0x10, 0, 8),
+            Err(Error::InvalidLength(8))
+        ));
+        let err = master.short_upload(0x10, 0, 1).unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::AccessDenied));
+
+        let data: Vec<u8> = (0..20).map(|i| 0xA0 + i).collect();
+        master.write(BASE + 0x40, 0, &data).unwrap();
+        assert_eq!(master.read(BASE + 0x3E, 0, 24).unwrap()[2..22], data[..]);
+
+        let err = master.short_download(BASE, 0, &[1]).unwrap_err();
+        assert!(matches!(err, Error::InvalidLength(1)));
+
+        master.disconnect().unwrap();
+        assert!(master.connect_info().is_none());
+        drop(master);
+        slave.join().unwrap();
+    }
+
+    #[test]
+    fn test_daq() {
+        let bus = VirtualBus::new();
+        let log = Arc::<Mutex<Vec<u8>>>::default();
+        let slave = slave(&bus, log.clone());
+        let mut master = master(&bus);
+        master.connect().unwrap();
+
+        let lists = [
+            DaqList::new(0)
+                .timestamp(true)
+                .measure("speed", BASE + 0x20, 0, 4)
+                .measure("temp", BASE + 0x30, 0, 2)
+                .measure("rpm", BASE + 0x40, 0, 4),
+            DaqList::new(1).measure("gear", BASE + 0x50, 0, 1),
+        ];
+        master.configure_daq(&lists).unwrap();
+        assert_eq!(
+            master.resolution().unwrap().tick(),
+            Duration::from_micros(10)
+        );
+
+        let log: Vec<u8> = log.lock().unwrap().split_off(1);
+        assert_eq!(
+            log[..7],
+            [
+                cmd::GET_DAQ_PROCESSOR_INFO,
+                cmd::GET_DAQ_RESOLUTION_INFO,
+                cmd::FREE_DAQ,
+                cmd::ALLOC_DAQ,
+                cmd::ALLOC_ODT,
+                cmd::ALLOC_ODT,
+                cmd::ALLOC_ODT_ENTRY,
+            ]
+        );
+
+        // The stray packet before the status response is for list 0
+        master.get_status().unwrap();
+        master.start_daq().unwrap();
+
+        let pkt = master
+            .recv_daq(Duration::from_millis(100))
+            .unwrap()
+            .unwrap();
+        assert_eq!((pkt.daq, pkt.odt, pkt.timestamp), (0, 0, Some(0x10)));
+        assert_eq!(pkt.get("speed"), Some(&[9u8, 9, 9, 9][..]));
+
+        let pkt = master
+            .recv_daq(Duration::from_millis(100))
+            .unwrap()
+            .unwrap();
+        assert_eq!((pkt.daq, pkt.odt, pkt.timestamp), (0, 0, Some(100)));
+        assert_eq!(pkt.get("speed"), Some(&[0x20u8, 0x21, 0x22, 0x23][..]));
+        assert_eq!(pkt.get("temp"), None);
+
+        let pkt = master
+            .recv_daq(Duration::from_millis(100))
+            .unwrap()
+            .unwrap();
+        assert_eq!((pkt.daq, pkt.odt, pkt.timestamp), (0, 1, None));
+        assert_eq!(pkt.get("temp"), Some(&[0x30u8, 0x31][..]));
+        assert_eq!(pkt.get("rpm"), Some(&[0x40u8, 0x41, 0x42, 0x43][..]));
+
+        let pkt = master
+            .recv_daq(Duration::from_millis(100))
+            .unwrap()
+            .unwrap();
+        assert_eq!((pkt.daq, pkt.odt, pkt.timestamp), (1, 0, None));
+        assert_eq!(pkt.get("gear"), Some(&[0x50u8][..]));
+
+        assert!(master
+            .recv_daq(Duration::from_millis(20))
+            .unwrap()
+            .is_none());
+        master.stop_daq().unwrap();
+        drop(master);
+        slave.join().unwrap();
+    }
+
+    #[test]
+    fn test_timeout() {
+        let bus = VirtualBus::new();
+        let mut master = master(&bus);
+        let start = Instant::now();
+        assert!(matches!(master.connect(), Err(Error::Timeout)));
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+}
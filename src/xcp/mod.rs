@@ -0,0 +1,559 @@
+// socketcan/src/xcp/mod.rs
+//
+// An XCP-on-CAN master.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+//! An XCP (ASAM MCD-1 XCP) master, for measurement and calibration of
+//! ECUs over CAN and CAN FD.
+//!
+//! The master sends command packets (CTO) on one CAN ID, and the slave
+//! answers on another, where it also sends the data acquisition (DAQ)
+//! packets. Memory in the slave is addressed directly, with a 32-bit
+//! address and an 8-bit address extension, so no A2L file is needed; the
+//! addresses of the measurements and calibration parameters are given by
+//! the caller.
+//!
+//! The [`XcpMaster`] runs over any CAN [`Link`](crate::link::Link),
+//! such as a [`CanSocket`](crate::CanSocket) or a
+//! [`CanFdSocket`](crate::CanFdSocket):
+//!
+//! ```no_run
+//! use socketcan::{
+//!     xcp::{DaqList, Options, XcpMaster},
+//!     CanSocket, StandardId,
+//! };
+//! use std::time::Duration;
+//!
+//! let opts = Options::new(StandardId::new(0x7F0).unwrap(), StandardId::new(0x7F1).unwrap());
+//! let mut master = XcpMaster::<CanSocket>::open("can0", opts).unwrap();
+//!
+//! let info = master.connect().unwrap();
+//! println!("Max CTO: {}, max DTO: {}", info.max_cto, info.max_dto);
+//!
+//! // Calibration
+//! let value = master.short_upload(0x2000_0010, 0, 4).unwrap();
+//! master.write(0x2000_0010, 0, &[0, 0, 0x80, 0x3F]).unwrap();
+//!
+//! // Measurement, on event channel 0
+//! let list = DaqList::new(0)
+//!     .timestamp(true)
+//!     .measure("engine_speed", 0x2000_0100, 0, 2)
+//!     .measure("coolant_temp", 0x2000_0104, 0, 1);
+//! master.configure_daq(&[list]).unwrap();
+//! master.start_daq().unwrap();
+//!
+//! while let Some(pkt) = master.recv_daq(Duration::from_secs(1)).unwrap() {
+//!     println!("{:?} {:?}", pkt.timestamp, pkt.get("engine_speed"));
+//! }
+//! master.stop_daq().unwrap();
+//! ```
+
+use crate::{frame::id_to_canid_t, ByteOrder, CanFilter, Id};
+use libc::{CAN_EFF_FLAG, CAN_EFF_MASK, CAN_RTR_FLAG, CAN_SFF_MASK};
+use std::{error, fmt, io, time::Duration};
+
+pub mod daq;
+pub use daq::{DaqList, DaqPacket, DaqProcessorInfo, DaqResolution, OdtEntry};
+
+pub mod master;
+pub use master::XcpMaster;
+
+/// The default time to wait for a response to a command (the XCP `T1`
+/// timeout).
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(25);
+
+/// The XCP command codes.
+pub mod cmd {
+    /// Starts a session
+    pub const CONNECT: u8 = 0xFF;
+    /// Ends a session
+    pub const DISCONNECT: u8 = 0xFE;
+    /// Gets the session status
+    pub const GET_STATUS: u8 = 0xFD;
+    /// Synchronizes after a timeout
+    pub const SYNCH: u8 = 0xFC;
+    /// Sets the memory transfer address
+    pub const SET_MTA: u8 = 0xF6;
+    /// Reads memory from the MTA
+    pub const UPLOAD: u8 = 0xF5;
+    /// Reads memory from an address
+    pub const SHORT_UPLOAD: u8 = 0xF4;
+    /// Writes memory at the MTA
+    pub const DOWNLOAD: u8 = 0xF0;
+    /// Writes memory at an address
+    pub const SHORT_DOWNLOAD: u8 = 0xED;
+    /// Sets the pointer to an ODT entry
+    pub const SET_DAQ_PTR: u8 = 0xE2;
+    /// Writes the ODT entry at the pointer
+    pub const WRITE_DAQ: u8 = 0xE1;
+    /// Sets the mode of a DAQ list
+    pub const SET_DAQ_LIST_MODE: u8 = 0xE0;
+    /// Starts, stops, or selects a DAQ list
+    pub const START_STOP_DAQ_LIST: u8 = 0xDE;
+    /// Starts or stops the selected DAQ lists together
+    pub const START_STOP_SYNCH: u8 = 0xDD;
+    /// Gets the clock of the slave
+    pub const GET_DAQ_CLOCK: u8 = 0xDC;
+    /// Gets the DAQ capabilities of the slave
+    pub const GET_DAQ_PROCESSOR_INFO: u8 = 0xDA;
+    /// Gets the timestamp resolution of the slave
+    pub const GET_DAQ_RESOLUTION_INFO: u8 = 0xD9;
+    /// Clears the dynamic DAQ configuration
+    pub const FREE_DAQ: u8 = 0xD6;
+    /// Allocates dynamic DAQ lists
+    pub const ALLOC_DAQ: u8 = 0xD5;
+    /// Allocates ODTs in a DAQ list
+    pub const ALLOC_ODT: u8 = 0xD4;
+    /// Allocates entries in an ODT
+    pub const ALLOC_ODT_ENTRY: u8 = 0xD3;
+}
+
+/// The packet identifier of a positive response
+pub(crate) const PID_RES: u8 = 0xFF;
+
+/// The packet identifier of an error response
+pub(crate) const PID_ERR: u8 = 0xFE;
+
+/// The packet identifier of an event
+pub(crate) const PID_EV: u8 = 0xFD;
+
+/// The packet identifier of a service request
+pub(crate) const PID_SERV: u8 = 0xFC;
+
+/// Reads an unsigned integer of up to 8 bytes in the byte order.
+pub(crate) fn get_uint(data: &[u8], order: ByteOrder) -> u64 {
+    let fold = |v: u64, &b: &u8| (v << 8) | u64::from(b);
+    match order {
+        ByteOrder::LittleEndian => data.iter().rev().fold(0, fold),
+        ByteOrder::BigEndian => data.iter().fold(0, fold),
+    }
+}
+
+/// Appends an unsigned integer of `n` bytes in the byte order.
+pub(crate) fn put_uint(buf: &mut Vec<u8>, val: u64, n: usize, order: ByteOrder) {
+    let bytes = val.to_le_bytes();
+    match order {
+        ByteOrder::LittleEndian => buf.extend_from_slice(&bytes[..n]),
+        ByteOrder::BigEndian => buf.extend(bytes[..n].iter().rev()),
+    }
+}
+
+// ===== ErrorCode =====
+
+/// The error code in an error response from the slave.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// The command was a SYNCH
+    CmdSynch,
+    /// The command was rejected because another one is running
+    CmdBusy,
+    /// The command was rejected because DAQ is running
+    DaqActive,
+    /// The command was rejected because programming is running
+    PgmActive,
+    /// The command is unknown or not implemented
+    CmdUnknown,
+    /// The command has a syntax error
+    CmdSyntax,
+    /// A parameter is out of range
+    OutOfRange,
+    /// The memory is write protected
+    WriteProtected,
+    /// Access to the memory is denied
+    AccessDenied,
+    /// Access is locked, and needs to be unlocked with a seed and key
+    AccessLocked,
+    /// The calibration page is not valid
+    PageNotValid,
+    /// The page mode is not valid
+    ModeNotValid,
+    /// The segment is not valid
+    SegmentNotValid,
+    /// The sequence of commands is wrong
+    Sequence,
+    /// The DAQ configuration is not valid
+    DaqConfig,
+    /// The memory overflowed
+    MemoryOverflow,
+    /// A generic error
+    Generic,
+    /// A verification failed
+    Verify,
+    /// A resource is temporarily not accessible
+    ResourceTemporaryNotAccessible,
+    /// Another error code
+    Other(u8),
+}
+
+impl From<u8> for ErrorCode {
+    fn from(code: u8) -> Self {
+        use ErrorCode::*;
+        match code {
+            0x00 => CmdSynch,
+            0x10 => CmdBusy,
+            0x11 => DaqActive,
+            0x12 => PgmActive,
+            0x20 => CmdUnknown,
+            0x21 => CmdSyntax,
+            0x22 => OutOfRange,
+            0x23 => WriteProtected,
+            0x24 => AccessDenied,
+            0x25 => AccessLocked,
+            0x26 => PageNotValid,
+            0x27 => ModeNotValid,
+            0x28 => SegmentNotValid,
+            0x29 => Sequence,
+            0x2A => DaqConfig,
+            0x30 => MemoryOverflow,
+            0x31 => Generic,
+            0x32 => Verify,
+            0x33 => ResourceTemporaryNotAccessible,
+            code => Other(code),
+        }
+    }
+}
+
+impl From<ErrorCode> for u8 {
+    fn from(code: ErrorCode) -> Self {
+        use ErrorCode::*;
+        match code {
+            CmdSynch => 0x00,
+            CmdBusy => 0x10,
+            DaqActive => 0x11,
+            PgmActive => 0x12,
+            CmdUnknown => 0x20,
+            CmdSyntax => 0x21,
+            OutOfRange => 0x22,
+            WriteProtected => 0x23,
+            AccessDenied => 0x24,
+            AccessLocked => 0x25,
+            PageNotValid => 0x26,
+            ModeNotValid => 0x27,
+            SegmentNotValid => 0x28,
+            Sequence => 0x29,
+            DaqConfig => 0x2A,
+            MemoryOverflow => 0x30,
+            Generic => 0x31,
+            Verify => 0x32,
+            ResourceTemporaryNotAccessible => 0x33,
+            Other(code) => code,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ErrorCode::*;
+        let msg = match *self {
+            CmdSynch => "command synchronization",
+            CmdBusy => "command busy",
+            DaqActive => "DAQ is running",
+            PgmActive => "programming is running",
+            CmdUnknown => "unknown command",
+            CmdSyntax => "command syntax error",
+            OutOfRange => "parameter out of range",
+            WriteProtected => "memory is write protected",
+            AccessDenied => "access denied",
+            AccessLocked => "access locked",
+            PageNotValid => "page not valid",
+            ModeNotValid => "mode not valid",
+            SegmentNotValid => "segment not valid",
+            Sequence => "wrong sequence",
+            DaqConfig => "DAQ configuration not valid",
+            MemoryOverflow => "memory overflow",
+            Generic => "generic error",
+            Verify => "verification failed",
+            ResourceTemporaryNotAccessible => "resource temporarily not accessible",
+            Other(code) => return write!(f, "error 0x{:02X}", code),
+        };
+        f.write_str(msg)
+    }
+}
+
+// ===== ConnectInfo =====
+
+/// The properties of the slave, from the response to CONNECT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectInfo {
+    /// The resources that the slave supports (CAL/PAG, DAQ, STIM, PGM)
+    pub resource: u8,
+    /// The byte order of multi-byte parameters and data
+    pub byte_order: ByteOrder,
+    /// The size of an element of memory, in bytes (1, 2, or 4)
+    pub address_granularity: u8,
+    /// Whether the slave supports block mode uploads
+    pub slave_block_mode: bool,
+    /// The largest command and response packet
+    pub max_cto: u8,
+    /// The largest data acquisition packet
+    pub max_dto: u16,
+    /// The XCP protocol layer version
+    pub protocol_version: u8,
+    /// The XCP transport layer version
+    pub transport_version: u8,
+}
+
+impl ConnectInfo {
+    /// The resource bit for calibration and paging
+    pub const CAL_PAG: u8 = 0x01;
+    /// The resource bit for data acquisition
+    pub const DAQ: u8 = 0x04;
+    /// The resource bit for stimulation
+    pub const STIM: u8 = 0x08;
+    /// The resource bit for programming
+    pub const PGM: u8 = 0x10;
+
+    /// Parses the response to CONNECT, after the PID.
+    ///
+    /// Returns `None` if the response is too short, the packet sizes are
+    /// below the minimum of 8 bytes, or the address granularity is the
+    /// reserved value.
+    pub(crate) fn from_bytes(data: &[u8]) -> Option<Self> {
+        if data.len() < 7 {
+            return None;
+        }
+        let mode = data[1];
+        if (mode >> 1) & 0x03 == 0x03 {
+            return None;
+        }
+        let byte_order = if mode & 0x01 != 0 {
+            ByteOrder::BigEndian
+        } else {
+            ByteOrder::LittleEndian
+        };
+        let (max_cto, max_dto) = (data[2], get_uint(&data[3..5], byte_order) as u16);
+        if max_cto < 8 || max_dto < 8 {
+            return None;
+        }
+        Some(Self {
+            resource: data[0],
+            byte_order,
+            address_granularity: 1 << ((mode >> 1) & 0x03),
+            slave_block_mode: mode & 0x40 != 0,
+            max_cto,
+            max_dto,
+            protocol_version: data[5],
+            transport_version: data[6],
+        })
+    }
+}
+
+// ===== Status =====
+
+/// The session status of the slave, from GET_STATUS.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Status {
+    /// The session status bits
+    pub session: u8,
+    /// The resources that are protected with a seed and key
+    pub protection: u8,
+    /// The session configuration ID
+    pub config_id: u16,
+}
+
+impl Status {
+    /// The session bit for a pending request to store calibration data
+    pub const STORE_CAL_REQ: u8 = 0x01;
+    /// The session bit for a pending request to store the DAQ lists
+    pub const STORE_DAQ_REQ: u8 = 0x04;
+    /// The session bit for a pending request to clear the DAQ lists
+    pub const CLEAR_DAQ_REQ: u8 = 0x08;
+    /// The session bit for running data acquisition
+    pub const DAQ_RUNNING: u8 = 0x40;
+    /// The session bit for resume mode
+    pub const RESUME: u8 = 0x80;
+
+    /// Determines if data acquisition is running.
+    pub fn is_daq_running(&self) -> bool {
+        self.session & Self::DAQ_RUNNING != 0
+    }
+}
+
+// ===== Options =====
+
+/// The CAN IDs and framing of an XCP-on-CAN connection.
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    cmd_id: Id,
+    res_id: Id,
+    fd: bool,
+    padding: Option<u8>,
+    timeout: Duration,
+}
+
+impl Options {
+    /// Creates options for a master that sends commands with the `cmd_id`
+    /// and receives responses and DAQ packets with the `res_id`.
+    ///
+    /// The defaults are classic CAN frames with no padding, and the
+    /// standard 25 ms response timeout.
+    pub fn new(cmd_id: impl Into<Id>, res_id: impl Into<Id>) -> Self {
+        Self {
+            cmd_id: cmd_id.into(),
+            res_id: res_id.into(),
+            fd: false,
+            padding: None,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Sends commands in CAN FD frames.
+    pub fn fd(mut self, fd: bool) -> Self {
+        self.fd = fd;
+        self
+    }
+
+    /// Pads the command frames to the full length, with the byte.
+    ///
+    /// Many slaves require frames with a DLC of 8 (`MAX_DLC_REQUIRED`).
+    pub fn padding(mut self, padding: Option<u8>) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Sets the time to wait for the response to a command.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Gets a filter for the frames from the slave.
+    pub fn filter(&self) -> CanFilter {
+        let mask = match self.res_id {
+            Id::Standard(_) => CAN_SFF_MASK,
+            Id::Extended(_) => CAN_EFF_MASK,
+        };
+        CanFilter::new(
+            id_to_canid_t(self.res_id),
+            mask | CAN_EFF_FLAG | CAN_RTR_FLAG,
+        )
+    }
+}
+
+// ===== Error =====
+
+/// An XCP error.
+#[derive(Debug)]
+pub enum Error {
+    /// An I/O error on the link
+    Io(io::Error),
+    /// No response arrived within the timeout
+    Timeout,
+    /// The slave answered with an error
+    Negative {
+        /// The command code
+        command: u8,
+        /// The error code
+        code: ErrorCode,
+    },
+    /// The response was malformed
+    InvalidResponse(Vec<u8>),
+    /// The data doesn't fit in a packet
+    InvalidLength(usize),
+    /// The command needs a connection to the slave
+    NotConnected,
+    /// The slave's address granularity, in bytes, isn't supported
+    UnsupportedGranularity(u8),
+}
+
+impl Error {
+    /// Gets the error code, if the slave answered with an error.
+    pub fn code(&self) -> Option<ErrorCode> {
+        match *self {
+            Error::Negative { code, .. } => Some(code),
+            _ => None,
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+        match self {
+            Io(err) => write!(f, "I/O error: {}", err),
+            Timeout => f.write_str("no response from the XCP slave"),
+            Negative { command, code } => {
+                write!(f, "command 0x{:02X} failed: {}", command, code)
+            }
+            InvalidResponse(data) => write!(f, "invalid response: {:02X?}", data),
+            InvalidLength(len) => write!(f, "invalid length: {}", len),
+            NotConnected => f.write_str("not connected to the XCP slave"),
+            UnsupportedGranularity(ag) => {
+                write!(f, "unsupported address granularity: {} bytes", ag)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_code() {
+        for code in 0..=0xFF {
+            assert_eq!(u8::from(ErrorCode::from(code)), code);
+        }
+        assert_eq!(ErrorCode::from(0x25), ErrorCode::AccessLocked);
+    }
+
+    #[test]
+    fn test_uint() {
+        let mut buf = Vec::new();
+        put_uint(&mut buf, 0x1234_5678, 4, ByteOrder::LittleEndian);
+        put_uint(&mut buf, 0xABCD, 2, ByteOrder::BigEndian);
+        assert_eq!(buf, [0x78, 0x56, 0x34, 0x12, 0xAB, 0xCD]);
+        assert_eq!(get_uint(&buf[..4], ByteOrder::LittleEndian), 0x1234_5678);
+        assert_eq!(get_uint(&buf[4..], ByteOrder::BigEndian), 0xABCD);
+    }
+
+    #[test]
+    fn test_connect_info() {
+        let info = ConnectInfo::from_bytes(&[0x15, 0x81, 8, 0, 8, 1, 1]).unwrap();
+        assert_eq!(
+            info.resource,
+            ConnectInfo::CAL_PAG | ConnectInfo::DAQ | ConnectInfo::PGM
+        );
+        assert_eq!(info.byte_order, ByteOrder::BigEndian);
+        assert_eq!(info.address_granularity, 1);
+        assert_eq!(info.max_cto, 8);
+        assert_eq!(info.max_dto, 8);
+        assert!(!info.slave_block_mode);
+
+        let info = ConnectInfo::from_bytes(&[0x05, 0x42, 64, 64, 0, 1, 1]).unwrap();
+        assert_eq!(info.byte_order, ByteOrder::LittleEndian);
+        assert_eq!(info.address_granularity, 2);
+        assert_eq!(info.max_dto, 64);
+        assert!(info.slave_block_mode);
+
+        assert!(ConnectInfo::from_bytes(&[0x05, 0x00]).is_none());
+
+        // Packets too small for the commands, or a reserved granularity
+        assert!(ConnectInfo::from_bytes(&[0x05, 0x00, 2, 8, 0, 1, 1]).is_none());
+        assert!(ConnectInfo::from_bytes(&[0x05, 0x00, 8, 7, 0, 1, 1]).is_none());
+        assert!(ConnectInfo::from_bytes(&[0x05, 0x06, 8, 8, 0, 1, 1]).is_none());
+    }
+}
@@ -0,0 +1,588 @@
+// Generated from 'vehicle.dbc' by socketcan::dbc::Codegen. Do not edit.
+
+/// EngineData, with ID 0x100
+///
+/// Engine status
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EngineData {
+    data: [u8; 8],
+}
+
+impl EngineData {
+    /// The ID word, with the `CAN_EFF_FLAG` bit set for an extended ID
+    pub const ID: u32 = 0x100;
+    /// The name of the message
+    pub const NAME: &'static str = "EngineData";
+    /// The size of the payload, in bytes
+    pub const SIZE: usize = 8;
+    /// The layout of the `Rpm` signal
+    pub const RPM: ::socketcan::SignalSpec = ::socketcan::SignalSpec::new(0, 16, ::socketcan::ByteOrder::LittleEndian, ::socketcan::ValueType::Unsigned).scaled(0.25, 0.0);
+    /// The minimum value of the `Rpm` signal
+    pub const RPM_MIN: f64 = 0.0;
+    /// The maximum value of the `Rpm` signal
+    pub const RPM_MAX: f64 = 16383.75;
+    /// The layout of the `Temp` signal
+    pub const TEMP: ::socketcan::SignalSpec = ::socketcan::SignalSpec::new(16, 8, ::socketcan::ByteOrder::LittleEndian, ::socketcan::ValueType::Signed).scaled(1.0, -40.0);
+    /// The minimum value of the `Temp` signal
+    pub const TEMP_MIN: f64 = -40.0;
+    /// The maximum value of the `Temp` signal
+    pub const TEMP_MAX: f64 = 215.0;
+    /// The layout of the `Gear` signal
+    pub const GEAR: ::socketcan::SignalSpec = ::socketcan::SignalSpec::new(24, 4, ::socketcan::ByteOrder::LittleEndian, ::socketcan::ValueType::Unsigned);
+    /// `Gear` value: Neutral
+    pub const GEAR_NEUTRAL: u8 = 0;
+    /// `Gear` value: First
+    pub const GEAR_FIRST: u8 = 1;
+    /// `Gear` value: Second
+    pub const GEAR_SECOND: u8 = 2;
+    /// `Gear` value: Not available
+    pub const GEAR_NOT_AVAILABLE: u8 = 15;
+    /// The layout of the `Torque` signal
+    pub const TORQUE: ::socketcan::SignalSpec = ::socketcan::SignalSpec::new(28, 12, ::socketcan::ByteOrder::LittleEndian, ::socketcan::ValueType::Signed);
+    /// The minimum value of the `Torque` signal
+    pub const TORQUE_MIN: f64 = -1000.0;
+    /// The maximum value of the `Torque` signal
+    pub const TORQUE_MAX: f64 = 1000.0;
+    /// The layout of the `Pressure` signal
+    pub const PRESSURE: ::socketcan::SignalSpec = ::socketcan::SignalSpec::new(47, 16, ::socketcan::ByteOrder::BigEndian, ::socketcan::ValueType::Unsigned).scaled(0.1, 0.0);
+    /// The minimum value of the `Pressure` signal
+    pub const PRESSURE_MIN: f64 = 0.0;
+    /// The maximum value of the `Pressure` signal
+    pub const PRESSURE_MAX: f64 = 6553.5;
+
+    /// Gets the CAN ID of the message.
+    pub fn id() -> ::socketcan::Id {
+        ::socketcan::StandardId::new(0x100).unwrap().into()
+    }
+
+    /// Creates a message from a data payload.
+    ///
+    /// Any bytes past the size of the message are ignored.
+    pub fn from_data(data: &[u8]) -> Result<Self, ::socketcan::dbc::MessageError> {
+        if data.len() < Self::SIZE {
+            return Err(::socketcan::dbc::MessageError::TooShort(data.len()));
+        }
+        let mut msg = Self::default();
+        msg.data.copy_from_slice(&data[..Self::SIZE]);
+        Ok(msg)
+    }
+
+    /// Gets the data payload of the message.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Gets the `Rpm` signal, in rpm.
+    ///
+    /// Engine speed
+    pub fn rpm(&self) -> f64 {
+        Self::RPM.to_physical(Self::RPM.extract(&self.data).unwrap())
+    }
+
+    /// Sets the `Rpm` signal.
+    pub fn set_rpm(&mut self, value: f64) -> Result<(), ::socketcan::dbc::MessageError> {
+        if !(Self::RPM_MIN..=Self::RPM_MAX).contains(&value) {
+            return Err(::socketcan::dbc::MessageError::OutOfRange {
+                signal: "Rpm",
+                value,
+            });
+        }
+        Self::RPM.insert(&mut self.data, Self::RPM.to_raw(value)).unwrap();
+        Ok(())
+    }
+
+    /// Gets the `Temp` signal, in degC.
+    pub fn temp(&self) -> f64 {
+        Self::TEMP.to_physical(Self::TEMP.extract(&self.data).unwrap())
+    }
+
+    /// Sets the `Temp` signal.
+    pub fn set_temp(&mut self, value: f64) -> Result<(), ::socketcan::dbc::MessageError> {
+        if !(Self::TEMP_MIN..=Self::TEMP_MAX).contains(&value) {
+            return Err(::socketcan::dbc::MessageError::OutOfRange {
+                signal: "Temp",
+                value,
+            });
+        }
+        Self::TEMP.insert(&mut self.data, Self::TEMP.to_raw(value)).unwrap();
+        Ok(())
+    }
+
+    /// Gets the `Gear` signal.
+    ///
+    /// Selected gear,
+    /// 0 is neutral
+    pub fn gear(&self) -> u8 {
+        Self::GEAR.extract(&self.data).unwrap() as u8
+    }
+
+    /// Sets the `Gear` signal.
+    pub fn set_gear(&mut self, value: u8) -> Result<(), ::socketcan::dbc::MessageError> {
+        if value > 15 {
+            return Err(::socketcan::dbc::MessageError::OutOfRange {
+                signal: "Gear",
+                value: value as f64,
+            });
+        }
+        Self::GEAR.insert(&mut self.data, value as u64).unwrap();
+        Ok(())
+    }
+
+    /// Gets the `Torque` signal, in Nm.
+    pub fn torque(&self) -> i16 {
+        (((Self::TORQUE.extract(&self.data).unwrap() << 52) as i64) >> 52) as i16
+    }
+
+    /// Sets the `Torque` signal.
+    pub fn set_torque(&mut self, value: i16) -> Result<(), ::socketcan::dbc::MessageError> {
+        if !(-2048..=2047).contains(&value) || !(Self::TORQUE_MIN..=Self::TORQUE_MAX).contains(&(value as f64)) {
+            return Err(::socketcan::dbc::MessageError::OutOfRange {
+                signal: "Torque",
+                value: value as f64,
+            });
+        }
+        Self::TORQUE.insert(&mut self.data, value as i64 as u64).unwrap();
+        Ok(())
+    }
+
+    /// Gets the `Pressure` signal, in kPa.
+    pub fn pressure(&self) -> f64 {
+        Self::PRESSURE.to_physical(Self::PRESSURE.extract(&self.data).unwrap())
+    }
+
+    /// Sets the `Pressure` signal.
+    pub fn set_pressure(&mut self, value: f64) -> Result<(), ::socketcan::dbc::MessageError> {
+        if !(Self::PRESSURE_MIN..=Self::PRESSURE_MAX).contains(&value) {
+            return Err(::socketcan::dbc::MessageError::OutOfRange {
+                signal: "Pressure",
+                value,
+            });
+        }
+        Self::PRESSURE.insert(&mut self.data, Self::PRESSURE.to_raw(value)).unwrap();
+        Ok(())
+    }
+}
+
+impl ::core::convert::TryFrom<&::socketcan::CanFrame> for EngineData {
+    type Error = ::socketcan::dbc::MessageError;
+
+    fn try_from(frame: &::socketcan::CanFrame) -> Result<Self, Self::Error> {
+        let id = ::socketcan::Frame::id_word(frame);
+        if id != Self::ID {
+            return Err(::socketcan::dbc::MessageError::WrongId(id));
+        }
+        Self::from_data(::socketcan::EmbeddedFrame::data(frame))
+    }
+}
+
+impl ::core::convert::TryFrom<::socketcan::CanFrame> for EngineData {
+    type Error = ::socketcan::dbc::MessageError;
+
+    fn try_from(frame: ::socketcan::CanFrame) -> Result<Self, Self::Error> {
+        Self::try_from(&frame)
+    }
+}
+
+impl ::core::convert::TryFrom<&::socketcan::CanFdFrame> for EngineData {
+    type Error = ::socketcan::dbc::MessageError;
+
+    fn try_from(frame: &::socketcan::CanFdFrame) -> Result<Self, Self::Error> {
+        let id = ::socketcan::Frame::id_word(frame);
+        if id != Self::ID {
+            return Err(::socketcan::dbc::MessageError::WrongId(id));
+        }
+        Self::from_data(::socketcan::EmbeddedFrame::data(frame))
+    }
+}
+
+impl ::core::convert::TryFrom<::socketcan::CanFdFrame> for EngineData {
+    type Error = ::socketcan::dbc::MessageError;
+
+    fn try_from(frame: ::socketcan::CanFdFrame) -> Result<Self, Self::Error> {
+        Self::try_from(&frame)
+    }
+}
+
+impl From<EngineData> for ::socketcan::CanFrame {
+    fn from(msg: EngineData) -> Self {
+        ::socketcan::EmbeddedFrame::new(EngineData::id(), &msg.data).unwrap()
+    }
+}
+
+impl From<EngineData> for ::socketcan::CanFdFrame {
+    fn from(msg: EngineData) -> Self {
+        ::socketcan::EmbeddedFrame::new(EngineData::id(), &msg.data).unwrap()
+    }
+}
+
+/// FdStatus, with ID 0x400
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FdStatus {
+    data: [u8; 64],
+}
+
+impl FdStatus {
+    /// The ID word, with the `CAN_EFF_FLAG` bit set for an extended ID
+    pub const ID: u32 = 0x400;
+    /// The name of the message
+    pub const NAME: &'static str = "FdStatus";
+    /// The size of the payload, in bytes
+    pub const SIZE: usize = 64;
+    /// The layout of the `Ratio` signal
+    pub const RATIO: ::socketcan::SignalSpec = ::socketcan::SignalSpec::new(0, 32, ::socketcan::ByteOrder::LittleEndian, ::socketcan::ValueType::Float32);
+    /// The layout of the `Counter` signal
+    pub const COUNTER: ::socketcan::SignalSpec = ::socketcan::SignalSpec::new(32, 64, ::socketcan::ByteOrder::LittleEndian, ::socketcan::ValueType::Unsigned);
+    /// The layout of the `Type` signal
+    pub const TYPE_: ::socketcan::SignalSpec = ::socketcan::SignalSpec::new(103, 8, ::socketcan::ByteOrder::BigEndian, ::socketcan::ValueType::Unsigned);
+
+    /// Gets the CAN ID of the message.
+    pub fn id() -> ::socketcan::Id {
+        ::socketcan::StandardId::new(0x400).unwrap().into()
+    }
+
+    /// Creates a message from a data payload.
+    ///
+    /// Any bytes past the size of the message are ignored.
+    pub fn from_data(data: &[u8]) -> Result<Self, ::socketcan::dbc::MessageError> {
+        if data.len() < Self::SIZE {
+            return Err(::socketcan::dbc::MessageError::TooShort(data.len()));
+        }
+        let mut msg = Self::default();
+        msg.data.copy_from_slice(&data[..Self::SIZE]);
+        Ok(msg)
+    }
+
+    /// Gets the data payload of the message.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Gets the `Ratio` signal.
+    pub fn ratio(&self) -> f32 {
+        f32::from_bits(Self::RATIO.extract(&self.data).unwrap() as u32)
+    }
+
+    /// Sets the `Ratio` signal.
+    pub fn set_ratio(&mut self, value: f32) -> Result<(), ::socketcan::dbc::MessageError> {
+        Self::RATIO.insert(&mut self.data, u64::from(value.to_bits())).unwrap();
+        Ok(())
+    }
+
+    /// Gets the `Counter` signal.
+    pub fn counter(&self) -> u64 {
+        Self::COUNTER.extract(&self.data).unwrap()
+    }
+
+    /// Sets the `Counter` signal.
+    pub fn set_counter(&mut self, value: u64) -> Result<(), ::socketcan::dbc::MessageError> {
+        Self::COUNTER.insert(&mut self.data, value).unwrap();
+        Ok(())
+    }
+
+    /// Gets the `Type` signal.
+    pub fn type_(&self) -> u8 {
+        Self::TYPE_.extract(&self.data).unwrap() as u8
+    }
+
+    /// Sets the `Type` signal.
+    pub fn set_type_(&mut self, value: u8) -> Result<(), ::socketcan::dbc::MessageError> {
+        Self::TYPE_.insert(&mut self.data, value as u64).unwrap();
+        Ok(())
+    }
+}
+
+impl Default for FdStatus {
+    fn default() -> Self {
+        Self { data: [0; 64] }
+    }
+}
+
+impl ::core::convert::TryFrom<&::socketcan::CanFrame> for FdStatus {
+    type Error = ::socketcan::dbc::MessageError;
+
+    fn try_from(frame: &::socketcan::CanFrame) -> Result<Self, Self::Error> {
+        let id = ::socketcan::Frame::id_word(frame);
+        if id != Self::ID {
+            return Err(::socketcan::dbc::MessageError::WrongId(id));
+        }
+        Self::from_data(::socketcan::EmbeddedFrame::data(frame))
+    }
+}
+
+impl ::core::convert::TryFrom<::socketcan::CanFrame> for FdStatus {
+    type Error = ::socketcan::dbc::MessageError;
+
+    fn try_from(frame: ::socketcan::CanFrame) -> Result<Self, Self::Error> {
+        Self::try_from(&frame)
+    }
+}
+
+impl ::core::convert::TryFrom<&::socketcan::CanFdFrame> for FdStatus {
+    type Error = ::socketcan::dbc::MessageError;
+
+    fn try_from(frame: &::socketcan::CanFdFrame) -> Result<Self, Self::Error> {
+        let id = ::socketcan::Frame::id_word(frame);
+        if id != Self::ID {
+            return Err(::socketcan::dbc::MessageError::WrongId(id));
+        }
+        Self::from_data(::socketcan::EmbeddedFrame::data(frame))
+    }
+}
+
+impl ::core::convert::TryFrom<::socketcan::CanFdFrame> for FdStatus {
+    type Error = ::socketcan::dbc::MessageError;
+
+    fn try_from(frame: ::socketcan::CanFdFrame) -> Result<Self, Self::Error> {
+        Self::try_from(&frame)
+    }
+}
+
+impl From<FdStatus> for ::socketcan::CanFdFrame {
+    fn from(msg: FdStatus) -> Self {
+        ::socketcan::EmbeddedFrame::new(FdStatus::id(), &msg.data).unwrap()
+    }
+}
+
+/// Diag, with ID 0x18FEF1FE
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Diag {
+    data: [u8; 8],
+}
+
+impl Diag {
+    /// The ID word, with the `CAN_EFF_FLAG` bit set for an extended ID
+    pub const ID: u32 = 0x98FEF1FE;
+    /// The name of the message
+    pub const NAME: &'static str = "Diag";
+    /// The size of the payload, in bytes
+    pub const SIZE: usize = 8;
+    /// The layout of the `Mode` signal
+    pub const MODE: ::socketcan::SignalSpec = ::socketcan::SignalSpec::new(0, 8, ::socketcan::ByteOrder::LittleEndian, ::socketcan::ValueType::Unsigned);
+    /// The minimum value of the `Mode` signal
+    pub const MODE_MIN: f64 = 0.0;
+    /// The maximum value of the `Mode` signal
+    pub const MODE_MAX: f64 = 255.0;
+    /// `Mode` value: Voltage
+    pub const MODE_VOLTAGE: u8 = 1;
+    /// `Mode` value: Current
+    pub const MODE_CURRENT: u8 = 2;
+    /// `Mode` value: Sub
+    pub const MODE_SUB: u8 = 3;
+    /// The layout of the `Voltage` signal
+    pub const VOLTAGE: ::socketcan::SignalSpec = ::socketcan::SignalSpec::new(8, 16, ::socketcan::ByteOrder::LittleEndian, ::socketcan::ValueType::Unsigned).scaled(0.001, 0.0);
+    /// The minimum value of the `Voltage` signal
+    pub const VOLTAGE_MIN: f64 = 0.0;
+    /// The maximum value of the `Voltage` signal
+    pub const VOLTAGE_MAX: f64 = 65.535;
+    /// The layout of the `Current` signal
+    pub const CURRENT: ::socketcan::SignalSpec = ::socketcan::SignalSpec::new(8, 16, ::socketcan::ByteOrder::LittleEndian, ::socketcan::ValueType::Signed).scaled(0.01, 0.0);
+    /// The minimum value of the `Current` signal
+    pub const CURRENT_MIN: f64 = -327.68;
+    /// The maximum value of the `Current` signal
+    pub const CURRENT_MAX: f64 = 327.67;
+    /// The layout of the `SubMode` signal
+    pub const SUB_MODE: ::socketcan::SignalSpec = ::socketcan::SignalSpec::new(8, 8, ::socketcan::ByteOrder::LittleEndian, ::socketcan::ValueType::Unsigned);
+    /// The minimum value of the `SubMode` signal
+    pub const SUB_MODE_MIN: f64 = 0.0;
+    /// The maximum value of the `SubMode` signal
+    pub const SUB_MODE_MAX: f64 = 255.0;
+    /// The layout of the `Deep` signal
+    pub const DEEP: ::socketcan::SignalSpec = ::socketcan::SignalSpec::new(16, 8, ::socketcan::ByteOrder::LittleEndian, ::socketcan::ValueType::Unsigned);
+    /// The minimum value of the `Deep` signal
+    pub const DEEP_MIN: f64 = 0.0;
+    /// The maximum value of the `Deep` signal
+    pub const DEEP_MAX: f64 = 255.0;
+
+    /// Gets the CAN ID of the message.
+    pub fn id() -> ::socketcan::Id {
+        ::socketcan::ExtendedId::new(0x18FEF1FE).unwrap().into()
+    }
+
+    /// Creates a message from a data payload.
+    ///
+    /// Any bytes past the size of the message are ignored.
+    pub fn from_data(data: &[u8]) -> Result<Self, ::socketcan::dbc::MessageError> {
+        if data.len() < Self::SIZE {
+            return Err(::socketcan::dbc::MessageError::TooShort(data.len()));
+        }
+        let mut msg = Self::default();
+        msg.data.copy_from_slice(&data[..Self::SIZE]);
+        Ok(msg)
+    }
+
+    /// Gets the data payload of the message.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Gets the `Mode` signal.
+    pub fn mode(&self) -> u8 {
+        Self::MODE.extract(&self.data).unwrap() as u8
+    }
+
+    /// Sets the `Mode` signal.
+    pub fn set_mode(&mut self, value: u8) -> Result<(), ::socketcan::dbc::MessageError> {
+        if !(Self::MODE_MIN..=Self::MODE_MAX).contains(&(value as f64)) {
+            return Err(::socketcan::dbc::MessageError::OutOfRange {
+                signal: "Mode",
+                value: value as f64,
+            });
+        }
+        Self::MODE.insert(&mut self.data, value as u64).unwrap();
+        Ok(())
+    }
+
+    /// Gets the `Voltage` signal, in V.
+    ///
+    /// This is `None` if the signal is not present in the message.
+    pub fn voltage(&self) -> Option<f64> {
+        if Self::MODE.extract(&self.data).unwrap() == 1 {
+            Some(Self::VOLTAGE.to_physical(Self::VOLTAGE.extract(&self.data).unwrap()))
+        } else {
+            None
+        }
+    }
+
+    /// Sets the `Voltage` signal.
+    ///
+    /// This also sets the multiplexor, `MODE`, to 1.
+    pub fn set_voltage(&mut self, value: f64) -> Result<(), ::socketcan::dbc::MessageError> {
+        if !(Self::VOLTAGE_MIN..=Self::VOLTAGE_MAX).contains(&value) {
+            return Err(::socketcan::dbc::MessageError::OutOfRange {
+                signal: "Voltage",
+                value,
+            });
+        }
+        Self::MODE.insert(&mut self.data, 1).unwrap();
+        Self::VOLTAGE.insert(&mut self.data, Self::VOLTAGE.to_raw(value)).unwrap();
+        Ok(())
+    }
+
+    /// Gets the `Current` signal, in A.
+    ///
+    /// This is `None` if the signal is not present in the message.
+    pub fn current(&self) -> Option<f64> {
+        if Self::MODE.extract(&self.data).unwrap() == 2 {
+            Some(Self::CURRENT.to_physical(Self::CURRENT.extract(&self.data).unwrap()))
+        } else {
+            None
+        }
+    }
+
+    /// Sets the `Current` signal.
+    ///
+    /// This also sets the multiplexor, `MODE`, to 2.
+    pub fn set_current(&mut self, value: f64) -> Result<(), ::socketcan::dbc::MessageError> {
+        if !(Self::CURRENT_MIN..=Self::CURRENT_MAX).contains(&value) {
+            return Err(::socketcan::dbc::MessageError::OutOfRange {
+                signal: "Current",
+                value,
+            });
+        }
+        Self::MODE.insert(&mut self.data, 2).unwrap();
+        Self::CURRENT.insert(&mut self.data, Self::CURRENT.to_raw(value)).unwrap();
+        Ok(())
+    }
+
+    /// Gets the `SubMode` signal.
+    ///
+    /// This is `None` if the signal is not present in the message.
+    pub fn sub_mode(&self) -> Option<u8> {
+        if Self::MODE.extract(&self.data).unwrap() == 3 {
+            Some(Self::SUB_MODE.extract(&self.data).unwrap() as u8)
+        } else {
+            None
+        }
+    }
+
+    /// Sets the `SubMode` signal.
+    ///
+    /// This also sets the multiplexor, `MODE`, to 3.
+    pub fn set_sub_mode(&mut self, value: u8) -> Result<(), ::socketcan::dbc::MessageError> {
+        if !(Self::SUB_MODE_MIN..=Self::SUB_MODE_MAX).contains(&(value as f64)) {
+            return Err(::socketcan::dbc::MessageError::OutOfRange {
+                signal: "SubMode",
+                value: value as f64,
+            });
+        }
+        Self::MODE.insert(&mut self.data, 3).unwrap();
+        Self::SUB_MODE.insert(&mut self.data, value as u64).unwrap();
+        Ok(())
+    }
+
+    /// Gets the `Deep` signal.
+    ///
+    /// This is `None` if the signal is not present in the message.
+    pub fn deep(&self) -> Option<u8> {
+        if Self::MODE.extract(&self.data).unwrap() == 3 && matches!(Self::SUB_MODE.extract(&self.data).unwrap(), 0 | 2..=4) {
+            Some(Self::DEEP.extract(&self.data).unwrap() as u8)
+        } else {
+            None
+        }
+    }
+
+    /// Sets the `Deep` signal.
+    pub fn set_deep(&mut self, value: u8) -> Result<(), ::socketcan::dbc::MessageError> {
+        if !(Self::DEEP_MIN..=Self::DEEP_MAX).contains(&(value as f64)) {
+            return Err(::socketcan::dbc::MessageError::OutOfRange {
+                signal: "Deep",
+                value: value as f64,
+            });
+        }
+        Self::DEEP.insert(&mut self.data, value as u64).unwrap();
+        Ok(())
+    }
+}
+
+impl ::core::convert::TryFrom<&::socketcan::CanFrame> for Diag {
+    type Error = ::socketcan::dbc::MessageError;
+
+    fn try_from(frame: &::socketcan::CanFrame) -> Result<Self, Self::Error> {
+        let id = ::socketcan::Frame::id_word(frame);
+        if id != Self::ID {
+            return Err(::socketcan::dbc::MessageError::WrongId(id));
+        }
+        Self::from_data(::socketcan::EmbeddedFrame::data(frame))
+    }
+}
+
+impl ::core::convert::TryFrom<::socketcan::CanFrame> for Diag {
+    type Error = ::socketcan::dbc::MessageError;
+
+    fn try_from(frame: ::socketcan::CanFrame) -> Result<Self, Self::Error> {
+        Self::try_from(&frame)
+    }
+}
+
+impl ::core::convert::TryFrom<&::socketcan::CanFdFrame> for Diag {
+    type Error = ::socketcan::dbc::MessageError;
+
+    fn try_from(frame: &::socketcan::CanFdFrame) -> Result<Self, Self::Error> {
+        let id = ::socketcan::Frame::id_word(frame);
+        if id != Self::ID {
+            return Err(::socketcan::dbc::MessageError::WrongId(id));
+        }
+        Self::from_data(::socketcan::EmbeddedFrame::data(frame))
+    }
+}
+
+impl ::core::convert::TryFrom<::socketcan::CanFdFrame> for Diag {
+    type Error = ::socketcan::dbc::MessageError;
+
+    fn try_from(frame: ::socketcan::CanFdFrame) -> Result<Self, Self::Error> {
+        Self::try_from(&frame)
+    }
+}
+
+impl From<Diag> for ::socketcan::CanFrame {
+    fn from(msg: Diag) -> Self {
+        ::socketcan::EmbeddedFrame::new(Diag::id(), &msg.data).unwrap()
+    }
+}
+
+impl From<Diag> for ::socketcan::CanFdFrame {
+    fn from(msg: Diag) -> Self {
+        ::socketcan::EmbeddedFrame::new(Diag::id(), &msg.data).unwrap()
+    }
+}
+
@@ -0,0 +1,142 @@
+// socketcan/tests/dbc_codegen.rs
+//
+// Integration tests for the code generated from DBC files.
+//
+// This file is part of the Rust 'socketcan-rs' library.
+//
+// Licensed under the MIT license:
+//   <LICENSE or http://opensource.org/licenses/MIT>
+// This file may not be copied, modified, or distributed except according
+// to those terms.
+
+// The generated code is checked in, so that it's compiled and tested here.
+// Regenerate it after changing the generator with:
+//
+//   $ SOCKETCAN_BLESS=1 cargo test --test dbc_codegen
+#[cfg(feature = "dbc")]
+mod vehicle {
+    include!("dbc/vehicle.rs");
+}
+
+#[cfg(feature = "dbc")]
+use socketcan::{
+    dbc::{Codegen, MessageError},
+    CanFdFrame, CanFrame, EmbeddedFrame, ExtendedId, Frame, StandardId,
+};
+
+#[cfg(feature = "dbc")]
+use std::{env, fs};
+
+#[cfg(feature = "dbc")]
+use vehicle::{Diag, EngineData, FdStatus};
+
+#[cfg(feature = "dbc")]
+#[test]
+fn test_generated_is_current() {
+    let out = env::temp_dir().join(format!("socketcan-vehicle-{}.rs", std::process::id()));
+    Codegen::new()
+        .generate_file("tests/dbc/vehicle.dbc", &out)
+        .unwrap();
+    let generated = fs::read_to_string(&out).unwrap();
+    let _ = fs::remove_file(&out);
+
+    if env::var_os("SOCKETCAN_BLESS").is_some() {
+        fs::write("tests/dbc/vehicle.rs", &generated).unwrap();
+    }
+    let current = fs::read_to_string("tests/dbc/vehicle.rs").unwrap();
+    assert!(
+        generated == current,
+        "tests/dbc/vehicle.rs is out of date; regenerate it with SOCKETCAN_BLESS=1"
+    );
+}
+
+#[cfg(feature = "dbc")]
+#[test]
+fn test_signals() {
+    let mut msg = EngineData::default();
+    msg.set_rpm(2500.0).unwrap();
+    msg.set_temp(-20.0).unwrap();
+    msg.set_gear(EngineData::GEAR_SECOND).unwrap();
+    msg.set_torque(-300).unwrap();
+    msg.set_pressure(101.3).unwrap();
+
+    assert_eq!(
+        msg.data(),
+        &[0x10, 0x27, 0x14, 0x42, 0xED, 0x03, 0xF5, 0x00]
+    );
+    assert_eq!(msg.rpm(), 2500.0);
+    assert_eq!(msg.temp(), -20.0);
+    assert_eq!(msg.gear(), 2);
+    assert_eq!(msg.torque(), -300);
+    assert!((msg.pressure() - 101.3).abs() < 1e-9);
+
+    assert_eq!(
+        msg.set_rpm(-1.0),
+        Err(MessageError::OutOfRange {
+            signal: "Rpm",
+            value: -1.0
+        })
+    );
+    assert!(msg.set_gear(16).is_err());
+    assert!(msg.set_torque(1001).is_err());
+    assert_eq!(msg.rpm(), 2500.0);
+}
+
+#[cfg(feature = "dbc")]
+#[test]
+fn test_multiplex() {
+    let mut msg = Diag::default();
+    msg.set_voltage(1.0).unwrap();
+    assert_eq!(msg.mode(), Diag::MODE_VOLTAGE);
+    assert_eq!(msg.voltage(), Some(1.0));
+    assert_eq!(msg.current(), None);
+    assert_eq!(msg.deep(), None);
+
+    msg.set_sub_mode(2).unwrap();
+    msg.set_deep(42).unwrap();
+    assert_eq!(msg.mode(), 3);
+    assert_eq!(msg.voltage(), None);
+    assert_eq!(msg.deep(), Some(42));
+
+    msg.set_sub_mode(1).unwrap();
+    assert_eq!(msg.deep(), None);
+}
+
+#[cfg(feature = "dbc")]
+#[test]
+fn test_frames() {
+    let mut msg = EngineData::default();
+    msg.set_rpm(1000.0).unwrap();
+
+    let frame: CanFrame = msg.into();
+    assert_eq!(frame.raw_id(), 0x100);
+    assert_eq!(EngineData::try_from(&frame), Ok(msg));
+
+    let frame = CanFrame::new(StandardId::new(0x101).unwrap(), &[0; 8]).unwrap();
+    assert_eq!(
+        EngineData::try_from(frame),
+        Err(MessageError::WrongId(0x101))
+    );
+
+    let frame = CanFrame::new(StandardId::new(0x100).unwrap(), &[0; 4]).unwrap();
+    assert_eq!(EngineData::try_from(frame), Err(MessageError::TooShort(4)));
+
+    let frame = CanFrame::new_remote(StandardId::new(0x100).unwrap(), 8).unwrap();
+    assert!(EngineData::try_from(frame).is_err());
+
+    let frame: CanFrame = Diag::default().into();
+    assert_eq!(frame.id(), ExtendedId::new(0x18FEF1FE).unwrap().into());
+
+    let mut msg = FdStatus::default();
+    msg.set_ratio(-0.5).unwrap();
+    msg.set_counter(u64::MAX).unwrap();
+    msg.set_type_(0xAB).unwrap();
+
+    let frame: CanFdFrame = msg.into();
+    assert_eq!(frame.len(), 64);
+    assert_eq!(frame.data()[12], 0xAB);
+
+    let msg = FdStatus::try_from(frame).unwrap();
+    assert_eq!(msg.ratio(), -0.5);
+    assert_eq!(msg.counter(), u64::MAX);
+}